@@ -0,0 +1,205 @@
+//! Compares the conversion of tables into arrow record batches to the previous implementation,
+//! which transposed the rows into columns of JSON values before deserializing each column.
+
+use std::str::FromStr;
+use std::sync::Arc;
+
+use arrow::array::{
+    ArrayRef, BooleanArray, DurationNanosecondArray, Float64Array, Int32Array, Int64Array,
+    RecordBatch, StringArray, TimestampNanosecondArray,
+};
+use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+use azure_kusto_data::models::{Column, ColumnType, DataTable, TableKind, V2QueryResult};
+use azure_kusto_data::prelude::{KustoDateTime, KustoResponseDataSetV2, KustoTimespan};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use serde_json::{json, Value};
+
+const ROWS: usize = 100_000;
+
+fn synthetic_table() -> DataTable {
+    let column = |name: &str, column_type| Column {
+        column_name: name.to_string(),
+        column_type,
+    };
+    let rows = (0..ROWS)
+        .map(|i| {
+            json!([
+                format!("name {i}"),
+                "2021-12-22T11:43:00.1234567Z",
+                "1.01:01:01.0",
+                i % 2 == 0,
+                i,
+                i * 1000,
+                if i % 100 == 0 { json!("NaN") } else { json!(i as f64 / 3.0) },
+                "2.00000000000001",
+                {"id": i, "tags": ["a", "b"]},
+            ])
+        })
+        .collect();
+
+    DataTable {
+        table_id: 1,
+        table_name: "synthetic".to_string(),
+        table_kind: TableKind::PrimaryResult,
+        columns: vec![
+            column("name", ColumnType::String),
+            column("time", ColumnType::Datetime),
+            column("offset", ColumnType::Timespan),
+            column("flag", ColumnType::Bool),
+            column("int", ColumnType::Int),
+            column("long", ColumnType::Long),
+            column("real", ColumnType::Real),
+            column("decimal", ColumnType::Decimal),
+            column("dynamic", ColumnType::Dynamic),
+        ],
+        rows,
+    }
+}
+
+/// The previous implementation, which clones every cell into per-column vectors before converting them.
+mod transposed {
+    use super::*;
+
+    fn strings(values: Vec<Value>) -> ArrayRef {
+        let strings: Vec<Option<String>> = serde_json::from_value(Value::Array(values)).unwrap();
+        Arc::new(StringArray::from(strings))
+    }
+
+    fn datetimes(values: Vec<Value>) -> ArrayRef {
+        let dates: Vec<Option<String>> = serde_json::from_value(Value::Array(values)).unwrap();
+        let timestamps: Vec<Option<i64>> = dates
+            .into_iter()
+            .map(|d| {
+                d.and_then(|d| KustoDateTime::from_str(&d).ok())
+                    .and_then(|d| d.unix_timestamp_nanos().try_into().ok())
+            })
+            .collect();
+        Arc::new(TimestampNanosecondArray::from(timestamps))
+    }
+
+    fn timespans(values: Vec<Value>) -> ArrayRef {
+        let spans: Vec<Option<String>> = serde_json::from_value(Value::Array(values)).unwrap();
+        let durations: Vec<Option<i64>> = spans
+            .into_iter()
+            .map(|s| {
+                s.and_then(|s| KustoTimespan::from_str(&s).ok())
+                    .and_then(|d| d.whole_nanoseconds().try_into().ok())
+            })
+            .collect();
+        Arc::new(DurationNanosecondArray::from(durations))
+    }
+
+    fn reals(values: Vec<Value>) -> ArrayRef {
+        let reals: Vec<Option<f64>> = values
+            .into_iter()
+            .map(|value| match value {
+                Value::String(val) if val == "NaN" => None,
+                Value::String(val) if val == "Infinity" => Some(f64::INFINITY),
+                Value::String(val) if val == "-Infinity" => Some(-f64::INFINITY),
+                _ => serde_json::from_value(value).unwrap(),
+            })
+            .collect();
+        Arc::new(Float64Array::from(reals))
+    }
+
+    fn column(values: Vec<Value>, column_type: &ColumnType) -> (DataType, ArrayRef) {
+        match column_type {
+            ColumnType::String | ColumnType::Guid => (DataType::Utf8, strings(values)),
+            ColumnType::Decimal => {
+                let decimals: Vec<Option<String>> = values
+                    .into_iter()
+                    .map(|v| match v {
+                        Value::Null => None,
+                        Value::String(s) => Some(s),
+                        other => Some(other.to_string()),
+                    })
+                    .collect();
+                (DataType::Utf8, Arc::new(StringArray::from(decimals)))
+            }
+            ColumnType::Dynamic => {
+                let json: Vec<Option<String>> = values
+                    .into_iter()
+                    .map(|v| (!v.is_null()).then(|| v.to_string()))
+                    .collect();
+                (DataType::Utf8, Arc::new(StringArray::from(json)))
+            }
+            ColumnType::Bool => {
+                let bools: Vec<Option<bool>> =
+                    serde_json::from_value(Value::Array(values)).unwrap();
+                (DataType::Boolean, Arc::new(BooleanArray::from(bools)))
+            }
+            ColumnType::Int => {
+                let ints: Vec<Option<i32>> = serde_json::from_value(Value::Array(values)).unwrap();
+                (DataType::Int32, Arc::new(Int32Array::from(ints)))
+            }
+            ColumnType::Long => {
+                let ints: Vec<Option<i64>> = serde_json::from_value(Value::Array(values)).unwrap();
+                (DataType::Int64, Arc::new(Int64Array::from(ints)))
+            }
+            ColumnType::Real => (DataType::Float64, reals(values)),
+            ColumnType::Datetime => (
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                datetimes(values),
+            ),
+            ColumnType::Timespan => (DataType::Duration(TimeUnit::Nanosecond), timespans(values)),
+        }
+    }
+
+    pub fn convert_table(table: DataTable) -> RecordBatch {
+        let mut buffer: Vec<Vec<Value>> = (0..table.columns.len())
+            .map(|_| Vec::with_capacity(table.rows.len()))
+            .collect();
+        for row in table.rows {
+            if let Value::Array(values) = row {
+                for (i, value) in values.into_iter().enumerate() {
+                    buffer[i].push(value);
+                }
+            }
+        }
+
+        let (fields, columns): (Vec<Field>, Vec<ArrayRef>) = buffer
+            .into_iter()
+            .zip(table.columns.iter())
+            .map(|(data, c)| {
+                let (data_type, array) = column(data, &c.column_type);
+                (Field::new(&c.column_name, data_type, true), array)
+            })
+            .unzip();
+        RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).unwrap()
+    }
+}
+
+fn single_pass(table: DataTable) -> RecordBatch {
+    KustoResponseDataSetV2 {
+        results: vec![V2QueryResult::DataTable(table)],
+    }
+    .into_record_batches()
+    .next()
+    .unwrap()
+    .unwrap()
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let table = synthetic_table();
+    assert_eq!(
+        single_pass(table.clone()),
+        transposed::convert_table(table.clone())
+    );
+
+    let mut group = c.benchmark_group("convert table of 100k rows");
+    group.sample_size(10);
+    group.bench_function("single pass", |b| {
+        b.iter_batched(|| table.clone(), single_pass, BatchSize::LargeInput)
+    });
+    group.bench_function("transposed", |b| {
+        b.iter_batched(
+            || table.clone(),
+            transposed::convert_table,
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
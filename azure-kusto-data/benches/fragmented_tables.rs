@@ -0,0 +1,66 @@
+//! Assembles a progressive table whose rows are spread over many small fragments,
+//! from a borrowed response, which clones the rows, and from a consumed one, which moves them.
+
+use azure_kusto_data::models::{
+    Column, ColumnType, TableCompletion, TableFragment, TableFragmentType, TableHeader, TableKind,
+    V2QueryResult,
+};
+use azure_kusto_data::prelude::KustoResponseDataSetV2;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use serde_json::json;
+
+const FRAGMENTS: usize = 10_000;
+const ROWS_PER_FRAGMENT: usize = 5;
+
+fn fragmented_response() -> KustoResponseDataSetV2 {
+    let mut results = vec![V2QueryResult::TableHeader(TableHeader {
+        table_id: 1,
+        table_name: "PrimaryResult".to_string(),
+        table_kind: TableKind::PrimaryResult,
+        columns: vec![
+            Column {
+                column_name: "name".to_string(),
+                column_type: ColumnType::String,
+            },
+            Column {
+                column_name: "value".to_string(),
+                column_type: ColumnType::Long,
+            },
+        ],
+    })];
+    results.extend((0..FRAGMENTS).map(|i| {
+        V2QueryResult::TableFragment(TableFragment {
+            table_id: 1,
+            field_count: Some(2),
+            table_fragment_type: TableFragmentType::DataAppend,
+            rows: (0..ROWS_PER_FRAGMENT)
+                .map(|j| json!([format!("row {i}.{j}"), i * j]))
+                .collect(),
+        })
+    }));
+    results.push(V2QueryResult::TableCompletion(TableCompletion::new(
+        1,
+        (FRAGMENTS * ROWS_PER_FRAGMENT) as i32,
+    )));
+    KustoResponseDataSetV2 { results }
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let response = fragmented_response();
+
+    let mut group = c.benchmark_group("assemble 10k fragments");
+    group.bench_function("borrowed", |b| {
+        b.iter(|| response.parsed_data_tables().next().unwrap().unwrap())
+    });
+    group.bench_function("consumed", |b| {
+        b.iter_batched(
+            || response.clone(),
+            |response| response.into_parsed_data_tables().next().unwrap().unwrap(),
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,102 @@
+//! Compares the deserialization of the rows of a table into structs by column name, from borrowed and consumed rows,
+//! to the previous implementation, which cloned the rows and built a map of the column names to the values of each row.
+
+use azure_kusto_data::models::{Column, ColumnType, DataTable, TableKind};
+use azure_kusto_data::prelude::CaseMapping;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+const ROWS: usize = 100_000;
+
+#[derive(Deserialize)]
+#[allow(dead_code)]
+struct Record {
+    name: String,
+    count: i64,
+    ratio: f64,
+    flag: bool,
+    tags: Value,
+}
+
+fn synthetic_table() -> DataTable {
+    let column = |name: &str, column_type| Column {
+        column_name: name.to_string(),
+        column_type,
+    };
+    DataTable {
+        table_id: 1,
+        table_name: "PrimaryResult".to_string(),
+        table_kind: TableKind::PrimaryResult,
+        columns: vec![
+            column("Name", ColumnType::String),
+            column("Count", ColumnType::Long),
+            column("Ratio", ColumnType::Real),
+            column("Flag", ColumnType::Bool),
+            column("Tags", ColumnType::Dynamic),
+        ],
+        rows: (0..ROWS)
+            .map(|i| {
+                json!([
+                    format!("name {i}"),
+                    i,
+                    i as f64 / 7.0,
+                    i % 3 == 0,
+                    ["a", "b"]
+                ])
+            })
+            .collect(),
+    }
+}
+
+/// The previous implementation, with the column names already mapped to the field names.
+fn cloned<T: DeserializeOwned>(table: &DataTable) -> Vec<T> {
+    let keys: Vec<String> = table
+        .columns
+        .iter()
+        .map(|c| c.column_name.to_lowercase())
+        .collect();
+    table
+        .rows
+        .clone()
+        .into_iter()
+        .map(|row| {
+            let Value::Array(values) = row else {
+                panic!("Expected an array")
+            };
+            let record: Map<String, Value> = keys.iter().cloned().zip(values).collect();
+            serde_json::from_value(Value::Object(record)).unwrap()
+        })
+        .collect()
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let table = synthetic_table();
+
+    let mut group = c.benchmark_group("deserialize 100k rows by column name");
+    group.sample_size(10);
+    group.bench_function("cloned rows", |b| b.iter(|| cloned::<Record>(&table)));
+    group.bench_function("borrowed rows", |b| {
+        b.iter(|| {
+            table
+                .to_structs::<Record>(CaseMapping::Insensitive)
+                .unwrap()
+        })
+    });
+    group.bench_function("consumed rows", |b| {
+        b.iter_batched(
+            || table.clone(),
+            |table| {
+                table
+                    .into_structs::<Record>(CaseMapping::Insensitive)
+                    .unwrap()
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,69 @@
+//! Compares the deserialization of a result into structs row by row, from the JSON values of the rows,
+//! to its deserialization column by column, from an arrow record batch.
+
+use azure_kusto_data::models::{Column, ColumnType, DataTable, TableKind, V2QueryResult};
+use azure_kusto_data::prelude::KustoResponseDataSetV2;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+const ROWS: usize = 100_000;
+
+#[derive(Deserialize)]
+#[allow(dead_code)]
+struct Record {
+    name: String,
+    count: i64,
+    ratio: f64,
+    flag: bool,
+}
+
+fn synthetic_response() -> KustoResponseDataSetV2 {
+    let column = |name: &str, column_type| Column {
+        column_name: name.to_string(),
+        column_type,
+    };
+    KustoResponseDataSetV2 {
+        results: vec![V2QueryResult::DataTable(DataTable {
+            table_id: 1,
+            table_name: "PrimaryResult".to_string(),
+            table_kind: TableKind::PrimaryResult,
+            columns: vec![
+                column("name", ColumnType::String),
+                column("count", ColumnType::Long),
+                column("ratio", ColumnType::Real),
+                column("flag", ColumnType::Bool),
+            ],
+            rows: (0..ROWS)
+                .map(|i| json!([format!("name {i}"), i, i as f64 / 7.0, i % 3 == 0]))
+                .collect(),
+        })],
+    }
+}
+
+/// The path of `execute_query_to_struct`, which deserializes the rows of the single primary result.
+fn row_wise(response: KustoResponseDataSetV2) -> Vec<Record> {
+    let table = response.into_primary_results().next().unwrap().unwrap();
+    serde_json::from_value(Value::Array(table.rows)).unwrap()
+}
+
+fn columnar(response: KustoResponseDataSetV2) -> Vec<Record> {
+    response.into_structs_via_arrow().unwrap()
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let response = synthetic_response();
+
+    let mut group = c.benchmark_group("deserialize 100k rows into structs");
+    group.sample_size(10);
+    group.bench_function("row wise", |b| {
+        b.iter_batched(|| response.clone(), row_wise, BatchSize::LargeInput)
+    });
+    group.bench_function("columnar", |b| {
+        b.iter_batched(|| response.clone(), columnar, BatchSize::LargeInput)
+    });
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -15,19 +15,8 @@ async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
         .nth(3)
         .expect("please specify query as third command line parameter");
 
-    let client_id =
-        std::env::var("AZURE_CLIENT_ID").expect("Set env variable AZURE_CLIENT_ID first!");
-    let client_secret =
-        std::env::var("AZURE_CLIENT_SECRET").expect("Set env variable AZURE_CLIENT_SECRET first!");
-    let authority_id =
-        std::env::var("AZURE_TENANT_ID").expect("Set env variable AZURE_TENANT_ID first!");
-
-    let kcsb = ConnectionString::with_application_auth(
-        service_url,
-        client_id,
-        client_secret,
-        authority_id,
-    );
+    // Authenticates with AZURE_CLIENT_ID, AZURE_CLIENT_SECRET and AZURE_TENANT_ID when they are set
+    let kcsb = ConnectionString::from_env(service_url);
 
     let client = KustoClient::try_from(kcsb).expect("Failed to create Kusto client");
 
@@ -1,6 +1,4 @@
-use azure_kusto_data::models::V2QueryResult;
 use azure_kusto_data::prelude::*;
-use azure_kusto_data::types::{KustoDateTime, KustoDuration};
 use clap::Parser;
 use futures::{pin_mut, TryStreamExt};
 use serde::{Deserialize, Serialize};
@@ -50,6 +48,8 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     progressive(&args, &client).await?;
 
+    streaming(&args, &client).await?;
+
     to_struct(&args, &client).await?;
 
     Ok(())
@@ -95,6 +95,27 @@ async fn progressive(args: &Args, client: &KustoClient) -> Result<(), Box<dyn Er
     Ok(())
 }
 
+async fn streaming(args: &Args, client: &KustoClient) -> Result<(), Box<dyn Error>> {
+    println!("Querying {} with streaming dataset", args.query);
+
+    let mut dataset = client
+        .execute_streaming_query(args.database.clone(), args.query.clone(), None)
+        .await?;
+
+    while let Some(table) = dataset.try_next().await? {
+        println!(
+            "primary result {} with {} rows",
+            table.table_id,
+            table.rows.len()
+        );
+    }
+
+    println!("header: {:#?}", dataset.header().await?);
+    println!("completion: {:#?}", dataset.completion().await?);
+
+    Ok(())
+}
+
 async fn non_progressive(args: &Args, client: &KustoClient) {
     println!("Querying {} with regular client", args.query);
 
@@ -131,7 +152,7 @@ async fn non_progressive(args: &Args, client: &KustoClient) {
     }
 
     // Print the primary tables
-    let primary_results = response.into_primary_results().collect::<Vec<_>>();
+    let primary_results = response.into_primary_results_lossy().collect::<Vec<_>>();
     println!("primary results: {:#?}", primary_results);
 }
 
@@ -140,7 +161,7 @@ struct Item {
     vnum: i32,
     vdec: String, // optionally, you can use a decimal type here
     vdate: KustoDateTime,
-    vspan: KustoDuration,
+    vspan: KustoTimespan,
     vobj: Value,
     vb: bool,
     vreal: f64,
@@ -167,10 +188,7 @@ async fn to_struct(args: &Args, client: &KustoClient) -> Result<(), Box<dyn Erro
         .execute_query(args.database.clone(), query, None)
         .await?;
 
-    let results = response
-        .into_primary_results()
-        .next()
-        .ok_or_else(|| "Expected to get a primary result, but got none".to_string())?;
+    let results = response.into_single_primary_result()?;
 
     let rows = results.rows;
 
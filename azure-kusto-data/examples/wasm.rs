@@ -0,0 +1,42 @@
+//! Queries Kusto from WebAssembly, with a token acquired by the host (e.g. a browser or an edge function).
+//! Build with `cargo build --example wasm --target wasm32-unknown-unknown --no-default-features --features wasm`,
+//! and call the exported `query` function from JavaScript through wasm-bindgen.
+
+#[cfg(target_arch = "wasm32")]
+mod wasm {
+    use azure_kusto_data::prelude::*;
+    use wasm_bindgen::prelude::*;
+
+    fn to_js_error(e: impl std::fmt::Display) -> JsValue {
+        JsValue::from_str(&e.to_string())
+    }
+
+    /// Runs a query, and returns the rows of its primary results as a JSON array.
+    #[wasm_bindgen]
+    pub async fn query(
+        cluster: String,
+        token: String,
+        database: String,
+        query: String,
+    ) -> Result<String, JsValue> {
+        let client = KustoClient::new(
+            ConnectionString::with_token_auth(cluster, token),
+            KustoClientOptions::default(),
+        )
+        .map_err(to_js_error)?;
+
+        let response = client
+            .execute_query(database, query, None)
+            .await
+            .map_err(to_js_error)?;
+
+        let rows: Vec<_> = response
+            .into_primary_results_lossy()
+            .flat_map(|table| table.rows)
+            .collect();
+
+        serde_json::to_string(&rows).map_err(to_js_error)
+    }
+}
+
+fn main() {}
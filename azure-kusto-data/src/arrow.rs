@@ -3,156 +3,452 @@ use std::convert::TryInto;
 use std::str::FromStr;
 use std::sync::Arc;
 
+use arrow_array::builder::{
+    BooleanBuilder, DurationNanosecondBuilder, Float64Builder, Int32Builder, Int64Builder,
+    StringBuilder, TimestampNanosecondBuilder,
+};
 use arrow_array::{
-    ArrayRef, BooleanArray, DurationNanosecondArray, Float64Array, Int32Array, Int64Array,
-    RecordBatch, StringArray, TimestampNanosecondArray,
+    ArrayRef, BooleanArray, Float64Array, Int64Array, RecordBatch, RecordBatchOptions, StringArray,
+    StructArray,
 };
-use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use arrow_buffer::NullBuffer;
+use arrow_schema::{DataType, Field, Fields, Schema, TimeUnit};
 use azure_core::error::{ErrorKind, ResultExt};
+use serde::de::DeserializeOwned;
 use serde_json::Value;
 
-use crate::error::Result;
+use crate::error::{Error, Result};
 use crate::models::ColumnType;
 use crate::models::{Column, DataTable};
-use crate::types::{KustoDateTime, KustoDuration};
+use crate::types::{parse_dynamic, KustoDateTime, KustoTimespan};
 
-fn convert_array_string(values: Vec<Value>) -> Result<ArrayRef> {
-    let strings: Vec<Option<String>> = serde_json::from_value(Value::Array(values))?;
-    let strings: Vec<Option<&str>> = strings.iter().map(Option::as_deref).collect();
-    Ok(Arc::new(StringArray::from(strings)))
+/// Options of the conversion of tables into arrow record batches.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ArrowOptions {
+    dynamic_as_struct: bool,
 }
 
-fn convert_array_datetime(values: Vec<Value>) -> Result<ArrayRef> {
-    let dates: Vec<String> = serde_json::from_value(Value::Array(values))?;
-    let timestamps = dates
-        .into_iter()
-        .map(|d| {
-            KustoDateTime::from_str(&d)
-                .ok()
-                .map(|d| d.unix_timestamp_nanos())
-                .and_then(|n| n.try_into().ok())
-        })
-        .collect::<Vec<Option<i64>>>();
-    let dates_array = Arc::new(TimestampNanosecondArray::from(timestamps));
-    Ok(dates_array)
+impl ArrowOptions {
+    /// Converts dynamic columns whose values are all objects of the same shape into arrow structs, with a field for each key of the objects,
+    /// rather than into JSON strings.
+    /// Keys that are missing from some of the objects are null in their rows.
+    ///
+    /// Columns with other values, or whose objects have values of different types for the same key, are still converted into JSON strings.
+    /// Integers and reals are compatible, and are converted into reals, while arrays are converted into JSON strings.
+    #[must_use]
+    pub fn with_dynamic_as_struct(mut self, dynamic_as_struct: bool) -> Self {
+        self.dynamic_as_struct = dynamic_as_struct;
+        self
+    }
+}
+
+/// Builds the arrow array of a column, one cell at a time.
+///
+/// Cells of the expected JSON type are appended directly, and the others go through serde,
+/// which either converts them or fails with the same error as deserializing the whole column would.
+enum ColumnBuilder {
+    String(StringBuilder),
+    Decimal(StringBuilder),
+    Dynamic(StringBuilder),
+    /// The values of a dynamic column, which are all needed to infer its struct type
+    DynamicValues(Vec<Value>),
+    Bool(BooleanBuilder),
+    Int(Int32Builder),
+    Long(Int64Builder),
+    Real(Float64Builder),
+    Datetime(TimestampNanosecondBuilder),
+    Timespan(DurationNanosecondBuilder),
+}
+
+fn deserialize<T: DeserializeOwned>(value: Value) -> Result<Option<T>> {
+    Ok(serde_json::from_value(value)?)
+}
+
+fn parse_datetime(value: &str) -> Option<i64> {
+    KustoDateTime::from_str(value)
+        .ok()
+        .and_then(|d| d.unix_timestamp_nanos().try_into().ok())
+}
+
+fn parse_timespan(value: &str) -> Option<i64> {
+    KustoTimespan::from_str(value)
+        .ok()
+        .and_then(|d| d.whole_nanoseconds().try_into().ok())
+}
+
+impl ColumnBuilder {
+    fn new(column_type: &ColumnType, capacity: usize, options: ArrowOptions) -> Self {
+        // A rough guess of the size of strings, to avoid most reallocations of their buffers
+        let strings = || StringBuilder::with_capacity(capacity, capacity * 16);
+        match column_type {
+            ColumnType::String | ColumnType::Guid => ColumnBuilder::String(strings()),
+            ColumnType::Decimal => ColumnBuilder::Decimal(strings()),
+            ColumnType::Dynamic if options.dynamic_as_struct => {
+                ColumnBuilder::DynamicValues(Vec::with_capacity(capacity))
+            }
+            ColumnType::Dynamic => ColumnBuilder::Dynamic(strings()),
+            ColumnType::Bool => ColumnBuilder::Bool(BooleanBuilder::with_capacity(capacity)),
+            ColumnType::Int => ColumnBuilder::Int(Int32Builder::with_capacity(capacity)),
+            ColumnType::Long => ColumnBuilder::Long(Int64Builder::with_capacity(capacity)),
+            ColumnType::Real => ColumnBuilder::Real(Float64Builder::with_capacity(capacity)),
+            ColumnType::Datetime => {
+                ColumnBuilder::Datetime(TimestampNanosecondBuilder::with_capacity(capacity))
+            }
+            ColumnType::Timespan => {
+                ColumnBuilder::Timespan(DurationNanosecondBuilder::with_capacity(capacity))
+            }
+        }
+    }
+
+    fn append(&mut self, value: Value) -> Result<()> {
+        match self {
+            ColumnBuilder::String(builder) => match value {
+                Value::String(s) => builder.append_value(s),
+                Value::Null => builder.append_null(),
+                other => builder.append_option(deserialize::<String>(other)?),
+            },
+            ColumnBuilder::Decimal(builder) => match value {
+                Value::String(s) => builder.append_value(s),
+                Value::Number(n) => builder.append_value(n.to_string()),
+                Value::Null => builder.append_null(),
+                other => {
+                    return Err(Error::ConversionError(format!(
+                        "decimal, expected a string or a number but got {other}"
+                    )))
+                }
+            },
+            // Dynamic values sent as strings holding JSON are converted as the JSON they hold
+            ColumnBuilder::Dynamic(builder) => match parse_dynamic(value) {
+                Value::Null => builder.append_null(),
+                value => builder.append_value(value.to_string()),
+            },
+            ColumnBuilder::DynamicValues(values) => values.push(parse_dynamic(value)),
+            ColumnBuilder::Bool(builder) => match value {
+                Value::Bool(b) => builder.append_value(b),
+                Value::Null => builder.append_null(),
+                other => builder.append_option(deserialize::<bool>(other)?),
+            },
+            ColumnBuilder::Int(builder) => {
+                match value.as_i64().and_then(|n| i32::try_from(n).ok()) {
+                    Some(n) => builder.append_value(n),
+                    None => builder.append_option(deserialize::<i32>(value)?),
+                }
+            }
+            ColumnBuilder::Long(builder) => match value.as_i64() {
+                Some(n) => builder.append_value(n),
+                None => builder.append_option(deserialize::<i64>(value)?),
+            },
+            // Reals are sent as numbers, except for NaN and the infinities, which are sent as strings
+            ColumnBuilder::Real(builder) => match value {
+                Value::Number(n) => builder.append_option(n.as_f64()),
+                Value::String(s) if s == "NaN" => builder.append_null(),
+                Value::String(s) if s == "Infinity" => builder.append_value(f64::INFINITY),
+                Value::String(s) if s == "-Infinity" => builder.append_value(-f64::INFINITY),
+                other => builder.append_option(deserialize::<f64>(other)?),
+            },
+            ColumnBuilder::Datetime(builder) => match value {
+                Value::String(s) => builder.append_option(parse_datetime(&s)),
+                other => builder
+                    .append_option(deserialize::<String>(other)?.and_then(|s| parse_datetime(&s))),
+            },
+            ColumnBuilder::Timespan(builder) => match value {
+                Value::String(s) => builder.append_option(parse_timespan(&s)),
+                other => builder
+                    .append_option(deserialize::<String>(other)?.and_then(|s| parse_timespan(&s))),
+            },
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> ArrayRef {
+        match self {
+            ColumnBuilder::String(mut builder)
+            | ColumnBuilder::Decimal(mut builder)
+            | ColumnBuilder::Dynamic(mut builder) => Arc::new(builder.finish()),
+            ColumnBuilder::DynamicValues(values) => convert_dynamic_values(&values),
+            ColumnBuilder::Bool(mut builder) => Arc::new(builder.finish()),
+            ColumnBuilder::Int(mut builder) => Arc::new(builder.finish()),
+            ColumnBuilder::Long(mut builder) => Arc::new(builder.finish()),
+            ColumnBuilder::Real(mut builder) => Arc::new(builder.finish()),
+            ColumnBuilder::Datetime(mut builder) => Arc::new(builder.finish()),
+            ColumnBuilder::Timespan(mut builder) => Arc::new(builder.finish()),
+        }
+    }
 }
 
-fn safe_map_f64(value: Value) -> Result<Option<f64>> {
-    match value {
-        Value::String(val) if val == "NaN" => Ok(None),
-        Value::String(val) if val == "Infinity" => Ok(Some(f64::INFINITY)),
-        Value::String(val) if val == "-Infinity" => Ok(Some(-f64::INFINITY)),
-        _ => Ok(serde_json::from_value(value)?),
+/// The arrow type that values of a Kusto column type are converted to.
+/// Decimals are kept as strings to preserve their precision, and dynamic values are serialized to JSON strings,
+/// unless they are converted into structs with [ArrowOptions::with_dynamic_as_struct].
+/// Dynamic values sent as strings holding JSON are first parsed, as [parse_dynamic] does, so that they are not serialized twice.
+pub fn arrow_data_type(column_type: &ColumnType) -> DataType {
+    match column_type {
+        ColumnType::String | ColumnType::Guid | ColumnType::Decimal | ColumnType::Dynamic => {
+            DataType::Utf8
+        }
+        ColumnType::Bool => DataType::Boolean,
+        ColumnType::Int => DataType::Int32,
+        ColumnType::Long => DataType::Int64,
+        ColumnType::Real => DataType::Float64,
+        ColumnType::Datetime => DataType::Timestamp(TimeUnit::Nanosecond, None),
+        ColumnType::Timespan => DataType::Duration(TimeUnit::Nanosecond),
     }
 }
 
-fn convert_array_float(values: Vec<Value>) -> Result<ArrayRef> {
-    let reals: Vec<Option<f64>> = values
+/// The field of a converted column, whose type is that of its array, as dynamic columns can be converted into structs.
+fn field(column: &Column, array: &ArrayRef) -> Field {
+    Field::new(&column.column_name, array.data_type().clone(), true)
+}
+
+/// Converts the values of a single column, as [convert_table] does for each column of its rows.
+#[cfg(test)]
+fn convert_column(
+    data: Vec<Value>,
+    column: &Column,
+    options: ArrowOptions,
+) -> Result<(Field, ArrayRef)> {
+    let mut builder = ColumnBuilder::new(&column.column_type, data.len(), options);
+    for value in data {
+        builder.append(value)?;
+    }
+    let array = builder.finish();
+    Ok((field(column, &array), array))
+}
+
+/// The shape of dynamic values, inferred from their JSON values.
+#[derive(Debug, Clone, PartialEq)]
+enum Shape {
+    /// Only nulls
+    Null,
+    Bool,
+    Int,
+    Real,
+    String,
+    /// Arrays, which are kept as JSON strings
+    Json,
+    /// Objects, with the shapes of their keys in order of appearance
+    Object(Vec<(String, Shape)>),
+}
+
+impl Shape {
+    fn of(value: &Value) -> Shape {
+        match value {
+            Value::Null => Shape::Null,
+            Value::Bool(_) => Shape::Bool,
+            Value::Number(n) if n.is_i64() => Shape::Int,
+            Value::Number(_) => Shape::Real,
+            Value::String(_) => Shape::String,
+            Value::Array(_) => Shape::Json,
+            Value::Object(object) => Shape::Object(
+                object
+                    .iter()
+                    .map(|(key, value)| (key.clone(), Shape::of(value)))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// The shape that both shapes fit into, if they are compatible.
+    fn merge(self, other: Shape) -> Option<Shape> {
+        match (self, other) {
+            (Shape::Null, shape) | (shape, Shape::Null) => Some(shape),
+            (Shape::Int, Shape::Real) | (Shape::Real, Shape::Int) => Some(Shape::Real),
+            (Shape::Object(mut fields), Shape::Object(other)) => {
+                for (key, shape) in other {
+                    match fields.iter_mut().find(|(k, _)| *k == key) {
+                        Some((_, existing)) => {
+                            *existing = std::mem::replace(existing, Shape::Null).merge(shape)?
+                        }
+                        None => fields.push((key, shape)),
+                    }
+                }
+                Some(Shape::Object(fields))
+            }
+            (a, b) if a == b => Some(a),
+            _ => None,
+        }
+    }
+}
+
+/// Builds the array of values of the given shape, where `None` is a null or missing value.
+fn build_shape(shape: &Shape, values: &[Option<&Value>]) -> ArrayRef {
+    match shape {
+        Shape::Bool => Arc::new(BooleanArray::from(
+            values
+                .iter()
+                .map(|v| v.and_then(Value::as_bool))
+                .collect::<Vec<_>>(),
+        )),
+        Shape::Int => Arc::new(Int64Array::from(
+            values
+                .iter()
+                .map(|v| v.and_then(Value::as_i64))
+                .collect::<Vec<_>>(),
+        )),
+        Shape::Real => Arc::new(Float64Array::from(
+            values
+                .iter()
+                .map(|v| v.and_then(Value::as_f64))
+                .collect::<Vec<_>>(),
+        )),
+        Shape::String => Arc::new(StringArray::from(
+            values
+                .iter()
+                .map(|v| v.and_then(Value::as_str))
+                .collect::<Vec<_>>(),
+        )),
+        Shape::Null | Shape::Json => Arc::new(StringArray::from(
+            values
+                .iter()
+                .map(|v| v.map(Value::to_string))
+                .collect::<Vec<_>>(),
+        )),
+        Shape::Object(shapes) if !shapes.is_empty() => {
+            let children: Vec<ArrayRef> = shapes
+                .iter()
+                .map(|(key, shape)| {
+                    let child_values: Vec<Option<&Value>> = values
+                        .iter()
+                        .map(|v| v.and_then(|v| v.get(key)).filter(|v| !v.is_null()))
+                        .collect();
+                    build_shape(shape, &child_values)
+                })
+                .collect();
+            let fields: Fields = shapes
+                .iter()
+                .zip(&children)
+                .map(|((key, _), child)| Field::new(key, child.data_type().clone(), true))
+                .collect();
+            let nulls = NullBuffer::from(values.iter().map(Option::is_some).collect::<Vec<_>>());
+            Arc::new(StructArray::new(fields, children, Some(nulls)))
+        }
+        // Arrow structs need fields, so empty objects are kept as JSON strings
+        Shape::Object(_) => build_shape(&Shape::Json, values),
+    }
+}
+
+/// Converts the values of a dynamic column into a struct array if they are objects of compatible shapes,
+/// and into JSON strings otherwise.
+fn convert_dynamic_values(values: &[Value]) -> ArrayRef {
+    let shape = values
+        .iter()
+        .filter(|v| !v.is_null())
+        .try_fold(None, |shape: Option<Shape>, value| {
+            if !value.is_object() {
+                return None;
+            }
+            match shape {
+                None => Some(Some(Shape::of(value))),
+                Some(shape) => shape.merge(Shape::of(value)).map(Some),
+            }
+        })
+        .flatten();
+
+    let values: Vec<Option<&Value>> = values.iter().map(|v| (!v.is_null()).then_some(v)).collect();
+    match shape {
+        Some(shape @ Shape::Object(_)) => build_shape(&shape, &values),
+        _ => build_shape(&Shape::Json, &values),
+    }
+}
+
+/// Converts the result of a query that was run with [query_results_apply_getschema](crate::request_options::Options::query_results_apply_getschema) into an arrow [Schema].
+/// Each row of the table describes a column of the query's result, by its `ColumnName` and `ColumnType`.
+pub fn convert_schema_table(table: DataTable) -> Result<Schema> {
+    let column_index = |name: &str| {
+        table
+            .columns
+            .iter()
+            .position(|c| c.column_name == name)
+            .ok_or_else(|| Error::ConversionError(format!("schema, the {name} column is missing")))
+    };
+    let name_index = column_index("ColumnName")?;
+    let type_index = column_index("ColumnType")?;
+
+    let fields = table
+        .rows
         .into_iter()
-        .map(safe_map_f64)
+        .map(|row| {
+            let mut values = match row {
+                Value::Array(values) => values,
+                other => {
+                    return Err(Error::ConversionError(format!(
+                        "schema row, expected an array but got {other}"
+                    )))
+                }
+            };
+            let mut take = |index: usize| values.get_mut(index).map_or(Value::Null, Value::take);
+            let column_name: String = serde_json::from_value(take(name_index))?;
+            let column_type: ColumnType = serde_json::from_value(take(type_index))?;
+            Ok(Field::new(column_name, arrow_data_type(&column_type), true))
+        })
         .collect::<Result<Vec<_>>>()?;
-    Ok(Arc::new(Float64Array::from(reals)))
+
+    Ok(Schema::new(fields))
 }
 
-fn convert_array_timespan(values: Vec<Value>) -> Result<ArrayRef> {
-    let strings: Vec<String> = serde_json::from_value(Value::Array(values))?;
-    let durations: Vec<Option<i64>> = strings
+/// Converts a table into a record batch in a single pass over its rows, appending each cell to the builder of its column.
+/// Rows that are shorter than the columns are padded with nulls.
+pub fn convert_table(table: DataTable, options: ArrowOptions) -> Result<RecordBatch> {
+    let row_count = table.rows.len();
+    let mut builders: Vec<ColumnBuilder> = table
+        .columns
         .iter()
-        .map(|s| {
-            KustoDuration::from_str(s)
-                .ok()
-                .and_then(|d| i64::try_from(d.whole_nanoseconds()).ok())
-        })
+        .map(|column| ColumnBuilder::new(&column.column_type, row_count, options))
         .collect();
-    Ok(Arc::new(DurationNanosecondArray::from(durations)))
-}
-
-fn convert_array_bool(values: Vec<Value>) -> Result<ArrayRef> {
-    let bools: Vec<Option<bool>> = serde_json::from_value(Value::Array(values))?;
-    Ok(Arc::new(BooleanArray::from(bools)))
-}
-
-fn convert_array_i32(values: Vec<Value>) -> Result<ArrayRef> {
-    let ints: Vec<Option<i32>> = serde_json::from_value(Value::Array(values))?;
-    Ok(Arc::new(Int32Array::from(ints)))
-}
-
-fn convert_array_i64(values: Vec<Value>) -> Result<ArrayRef> {
-    let ints: Vec<Option<i64>> = serde_json::from_value(Value::Array(values))?;
-    Ok(Arc::new(Int64Array::from(ints)))
-}
-
-pub fn convert_column(data: Vec<Value>, column: &Column) -> Result<(Field, ArrayRef)> {
-    let column_name = &column.column_name;
-    match column.column_type {
-        ColumnType::String => convert_array_string(data)
-            .map(|data| (Field::new(column_name, DataType::Utf8, true), data)),
-        ColumnType::Bool => convert_array_bool(data)
-            .map(|data| (Field::new(column_name, DataType::Boolean, true), data)),
-        ColumnType::Int => convert_array_i32(data)
-            .map(|data| (Field::new(column_name, DataType::Int32, true), data)),
-        ColumnType::Long => convert_array_i64(data)
-            .map(|data| (Field::new(column_name, DataType::Int64, true), data)),
-        ColumnType::Real => convert_array_float(data)
-            .map(|data| (Field::new(column_name, DataType::Float64, true), data)),
-        ColumnType::Datetime => convert_array_datetime(data).map(|data| {
-            (
-                Field::new(
-                    column_name,
-                    DataType::Timestamp(TimeUnit::Nanosecond, None),
-                    true,
-                ),
-                data,
-            )
-        }),
-        ColumnType::Timespan => convert_array_timespan(data).map(|data| {
-            (
-                Field::new(column_name, DataType::Duration(TimeUnit::Nanosecond), true),
-                data,
-            )
-        }),
-        _ => todo!(),
-    }
-}
-
-pub fn convert_table(table: DataTable) -> Result<RecordBatch> {
-    let mut buffer: Vec<Vec<Value>> = Vec::with_capacity(table.columns.len());
-    let mut fields: Vec<Field> = Vec::with_capacity(table.columns.len());
-    let mut columns: Vec<ArrayRef> = Vec::with_capacity(table.columns.len());
-
-    for _ in 0..table.columns.len() {
-        buffer.push(Vec::with_capacity(table.rows.len()));
-    }
-    table.rows.into_iter().for_each(|row| match row {
-        Value::Array(v) => {
-            v.into_iter().enumerate().for_each(|(i, v)| {
-                buffer[i].push(v);
-            });
+
+    for (index, row) in table.rows.into_iter().enumerate() {
+        let values = match row {
+            Value::Array(values) if values.len() <= builders.len() => values,
+            other => {
+                return Err(Error::ConversionError(format!(
+                "row {index} of table '{}', expected an array of up to {} values but got {other}",
+                table.table_name,
+                builders.len()
+            )))
+            }
+        };
+        let len = values.len();
+        for (builder, value) in builders.iter_mut().zip(values) {
+            builder.append(value)?;
+        }
+        for builder in &mut builders[len..] {
+            builder.append(Value::Null)?;
         }
-        _ => unreachable!("Must be an array"),
-    });
+    }
 
-    buffer
-        .into_iter()
-        .zip(table.columns.into_iter())
-        .map(|(data, column)| convert_column(data, &column))
-        .try_for_each::<_, Result<()>>(|result| {
-            let (field, data) = result?;
-            fields.push(field);
-            columns.push(data);
-            Ok(())
-        })?;
+    let columns: Vec<ArrayRef> = builders.into_iter().map(ColumnBuilder::finish).collect();
+    let fields: Vec<Field> = table
+        .columns
+        .iter()
+        .zip(&columns)
+        .map(|(column, array)| field(column, array))
+        .collect();
+
+    // The row count is explicit, as it can't be inferred from the columns of tables that have none
+    let options = RecordBatchOptions::new().with_row_count(Some(row_count));
+    Ok(
+        RecordBatch::try_new_with_options(Arc::new(Schema::new(fields)), columns, &options)
+            .context(ErrorKind::DataConversion, "Failed to create record batch")?,
+    )
+}
 
-    Ok(RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
-        .context(ErrorKind::DataConversion, "Failed to create record batch")?)
+/// Deserializes the rows of a record batch into `T` column by column, matching the columns to fields by name.
+///
+/// The fields must have types that the arrow types of their columns deserialize into,
+/// such as `i64` for the nanoseconds of datetimes and timespans, rather than [KustoDateTime] and [KustoTimespan].
+pub fn deserialize_record_batch<T: DeserializeOwned>(batch: &RecordBatch) -> Result<Vec<T>> {
+    Ok(serde_arrow::from_record_batch(batch).context(
+        ErrorKind::DataConversion,
+        "Failed to deserialize record batch",
+    )?)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::fixtures::load_v2_response;
     use crate::models::{TableKind, V2QueryResult};
     use crate::operations::query::KustoResponseDataSetV2;
-    use std::path::PathBuf;
+    use arrow_array::{Array, StringArray};
 
     #[test]
     fn deserialize_column() {
@@ -201,13 +497,7 @@ mod tests {
 
     #[test]
     fn read_data_types() {
-        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-        path.push("tests/inputs/dataframe.json");
-
-        let data = std::fs::read_to_string(path).expect("Failed to read file");
-        let tables: Vec<V2QueryResult> =
-            serde_json::from_str(&data).expect("Failed to deserialize result table");
-        let response = KustoResponseDataSetV2 { results: tables };
+        let response = load_v2_response("dataframe.json");
         let record_batches = response
             .record_batches()
             .collect::<std::result::Result<Vec<_>, _>>()
@@ -216,4 +506,468 @@ mod tests {
         assert!(record_batches[0].num_columns() > 0);
         assert!(record_batches[0].num_rows() > 0);
     }
+
+    #[test]
+    fn record_batches_keep_column_types() {
+        let response = load_v2_response("dataframe.json");
+        let record_batches = response
+            .into_record_batches()
+            .collect::<Result<Vec<_>>>()
+            .expect("Failed to convert to record batches");
+
+        let schema = record_batches[0].schema();
+        let types: Vec<(&str, &DataType)> = schema
+            .fields()
+            .iter()
+            .map(|f| (f.name().as_str(), f.data_type()))
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                ("RecordName", &DataType::Utf8),
+                (
+                    "RecordTime",
+                    &DataType::Timestamp(TimeUnit::Nanosecond, None)
+                ),
+                ("RecordOffset", &DataType::Duration(TimeUnit::Nanosecond)),
+                ("RecordBool", &DataType::Boolean),
+                ("RecordInt", &DataType::Int32),
+                ("RecordReal", &DataType::Float64),
+            ]
+        );
+    }
+
+    #[test]
+    fn schema_is_read_from_getschema_results() {
+        let response = load_v2_response("getschema.json");
+        let table = response
+            .into_primary_results()
+            .next()
+            .expect("Expected a primary result")
+            .expect("Failed to parse the primary result");
+
+        let schema = convert_schema_table(table).expect("Failed to convert schema");
+
+        let types: Vec<(&str, &DataType)> = schema
+            .fields()
+            .iter()
+            .map(|f| (f.name().as_str(), f.data_type()))
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                ("vnum", &DataType::Int32),
+                ("vdec", &DataType::Utf8),
+                ("vdate", &DataType::Timestamp(TimeUnit::Nanosecond, None)),
+                ("vspan", &DataType::Duration(TimeUnit::Nanosecond)),
+                ("vobj", &DataType::Utf8),
+                ("vb", &DataType::Boolean),
+                ("vreal", &DataType::Float64),
+                ("vstr", &DataType::Utf8),
+                ("vlong", &DataType::Int64),
+                ("vguid", &DataType::Utf8),
+            ]
+        );
+    }
+
+    #[test]
+    fn decimal_dynamic_and_guid_columns_are_strings() {
+        let column = |column_type| Column {
+            column_name: "col".to_string(),
+            column_type,
+        };
+        let strings = |array: ArrayRef| -> Vec<Option<String>> {
+            array
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .expect("Expected a string array")
+                .iter()
+                .map(|v| v.map(str::to_string))
+                .collect()
+        };
+
+        let (field, decimals) = convert_column(
+            vec![Value::from("2.00000000000001"), Value::from(5), Value::Null],
+            &column(ColumnType::Decimal),
+            ArrowOptions::default(),
+        )
+        .expect("Failed to convert decimals");
+        assert_eq!(field.data_type(), &DataType::Utf8);
+        assert_eq!(
+            strings(decimals),
+            vec![
+                Some("2.00000000000001".to_string()),
+                Some("5".to_string()),
+                None
+            ]
+        );
+
+        let (_, dynamics) = convert_column(
+            vec![
+                serde_json::json!({"moshe": "value"}),
+                Value::Null,
+                Value::from(r#"{"moshe": ["nested", {"value": 1}]}"#),
+                Value::from("[looks like json"),
+            ],
+            &column(ColumnType::Dynamic),
+            ArrowOptions::default(),
+        )
+        .expect("Failed to convert dynamics");
+        assert_eq!(
+            strings(dynamics),
+            vec![
+                Some(r#"{"moshe":"value"}"#.to_string()),
+                None,
+                Some(r#"{"moshe":["nested",{"value":1}]}"#.to_string()),
+                Some(r#""[looks like json""#.to_string()),
+            ]
+        );
+
+        let (_, guids) = convert_column(
+            vec![Value::from("74be27de-1e4e-49d9-b579-fe0b331d3642")],
+            &column(ColumnType::Guid),
+            ArrowOptions::default(),
+        )
+        .expect("Failed to convert guids");
+        assert_eq!(
+            strings(guids),
+            vec![Some("74be27de-1e4e-49d9-b579-fe0b331d3642".to_string())]
+        );
+    }
+
+    fn all_column_types() -> Vec<ColumnType> {
+        vec![
+            ColumnType::Bool,
+            ColumnType::Datetime,
+            ColumnType::Decimal,
+            ColumnType::Dynamic,
+            ColumnType::Guid,
+            ColumnType::Int,
+            ColumnType::Long,
+            ColumnType::Real,
+            ColumnType::String,
+            ColumnType::Timespan,
+        ]
+    }
+
+    fn table_of(columns: Vec<Column>, rows: Vec<Value>) -> DataTable {
+        DataTable {
+            table_id: 1,
+            table_name: "table".to_string(),
+            table_kind: TableKind::PrimaryResult,
+            columns,
+            rows,
+        }
+    }
+
+    #[test]
+    fn empty_tables_keep_column_types() {
+        let columns: Vec<Column> = all_column_types()
+            .into_iter()
+            .enumerate()
+            .map(|(i, column_type)| Column {
+                column_name: format!("col{i}"),
+                column_type,
+            })
+            .collect();
+
+        let batch = convert_table(table_of(columns.clone(), vec![]), ArrowOptions::default())
+            .expect("Failed to convert");
+
+        assert_eq!(batch.num_rows(), 0);
+        assert_eq!(batch.num_columns(), columns.len());
+        for (field, column) in batch.schema().fields().iter().zip(&columns) {
+            assert_eq!(field.data_type(), &arrow_data_type(&column.column_type));
+        }
+    }
+
+    #[test]
+    fn tables_without_columns() {
+        let batch = convert_table(table_of(vec![], vec![]), ArrowOptions::default())
+            .expect("Failed to convert");
+        assert_eq!((batch.num_rows(), batch.num_columns()), (0, 0));
+
+        let batch = convert_table(
+            table_of(vec![], vec![Value::Array(vec![]); 2]),
+            ArrowOptions::default(),
+        )
+        .expect("Failed to convert");
+        assert_eq!((batch.num_rows(), batch.num_columns()), (2, 0));
+    }
+
+    #[test]
+    fn short_rows_are_padded_with_nulls() {
+        let columns = vec![
+            Column {
+                column_name: "name".to_string(),
+                column_type: ColumnType::String,
+            },
+            Column {
+                column_name: "count".to_string(),
+                column_type: ColumnType::Long,
+            },
+        ];
+        let rows = vec![serde_json::json!(["a", 1]), serde_json::json!(["b"])];
+
+        let batch = convert_table(table_of(columns.clone(), rows), ArrowOptions::default())
+            .expect("Failed to convert");
+
+        assert_eq!(batch.num_rows(), 2);
+        assert_eq!(batch.column(0).null_count(), 0);
+        assert_eq!(batch.column(1).null_count(), 1);
+        assert!(batch.column(1).is_null(1));
+
+        let rows = vec![serde_json::json!(["a", 1, true])];
+        let error = convert_table(table_of(columns, rows), ArrowOptions::default()).unwrap_err();
+        assert!(error.to_string().contains("row 0"), "{error}");
+    }
+
+    #[test]
+    fn null_columns_of_every_type() {
+        for column_type in all_column_types() {
+            let column = Column {
+                column_name: "col".to_string(),
+                column_type: column_type.clone(),
+            };
+
+            let (field, array) =
+                convert_column(vec![Value::Null; 3], &column, ArrowOptions::default())
+                    .unwrap_or_else(|e| panic!("Failed to convert nulls of {column_type:?}: {e}"));
+
+            assert_eq!(field.data_type(), &arrow_data_type(&column_type));
+            assert_eq!(array.data_type(), &arrow_data_type(&column_type));
+            assert_eq!(array.len(), 3);
+            assert_eq!(array.null_count(), 3, "{column_type:?}");
+        }
+    }
+
+    fn convert_dynamics(values: Vec<Value>) -> ArrayRef {
+        let column = Column {
+            column_name: "col".to_string(),
+            column_type: ColumnType::Dynamic,
+        };
+        let options = ArrowOptions::default().with_dynamic_as_struct(true);
+        convert_column(values, &column, options)
+            .expect("Failed to convert dynamics")
+            .1
+    }
+
+    #[test]
+    fn dynamic_strings_holding_objects_are_structs() {
+        let array = convert_dynamics(vec![
+            Value::from(r#"{"name": "a", "inner": {"flag": true}}"#),
+            serde_json::json!({"name": "b", "inner": {"flag": false}}),
+        ]);
+
+        let structs = array
+            .as_any()
+            .downcast_ref::<StructArray>()
+            .expect("Expected the parsed strings to be structs");
+        let names = structs
+            .column_by_name("name")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(names.value(0), "a");
+        assert_eq!(names.value(1), "b");
+    }
+
+    #[test]
+    fn homogeneous_dynamic_objects_are_structs() {
+        let array = convert_dynamics(vec![
+            serde_json::json!({"name": "a", "count": 1, "inner": {"flag": true}}),
+            Value::Null,
+            serde_json::json!({"name": "b", "count": 2.5, "tags": ["x", "y"]}),
+        ]);
+
+        let structs = array
+            .as_any()
+            .downcast_ref::<StructArray>()
+            .expect("Expected a struct array");
+        let types: Vec<(&str, &DataType)> = structs
+            .fields()
+            .iter()
+            .map(|f| (f.name().as_str(), f.data_type()))
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                ("count", &DataType::Float64),
+                (
+                    "inner",
+                    &DataType::Struct(Fields::from(vec![Field::new(
+                        "flag",
+                        DataType::Boolean,
+                        true
+                    )]))
+                ),
+                ("name", &DataType::Utf8),
+                ("tags", &DataType::Utf8),
+            ]
+        );
+        assert_eq!(structs.len(), 3);
+        assert!(structs.is_null(1));
+
+        let counts = structs
+            .column_by_name("count")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(counts.value(0), 1.0);
+        assert_eq!(counts.value(2), 2.5);
+
+        let tags = structs
+            .column_by_name("tags")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert!(tags.is_null(0));
+        assert_eq!(tags.value(2), r#"["x","y"]"#);
+    }
+
+    #[test]
+    fn mixed_dynamic_shapes_are_strings() {
+        for values in [
+            vec![serde_json::json!({"a": 1}), serde_json::json!([1, 2])],
+            vec![serde_json::json!({"a": 1}), serde_json::json!({"a": "one"})],
+            vec![
+                serde_json::json!({"a": {"b": 1}}),
+                serde_json::json!({"a": {"b": false}}),
+            ],
+            vec![Value::Null, Value::Null],
+        ] {
+            let expected: Vec<Option<String>> = values
+                .iter()
+                .map(|v| (!v.is_null()).then(|| v.to_string()))
+                .collect();
+
+            let array = convert_dynamics(values);
+
+            let strings = array
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .expect("Expected a string array");
+            assert_eq!(
+                strings
+                    .iter()
+                    .map(|v| v.map(str::to_string))
+                    .collect::<Vec<_>>(),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn record_batches_with_dynamic_structs() {
+        let response = KustoResponseDataSetV2 {
+            results: vec![V2QueryResult::DataTable(table_of(
+                vec![Column {
+                    column_name: "d".to_string(),
+                    column_type: ColumnType::Dynamic,
+                }],
+                vec![serde_json::json!([{"a": 1}])],
+            ))],
+        };
+
+        let batch = response
+            .record_batches_with(ArrowOptions::default().with_dynamic_as_struct(true))
+            .next()
+            .unwrap()
+            .unwrap();
+        assert!(matches!(
+            batch.schema().field(0).data_type(),
+            DataType::Struct(_)
+        ));
+
+        let batch = response.record_batches().next().unwrap().unwrap();
+        assert_eq!(batch.schema().field(0).data_type(), &DataType::Utf8);
+    }
+
+    #[derive(serde::Deserialize, Debug, PartialEq)]
+    #[serde(rename_all = "PascalCase")]
+    struct Record {
+        record_name: Option<String>,
+        record_time: Option<i64>,
+        record_offset: Option<i64>,
+        record_bool: bool,
+        record_int: i32,
+        record_real: Option<f64>,
+    }
+
+    #[test]
+    #[allow(clippy::approx_constant)]
+    fn structs_are_deserialized_from_record_batches() {
+        let response = load_v2_response("dataframe.json");
+
+        let records: Vec<Record> = response
+            .into_structs_via_arrow()
+            .expect("Failed to deserialize records");
+
+        assert_eq!(records.len(), 8);
+        assert_eq!(
+            records[0],
+            Record {
+                record_name: Some("now".to_string()),
+                record_time: Some(1_640_173_380_000_000_000),
+                record_offset: Some(90_061_000_000_000),
+                record_bool: true,
+                record_int: 5678,
+                record_real: Some(3.14159),
+            }
+        );
+        assert_eq!(records[1].record_real, None);
+        assert_eq!(records[2].record_real, Some(f64::INFINITY));
+        assert_eq!(records[7].record_name, None);
+        assert_eq!(records[7].record_time, None);
+    }
+
+    #[test]
+    fn structs_of_other_types_fail_to_deserialize() {
+        #[derive(serde::Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Wrong {
+            count: String,
+        }
+
+        let response = KustoResponseDataSetV2 {
+            results: vec![V2QueryResult::DataTable(table_of(
+                vec![Column {
+                    column_name: "count".to_string(),
+                    column_type: ColumnType::Long,
+                }],
+                vec![serde_json::json!([1])],
+            ))],
+        };
+
+        assert!(response.into_structs_via_arrow::<Wrong>().is_err());
+    }
+
+    #[test]
+    fn record_batches_propagate_conversion_errors() {
+        let table = |table_id, value: Value| {
+            V2QueryResult::DataTable(DataTable {
+                table_id,
+                table_name: format!("table_{table_id}"),
+                table_kind: TableKind::PrimaryResult,
+                columns: vec![Column {
+                    column_name: "count".to_string(),
+                    column_type: ColumnType::Long,
+                }],
+                rows: vec![Value::Array(vec![value])],
+            })
+        };
+        let response = KustoResponseDataSetV2 {
+            results: vec![
+                table(1, Value::from(1)),
+                table(2, Value::from("not a number")),
+            ],
+        };
+
+        let result = response.into_record_batches().collect::<Result<Vec<_>>>();
+
+        assert!(matches!(result, Err(crate::error::Error::JsonError(_))));
+    }
 }
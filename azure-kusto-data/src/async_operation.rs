@@ -0,0 +1,397 @@
+//! Following management commands that run asynchronously, such as `.set-or-append async`, until they complete.
+//!
+//! An asynchronous command returns the id of its operation right away, see [KustoClient::execute_command_async].
+//! The state of the operation is then read from `.show operations`, with [KustoOperation].
+
+use std::fmt::{Display, Formatter};
+use std::future::Future;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::client::KustoClient;
+use crate::deserialization::{deserialize_table_v1, CaseMapping};
+use crate::error::{Error, Result};
+use crate::operations::query::KustoResponseDataSetV1;
+use crate::prelude::ClientRequestProperties;
+use crate::types::KustoDateTime;
+
+/// The state of an operation, as reported by `.show operations`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(from = "String", into = "String")]
+pub enum OperationState {
+    /// The operation is scheduled or running.
+    InProgress,
+    /// The operation completed successfully.
+    Completed,
+    /// The operation failed, see [OperationStatus::status] for the reason.
+    Failed,
+    /// The operation was rejected because the cluster is overloaded, and may be run again later.
+    Throttled,
+    /// Another state, such as `Abandoned` or `Cancelled`, in which the operation did not complete.
+    Other(String),
+}
+
+impl OperationState {
+    /// Whether the operation is done, successfully or not.
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        !matches!(self, OperationState::InProgress)
+    }
+
+    fn as_str(&self) -> &str {
+        match self {
+            OperationState::InProgress => "InProgress",
+            OperationState::Completed => "Completed",
+            OperationState::Failed => "Failed",
+            OperationState::Throttled => "Throttled",
+            OperationState::Other(state) => state,
+        }
+    }
+}
+
+impl From<String> for OperationState {
+    fn from(state: String) -> Self {
+        match state.as_str() {
+            "InProgress" | "Scheduled" => OperationState::InProgress,
+            "Completed" => OperationState::Completed,
+            "Failed" => OperationState::Failed,
+            "Throttled" => OperationState::Throttled,
+            _ => OperationState::Other(state),
+        }
+    }
+}
+
+impl From<OperationState> for String {
+    fn from(state: OperationState) -> Self {
+        state.as_str().to_string()
+    }
+}
+
+impl Display for OperationState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A row of `.show operations`, describing the progress of an operation.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct OperationStatus {
+    /// The id of the operation.
+    pub operation_id: Uuid,
+    /// The kind of the operation, such as `TableSetOrAppend`.
+    pub operation: String,
+    /// When the operation started.
+    pub started_on: Option<KustoDateTime>,
+    /// When the state of the operation last changed.
+    pub last_updated_on: Option<KustoDateTime>,
+    /// The state of the operation.
+    pub state: OperationState,
+    /// Details about the state, such as the reason the operation failed. Empty while it runs.
+    #[serde(default, deserialize_with = "crate::deserialization::null_as_empty")]
+    pub status: String,
+    /// Whether the service expects running the command again to succeed, when the operation failed.
+    #[serde(default)]
+    pub should_retry: bool,
+    /// The database the operation runs in.
+    pub database: Option<String>,
+}
+
+fn parse_operation_id(response: KustoResponseDataSetV1) -> Result<Uuid> {
+    #[derive(Deserialize)]
+    struct OperationIdRow {
+        operation_id: Uuid,
+    }
+
+    let table = response.tables.into_iter().next().ok_or_else(|| {
+        Error::QueryError("No operation id returned by the asynchronous command".into())
+    })?;
+    deserialize_table_v1::<OperationIdRow>(table, CaseMapping::PascalToSnake)?
+        .into_iter()
+        .next()
+        .map(|row| row.operation_id)
+        .ok_or_else(|| {
+            Error::QueryError("No operation id returned by the asynchronous command".into())
+        })
+}
+
+/// Parses the status of an operation from `.show operations <id>`, which lists its states over time, the last one being current.
+fn parse_operation_status(
+    response: KustoResponseDataSetV1,
+    operation_id: Uuid,
+) -> Result<OperationStatus> {
+    let table = response
+        .tables
+        .into_iter()
+        .next()
+        .ok_or_else(|| Error::QueryError("No results found for `.show operations`".into()))?;
+    deserialize_table_v1::<OperationStatus>(table, CaseMapping::PascalToSnake)?
+        .into_iter()
+        .rfind(|status| status.operation_id == operation_id)
+        .ok_or_else(|| {
+            Error::QueryError(format!("The operation {operation_id} was not found").into())
+        })
+}
+
+/// The time since `started`, measured with the system clock, since [std::time::Instant] is not available in WebAssembly.
+fn elapsed_since(started: OffsetDateTime) -> Duration {
+    (OffsetDateTime::now_utc() - started)
+        .try_into()
+        .unwrap_or_default()
+}
+
+/// Reads the status of an operation with `next_status` every `poll_interval`, until it finishes or `timeout` elapses.
+async fn wait_for<F, Fut>(
+    operation_id: Uuid,
+    mut next_status: F,
+    poll_interval: Duration,
+    timeout: Duration,
+) -> Result<OperationStatus>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<OperationStatus>>,
+{
+    let started = OffsetDateTime::now_utc();
+    loop {
+        let status = next_status().await?;
+        match status.state {
+            OperationState::InProgress => {}
+            OperationState::Completed => return Ok(status),
+            _ => {
+                return Err(Error::OperationFailed {
+                    operation_id,
+                    state: status.state,
+                    status: status.status,
+                    should_retry: status.should_retry,
+                })
+            }
+        }
+
+        let elapsed = elapsed_since(started);
+        if elapsed >= timeout {
+            return Err(Error::OperationTimeout {
+                operation_id,
+                timeout,
+            });
+        }
+        azure_core::sleep::sleep(poll_interval.min(timeout - elapsed)).await;
+    }
+}
+
+/// An operation started by an asynchronous management command, see [KustoClient::execute_command_async].
+#[derive(Debug, Clone)]
+pub struct KustoOperation {
+    client: KustoClient,
+    database: String,
+    operation_id: Uuid,
+}
+
+impl KustoOperation {
+    /// Follows an operation that was already started, such as by another process.
+    pub fn new(client: KustoClient, database: impl Into<String>, operation_id: Uuid) -> Self {
+        Self {
+            client,
+            database: database.into(),
+            operation_id,
+        }
+    }
+
+    /// The id of the operation.
+    #[must_use]
+    pub fn operation_id(&self) -> Uuid {
+        self.operation_id
+    }
+
+    /// Reads the current status of the operation, with `.show operations`.
+    pub async fn status(&self) -> Result<OperationStatus> {
+        let response = self
+            .client
+            .execute_command(
+                self.database.clone(),
+                format!(".show operations {}", self.operation_id),
+                None,
+            )
+            .await?;
+        parse_operation_status(response, self.operation_id)
+    }
+
+    /// Waits for the operation to finish, reading its status every `poll_interval`.
+    ///
+    /// Returns the final status once the operation completes. Fails with [Error::OperationFailed] if it ends in any other state,
+    /// such as when it fails or is throttled, and with [Error::OperationTimeout] if it is still running after `timeout`.
+    pub async fn wait_for_completion(
+        &self,
+        poll_interval: Duration,
+        timeout: Duration,
+    ) -> Result<OperationStatus> {
+        wait_for(self.operation_id, || self.status(), poll_interval, timeout).await
+    }
+}
+
+impl KustoClient {
+    /// Runs a management command asynchronously, returning a handle to follow its operation.
+    ///
+    /// The command must be one that supports running asynchronously, and include the `async` keyword,
+    /// such as `.set-or-append async`, so that the service returns the id of its operation instead of waiting for it.
+    /// # Example
+    /// ```no_run
+    /// use azure_kusto_data::prelude::*;
+    /// use std::time::Duration;
+    /// # #[tokio::main] async fn main() -> Result<(), Error> {
+    /// let client = KustoClient::new(
+    ///    ConnectionString::with_default_auth("https://mycluster.region.kusto.windows.net/"),
+    ///    KustoClientOptions::default())?;
+    ///
+    /// let operation = client
+    ///     .execute_command_async(
+    ///         "some_database",
+    ///         ".set-or-append async Summary <| Events | summarize count() by Name",
+    ///         None,
+    ///     )
+    ///     .await?;
+    /// let status = operation
+    ///     .wait_for_completion(Duration::from_secs(5), Duration::from_secs(600))
+    ///     .await?;
+    /// println!("{} completed at {:?}", status.operation_id, status.last_updated_on);
+    /// # Ok(())}
+    /// ```
+    pub async fn execute_command_async(
+        &self,
+        database: impl Into<String>,
+        command: impl Into<String>,
+        options: Option<ClientRequestProperties>,
+    ) -> Result<KustoOperation> {
+        let database = database.into();
+        let response = self
+            .execute_command(database.clone(), command, options)
+            .await?;
+        let operation_id = parse_operation_id(response)?;
+        Ok(KustoOperation::new(self.clone(), database, operation_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::load_v1_response;
+    use std::collections::VecDeque;
+
+    const OPERATION_ID: &str = "4d5c2a1b-8e9f-4a3b-9c7d-6e5f4a3b2c1d";
+
+    fn operation_id() -> Uuid {
+        Uuid::parse_str(OPERATION_ID).unwrap()
+    }
+
+    fn load_status(state: &str) -> OperationStatus {
+        parse_operation_status(
+            load_v1_response(&format!("show_operations_{state}.json")),
+            operation_id(),
+        )
+        .expect("Failed to parse the status")
+    }
+
+    async fn wait_for_states(states: &[&str], timeout: Duration) -> Result<OperationStatus> {
+        let mut statuses: VecDeque<_> = states.iter().map(|state| load_status(state)).collect();
+        wait_for(
+            operation_id(),
+            || {
+                let status = statuses.pop_front().expect("Polled after the last status");
+                async move { Ok(status) }
+            },
+            Duration::ZERO,
+            timeout,
+        )
+        .await
+    }
+
+    #[test]
+    fn parses_the_operation_id() {
+        let id = parse_operation_id(load_v1_response("execute_command_async.json"))
+            .expect("Failed to parse the operation id");
+        assert_eq!(id, operation_id());
+    }
+
+    #[test]
+    fn parses_operation_states() {
+        let status = load_status("in_progress");
+        assert_eq!(status.operation, "TableSetOrAppend");
+        assert_eq!(status.state, OperationState::InProgress);
+        assert_eq!(status.status, "");
+        assert!(!status.state.is_finished());
+        assert_eq!(status.database.as_deref(), Some("mydb"));
+
+        assert_eq!(load_status("completed").state, OperationState::Completed);
+
+        let failed = load_status("failed");
+        assert_eq!(failed.state, OperationState::Failed);
+        assert_eq!(
+            failed.status,
+            "Semantic error: 'MyTable' could not be resolved"
+        );
+        assert!(!failed.should_retry);
+
+        let throttled = load_status("throttled");
+        assert_eq!(throttled.state, OperationState::Throttled);
+        assert!(throttled.should_retry);
+
+        assert_eq!(
+            OperationState::from("Scheduled".to_string()),
+            OperationState::InProgress
+        );
+        assert_eq!(
+            OperationState::from("Abandoned".to_string()),
+            OperationState::Other("Abandoned".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn waits_until_the_operation_completes() {
+        let status = wait_for_states(
+            &["in_progress", "in_progress", "completed"],
+            Duration::from_secs(60),
+        )
+        .await
+        .expect("Expected the operation to complete");
+        assert_eq!(status.state, OperationState::Completed);
+    }
+
+    #[tokio::test]
+    async fn failed_and_throttled_operations_are_errors() {
+        let error = wait_for_states(&["in_progress", "failed"], Duration::from_secs(60))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            Error::OperationFailed {
+                state: OperationState::Failed,
+                should_retry: false,
+                ..
+            }
+        ));
+
+        let error = wait_for_states(&["throttled"], Duration::from_secs(60))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            Error::OperationFailed {
+                state: OperationState::Throttled,
+                should_retry: true,
+                ..
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn operations_still_running_after_the_timeout_are_errors() {
+        let error = wait_for_states(&["in_progress"], Duration::ZERO)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            Error::OperationTimeout { operation_id: id, timeout } if id == operation_id() && timeout == Duration::ZERO
+        ));
+    }
+}
@@ -1,39 +1,124 @@
 use crate::cloud_info::CloudInfo;
+use crate::cloud_info_disk_cache::CloudInfoDiskCache;
+use crate::instrumentation::{record_error, span, Instrument};
 use crate::prelude::ConnectionStringAuth;
+use crate::trusted_endpoints::is_trusted_endpoint;
+use azure_core::error::{Error as CoreError, ErrorKind};
 use azure_core::headers::AUTHORIZATION;
 use azure_core::{
-    auth::TokenCredential, ClientOptions, Context, Pipeline, Policy, PolicyResult, Request,
+    auth::TokenCredential, ClientOptions, Context, Pipeline, Policy, PolicyResult, Request, Url,
 };
 use futures::lock::Mutex;
+use hashbrown::HashMap;
 use std::fmt::{Debug, Formatter};
 use std::sync::Arc;
 
 pub struct AuthorizationPolicy {
     auth: ConnectionStringAuth,
+    authority_id: Option<String>,
+    /// The login endpoint of the cloud given in the options, which credentials log in to unless the authority has its own host.
+    authority_host: Option<String>,
     raw_resource: String,
-    credential: Mutex<Option<(Arc<dyn TokenCredential>, String)>>,
+    /// The origins of the endpoints that are the cluster of the connection string under another name, such as a proxy.
+    aliases: Vec<String>,
+    credential: Mutex<Option<Arc<dyn TokenCredential>>>,
+    /// The cloud info of the endpoints requests are sent to, which holds the resource to request tokens for.
+    cloud_infos: Mutex<HashMap<String, CloudInfo>>,
+    cloud_info_disk_cache: Option<CloudInfoDiskCache>,
+    /// The pipeline that fetches cloud infos, shared by all the endpoints.
+    cloud_info_pipeline: Pipeline,
+    /// Whether tokens may be sent to endpoints that are not [trusted](is_trusted_endpoint).
+    allow_untrusted_endpoints: bool,
 }
 
 impl Debug for AuthorizationPolicy {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("AuthorizationPolicy")
             .field("auth", &self.auth)
+            .field("authority_id", &self.authority_id)
             .field("raw_resource", &self.raw_resource)
             .finish()
     }
 }
 
 impl AuthorizationPolicy {
-    pub(crate) fn new(auth: ConnectionStringAuth, raw_resource: String) -> Self {
+    pub(crate) fn new(
+        auth: ConnectionStringAuth,
+        authority_id: Option<String>,
+        raw_resource: String,
+        cloud_info_disk_cache: Option<CloudInfoDiskCache>,
+        cloud_info: Option<CloudInfo>,
+        allow_untrusted_endpoints: bool,
+    ) -> Self {
+        // A cloud info given in the options is used for the cluster of the connection string, rather than fetching it
+        let authority_host = cloud_info
+            .as_ref()
+            .map(|cloud_info| cloud_info.login_endpoint.to_string());
+        let mut cloud_infos = HashMap::new();
+        if let Some(cloud_info) = cloud_info {
+            cloud_infos.insert(raw_resource.clone(), cloud_info);
+        }
+
         Self {
             auth,
+            authority_id,
+            authority_host,
             raw_resource,
+            aliases: Vec::new(),
             credential: Mutex::new(None),
+            cloud_infos: Mutex::new(cloud_infos),
+            cloud_info_disk_cache,
+            cloud_info_pipeline: Pipeline::new(
+                option_env!("CARGO_PKG_NAME"),
+                option_env!("CARGO_PKG_VERSION"),
+                ClientOptions::default(),
+                Vec::new(),
+                Vec::new(),
+            ),
+            allow_untrusted_endpoints,
         }
     }
+
+    /// Treats the requests sent to the given endpoints as sent to the cluster of the connection string,
+    /// so that they are authorized with its resource rather than with that of the endpoints.
+    pub(crate) fn with_aliases<'a>(mut self, aliases: impl IntoIterator<Item = &'a Url>) -> Self {
+        self.aliases = aliases
+            .into_iter()
+            .map(|alias| alias.origin().ascii_serialization())
+            .collect();
+        self
+    }
+
+    /// Resolves the cloud info of the endpoint the request is sent to, which holds the resource to request a token for.
+    /// This is the cluster of the connection string, unless the request was redirected to another cluster.
+    async fn cloud_info_for(&self, url: &Url) -> CloudInfo {
+        let origin = url.origin().ascii_serialization();
+        let endpoint = match Url::parse(&self.raw_resource) {
+            Ok(raw_resource) if raw_resource.origin() == url.origin() => self.raw_resource.clone(),
+            _ if self.aliases.contains(&origin) => self.raw_resource.clone(),
+            _ => origin,
+        };
+
+        let mut cloud_infos = self.cloud_infos.lock().await;
+        if let Some(cloud_info) = cloud_infos.get(&endpoint) {
+            return cloud_info.clone();
+        }
+
+        let cloud_info = CloudInfo::get_with_disk_cache(
+            &self.cloud_info_pipeline,
+            &endpoint,
+            self.cloud_info_disk_cache.as_ref(),
+        )
+        .await
+        .unwrap_or_default();
+
+        cloud_infos.insert(endpoint, cloud_info.clone());
+        cloud_info
+    }
 }
 
-#[async_trait::async_trait]
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
 impl Policy for AuthorizationPolicy {
     async fn send(
         &self,
@@ -46,39 +131,347 @@ impl Policy for AuthorizationPolicy {
             "Authorization policies cannot be the last policy of a pipeline"
         );
 
-        let (cred, resource) = {
-            let mut lock = self.credential.lock().await;
-            if let Some((cred, resource)) = lock.clone() {
-                (cred, resource)
-            } else {
-                let cloud_info = CloudInfo::get(
-                    &Pipeline::new(
-                        option_env!("CARGO_PKG_NAME"),
-                        option_env!("CARGO_PKG_VERSION"),
-                        ClientOptions::default(),
-                        Vec::new(),
-                        Vec::new(),
+        if matches!(self.auth, ConnectionStringAuth::None) {
+            return next[0].send(ctx, request, &next[1..]).await;
+        }
+
+        let cred = self
+            .credential
+            .lock()
+            .await
+            .get_or_insert_with(|| {
+                self.auth
+                    .clone()
+                    .into_credential(self.authority_id.clone(), self.authority_host.as_deref())
+            })
+            .clone();
+        let cloud_info = self.cloud_info_for(request.url()).await;
+        if !self.allow_untrusted_endpoints && !is_trusted_endpoint(request.url(), Some(&cloud_info))
+        {
+            return Err(CoreError::with_message(ErrorKind::Other, || {
+                format!(
+                    "Refusing to send a token to {}, which is not a known Kusto endpoint. Allow untrusted endpoints in the client options to connect to it",
+                    request.url().origin().ascii_serialization()
+                )
+            }));
+        }
+
+        let scope = format!("{}/.default", cloud_info.get_resource_uri());
+
+        // Only the scope is recorded - never the token.
+        let span = span!(
+            "kusto.authorize",
+            scope = %scope,
+            error = tracing::field::Empty,
+            otel.status_code = tracing::field::Empty,
+        );
+        let token = cred
+            .get_token(&[&scope])
+            .instrument(span.clone())
+            .await
+            .map_err(|e| {
+                record_error(&span, &e);
+                CoreError::full(
+                    ErrorKind::Credential,
+                    e,
+                    format!(
+                        "Failed to get a token for {scope} with {} authentication",
+                        self.auth.kind()
                     ),
-                    &self.raw_resource,
                 )
-                .await
-                .unwrap_or_default();
+            })?;
 
-                *lock = Some((
-                    self.auth.clone().into_credential(),
-                    cloud_info.get_resource_uri().to_string(),
-                ));
+        request.insert_header(AUTHORIZATION, format!("Bearer {}", token.token.secret()));
 
-                lock.clone().unwrap()
-            }
-        };
+        next[0].send(ctx, request, &next[1..]).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::ConnectionString;
+    use azure_core::auth::AccessToken;
+    use azure_core::headers::Headers;
+    use azure_core::{BytesStream, Method, Response, StatusCode};
+
+    /// Fails requests that carry an Authorization header.
+    #[derive(Debug)]
+    struct RejectAuthorization;
 
-        let scope = format!("{}/.default", resource);
+    #[async_trait::async_trait]
+    impl Policy for RejectAuthorization {
+        async fn send(
+            &self,
+            _ctx: &Context,
+            request: &mut Request,
+            _next: &[Arc<dyn Policy>],
+        ) -> PolicyResult {
+            assert!(request.headers().get_optional_str(&AUTHORIZATION).is_none());
+            Ok(Response::new(
+                StatusCode::Ok,
+                Headers::new(),
+                Box::pin(BytesStream::new(bytes::Bytes::new())),
+            ))
+        }
+    }
 
-        let token = cred.get_token(&[&scope]).await?;
+    #[tokio::test]
+    async fn requests_without_authentication_skip_the_token() {
+        let policy = AuthorizationPolicy::new(
+            ConnectionStringAuth::None,
+            None,
+            "http://localhost:8080".to_string(),
+            None,
+            None,
+            false,
+        );
+        let mut request = Request::new(
+            Url::parse("http://localhost:8080/v1/rest/mgmt").unwrap(),
+            Method::Post,
+        );
+        let next: Vec<Arc<dyn Policy>> = vec![Arc::new(RejectAuthorization)];
 
-        request.insert_header(AUTHORIZATION, &format!("Bearer {}", token.token.secret()));
+        let response = policy
+            .send(&Context::new(), &mut request, &next)
+            .await
+            .expect("Failed to send request");
 
-        next[0].send(ctx, request, &next[1..]).await
+        assert_eq!(response.status(), StatusCode::Ok);
+        assert!(policy.credential.lock().await.is_none());
+    }
+
+    #[derive(Debug)]
+    struct FailingCredential;
+
+    #[async_trait::async_trait]
+    impl TokenCredential for FailingCredential {
+        async fn get_token(&self, _scopes: &[&str]) -> azure_core::Result<AccessToken> {
+            Err(CoreError::message(ErrorKind::Credential, "not logged in"))
+        }
+
+        async fn clear_cache(&self) -> azure_core::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn token_errors_name_the_credential() {
+        let endpoint = "https://labeled.kusto.windows.net";
+        CloudInfo::add_to_cache(endpoint, CloudInfo::default()).await;
+        let connection_string =
+            ConnectionString::with_chained_auth(endpoint, vec![Arc::new(FailingCredential)]);
+        let policy = AuthorizationPolicy::new(
+            connection_string.auth,
+            None,
+            endpoint.to_string(),
+            None,
+            None,
+            false,
+        );
+        let mut request = Request::new(
+            Url::parse(&format!("{endpoint}/v1/rest/mgmt")).unwrap(),
+            Method::Post,
+        );
+        let next: Vec<Arc<dyn Policy>> = vec![Arc::new(RejectAuthorization)];
+
+        let error = policy
+            .send(&Context::new(), &mut request, &next)
+            .await
+            .expect_err("Expected the credential to fail");
+
+        assert_eq!(error.kind(), &ErrorKind::Credential);
+        assert!(error.to_string().contains("with Chained authentication"));
+        assert!(format!("{:?}", policy).contains("TokenCredential(Chained)"));
+    }
+
+    #[tokio::test]
+    async fn tokens_are_not_sent_to_untrusted_endpoints() {
+        let endpoint = "https://cluster.attacker.example.com";
+        CloudInfo::add_to_cache(endpoint, CloudInfo::default()).await;
+        let connection_string =
+            ConnectionString::with_chained_auth(endpoint, vec![Arc::new(FailingCredential)]);
+        let policy = AuthorizationPolicy::new(
+            connection_string.auth,
+            None,
+            endpoint.to_string(),
+            None,
+            None,
+            false,
+        );
+        let mut request = Request::new(
+            Url::parse(&format!("{endpoint}/v1/rest/mgmt")).unwrap(),
+            Method::Post,
+        );
+        let next: Vec<Arc<dyn Policy>> = vec![Arc::new(RejectAuthorization)];
+
+        let error = policy
+            .send(&Context::new(), &mut request, &next)
+            .await
+            .expect_err("Expected the endpoint to be refused");
+
+        // The endpoint is refused before the credential is asked for a token
+        assert_eq!(error.kind(), &ErrorKind::Other);
+        assert!(error.to_string().contains("not a known Kusto endpoint"));
+    }
+
+    /// Records the Authorization header of the requests it receives.
+    #[derive(Debug, Default)]
+    struct RecordAuthorization(std::sync::Mutex<Vec<String>>);
+
+    #[async_trait::async_trait]
+    impl Policy for RecordAuthorization {
+        async fn send(
+            &self,
+            _ctx: &Context,
+            request: &mut Request,
+            _next: &[Arc<dyn Policy>],
+        ) -> PolicyResult {
+            self.0.lock().unwrap().push(
+                request
+                    .headers()
+                    .get_str(&AUTHORIZATION)
+                    .expect("Expected an Authorization header")
+                    .to_string(),
+            );
+            Ok(Response::new(
+                StatusCode::Ok,
+                Headers::new(),
+                Box::pin(BytesStream::new(bytes::Bytes::new())),
+            ))
+        }
+    }
+
+    /// Sends a request through a policy with the given cloud info, and returns the scope it requested a token for.
+    async fn scope_for(endpoint: &str, cloud_info: CloudInfo) -> String {
+        // The token is the scope it was requested for
+        let connection_string = ConnectionString::with_token_callback_auth(
+            endpoint,
+            Arc::new(|scopes: &[&str]| scopes.join(" ")),
+            None,
+        );
+        let policy = AuthorizationPolicy::new(
+            connection_string.auth,
+            None,
+            endpoint.to_string(),
+            None,
+            Some(cloud_info),
+            false,
+        );
+        let mut request = Request::new(
+            Url::parse(&format!("{endpoint}/v1/rest/mgmt")).unwrap(),
+            Method::Post,
+        );
+        let recorder = Arc::new(RecordAuthorization::default());
+        let next: Vec<Arc<dyn Policy>> = vec![recorder.clone()];
+
+        policy
+            .send(&Context::new(), &mut request, &next)
+            .await
+            .expect("Failed to send request");
+
+        let headers = recorder.0.lock().unwrap().clone();
+        assert_eq!(headers.len(), 1);
+        headers[0]
+            .strip_prefix("Bearer ")
+            .expect("Expected a bearer token")
+            .to_string()
+    }
+
+    #[tokio::test]
+    async fn requests_to_aliases_are_authorized_for_the_cluster() {
+        let endpoint = "https://cluster.region.kusto.usgovcloudapi.net";
+        let proxy = Url::parse("https://kusto-proxy.contoso.com/query").unwrap();
+        let connection_string = ConnectionString::with_token_callback_auth(
+            endpoint,
+            Arc::new(|scopes: &[&str]| scopes.join(" ")),
+            None,
+        );
+        // The cloud info of the proxy is not in the global cache, so fetching it would fall back to the public cloud
+        let policy = AuthorizationPolicy::new(
+            connection_string.auth,
+            None,
+            endpoint.to_string(),
+            None,
+            Some(CloudInfo::azure_us_government()),
+            true,
+        )
+        .with_aliases([&proxy]);
+        let recorder = Arc::new(RecordAuthorization::default());
+        let next: Vec<Arc<dyn Policy>> = vec![recorder.clone()];
+
+        let mut request = Request::new(
+            Url::parse("https://kusto-proxy.contoso.com/v2/rest/query").unwrap(),
+            Method::Post,
+        );
+        policy
+            .send(&Context::new(), &mut request, &next)
+            .await
+            .expect("Failed to send request");
+
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec!["Bearer https://kusto.kusto.usgovcloudapi.net/.default".to_string()]
+        );
+    }
+
+    #[test]
+    fn credentials_log_in_to_the_configured_cloud() {
+        let policy = |cloud_info| {
+            AuthorizationPolicy::new(
+                ConnectionStringAuth::Default,
+                None,
+                "https://cluster.kusto.chinacloudapi.cn".to_string(),
+                None,
+                cloud_info,
+                false,
+            )
+        };
+
+        assert_eq!(
+            policy(Some(CloudInfo::azure_china()))
+                .authority_host
+                .as_deref(),
+            Some("https://login.chinacloudapi.cn")
+        );
+        assert_eq!(
+            policy(Some(CloudInfo::azure_us_government()))
+                .authority_host
+                .as_deref(),
+            Some("https://login.microsoftonline.us")
+        );
+        assert_eq!(policy(None).authority_host, None);
+    }
+
+    #[tokio::test]
+    async fn scopes_derive_from_the_configured_cloud() {
+        // The cloud infos are not in the global cache, so any attempt to fetch them would fall back to the public cloud
+        assert_eq!(
+            scope_for(
+                "https://cluster.region.kusto.usgovcloudapi.net",
+                CloudInfo::azure_us_government()
+            )
+            .await,
+            "https://kusto.kusto.usgovcloudapi.net/.default"
+        );
+        assert_eq!(
+            scope_for(
+                "https://cluster.region.kusto.chinacloudapi.cn",
+                CloudInfo::azure_china()
+            )
+            .await,
+            "https://kusto.kusto.chinacloudapi.cn/.default"
+        );
+        assert_eq!(
+            scope_for(
+                "https://configured.region.kusto.windows.net",
+                CloudInfo {
+                    login_mfa_required: true,
+                    ..CloudInfo::azure_public()
+                }
+            )
+            .await,
+            "https://kusto.kustomfa.windows.net/.default"
+        );
     }
 }
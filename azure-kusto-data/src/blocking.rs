@@ -0,0 +1,196 @@
+//! A synchronous facade over [KustoClient], for applications that don't run an async runtime.
+//!
+//! [BlockingKustoClient] owns a single threaded tokio runtime, and blocks the calling thread on it until each request completes.
+//! This comes with a few tradeoffs compared to the async client:
+//! - Each call blocks the calling thread until the request is done, so requests from a single thread run one after the other.
+//!   Clones of the client share the same runtime, so they don't create additional threads.
+//! - Results are always fully buffered in memory, there is no streaming equivalent.
+//! - The blocking client must not be used from within an async runtime, as blocking inside of one would panic or deadlock.
+//!   Calls made from such a context fail with [Error::UnsupportedOperation] - use [KustoClient] directly instead.
+
+use std::future::{Future, IntoFuture};
+use std::sync::Arc;
+
+use serde::de::DeserializeOwned;
+use tokio::runtime::{Builder, Handle, Runtime};
+
+use crate::client::{KustoClient, KustoClientOptions};
+use crate::connection_string::ConnectionString;
+use crate::error::{Error, Result};
+use crate::operations::query::{KustoResponseDataSetV1, KustoResponseDataSetV2};
+use crate::request_options::ClientRequestProperties;
+
+/// A synchronous client for Kusto, wrapping a [KustoClient] and an internal runtime to drive it.
+///
+/// # Example
+/// ```no_run
+/// use azure_kusto_data::blocking::BlockingKustoClient;
+/// use azure_kusto_data::prelude::*;
+///
+/// # fn main() -> Result<(), Error> {
+/// let client = BlockingKustoClient::new(
+///    ConnectionString::with_default_auth("https://mycluster.region.kusto.windows.net/"),
+///    KustoClientOptions::default())?;
+///
+/// let response = client.execute_query("some_database", "MyTable | take 10", None)?;
+///
+/// for table in response.into_primary_results() {
+///     let table = table?;
+///     println!("{}", table.table_name);
+/// }
+/// # Ok(())}
+/// ```
+#[derive(Clone, Debug)]
+pub struct BlockingKustoClient {
+    client: KustoClient,
+    runtime: Arc<OwnedRuntime>,
+}
+
+/// Shuts the runtime down without blocking when dropped, as dropping a [Runtime] from within an async context panics.
+#[derive(Debug)]
+struct OwnedRuntime(Option<Runtime>);
+
+impl Drop for OwnedRuntime {
+    fn drop(&mut self) {
+        if let Some(runtime) = self.0.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+impl BlockingKustoClient {
+    /// Create a new blocking client from a connection string and options.
+    pub fn new(connection_string: ConnectionString, options: KustoClientOptions) -> Result<Self> {
+        Self::from_client(KustoClient::new(connection_string, options)?)
+    }
+
+    /// Wrap an existing [KustoClient] in a blocking client.
+    pub fn from_client(client: KustoClient) -> Result<Self> {
+        let runtime = Builder::new_current_thread().enable_all().build()?;
+        Ok(Self {
+            client,
+            runtime: Arc::new(OwnedRuntime(Some(runtime))),
+        })
+    }
+
+    /// The async client used by this client.
+    #[must_use]
+    pub fn client(&self) -> &KustoClient {
+        &self.client
+    }
+
+    /// Execute a KQL query, blocking until the whole response was received.
+    /// See [KustoClient::execute_query] for details.
+    pub fn execute_query(
+        &self,
+        database: impl Into<String>,
+        query: impl Into<String>,
+        options: Option<ClientRequestProperties>,
+    ) -> Result<KustoResponseDataSetV2> {
+        self.block_on(
+            self.client
+                .execute_query(database, query, options)
+                .into_future(),
+        )
+    }
+
+    /// Execute a KQL query into an array of structs, blocking until the whole response was received.
+    /// See [KustoClient::execute_query_to_struct] for details.
+    pub fn execute_query_to_struct<T: DeserializeOwned>(
+        &self,
+        database: impl Into<String>,
+        query: impl Into<String>,
+        client_request_properties: Option<ClientRequestProperties>,
+    ) -> Result<Vec<T>> {
+        self.block_on(self.client.execute_query_to_struct(
+            database,
+            query,
+            client_request_properties,
+        ))
+    }
+
+    /// Execute a KQL query with two tabular statements into two arrays of structs, blocking until the whole response was received.
+    /// See [KustoClient::execute_query_to_structs_multi] for details.
+    pub fn execute_query_to_structs_multi<T1: DeserializeOwned, T2: DeserializeOwned>(
+        &self,
+        database: impl Into<String>,
+        query: impl Into<String>,
+        client_request_properties: Option<ClientRequestProperties>,
+    ) -> Result<(Vec<T1>, Vec<T2>)> {
+        self.block_on(self.client.execute_query_to_structs_multi(
+            database,
+            query,
+            client_request_properties,
+        ))
+    }
+
+    /// Execute a management command, blocking until the whole response was received.
+    /// See [KustoClient::execute_command] for details.
+    pub fn execute_command(
+        &self,
+        database: impl Into<String>,
+        query: impl Into<String>,
+        options: Option<ClientRequestProperties>,
+    ) -> Result<KustoResponseDataSetV1> {
+        self.block_on(
+            self.client
+                .execute_command(database, query, options)
+                .into_future(),
+        )
+    }
+
+    fn block_on<T>(&self, future: impl Future<Output = Result<T>>) -> Result<T> {
+        if Handle::try_current().is_ok() {
+            return Err(Error::UnsupportedOperation(
+                "the blocking client cannot be used from within an async runtime, use KustoClient instead".into(),
+            ));
+        }
+        self.runtime
+            .0
+            .as_ref()
+            .expect("Runtime is only taken on drop - please report this issue to the Kusto team")
+            .block_on(future)
+    }
+}
+
+impl TryFrom<ConnectionString> for BlockingKustoClient {
+    type Error = Error;
+
+    fn try_from(value: ConnectionString) -> Result<Self> {
+        Self::new(value, KustoClientOptions::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client() -> BlockingKustoClient {
+        BlockingKustoClient::new(
+            ConnectionString::with_token_auth("https://mycluster.kusto.windows.net", "token"),
+            KustoClientOptions::default(),
+        )
+        .expect("Failed to create client")
+    }
+
+    #[test]
+    fn blocking_client_runs_outside_of_a_runtime() {
+        let client = client();
+
+        let result = client.block_on(async {
+            tokio::task::yield_now().await;
+            Ok(42)
+        });
+
+        assert_eq!(result.expect("Failed to run future"), 42);
+    }
+
+    #[tokio::test]
+    async fn blocking_client_refuses_to_run_inside_a_runtime() {
+        let client = client();
+
+        let result = client.execute_query("db", "print 1", None);
+
+        assert!(matches!(result, Err(Error::UnsupportedOperation(_))));
+    }
+}
@@ -0,0 +1,448 @@
+//! Fault and latency injection, to test how applications handle a slow or flaky Kusto without touching the cluster.
+//!
+//! A [ChaosPolicy] is added to the pipeline of a [KustoClient](crate::client::KustoClient) through its [ClientOptions](azure_core::ClientOptions):
+//! - as a per-call policy, it runs once for each call, before the retry policy and before the request is authorized -
+//!   so injected faults reach the application without being retried, and don't require a token.
+//! - as a per-retry policy, it runs for every attempt, after the request is authorized - so injected faults are retried by the pipeline.
+//!
+//! ```no_run
+//! use azure_core::ClientOptions;
+//! use azure_kusto_data::chaos::{ChaosPolicy, Fault, Latency};
+//! use azure_kusto_data::prelude::*;
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//!
+//! let chaos = Arc::new(
+//!     ChaosPolicy::new()
+//!         .with_latency(Latency::Jittered { min: Duration::from_millis(50), max: Duration::from_millis(500) })
+//!         .with_faults(0.1, vec![Fault::Throttled, Fault::ConnectionReset]),
+//! );
+//! let client = KustoClient::new(
+//!     ConnectionString::with_default_auth("https://mycluster.region.kusto.windows.net/"),
+//!     KustoClientOptions::from(ClientOptions::default().per_call_policies(vec![chaos.clone()])),
+//! ).unwrap();
+//!
+//! // ... exercise the application, then check how it handled the faults in chaos.events()
+//! ```
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use azure_core::error::{Error as CoreError, ErrorKind};
+use azure_core::headers::Headers;
+use azure_core::{BytesStream, Context, Policy, PolicyResult, Request, Response, StatusCode};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+/// Latency added to the requests of a [ChaosPolicy].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Latency {
+    /// Requests are not delayed.
+    #[default]
+    None,
+    /// Every request is delayed by the same duration.
+    Fixed(Duration),
+    /// Every request is delayed by a duration picked uniformly between `min` and `max`.
+    Jittered {
+        /// The shortest delay.
+        min: Duration,
+        /// The longest delay.
+        max: Duration,
+    },
+}
+
+/// A fault injected by a [ChaosPolicy], in place of sending the request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// The cluster throttles the request, responding with `429 Too Many Requests`.
+    Throttled,
+    /// The cluster is unavailable, responding with `503 Service Unavailable`.
+    ServiceUnavailable,
+    /// The connection is reset before a response is received.
+    ConnectionReset,
+}
+
+/// The requests that a [ChaosPolicy] applies to. Other requests are sent untouched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChaosScope {
+    /// Both queries and management commands.
+    #[default]
+    All,
+    /// Only queries, including progressive and streaming ones.
+    Queries,
+    /// Only management commands.
+    Management,
+}
+
+impl ChaosScope {
+    fn applies_to(self, request: &Request) -> bool {
+        let path = request.url().path();
+        match self {
+            ChaosScope::All => true,
+            ChaosScope::Queries => path.contains("/rest/query"),
+            ChaosScope::Management => path.contains("/rest/mgmt"),
+        }
+    }
+}
+
+/// A request that a [ChaosPolicy] delayed, or injected a fault into.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChaosEvent {
+    /// The path of the request, e.g. `/v2/rest/query`.
+    pub path: String,
+    /// The latency added to the request.
+    pub latency: Duration,
+    /// The fault injected, when the request was not sent.
+    pub fault: Option<Fault>,
+}
+
+/// A [Policy] that delays requests and injects faults in place of sending them, according to its configuration.
+/// Every request it applies to is recorded as a [ChaosEvent].
+#[derive(Debug)]
+pub struct ChaosPolicy {
+    latency: Latency,
+    fault_rate: f64,
+    faults: Vec<Fault>,
+    scope: ChaosScope,
+    rng: Mutex<StdRng>,
+    events: Mutex<Vec<ChaosEvent>>,
+}
+
+impl Default for ChaosPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChaosPolicy {
+    /// Creates a policy that sends all requests untouched, until configured otherwise.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            latency: Latency::None,
+            fault_rate: 0.0,
+            faults: Vec::new(),
+            scope: ChaosScope::All,
+            rng: Mutex::new(StdRng::from_entropy()),
+            events: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Sets the latency added to the requests.
+    #[must_use]
+    pub fn with_latency(mut self, latency: Latency) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Injects a fault in place of sending `rate` of the requests, between 0 and 1, picking uniformly from `faults`.
+    #[must_use]
+    pub fn with_faults(mut self, rate: f64, faults: Vec<Fault>) -> Self {
+        self.fault_rate = rate.clamp(0.0, 1.0);
+        self.faults = faults;
+        self
+    }
+
+    /// Restricts the policy to some of the requests.
+    #[must_use]
+    pub fn with_scope(mut self, scope: ChaosScope) -> Self {
+        self.scope = scope;
+        self
+    }
+
+    /// Seeds the random choices of the policy, so that a test injects the same faults on every run.
+    #[must_use]
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = Mutex::new(StdRng::seed_from_u64(seed));
+        self
+    }
+
+    /// The requests that the policy applied to so far, in order.
+    pub fn events(&self) -> Vec<ChaosEvent> {
+        self.events.lock().expect("Poisoned chaos events").clone()
+    }
+
+    /// The faults that the policy injected so far, in order.
+    pub fn injected_faults(&self) -> Vec<Fault> {
+        self.events().into_iter().filter_map(|e| e.fault).collect()
+    }
+
+    /// Forgets the recorded events, e.g. between the phases of a test.
+    pub fn clear_events(&self) {
+        self.events.lock().expect("Poisoned chaos events").clear();
+    }
+
+    /// Picks the latency and fault for a request.
+    fn sample(&self) -> (Duration, Option<Fault>) {
+        let mut rng = self.rng.lock().expect("Poisoned chaos rng");
+        let latency = match self.latency {
+            Latency::None => Duration::ZERO,
+            Latency::Fixed(latency) => latency,
+            Latency::Jittered { min, max } if min < max => rng.gen_range(min..=max),
+            Latency::Jittered { min, .. } => min,
+        };
+        let fault = if self.fault_rate > 0.0 && rng.gen_bool(self.fault_rate) {
+            self.faults.choose(&mut *rng).copied()
+        } else {
+            None
+        };
+        (latency, fault)
+    }
+}
+
+fn fault_response(status: StatusCode, code: &str) -> PolicyResult {
+    let body = format!(
+        r#"{{"error":{{"code":"{code}","message":"Injected by ChaosPolicy","@permanent":false}}}}"#
+    );
+    Ok(Response::new(
+        status,
+        Headers::new(),
+        Box::pin(BytesStream::new(body)),
+    ))
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+impl Policy for ChaosPolicy {
+    async fn send(
+        &self,
+        ctx: &Context,
+        request: &mut Request,
+        next: &[Arc<dyn Policy>],
+    ) -> PolicyResult {
+        if !self.scope.applies_to(request) {
+            return next[0].send(ctx, request, &next[1..]).await;
+        }
+
+        let (latency, fault) = self.sample();
+        self.events
+            .lock()
+            .expect("Poisoned chaos events")
+            .push(ChaosEvent {
+                path: request.url().path().to_string(),
+                latency,
+                fault,
+            });
+
+        if !latency.is_zero() {
+            azure_core::sleep::sleep(latency).await;
+        }
+
+        match fault {
+            None => next[0].send(ctx, request, &next[1..]).await,
+            Some(Fault::Throttled) => {
+                fault_response(StatusCode::TooManyRequests, "TooManyRequests")
+            }
+            Some(Fault::ServiceUnavailable) => {
+                fault_response(StatusCode::ServiceUnavailable, "ServiceUnavailable")
+            }
+            Some(Fault::ConnectionReset) => Err(CoreError::full(
+                ErrorKind::Io,
+                std::io::Error::from(std::io::ErrorKind::ConnectionReset),
+                "Connection reset, injected by ChaosPolicy",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cloud_info::CloudInfo;
+    use crate::prelude::{ConnectionString, KustoClient, KustoClientOptions};
+    use azure_core::auth::{AccessToken, TokenCredential};
+    use azure_core::{ClientOptions, Method, RetryOptions, TransportOptions, Url};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const CALLS: usize = 2000;
+
+    /// Answers every request with an empty management response.
+    #[derive(Debug)]
+    struct Ok200;
+
+    #[async_trait::async_trait]
+    impl Policy for Ok200 {
+        async fn send(
+            &self,
+            _ctx: &Context,
+            _request: &mut Request,
+            _next: &[Arc<dyn Policy>],
+        ) -> PolicyResult {
+            Ok(Response::new(
+                StatusCode::Ok,
+                Headers::new(),
+                Box::pin(BytesStream::new(r#"{"Tables":[]}"#)),
+            ))
+        }
+    }
+
+    async fn send(policy: &ChaosPolicy, path: &str) -> PolicyResult {
+        let mut request = Request::new(
+            Url::parse(&format!("https://mycluster.kusto.windows.net{path}")).unwrap(),
+            Method::Post,
+        );
+        let next: Vec<Arc<dyn Policy>> = vec![Arc::new(Ok200)];
+        policy.send(&Context::new(), &mut request, &next).await
+    }
+
+    #[tokio::test]
+    async fn fault_rate_is_honored() {
+        let policy = ChaosPolicy::new()
+            .with_faults(
+                0.2,
+                vec![
+                    Fault::Throttled,
+                    Fault::ServiceUnavailable,
+                    Fault::ConnectionReset,
+                ],
+            )
+            .with_seed(7);
+
+        let mut throttled = 0;
+        let mut unavailable = 0;
+        let mut reset = 0;
+        for _ in 0..CALLS {
+            match send(&policy, "/v1/rest/mgmt").await {
+                Ok(response) if response.status() == StatusCode::TooManyRequests => throttled += 1,
+                Ok(response) if response.status() == StatusCode::ServiceUnavailable => {
+                    unavailable += 1
+                }
+                Ok(response) => assert_eq!(response.status(), StatusCode::Ok),
+                Err(e) => {
+                    assert_eq!(e.kind(), &ErrorKind::Io);
+                    reset += 1
+                }
+            }
+        }
+
+        let faults = throttled + unavailable + reset;
+        let rate = faults as f64 / CALLS as f64;
+        assert!((0.17..0.23).contains(&rate), "fault rate was {rate}");
+        for count in [throttled, unavailable, reset] {
+            let share = count as f64 / faults as f64;
+            assert!((0.25..0.42).contains(&share), "fault share was {share}");
+        }
+        assert_eq!(policy.injected_faults().len(), faults);
+        assert_eq!(policy.events().len(), CALLS);
+    }
+
+    #[test]
+    fn jittered_latency_is_within_bounds() {
+        let min = Duration::from_millis(10);
+        let max = Duration::from_millis(30);
+        let policy = ChaosPolicy::new()
+            .with_latency(Latency::Jittered { min, max })
+            .with_seed(7);
+
+        let latencies: Vec<Duration> = (0..CALLS).map(|_| policy.sample().0).collect();
+
+        assert!(latencies.iter().all(|l| (min..=max).contains(l)));
+        let mean = latencies.iter().sum::<Duration>() / CALLS as u32;
+        assert!(
+            (Duration::from_millis(19)..Duration::from_millis(21)).contains(&mean),
+            "mean latency was {mean:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn fixed_latency_delays_requests() {
+        let latency = Duration::from_millis(20);
+        let policy = ChaosPolicy::new().with_latency(Latency::Fixed(latency));
+
+        let start = std::time::Instant::now();
+        send(&policy, "/v2/rest/query").await.unwrap();
+
+        assert!(start.elapsed() >= latency);
+        assert_eq!(
+            policy.events(),
+            vec![ChaosEvent {
+                path: "/v2/rest/query".to_string(),
+                latency,
+                fault: None,
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn scope_limits_the_requests() {
+        let policy = ChaosPolicy::new()
+            .with_faults(1.0, vec![Fault::ServiceUnavailable])
+            .with_scope(ChaosScope::Queries);
+
+        let command = send(&policy, "/v1/rest/mgmt").await.unwrap();
+        let query = send(&policy, "/v2/rest/query").await.unwrap();
+
+        assert_eq!(command.status(), StatusCode::Ok);
+        assert_eq!(query.status(), StatusCode::ServiceUnavailable);
+        assert_eq!(policy.injected_faults(), vec![Fault::ServiceUnavailable]);
+
+        policy.clear_events();
+        assert!(policy.events().is_empty());
+    }
+
+    #[derive(Debug, Default)]
+    struct CountingCredential {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl TokenCredential for CountingCredential {
+        async fn get_token(&self, _scopes: &[&str]) -> azure_core::Result<AccessToken> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(AccessToken {
+                token: "token".to_string().into(),
+                expires_on: time::OffsetDateTime::now_utc() + Duration::from_secs(3600),
+            })
+        }
+
+        async fn clear_cache(&self) -> azure_core::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Creates a client whose pipeline has the chaos policy at the given position, and returns how many tokens it requested for a failed call.
+    async fn tokens_requested(endpoint: &str, per_call: bool) -> usize {
+        CloudInfo::add_to_cache(endpoint, CloudInfo::default()).await;
+        let credential = Arc::new(CountingCredential::default());
+        let chaos: Arc<dyn Policy> =
+            Arc::new(ChaosPolicy::new().with_faults(1.0, vec![Fault::ServiceUnavailable]));
+
+        let options = ClientOptions::default()
+            .retry(RetryOptions::none())
+            .transport(TransportOptions::new_custom_policy(Arc::new(Ok200)));
+        let options = if per_call {
+            options.per_call_policies(vec![chaos])
+        } else {
+            options.per_retry_policies(vec![chaos])
+        };
+        let client = KustoClient::new(
+            ConnectionString::with_token_credential(endpoint, credential.clone()),
+            KustoClientOptions::from(options),
+        )
+        .unwrap();
+
+        client
+            .execute_command("db", ".show version", None)
+            .await
+            .expect_err("Expected the injected fault");
+
+        credential.calls.load(Ordering::SeqCst)
+    }
+
+    #[tokio::test]
+    async fn per_call_faults_are_injected_before_authorization() {
+        assert_eq!(
+            tokens_requested("https://chaospercall.kusto.windows.net", true).await,
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn per_retry_faults_are_injected_after_authorization() {
+        assert_eq!(
+            tokens_requested("https://chaosperretry.kusto.windows.net", false).await,
+            1
+        );
+    }
+}
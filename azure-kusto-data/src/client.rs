@@ -1,30 +1,118 @@
 //! This module contains the client for the Azure Kusto Data service.
 
 use crate::authorization_policy::AuthorizationPolicy;
+use crate::cluster_stopped::{ClusterStoppedCallback, ClusterStoppedHandler};
+use crate::coalescing::QueryCoalescer;
 use crate::connection_string::{ConnectionString, ConnectionStringAuth};
-use crate::error::{Error, Result};
-use crate::operations::query::{QueryRunner, QueryRunnerBuilder, V1QueryRunner, V2QueryRunner};
+use crate::deserialization::{
+    check_positional_columns, deserialize_positional, deserialize_table_v1, deserialize_values,
+    CaseMapping,
+};
+use crate::error::{ConnectionStringError, Error, InvalidArgumentError, Result};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::http_transport::{default_http_client, HttpTransportOptions};
+use crate::lookup::DuplicateKeys;
+use crate::models::DataTable;
+use crate::operations::query::{
+    KustoResponse, KustoResponseDataSetV1, KustoResponseDataSetV2, QueryRunner, QueryRunnerBuilder,
+    V1QueryRunner, V2QueryRunner,
+};
+#[cfg(feature = "tokio")]
+use crate::operations::streaming::{Cancellable, StreamingDataset, StreamingOptions};
+use crate::operations::streaming_ingest::{
+    execute_streaming_ingest, streaming_ingest_url, StreamingIngestSource,
+};
+use crate::redirect_policy::RedirectPolicy;
+use crate::slow_query::{SlowQueryCallback, SlowQueryHook, SlowQueryThresholds};
+use crate::trace_context::{
+    new_client_request_id, TraceContext, TraceContextProvider, TraceContextSource,
+};
+use crate::trusted_endpoints::is_trusted_endpoint;
 
-use azure_core::{ClientOptions, Pipeline};
+#[cfg(feature = "arrow")]
+use crate::arrow::convert_schema_table;
+#[cfg(feature = "arrow")]
+use arrow_array::RecordBatch;
+#[cfg(feature = "arrow")]
+use arrow_schema::Schema;
+#[cfg(not(target_arch = "wasm32"))]
+use azure_core::TransportOptions;
+use azure_core::{ClientOptions, Pipeline, Url};
 
-use crate::client_details::ClientDetails;
+use crate::client_details::{to_header_safe, ClientDetails};
+use crate::cloud_info::CloudInfo;
+use crate::cloud_info_disk_cache::CloudInfoDiskCache;
 use crate::prelude::ClientRequestProperties;
+#[cfg(feature = "arrow")]
+use crate::request_options::Options;
+use crate::request_options::{ParseOptions, PropertiesSerialization, WeakConsistencySession};
 use azure_core::headers::Headers;
 use azure_core::prelude::{Accept, AcceptEncoding, ClientVersion, ContentType};
+use futures::future::{self, Either};
+use futures::{Future, StreamExt};
+use hashbrown::HashMap;
 use serde::de::DeserializeOwned;
 use std::convert::TryFrom;
 use std::fmt::Debug;
+use std::future::IntoFuture;
+use std::hash::Hash;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
 /// Options for specifying how a Kusto client will behave
 #[derive(Clone, Default)]
 pub struct KustoClientOptions {
     options: ClientOptions,
+    allow_insecure_endpoint: bool,
+    allow_untrusted_endpoints: bool,
+    cloud_info_disk_cache: Option<CloudInfoDiskCache>,
+    cloud_info: Option<CloudInfo>,
+    request_coalescing: Option<Duration>,
+    slow_query_hook: Option<SlowQueryHook>,
+    cluster_stopped_callback: Option<ClusterStoppedCallback>,
+    trace_context_provider: Option<TraceContextProvider>,
+    endpoint_overrides: Option<(String, String)>,
+    properties_serialization: PropertiesSerialization,
+    #[cfg(not(target_arch = "wasm32"))]
+    http_transport: Option<HttpTransportOptions>,
+    streaming_table_capacity: Option<usize>,
+    streaming_table_bytes: bool,
+    parse_options: ParseOptions,
+    /// Whether the options were created from [ClientOptions] given by the user, whose transport is then kept as is,
+    /// unless [with_http_transport](Self::with_http_transport) replaces it.
+    #[cfg(not(target_arch = "wasm32"))]
+    custom_client_options: bool,
 }
 
+/// Creates options with the given [ClientOptions], whose transport is kept as is, so that it can be replaced, such as in tests.
+///
+/// The default transport of azure_core follows redirects by itself, so they never reach the client. Redirects to endpoints that are not trusted
+/// are then followed, and [client_max_redirect_count](crate::request_options::Options::client_max_redirect_count) is not applied.
+/// Set [with_http_transport](KustoClientOptions::with_http_transport) to send the requests with a transport that leaves redirects to the client,
+/// as the options created with [KustoClientOptions::new] do, or give a transport that doesn't follow them.
 impl From<ClientOptions> for KustoClientOptions {
     fn from(c: ClientOptions) -> Self {
-        Self { options: c }
+        Self {
+            options: c,
+            allow_insecure_endpoint: false,
+            allow_untrusted_endpoints: false,
+            cloud_info_disk_cache: None,
+            cloud_info: None,
+            request_coalescing: None,
+            slow_query_hook: None,
+            cluster_stopped_callback: None,
+            trace_context_provider: None,
+            endpoint_overrides: None,
+            properties_serialization: PropertiesSerialization::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            http_transport: None,
+            streaming_table_capacity: None,
+            streaming_table_bytes: false,
+            parse_options: ParseOptions::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            custom_client_options: true,
+        }
     }
 }
 
@@ -34,16 +122,316 @@ impl KustoClientOptions {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Allows connecting to endpoints over http, such as the Kusto emulator. Only https endpoints are allowed by default.
+    #[must_use]
+    pub fn with_allow_insecure_endpoint(mut self, allow_insecure_endpoint: bool) -> Self {
+        self.allow_insecure_endpoint = allow_insecure_endpoint;
+        self
+    }
+
+    /// Allows sending tokens to endpoints that are not well-known Kusto endpoints, such as private or custom deployments.
+    ///
+    /// By default, clients that authenticate only connect to the domains of the public and sovereign Kusto clouds, and to the local machine,
+    /// so that a mistyped or malicious data source can't receive the tokens of the caller.
+    /// Clients of a private cloud set with [with_cloud_info](Self::with_cloud_info) connect to the domains of that cloud instead.
+    /// # Example
+    /// ```rust
+    /// use azure_kusto_data::prelude::*;
+    ///
+    /// let public = ConnectionString::with_default_auth("https://mycluster.region.kusto.windows.net/");
+    /// assert!(KustoClient::new(public, KustoClientOptions::new()).is_ok());
+    ///
+    /// // Other hosts are rejected unless they are explicitly allowed.
+    /// let custom = ConnectionString::with_default_auth("https://kusto.contoso.com/");
+    /// assert!(KustoClient::new(custom.clone(), KustoClientOptions::new()).is_err());
+    /// assert!(KustoClient::new(custom, KustoClientOptions::new().with_allow_untrusted_endpoints(true)).is_ok());
+    /// ```
+    #[must_use]
+    pub fn with_allow_untrusted_endpoints(mut self, allow_untrusted_endpoints: bool) -> Self {
+        self.allow_untrusted_endpoints = allow_untrusted_endpoints;
+        self
+    }
+
+    /// Caches the cloud info of the endpoints, fetched from their metadata endpoint before the first request, in a JSON file at `path`.
+    /// Entries expire after `ttl`.
+    ///
+    /// The file can be shared by many processes, which saves each of them from fetching the cloud info when it starts.
+    /// The file is only read when the cloud info is first needed, and any error reading or writing it, including a corrupted file,
+    /// falls back to fetching the cloud info. The cache is not available in WebAssembly.
+    #[must_use]
+    pub fn with_cloud_info_disk_cache(mut self, path: impl Into<PathBuf>, ttl: Duration) -> Self {
+        self.cloud_info_disk_cache = Some(CloudInfoDiskCache::new(path.into(), ttl));
+        self
+    }
+
+    /// Uses the given cloud info for the cluster of the connection string, instead of fetching it from its metadata endpoint.
+    /// The cloud info determines the login endpoint and the resource that tokens are requested for.
+    /// Credentials log in to its login endpoint, unless the authority of the connection string is a url with its own host.
+    ///
+    /// Clusters that redirect requests to other clusters still have the cloud info of those clusters fetched.
+    ///
+    /// Only the domains of the cloud are trusted to receive tokens. For a private cloud, whose login endpoint isn't well-known,
+    /// those are the domains of its login endpoint and of its Kusto service resource.
+    /// # Example
+    /// ```rust
+    /// use azure_kusto_data::cloud_info::CloudInfo;
+    /// use azure_kusto_data::prelude::*;
+    ///
+    /// let client = KustoClient::new(
+    ///     ConnectionString::with_default_auth("https://mycluster.region.kusto.usgovcloudapi.net/"),
+    ///     KustoClientOptions::new().with_cloud_info(CloudInfo::azure_us_government()),
+    /// );
+    /// # assert!(client.is_ok());
+    ///
+    /// let private_cloud = CloudInfo {
+    ///     login_endpoint: "https://login.contoso.cloud".into(),
+    ///     kusto_service_resource_id: "https://kusto.contoso.cloud".into(),
+    ///     ..CloudInfo::default()
+    /// };
+    /// let client = KustoClient::new(
+    ///     ConnectionString::with_default_auth("https://mycluster.region.contoso.cloud/"),
+    ///     KustoClientOptions::new().with_cloud_info(private_cloud),
+    /// );
+    /// # assert!(client.is_ok());
+    /// ```
+    #[must_use]
+    pub fn with_cloud_info(mut self, cloud_info: CloudInfo) -> Self {
+        self.cloud_info = Some(cloud_info);
+        self
+    }
+
+    /// Coalesces identical queries that run at the same time, such as those of the widgets of a dashboard, so that they are sent to the
+    /// service once and all receive a clone of its response. Queries are identical if they have the same database, text, options and parameters.
+    ///
+    /// Responses are also shared with identical queries that are executed up to `reuse_window` after the query completes,
+    /// which can be zero to only share them while the query runs. Completed responses are not shared in WebAssembly.
+    ///
+    /// Only queries whose response is buffered, such as with [execute_query](KustoClient::execute_query), are coalesced - progressive
+    /// streams and management commands are always sent. A query is only cancelled when all of the callers waiting for it are dropped.
+    /// Callers that share a failed query receive [Error::CoalescedQueryError], unless they are the last to receive the error.
+    /// # Example
+    /// ```rust
+    /// use azure_kusto_data::prelude::*;
+    /// use std::time::Duration;
+    ///
+    /// let client = KustoClient::new(
+    ///     ConnectionString::with_default_auth("https://mycluster.region.kusto.windows.net/"),
+    ///     KustoClientOptions::new().with_request_coalescing(Duration::from_millis(500)),
+    /// );
+    /// # assert!(client.is_ok());
+    /// ```
+    #[must_use]
+    pub fn with_request_coalescing(mut self, reuse_window: Duration) -> Self {
+        self.request_coalescing = Some(reuse_window);
+        self
+    }
+
+    /// Calls `callback` with a [SlowQueryReport](crate::slow_query::SlowQueryReport) for each query that exceeds any of the `thresholds`,
+    /// once it completes successfully. The report has the text of the query with its string literals masked, its database and client request id,
+    /// what was measured for it, and which thresholds it exceeded.
+    ///
+    /// Both buffered and progressive queries are measured - progressive queries once their whole response was read.
+    /// Shared responses of [coalesced](#method.with_request_coalescing) queries are only reported for the query that was sent.
+    /// The callback is called on the task that reads the response, so it should return quickly, and a panic in it is caught.
+    /// Queries are not measured in WebAssembly.
+    /// # Example
+    /// ```rust
+    /// use azure_kusto_data::prelude::*;
+    /// use azure_kusto_data::slow_query::SlowQueryThresholds;
+    /// use std::sync::Arc;
+    /// use std::time::Duration;
+    ///
+    /// let client = KustoClient::new(
+    ///     ConnectionString::with_default_auth("https://mycluster.region.kusto.windows.net/"),
+    ///     KustoClientOptions::new().with_slow_query_hook(
+    ///         SlowQueryThresholds::default().with_duration(Duration::from_secs(30)).with_rows(1_000_000),
+    ///         Arc::new(|report| eprintln!("slow query in {}: {:?} exceeded {:?}", report.database, report.query, report.exceeded)),
+    ///     ),
+    /// );
+    /// # assert!(client.is_ok());
+    /// ```
+    #[must_use]
+    pub fn with_slow_query_hook(
+        mut self,
+        thresholds: SlowQueryThresholds,
+        callback: SlowQueryCallback,
+    ) -> Self {
+        self.slow_query_hook = Some(SlowQueryHook::new(thresholds, callback));
+        self
+    }
+
+    /// Calls `callback` with the URL of the cluster when requests fail because it is stopped, so that it can be started,
+    /// such as through Azure Resource Manager, before the requests are retried. The requests still fail with [Error::ClusterStopped].
+    ///
+    /// The callback is called once each time the cluster is found stopped: concurrent requests that fail with it,
+    /// and later ones, don't call it again until a request to the cluster succeeds.
+    /// It is called on the task of the failing request, so it should return quickly, and a panic in it is caught.
+    /// # Example
+    /// ```rust
+    /// use azure_kusto_data::prelude::*;
+    /// use std::sync::Arc;
+    ///
+    /// let client = KustoClient::new(
+    ///     ConnectionString::with_default_auth("https://mycluster.region.kusto.windows.net/"),
+    ///     KustoClientOptions::new().with_cluster_stopped_handler(Arc::new(|cluster| eprintln!("{cluster} is stopped, starting it"))),
+    /// );
+    /// # assert!(client.is_ok());
+    /// ```
+    #[must_use]
+    pub fn with_cluster_stopped_handler(mut self, callback: ClusterStoppedCallback) -> Self {
+        self.cluster_stopped_callback = Some(callback);
+        self
+    }
+
+    /// Sets where the W3C trace context of requests is read from, instead of the current span, such as from an OpenTelemetry context.
+    /// Requests carry the context in their `traceparent` header, and the client request ids generated for them embed its trace id.
+    /// See [trace_context](crate::trace_context) for details.
+    /// # Example
+    /// ```rust
+    /// use azure_kusto_data::prelude::*;
+    /// use azure_kusto_data::trace_context::TraceContext;
+    /// use std::sync::Arc;
+    ///
+    /// let client = KustoClient::new(
+    ///     ConnectionString::with_default_auth("https://mycluster.region.kusto.windows.net/"),
+    ///     KustoClientOptions::new().with_trace_context_provider(Arc::new(|| {
+    ///         TraceContext::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01")
+    ///     })),
+    /// );
+    /// # assert!(client.is_ok());
+    /// ```
+    #[must_use]
+    pub fn with_trace_context_provider(mut self, provider: TraceContextProvider) -> Self {
+        self.trace_context_provider = Some(provider);
+        self
+    }
+
+    /// Sends queries to `query_url` and management commands to `management_url`, instead of to the endpoints of the data source,
+    /// such as to route them through a proxy or a private link. The URLs are used as they are, so they include their path.
+    /// Streaming ingestion is sent next to `management_url`, to the path whose last segment is replaced with `ingest`,
+    /// such as `/v1/rest/ingest` for `/v1/rest/mgmt`.
+    ///
+    /// Tokens are still requested for the cluster of the connection string, and its cloud info. The URLs must be https,
+    /// unless [insecure endpoints are allowed](Self::with_allow_insecure_endpoint), and known Kusto endpoints,
+    /// unless [untrusted endpoints are allowed](Self::with_allow_untrusted_endpoints), or the client fails to be created.
+    /// # Example
+    /// ```rust
+    /// use azure_kusto_data::prelude::*;
+    ///
+    /// let client = KustoClient::new(
+    ///     ConnectionString::with_default_auth("https://mycluster.region.kusto.windows.net/"),
+    ///     KustoClientOptions::new().with_endpoint_overrides(
+    ///         "https://mycluster.privatelink.region.kusto.windows.net/v2/rest/query",
+    ///         "https://mycluster.privatelink.region.kusto.windows.net/v1/rest/mgmt",
+    ///     ),
+    /// );
+    /// # assert!(client.is_ok());
+    /// ```
+    #[must_use]
+    pub fn with_endpoint_overrides(
+        mut self,
+        query_url: impl Into<String>,
+        management_url: impl Into<String>,
+    ) -> Self {
+        self.endpoint_overrides = Some((query_url.into(), management_url.into()));
+        self
+    }
+
+    /// Sets how the `properties` of request bodies are serialized when they, or their options and parameters, are not set,
+    /// for gateways in front of the cluster that only accept some of the forms. They are left out by default.
+    /// # Example
+    /// ```rust
+    /// use azure_kusto_data::prelude::*;
+    ///
+    /// let client = KustoClient::new(
+    ///     ConnectionString::with_default_auth("https://mycluster.region.kusto.windows.net/"),
+    ///     KustoClientOptions::new().with_properties_serialization(PropertiesSerialization::EmitEmptyObject),
+    /// );
+    /// # assert!(client.is_ok());
+    /// ```
+    #[must_use]
+    pub fn with_properties_serialization(
+        mut self,
+        properties_serialization: PropertiesSerialization,
+    ) -> Self {
+        self.properties_serialization = properties_serialization;
+        self
+    }
+
+    /// Sets the number of tables that [execute_streaming_query](KustoClient::execute_streaming_query) buffers
+    /// before the reading of the response waits for them to be consumed.
+    /// Defaults to [DEFAULT_TABLE_CAPACITY](crate::operations::streaming::DEFAULT_TABLE_CAPACITY).
+    /// The header and completion of the dataset are only received once enough of the tables were read.
+    #[must_use]
+    pub fn with_streaming_table_capacity(mut self, capacity: usize) -> Self {
+        self.streaming_table_capacity = Some(capacity);
+        self
+    }
+
+    /// Measures the size of the rows of each table that [execute_streaming_query](KustoClient::execute_streaming_query) reads,
+    /// as the length of their JSON, in the [bytes](crate::operations::streaming::TableSummary::bytes) of its summary.
+    /// Off by default, as every row is serialized again to measure it.
+    #[must_use]
+    pub fn with_streaming_table_bytes(mut self, streaming_table_bytes: bool) -> Self {
+        self.streaming_table_bytes = streaming_table_bytes;
+        self
+    }
+
+    /// Sets how the responses of the queries of the client are parsed, and how their results are deserialized into structs.
+    #[must_use]
+    pub fn with_parse_options(mut self, parse_options: ParseOptions) -> Self {
+        self.parse_options = parse_options;
+        self
+    }
+
+    /// Sends the requests of the client with an HTTP client tuned by `http_transport`, such as to keep idle connections
+    /// to reuse them for later requests. This replaces the transport of the [ClientOptions] the options were created from.
+    /// Like the default transport of the client, it leaves redirects to the client, which only follows them to trusted endpoints.
+    /// The transport can't be tuned in WebAssembly.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[must_use]
+    pub fn with_http_transport(mut self, http_transport: HttpTransportOptions) -> Self {
+        self.http_transport = Some(http_transport);
+        self
+    }
+}
+
+/// The URL of streaming ingestion next to an overridden management endpoint, replacing the last segment of its path with `ingest`.
+fn ingest_url_next_to(management_url: &Url) -> String {
+    let mut url = management_url.clone();
+    // Urls that can't be a base have no segments, but https and http urls always can
+    if let Ok(mut segments) = url.path_segments_mut() {
+        segments.pop_if_empty().pop().push("ingest");
+    }
+    url.to_string()
 }
 
 fn new_pipeline_from_options(
     auth: ConnectionStringAuth,
+    authority_id: Option<String>,
     resource: String,
+    aliases: &[Url],
     options: KustoClientOptions,
 ) -> Pipeline {
-    let auth_policy = Arc::new(AuthorizationPolicy::new(auth, resource));
     // take care of adding the AuthorizationPolicy as **last** retry policy.
-    let per_retry_policies: Vec<Arc<(dyn azure_core::Policy + 'static)>> = vec![auth_policy];
+    // Redirects are followed before it, so that redirected requests are authorized for their new endpoint.
+    let mut per_retry_policies: Vec<Arc<dyn azure_core::Policy + 'static>> =
+        vec![Arc::new(RedirectPolicy)];
+    // Anonymous requests, such as to the emulator, have no token to fetch, nor a cloud info to resolve for it.
+    if !matches!(auth, ConnectionStringAuth::None) {
+        per_retry_policies.push(Arc::new(
+            AuthorizationPolicy::new(
+                auth,
+                authority_id,
+                resource,
+                options.cloud_info_disk_cache,
+                options.cloud_info,
+                options.allow_untrusted_endpoints,
+            )
+            .with_aliases(aliases),
+        ));
+    }
 
     Pipeline::new(
         option_env!("CARGO_PKG_NAME"),
@@ -54,6 +442,12 @@ fn new_pipeline_from_options(
     )
 }
 
+/// How long a cancelled query waits for `.cancel query` to be sent, when it can't be sent in the background.
+const CANCEL_QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The database of cluster-level management commands, when the connection string has no initial catalog.
+const CLUSTER_COMMANDS_DATABASE: &str = "NetDefaultDB";
+
 /// Kusto client for Rust.
 /// The client is a wrapper around the Kusto REST API.
 /// To read more about it, go to [https://docs.microsoft.com/en-us/azure/kusto/api/rest/](https://docs.microsoft.com/en-us/azure/kusto/api/rest/)
@@ -65,7 +459,21 @@ pub struct KustoClient {
     pipeline: Arc<Pipeline>,
     query_url: Arc<String>,
     management_url: Arc<String>,
+    ingest_url: Arc<String>,
     default_headers: Arc<Headers>,
+    coalescer: Option<Arc<QueryCoalescer>>,
+    slow_query_hook: Option<Arc<SlowQueryHook>>,
+    cluster_stopped_handler: Option<Arc<ClusterStoppedHandler>>,
+    trace_context: TraceContextSource,
+    properties_serialization: PropertiesSerialization,
+    default_database: Option<Arc<String>>,
+    service_url: Arc<String>,
+    // Only streaming queries, which need tokio, read the options of streaming datasets
+    #[cfg_attr(not(feature = "tokio"), allow(dead_code))]
+    streaming_table_capacity: Option<usize>,
+    #[cfg_attr(not(feature = "tokio"), allow(dead_code))]
+    streaming_table_bytes: bool,
+    parse_options: ParseOptions,
 }
 
 /// Denotes what kind of query is being executed.
@@ -77,6 +485,135 @@ pub enum QueryKind {
     Query,
 }
 
+impl QueryKind {
+    /// The kind of the query, as told by its text: management commands start with a dot, after any leading whitespace
+    /// and `//` comment lines.
+    /// # Example
+    /// ```
+    /// use azure_kusto_data::prelude::QueryKind;
+    ///
+    /// assert_eq!(QueryKind::detect("  .show tables"), QueryKind::Management);
+    /// assert_eq!(QueryKind::detect("// List the tables\n.show tables"), QueryKind::Management);
+    /// assert_eq!(QueryKind::detect("// .show tables\nStormEvents"), QueryKind::Query);
+    /// ```
+    #[must_use]
+    pub fn detect(query: &str) -> QueryKind {
+        let mut text = query.trim_start();
+        while let Some(comment) = text.strip_prefix("//") {
+            text = comment
+                .split_once('\n')
+                .map_or("", |(_, rest)| rest)
+                .trim_start();
+        }
+
+        if text.starts_with('.') {
+            QueryKind::Management
+        } else {
+            QueryKind::Query
+        }
+    }
+}
+
+/// Deserializes the rows of a primary result into `T`, matching the columns to the fields as [ClientRequestProperties::case_mapping] tells.
+fn deserialize_rows<T: DeserializeOwned>(
+    table: DataTable,
+    case_mapping: Option<CaseMapping>,
+    check_struct_columns: bool,
+) -> Result<Vec<T>> {
+    match case_mapping {
+        Some(case_mapping) => deserialize_values(&table.columns, table.rows, case_mapping),
+        None => {
+            if check_struct_columns {
+                check_positional_columns::<T>(&table.columns)?;
+            }
+            deserialize_positional(&table.columns, table.rows)
+        }
+    }
+}
+
+/// Parses the data source of a connection string into the URL of the cluster, keeping its port.
+///
+/// Data sources without a scheme, such as `mycluster.kusto.windows.net:443`, are assumed to be https.
+fn parse_data_source(data_source: &str) -> std::result::Result<Url, ConnectionStringError> {
+    let invalid = |msg: &str| ConnectionStringError::InvalidDataSource {
+        data_source: data_source.to_string(),
+        msg: msg.to_string(),
+    };
+
+    let trimmed = data_source.trim();
+    let url = if trimmed.contains("://") {
+        Url::parse(trimmed)
+    } else {
+        Url::parse(&format!("https://{trimmed}"))
+    }
+    .map_err(|e| invalid(&e.to_string()))?;
+
+    if !matches!(url.scheme(), "https" | "http") {
+        return Err(ConnectionStringError::UnsupportedScheme {
+            data_source: data_source.to_string(),
+            scheme: url.scheme().to_string(),
+        });
+    }
+    if url.host_str().map_or(true, str::is_empty) {
+        return Err(invalid("it has no host"));
+    }
+    if url.query().is_some() || url.fragment().is_some() {
+        return Err(invalid("it has a query or a fragment"));
+    }
+    Ok(url)
+}
+
+/// Parses an endpoint override, which must be an absolute URL that the client may send requests, and tokens, to.
+fn parse_endpoint_override(
+    endpoint: &str,
+    options: &KustoClientOptions,
+    authenticated: bool,
+) -> std::result::Result<Url, InvalidArgumentError> {
+    let invalid = |msg: &str| InvalidArgumentError::InvalidEndpoint {
+        endpoint: endpoint.to_string(),
+        msg: msg.to_string(),
+    };
+    let url = Url::parse(endpoint.trim()).map_err(|e| invalid(&e.to_string()))?;
+    match url.scheme() {
+        "https" => {}
+        "http" if options.allow_insecure_endpoint => {}
+        "http" => {
+            return Err(invalid(
+                "it is not secure, use https or allow insecure endpoints",
+            ))
+        }
+        _ => return Err(invalid("only https and http endpoints are supported")),
+    }
+    if url.host_str().map_or(true, str::is_empty) {
+        return Err(invalid("it has no host"));
+    }
+    if url.fragment().is_some() {
+        return Err(invalid("it has a fragment"));
+    }
+    if authenticated
+        && !options.allow_untrusted_endpoints
+        && !is_trusted_endpoint(&url, options.cloud_info.as_ref())
+    {
+        return Err(invalid(
+            "it is not a known Kusto endpoint, allow untrusted endpoints to connect to it",
+        ));
+    }
+    Ok(url)
+}
+
+/// Returns the properties with a client request id, generating one if they have none, along with the id.
+fn with_client_request_id(
+    properties: Option<ClientRequestProperties>,
+    trace_context: Option<&TraceContext>,
+) -> (ClientRequestProperties, String) {
+    let mut properties = properties.unwrap_or_default();
+    let client_request_id = properties
+        .client_request_id
+        .get_or_insert_with(|| new_client_request_id(trace_context))
+        .clone();
+    (properties, client_request_id)
+}
+
 impl KustoClient {
     /// Create a new Kusto client.
     /// This method accepts a connection string, that includes the Kusto cluster and the authentication information for the cluster.
@@ -90,19 +627,103 @@ impl KustoClient {
     ///
     /// assert!(client.is_ok());
     /// ```
-    pub fn new(connection_string: ConnectionString, options: KustoClientOptions) -> Result<Self> {
+    pub fn new(
+        connection_string: ConnectionString,
+        mut options: KustoClientOptions,
+    ) -> Result<Self> {
+        let url = parse_data_source(&connection_string.data_source)?;
+        if !options.allow_insecure_endpoint && url.scheme() == "http" {
+            return Err(ConnectionStringError::InsecureEndpoint {
+                data_source: connection_string.data_source,
+            }
+            .into());
+        }
+
+        // Clients without authentication don't send tokens, so they may connect anywhere
+        if !options.allow_untrusted_endpoints
+            && !matches!(connection_string.auth, ConnectionStringAuth::None)
+            && !is_trusted_endpoint(&url, options.cloud_info.as_ref())
+        {
+            return Err(ConnectionStringError::UntrustedEndpoint {
+                data_source: connection_string.data_source,
+            }
+            .into());
+        }
+
+        let authenticated = !matches!(connection_string.auth, ConnectionStringAuth::None);
+        let endpoint_overrides = match &options.endpoint_overrides {
+            Some((query_url, management_url)) => vec![
+                parse_endpoint_override(query_url, &options, authenticated)?,
+                parse_endpoint_override(management_url, &options, authenticated)?,
+            ],
+            None => Vec::new(),
+        };
+
+        // The transport of azure_core follows redirects by itself, so it is replaced with one that leaves them to the RedirectPolicy.
+        #[cfg(not(target_arch = "wasm32"))]
+        let http_client = match &options.http_transport {
+            Some(http_transport) => Some(http_transport.build()?),
+            None if !options.custom_client_options => Some(default_http_client()?),
+            None => None,
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(http_client) = http_client {
+            options.options =
+                std::mem::take(&mut options.options).transport(TransportOptions::new(http_client));
+        }
+
         let default_headers = Arc::new(Self::default_headers(connection_string.client_details()));
-        let (data_source, credentials) = connection_string.into_data_source_and_auth();
-        let service_url = Arc::new(data_source.trim_end_matches('/').to_string());
-        let query_url = format!("{service_url}/v2/rest/query");
-        let management_url = format!("{service_url}/v1/rest/mgmt");
-        let pipeline = new_pipeline_from_options(credentials, (*service_url).clone(), options);
+        let default_database = connection_string.initial_catalog.clone().map(Arc::new);
+        let (_, credentials, authority_id) = connection_string.into_data_source_and_auth();
+        let service_url = Arc::new(url.as_str().trim_end_matches('/').to_string());
+        let (query_url, management_url) = match endpoint_overrides.as_slice() {
+            [query_url, management_url] => (query_url.to_string(), management_url.to_string()),
+            _ => (
+                format!("{service_url}/v2/rest/query"),
+                format!("{service_url}/v1/rest/mgmt"),
+            ),
+        };
+        let ingest_url = match endpoint_overrides.as_slice() {
+            [_, management_url] => ingest_url_next_to(management_url),
+            _ => format!("{service_url}/v1/rest/ingest"),
+        };
+        let coalescer = options
+            .request_coalescing
+            .map(|reuse_window| Arc::new(QueryCoalescer::new(reuse_window)));
+        let slow_query_hook = options.slow_query_hook.clone().map(Arc::new);
+        let cluster_stopped_handler = options
+            .cluster_stopped_callback
+            .clone()
+            .map(|callback| Arc::new(ClusterStoppedHandler::new(callback)));
+        let trace_context = TraceContextSource::new(options.trace_context_provider.clone());
+        let properties_serialization = options.properties_serialization;
+        let streaming_table_capacity = options.streaming_table_capacity;
+        let streaming_table_bytes = options.streaming_table_bytes;
+        let parse_options = options.parse_options;
+        let pipeline = new_pipeline_from_options(
+            credentials,
+            authority_id,
+            (*service_url).clone(),
+            &endpoint_overrides,
+            options,
+        );
 
         Ok(Self {
             pipeline: pipeline.into(),
             query_url: query_url.into(),
             management_url: management_url.into(),
+            ingest_url: ingest_url.into(),
             default_headers,
+            coalescer,
+            slow_query_hook,
+            cluster_stopped_handler,
+            trace_context,
+            properties_serialization,
+            default_database,
+            service_url,
+            streaming_table_capacity,
+            streaming_table_bytes,
+            parse_options,
         })
     }
 
@@ -133,9 +754,138 @@ impl KustoClient {
         &self.pipeline
     }
 
+    pub(crate) fn coalescer(&self) -> Option<&Arc<QueryCoalescer>> {
+        self.coalescer.as_ref()
+    }
+
+    pub(crate) fn slow_query_hook(&self) -> Option<&Arc<SlowQueryHook>> {
+        self.slow_query_hook.as_ref()
+    }
+
+    /// Converts the errors of requests to a stopped cluster into [Error::ClusterStopped], reporting them to the handler of the client.
+    pub(crate) fn classify_error(&self, error: Error) -> Error {
+        let error = crate::cluster_stopped::classify(error, &self.service_url);
+        if let (Error::ClusterStopped { cluster }, Some(handler)) =
+            (&error, &self.cluster_stopped_handler)
+        {
+            handler.stopped(cluster);
+        }
+        error
+    }
+
+    /// Records that a request to the cluster succeeded, so that it being stopped again is reported.
+    pub(crate) fn request_succeeded(&self) {
+        if let Some(handler) = &self.cluster_stopped_handler {
+            handler.succeeded();
+        }
+    }
+
+    pub(crate) fn properties_serialization(&self) -> PropertiesSerialization {
+        self.properties_serialization
+    }
+
+    pub(crate) fn parse_options(&self) -> ParseOptions {
+        self.parse_options
+    }
+
+    /// The database that queries run against when they don't name one, from the initial catalog of the connection string.
+    #[must_use]
+    pub fn default_database(&self) -> Option<&str> {
+        self.default_database.as_deref().map(String::as_str)
+    }
+
+    /// The current W3C trace context, which requests are sent with. See [trace_context](crate::trace_context).
+    #[must_use]
+    pub fn trace_context(&self) -> Option<TraceContext> {
+        self.trace_context.current()
+    }
+
+    /// The default headers, with the client request id, application and user of the request properties, when set.
+    /// See [ClientRequestProperties::application] for the precedence of the application and user.
+    ///
+    /// When there is a current trace context, it is sent in the `traceparent` header, and a client request id embedding
+    /// its trace id is generated if the properties have none.
+    pub(crate) fn default_headers_for(
+        &self,
+        client_request_properties: Option<&ClientRequestProperties>,
+    ) -> Headers {
+        let mut headers = self.default_headers.as_ref().clone();
+        let client_request_id =
+            client_request_properties.and_then(|p| p.client_request_id.as_deref());
+        if let Some(trace_context) = self.trace_context() {
+            headers.insert("traceparent", trace_context.traceparent());
+            if client_request_id.is_none() {
+                headers.insert(
+                    "x-ms-client-request-id",
+                    new_client_request_id(Some(&trace_context)),
+                );
+            }
+        }
+        if let Some(client_request_id) = client_request_id {
+            headers.insert("x-ms-client-request-id", client_request_id.to_string());
+        }
+        if let Some(client_request_properties) = client_request_properties {
+            if let Some(application) = client_request_properties.request_application() {
+                headers.insert("x-ms-app", to_header_safe(application).into_owned());
+            }
+            if let Some(user) = client_request_properties.request_user() {
+                headers.insert("x-ms-user", to_header_safe(user).into_owned());
+            }
+        }
+        headers
+    }
+
+    /// Creates a [WeakConsistencySession], to run queries with weak consistency on the same node of the cluster.
+    /// See its documentation for the tradeoffs of weak consistency sessions.
+    #[must_use]
+    pub fn weak_consistency_session(&self) -> WeakConsistencySession {
+        WeakConsistencySession::new()
+    }
+
+    /// The runner behind every way of executing a query, so that they all build the same request for the same inputs.
+    /// Queries without a database run against the [default database](Self::default_database), and fail when it isn't set either.
+    fn query_runner(
+        &self,
+        database: Option<String>,
+        query: impl Into<String>,
+        kind: QueryKind,
+        client_request_properties: Option<ClientRequestProperties>,
+    ) -> QueryRunner {
+        let database = database.or_else(|| self.default_database().map(str::to_string));
+        QueryRunnerBuilder::default()
+            .with_kind(kind)
+            .with_client(self.clone())
+            .with_database(database)
+            .with_query(query)
+            .with_client_request_properties(client_request_properties)
+            .build()
+            .expect("Unexpected error when building query runner - please report this issue to the Kusto team")
+    }
+
     /// Execute a query against the Kusto cluster.
     /// The `kind` parameter determines whether the request is a query (retrieves data from the tables) or a management query (commands to monitor and manage the cluster).
-    /// This method should only be used if the query kind is not known at compile time, otherwise use [execute](#method.execute) or [execute_command](#method.execute_command).
+    #[deprecated(
+        note = "Use execute_query or execute_command, or execute_either when the kind of the query is only known at runtime"
+    )]
+    #[must_use]
+    pub fn execute_with_options(
+        &self,
+        database: impl Into<String>,
+        query: impl Into<String>,
+        kind: QueryKind,
+        client_request_properties: Option<ClientRequestProperties>,
+    ) -> QueryRunner {
+        self.query_runner(
+            Some(database.into()),
+            query,
+            kind,
+            client_request_properties,
+        )
+    }
+
+    /// Execute either a query or a management command, as [detected](QueryKind::detect) from the text of the query.
+    /// This method should only be used if the kind of the query is not known at compile time, otherwise use
+    /// [execute_query](#method.execute_query) or [execute_command](#method.execute_command), which return typed responses.
     /// # Example
     /// ```no_run
     /// use azure_kusto_data::prelude::*;
@@ -145,28 +895,26 @@ impl KustoClient {
     ///   ConnectionString::with_default_auth("https://mycluster.region.kusto.windows.net/"),
     ///   KustoClientOptions::default())?;
     ///
-    ///  let result = client.execute_with_options("some_database", ".show version", QueryKind::Management, None).await?;
+    /// let result = client.execute_either("some_database", ".show version", None).await?;
     ///
     /// assert!(matches!(result, KustoResponse::V1(..)));
     /// # Ok(())}
     /// ```
     #[must_use]
-    pub fn execute_with_options(
+    pub fn execute_either(
         &self,
         database: impl Into<String>,
         query: impl Into<String>,
-        kind: QueryKind,
         client_request_properties: Option<ClientRequestProperties>,
     ) -> QueryRunner {
-        QueryRunnerBuilder::default()
-            .with_kind(kind)
-            .with_client(self.clone())
-            .with_database(database)
-            .with_query(query)
-            .with_default_headers(self.default_headers.clone())
-            .with_client_request_properties(client_request_properties)
-            .build()
-            .expect("Unexpected error when building query runner - please report this issue to the Kusto team")
+        let query = query.into();
+        let kind = QueryKind::detect(&query);
+        self.query_runner(
+            Some(database.into()),
+            query,
+            kind,
+            client_request_properties,
+        )
     }
 
     /// Execute a KQL query with additional request options.
@@ -188,7 +936,7 @@ impl KustoClient {
     ///     .await?;
     ///
     ///   for table in result.into_primary_results() {
-    ///        println!("{}", table.table_name);
+    ///        println!("{}", table?.table_name);
     ///    }
     /// # Ok(())}
     /// ```
@@ -200,17 +948,290 @@ impl KustoClient {
         query: impl Into<String>,
         options: Option<ClientRequestProperties>,
     ) -> V2QueryRunner {
-        V2QueryRunner(self.execute_with_options(database, query, QueryKind::Query, options))
+        V2QueryRunner(self.query_runner(Some(database.into()), query, QueryKind::Query, options))
+    }
+
+    /// Execute a KQL query against the [default database](Self::default_database), set by the initial catalog of the connection string.
+    /// Fails with [Error::QueryError] when the connection string has no initial catalog.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use azure_kusto_data::prelude::*;
+    /// # #[tokio::main] async fn main() -> Result<(), Error> {
+    /// let client = KustoClient::new(
+    ///    ConnectionString::from_raw_connection_string("Data Source=https://mycluster.region.kusto.windows.net/;Initial Catalog=some_database")?,
+    ///    KustoClientOptions::default())?;
+    ///
+    /// let result = client.execute_query_on_default_db("MyTable | take 10", None).await?;
+    /// # Ok(())}
+    /// ```
+    #[must_use]
+    pub fn execute_query_on_default_db(
+        &self,
+        query: impl Into<String>,
+        options: Option<ClientRequestProperties>,
+    ) -> V2QueryRunner {
+        V2QueryRunner(self.query_runner(None, query, QueryKind::Query, options))
+    }
+
+    /// Execute many KQL queries against the same database, with at most `max_concurrency` of them running at once.
+    /// Returns the result of each query in the order of the `queries`, so that a failed query doesn't fail the others.
+    ///
+    /// Each query is sent with a clone of the `client_request_properties`, so a client request id in them is shared by all of the queries.
+    /// A `max_concurrency` of zero is treated as one.
+    /// # Example
+    /// ```no_run
+    /// use azure_kusto_data::prelude::*;
+    /// # #[tokio::main] async fn main() -> Result<(), Error> {
+    /// let client = KustoClient::new(
+    ///    ConnectionString::with_default_auth("https://mycluster.region.kusto.windows.net/"),
+    ///    KustoClientOptions::default())?;
+    ///
+    /// let queries = (1..=100).map(|day| format!("Events | where Day == {day} | count"));
+    /// for response in client.execute_queries_parallel("some_database", queries, None, 8).await {
+    ///     println!("{} frames", response?.raw_results_count());
+    /// }
+    /// # Ok(())}
+    /// ```
+    pub async fn execute_queries_parallel(
+        &self,
+        database: impl Into<String>,
+        queries: impl IntoIterator<Item = impl Into<String>>,
+        client_request_properties: Option<ClientRequestProperties>,
+        max_concurrency: usize,
+    ) -> Vec<Result<KustoResponseDataSetV2>> {
+        let database = database.into();
+        futures::stream::iter(queries)
+            .map(|query| {
+                self.execute_query(database.clone(), query, client_request_properties.clone())
+                    .into_future()
+            })
+            .buffered(max_concurrency.max(1))
+            .collect()
+            .await
+    }
+
+    /// Execute a KQL query as a progressive stream, yielding each primary result table once it was fully received.
+    /// The options required for progressive streaming are set automatically, overriding any conflicting values in `client_request_properties`.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use azure_kusto_data::prelude::*;
+    /// use futures::TryStreamExt;
+    /// # #[tokio::main] async fn main() -> Result<(), Error> {
+    ///
+    /// let client = KustoClient::new(
+    ///    ConnectionString::with_default_auth("https://mycluster.region.kusto.windows.net/"),
+    ///    KustoClientOptions::default())?;
+    ///
+    /// let mut dataset = client.execute_streaming_query("some_database", "MyTable | take 10", None).await?;
+    ///
+    /// while let Some(table) = dataset.try_next().await? {
+    ///     println!("{} rows", table.rows.len());
+    /// }
+    ///
+    /// println!("{:?}", dataset.completion().await?);
+    /// # Ok(())}
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub async fn execute_streaming_query(
+        &self,
+        database: impl Into<String>,
+        query: impl Into<String>,
+        client_request_properties: Option<ClientRequestProperties>,
+    ) -> Result<StreamingDataset> {
+        let results = self
+            .start_streaming_query(database, query, client_request_properties)
+            .await?;
+        Ok(StreamingDataset::new(results, self.streaming_options()))
+    }
+
+    /// Execute a KQL query as a progressive stream, as [execute_streaming_query](Self::execute_streaming_query) does,
+    /// until `cancel` resolves.
+    ///
+    /// Once cancelled, the response is dropped, the background task that reads it stops, and the stream fails with [Error::QueryCancelled].
+    /// `.cancel query` is sent in the background, on a best-effort basis, with the client request id of the query,
+    /// which is generated if `client_request_properties` has none.
+    #[cfg(feature = "tokio")]
+    pub async fn execute_streaming_query_with_cancellation(
+        &self,
+        database: impl Into<String>,
+        query: impl Into<String>,
+        client_request_properties: Option<ClientRequestProperties>,
+        cancel: impl Future<Output = ()> + Send + 'static,
+    ) -> Result<StreamingDataset> {
+        let database = database.into();
+        let (properties, client_request_id) =
+            with_client_request_id(client_request_properties, self.trace_context().as_ref());
+        let cancel = futures::FutureExt::shared(cancel);
+
+        let start = Box::pin(self.start_streaming_query(database.clone(), query, Some(properties)));
+        let results = match future::select(start, cancel.clone()).await {
+            Either::Left((started, _)) => started?,
+            Either::Right(((), start)) => {
+                drop(start);
+                self.cancel_best_effort(database, client_request_id.clone())
+                    .await;
+                return Err(Error::QueryCancelled { client_request_id });
+            }
+        };
+
+        let client = self.clone();
+        let id = client_request_id.clone();
+        let cancel = async move {
+            cancel.await;
+            client.cancel_best_effort(database, id).await;
+        };
+        Ok(StreamingDataset::new(
+            Cancellable::new(results, cancel, client_request_id),
+            self.streaming_options(),
+        ))
+    }
+
+    /// How the [StreamingDataset]s of the client read their responses.
+    #[cfg(feature = "tokio")]
+    fn streaming_options(&self) -> StreamingOptions {
+        StreamingOptions {
+            pad_short_rows: self.parse_options.pad_short_rows,
+            table_capacity: self.streaming_table_capacity,
+            measure_table_bytes: self.streaming_table_bytes,
+        }
+    }
+
+    /// Sends a query for progressive streaming, returning its results to assemble into a [StreamingDataset].
+    #[cfg(feature = "tokio")]
+    async fn start_streaming_query(
+        &self,
+        database: impl Into<String>,
+        query: impl Into<String>,
+        client_request_properties: Option<ClientRequestProperties>,
+    ) -> Result<impl futures::Stream<Item = Result<crate::models::V2QueryResult>> + Send + 'static>
+    {
+        let mut client_request_properties = client_request_properties.unwrap_or_default();
+        let mut options = client_request_properties.options.take().unwrap_or_default();
+        options.results_progressive_enabled = Some(true);
+        client_request_properties.options = Some(options);
+
+        let results = self
+            .execute_query(database, query, Some(client_request_properties))
+            .into_stream()
+            .await?;
+
+        Ok(results)
+    }
+
+    /// Execute a KQL query, as [execute_query](Self::execute_query) does, until `cancel` resolves.
+    ///
+    /// Once cancelled, the request is dropped, and `.cancel query` is sent on a best-effort basis with the client request id of the query,
+    /// which is generated if `client_request_properties` has none, before failing with [Error::QueryCancelled].
+    /// As cancelling a query that is shared with others would fail them too, the query is never [coalesced](KustoClientOptions::with_request_coalescing).
+    ///
+    /// `cancel` may be any future, such as a timer for a deadline, or the `cancelled` future of a `tokio_util` `CancellationToken`.
+    /// # Example
+    /// ```no_run
+    /// use azure_kusto_data::prelude::*;
+    /// # #[tokio::main] async fn main() -> Result<(), Error> {
+    /// let client = KustoClient::new(
+    ///    ConnectionString::with_default_auth("https://mycluster.region.kusto.windows.net/"),
+    ///    KustoClientOptions::default())?;
+    ///
+    /// let (cancel, cancelled) = futures::channel::oneshot::channel::<()>();
+    /// // Call `cancel.send(())` from elsewhere to abort the query
+    /// # drop(cancel);
+    /// let result = client
+    ///     .execute_query_with_cancellation("some_database", "MyTable | take 10", None, async {
+    ///         let _ = cancelled.await;
+    ///     })
+    ///     .await;
+    /// if let Err(Error::QueryCancelled { client_request_id }) = &result {
+    ///     println!("{client_request_id} was cancelled");
+    /// }
+    /// # Ok(())}
+    /// ```
+    pub async fn execute_query_with_cancellation(
+        &self,
+        database: impl Into<String>,
+        query: impl Into<String>,
+        client_request_properties: Option<ClientRequestProperties>,
+        cancel: impl Future<Output = ()>,
+    ) -> Result<KustoResponseDataSetV2> {
+        let database = database.into();
+        let (properties, client_request_id) =
+            with_client_request_id(client_request_properties, self.trace_context().as_ref());
+        let query = self
+            .query_runner(
+                Some(database.clone()),
+                query,
+                QueryKind::Query,
+                Some(properties),
+            )
+            .into_future();
+        futures::pin_mut!(cancel);
+
+        match future::select(query, cancel).await {
+            Either::Left((response, _)) => match response? {
+                KustoResponse::V2(response) => Ok(response),
+                KustoResponse::V1(_) => Err(Error::QueryError(
+                    "Expected a V2 response to the query".into(),
+                )),
+            },
+            Either::Right(((), query)) => {
+                drop(query);
+                self.cancel_best_effort(database, client_request_id.clone())
+                    .await;
+                Err(Error::QueryCancelled { client_request_id })
+            }
+        }
+    }
+
+    /// Cancels a running query with `.cancel query`, using the client request id it was sent with.
+    /// Fails if the query is not running, such as when it already completed.
+    pub async fn cancel_query(
+        &self,
+        database: impl Into<String>,
+        client_request_id: &str,
+    ) -> Result<()> {
+        let command = format!(
+            ".cancel query \"{}\"",
+            client_request_id.replace('\\', "\\\\").replace('"', "\\\"")
+        );
+        self.execute_command(database, command, None).await?;
+        Ok(())
+    }
+
+    /// Sends `.cancel query` for a query that its caller cancelled, ignoring failures, as the query may have completed meanwhile.
+    /// On a tokio runtime it is sent in the background, so that the caller isn't held up by it,
+    /// and otherwise it is waited for up to [CANCEL_QUERY_TIMEOUT].
+    async fn cancel_best_effort(&self, database: String, client_request_id: String) {
+        #[cfg(feature = "tokio")]
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            let client = self.clone();
+            runtime.spawn(async move {
+                let _ = client.cancel_query(database, &client_request_id).await;
+            });
+            return;
+        }
+
+        let cancel = Box::pin(self.cancel_query(database, &client_request_id));
+        let timeout = Box::pin(azure_core::sleep::sleep(CANCEL_QUERY_TIMEOUT));
+        let _ = future::select(cancel, timeout).await;
     }
 
     /// Execute a KQL query into an array of structs.
     /// To learn more about KQL go to [https://docs.microsoft.com/en-us/azure/kusto/query/](https://docs.microsoft.com/en-us/azure/kusto/query)
     ///
     /// This method is the simplest way to just convert your data into a struct.
-    /// It assumes there is one primary result table.
+    /// It fails if the query has no primary result table or more than one, such as when it has several tabular statements.
+    /// Use [execute_query_to_structs_multi](#method.execute_query_to_structs_multi) for queries with two of them,
+    /// or [KustoResponseDataSetV2::primary_result_by_index] to select one of the results of [execute_query](#method.execute_query).
     ///
     /// Your struct should implement the [serde::DeserializeOwned](https://docs.serde.rs/serde/trait.DeserializeOwned.html) trait.
     ///
+    /// By default columns are matched to fields by position. Set [ClientRequestProperties::case_mapping]
+    /// to match them by name instead, e.g. [PascalToSnake](crate::deserialization::CaseMapping::PascalToSnake) maps a `ClientRequestId` column to a `client_request_id` field.
+    /// When matching by position, set [ParseOptions::with_check_struct_columns] on the client to check that the columns match the fields
+    /// before deserializing the rows.
+    ///
     /// # Example
     /// ```no_run
     /// use azure_kusto_data::prelude::*;
@@ -238,18 +1259,265 @@ impl KustoClient {
         query: impl Into<String>,
         client_request_properties: Option<ClientRequestProperties>,
     ) -> Result<Vec<T>> {
+        let case_mapping = client_request_properties
+            .as_ref()
+            .and_then(|p| p.case_mapping);
+        let check_struct_columns = self.parse_options.check_struct_columns;
+
         let response = self
             .execute_query(database, query, client_request_properties)
             .await?;
 
-        let results = response
+        deserialize_rows(
+            response.into_single_primary_result()?,
+            case_mapping,
+            check_struct_columns,
+        )
+    }
+
+    /// Execute a KQL query with two tabular statements, such as `print 1; StormEvents | take 5`,
+    /// deserializing the primary result of the first one into `T1` and that of the second one into `T2`.
+    ///
+    /// The rows are deserialized as in [execute_query_to_struct](#method.execute_query_to_struct).
+    /// Fails if the query doesn't have exactly two primary result tables.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use azure_kusto_data::prelude::*;
+    ///
+    /// #[derive(serde::Deserialize, Debug)]
+    /// struct Count {
+    ///    count: i64,
+    /// }
+    ///
+    /// #[derive(serde::Deserialize, Debug)]
+    /// struct Event {
+    ///    name: String,
+    ///    timestamp: String,
+    /// }
+    ///
+    /// # #[tokio::main] async fn main() -> Result<(), Error> {
+    /// let client = KustoClient::new(
+    ///    ConnectionString::with_default_auth("https://mycluster.region.kusto.windows.net/"),
+    ///    KustoClientOptions::default())?;
+    ///
+    /// let (counts, events): (Vec<Count>, Vec<Event>) = client
+    ///     .execute_query_to_structs_multi("some_database", "Events | count; Events | project name, timestamp | take 10", None)
+    ///     .await?;
+    /// # Ok(())}
+    /// ```
+    pub async fn execute_query_to_structs_multi<T1: DeserializeOwned, T2: DeserializeOwned>(
+        &self,
+        database: impl Into<String>,
+        query: impl Into<String>,
+        client_request_properties: Option<ClientRequestProperties>,
+    ) -> Result<(Vec<T1>, Vec<T2>)> {
+        let case_mapping = client_request_properties
+            .as_ref()
+            .and_then(|p| p.case_mapping);
+        let check_struct_columns = self.parse_options.check_struct_columns;
+
+        let tables = self
+            .execute_query(database, query, client_request_properties)
+            .await?
             .into_primary_results()
-            .next()
-            .ok_or_else(|| Error::QueryError("No primary results found".into()))?;
+            .collect::<Result<Vec<_>>>()?;
+        let count = tables.len();
+        let (first, second) = match <[DataTable; 2]>::try_from(tables) {
+            Ok([first, second]) => (first, second),
+            Err(_) => {
+                return Err(Error::QueryError(
+                    format!("Expected 2 primary results, but found {count}").into(),
+                ))
+            }
+        };
+
+        Ok((
+            deserialize_rows(first, case_mapping, check_struct_columns)?,
+            deserialize_rows(second, case_mapping, check_struct_columns)?,
+        ))
+    }
+
+    /// Execute a KQL query into a lookup from the values of its key column to those of its value column.
+    /// It assumes there is one primary result table.
+    ///
+    /// Rows with the same key are handled according to `duplicates`. See [DataTable::to_lookup].
+    ///
+    /// # Example
+    /// ```no_run
+    /// use azure_kusto_data::prelude::*;
+    /// # #[tokio::main] async fn main() -> Result<(), Error> {
+    /// let client = KustoClient::new(
+    ///    ConnectionString::with_default_auth("https://mycluster.region.kusto.windows.net/"),
+    ///    KustoClientOptions::default())?;
+    ///
+    /// let names: hashbrown::HashMap<i64, String> = client
+    ///     .execute_query_to_lookup("some_database", "Users | project Id, Name", "Id", "Name", DuplicateKeys::Error, None)
+    ///     .await?;
+    /// # Ok(())}
+    /// ```
+    pub async fn execute_query_to_lookup<K, V>(
+        &self,
+        database: impl Into<String>,
+        query: impl Into<String>,
+        key_column: &str,
+        value_column: &str,
+        duplicates: DuplicateKeys,
+        client_request_properties: Option<ClientRequestProperties>,
+    ) -> Result<HashMap<K, V>>
+    where
+        K: DeserializeOwned + Eq + Hash,
+        V: DeserializeOwned,
+    {
+        let response = self
+            .execute_query(database, query, client_request_properties)
+            .await?;
+
+        let results = response.into_single_primary_result()?;
+
+        results.to_lookup(key_column, value_column, duplicates)
+    }
+
+    /// Execute a KQL query into structs keyed by the values of one of its columns.
+    /// It assumes there is one primary result table.
+    ///
+    /// Columns are matched to fields by name, according to [ClientRequestProperties::case_mapping], which defaults to an exact match.
+    /// Rows with the same key are handled according to `duplicates`. See [DataTable::rows_keyed_by].
+    pub async fn execute_query_keyed_by<K, T>(
+        &self,
+        database: impl Into<String>,
+        query: impl Into<String>,
+        key_column: &str,
+        duplicates: DuplicateKeys,
+        client_request_properties: Option<ClientRequestProperties>,
+    ) -> Result<HashMap<K, T>>
+    where
+        K: DeserializeOwned + Eq + Hash,
+        T: DeserializeOwned,
+    {
+        let case_mapping = client_request_properties
+            .as_ref()
+            .and_then(|p| p.case_mapping)
+            .unwrap_or_default();
+
+        let response = self
+            .execute_query(database, query, client_request_properties)
+            .await?;
+
+        let results = response.into_single_primary_result()?;
+
+        results.rows_keyed_by(key_column, case_mapping, duplicates)
+    }
+
+    /// Execute a KQL query into `arrow` record batches, one for each primary result table.
+    /// To learn more about KQL go to [https://docs.microsoft.com/en-us/azure/kusto/query/](https://docs.microsoft.com/en-us/azure/kusto/query)
+    ///
+    /// Fails with the first error that occurs while converting a table.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use azure_kusto_data::prelude::*;
+    /// # #[tokio::main] async fn main() -> Result<(), Error> {
+    /// let client = KustoClient::new(
+    ///    ConnectionString::with_default_auth("https://mycluster.region.kusto.windows.net/"),
+    ///    KustoClientOptions::default())?;
+    ///
+    /// let batches = client.execute_query_to_arrow("some_database", "MyTable | take 10", None).await?;
+    ///
+    /// for batch in batches {
+    ///     println!("{} rows, schema: {:?}", batch.num_rows(), batch.schema());
+    /// }
+    /// # Ok(())}
+    /// ```
+    #[cfg(feature = "arrow")]
+    pub async fn execute_query_to_arrow(
+        &self,
+        database: impl Into<String>,
+        query: impl Into<String>,
+        client_request_properties: Option<ClientRequestProperties>,
+    ) -> Result<Vec<RecordBatch>> {
+        self.execute_query(database, query, client_request_properties)
+            .await?
+            .into_record_batches()
+            .collect()
+    }
+
+    /// Execute a KQL query into an array of structs, deserialized column by column from an `arrow` record batch of its primary result,
+    /// which is faster than [execute_query_to_struct](#method.execute_query_to_struct) for large results.
+    ///
+    /// The columns are matched to fields by name, and are deserialized from their `arrow` types,
+    /// as described in [KustoResponseDataSetV2::into_structs_via_arrow](crate::prelude::KustoResponseDataSetV2::into_structs_via_arrow).
+    ///
+    /// # Example
+    /// ```no_run
+    /// use azure_kusto_data::prelude::*;
+    ///
+    /// #[derive(serde::Deserialize, Debug)]
+    /// struct Event {
+    ///    name: String,
+    ///    timestamp: i64,
+    /// }
+    ///
+    /// # #[tokio::main] async fn main() -> Result<(), Error> {
+    /// let client = KustoClient::new(
+    ///    ConnectionString::with_default_auth("https://mycluster.region.kusto.windows.net/"),
+    ///    KustoClientOptions::default())?;
+    ///
+    /// let events: Vec<Event> = client
+    ///     .execute_query_to_struct_via_arrow("some_database", "Events | project name, timestamp", None)
+    ///     .await?;
+    /// # Ok(())}
+    /// ```
+    #[cfg(feature = "arrow")]
+    pub async fn execute_query_to_struct_via_arrow<T: DeserializeOwned>(
+        &self,
+        database: impl Into<String>,
+        query: impl Into<String>,
+        client_request_properties: Option<ClientRequestProperties>,
+    ) -> Result<Vec<T>> {
+        self.execute_query(database, query, client_request_properties)
+            .await?
+            .into_structs_via_arrow()
+    }
+
+    /// Retrieve the schema of a KQL query's primary result as an `arrow` [Schema], without retrieving its data.
+    /// The query is run with [query_results_apply_getschema](crate::request_options::Options::query_results_apply_getschema) set,
+    /// and its columns are mapped to the same arrow types as in [execute_query_to_arrow](#method.execute_query_to_arrow).
+    ///
+    /// # Example
+    /// ```no_run
+    /// use azure_kusto_data::prelude::*;
+    /// # #[tokio::main] async fn main() -> Result<(), Error> {
+    /// let client = KustoClient::new(
+    ///    ConnectionString::with_default_auth("https://mycluster.region.kusto.windows.net/"),
+    ///    KustoClientOptions::default())?;
+    ///
+    /// let schema = client.get_query_schema("some_database", "MyTable | take 10").await?;
+    ///
+    /// for field in schema.fields() {
+    ///     println!("{}: {}", field.name(), field.data_type());
+    /// }
+    /// # Ok(())}
+    /// ```
+    #[cfg(feature = "arrow")]
+    pub async fn get_query_schema(
+        &self,
+        database: impl Into<String>,
+        query: impl Into<String>,
+    ) -> Result<Schema> {
+        let mut options = Options::default();
+        options.query_results_apply_getschema = Some(true);
+
+        let table = self
+            .execute_query(
+                database,
+                query,
+                Some(ClientRequestProperties::from(options)),
+            )
+            .await?
+            .into_single_primary_result()?;
 
-        Ok(serde_json::from_value::<Vec<T>>(serde_json::Value::Array(
-            results.rows,
-        ))?)
+        convert_schema_table(table)
     }
 
     /// Execute a management command with additional options.
@@ -279,7 +1547,133 @@ impl KustoClient {
         query: impl Into<String>,
         options: Option<ClientRequestProperties>,
     ) -> V1QueryRunner {
-        V1QueryRunner(self.execute_with_options(database, query, QueryKind::Management, options))
+        V1QueryRunner(self.query_runner(
+            Some(database.into()),
+            query,
+            QueryKind::Management,
+            options,
+        ))
+    }
+
+    /// Execute a management command against the [default database](Self::default_database), set by the initial catalog of the connection string.
+    /// Fails with [Error::QueryError] when the connection string has no initial catalog.
+    #[must_use]
+    pub fn execute_command_on_default_db(
+        &self,
+        query: impl Into<String>,
+        options: Option<ClientRequestProperties>,
+    ) -> V1QueryRunner {
+        V1QueryRunner(self.query_runner(None, query, QueryKind::Management, options))
+    }
+
+    /// Execute a management command that applies to the whole cluster, such as `.show cluster`, rather than to a database.
+    /// It runs against the [default database](Self::default_database) when the connection string has one, and otherwise against `NetDefaultDB`,
+    /// the database the service runs such commands in when none is named.
+    #[must_use]
+    pub fn execute_cluster_command(
+        &self,
+        query: impl Into<String>,
+        options: Option<ClientRequestProperties>,
+    ) -> V1QueryRunner {
+        let database = self
+            .default_database()
+            .unwrap_or(CLUSTER_COMMANDS_DATABASE)
+            .to_string();
+        self.execute_command(database, query, options)
+    }
+
+    /// Execute a management command into an array of structs, deserialized from the rows of the first table of its response.
+    /// To learn more about see [commands](https://docs.microsoft.com/en-us/azure/data-explorer/kusto/management/)
+    ///
+    /// Columns are matched to fields by name, exactly unless [ClientRequestProperties::case_mapping] is set.
+    /// As most commands have PascalCase columns, e.g. `TableName`, either rename the fields of the struct,
+    /// or use [PascalToSnake](crate::deserialization::CaseMapping::PascalToSnake) to match them to snake_case fields.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use azure_kusto_data::prelude::*;
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Deserialize, Debug)]
+    /// #[serde(rename_all = "PascalCase")]
+    /// struct Table {
+    ///    table_name: String,
+    ///    database_name: String,
+    /// }
+    ///
+    /// # #[tokio::main] async fn main() -> Result<(), Error> {
+    /// let client = KustoClient::new(
+    ///    ConnectionString::with_default_auth("https://mycluster.region.kusto.windows.net/"),
+    ///    KustoClientOptions::default())?;
+    ///
+    ///    let tables: Vec<Table> = client.execute_command_to_struct("some_database", ".show tables", None).await?;
+    ///    println!("{:?}", tables);
+    ///
+    /// # Ok(())}
+    /// ```
+    pub async fn execute_command_to_struct<T: DeserializeOwned>(
+        &self,
+        database: impl Into<String>,
+        command: impl Into<String>,
+        client_request_properties: Option<ClientRequestProperties>,
+    ) -> Result<Vec<T>> {
+        let case_mapping = client_request_properties
+            .as_ref()
+            .and_then(|p| p.case_mapping)
+            .unwrap_or_default();
+
+        let response = self
+            .execute_command(database, command, client_request_properties)
+            .await?;
+
+        let table = response
+            .tables
+            .into_iter()
+            .next()
+            .ok_or_else(|| Error::QueryError("No results found for the command".into()))?;
+
+        deserialize_table_v1(table, case_mapping)
+    }
+
+    /// Ingests data directly into a table with streaming ingestion, returning once the data is ingested.
+    ///
+    /// Streaming ingestion must be enabled on the cluster, and the table or database must have a streaming ingestion policy.
+    /// The client must point to the engine endpoint of the cluster, not to its ingestion endpoint.
+    /// Streaming ingestion is meant for small amounts of data with low latency requirements - for large amounts of data,
+    /// or when streaming ingestion is not enabled, use the queued ingestion of the `azure-kusto-ingest` crate.
+    ///
+    /// `format` is the name of the data format, e.g. `csv` or `json`, and `mapping_name` the name of an ingestion mapping defined on the table.
+    /// # Example
+    /// ```no_run
+    /// use azure_kusto_data::prelude::*;
+    /// # #[tokio::main] async fn main() -> Result<(), Error> {
+    ///
+    /// let client = KustoClient::new(
+    ///    ConnectionString::with_default_auth("https://mycluster.region.kusto.windows.net/"),
+    ///    KustoClientOptions::default())?;
+    ///
+    /// let data = StreamingIngestSource::Data { data: "1,first\n2,second\n".into(), compressed: false };
+    /// client.execute_streaming_ingest("some_database", "some_table", data, "csv", None, None).await?;
+    /// # Ok(())}
+    /// ```
+    pub async fn execute_streaming_ingest(
+        &self,
+        database: &str,
+        table: &str,
+        source: StreamingIngestSource,
+        format: &str,
+        mapping_name: Option<&str>,
+        options: Option<ClientRequestProperties>,
+    ) -> Result<KustoResponseDataSetV1> {
+        let url = streaming_ingest_url(
+            &self.ingest_url,
+            database,
+            table,
+            format,
+            mapping_name,
+            &source,
+        )?;
+        execute_streaming_ingest(self, url, source, options).await
     }
 }
 
@@ -15,8 +15,12 @@ pub(crate) struct ClientDetails {
 impl ClientDetails {
     pub(crate) fn new(application: Option<String>, user: Option<String>) -> Self {
         ClientDetails {
-            application: application.unwrap_or_else(|| DEFAULT_APPLICATION.to_string()),
-            user: user.unwrap_or_else(|| DEFAULT_USER.to_string()),
+            application: application
+                .map(|application| to_header_safe(&application).into_owned())
+                .unwrap_or_else(|| DEFAULT_APPLICATION.to_string()),
+            user: user
+                .map(|user| to_header_safe(&user).into_owned())
+                .unwrap_or_else(|| DEFAULT_USER.to_string()),
             version: DEFAULT_VERSION.to_string(),
         }
     }
@@ -27,20 +31,38 @@ static NONE: &str = "[none]";
 
 static ESCAPE_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new("[\\r\\n\\s{}|]+").unwrap());
 
+/// Replaces the characters that can't be sent in a header value - anything but printable ASCII - with `_`.
+/// The environment may hold any unicode, so values derived from it must go through this before being sent.
+pub(crate) fn to_header_safe(s: &str) -> Cow<'_, str> {
+    let is_safe = |c: char| c.is_ascii_graphic() || c == ' ';
+    if s.chars().all(is_safe) {
+        Cow::Borrowed(s)
+    } else {
+        s.chars()
+            .map(|c| if is_safe(c) { c } else { '_' })
+            .collect::<String>()
+            .into()
+    }
+}
+
 static DEFAULT_USER: Lazy<String> = Lazy::new(|| {
     let domain = std::env::var("USERDOMAIN");
     let user = std::env::var("USERNAME");
-    match (domain, user) {
+    let user = match (domain, user) {
         (Ok(domain), Ok(user)) => format!("{}\\{}", domain, user),
         (Err(_), Ok(user)) => user,
         _ => UNKNOWN.to_string(),
-    }
+    };
+    to_header_safe(&user).into_owned()
 });
 
 static DEFAULT_APPLICATION: Lazy<String> = Lazy::new(|| {
     std::env::current_exe()
         .ok()
-        .and_then(|x| x.file_name().map(|x| x.to_string_lossy().to_string()))
+        .and_then(|x| {
+            x.file_name()
+                .map(|x| to_header_safe(&x.to_string_lossy()).into_owned())
+        })
         .unwrap_or_else(|| UNKNOWN.to_string())
 });
 
@@ -54,13 +76,16 @@ static DEFAULT_VERSION: Lazy<String> = Lazy::new(|| {
 
 fn format_header<'a, T: IntoIterator<Item = (Cow<'a, str>, Cow<'a, str>)>>(args: T) -> String {
     args.into_iter()
-        .map(|(k, v)| format!("{}:{}", k, escape_value(v)))
+        .map(|(k, v)| format!("{}:{}", to_header_safe(&k), escape_value(v)))
         .collect::<Vec<_>>()
         .join("|")
 }
 
 fn escape_value(s: Cow<str>) -> String {
-    format!("{{{}}}", ESCAPE_REGEX.replace_all(s.as_ref(), "_"))
+    format!(
+        "{{{}}}",
+        ESCAPE_REGEX.replace_all(&to_header_safe(s.as_ref()), "_")
+    )
 }
 
 pub(crate) fn set_connector_details(details: ConnectorDetails) -> (String, String) {
@@ -101,11 +126,15 @@ pub(crate) fn set_connector_details(details: ConnectorDetails) -> (String, Strin
         NONE
     };
 
-    (format_header(fields), user.to_string())
+    (format_header(fields), to_header_safe(user).into_owned())
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Eq, derive_builder::Builder)]
-#[builder(setter(into, strip_option, prefix = "with"), default)]
+#[builder(
+    setter(into, strip_option, prefix = "with"),
+    default,
+    build_fn(validate = "Self::validate")
+)]
 /// Connector details for tracing.
 pub struct ConnectorDetails<'a> {
     /// Connector name.
@@ -124,6 +153,23 @@ pub struct ConnectorDetails<'a> {
     additional_fields: Vec<(&'a str, &'a str)>,
 }
 
+impl<'a> ConnectorDetailsBuilder<'a> {
+    /// The connector name and version are chosen by the connector's authors, so unlike values from the environment,
+    /// they are rejected rather than silently rewritten when they can't be sent in a header.
+    fn validate(&self) -> Result<(), String> {
+        for (field, value) in [("name", self.name), ("version", self.version)] {
+            if let Some(value) = value {
+                if to_header_safe(value) != value {
+                    return Err(format!(
+                        "Connector {field} must only contain printable ASCII characters, got '{value}'"
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -137,6 +183,24 @@ mod tests {
         assert_eq!(escape_value("ab_c".into()), "{ab_c}");
         assert_eq!(escape_value("ab|c".into()), "{ab_c}");
         assert_eq!(escape_value("ab{}c".into()), "{ab_c}");
+        assert_eq!(escape_value("ab\tc".into()), "{ab_c}");
+        assert_eq!(escape_value("прога.exe".into()), "{_____.exe}");
+        assert_eq!(escape_value("naïve🦀".into()), "{na_ve_}");
+    }
+
+    #[test]
+    fn test_to_header_safe() {
+        assert!(matches!(to_header_safe("my app"), Cow::Borrowed("my app")));
+        assert_eq!(to_header_safe("DOMAIN\\jürgen"), "DOMAIN\\j_rgen");
+        assert_eq!(to_header_safe("a\u{0}b\u{7f}c"), "a_b_c");
+    }
+
+    #[test]
+    fn test_format_header_non_ascii() {
+        assert_eq!(
+            format_header(vec![("ключ".into(), "значение".into())]),
+            "____:{________}"
+        );
     }
 
     #[test]
@@ -180,6 +244,13 @@ mod tests {
             }
         );
 
+        let client_details = ClientDetails::new(
+            Some("アプリ".to_string()),
+            Some("DOMAIN\\jürgen".to_string()),
+        );
+        assert_eq!(client_details.application, "___");
+        assert_eq!(client_details.user, "DOMAIN\\j_rgen");
+
         let client_details =
             ClientDetails::new(Some("my_app".to_string()), Some("my_user".to_string()));
         assert_eq!(
@@ -255,6 +326,46 @@ mod tests {
         assert_eq!(header, expected_header);
     }
 
+    #[test]
+    fn test_set_connector_details_non_ascii() {
+        let details = ConnectorDetailsBuilder::default()
+            .with_name("MyConnector")
+            .with_version("1.0")
+            .with_send_user(true)
+            .with_override_user("DOMAIN\\jürgen")
+            .with_app_name("データ取込.exe")
+            .with_app_version("1.0.1")
+            .with_additional_fields(vec![("clé", "valeur 🦀")])
+            .build()
+            .unwrap();
+
+        let (header, user) = set_connector_details(details);
+
+        assert_eq!(
+            header,
+            "Kusto.MyConnector:{1.0}|App.{_____.exe}:{1.0.1}|cl_:{valeur__}"
+        );
+        assert_eq!(user, "DOMAIN\\j_rgen");
+        assert!(header.is_ascii());
+    }
+
+    #[test]
+    fn test_connector_details_validation() {
+        let error = ConnectorDetailsBuilder::default()
+            .with_name("Connecteur·Kusto")
+            .with_version("1.0")
+            .build()
+            .unwrap_err();
+        assert!(error.to_string().contains("Connector name"));
+
+        let error = ConnectorDetailsBuilder::default()
+            .with_name("MyConnector")
+            .with_version("1.0\n")
+            .build()
+            .unwrap_err();
+        assert!(error.to_string().contains("Connector version"));
+    }
+
     #[test]
     fn test_set_connector_details_no_app_name() {
         let details = ConnectorDetailsBuilder::default()
@@ -10,6 +10,9 @@ use hashbrown::HashMap;
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 
+use crate::cloud_info_disk_cache::CloudInfoDiskCache;
+use crate::operations::compression::decompress_body;
+
 static CLOUDINFO_CACHE: Lazy<Mutex<HashMap<String, CloudInfo>>> =
     Lazy::new(|| Mutex::new(HashMap::new()));
 
@@ -39,6 +42,16 @@ struct AzureAd {
 
 impl Default for CloudInfo {
     fn default() -> Self {
+        Self::azure_public()
+    }
+}
+
+impl CloudInfo {
+    const METADATA_ENDPOINT: &'static str = "v1/rest/auth/metadata";
+
+    /// The cloud info of the public Azure cloud, which is also the [default](CloudInfo::default).
+    #[must_use]
+    pub fn azure_public() -> Self {
         Self {
             login_mfa_required: false,
             login_endpoint: "https://login.microsoftonline.com".into(),
@@ -49,10 +62,34 @@ impl Default for CloudInfo {
                 "https://login.microsoftonline.com/f8cdef31-a31e-4b4a-93e4-5f571e91255a".into(),
         }
     }
-}
 
-impl CloudInfo {
-    const METADATA_ENDPOINT: &'static str = "v1/rest/auth/metadata";
+    /// The cloud info of Azure US Government.
+    #[must_use]
+    pub fn azure_us_government() -> Self {
+        Self {
+            login_mfa_required: false,
+            login_endpoint: "https://login.microsoftonline.us".into(),
+            kusto_client_app_id: "730ea9e6-1e1d-480c-9df6-0bb9a90e1a0f".into(),
+            kusto_client_redirect_uri: "https://microsoft/kustoclient".into(),
+            kusto_service_resource_id: "https://kusto.kusto.usgovcloudapi.net".into(),
+            first_party_authority_url:
+                "https://login.microsoftonline.us/f8cdef31-a31e-4b4a-93e4-5f571e91255a".into(),
+        }
+    }
+
+    /// The cloud info of Azure China, operated by 21Vianet.
+    #[must_use]
+    pub fn azure_china() -> Self {
+        Self {
+            login_mfa_required: false,
+            login_endpoint: "https://login.chinacloudapi.cn".into(),
+            kusto_client_app_id: "db662dc1-0cfe-4e1c-a843-19a68e65be58".into(),
+            kusto_client_redirect_uri: "https://microsoft/kustoclient".into(),
+            kusto_service_resource_id: "https://kusto.kusto.chinacloudapi.cn".into(),
+            first_party_authority_url:
+                "https://login.chinacloudapi.cn/a55a4d5b-9241-49b1-b4ff-befa8db00269".into(),
+        }
+    }
 
     async fn fetch(pipeline: &Pipeline, endpoint: &str) -> Result<CloudInfo, crate::error::Error> {
         let metadata_endpoint = format!("{}/{}", endpoint, CloudInfo::METADATA_ENDPOINT);
@@ -63,17 +100,17 @@ impl CloudInfo {
         request.insert_headers(&Accept::from("application/json"));
         request.insert_headers(&AcceptEncoding::from("gzip, deflate"));
         let response = pipeline.send(&Context::new(), &mut request).await?;
-        let (status_code, _header_map, pinned_stream) = response.deconstruct();
+        let (status_code, header_map, pinned_stream) = response.deconstruct();
         match status_code {
             StatusCode::Ok => {
-                let data = pinned_stream.collect().await?;
+                let data = decompress_body(&header_map, pinned_stream.collect().await?)?;
                 let result: AzureAd = serde_json::from_slice(&data)?;
                 Ok(result.azure_ad)
             }
             StatusCode::NotFound => Ok(Default::default()),
-            _ => Err(crate::error::Error::HttpError(
+            _ => Err(crate::error::Error::from_http_body(
                 status_code,
-                String::from_utf8_lossy((pinned_stream).collect().await?.as_ref()).to_string(),
+                &pinned_stream.collect().await?,
             )),
         }
     }
@@ -83,12 +120,39 @@ impl CloudInfo {
         pipeline: &Pipeline,
         endpoint: &str,
     ) -> Result<CloudInfo, crate::error::Error> {
-        Ok(match CLOUDINFO_CACHE.lock().await.entry_ref(endpoint) {
-            EntryRef::Occupied(o) => o.get().clone(),
-            EntryRef::Vacant(e) => {
+        CloudInfo::get_with_disk_cache(pipeline, endpoint, None).await
+    }
+
+    /// Same as [CloudInfo::get], but looks for the metadata in the disk cache before fetching it, and writes fetched metadata to it.
+    /// The disk cache is read and written without holding the lock of the in-memory cache, so that other endpoints are not held up by it.
+    pub(crate) async fn get_with_disk_cache(
+        pipeline: &Pipeline,
+        endpoint: &str,
+        disk_cache: Option<&CloudInfoDiskCache>,
+    ) -> Result<CloudInfo, crate::error::Error> {
+        if let Some(cached) = CloudInfo::get_from_cache(endpoint).await {
+            return Ok(cached);
+        }
+
+        let cached = match disk_cache {
+            Some(cache) => cache.load_async(endpoint).await,
+            None => None,
+        };
+        let result = match cached {
+            Some(cached) => cached,
+            None => {
                 let result = CloudInfo::fetch(pipeline, endpoint).await?;
-                e.insert(result).clone()
+                if let Some(cache) = disk_cache {
+                    cache.store_async(endpoint, &result).await;
+                }
+                result
             }
+        };
+
+        // Another request may have resolved the endpoint meanwhile, in which case its cloud info is kept
+        Ok(match CLOUDINFO_CACHE.lock().await.entry_ref(endpoint) {
+            EntryRef::Occupied(o) => o.get().clone(),
+            EntryRef::Vacant(e) => e.insert(result).clone(),
         })
     }
 
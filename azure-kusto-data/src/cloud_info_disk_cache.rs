@@ -0,0 +1,281 @@
+//! A cache of [CloudInfo] on disk, shared by the processes of a machine so that each of them does not fetch the metadata on startup.
+//! See [KustoClientOptions::with_cloud_info_disk_cache](crate::client::KustoClientOptions::with_cloud_info_disk_cache).
+//!
+//! The cache is a best effort - any error reading or writing it, including a corrupted file, falls back to fetching the metadata.
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use azure_core::Url;
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::cloud_info::CloudInfo;
+use crate::utils::run_blocking;
+
+/// The cloud info of an endpoint, and when it stops being valid
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct CacheEntry {
+    cloud_info: CloudInfo,
+    /// Seconds since the unix epoch
+    expires_at: u64,
+}
+
+/// The contents of the cache file, keyed by the [normalized](normalize_endpoint) endpoint
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct CacheFile {
+    entries: HashMap<String, CacheEntry>,
+}
+
+/// A cache of [CloudInfo] in a JSON file, whose entries expire after a time to live.
+#[derive(Debug, Clone)]
+pub(crate) struct CloudInfoDiskCache {
+    path: PathBuf,
+    ttl: Duration,
+}
+
+impl CloudInfoDiskCache {
+    pub(crate) fn new(path: PathBuf, ttl: Duration) -> Self {
+        Self { path, ttl }
+    }
+
+    /// Reads the cloud info of the endpoint, if it is cached and has not expired
+    pub(crate) fn load(&self, endpoint: &str) -> Option<CloudInfo> {
+        self.load_at(endpoint, SystemTime::now())
+    }
+
+    /// Writes the cloud info of the endpoint, ignoring any error, as the cloud info can always be fetched again
+    pub(crate) fn store(&self, endpoint: &str, cloud_info: &CloudInfo) {
+        let _ = self.store_at(endpoint, cloud_info, SystemTime::now());
+    }
+
+    /// Same as [load](Self::load), without blocking the executor on the file lock and read.
+    pub(crate) async fn load_async(&self, endpoint: &str) -> Option<CloudInfo> {
+        let cache = self.clone();
+        let endpoint = endpoint.to_string();
+        run_blocking(move || cache.load(&endpoint)).await
+    }
+
+    /// Same as [store](Self::store), without blocking the executor on the file lock and write.
+    pub(crate) async fn store_async(&self, endpoint: &str, cloud_info: &CloudInfo) {
+        let cache = self.clone();
+        let endpoint = endpoint.to_string();
+        let cloud_info = cloud_info.clone();
+        run_blocking(move || cache.store(&endpoint, &cloud_info)).await;
+    }
+
+    fn load_at(&self, endpoint: &str, now: SystemTime) -> Option<CloudInfo> {
+        let cache: CacheFile = serde_json::from_slice(&read_locked(&self.path).ok()?).ok()?;
+        cache
+            .entries
+            .get(&normalize_endpoint(endpoint))
+            .filter(|entry| entry.expires_at > unix_seconds(now))
+            .map(|entry| entry.cloud_info.clone())
+    }
+
+    fn store_at(&self, endpoint: &str, cloud_info: &CloudInfo, now: SystemTime) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let now = unix_seconds(now);
+        let entry = CacheEntry {
+            cloud_info: cloud_info.clone(),
+            expires_at: now.saturating_add(self.ttl.as_secs()),
+        };
+        update_locked(&self.path, |contents| {
+            // A corrupted file is replaced rather than failing the write
+            let mut cache: CacheFile = serde_json::from_slice(contents).unwrap_or_default();
+            cache.entries.retain(|_, entry| entry.expires_at > now);
+            cache.entries.insert(normalize_endpoint(endpoint), entry);
+            Ok(serde_json::to_vec(&cache)?)
+        })
+    }
+}
+
+/// Endpoints are cached by origin, so that `https://Cluster.kusto.windows.net/` and `https://cluster.kusto.windows.net` share an entry
+fn normalize_endpoint(endpoint: &str) -> String {
+    match Url::parse(endpoint) {
+        Ok(url) => url.origin().ascii_serialization(),
+        Err(_) => endpoint.trim_end_matches('/').to_lowercase(),
+    }
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+/// Reads the file under a shared lock, so that it is not read while another process writes it
+#[cfg(not(target_arch = "wasm32"))]
+fn read_locked(path: &Path) -> io::Result<Vec<u8>> {
+    use std::io::Read;
+
+    let lock = fd_lock::RwLock::new(std::fs::File::open(path)?);
+    let guard = lock.read()?;
+    let mut contents = Vec::new();
+    (&*guard).read_to_end(&mut contents)?;
+    Ok(contents)
+}
+
+/// Replaces the contents of the file under an exclusive lock, so that concurrent processes don't interleave their writes
+#[cfg(not(target_arch = "wasm32"))]
+fn update_locked(path: &Path, update: impl FnOnce(&[u8]) -> io::Result<Vec<u8>>) -> io::Result<()> {
+    use std::io::{Read, Seek, SeekFrom, Write};
+
+    let file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)?;
+    let mut lock = fd_lock::RwLock::new(file);
+    let mut guard = lock.write()?;
+
+    let mut contents = Vec::new();
+    guard.read_to_end(&mut contents)?;
+    let contents = update(&contents)?;
+
+    guard.set_len(0)?;
+    guard.seek(SeekFrom::Start(0))?;
+    guard.write_all(&contents)?;
+    guard.flush()
+}
+
+// There is no file system in WebAssembly, so the metadata is always fetched
+#[cfg(target_arch = "wasm32")]
+fn read_locked(_path: &Path) -> io::Result<Vec<u8>> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn update_locked(
+    _path: &Path,
+    _update: impl FnOnce(&[u8]) -> io::Result<Vec<u8>>,
+) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENDPOINT: &str = "https://cluster.kusto.windows.net";
+
+    /// A cache in a file of its own, deleted when dropped
+    struct TempCache(CloudInfoDiskCache);
+
+    impl TempCache {
+        fn new(ttl: Duration) -> Self {
+            let path = std::env::temp_dir()
+                .join(format!("kusto-cloud-info-{}.json", uuid::Uuid::new_v4()));
+            Self(CloudInfoDiskCache::new(path, ttl))
+        }
+    }
+
+    impl Drop for TempCache {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0.path);
+        }
+    }
+
+    fn cloud_info() -> CloudInfo {
+        CloudInfo {
+            login_mfa_required: true,
+            ..CloudInfo::default()
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let cache = TempCache::new(Duration::from_secs(60));
+
+        assert_eq!(cache.0.load(ENDPOINT), None);
+        cache.0.store(ENDPOINT, &cloud_info());
+
+        assert_eq!(cache.0.load(ENDPOINT), Some(cloud_info()));
+        assert_eq!(
+            cache.0.load("https://CLUSTER.kusto.windows.net/"),
+            Some(cloud_info())
+        );
+        assert_eq!(cache.0.load("https://other.kusto.windows.net"), None);
+    }
+
+    #[test]
+    fn entries_expire() {
+        let cache = TempCache::new(Duration::from_secs(60));
+        let now = SystemTime::now();
+        cache
+            .0
+            .store_at(ENDPOINT, &cloud_info(), now)
+            .expect("Failed to write cache");
+
+        assert_eq!(
+            cache.0.load_at(ENDPOINT, now + Duration::from_secs(59)),
+            Some(cloud_info())
+        );
+        assert_eq!(
+            cache.0.load_at(ENDPOINT, now + Duration::from_secs(60)),
+            None
+        );
+
+        // Expired entries are dropped when another entry is written
+        let other = "https://other.kusto.windows.net";
+        cache
+            .0
+            .store_at(other, &CloudInfo::default(), now + Duration::from_secs(120))
+            .expect("Failed to write cache");
+        let contents: CacheFile =
+            serde_json::from_slice(&std::fs::read(&cache.0.path).unwrap()).unwrap();
+        assert_eq!(
+            contents.entries.keys().collect::<Vec<_>>(),
+            vec![&other.to_string()]
+        );
+    }
+
+    #[test]
+    fn corrupted_files_are_replaced() {
+        let cache = TempCache::new(Duration::from_secs(60));
+        std::fs::write(&cache.0.path, b"{\"entries\": {\"https://clu").unwrap();
+
+        assert_eq!(cache.0.load(ENDPOINT), None);
+
+        cache.0.store(ENDPOINT, &cloud_info());
+        assert_eq!(cache.0.load(ENDPOINT), Some(cloud_info()));
+    }
+
+    #[test]
+    fn unwritable_caches_are_ignored() {
+        let cache = TempCache::new(Duration::from_secs(60));
+        // A directory can't be opened as a cache file
+        std::fs::create_dir(&cache.0.path).unwrap();
+
+        cache.0.store(ENDPOINT, &cloud_info());
+        assert_eq!(cache.0.load(ENDPOINT), None);
+
+        std::fs::remove_dir(&cache.0.path).unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn concurrent_access() {
+        let cache = TempCache::new(Duration::from_secs(60));
+
+        let tasks = (0..2).map(|task| {
+            let cache = cache.0.clone();
+            tokio::spawn(async move {
+                let endpoint = format!("https://cluster{task}.kusto.windows.net");
+                for _ in 0..50 {
+                    cache.store(&endpoint, &cloud_info());
+                    assert_eq!(cache.load(&endpoint), Some(cloud_info()));
+                }
+            })
+        });
+        for task in tasks.collect::<Vec<_>>() {
+            task.await.expect("Task panicked");
+        }
+
+        let contents: CacheFile =
+            serde_json::from_slice(&std::fs::read(&cache.0.path).unwrap()).unwrap();
+        assert_eq!(contents.entries.len(), 2);
+    }
+}
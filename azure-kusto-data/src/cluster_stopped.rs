@@ -0,0 +1,190 @@
+//! Detection of queries that fail because their cluster is stopped, and the hook to act on it, such as by starting the cluster.
+//!
+//! Such failures are returned as [Error::ClusterStopped], and reported to the callback set with
+//! [KustoClientOptions::with_cluster_stopped_handler](crate::client::KustoClientOptions::with_cluster_stopped_handler).
+
+use std::fmt::{Debug, Formatter};
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::error::Error;
+use crate::models::OneApiError;
+
+/// Called with the URL of the cluster when queries fail because it is stopped.
+pub type ClusterStoppedCallback = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// The error codes the service reports for stopped clusters.
+const STOPPED_ERROR_CODES: &[&str] = &["ClusterStopped", "ClusterSuspended"];
+
+/// Phrases of the messages the service reports for stopped clusters, in lowercase.
+const STOPPED_MESSAGES: &[&str] = &[
+    "cluster is stopped",
+    "cluster is suspended",
+    "is in state 'stopped'",
+    "is in state 'suspended'",
+];
+
+/// Whether an error reported by the service, with the given code and message, means that the cluster is stopped.
+pub(crate) fn is_cluster_stopped(code: Option<&str>, message: Option<&str>) -> bool {
+    code.map_or(false, |code| {
+        STOPPED_ERROR_CODES
+            .iter()
+            .any(|c| c.eq_ignore_ascii_case(code))
+    }) || message.map_or(false, |message| {
+        let message = message.to_lowercase();
+        STOPPED_MESSAGES.iter().any(|m| message.contains(m))
+    })
+}
+
+/// Converts the errors that mean that the cluster is stopped into [Error::ClusterStopped], leaving the others as they are.
+pub(crate) fn classify(error: Error, cluster: &str) -> Error {
+    let stopped = match &error {
+        Error::AzureError(e) => e.as_http_error().map_or(false, |e| {
+            is_cluster_stopped(e.error_code(), e.error_message())
+        }),
+        Error::QueryApiError(e) => {
+            is_cluster_stopped(Some(&e.error_message.code), Some(&e.error_message.message))
+        }
+        _ => false,
+    };
+    if stopped {
+        Error::ClusterStopped {
+            cluster: cluster.to_string(),
+        }
+    } else {
+        error
+    }
+}
+
+/// The first of the errors reported within a response that means that the cluster is stopped, as an error to [classify].
+pub(crate) fn stopped_error_in(errors: &[OneApiError]) -> Option<Error> {
+    errors
+        .iter()
+        .find(|e| is_cluster_stopped(Some(&e.error_message.code), Some(&e.error_message.message)))
+        .map(|e| Error::QueryApiError(Box::new(e.clone())))
+}
+
+/// Calls the callback once for each time the cluster is found stopped: failures are reported until the next successful request.
+pub(crate) struct ClusterStoppedHandler {
+    callback: ClusterStoppedCallback,
+    reported: AtomicBool,
+}
+
+impl Debug for ClusterStoppedHandler {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClusterStoppedHandler")
+            .field("reported", &self.reported)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ClusterStoppedHandler {
+    pub(crate) fn new(callback: ClusterStoppedCallback) -> Self {
+        Self {
+            callback,
+            reported: AtomicBool::new(false),
+        }
+    }
+
+    /// Reports that a request failed because the cluster is stopped, unless it was already reported.
+    pub(crate) fn stopped(&self, cluster: &str) {
+        if !self.reported.swap(true, Ordering::AcqRel) {
+            let callback = &self.callback;
+            let _ = std::panic::catch_unwind(AssertUnwindSafe(|| callback(cluster)));
+        }
+    }
+
+    /// Records that a request succeeded, so that the cluster being stopped again is reported.
+    pub(crate) fn succeeded(&self) {
+        self.reported.store(false, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use azure_core::error::{ErrorKind, HttpError};
+    use azure_core::headers::Headers;
+    use azure_core::{BytesStream, Response, StatusCode};
+    use std::sync::atomic::AtomicUsize;
+
+    async fn http_error(status: StatusCode, body: &'static str) -> Error {
+        let response = Response::new(status, Headers::new(), Box::pin(BytesStream::new(body)));
+        let error = HttpError::new(response).await;
+        let kind = ErrorKind::http_response(status, error.error_code().map(str::to_string));
+        Error::AzureError(azure_core::Error::new(kind, error))
+    }
+
+    #[tokio::test]
+    async fn stopped_clusters_are_detected_from_the_error_body() {
+        for body in [
+            r#"{"error": {"code": "ClusterSuspended", "message": "Request is invalid and cannot be executed."}}"#,
+            r#"{"error": {"code": "BadRequest", "message": "Cluster 'mycluster' is in state 'Stopped', start it to run queries."}}"#,
+            r#"{"code": "clusterstopped", "message": "The cluster is not running"}"#,
+        ] {
+            let error = classify(
+                http_error(StatusCode::BadRequest, body).await,
+                "https://mycluster",
+            );
+            assert!(
+                matches!(&error, Error::ClusterStopped { cluster } if cluster == "https://mycluster"),
+                "Expected {body} to be detected, got {error:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn other_errors_are_left_as_they_are() {
+        for body in [
+            r#"{"error": {"code": "BadRequest", "message": "Syntax error: Query could not be parsed"}}"#,
+            r#"{"error": {"code": "LimitsExceeded", "message": "Query execution was stopped: E_RUNAWAY_QUERY"}}"#,
+            "Bad Request",
+        ] {
+            let error = classify(
+                http_error(StatusCode::BadRequest, body).await,
+                "https://mycluster",
+            );
+            assert!(
+                matches!(error, Error::AzureError(_)),
+                "Expected {body} not to be detected, got {error:?}"
+            );
+        }
+
+        let error = classify(
+            Error::QueryError("cluster is stopped".into()),
+            "https://mycluster",
+        );
+        assert!(matches!(error, Error::QueryError(_)));
+    }
+
+    #[test]
+    fn stopped_clusters_are_detected_from_exceptions() {
+        let exception: OneApiError = serde_json::from_str(
+            r#"{"error": {"code": "ClusterStopped", "message": "The cluster is stopped"}}"#,
+        )
+        .unwrap();
+        let error = classify(
+            Error::QueryApiError(Box::new(exception)),
+            "https://mycluster",
+        );
+        assert!(matches!(error, Error::ClusterStopped { .. }));
+    }
+
+    #[test]
+    fn the_handler_is_called_once_until_a_request_succeeds() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counted = calls.clone();
+        let handler = ClusterStoppedHandler::new(Arc::new(move |_| {
+            counted.fetch_add(1, Ordering::SeqCst);
+        }));
+
+        handler.stopped("https://mycluster");
+        handler.stopped("https://mycluster");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        handler.succeeded();
+        handler.stopped("https://mycluster");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}
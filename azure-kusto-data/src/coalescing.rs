@@ -0,0 +1,221 @@
+//! Coalescing of identical queries that run concurrently, so that they are sent to the service once and share its response.
+//! See [KustoClientOptions::with_request_coalescing](crate::client::KustoClientOptions::with_request_coalescing).
+use std::fmt::{Debug, Formatter};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+// The wasm32 transport is not `Send`, so neither are the futures that use it.
+#[cfg(not(target_arch = "wasm32"))]
+use futures::future::BoxFuture;
+#[cfg(target_arch = "wasm32")]
+use futures::future::LocalBoxFuture as BoxFuture;
+use futures::future::{Shared, WeakShared};
+use futures::FutureExt;
+use hashbrown::HashMap;
+use serde_json::Value;
+
+use crate::error::{Error, Result};
+use crate::operations::query::KustoResponseDataSetV2;
+use crate::request_options::ClientRequestProperties;
+
+type SharedResult = std::result::Result<KustoResponseDataSetV2, Arc<Error>>;
+type QueryFuture = BoxFuture<'static, SharedResult>;
+
+/// Identifies the queries that can share a response: those with the same database, text, options and parameters.
+/// The client request id and other properties that are only sent as headers are not part of the key.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct QueryKey {
+    database: String,
+    query: String,
+    properties: String,
+}
+
+impl QueryKey {
+    pub(crate) fn new(
+        database: &str,
+        query: &str,
+        client_request_properties: Option<&ClientRequestProperties>,
+    ) -> Self {
+        let properties = client_request_properties
+            .and_then(|p| serde_json::to_value(p).ok())
+            .map(|value| canonical_json(&value))
+            .unwrap_or_default();
+        Self {
+            database: database.to_string(),
+            query: query.to_string(),
+            properties,
+        }
+    }
+}
+
+/// Writes the value as JSON with the keys of objects sorted, so that properties built in a different order have the same key.
+fn canonical_json(value: &Value) -> String {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by_key(|(key, _)| *key);
+            let entries: Vec<String> = entries
+                .into_iter()
+                .map(|(key, value)| {
+                    format!("{}:{}", Value::from(key.as_str()), canonical_json(value))
+                })
+                .collect();
+            format!("{{{}}}", entries.join(","))
+        }
+        Value::Array(values) => {
+            let values: Vec<String> = values.iter().map(canonical_json).collect();
+            format!("[{}]", values.join(","))
+        }
+        other => other.to_string(),
+    }
+}
+
+#[derive(Default)]
+struct State {
+    /// Queries that are running, held weakly so that they are cancelled when all of their callers are dropped
+    in_flight: HashMap<QueryKey, WeakShared<QueryFuture>>,
+    /// Responses of queries that completed successfully, and when they did
+    completed: HashMap<QueryKey, (Instant, KustoResponseDataSetV2)>,
+}
+
+/// Shares the response of a running query with the identical queries that are executed while it runs,
+/// and with those executed within the reuse window after it completes.
+pub(crate) struct QueryCoalescer {
+    reuse_window: Duration,
+    state: Mutex<State>,
+}
+
+impl Debug for QueryCoalescer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("QueryCoalescer")
+            .field("reuse_window", &self.reuse_window)
+            .finish_non_exhaustive()
+    }
+}
+
+impl QueryCoalescer {
+    pub(crate) fn new(reuse_window: Duration) -> Self {
+        Self {
+            reuse_window,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Runs the query, unless an identical query is running or recently completed, in which case its response is shared.
+    ///
+    /// Dropping the returned future only cancels the query if no other caller is waiting for it.
+    /// Callers that share a failed query receive [Error::CoalescedQueryError], except for the last of them to receive it,
+    /// which receives the error itself - so that a query that was not shared fails with its own error.
+    pub(crate) async fn run(
+        self: &Arc<Self>,
+        key: QueryKey,
+        query: impl FnOnce() -> BoxFuture<'static, Result<KustoResponseDataSetV2>>,
+    ) -> Result<KustoResponseDataSetV2> {
+        let shared = {
+            let mut state = self.state();
+            let reuse_window = self.reuse_window;
+            state
+                .completed
+                .retain(|_, (completed_at, _)| completed_at.elapsed() < reuse_window);
+            state.in_flight.retain(|_, weak| weak.upgrade().is_some());
+
+            if let Some((_, response)) = state.completed.get(&key) {
+                return Ok(response.clone());
+            }
+
+            match state.in_flight.get(&key).and_then(WeakShared::upgrade) {
+                Some(shared) => shared,
+                None => {
+                    let shared = self.start(key.clone(), query());
+                    if let Some(weak) = shared.downgrade() {
+                        state.in_flight.insert(key, weak);
+                    }
+                    shared
+                }
+            }
+        };
+
+        shared
+            .await
+            .map_err(|error| Arc::try_unwrap(error).unwrap_or_else(Error::CoalescedQueryError))
+    }
+
+    fn start(
+        self: &Arc<Self>,
+        key: QueryKey,
+        query: BoxFuture<'static, Result<KustoResponseDataSetV2>>,
+    ) -> Shared<QueryFuture> {
+        let coalescer = self.clone();
+        let future: QueryFuture = Box::pin(async move {
+            let result = query.await.map_err(Arc::new);
+            coalescer.complete(key, &result);
+            result
+        });
+        future.shared()
+    }
+
+    fn complete(&self, key: QueryKey, result: &SharedResult) {
+        let mut state = self.state();
+        state.in_flight.remove(&key);
+        // Instant is not available in WebAssembly, so responses are only shared while they run
+        if cfg!(not(target_arch = "wasm32")) && !self.reuse_window.is_zero() {
+            if let Ok(response) = result {
+                state
+                    .completed
+                    .insert(key, (Instant::now(), response.clone()));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request_options::OptionsBuilder;
+    use std::borrow::Cow;
+
+    #[test]
+    fn keys_ignore_the_order_of_properties() {
+        let properties = |names: &[&str]| {
+            let mut properties = ClientRequestProperties::from(
+                OptionsBuilder::default()
+                    .with_truncation_max_records(10)
+                    .build()
+                    .unwrap(),
+            );
+            for name in names {
+                properties.add_string_parameter(Cow::Borrowed(*name), Cow::Borrowed("value"));
+            }
+            properties.client_request_id = Some(uuid::Uuid::new_v4().to_string());
+            properties
+        };
+
+        let key = QueryKey::new("db", "T", Some(&properties(&["a", "b", "c"])));
+        assert_eq!(
+            key,
+            QueryKey::new("db", "T", Some(&properties(&["c", "b", "a"])))
+        );
+        assert_ne!(
+            key,
+            QueryKey::new("db", "T", Some(&properties(&["a", "b"])))
+        );
+        assert_ne!(key, QueryKey::new("db", "T", None));
+        assert_ne!(
+            key,
+            QueryKey::new("db", "T | take 1", Some(&properties(&["a", "b", "c"])))
+        );
+    }
+
+    #[test]
+    fn canonical_json_sorts_keys() {
+        let value = serde_json::json!({"b": [1, {"d": null, "c": "x"}], "a": true});
+        assert_eq!(
+            canonical_json(&value),
+            r#"{"a":true,"b":[1,{"c":"x","d":null}]}"#
+        );
+    }
+}
@@ -1,6 +1,7 @@
 //! Set of properties that can be use in a connection string provided to KustoConnectionStringBuilder.
 //! For a complete list of properties go to [the official docs](https://docs.microsoft.com/en-us/azure/kusto/api/connection-strings/kusto)
 
+use std::borrow::Cow;
 use std::fmt::{Debug, Formatter};
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -9,14 +10,18 @@ use std::time::Duration;
 use crate::client_details;
 use crate::client_details::{ClientDetails, ConnectorDetails};
 use azure_core::auth::TokenCredential;
+use azure_core::Url;
 use azure_identity::{
-    AzureCliCredential, ClientSecretCredential, DefaultAzureCredential,
-    ImdsManagedIdentityCredential, TokenCredentialOptions,
+    AzureCliCredential, ClientSecretCredential, DefaultAzureCredential, DefaultAzureCredentialEnum,
+    EnvironmentCredential, ImdsManagedIdentityCredential, TokenCredentialOptions,
 };
 use hashbrown::HashMap;
 use once_cell::sync::Lazy;
 
-use crate::credentials::{CallbackTokenCredential, ConstTokenCredential};
+use crate::credentials::{
+    AzureCliTenantCredential, AzureDeveloperCliCredential, CallbackTokenCredential,
+    ChainedTokenCredential, ConstTokenCredential,
+};
 use crate::error::ConnectionStringError;
 
 /// Function that handles the device code flow.
@@ -41,6 +46,7 @@ enum ConnectionStringKey {
     MsiParams,
     AzCli,
     InteractiveLogin,
+    InitialCatalog,
 }
 
 const CENSORED_VALUE: &str = "******";
@@ -67,6 +73,7 @@ impl ConnectionStringKey {
             ConnectionStringKey::MsiParams => "MSI Params",
             ConnectionStringKey::AzCli => "AZ CLI",
             ConnectionStringKey::InteractiveLogin => "Interactive Login",
+            ConnectionStringKey::InitialCatalog => "Initial Catalog",
         }
     }
 }
@@ -142,6 +149,9 @@ static ALIAS_MAP: Lazy<HashMap<&'static str, ConnectionStringKey>> = Lazy::new(|
 
     m.insert("az cli", ConnectionStringKey::AzCli);
 
+    m.insert("initial catalog", ConnectionStringKey::InitialCatalog);
+    m.insert("database", ConnectionStringKey::InitialCatalog);
+
     m
 });
 
@@ -161,10 +171,16 @@ static ALIAS_MAP: Lazy<HashMap<&'static str, ConnectionStringKey>> = Lazy::new(|
 */
 
 /// A connection string is a string that contains the parameters that are used to connect to an ADX cluster, as well as an authentication method.
+///
+/// Create it with one of the `with_*` constructors, such as [with_default_auth](Self::with_default_auth), or parse it with
+/// [from_raw_connection_string](Self::from_raw_connection_string).
 #[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
 pub struct ConnectionString {
     /// The URI specifying the Kusto service endpoint.
-    /// For example, <https://mycluster.kusto.windows.net> or net.tcp://localhost
+    /// For example, <https://mycluster.kusto.windows.net> or <http://localhost:8080>.
+    /// A port may be given, and a data source without a scheme is assumed to be https.
+    /// Other schemes, such as net.tcp, are rejected by [KustoClient::new](crate::client::KustoClient::new).
     pub data_source: String,
     /// Instructs the client to perform Azure Active Directory login, is true by default.
     pub federated_security: bool,
@@ -175,6 +191,55 @@ pub struct ConnectionString {
     pub application: Option<String>,
     /// User name for tracing.
     pub user: Option<String>,
+    /// The authority or tenant id to authenticate against, when it differs from the home tenant of the credential.
+    /// Can be either a tenant id, or a full authority url such as `https://login.microsoftonline.com/<tenant id>`.
+    /// Application based authentication methods carry their own authority, and ignore this value.
+    pub authority_id: Option<String>,
+    /// The database that queries run against when they don't name one, set by `Initial Catalog` or `Database`.
+    /// See [KustoClient::execute_query_on_default_db](crate::client::KustoClient::execute_query_on_default_db).
+    pub initial_catalog: Option<String>,
+}
+
+/// The kind of authentication a [ConnectionStringAuth] uses, without any of its secrets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum AuthKind {
+    /// [ConnectionStringAuth::Default]
+    Default,
+    /// [ConnectionStringAuth::UserAndPassword]
+    UserAndPassword,
+    /// [ConnectionStringAuth::Token]
+    Token,
+    /// [ConnectionStringAuth::TokenCallback]
+    TokenCallback,
+    /// [ConnectionStringAuth::Application]
+    Application,
+    /// [ConnectionStringAuth::ApplicationCertificate]
+    ApplicationCertificate,
+    /// [ConnectionStringAuth::ManagedIdentity]
+    ManagedIdentity,
+    /// [ConnectionStringAuth::AzureCli]
+    AzureCli,
+    /// [ConnectionStringAuth::DeviceCode]
+    DeviceCode,
+    /// [ConnectionStringAuth::InteractiveLogin]
+    InteractiveLogin,
+    /// [ConnectionStringAuth::TokenCredential] without a more specific kind.
+    TokenCredential,
+    /// The environment credential, created by [ConnectionString::with_environment_auth].
+    Environment,
+    /// The azure developer cli credential, created by [ConnectionString::with_azure_developer_cli_auth].
+    AzureDeveloperCli,
+    /// A chain of credentials, created by [ConnectionString::with_chained_auth].
+    Chained,
+    /// [ConnectionStringAuth::None]
+    None,
+}
+
+impl std::fmt::Display for AuthKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(self, f)
+    }
 }
 
 /// Authentication methods to use when connecting to an ADX cluster.
@@ -239,7 +304,12 @@ pub enum ConnectionStringAuth {
     TokenCredential {
         /// The token credential to use.
         credential: Arc<dyn TokenCredential>,
+        /// The kind of the credential, shown in its `Debug` output and in errors when it fails to provide a token.
+        label: Option<AuthKind>,
     },
+    /// None - sends requests anonymously, without an `Authorization` header, for local development against the Kusto emulator.
+    /// Created by [ConnectionString::with_no_auth], or by a connection string with `AAD Federated Security=False` and no credentials.
+    None,
 }
 
 impl ConnectionStringAuth {
@@ -273,18 +343,18 @@ impl ConnectionStringAuth {
     #[must_use]
     pub fn build(&self, safe: bool) -> Option<String> {
         match self {
-            ConnectionStringAuth::Default => Some("".to_string()),
+            ConnectionStringAuth::Default | ConnectionStringAuth::None => Some("".to_string()),
             ConnectionStringAuth::UserAndPassword { user_id, password } => Some(format!(
                 "{}={};{}={}",
                 ConnectionStringKey::UserId.to_str(),
-                user_id,
+                quote_value(user_id),
                 ConnectionStringKey::Password.to_str(),
-                if safe { CENSORED_VALUE } else { password }
+                quote_value(if safe { CENSORED_VALUE } else { password })
             )),
             ConnectionStringAuth::Token { token } => Some(format!(
                 "{}={}",
                 ConnectionStringKey::ApplicationToken.to_str(),
-                if safe { CENSORED_VALUE } else { token }
+                quote_value(if safe { CENSORED_VALUE } else { token })
             )),
             ConnectionStringAuth::Application {
                 client_id,
@@ -293,11 +363,11 @@ impl ConnectionStringAuth {
             } => Some(format!(
                 "{}={};{}={};{}={}",
                 ConnectionStringKey::ApplicationClientId.to_str(),
-                client_id,
+                quote_value(client_id),
                 ConnectionStringKey::ApplicationKey.to_str(),
-                if safe { CENSORED_VALUE } else { client_secret },
+                quote_value(if safe { CENSORED_VALUE } else { client_secret }),
                 ConnectionStringKey::AuthorityId.to_str(),
-                client_authority
+                quote_value(client_authority)
             )),
             ConnectionStringAuth::ApplicationCertificate {
                 client_id,
@@ -307,13 +377,13 @@ impl ConnectionStringAuth {
             } => Some(format!(
                 "{}={};{}={};{}={};{}={}",
                 ConnectionStringKey::ApplicationClientId.to_str(),
-                client_id,
+                quote_value(client_id),
                 ConnectionStringKey::ApplicationCertificate.to_str(),
-                private_certificate_path.display(),
+                quote_value(&private_certificate_path.display().to_string()),
                 ConnectionStringKey::ApplicationCertificateThumbprint.to_str(),
-                if safe { CENSORED_VALUE } else { thumbprint },
+                quote_value(if safe { CENSORED_VALUE } else { thumbprint }),
                 ConnectionStringKey::AuthorityId.to_str(),
-                client_authority
+                quote_value(client_authority)
             )),
             ConnectionStringAuth::ManagedIdentity { user_id } => {
                 if let Some(user_id) = user_id {
@@ -322,7 +392,7 @@ impl ConnectionStringAuth {
                         ConnectionStringKey::MsiAuth.to_str(),
                         CONNECTION_STRING_TRUE,
                         ConnectionStringKey::MsiParams.to_str(),
-                        user_id,
+                        quote_value(user_id),
                     ))
                 } else {
                     Some(format!(
@@ -346,9 +416,73 @@ impl ConnectionStringAuth {
         }
     }
 
-    pub(crate) fn into_credential(self) -> Arc<dyn TokenCredential> {
+    /// A short description of the authentication method, without any of its secrets, to give context to authentication errors.
+    pub(crate) fn kind(&self) -> AuthKind {
+        match self {
+            ConnectionStringAuth::Default => AuthKind::Default,
+            ConnectionStringAuth::UserAndPassword { .. } => AuthKind::UserAndPassword,
+            ConnectionStringAuth::Token { .. } => AuthKind::Token,
+            ConnectionStringAuth::TokenCallback { .. } => AuthKind::TokenCallback,
+            ConnectionStringAuth::Application { .. } => AuthKind::Application,
+            ConnectionStringAuth::ApplicationCertificate { .. } => AuthKind::ApplicationCertificate,
+            ConnectionStringAuth::ManagedIdentity { .. } => AuthKind::ManagedIdentity,
+            ConnectionStringAuth::AzureCli => AuthKind::AzureCli,
+            ConnectionStringAuth::DeviceCode { .. } => AuthKind::DeviceCode,
+            ConnectionStringAuth::InteractiveLogin => AuthKind::InteractiveLogin,
+            ConnectionStringAuth::TokenCredential { label, .. } => {
+                label.unwrap_or(AuthKind::TokenCredential)
+            }
+            ConnectionStringAuth::None => AuthKind::None,
+        }
+    }
+
+    /// Creates the credential of the authentication, for the authority, or else the login endpoint of the cloud, `authority_host`, when they are known.
+    pub(crate) fn into_credential(
+        self,
+        authority_id: Option<String>,
+        authority_host: Option<&str>,
+    ) -> Arc<dyn TokenCredential> {
+        let options = token_credential_options(authority_id.as_deref(), authority_host);
+        let has_authority_host = authority_host.is_some()
+            || matches!(
+                authority_id.as_deref().map(split_authority),
+                Some((Some(_), _))
+            );
+        let tenant_id = authority_id.as_deref().map(tenant_from_authority);
+
         match self {
-            ConnectionStringAuth::Default => Arc::new(DefaultAzureCredential::default()),
+            ConnectionStringAuth::Default => match tenant_id {
+                // The default chain can't be given the authority, so it is rebuilt from its sources, and the azure cli
+                // is replaced with one that is scoped to the requested tenant.
+                Some(tenant_id) => Arc::new(ChainedTokenCredential {
+                    credentials: vec![
+                        Arc::new(DefaultAzureCredential::with_sources(vec![
+                            DefaultAzureCredentialEnum::Environment(EnvironmentCredential::new(
+                                azure_core::new_http_client(),
+                                options,
+                            )),
+                            DefaultAzureCredentialEnum::ManagedIdentity(
+                                ImdsManagedIdentityCredential::default(),
+                            ),
+                        ])),
+                        Arc::new(AzureCliTenantCredential {
+                            tenant_id: tenant_id.to_string(),
+                        }),
+                    ],
+                }),
+                // The environment credential of the default chain logs in to the public cloud, unless it is given the authority host
+                None if has_authority_host => Arc::new(DefaultAzureCredential::with_sources(vec![
+                    DefaultAzureCredentialEnum::Environment(EnvironmentCredential::new(
+                        azure_core::new_http_client(),
+                        options,
+                    )),
+                    DefaultAzureCredentialEnum::ManagedIdentity(
+                        ImdsManagedIdentityCredential::default(),
+                    ),
+                    DefaultAzureCredentialEnum::AzureCli(AzureCliCredential::default()),
+                ])),
+                None => Arc::new(DefaultAzureCredential::default()),
+            },
             ConnectionStringAuth::UserAndPassword { .. } => unimplemented!(),
             ConnectionStringAuth::Token { token } => Arc::new(ConstTokenCredential { token }),
             ConnectionStringAuth::TokenCallback {
@@ -367,9 +501,10 @@ impl ConnectionStringAuth {
                 client_authority,
                 client_id,
                 client_secret,
-                TokenCredentialOptions::default(),
+                options,
             )),
             ConnectionStringAuth::ApplicationCertificate { .. } => unimplemented!(),
+            // Managed identities are bound to the tenant of the resource they are assigned to, so the authority does not apply.
             ConnectionStringAuth::ManagedIdentity { user_id } => {
                 if let Some(user_id) = user_id {
                     Arc::new(ImdsManagedIdentityCredential::default().with_object_id(user_id))
@@ -377,12 +512,54 @@ impl ConnectionStringAuth {
                     Arc::new(ImdsManagedIdentityCredential::default())
                 }
             }
-            ConnectionStringAuth::AzureCli => Arc::new(AzureCliCredential::default()),
+            ConnectionStringAuth::AzureCli => match tenant_id {
+                Some(tenant_id) => Arc::new(AzureCliTenantCredential {
+                    tenant_id: tenant_id.to_string(),
+                }),
+                None => Arc::new(AzureCliCredential::default()),
+            },
             ConnectionStringAuth::DeviceCode { .. } => unimplemented!(),
             ConnectionStringAuth::InteractiveLogin => unimplemented!(),
-            ConnectionStringAuth::TokenCredential { credential } => credential.clone(),
+            ConnectionStringAuth::TokenCredential { credential, .. } => credential.clone(),
+            ConnectionStringAuth::None => {
+                unreachable!("Requests without authentication do not use a credential - please report this issue to the Kusto team")
+            }
+        }
+    }
+}
+
+/// Splits an authority into the authority host, if it is given as a full url, and the tenant id.
+fn split_authority(authority_id: &str) -> (Option<&str>, &str) {
+    let authority_id = authority_id.trim_end_matches('/');
+    if authority_id.starts_with("https://") {
+        if let Some((host, tenant_id)) = authority_id.rsplit_once('/') {
+            if host != "https:/" {
+                return (Some(host), tenant_id);
+            }
         }
     }
+    (None, authority_id)
+}
+
+fn tenant_from_authority(authority_id: &str) -> &str {
+    split_authority(authority_id).1
+}
+
+/// Creates the options for the azure identity credentials, pointing them to the authority host if one was given,
+/// either as part of the authority or as the `default_authority_host`, such as the login endpoint of the cloud.
+fn token_credential_options(
+    authority_id: Option<&str>,
+    default_authority_host: Option<&str>,
+) -> TokenCredentialOptions {
+    let mut options = TokenCredentialOptions::default();
+    let authority_host = match authority_id.map(split_authority) {
+        Some((Some(authority_host), _)) => Some(authority_host),
+        _ => default_authority_host,
+    };
+    if let Some(Ok(authority_host)) = authority_host.map(Url::parse) {
+        options.set_authority_host(authority_host);
+    }
+    options
 }
 
 impl PartialEq for ConnectionStringAuth {
@@ -434,9 +611,8 @@ impl PartialEq for ConnectionStringAuth {
                 ConnectionStringAuth::ManagedIdentity { user_id: u2 },
             ) => u1 == u2,
             (ConnectionStringAuth::AzureCli, ConnectionStringAuth::AzureCli)
-            | (ConnectionStringAuth::InteractiveLogin, ConnectionStringAuth::InteractiveLogin) => {
-                true
-            }
+            | (ConnectionStringAuth::InteractiveLogin, ConnectionStringAuth::InteractiveLogin)
+            | (ConnectionStringAuth::None, ConnectionStringAuth::None) => true,
             _ => false,
         }
     }
@@ -488,14 +664,22 @@ impl Debug for ConnectionStringAuth {
                 write!(f, "DeviceCode()")
             }
             ConnectionStringAuth::InteractiveLogin => write!(f, "InteractiveLogin"),
-            ConnectionStringAuth::TokenCredential { .. } => write!(f, "TokenCredential"),
+            ConnectionStringAuth::TokenCredential { label, .. } => match label {
+                Some(label) => write!(f, "TokenCredential({label})"),
+                None => write!(f, "TokenCredential"),
+            },
+            ConnectionStringAuth::None => write!(f, "None"),
         }
     }
 }
 
 impl ConnectionString {
     /// Parses a connection string in order to create a `ConnectionString` object.
-    /// The connection string is a series of key-value pairs separated by semicolons.
+    /// The connection string is a series of key-value pairs separated by semicolons, where empty pairs, such as a trailing semicolon, are ignored.
+    ///
+    /// Values may be wrapped in single or double quotes, to include semicolons, equal signs, or leading and trailing whitespace.
+    /// A quoted value may contain quotes of the other kind, and quotes of its own kind when they are doubled, such as `"say ""hi"""`.
+    /// Whitespace around unquoted values is trimmed.
     /// # Examples
     /// ```rust
     /// use azure_kusto_data::error::Error;
@@ -516,29 +700,11 @@ impl ConnectionString {
     pub fn from_raw_connection_string(
         connection_string: &str,
     ) -> Result<Self, ConnectionStringError> {
-        let kv_str_pairs = connection_string
-            .split(';')
-            .filter(|s| !s.chars().all(char::is_whitespace));
-
-        let mut result_map = HashMap::<ConnectionStringKey, &str>::new();
-
-        for kv_pair_str in kv_str_pairs {
-            let mut kv = kv_pair_str.trim().split('=');
-            let k = match kv.next().filter(|k| !k.chars().all(char::is_whitespace)) {
-                None => {
-                    return Err(ConnectionStringError::Parsing {
-                        msg: "No key found".to_string(),
-                    });
-                }
-                Some(k) => k,
-            };
-            let v = match kv.next().filter(|k| !k.chars().all(char::is_whitespace)) {
-                None => return Err(ConnectionStringError::MissingValue { key: k.to_string() }),
-                Some(v) => v,
-            };
-
-            if let Some(&key) = ALIAS_MAP.get(k.to_ascii_lowercase().trim()) {
-                result_map.insert(key, v.trim());
+        let mut result_map = HashMap::<ConnectionStringKey, String>::new();
+
+        for (k, v) in parse_pairs(connection_string)? {
+            if let Some(&key) = ALIAS_MAP.get(k.to_ascii_lowercase().as_str()) {
+                result_map.insert(key, v);
             } else {
                 return Err(ConnectionStringError::from_unexpected_key(k));
             }
@@ -551,9 +717,18 @@ impl ConnectionString {
         )?)
         .to_string();
 
-        let federated_security = result_map
+        let explicit_federated_security = result_map
             .get(&ConnectionStringKey::FederatedSecurity)
-            .map_or(Ok(false), |s| parse_boolean(s, "federated_security"))?;
+            .map(|s| parse_boolean(s, "federated_security"))
+            .transpose()?;
+        let federated_security = explicit_federated_security.unwrap_or(false);
+
+        let authority_id = result_map
+            .get(&ConnectionStringKey::AuthorityId)
+            .map(|s| (*s).to_string());
+        let initial_catalog = result_map
+            .get(&ConnectionStringKey::InitialCatalog)
+            .cloned();
 
         if let Some(user_id) = result_map.get(&ConnectionStringKey::UserId) {
             let password = result_map
@@ -569,6 +744,8 @@ impl ConnectionString {
                 },
                 application: None,
                 user: None,
+                authority_id,
+                initial_catalog,
             })
         } else if let Some(token) = result_map.get(&ConnectionStringKey::ApplicationToken) {
             Ok(Self {
@@ -579,6 +756,8 @@ impl ConnectionString {
                 },
                 application: None,
                 user: None,
+                authority_id,
+                initial_catalog,
             })
         } else if let Some(token) = result_map.get(&ConnectionStringKey::UserToken) {
             Ok(Self {
@@ -589,6 +768,8 @@ impl ConnectionString {
                 },
                 application: None,
                 user: None,
+                authority_id,
+                initial_catalog,
             })
         } else if let Some(client_id) = result_map.get(&ConnectionStringKey::ApplicationClientId) {
             let client_secret = result_map
@@ -607,6 +788,8 @@ impl ConnectionString {
                 },
                 application: None,
                 user: None,
+                authority_id,
+                initial_catalog,
             })
         } else if let Some(client_id) = result_map.get(&ConnectionStringKey::ApplicationCertificate)
         {
@@ -634,6 +817,8 @@ impl ConnectionString {
                 },
                 application: None,
                 user: None,
+                authority_id,
+                initial_catalog,
             })
         } else if result_map
             .get(&ConnectionStringKey::MsiAuth)
@@ -652,6 +837,8 @@ impl ConnectionString {
                 },
                 application: None,
                 user: None,
+                authority_id,
+                initial_catalog,
             })
         } else if result_map
             .get(&ConnectionStringKey::AzCli)
@@ -665,6 +852,8 @@ impl ConnectionString {
                 auth: ConnectionStringAuth::AzureCli,
                 application: None,
                 user: None,
+                authority_id,
+                initial_catalog,
             })
         } else if result_map
             .get(&ConnectionStringKey::InteractiveLogin)
@@ -678,6 +867,19 @@ impl ConnectionString {
                 auth: ConnectionStringAuth::InteractiveLogin,
                 application: None,
                 user: None,
+                authority_id,
+                initial_catalog,
+            })
+        } else if explicit_federated_security == Some(false) {
+            // No credentials were given, and AAD login was explicitly turned off.
+            Ok(Self {
+                data_source,
+                federated_security,
+                auth: ConnectionStringAuth::None,
+                application: None,
+                user: None,
+                authority_id,
+                initial_catalog,
             })
         } else {
             Ok(Self {
@@ -686,6 +888,8 @@ impl ConnectionString {
                 auth: ConnectionStringAuth::Default,
                 application: None,
                 user: None,
+                authority_id,
+                initial_catalog,
             })
         }
     }
@@ -711,6 +915,32 @@ impl ConnectionString {
             auth: ConnectionStringAuth::Default,
             application: None,
             user: None,
+            authority_id: None,
+            initial_catalog: None,
+        }
+    }
+
+    /// Creates a connection string with the default authentication credentials, authenticating against the given tenant.
+    /// Useful when the cluster is in a different tenant than the home tenant of the credential.
+    /// # Example
+    /// ```rust
+    /// use azure_kusto_data::prelude::{ConnectionString, ConnectionStringAuth};
+    ///
+    /// let conn = ConnectionString::with_default_auth_and_tenant("https://mycluster.kusto.windows.net", "e7f86dff-7a05-4b87-8c48-ed1ea5b5b814");
+    ///
+    /// assert_eq!(conn.auth, ConnectionStringAuth::Default);
+    /// assert_eq!(conn.authority_id, Some("e7f86dff-7a05-4b87-8c48-ed1ea5b5b814".to_string()));
+    ///
+    /// assert_eq!(conn.build(), Some("Data Source=https://mycluster.kusto.windows.net;AAD Federated Security=True;Authority Id=e7f86dff-7a05-4b87-8c48-ed1ea5b5b814".to_string()))
+    /// ```
+    #[must_use]
+    pub fn with_default_auth_and_tenant(
+        data_source: impl Into<String>,
+        authority_id: impl Into<String>,
+    ) -> Self {
+        Self {
+            authority_id: Some(authority_id.into()),
+            ..Self::with_default_auth(data_source)
         }
     }
 
@@ -741,6 +971,8 @@ impl ConnectionString {
             },
             application: None,
             user: None,
+            authority_id: None,
+            initial_catalog: None,
         }
     }
 
@@ -766,6 +998,8 @@ impl ConnectionString {
             },
             application: None,
             user: None,
+            authority_id: None,
+            initial_catalog: None,
         }
     }
 
@@ -798,6 +1032,8 @@ impl ConnectionString {
             },
             application: None,
             user: None,
+            authority_id: None,
+            initial_catalog: None,
         }
     }
 
@@ -832,6 +1068,41 @@ impl ConnectionString {
             },
             application: None,
             user: None,
+            authority_id: None,
+            initial_catalog: None,
+        }
+    }
+
+    /// Creates a connection string whose authentication is selected by the environment variables that are set:
+    /// - `AZURE_CLIENT_ID`, `AZURE_CLIENT_SECRET` and `AZURE_TENANT_ID` authenticate as an application with a secret,
+    ///   like [with_application_auth](Self::with_application_auth).
+    /// - Otherwise, the [default credentials](Self::with_default_auth) are used.
+    ///
+    /// Variables that are set to an empty string are treated as unset.
+    /// # Example
+    /// ```rust
+    /// use azure_kusto_data::prelude::{ConnectionString, ConnectionStringAuth};
+    ///
+    /// let conn = ConnectionString::from_env("https://mycluster.kusto.windows.net");
+    /// assert_eq!(conn.data_source, "https://mycluster.kusto.windows.net".to_string());
+    ///
+    /// if std::env::var("AZURE_CLIENT_SECRET").is_err() {
+    ///     assert_eq!(conn.auth, ConnectionStringAuth::Default);
+    /// }
+    /// ```
+    #[must_use]
+    pub fn from_env(data_source: impl Into<String>) -> Self {
+        let var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+
+        match (
+            var("AZURE_CLIENT_ID"),
+            var("AZURE_CLIENT_SECRET"),
+            var("AZURE_TENANT_ID"),
+        ) {
+            (Some(client_id), Some(client_secret), Some(tenant_id)) => {
+                Self::with_application_auth(data_source, client_id, client_secret, tenant_id)
+            }
+            _ => Self::with_default_auth(data_source),
         }
     }
 
@@ -868,6 +1139,8 @@ impl ConnectionString {
             },
             application: None,
             user: None,
+            authority_id: None,
+            initial_catalog: None,
         }
     }
 
@@ -897,6 +1170,8 @@ impl ConnectionString {
             },
             application: None,
             user: None,
+            authority_id: None,
+            initial_catalog: None,
         }
     }
 
@@ -921,6 +1196,8 @@ impl ConnectionString {
             auth: ConnectionStringAuth::AzureCli,
             application: None,
             user: None,
+            authority_id: None,
+            initial_catalog: None,
         }
     }
 
@@ -950,6 +1227,8 @@ impl ConnectionString {
             auth: ConnectionStringAuth::DeviceCode { callback },
             application: None,
             user: None,
+            authority_id: None,
+            initial_catalog: None,
         }
     }
 
@@ -973,6 +1252,8 @@ impl ConnectionString {
             auth: ConnectionStringAuth::InteractiveLogin,
             application: None,
             user: None,
+            authority_id: None,
+            initial_catalog: None,
         }
     }
 
@@ -1002,12 +1283,149 @@ impl ConnectionString {
             federated_security: true,
             auth: ConnectionStringAuth::TokenCredential {
                 credential: token_credential,
+                label: None,
             },
             application: None,
             user: None,
+            authority_id: None,
+            initial_catalog: None,
         }
     }
 
+    /// Creates a connection string that authenticates with the credentials of an application, taken from the environment variables
+    /// `AZURE_TENANT_ID`, `AZURE_CLIENT_ID` and `AZURE_CLIENT_SECRET`. See [EnvironmentCredential] for more details.
+    /// # Example
+    /// ```rust
+    /// use azure_kusto_data::prelude::{AuthKind, ConnectionString, ConnectionStringAuth};
+    ///
+    /// let conn = ConnectionString::with_environment_auth("https://mycluster.kusto.windows.net");
+    ///
+    /// assert_eq!(conn.data_source, "https://mycluster.kusto.windows.net".to_string());
+    /// assert!(matches!(conn.auth, ConnectionStringAuth::TokenCredential { label: Some(label), .. } if label == AuthKind::Environment));
+    /// ```
+    #[must_use]
+    pub fn with_environment_auth(data_source: impl Into<String>) -> Self {
+        Self::with_labeled_token_credential(
+            data_source,
+            Arc::new(EnvironmentCredential::new(
+                azure_core::new_http_client(),
+                TokenCredentialOptions::default(),
+            )),
+            AuthKind::Environment,
+        )
+    }
+
+    /// Creates a connection string that uses the Azure Developer CLI to authenticate. Run `azd auth login` to start the process.
+    /// # Example
+    /// ```rust
+    /// use azure_kusto_data::prelude::{AuthKind, ConnectionString, ConnectionStringAuth};
+    ///
+    /// let conn = ConnectionString::with_azure_developer_cli_auth("https://mycluster.kusto.windows.net");
+    ///
+    /// assert_eq!(conn.data_source, "https://mycluster.kusto.windows.net".to_string());
+    /// assert!(matches!(conn.auth, ConnectionStringAuth::TokenCredential { label: Some(label), .. } if label == AuthKind::AzureDeveloperCli));
+    /// ```
+    #[must_use]
+    pub fn with_azure_developer_cli_auth(data_source: impl Into<String>) -> Self {
+        Self::with_labeled_token_credential(
+            data_source,
+            Arc::new(AzureDeveloperCliCredential),
+            AuthKind::AzureDeveloperCli,
+        )
+    }
+
+    /// Creates a connection string that tries each of the credentials in order, and uses the first one that succeeds in providing a token.
+    /// # Example
+    /// ```rust
+    /// use std::sync::Arc;
+    /// use azure_identity::{AzureCliCredential, DefaultAzureCredential};
+    /// use azure_kusto_data::prelude::{AuthKind, ConnectionString, ConnectionStringAuth};
+    ///
+    /// let conn = ConnectionString::with_chained_auth(
+    ///     "https://mycluster.kusto.windows.net",
+    ///     vec![Arc::new(AzureCliCredential::default()), Arc::new(DefaultAzureCredential::default())],
+    /// );
+    ///
+    /// assert_eq!(conn.data_source, "https://mycluster.kusto.windows.net".to_string());
+    /// assert!(matches!(conn.auth, ConnectionStringAuth::TokenCredential { label: Some(label), .. } if label == AuthKind::Chained));
+    /// ```
+    #[must_use]
+    pub fn with_chained_auth(
+        data_source: impl Into<String>,
+        credentials: Vec<Arc<dyn TokenCredential>>,
+    ) -> Self {
+        Self::with_labeled_token_credential(
+            data_source,
+            Arc::new(ChainedTokenCredential { credentials }),
+            AuthKind::Chained,
+        )
+    }
+
+    fn with_labeled_token_credential(
+        data_source: impl Into<String>,
+        credential: Arc<dyn TokenCredential>,
+        label: AuthKind,
+    ) -> Self {
+        Self {
+            data_source: data_source.into(),
+            federated_security: true,
+            auth: ConnectionStringAuth::TokenCredential {
+                credential,
+                label: Some(label),
+            },
+            application: None,
+            user: None,
+            authority_id: None,
+            initial_catalog: None,
+        }
+    }
+
+    /// Creates a connection string that sends requests without authentication, such as for the Kusto emulator.
+    /// This is equivalent to the connection string `Data Source=http://localhost:8080;AAD Federated Security=False`.
+    /// As the emulator is served over http, insecure endpoints must be allowed in the [KustoClientOptions](crate::client::KustoClientOptions).
+    /// # Example
+    /// ```rust
+    /// use azure_kusto_data::prelude::{ConnectionString, ConnectionStringAuth, KustoClient, KustoClientOptions};
+    ///
+    /// let conn = ConnectionString::with_no_auth("http://localhost:8080");
+    ///
+    /// assert_eq!(conn.auth, ConnectionStringAuth::None);
+    /// assert_eq!(conn.build(), Some("Data Source=http://localhost:8080;AAD Federated Security=False;".to_string()));
+    /// assert_eq!(ConnectionString::from_raw_connection_string("Data Source=http://localhost:8080;AAD Federated Security=False"), Ok(conn.clone()));
+    ///
+    /// // http endpoints are rejected unless they are explicitly allowed.
+    /// assert!(KustoClient::new(conn.clone(), KustoClientOptions::new()).is_err());
+    /// assert!(KustoClient::new(conn, KustoClientOptions::new().with_allow_insecure_endpoint(true)).is_ok());
+    /// ```
+    #[must_use]
+    pub fn with_no_auth(data_source: impl Into<String>) -> Self {
+        Self {
+            data_source: data_source.into(),
+            federated_security: false,
+            auth: ConnectionStringAuth::None,
+            application: None,
+            user: None,
+            authority_id: None,
+            initial_catalog: None,
+        }
+    }
+
+    /// Sets the database that queries run against when they don't name one.
+    /// # Example
+    /// ```rust
+    /// use azure_kusto_data::prelude::ConnectionString;
+    ///
+    /// let conn = ConnectionString::with_default_auth("https://mycluster.kusto.windows.net").with_initial_catalog("mydb");
+    ///
+    /// assert_eq!(conn.initial_catalog, Some("mydb".to_string()));
+    /// assert_eq!(conn.build(), Some("Data Source=https://mycluster.kusto.windows.net;AAD Federated Security=True;Initial Catalog=mydb".to_string()));
+    /// ```
+    #[must_use]
+    pub fn with_initial_catalog(mut self, database: impl Into<String>) -> Self {
+        self.initial_catalog = Some(database.into());
+        self
+    }
+
     /// Builds the connection string into a string.
     /// By default, it will include the authentication, and censor secrets.
     /// If you want to use different options, use the [build_with_options](#method.build_with_options) method.
@@ -1038,7 +1456,7 @@ impl ConnectionString {
         let mut s = format!(
             "{}={};{}={}",
             ConnectionStringKey::DataSource.to_str(),
-            self.data_source,
+            quote_value(&self.data_source),
             ConnectionStringKey::FederatedSecurity.to_str(),
             if self.federated_security {
                 CONNECTION_STRING_TRUE
@@ -1053,13 +1471,41 @@ impl ConnectionString {
             } else {
                 return None;
             }
+            if let Some(authority_id) = &self.authority_id {
+                if !matches!(
+                    self.auth,
+                    ConnectionStringAuth::Application { .. }
+                        | ConnectionStringAuth::ApplicationCertificate { .. }
+                ) {
+                    if !s.ends_with(';') {
+                        s.push(';');
+                    }
+                    s.push_str(&format!(
+                        "{}={}",
+                        ConnectionStringKey::AuthorityId.to_str(),
+                        quote_value(authority_id)
+                    ));
+                }
+            }
+        }
+        if let Some(initial_catalog) = &self.initial_catalog {
+            if !s.ends_with(';') {
+                s.push(';');
+            }
+            s.push_str(&format!(
+                "{}={}",
+                ConnectionStringKey::InitialCatalog.to_str(),
+                quote_value(initial_catalog)
+            ));
         }
 
         Some(s)
     }
 
-    pub(crate) fn into_data_source_and_auth(self) -> (String, ConnectionStringAuth) {
-        (self.data_source, self.auth)
+    pub(crate) fn into_data_source_and_auth(
+        self,
+    ) -> (String, ConnectionStringAuth, Option<String>) {
+        (self.data_source, self.auth, self.authority_id)
     }
 
     /// Extracts the client details from the connection string.
@@ -1075,6 +1521,91 @@ impl ConnectionString {
     }
 }
 
+/// Splits a connection string into its keys and values, unquoting the values.
+fn parse_pairs(connection_string: &str) -> Result<Vec<(&str, String)>, ConnectionStringError> {
+    let mut pairs = Vec::new();
+    let mut rest = connection_string;
+
+    loop {
+        rest = rest.trim_start_matches(|c: char| c == ';' || c.is_whitespace());
+        if rest.is_empty() {
+            return Ok(pairs);
+        }
+
+        let (key, value) = match rest.find(['=', ';']) {
+            Some(end) if rest[end..].starts_with('=') => (rest[..end].trim(), &rest[end + 1..]),
+            end => {
+                let key = rest[..end.unwrap_or(rest.len())].trim();
+                return Err(ConnectionStringError::from_missing_value(key));
+            }
+        };
+        if key.is_empty() {
+            return Err(ConnectionStringError::from_parsing_error("No key found"));
+        }
+
+        let value = value.trim_start();
+        let (value, remainder) = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                let (unquoted, remainder) = unquote(&value[1..], quote).ok_or_else(|| {
+                    ConnectionStringError::from_parsing_error(format!(
+                        "The quoted value of '{key}' is missing its closing quote"
+                    ))
+                })?;
+                let remainder = remainder.trim_start();
+                if !remainder.is_empty() && !remainder.starts_with(';') {
+                    return Err(ConnectionStringError::from_parsing_error(format!(
+                        "Unexpected characters after the quoted value of '{key}'"
+                    )));
+                }
+                (unquoted, remainder)
+            }
+            _ => {
+                let end = value.find(';').unwrap_or(value.len());
+                (value[..end].trim_end().to_string(), &value[end..])
+            }
+        };
+        if value.is_empty() {
+            return Err(ConnectionStringError::from_missing_value(key));
+        }
+
+        pairs.push((key, value));
+        rest = remainder;
+    }
+}
+
+/// Reads a value up to its closing `quote`, where doubled quotes stand for a single one.
+/// Returns the value, and what follows its closing quote.
+fn unquote(quoted: &str, quote: char) -> Option<(String, &str)> {
+    let mut value = String::new();
+    let mut rest = quoted;
+    loop {
+        let end = rest.find(quote)?;
+        value.push_str(&rest[..end]);
+        rest = &rest[end + 1..];
+        match rest.strip_prefix(quote) {
+            Some(after_escape) => {
+                value.push(quote);
+                rest = after_escape;
+            }
+            None => return Some((value, rest)),
+        }
+    }
+}
+
+/// Quotes a value of a connection string if it can't be written as is, using the same rules as the .NET connection string builder.
+fn quote_value(value: &str) -> Cow<'_, str> {
+    let plain = value
+        .chars()
+        .all(|c| !matches!(c, '"' | '\'' | ';' | '=') && !c.is_whitespace() && !c.is_control());
+    if plain {
+        Cow::Borrowed(value)
+    } else if value.contains('"') && !value.contains('\'') {
+        Cow::Owned(format!("'{value}'"))
+    } else {
+        Cow::Owned(format!("\"{}\"", value.replace('"', "\"\"")))
+    }
+}
+
 fn parse_boolean(term: &str, name: &str) -> Result<bool, ConnectionStringError> {
     match term.to_lowercase().trim() {
         "true" => Ok(true),
@@ -1090,6 +1621,91 @@ mod tests {
     #[allow(unused_imports)]
     use super::*;
 
+    /// Sets environment variables for the lifetime of the guard, restoring their previous values when dropped.
+    /// Guards are serialized, since the environment is shared by all the tests of the process.
+    struct EnvGuard {
+        previous: Vec<(&'static str, Option<String>)>,
+        _lock: std::sync::MutexGuard<'static, ()>,
+    }
+
+    impl EnvGuard {
+        fn new(vars: &[(&'static str, Option<&str>)]) -> Self {
+            static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+            let lock = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+            let previous = vars
+                .iter()
+                .map(|(name, value)| {
+                    let previous = std::env::var(name).ok();
+                    match value {
+                        Some(value) => std::env::set_var(name, value),
+                        None => std::env::remove_var(name),
+                    }
+                    (*name, previous)
+                })
+                .collect();
+            Self {
+                previous,
+                _lock: lock,
+            }
+        }
+    }
+
+    impl Drop for EnvGuard {
+        fn drop(&mut self) {
+            for (name, value) in &self.previous {
+                match value {
+                    Some(value) => std::env::set_var(name, value),
+                    None => std::env::remove_var(name),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn from_env_uses_service_principal() {
+        let _env = EnvGuard::new(&[
+            (
+                "AZURE_CLIENT_ID",
+                Some("029067d2-220e-4467-99be-b74f4751270b"),
+            ),
+            ("AZURE_CLIENT_SECRET", Some("secret")),
+            (
+                "AZURE_TENANT_ID",
+                Some("e7f86dff-7a05-4b87-8c48-ed1ea5b5b814"),
+            ),
+        ]);
+
+        let conn = ConnectionString::from_env("https://mycluster.kusto.windows.net");
+        assert_eq!(
+            conn,
+            ConnectionString::with_application_auth(
+                "https://mycluster.kusto.windows.net",
+                "029067d2-220e-4467-99be-b74f4751270b",
+                "secret",
+                "e7f86dff-7a05-4b87-8c48-ed1ea5b5b814"
+            )
+        );
+    }
+
+    #[test]
+    fn from_env_falls_back_to_default() {
+        let _env = EnvGuard::new(&[
+            (
+                "AZURE_CLIENT_ID",
+                Some("029067d2-220e-4467-99be-b74f4751270b"),
+            ),
+            ("AZURE_CLIENT_SECRET", Some("")),
+            ("AZURE_TENANT_ID", None),
+        ]);
+
+        let conn = ConnectionString::from_env("https://mycluster.kusto.windows.net");
+        assert_eq!(
+            conn,
+            ConnectionString::with_default_auth("https://mycluster.kusto.windows.net")
+        );
+    }
+
     #[test]
     fn it_returns_expected_errors() {
         assert!(matches!(
@@ -1106,6 +1722,101 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn it_parses_the_initial_catalog() {
+        for raw in [
+            "Data Source=ds;Initial Catalog=mydb",
+            "Data Source=ds;database=mydb",
+            "Data Source=ds; DATABASE = 'mydb' ;",
+        ] {
+            let conn = ConnectionString::from_raw_connection_string(raw).unwrap();
+            assert_eq!(conn.initial_catalog.as_deref(), Some("mydb"), "{raw}");
+            assert_eq!(conn.auth, ConnectionStringAuth::Default);
+        }
+        assert_eq!(
+            ConnectionString::from_raw_connection_string("Data Source=ds")
+                .unwrap()
+                .initial_catalog,
+            None
+        );
+
+        let conn = ConnectionString::with_user_password_auth("ds", "user", "password")
+            .with_initial_catalog("my;db");
+        let built = conn.build_with_options(false, false).unwrap();
+        assert_eq!(
+            built,
+            "Data Source=ds;AAD Federated Security=True;AAD User ID=user;Password=password;Initial Catalog=\"my;db\""
+        );
+        assert_eq!(
+            ConnectionString::from_raw_connection_string(&built),
+            Ok(conn)
+        );
+    }
+
+    /// Cases from the connection string tests of the .NET SDK, whose builder emits quoted values and trailing semicolons.
+    #[test]
+    fn it_parses_the_quoting_grammar() {
+        let cases: &[(&str, &[(&str, &str)])] = &[
+            ("Data Source=ds;", &[("Data Source", "ds")]),
+            (" ; Data Source=ds ;; ", &[("Data Source", "ds")]),
+            ("Data Source = a b ;", &[("Data Source", "a b")]),
+            ("Password=a=b", &[("Password", "a=b")]),
+            ("Password=ab\"c'd", &[("Password", "ab\"c'd")]),
+            ("Password=\"a;b=c\"", &[("Password", "a;b=c")]),
+            ("Password='a;b'  ;", &[("Password", "a;b")]),
+            ("Password=\" padded \"", &[("Password", " padded ")]),
+            ("Password=\"it's\"", &[("Password", "it's")]),
+            ("Password='say \"hi\"'", &[("Password", "say \"hi\"")]),
+            ("Password=\"say \"\"hi\"\"\"", &[("Password", "say \"hi\"")]),
+            ("Password='it''s'", &[("Password", "it's")]),
+            (
+                "Data Source=\"ds\";Password=';'",
+                &[("Data Source", "ds"), ("Password", ";")],
+            ),
+        ];
+        for (connection_string, expected) in cases {
+            let pairs = parse_pairs(connection_string)
+                .unwrap_or_else(|e| panic!("Failed to parse {connection_string}: {e}"));
+            let pairs: Vec<(&str, &str)> = pairs.iter().map(|(k, v)| (*k, v.as_str())).collect();
+            assert_eq!(&pairs, expected, "{connection_string}");
+        }
+
+        for connection_string in [
+            "Password=\"unterminated",
+            "Password='unterminated\"",
+            "Password=\"quoted\" trailing",
+            "Password=''",
+            "Password=  ;",
+            "Password",
+        ] {
+            assert!(
+                parse_pairs(connection_string).is_err(),
+                "Expected {connection_string} to fail"
+            );
+        }
+    }
+
+    #[test]
+    fn it_quotes_values_that_need_it() {
+        assert_eq!(quote_value("plain"), "plain");
+        assert_eq!(quote_value("a;b"), "\"a;b\"");
+        assert_eq!(quote_value(" a"), "\" a\"");
+        assert_eq!(quote_value("a=b"), "\"a=b\"");
+        assert_eq!(quote_value("say \"hi\""), "'say \"hi\"'");
+        assert_eq!(quote_value("it's \"x\""), "\"it's \"\"x\"\"\"");
+
+        let conn = ConnectionString::with_application_auth(
+            "https://mycluster.kusto.windows.net",
+            "cid",
+            "se;cr=et\"'",
+            "tid",
+        );
+        let built = conn.build_with_options(false, false).unwrap();
+        assert!(built.contains("Application Key=\"se;cr=et\"\"'\""));
+        let parsed = ConnectionString::from_raw_connection_string(&built).unwrap();
+        assert_eq!(parsed.auth, conn.auth);
+    }
+
     #[test]
     fn it_parses_basic_cases() {
         assert_eq!(
@@ -1115,7 +1826,9 @@ mod tests {
                 federated_security: false,
                 auth: ConnectionStringAuth::Default,
                 application: None,
-                user: None
+                user: None,
+                authority_id: None,
+                initial_catalog: None,
             })
         );
         assert_eq!(
@@ -1125,7 +1838,9 @@ mod tests {
                 federated_security: false,
                 auth: ConnectionStringAuth::Default,
                 application: None,
-                user: None
+                user: None,
+                authority_id: None,
+                initial_catalog: None,
             })
         );
         assert_eq!(
@@ -1141,7 +1856,9 @@ mod tests {
                     client_authority: "tid".to_string(),
                 },
                 application: None,
-                user: None
+                user: None,
+                authority_id: Some("tid".to_string()),
+                initial_catalog: None,
             })
         );
         assert_eq!(
@@ -1155,8 +1872,134 @@ mod tests {
                     token: "token".to_string()
                 },
                 application: None,
-                user: None
+                user: None,
+                authority_id: None,
+                initial_catalog: None,
+            })
+        );
+    }
+
+    #[test]
+    fn it_parses_authority_for_non_application_auth() {
+        assert_eq!(
+            ConnectionString::from_raw_connection_string("Data Source=ds;Authority Id=tid"),
+            Ok(ConnectionString {
+                data_source: "ds".to_string(),
+                federated_security: false,
+                auth: ConnectionStringAuth::Default,
+                application: None,
+                user: None,
+                authority_id: Some("tid".to_string()),
+                initial_catalog: None,
+            })
+        );
+        assert_eq!(
+            ConnectionString::from_raw_connection_string("Data Source=ds;AZ CLI=True;Tenant=tid"),
+            Ok(ConnectionString {
+                data_source: "ds".to_string(),
+                federated_security: false,
+                auth: ConnectionStringAuth::AzureCli,
+                application: None,
+                user: None,
+                authority_id: Some("tid".to_string()),
+                initial_catalog: None,
             })
         );
+        assert_eq!(
+            ConnectionString::from_raw_connection_string("Data Source=ds;MSI=True;Authority=tid"),
+            Ok(ConnectionString {
+                data_source: "ds".to_string(),
+                federated_security: false,
+                auth: ConnectionStringAuth::ManagedIdentity { user_id: None },
+                application: None,
+                user: None,
+                authority_id: Some("tid".to_string()),
+                initial_catalog: None,
+            })
+        );
+    }
+
+    #[test]
+    fn labeled_credentials_are_redacted_in_debug() {
+        let credential: Arc<dyn TokenCredential> = Arc::new(ConstTokenCredential {
+            token: "secret-token".to_string(),
+        });
+
+        let chained = ConnectionString::with_chained_auth("localhost", vec![credential.clone()]);
+        assert_eq!(format!("{:?}", chained.auth), "TokenCredential(Chained)");
+        assert_eq!(chained.auth.kind(), AuthKind::Chained);
+
+        let unlabeled = ConnectionString::with_token_credential("localhost", credential);
+        assert_eq!(format!("{:?}", unlabeled.auth), "TokenCredential");
+        assert!(!format!("{:?}", unlabeled).contains("secret-token"));
+    }
+
+    #[test]
+    fn it_parses_emulator_connection_strings() {
+        assert_eq!(
+            ConnectionString::from_raw_connection_string(
+                "Data Source=http://localhost:8080;AAD Federated Security=False"
+            ),
+            Ok(ConnectionString {
+                data_source: "http://localhost:8080".to_string(),
+                federated_security: false,
+                auth: ConnectionStringAuth::None,
+                application: None,
+                user: None,
+                authority_id: None,
+                initial_catalog: None,
+            })
+        );
+        // Explicit credentials are still used when AAD login is turned off.
+        assert_eq!(
+            ConnectionString::from_raw_connection_string(
+                "Data Source=http://localhost:8080;Fed=False;AppToken=token"
+            )
+            .map(|c| c.auth),
+            Ok(ConnectionStringAuth::Token {
+                token: "token".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn it_builds_credential_options_from_authority() {
+        assert_eq!(split_authority("tid"), (None, "tid"));
+        assert_eq!(
+            split_authority("https://login.microsoftonline.us/tid/"),
+            (Some("https://login.microsoftonline.us"), "tid")
+        );
+
+        let options = token_credential_options(Some("https://login.microsoftonline.us/tid"), None);
+        assert_eq!(
+            options.authority_host().as_str(),
+            "https://login.microsoftonline.us/"
+        );
+
+        let options = token_credential_options(Some("tid"), None);
+        assert_eq!(
+            options.authority_host(),
+            TokenCredentialOptions::default().authority_host()
+        );
+
+        // The login endpoint of the cloud applies unless the authority has a host
+        let options = token_credential_options(Some("tid"), Some("https://login.chinacloudapi.cn"));
+        assert_eq!(
+            options.authority_host().as_str(),
+            "https://login.chinacloudapi.cn/"
+        );
+        let options = token_credential_options(None, Some("https://login.chinacloudapi.cn"));
+        assert_eq!(
+            options.authority_host().as_str(),
+            "https://login.chinacloudapi.cn/"
+        );
+        let options = token_credential_options(
+            Some("https://login.microsoftonline.us/tid"),
+            Some("https://login.chinacloudapi.cn"),
+        );
+        assert_eq!(
+            options.authority_host().as_str(),
+            "https://login.microsoftonline.us/"
+        );
     }
 }
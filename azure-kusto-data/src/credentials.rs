@@ -1,9 +1,15 @@
 //! Custom credentials for Azure Data Explorer.
 
 use crate::connection_string::TokenCallbackFunction;
+use crate::utils::run_blocking;
 use azure_core::auth::{AccessToken, TokenCredential};
+use azure_core::error::{Error as CoreError, ErrorKind, ResultExt};
+use serde::Deserialize;
 use std::fmt::{Debug, Formatter};
+use std::process::{Command, Output};
+use std::sync::Arc;
 use std::time::Duration;
+use time::format_description::well_known::Rfc3339;
 use time::OffsetDateTime;
 
 const SECONDS_IN_50_YEARS: u64 = 60 * 60 * 24 * 365 * 50;
@@ -13,7 +19,8 @@ const SECONDS_IN_50_YEARS: u64 = 60 * 60 * 24 * 365 * 50;
 pub struct ConstTokenCredential {
     pub(crate) token: String,
 }
-#[async_trait::async_trait]
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
 impl TokenCredential for ConstTokenCredential {
     async fn get_token(&self, _: &[&str]) -> azure_core::Result<AccessToken> {
         Ok(AccessToken {
@@ -42,7 +49,8 @@ impl Debug for CallbackTokenCredential {
     }
 }
 
-#[async_trait::async_trait]
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
 impl TokenCredential for CallbackTokenCredential {
     async fn get_token(&self, scopes: &[&str]) -> azure_core::Result<AccessToken> {
         let callback = &self.token_callback;
@@ -59,3 +67,168 @@ impl TokenCredential for CallbackTokenCredential {
         Ok(())
     }
 }
+
+/// Runs a cli with the given arguments, without blocking the executor while waiting for the process.
+async fn cli_output(program: &str, args: &[&str]) -> std::io::Result<Output> {
+    #[cfg(target_os = "windows")]
+    let mut command = {
+        let mut command = Command::new("cmd");
+        command.arg("/C").arg(program);
+        command
+    };
+    #[cfg(not(target_os = "windows"))]
+    let mut command = Command::new(program);
+    command.args(args);
+
+    run_blocking(move || command.output()).await
+}
+
+/// Uses the Azure CLI to authenticate against a specific tenant, which may differ from the tenant of the logged in account.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AzureCliTenantCredential {
+    pub(crate) tenant_id: String,
+}
+
+#[derive(Deserialize)]
+struct CliTokenResponse {
+    #[serde(rename = "accessToken")]
+    access_token: String,
+    expires_on: Option<i64>,
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+impl TokenCredential for AzureCliTenantCredential {
+    async fn get_token(&self, scopes: &[&str]) -> azure_core::Result<AccessToken> {
+        let mut args = vec![
+            "account",
+            "get-access-token",
+            "--output",
+            "json",
+            "--tenant",
+            self.tenant_id.as_str(),
+            "--scope",
+        ];
+        args.extend(scopes);
+
+        let output = cli_output("az", &args)
+            .await
+            .context(ErrorKind::Credential, "Failed to execute the azure cli")?;
+        if !output.status.success() {
+            return Err(CoreError::message(
+                ErrorKind::Credential,
+                format!(
+                    "The azure cli failed to get a token for tenant {}: {}",
+                    self.tenant_id,
+                    String::from_utf8_lossy(&output.stderr)
+                ),
+            ));
+        }
+
+        let response: CliTokenResponse = serde_json::from_slice(&output.stdout).context(
+            ErrorKind::DataConversion,
+            "Failed to parse the azure cli token response",
+        )?;
+
+        Ok(AccessToken {
+            token: response.access_token.into(),
+            // Older versions of the cli don't report the expiry as a timestamp, so be conservative and refresh soon
+            expires_on: response
+                .expires_on
+                .and_then(|expires_on| OffsetDateTime::from_unix_timestamp(expires_on).ok())
+                .unwrap_or_else(|| OffsetDateTime::now_utc() + Duration::from_secs(5 * 60)),
+        })
+    }
+
+    async fn clear_cache(&self) -> azure_core::Result<()> {
+        Ok(())
+    }
+}
+
+/// Uses the Azure Developer CLI to authenticate. Run `azd auth login` to start the process.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct AzureDeveloperCliCredential;
+
+#[derive(Deserialize)]
+struct AzdTokenResponse {
+    token: String,
+    #[serde(rename = "expiresOn")]
+    expires_on: String,
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+impl TokenCredential for AzureDeveloperCliCredential {
+    async fn get_token(&self, scopes: &[&str]) -> azure_core::Result<AccessToken> {
+        let mut args = vec!["auth", "token", "--output", "json"];
+        for scope in scopes {
+            args.extend(["--scope", *scope]);
+        }
+
+        let output = cli_output("azd", &args).await.context(
+            ErrorKind::Credential,
+            "Failed to execute the azure developer cli",
+        )?;
+        if !output.status.success() {
+            return Err(CoreError::message(
+                ErrorKind::Credential,
+                format!(
+                    "The azure developer cli failed to get a token: {}",
+                    String::from_utf8_lossy(&output.stderr)
+                ),
+            ));
+        }
+
+        let response: AzdTokenResponse = serde_json::from_slice(&output.stdout).context(
+            ErrorKind::DataConversion,
+            "Failed to parse the azure developer cli token response",
+        )?;
+
+        Ok(AccessToken {
+            token: response.token.into(),
+            expires_on: OffsetDateTime::parse(&response.expires_on, &Rfc3339).context(
+                ErrorKind::DataConversion,
+                "Failed to parse the expiry of the azure developer cli token",
+            )?,
+        })
+    }
+
+    async fn clear_cache(&self) -> azure_core::Result<()> {
+        Ok(())
+    }
+}
+
+/// Tries each of the credentials in order, and uses the first one that succeeds in providing a token.
+#[derive(Debug, Clone)]
+pub(crate) struct ChainedTokenCredential {
+    pub(crate) credentials: Vec<Arc<dyn TokenCredential>>,
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+impl TokenCredential for ChainedTokenCredential {
+    async fn get_token(&self, scopes: &[&str]) -> azure_core::Result<AccessToken> {
+        let mut errors = Vec::with_capacity(self.credentials.len());
+        for credential in &self.credentials {
+            match credential.get_token(scopes).await {
+                Ok(token) => return Ok(token),
+                Err(e) => errors.push(e.to_string()),
+            }
+        }
+
+        Err(CoreError::message(
+            ErrorKind::Credential,
+            format!(
+                "None of the chained credentials could provide a token: {}",
+                errors.join(", ")
+            ),
+        ))
+    }
+
+    async fn clear_cache(&self) -> azure_core::Result<()> {
+        for credential in &self.credentials {
+            credential.clear_cache().await?;
+        }
+        Ok(())
+    }
+}
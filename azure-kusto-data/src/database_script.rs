@@ -0,0 +1,211 @@
+//! Running several management commands in a single request, with `.execute database script`.
+//!
+//! See [KustoClient::execute_database_script].
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::client::KustoClient;
+use crate::deserialization::{deserialize_table_v1, CaseMapping};
+use crate::error::{Error, InvalidArgumentError, Result};
+use crate::operations::query::KustoResponseDataSetV1;
+use crate::prelude::ClientRequestProperties;
+
+/// What a database script does when one of its commands fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScriptErrorMode {
+    /// Run the remaining commands, and report the failure in the result of the command.
+    #[default]
+    ContinueOnErrors,
+    /// Skip the remaining commands, and fail the whole script.
+    ThrowOnErrors,
+}
+
+impl ScriptErrorMode {
+    fn property(self) -> &'static str {
+        match self {
+            ScriptErrorMode::ContinueOnErrors => "ContinueOnErrors=true",
+            ScriptErrorMode::ThrowOnErrors => "ThrowOnErrors=true",
+        }
+    }
+}
+
+/// The result of a command of a database script.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ScriptCommandResult {
+    /// The id of the operation of the command, which is empty for the commands that were not run.
+    pub operation_id: Uuid,
+    /// The type of the command, such as `TableCreate`.
+    pub command_type: String,
+    /// The text of the command.
+    pub command_text: String,
+    /// The result of the command: `Completed`, `Failed` or `Skipped`.
+    pub result: String,
+    /// Why the command failed or was skipped, empty if it completed.
+    #[serde(default, deserialize_with = "crate::deserialization::null_as_empty")]
+    pub reason: String,
+}
+
+impl ScriptCommandResult {
+    /// Whether the command failed.
+    #[must_use]
+    pub fn failed(&self) -> bool {
+        self.result == "Failed"
+    }
+}
+
+/// Builds the `.execute database script` command that runs the commands in order.
+///
+/// Each command starts on a new line of the script, and the lines that follow it up to the next command are part of it,
+/// so commands may span several lines, as long as none of their other lines start with a dot.
+fn script_command(commands: &[&str], mode: ScriptErrorMode) -> Result<String> {
+    if commands.is_empty() {
+        return Err(InvalidArgumentError::InvalidScriptCommand("".into()).into());
+    }
+
+    let mut script = format!(".execute database script with ({}) <|", mode.property());
+    for command in commands {
+        let command = command.trim();
+        let mut lines = command.lines();
+        let starts_with_dot = lines.next().map_or(false, |line| line.starts_with('.'));
+        if !starts_with_dot || lines.any(|line| line.trim_start().starts_with('.')) {
+            return Err(InvalidArgumentError::InvalidScriptCommand(command.into()).into());
+        }
+        script.push('\n');
+        script.push_str(command);
+    }
+    Ok(script)
+}
+
+fn parse_script_results(response: KustoResponseDataSetV1) -> Result<Vec<ScriptCommandResult>> {
+    let table = response.tables.into_iter().next().ok_or_else(|| {
+        Error::QueryError("No results found for `.execute database script`".into())
+    })?;
+    deserialize_table_v1(table, CaseMapping::PascalToSnake)
+}
+
+impl KustoClient {
+    /// Runs several management commands in a single request, with `.execute database script`, returning the result of each command in order.
+    ///
+    /// With [ScriptErrorMode::ContinueOnErrors], failed commands are reported in their [ScriptCommandResult],
+    /// while with [ScriptErrorMode::ThrowOnErrors] the first failure fails the whole script.
+    /// Each command must start with a dot, and may span several lines as long as none of its other lines start with one.
+    /// # Example
+    /// ```no_run
+    /// use azure_kusto_data::prelude::*;
+    /// use azure_kusto_data::database_script::ScriptErrorMode;
+    /// # #[tokio::main] async fn main() -> Result<(), Error> {
+    /// let client = KustoClient::new(
+    ///    ConnectionString::with_default_auth("https://mycluster.region.kusto.windows.net/"),
+    ///    KustoClientOptions::default())?;
+    ///
+    /// let results = client
+    ///     .execute_database_script(
+    ///         "some_database",
+    ///         &[
+    ///             ".create-merge table Events (Timestamp: datetime, Name: string)",
+    ///             ".alter table Events policy ingestiontime true",
+    ///         ],
+    ///         ScriptErrorMode::ContinueOnErrors,
+    ///         None,
+    ///     )
+    ///     .await?;
+    /// for result in results.iter().filter(|r| r.failed()) {
+    ///     println!("{} failed: {}", result.command_text, result.reason);
+    /// }
+    /// # Ok(())}
+    /// ```
+    pub async fn execute_database_script(
+        &self,
+        database: impl Into<String>,
+        commands: &[&str],
+        mode: ScriptErrorMode,
+        options: Option<ClientRequestProperties>,
+    ) -> Result<Vec<ScriptCommandResult>> {
+        let script = script_command(commands, mode)?;
+        let response = self.execute_command(database, script, options).await?;
+        parse_script_results(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::load_v1_response;
+
+    #[test]
+    fn commands_are_joined_into_a_script() {
+        let script = script_command(
+            &[
+                "  .create-merge table Events (Timestamp: datetime, Name: string)\n",
+                ".alter table Events policy retention\n```\n{\"SoftDeletePeriod\": \"10.00:00:00\"}\n```",
+            ],
+            ScriptErrorMode::ContinueOnErrors,
+        )
+        .expect("Failed to build the script");
+
+        assert_eq!(
+            script,
+            ".execute database script with (ContinueOnErrors=true) <|\n\
+             .create-merge table Events (Timestamp: datetime, Name: string)\n\
+             .alter table Events policy retention\n```\n{\"SoftDeletePeriod\": \"10.00:00:00\"}\n```"
+        );
+
+        let script = script_command(&[".show tables"], ScriptErrorMode::ThrowOnErrors).unwrap();
+        assert_eq!(
+            script,
+            ".execute database script with (ThrowOnErrors=true) <|\n.show tables"
+        );
+    }
+
+    #[test]
+    fn commands_that_would_break_the_script_are_rejected() {
+        for commands in [
+            &[][..],
+            &["MyTable | take 10"][..],
+            &[".show tables", ""][..],
+            &[".create table T (a: int)\n.drop table T"][..],
+        ] {
+            assert!(
+                matches!(
+                    script_command(commands, ScriptErrorMode::default()),
+                    Err(Error::InvalidArgumentError(
+                        InvalidArgumentError::InvalidScriptCommand(_)
+                    ))
+                ),
+                "Expected {commands:?} to be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn parses_script_results() {
+        let results = parse_script_results(load_v1_response("execute_database_script.json"))
+            .expect("Failed to parse the results");
+
+        assert_eq!(results.len(), 4);
+        assert_eq!(
+            results[0],
+            ScriptCommandResult {
+                operation_id: Uuid::parse_str("1b2e3f4a-5b6c-4d7e-8f90-a1b2c3d4e5f6").unwrap(),
+                command_type: "TableCreate".to_string(),
+                command_text: ".create-merge table Events (Timestamp: datetime, Name: string)"
+                    .to_string(),
+                result: "Completed".to_string(),
+                reason: String::new(),
+            }
+        );
+
+        let failed: Vec<_> = results.iter().filter(|r| r.failed()).collect();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].command_type, "TableRetentionPolicyAlter");
+        assert_eq!(
+            failed[0].reason,
+            "Table 'Missing' could not be found in database 'mydb'"
+        );
+
+        assert_eq!(results[3].result, "Skipped");
+        assert_eq!(results[3].operation_id, Uuid::nil());
+        assert_eq!(results[3].reason, "");
+    }
+}
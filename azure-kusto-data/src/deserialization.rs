@@ -0,0 +1,754 @@
+//! Deserialization of result rows into user defined types, matching columns to fields by name.
+
+use hashbrown::HashMap;
+use serde::de::value::BorrowedStrDeserializer;
+use serde::de::{self, DeserializeOwned, Visitor};
+use serde::forward_to_deserialize_any;
+use serde_json::Value;
+
+use crate::error::{Error, Result};
+use crate::models::{Column, ColumnType, DataTable, TableV1};
+use crate::types::parse_dynamic;
+
+/// Controls how the column names of a result are matched to the field names of the type it is deserialized into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CaseMapping {
+    /// Column names must match the field names exactly.
+    #[default]
+    Exact,
+    /// PascalCase column names are converted to snake_case, so `ClientRequestId` matches `client_request_id`.
+    PascalToSnake,
+    /// Column names match field names regardless of case and underscores, so `ClientRequestId` matches `client_request_id` and `clientrequestid`.
+    Insensitive,
+}
+
+/// Converts a PascalCase or camelCase name to snake_case, keeping acronyms together (`HTTPStatus` becomes `http_status`).
+fn pascal_to_snake(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut result = String::with_capacity(name.len() + 4);
+    for (i, c) in chars.iter().enumerate() {
+        if c.is_uppercase() {
+            let prev = i.checked_sub(1).map(|p| chars[p]);
+            let next = chars.get(i + 1);
+            let starts_word = match prev {
+                None | Some('_') => false,
+                Some(p) if p.is_lowercase() || p.is_numeric() => true,
+                Some(p) => p.is_uppercase() && matches!(next, Some(n) if n.is_lowercase()),
+            };
+            if starts_word {
+                result.push('_');
+            }
+            result.extend(c.to_lowercase());
+        } else {
+            result.push(*c);
+        }
+    }
+    result
+}
+
+fn insensitive_key(name: &str) -> String {
+    name.chars()
+        .filter(|c| *c != '_')
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Collects the field names of a struct, by intercepting the call to `deserialize_struct`.
+struct FieldNamesDeserializer<'a>(&'a mut Option<&'static [&'static str]>);
+
+impl<'de, 'a> de::Deserializer<'de> for FieldNamesDeserializer<'a> {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(
+        self,
+        _visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error> {
+        Err(de::Error::custom("not a struct"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error> {
+        *self.0 = Some(fields);
+        Err(de::Error::custom("field names collected"))
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
+
+/// Returns the field names of `T`, if it is deserialized as a struct.
+pub(crate) fn struct_field_names<T: DeserializeOwned>() -> Option<&'static [&'static str]> {
+    let mut fields = None;
+    let _ = T::deserialize(FieldNamesDeserializer(&mut fields));
+    fields
+}
+
+/// Resolves the key each column is deserialized as, erroring if two columns resolve to the same key.
+fn column_keys<T: DeserializeOwned>(
+    columns: &[Column],
+    case_mapping: CaseMapping,
+) -> Result<Vec<String>> {
+    let field_names = struct_field_names::<T>();
+
+    let keys: Vec<String> = columns
+        .iter()
+        .map(|column| {
+            let name = column.column_name.as_str();
+            match case_mapping {
+                CaseMapping::Exact => name.to_string(),
+                CaseMapping::PascalToSnake => pascal_to_snake(name),
+                CaseMapping::Insensitive => {
+                    let key = insensitive_key(name);
+                    field_names
+                        .and_then(|fields| fields.iter().find(|f| insensitive_key(f) == key))
+                        .map_or_else(|| name.to_string(), |f| (*f).to_string())
+                }
+            }
+        })
+        .collect();
+
+    {
+        let mut seen: HashMap<&str, &str> = HashMap::with_capacity(keys.len());
+        for (key, column) in keys.iter().zip(columns) {
+            if let Some(previous) = seen.insert(key, &column.column_name) {
+                return Err(Error::ColumnMappingError(format!(
+                    "columns '{}' and '{}' both map to the field '{}'",
+                    previous, column.column_name, key
+                )));
+            }
+        }
+    }
+
+    Ok(keys)
+}
+
+/// Deserializes a row as a map from the keys of its columns to its values, without copying either.
+/// Errors in a value name the column it is in.
+struct RowDeserializer<'a> {
+    columns: &'a [Column],
+    keys: &'a [String],
+    values: &'a [Value],
+}
+
+impl<'a> de::Deserializer<'a> for RowDeserializer<'a> {
+    type Error = serde_json::Error;
+
+    fn deserialize_any<V: Visitor<'a>>(
+        self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error> {
+        visitor.visit_map(RowAccess {
+            columns: self.columns,
+            entries: self.keys.iter().zip(self.values).enumerate(),
+            value: None,
+        })
+    }
+
+    fn deserialize_option<V: Visitor<'a>>(
+        self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'a>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        <W: Visitor<'a>>
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+struct RowAccess<'a> {
+    columns: &'a [Column],
+    entries: std::iter::Enumerate<
+        std::iter::Zip<std::slice::Iter<'a, String>, std::slice::Iter<'a, Value>>,
+    >,
+    /// The value of the last key, with the position of its column
+    value: Option<(usize, &'a Value)>,
+}
+
+impl<'a> de::MapAccess<'a> for RowAccess<'a> {
+    type Error = serde_json::Error;
+
+    fn next_key_seed<K: de::DeserializeSeed<'a>>(
+        &mut self,
+        seed: K,
+    ) -> std::result::Result<Option<K::Value>, Self::Error> {
+        match self.entries.next() {
+            Some((position, (key, value))) => {
+                self.value = Some((position, value));
+                seed.deserialize(BorrowedStrDeserializer::new(key))
+                    .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'a>>(
+        &mut self,
+        seed: V,
+    ) -> std::result::Result<V::Value, Self::Error> {
+        match self.value.take() {
+            Some((position, value)) => seed.deserialize(value).map_err(|e| {
+                let column = self
+                    .columns
+                    .get(position)
+                    .map_or("", |c| c.column_name.as_str());
+                de::Error::custom(format_args!("column '{column}': {e}"))
+            }),
+            None => Err(de::Error::custom("value is missing")),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+fn row_values(index: usize, row: &Value) -> Result<&[Value]> {
+    match row {
+        Value::Array(values) => Ok(values),
+        other => Err(Error::ConversionError(format!(
+            "row {index}, expected an array but got {other}"
+        ))),
+    }
+}
+
+/// Deserializes the row at `index` of a table, naming the row, and the column if there is one, in errors.
+fn deserialize_row<T: DeserializeOwned>(
+    index: usize,
+    columns: &[Column],
+    keys: &[String],
+    values: &[Value],
+) -> Result<T> {
+    T::deserialize(RowDeserializer {
+        columns,
+        keys,
+        values,
+    })
+    .map_err(|e| Error::JsonError(de::Error::custom(format_args!("row {index}: {e}"))))
+}
+
+/// Deserializes the rows of a table into `T`, matching each column to a field by its name according to the `case_mapping`.
+/// Each row is dropped once it is deserialized. Errors name the index of the row that failed, and its column if a value failed.
+pub(crate) fn deserialize_values<T: DeserializeOwned>(
+    columns: &[Column],
+    rows: Vec<Value>,
+    case_mapping: CaseMapping,
+) -> Result<Vec<T>> {
+    let keys = column_keys::<T>(columns, case_mapping)?;
+
+    rows.into_iter()
+        .enumerate()
+        .map(|(index, row)| deserialize_row(index, columns, &keys, row_values(index, &row)?))
+        .collect()
+}
+
+/// Deserializes the rows of a table into `T`, as [deserialize_values] does,
+/// after parsing the values of its dynamic columns that were sent as strings holding JSON, as [parse_dynamic] does.
+pub(crate) fn deserialize_values_normalized<T: DeserializeOwned>(
+    columns: &[Column],
+    mut rows: Vec<Value>,
+    case_mapping: CaseMapping,
+) -> Result<Vec<T>> {
+    let dynamic_columns: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .filter(|(_, column)| column.column_type == ColumnType::Dynamic)
+        .map(|(index, _)| index)
+        .collect();
+    if !dynamic_columns.is_empty() {
+        for row in &mut rows {
+            if let Value::Array(values) = row {
+                for &index in &dynamic_columns {
+                    if let Some(value) = values.get_mut(index) {
+                        *value = parse_dynamic(value.take());
+                    }
+                }
+            }
+        }
+    }
+
+    deserialize_values(columns, rows, case_mapping)
+}
+
+/// Deserializes borrowed rows of a table into `T`, as [deserialize_values] does.
+pub(crate) fn deserialize_borrowed_values<T: DeserializeOwned>(
+    columns: &[Column],
+    rows: &[Value],
+    case_mapping: CaseMapping,
+) -> Result<Vec<T>> {
+    let keys = column_keys::<T>(columns, case_mapping)?;
+
+    rows.iter()
+        .enumerate()
+        .map(|(index, row)| deserialize_row(index, columns, &keys, row_values(index, row)?))
+        .collect()
+}
+
+fn describe_columns(columns: &[Column]) -> String {
+    columns
+        .iter()
+        .map(|c| format!("{}: {:?}", c.column_name, c.column_type))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Checks that the columns of a result match the fields of `T` by position, if `T` is deserialized as a struct:
+/// there must be a column for each field, named as the field regardless of case and underscores.
+/// The types of the fields can't be known before deserializing them, so they are not checked.
+pub(crate) fn check_positional_columns<T: DeserializeOwned>(columns: &[Column]) -> Result<()> {
+    let fields = match struct_field_names::<T>() {
+        Some(fields) => fields,
+        None => return Ok(()),
+    };
+    let type_name = std::any::type_name::<T>();
+
+    if fields.len() != columns.len() {
+        return Err(Error::ConversionError(format!(
+            "rows into {type_name}, which has {} fields ({}) but the result has {} columns ({})",
+            fields.len(),
+            fields.join(", "),
+            columns.len(),
+            describe_columns(columns)
+        )));
+    }
+    for (position, (field, column)) in fields.iter().zip(columns).enumerate() {
+        if insensitive_key(field) != insensitive_key(&column.column_name) {
+            return Err(Error::ConversionError(format!(
+                "rows into {type_name}, as column {position} is '{}' but field {position} is '{field}'",
+                column.column_name
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Deserializes the rows of a table into `T` by position, one row at a time,
+/// naming the row that doesn't fit and the columns of the table.
+pub(crate) fn deserialize_positional<T: DeserializeOwned>(
+    columns: &[Column],
+    rows: Vec<Value>,
+) -> Result<Vec<T>> {
+    rows.into_iter()
+        .enumerate()
+        .map(|(index, row)| {
+            serde_json::from_value(row).map_err(|e| {
+                Error::ConversionError(format!(
+                    "row {index} into {}, as {e}, with the columns {}",
+                    std::any::type_name::<T>(),
+                    describe_columns(columns)
+                ))
+            })
+        })
+        .collect()
+}
+
+/// Deserializes a string that may be null, for the string columns of management commands that are null when empty.
+pub(crate) fn null_as_empty<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<String, D::Error> {
+    Ok(<Option<String> as serde::Deserialize>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+/// Deserializes the rows of a V1 table into `T`, matching each column to a field by its name according to the `case_mapping`.
+/// Columns without a type are treated as dynamic.
+pub(crate) fn deserialize_table_v1<T: DeserializeOwned>(
+    table: TableV1,
+    case_mapping: CaseMapping,
+) -> Result<Vec<T>> {
+    let columns: Vec<Column> = table
+        .columns
+        .iter()
+        .map(|column| Column {
+            column_name: column.column_name.clone(),
+            column_type: column.kusto_type().cloned().unwrap_or(ColumnType::Dynamic),
+        })
+        .collect();
+    let keys = column_keys::<T>(&columns, case_mapping)?;
+
+    table
+        .rows
+        .into_iter()
+        .enumerate()
+        .map(|(index, row)| deserialize_row(index, &columns, &keys, &row))
+        .collect()
+}
+
+impl DataTable {
+    /// Deserializes the rows of the table into `T`, matching each column to a field by its name according to the `case_mapping`.
+    ///
+    /// The values are deserialized from the rows in place, so they are not copied before their conversion.
+    pub fn to_structs<T: DeserializeOwned>(&self, case_mapping: CaseMapping) -> Result<Vec<T>> {
+        deserialize_borrowed_values(&self.columns, &self.rows, case_mapping)
+    }
+
+    /// Deserializes the rows of the table into `T`, as [to_structs](DataTable::to_structs) does,
+    /// dropping each row once it is deserialized.
+    pub fn into_structs<T: DeserializeOwned>(self, case_mapping: CaseMapping) -> Result<Vec<T>> {
+        deserialize_values(&self.columns, self.rows, case_mapping)
+    }
+
+    /// Deserializes the rows of the table into `T`, as [into_structs](DataTable::into_structs) does,
+    /// after parsing the values of dynamic columns that were sent as strings holding JSON, as [parse_dynamic] does.
+    ///
+    /// The fields of dynamic columns can then be declared with the types of the JSON they hold, such as structs or maps, rather than as strings to parse.
+    pub fn into_structs_normalized<T: DeserializeOwned>(
+        self,
+        case_mapping: CaseMapping,
+    ) -> Result<Vec<T>> {
+        deserialize_values_normalized(&self.columns, self.rows, case_mapping)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ColumnV1;
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Request {
+        client_request_id: String,
+        duration_ms: i64,
+    }
+
+    fn columns(names: &[&str]) -> Vec<Column> {
+        names
+            .iter()
+            .map(|name| Column {
+                column_name: name.to_string(),
+                column_type: ColumnType::String,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn converts_pascal_to_snake() {
+        assert_eq!(pascal_to_snake("ClientRequestId"), "client_request_id");
+        assert_eq!(pascal_to_snake("clientRequestId"), "client_request_id");
+        assert_eq!(pascal_to_snake("HTTPStatus"), "http_status");
+        assert_eq!(pascal_to_snake("Value2Max"), "value2_max");
+        assert_eq!(pascal_to_snake("already_snake"), "already_snake");
+        assert_eq!(pascal_to_snake("Table_Name"), "table_name");
+    }
+
+    #[test]
+    fn collects_struct_field_names() {
+        assert_eq!(
+            struct_field_names::<Request>(),
+            Some(&["client_request_id", "duration_ms"][..])
+        );
+        assert_eq!(struct_field_names::<Value>(), None);
+    }
+
+    #[test]
+    fn maps_pascal_columns_to_snake_fields() {
+        let rows = vec![json!(["abc", 10]), json!(["def", 20])];
+        let result: Vec<Request> = deserialize_values(
+            &columns(&["ClientRequestId", "DurationMs"]),
+            rows,
+            CaseMapping::PascalToSnake,
+        )
+        .expect("Failed to deserialize");
+
+        assert_eq!(
+            result,
+            vec![
+                Request {
+                    client_request_id: "abc".to_string(),
+                    duration_ms: 10
+                },
+                Request {
+                    client_request_id: "def".to_string(),
+                    duration_ms: 20
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn maps_columns_insensitively_in_any_order() {
+        let rows = vec![json!([10, "abc"])];
+        let result: Vec<Request> = deserialize_values(
+            &columns(&["DURATIONMS", "clientrequestid"]),
+            rows,
+            CaseMapping::Insensitive,
+        )
+        .expect("Failed to deserialize");
+
+        assert_eq!(
+            result,
+            vec![Request {
+                client_request_id: "abc".to_string(),
+                duration_ms: 10
+            }]
+        );
+    }
+
+    #[test]
+    fn exact_mapping_requires_matching_names() {
+        let rows = vec![json!(["abc", 10])];
+        let result = deserialize_values::<Request>(
+            &columns(&["ClientRequestId", "DurationMs"]),
+            rows,
+            CaseMapping::Exact,
+        );
+
+        assert!(matches!(result, Err(Error::JsonError(_))));
+    }
+
+    #[test]
+    fn ambiguous_columns_are_rejected() {
+        let rows = vec![json!(["abc", "def", 10])];
+        let result = deserialize_values::<Request>(
+            &columns(&["ClientRequestId", "client_request_id", "DurationMs"]),
+            rows,
+            CaseMapping::PascalToSnake,
+        );
+
+        assert!(
+            matches!(result, Err(Error::ColumnMappingError(msg)) if msg.contains("ClientRequestId") && msg.contains("client_request_id"))
+        );
+    }
+
+    #[test]
+    fn borrowed_and_consumed_rows_are_deserialized_alike() {
+        let table = DataTable {
+            table_id: 1,
+            table_name: "Requests".to_string(),
+            table_kind: crate::models::TableKind::PrimaryResult,
+            columns: columns(&["ClientRequestId", "DurationMs", "Extra"]),
+            rows: vec![json!(["abc", 10, {"a": 1}]), json!(["def", 20, null])],
+        };
+
+        let borrowed: Vec<Request> = table
+            .to_structs(CaseMapping::PascalToSnake)
+            .expect("Failed to deserialize");
+        let maps: Vec<HashMap<String, Value>> = table
+            .to_structs(CaseMapping::Exact)
+            .expect("Failed to deserialize");
+        let optional: Vec<Option<Request>> = table
+            .to_structs(CaseMapping::PascalToSnake)
+            .expect("Failed to deserialize");
+        let consumed: Vec<Request> = table
+            .into_structs(CaseMapping::PascalToSnake)
+            .expect("Failed to deserialize");
+
+        assert_eq!(borrowed, consumed);
+        assert_eq!(borrowed[1].client_request_id, "def");
+        assert_eq!(maps[0]["Extra"], json!({"a": 1}));
+        assert_eq!(optional[0].as_ref(), Some(&borrowed[0]));
+    }
+
+    #[test]
+    fn rows_that_are_not_arrays_are_rejected() {
+        let result = deserialize_borrowed_values::<Request>(
+            &columns(&["client_request_id", "duration_ms"]),
+            &[json!({"client_request_id": "abc", "duration_ms": 10})],
+            CaseMapping::Exact,
+        );
+        assert!(
+            matches!(result, Err(Error::ConversionError(msg)) if msg.contains("expected an array"))
+        );
+
+        let short = deserialize_borrowed_values::<Request>(
+            &columns(&["client_request_id", "duration_ms"]),
+            &[json!(["abc"])],
+            CaseMapping::Exact,
+        );
+        assert!(matches!(short, Err(Error::JsonError(_))));
+    }
+
+    #[test]
+    fn errors_name_the_row_and_column() {
+        let mut rows: Vec<Value> = (0..1000).map(|i| json!([format!("id {i}"), i])).collect();
+        rows[734] = json!(["id 734", "slow"]);
+        let columns = columns(&["ClientRequestId", "DurationMs"]);
+
+        let borrowed =
+            deserialize_borrowed_values::<Request>(&columns, &rows, CaseMapping::PascalToSnake)
+                .expect_err("Expected the malformed row to fail");
+        let consumed = deserialize_values::<Request>(&columns, rows, CaseMapping::PascalToSnake)
+            .expect_err("Expected the malformed row to fail");
+        for error in [borrowed, consumed] {
+            assert!(matches!(error, Error::JsonError(_)));
+            assert!(error
+                .to_string()
+                .contains(r#"row 734: column 'DurationMs': invalid type: string "slow""#));
+        }
+
+        let missing = deserialize_values::<Request>(
+            &columns,
+            vec![json!(["abc", 10]), json!(["def"])],
+            CaseMapping::PascalToSnake,
+        )
+        .expect_err("Expected the short row to fail");
+        assert!(missing
+            .to_string()
+            .contains("row 1: missing field `duration_ms`"));
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Mismatched {
+        client_request_id: String,
+        user: String,
+        duration_ms: i64,
+    }
+
+    #[test]
+    fn positional_columns_are_checked_against_fields() {
+        let matching = columns(&["ClientRequestId", "DurationMs"]);
+        assert!(check_positional_columns::<Request>(&matching).is_ok());
+        assert!(check_positional_columns::<Value>(&matching).is_ok());
+
+        let error = check_positional_columns::<Mismatched>(&matching)
+            .expect_err("Expected the missing column to fail")
+            .to_string();
+        assert!(error.contains("has 3 fields (client_request_id, user, duration_ms)"));
+        assert!(error.contains("2 columns (ClientRequestId: String, DurationMs: String)"));
+
+        let error =
+            check_positional_columns::<Request>(&columns(&["DurationMs", "ClientRequestId"]))
+                .expect_err("Expected the swapped columns to fail")
+                .to_string();
+        assert!(error.contains("column 0 is 'DurationMs' but field 0 is 'client_request_id'"));
+    }
+
+    #[test]
+    fn positional_errors_name_the_columns() {
+        let rows = vec![json!(["abc", "someone", 20]), json!(["abc", 10])];
+        let error = deserialize_positional::<Mismatched>(
+            &columns(&["ClientRequestId", "DurationMs"]),
+            rows,
+        )
+        .expect_err("Expected the missing column to fail");
+
+        assert!(matches!(error, Error::ConversionError(_)));
+        let error = error.to_string();
+        assert!(error.contains("row 1 into"), "{error}");
+        assert!(error.contains("Mismatched"));
+        assert!(error.contains("invalid"));
+        assert!(error.contains("with the columns ClientRequestId: String, DurationMs: String"));
+    }
+
+    #[test]
+    fn v1_tables_are_deserialized_by_column_name() {
+        let column = |name: &str, column_type, data_type| ColumnV1 {
+            column_name: name.to_string(),
+            column_type,
+            data_type,
+        };
+        let table = TableV1 {
+            table_name: "Table_0".to_string(),
+            columns: vec![
+                column("DurationMs", None, Some(ColumnType::Long)),
+                column("ClientRequestId", Some(ColumnType::String), None),
+                column("Extra", None, None),
+            ],
+            rows: vec![vec![json!(10), json!("abc"), json!({"a": 1})]],
+            exceptions: None,
+        };
+
+        assert_eq!(table.columns[0].kusto_type(), Some(&ColumnType::Long));
+        assert_eq!(table.columns[2].kusto_type(), None);
+        assert_eq!(
+            column("Both", Some(ColumnType::Timespan), Some(ColumnType::String)).kusto_type(),
+            Some(&ColumnType::Timespan)
+        );
+        let result: Vec<Request> =
+            deserialize_table_v1(table, CaseMapping::PascalToSnake).expect("Failed to deserialize");
+        assert_eq!(
+            result,
+            vec![Request {
+                client_request_id: "abc".to_string(),
+                duration_ms: 10
+            }]
+        );
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Visualization {
+        kind: Option<String>,
+        series: Vec<String>,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Properties {
+        key: String,
+        value: Visualization,
+        text: String,
+        extra: serde_json::Value,
+    }
+
+    #[test]
+    fn dynamic_strings_are_parsed_when_normalized() {
+        let column = |name: &str, column_type| Column {
+            column_name: name.to_string(),
+            column_type,
+        };
+        let table = DataTable {
+            table_id: 0,
+            table_name: "@ExtendedProperties".to_string(),
+            table_kind: crate::models::TableKind::QueryProperties,
+            columns: vec![
+                column("key", ColumnType::String),
+                column("value", ColumnType::Dynamic),
+                column("text", ColumnType::String),
+                column("extra", ColumnType::Dynamic),
+            ],
+            rows: vec![
+                json!([
+                    "Visualization",
+                    r#"{"kind": "table", "series": ["a", "b"]}"#,
+                    r#"{"kind": null}"#,
+                    r#"{"nested": "[1, 2]"}"#
+                ]),
+                json!([
+                    "Visualization",
+                    {"kind": null, "series": []},
+                    "[not json]",
+                    "[not json]"
+                ]),
+            ],
+        };
+
+        assert!(table
+            .clone()
+            .into_structs::<Properties>(CaseMapping::Exact)
+            .is_err());
+
+        let properties: Vec<Properties> = table
+            .into_structs_normalized(CaseMapping::Exact)
+            .expect("Failed to deserialize");
+        assert_eq!(
+            properties[0].value,
+            Visualization {
+                kind: Some("table".to_string()),
+                series: vec!["a".to_string(), "b".to_string()]
+            }
+        );
+        // Only dynamic columns are parsed, and only at their top level
+        assert_eq!(properties[0].text, r#"{"kind": null}"#);
+        assert_eq!(properties[0].extra, json!({"nested": "[1, 2]"}));
+        assert_eq!(properties[1].value.series, Vec::<String>::new());
+        assert_eq!(properties[1].extra, json!("[not json]"));
+    }
+}
@@ -1,6 +1,8 @@
 //! Defines [Error] for representing failures in various operations.
+use crate::models::OneApiError;
+use crate::utils::{redact_string_literals, truncate_utf8_lossy};
 use azure_core::StatusCode;
-use std::fmt::Debug;
+use std::fmt::{Debug, Display, Formatter};
 use std::num::TryFromIntError;
 
 use thiserror;
@@ -16,9 +18,9 @@ pub enum Error {
     #[error("Error in external crate {0}")]
     ExternalError(String),
 
-    /// Error in HTTP
+    /// Error in HTTP, with the body of the response, which may quote the query and is redacted when displayed.
     #[error("Error in HTTP: {0} {1}")]
-    HttpError(StatusCode, String),
+    HttpError(StatusCode, QueryText),
 
     /// Error raised when an invalid argument / option is provided.
     #[error("Invalid argument {0}")]
@@ -44,13 +46,143 @@ pub enum Error {
     #[error("Operation not supported: {0}")]
     UnsupportedOperation(String),
 
-    /// Errors raised when the query is invalid
+    /// Errors raised when the query is invalid. The message may quote the query, and is redacted when displayed.
     #[error("Invalid query: {0}")]
-    QueryError(String),
+    QueryError(QueryText),
 
     /// Errors raised for IO operations
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
+
+    /// Error reported by the service within a response
+    #[error("Error in the query API: {0}")]
+    QueryApiError(Box<OneApiError>),
+
+    /// Multiple errors reported together
+    #[error("Multiple errors: {}", format_errors(.0))]
+    MultipleErrors(Vec<Error>),
+
+    /// Error raised when the columns of a result cannot be mapped to the fields of a struct
+    #[error("Error mapping columns to fields: {0}")]
+    ColumnMappingError(String),
+
+    /// Error of a query whose response was shared by identical concurrent queries, see [KustoClientOptions::with_request_coalescing](crate::client::KustoClientOptions::with_request_coalescing)
+    #[error("Error in a query shared with identical queries: {0}")]
+    CoalescedQueryError(std::sync::Arc<Error>),
+
+    /// Raised when a request fails because the cluster is stopped, and must be started before it can run queries.
+    /// See [KustoClientOptions::with_cluster_stopped_handler](crate::client::KustoClientOptions::with_cluster_stopped_handler).
+    #[error("The cluster {cluster} is stopped, start it to run queries")]
+    ClusterStopped {
+        /// The URL of the stopped cluster.
+        cluster: String,
+    },
+
+    /// Raised when a frame of a [StreamingDataset](crate::operations::streaming::StreamingDataset) is waited for,
+    /// but the response failed before it was received. The error the response failed with is the source of this one,
+    /// and is shared with the stream of tables, which yields the error itself unless it is still shared.
+    #[error("The {frame} was not received, as the response failed: {source}")]
+    StreamFailed {
+        /// The frame that was waited for.
+        frame: String,
+        /// The error that ended the response.
+        #[source]
+        source: std::sync::Arc<Error>,
+    },
+
+    /// Raised when a query is cancelled by its caller, see [KustoClient::execute_query_with_cancellation](crate::client::KustoClient::execute_query_with_cancellation).
+    #[error("The query {client_request_id} was cancelled")]
+    QueryCancelled {
+        /// The client request id the query was sent with, which `.cancel query` was sent for.
+        client_request_id: String,
+    },
+
+    /// Raised when an asynchronous management command ends without completing, see [KustoOperation::wait_for_completion](crate::async_operation::KustoOperation::wait_for_completion).
+    #[error("The operation {operation_id} ended in state {state}: {status}")]
+    OperationFailed {
+        /// The id of the operation.
+        operation_id: uuid::Uuid,
+        /// The state the operation ended in.
+        state: crate::async_operation::OperationState,
+        /// The status reported for the operation, usually the reason it failed.
+        status: String,
+        /// Whether the service reported that running the command again may succeed.
+        should_retry: bool,
+    },
+
+    /// Raised when an asynchronous management command is still running after the time it was waited for.
+    /// The operation itself keeps running on the cluster.
+    #[error("The operation {operation_id} did not complete within {timeout:?}")]
+    OperationTimeout {
+        /// The id of the operation.
+        operation_id: uuid::Uuid,
+        /// How long the operation was waited for.
+        timeout: std::time::Duration,
+    },
+
+    /// Raised when a response has no primary result, but one was expected.
+    #[error("No primary results found")]
+    NoPrimaryResult,
+
+    /// Raised when a response has several primary results, but a single one was expected.
+    #[error("Expected a single primary result, but found {0}")]
+    SeveralPrimaryResults(usize),
+}
+
+/// The maximum number of characters of a query shown when it is displayed as part of an [Error].
+pub const QUERY_TEXT_DISPLAY_CHARS: usize = 200;
+
+/// The text of a query carried by an [Error], or of a message that may quote one, such as an error reported by the service.
+///
+/// Queries may contain sensitive data, so they are displayed, and debug formatted, with their string literals masked and
+/// only their first [QUERY_TEXT_DISPLAY_CHARS] characters shown, since errors often end up in logs.
+/// The original text is returned by [as_str](QueryText::as_str), for callers that choose to log it.
+#[derive(Clone, PartialEq, Eq)]
+pub struct QueryText(String);
+
+impl QueryText {
+    /// Wraps the text of a query.
+    pub fn new(query: impl Into<String>) -> Self {
+        Self(query.into())
+    }
+
+    /// The original text of the query, including the contents of its string literals.
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for QueryText {
+    fn from(query: String) -> Self {
+        Self(query)
+    }
+}
+
+impl From<&str> for QueryText {
+    fn from(query: &str) -> Self {
+        Self(query.to_string())
+    }
+}
+
+impl Display for QueryText {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // The literals are masked first, so that a literal cut short is masked too
+        let redacted = redact_string_literals(&self.0);
+        let mut chars = redacted.chars();
+        let shown: String = chars.by_ref().take(QUERY_TEXT_DISPLAY_CHARS).collect();
+        f.write_str(&shown)?;
+        if chars.next().is_some() {
+            f.write_str("...")?;
+        }
+        Ok(())
+    }
+}
+
+impl Debug for QueryText {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("QueryText").field(&self.to_string()).finish()
+    }
 }
 
 /// Errors raised when an invalid argument or option is provided.
@@ -59,9 +191,35 @@ pub enum InvalidArgumentError {
     /// Error raised when a string denoting a duration is not valid.
     #[error("{0} is not a valid duration")]
     InvalidDuration(String),
+    /// Error raised when a negative duration is converted to a type that cannot represent it.
+    #[error("{0} is negative and cannot be converted to an unsigned duration")]
+    NegativeDuration(String),
+    /// Error raised when a datetime is outside of the range that can be represented.
+    #[error("{0} is outside of the supported range of datetimes")]
+    DateTimeOutOfRange(String),
     /// Error raised when failing to convert a number to u32.
     #[error("{0} is too large to fit in a u32")]
     PayloadTooLarge(#[from] TryFromIntError),
+    /// Error raised when a command can't be part of a database script, such as when it doesn't start with a dot.
+    #[error("'{0}' is not a management command that can be run in a database script")]
+    InvalidScriptCommand(QueryText),
+    /// Error raised when an endpoint override is not a URL the client can send requests to.
+    /// See [KustoClientOptions::with_endpoint_overrides](crate::client::KustoClientOptions::with_endpoint_overrides).
+    #[error("The endpoint '{endpoint}' can't be used: {msg}")]
+    InvalidEndpoint {
+        /// The endpoint.
+        endpoint: String,
+        /// Why the endpoint can't be used.
+        msg: String,
+    },
+    /// Error raised when a query parameter is NaN or infinite, which JSON numbers can't represent.
+    #[error("The query parameter '{name}' is {value}, which is not a finite number")]
+    NonFiniteParameter {
+        /// The name of the parameter.
+        name: String,
+        /// The value of the parameter, such as `NaN` or `inf`.
+        value: String,
+    },
 }
 
 /// Errors raised when parsing connection strings.
@@ -85,6 +243,91 @@ pub enum ConnectionStringError {
         /// The error message.
         msg: String,
     },
+    /// Raised when the data source is not https, and insecure endpoints were not allowed in the client options.
+    #[error(
+        "Data source '{}' is not secure, use https or allow insecure endpoints",
+        data_source
+    )]
+    InsecureEndpoint {
+        /// The insecure data source.
+        data_source: String,
+    },
+    /// Raised when the data source is not a valid URL of a cluster.
+    #[error("Data source '{}' is not a valid cluster URL: {}", data_source, msg)]
+    InvalidDataSource {
+        /// The invalid data source.
+        data_source: String,
+        /// Why the data source is invalid.
+        msg: String,
+    },
+    /// Raised when the data source uses a scheme other than https or http, such as net.tcp, which the client can't connect with.
+    #[error(
+        "Data source '{}' uses the unsupported scheme '{}', use the https endpoint of the cluster",
+        data_source,
+        scheme
+    )]
+    UnsupportedScheme {
+        /// The data source.
+        data_source: String,
+        /// The scheme of the data source.
+        scheme: String,
+    },
+    /// Raised when the data source is not a known Kusto endpoint, and untrusted endpoints are not allowed.
+    #[error(
+        "Data source '{}' is not a known Kusto endpoint, allow untrusted endpoints to connect to it",
+        data_source
+    )]
+    UntrustedEndpoint {
+        /// The untrusted data source.
+        data_source: String,
+    },
+}
+
+/// The maximum length, in bytes, of a response body kept in an [Error::HttpError].
+const MAX_ERROR_BODY_BYTES: usize = 4096;
+
+impl Error {
+    /// Creates an [Error::HttpError] from a response body that could not be parsed, keeping only the start of long bodies.
+    pub(crate) fn from_http_body(status_code: StatusCode, body: &[u8]) -> Self {
+        let body = String::from_utf8_lossy(body);
+        Error::HttpError(
+            status_code,
+            truncate_utf8_lossy(&body, MAX_ERROR_BODY_BYTES).into(),
+        )
+    }
+
+    /// Converts an exception reported by the service into an error, keeping its details if it is a [OneApiError].
+    pub(crate) fn from_exception(exception: &str) -> Self {
+        serde_json::from_str::<OneApiError>(exception).map_or_else(
+            |_| Error::QueryError(exception.into()),
+            |error| Error::QueryApiError(Box::new(error)),
+        )
+    }
+}
+
+impl Error {
+    /// The original text of the query carried by the error, or of the message or response body that may quote it, including the contents of its string literals,
+    /// which are masked when the error is displayed. Errors that are made of other errors return the text of the first that carries one.
+    #[must_use]
+    pub fn query_text(&self) -> Option<&str> {
+        match self {
+            Error::QueryError(query) | Error::HttpError(_, query) => Some(query.as_str()),
+            Error::InvalidArgumentError(InvalidArgumentError::InvalidScriptCommand(query)) => {
+                Some(query.as_str())
+            }
+            Error::CoalescedQueryError(error) => error.query_text(),
+            Error::MultipleErrors(errors) => errors.iter().find_map(Error::query_text),
+            _ => None,
+        }
+    }
+}
+
+fn format_errors(errors: &[Error]) -> String {
+    errors
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
 }
 
 impl ConnectionStringError {
@@ -101,3 +344,51 @@ impl ConnectionStringError {
 
 /// Result type for kusto operations.
 pub type Result<T> = std::result::Result<T, Error>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_literals_are_not_displayed() {
+        let query =
+            "Customers | where Email == 'jane@contoso.com' or Name == \"Jane \\\"JD\\\" Doe\"";
+        let errors = [
+            Error::QueryError(format!("Syntax error in {query}").into()),
+            Error::from_exception(&format!("Semantic error in {query}")),
+            Error::from_http_body(StatusCode::BadRequest, query.as_bytes()),
+            InvalidArgumentError::InvalidScriptCommand(query.into()).into(),
+        ];
+
+        for error in errors {
+            for shown in [error.to_string(), format!("{error:?}")] {
+                assert!(!shown.contains("jane@contoso.com"), "{shown}");
+                assert!(!shown.contains("JD"), "{shown}");
+                assert!(
+                    shown.contains("Customers | where Email == '***'"),
+                    "{shown}"
+                );
+            }
+            let original = error.query_text().expect("Expected the original text");
+            assert!(original.contains(query), "{original}");
+
+            let shared = Error::CoalescedQueryError(std::sync::Arc::new(error));
+            assert!(!shared.to_string().contains("jane@contoso.com"));
+            assert!(shared
+                .query_text()
+                .map_or(false, |text| text.contains(query)));
+        }
+        assert_eq!(Error::NoPrimaryResult.query_text(), None);
+    }
+
+    #[test]
+    fn long_queries_are_shortened() {
+        let query = format!("print x = '{}', y = {}", "secret", "1 + ".repeat(100));
+        let shown = QueryText::new(query.clone()).to_string();
+
+        assert!(shown.starts_with("print x = '***', y = 1 + 1 + "));
+        assert!(shown.ends_with("..."));
+        assert_eq!(shown.chars().count(), QUERY_TEXT_DISPLAY_CHARS + 3);
+        assert_eq!(QueryText::new(query.clone()).as_str(), query);
+    }
+}
@@ -0,0 +1,39 @@
+//! Loads the responses recorded in `tests/inputs`, for the unit tests of the crate.
+
+use crate::operations::query::{KustoResponseDataSetV1, KustoResponseDataSetV2};
+use crate::request_options::ParseOptions;
+use std::path::PathBuf;
+
+/// The path of a recorded response.
+pub(crate) fn fixture_path(file: &str) -> PathBuf {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("tests/inputs");
+    path.push(file);
+    path
+}
+
+/// The body of a recorded response.
+pub(crate) fn read_fixture(file: &str) -> Vec<u8> {
+    let path = fixture_path(file);
+    std::fs::read(&path).unwrap_or_else(|_| panic!("Failed to read {}", path.display()))
+}
+
+/// Parses a recorded V1 response.
+pub(crate) fn load_v1_response(file: &str) -> KustoResponseDataSetV1 {
+    serde_json::from_slice(&read_fixture(file)).expect("Failed to parse response")
+}
+
+/// Parses a recorded V2 response, as the client does.
+pub(crate) fn load_v2_response(file: &str) -> KustoResponseDataSetV2 {
+    KustoResponseDataSetV2::from_body(&read_fixture(file), ParseOptions::default())
+        .expect("Failed to parse response")
+}
+
+/// The frames of `three_statements.json`, with the frames of the third table moved before those of the first.
+pub(crate) fn three_statements_out_of_order() -> Vec<serde_json::Value> {
+    let mut frames: Vec<serde_json::Value> =
+        serde_json::from_slice(&read_fixture("three_statements.json")).unwrap();
+    let third: Vec<_> = frames.drain(9..12).collect();
+    frames.splice(2..2, third);
+    frames
+}
@@ -0,0 +1,129 @@
+//! Tuning of the HTTP connections that a [KustoClient](crate::client::KustoClient) sends its requests over.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use azure_core::error::{ErrorKind, ResultExt};
+use azure_core::HttpClient;
+
+use crate::error::Result;
+
+/// Settings of the HTTP client that sends the requests of a [KustoClient](crate::client::KustoClient),
+/// set with [with_http_transport](crate::client::KustoClientOptions::with_http_transport).
+///
+/// The default transport of azure_core doesn't keep idle connections, so each request opens a new connection and pays for its TLS handshake.
+/// Keeping idle connections lets requests that follow each other, such as those of [execute_queries_parallel](crate::client::KustoClient::execute_queries_parallel),
+/// reuse them instead. The number of connections that are open at once is not limited by the transport, but by the number of requests in flight.
+/// # Example
+/// ```rust
+/// use azure_kusto_data::http_transport::HttpTransportOptions;
+/// use azure_kusto_data::prelude::*;
+/// use std::time::Duration;
+///
+/// let client = KustoClient::new(
+///     ConnectionString::with_default_auth("https://mycluster.region.kusto.windows.net/"),
+///     KustoClientOptions::new().with_http_transport(
+///         HttpTransportOptions::new()
+///             .with_max_idle_connections_per_host(32)
+///             .with_idle_timeout(Duration::from_secs(60)),
+///     ),
+/// );
+/// # assert!(client.is_ok());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HttpTransportOptions {
+    max_idle_connections_per_host: Option<usize>,
+    idle_timeout: Option<Duration>,
+    http2_prior_knowledge: bool,
+    tcp_keepalive: Option<Duration>,
+}
+
+impl HttpTransportOptions {
+    /// Creates options that keep idle connections, with the defaults of the HTTP client for everything else.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how many idle connections are kept for each host. Unlimited by default.
+    #[must_use]
+    pub fn with_max_idle_connections_per_host(mut self, max: usize) -> Self {
+        self.max_idle_connections_per_host = Some(max);
+        self
+    }
+
+    /// Sets how long idle connections are kept before they are closed. 90 seconds by default.
+    #[must_use]
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = Some(idle_timeout);
+        self
+    }
+
+    /// Sends requests over HTTP/2 without negotiating it first, so that they are multiplexed over a single connection per host.
+    /// Only use it with endpoints that are known to support HTTP/2. Off by default.
+    #[must_use]
+    pub fn with_http2_prior_knowledge(mut self, http2_prior_knowledge: bool) -> Self {
+        self.http2_prior_knowledge = http2_prior_knowledge;
+        self
+    }
+
+    /// Sends TCP keep-alive probes on open connections at the given interval, so that idle connections are not dropped by proxies and load balancers.
+    /// Off by default.
+    #[must_use]
+    pub fn with_tcp_keepalive(mut self, interval: Duration) -> Self {
+        self.tcp_keepalive = Some(interval);
+        self
+    }
+
+    /// Builds the HTTP client for the transport of a client.
+    pub(crate) fn build(&self) -> Result<Arc<dyn HttpClient>> {
+        let mut builder = new_builder()
+            .pool_max_idle_per_host(self.max_idle_connections_per_host.unwrap_or(usize::MAX))
+            .tcp_keepalive(self.tcp_keepalive);
+        if let Some(idle_timeout) = self.idle_timeout {
+            builder = builder.pool_idle_timeout(idle_timeout);
+        }
+        if self.http2_prior_knowledge {
+            builder = builder.http2_prior_knowledge();
+        }
+        build_client(builder)
+    }
+}
+
+/// Builds the HTTP client of clients that don't tune their transport, which is the default transport of azure_core,
+/// except that it doesn't follow redirects.
+pub(crate) fn default_http_client() -> Result<Arc<dyn HttpClient>> {
+    // Like azure_core, don't keep idle connections, which may cause hyper to hang.
+    build_client(new_builder().pool_max_idle_per_host(0))
+}
+
+/// Redirects are followed by the [RedirectPolicy](crate::redirect_policy::RedirectPolicy) of the client,
+/// which only follows them to trusted endpoints, so the HTTP client must return them as they are.
+fn new_builder() -> reqwest::ClientBuilder {
+    reqwest::ClientBuilder::new().redirect(reqwest::redirect::Policy::none())
+}
+
+fn build_client(builder: reqwest::ClientBuilder) -> Result<Arc<dyn HttpClient>> {
+    let client = builder
+        .build()
+        .context(ErrorKind::Other, "Failed to build the HTTP client")?;
+    Ok(Arc::new(client))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transports_are_built() {
+        assert!(HttpTransportOptions::new().build().is_ok());
+        assert!(HttpTransportOptions::new()
+            .with_max_idle_connections_per_host(4)
+            .with_idle_timeout(Duration::from_secs(30))
+            .with_http2_prior_knowledge(true)
+            .with_tcp_keepalive(Duration::from_secs(15))
+            .build()
+            .is_ok());
+        assert!(default_http_client().is_ok());
+    }
+}
@@ -0,0 +1,79 @@
+//! Spans for queries, token acquisition and progressive responses, emitted when the `tracing` feature is enabled.
+//!
+//! Without the feature, [Span], [span!] and [Instrument] are no-ops, so the call sites don't need to be feature gated.
+//! Spans never carry secrets - tokens and connection strings are not recorded, and queries are identified by a hash of their text.
+//!
+//! Spans follow the conventions of `tracing-opentelemetry`, so that they can be exported to OpenTelemetry backends such as Jaeger
+//! by adding its layer to the subscriber: query spans are client spans (`otel.kind`) of the `kusto` database system (`db.system`),
+//! and spans that failed have their `otel.status_code` set to `ERROR`, besides the `error` itself.
+
+use std::fmt::Display;
+
+#[cfg(feature = "tracing")]
+pub(crate) use tracing::{Instrument, Span};
+
+/// Creates an `info` level span, see [tracing::info_span].
+#[cfg(feature = "tracing")]
+macro_rules! span {
+    ($($tokens:tt)*) => {
+        ::tracing::info_span!($($tokens)*)
+    };
+}
+
+/// Creates a no-op span, ignoring its fields.
+#[cfg(not(feature = "tracing"))]
+macro_rules! span {
+    ($($tokens:tt)*) => {
+        $crate::instrumentation::Span
+    };
+}
+
+pub(crate) use span;
+
+/// Records an error in the `error` field of the span, and marks the span as failed for OpenTelemetry.
+#[cfg(feature = "tracing")]
+pub(crate) fn record_error(span: &Span, error: &impl Display) {
+    span.record("error", tracing::field::display(error));
+    span.record("otel.status_code", "ERROR");
+}
+
+/// Records an error in the `error` field of the span, and marks the span as failed for OpenTelemetry.
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn record_error(_span: &Span, _error: &impl Display) {}
+
+/// Identifies a query in spans without recording its text, which may contain sensitive data.
+#[cfg(feature = "tracing")]
+pub(crate) fn query_hash(query: &str) -> String {
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    query.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// A no-op stand-in for [tracing::Span].
+#[cfg(not(feature = "tracing"))]
+#[derive(Debug, Clone)]
+pub(crate) struct Span;
+
+#[cfg(not(feature = "tracing"))]
+impl Span {
+    pub(crate) fn current() -> Self {
+        Span
+    }
+
+    pub(crate) fn record<V>(&self, _field: &str, _value: V) -> &Self {
+        self
+    }
+}
+
+/// A no-op stand-in for [tracing::Instrument].
+#[cfg(not(feature = "tracing"))]
+pub(crate) trait Instrument: Sized {
+    fn instrument(self, _span: Span) -> Self {
+        self
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+impl<T> Instrument for T {}
@@ -0,0 +1,460 @@
+//! Typed rows of the metadata tables that the service returns along with the results of a V2 query:
+//! the `QueryProperties` table, named `@ExtendedProperties`, and the `QueryCompletionInformation` table.
+//!
+//! Get them with [KustoResponseDataSetV2::query_properties] and [KustoResponseDataSetV2::query_completion].
+//!
+//! Also holds typed rows of the health outputs of the `.show cluster` and `.show diagnostics` management commands,
+//! which are V1 tables. Get them with [KustoClient::show_cluster] and [KustoClient::show_diagnostics].
+//! Their columns are matched by name, and the columns that older clusters don't return are optional.
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::client::KustoClient;
+use crate::deserialization::{deserialize_table_v1, deserialize_values, CaseMapping};
+use crate::error::{Error, Result};
+use crate::models::{DataTable, TableKind};
+use crate::operations::query::{checked, KustoResponseDataSetV1, KustoResponseDataSetV2, Partial};
+use crate::types::KustoDateTime;
+
+/// The name of the `QueryProperties` table.
+pub const QUERY_PROPERTIES_TABLE_NAME: &str = "@ExtendedProperties";
+/// The name of the `QueryCompletionInformation` table.
+pub const QUERY_COMPLETION_INFORMATION_TABLE_NAME: &str = "QueryCompletionInformation";
+
+/// A row of the `QueryProperties` table, such as the visualization requested by a `render` operator.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct QueryProperties {
+    /// The id of the table that the property applies to.
+    pub table_id: i32,
+    /// The name of the property, such as `Visualization`.
+    pub key: String,
+    /// The value of the property, which is often a JSON document encoded as a string.
+    pub value: serde_json::Value,
+}
+
+/// A row of the `QueryCompletionInformation` table, which holds the events of the execution of a query.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct QueryCompletionInformation {
+    /// When the event happened.
+    pub timestamp: KustoDateTime,
+    /// The client request id of the query.
+    pub client_request_id: String,
+    /// The activity id of the query.
+    pub activity_id: Uuid,
+    /// The id of the sub-activity that raised the event.
+    pub sub_activity_id: Uuid,
+    /// The id of the parent activity of the query.
+    pub parent_activity_id: Uuid,
+    /// The severity of the event.
+    pub level: i32,
+    /// The name of the severity of the event, such as `Info` or `Error`.
+    pub level_name: String,
+    /// The status code of the event.
+    pub status_code: i32,
+    /// The name of the status code, such as `S_OK (0)`.
+    pub status_code_name: String,
+    /// The type of the event.
+    pub event_type: i32,
+    /// The name of the type of the event, such as `QueryInfo` or `QueryResourceConsumption`.
+    pub event_type_name: String,
+    /// The details of the event, as a JSON document.
+    pub payload: String,
+}
+
+/// A node of the cluster, as listed by `.show cluster`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ClusterNode {
+    /// The id of the node.
+    pub node_id: String,
+    /// The address of the node, such as `net.tcp://10.0.0.4:23107/`.
+    pub address: String,
+    /// The name of the machine of the node.
+    pub name: String,
+    /// When the node was last started.
+    pub start_time: KustoDateTime,
+    /// Whether the node is the admin node of the cluster.
+    pub is_admin: Option<bool>,
+    /// The total memory of the machine, in bytes.
+    pub machine_total_memory: Option<i64>,
+    /// The memory available on the machine, in bytes.
+    pub machine_available_memory: Option<i64>,
+    /// The number of processors of the machine.
+    pub processor_count: Option<i32>,
+    /// The size of the hot cache assigned to the node, in bytes.
+    pub assigned_hot_cache_size: Option<i64>,
+    /// The version of the service running on the node.
+    pub product_version: Option<String>,
+}
+
+/// The health of the cluster, as returned by `.show diagnostics`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ClusterDiagnostics {
+    /// Whether the cluster is healthy.
+    pub is_healthy: bool,
+    /// Why the cluster is not healthy, or `[none]`.
+    pub not_healthy_reason: Option<String>,
+    /// Whether the cluster needs the attention of an operator.
+    pub is_attention_required: Option<bool>,
+    /// Why the cluster needs attention, or `[none]`.
+    pub attention_required_reason: Option<String>,
+    /// Whether the cluster should be scaled out.
+    pub is_scale_out_required: Option<bool>,
+    /// The number of machines of the cluster.
+    pub machines_total: i32,
+    /// The number of machines of the cluster that are offline.
+    pub machines_offline: i32,
+    /// When a node of the cluster was last restarted.
+    pub node_last_restarted_on: Option<KustoDateTime>,
+    /// When the admin node was last elected.
+    pub admin_last_elected_on: Option<KustoDateTime>,
+    /// The memory load of the cluster, as a percentage.
+    pub memory_load_factor: Option<f64>,
+    /// The number of extents in the cluster.
+    pub extents_total: Option<i64>,
+    /// The ingestion load of the cluster, as a percentage.
+    pub ingestions_load_factor: Option<f64>,
+    /// The number of ingestions in progress.
+    pub ingestions_in_progress: Option<i64>,
+    /// The percentage of ingestions that succeeded in the last 10 minutes.
+    pub ingestions_success_rate: Option<f64>,
+    /// The number of merges in progress.
+    pub merges_in_progress: Option<i64>,
+    /// The percentage of the data capacity of the cluster that is used.
+    pub cluster_data_capacity_factor: Option<f64>,
+    /// The version of the build deployed to the cluster.
+    pub build_version: Option<String>,
+    /// The version of the service.
+    pub product_version: Option<String>,
+}
+
+/// Whether a table holds metadata of the query rather than its results, judging by either its kind or its name.
+fn is_metadata(table: &DataTable) -> bool {
+    matches!(
+        table.table_kind,
+        TableKind::QueryProperties | TableKind::QueryCompletionInformation
+    ) || table.table_name == QUERY_PROPERTIES_TABLE_NAME
+        || table.table_name == QUERY_COMPLETION_INFORMATION_TABLE_NAME
+}
+
+impl KustoResponseDataSetV2 {
+    /// Deserializes the rows of the `QueryProperties` tables of the response,
+    /// along with the errors that the service reported in place of some of them.
+    ///
+    /// Returns an empty list if the response has no such table.
+    pub fn query_properties(&self) -> Result<Partial<Vec<QueryProperties>>> {
+        self.known_table_rows(TableKind::QueryProperties)
+    }
+
+    /// Deserializes the rows of the `QueryCompletionInformation` tables of the response,
+    /// along with the errors that the service reported in place of some of them.
+    ///
+    /// Returns an empty list if the response has no such table, such as when it was not fully received.
+    /// # Example
+    /// ```no_run
+    /// use azure_kusto_data::prelude::*;
+    /// # #[tokio::main] async fn main() -> Result<(), Error> {
+    /// let client = KustoClient::new(
+    ///    ConnectionString::with_default_auth("https://mycluster.region.kusto.windows.net/"),
+    ///    KustoClientOptions::default())?;
+    ///
+    /// let response = client.execute_query("some_database", "MyTable | take 10", None).await?;
+    /// for event in response.query_completion()?.data {
+    ///     println!("{}: {}", event.event_type_name, event.payload);
+    /// }
+    /// # Ok(())}
+    /// ```
+    pub fn query_completion(&self) -> Result<Partial<Vec<QueryCompletionInformation>>> {
+        self.known_table_rows(TableKind::QueryCompletionInformation)
+    }
+
+    /// Iterates over the primary tables in the response, as [primary_results](Self::primary_results) does,
+    /// but also skips the metadata tables that are marked as primary results, recognizing them by their name.
+    pub fn primary_results_without_metadata(&self) -> impl Iterator<Item = Result<DataTable>> + '_ {
+        self.primary_results()
+            .filter(|table| table.as_ref().map_or(true, |table| !is_metadata(table)))
+    }
+
+    fn known_table_rows<T: serde::de::DeserializeOwned>(
+        &self,
+        kind: TableKind,
+    ) -> Result<Partial<Vec<T>>> {
+        let tables = checked(self.parsed_tables_of_kind(kind))?;
+        let mut rows = Vec::new();
+        for table in tables.data {
+            rows.extend(deserialize_values(
+                &table.columns,
+                table.rows,
+                CaseMapping::PascalToSnake,
+            )?);
+        }
+        Ok(Partial {
+            data: rows,
+            errors: tables.errors,
+        })
+    }
+}
+
+/// Deserializes the rows of the first table of the response to a management command, converting the column names to snake_case.
+fn command_rows<T: serde::de::DeserializeOwned>(
+    response: KustoResponseDataSetV1,
+    command: &str,
+) -> Result<Vec<T>> {
+    let table = response
+        .tables
+        .into_iter()
+        .next()
+        .ok_or_else(|| Error::QueryError(format!("No results found for `{command}`").into()))?;
+    deserialize_table_v1(table, CaseMapping::PascalToSnake)
+}
+
+fn parse_cluster_nodes(response: KustoResponseDataSetV1) -> Result<Vec<ClusterNode>> {
+    command_rows(response, ".show cluster")
+}
+
+fn parse_diagnostics(response: KustoResponseDataSetV1) -> Result<ClusterDiagnostics> {
+    command_rows(response, ".show diagnostics")?
+        .into_iter()
+        .next()
+        .ok_or_else(|| Error::QueryError("`.show diagnostics` returned no rows".into()))
+}
+
+impl KustoClient {
+    /// Lists the nodes of the cluster, with `.show cluster`.
+    /// # Example
+    /// ```no_run
+    /// use azure_kusto_data::prelude::*;
+    /// # #[tokio::main] async fn main() -> Result<(), Error> {
+    /// let client = KustoClient::new(
+    ///    ConnectionString::with_default_auth("https://mycluster.region.kusto.windows.net/"),
+    ///    KustoClientOptions::default())?;
+    ///
+    /// for node in client.show_cluster().await? {
+    ///     println!("{} started at {}", node.name, node.start_time);
+    /// }
+    /// # Ok(())}
+    /// ```
+    pub async fn show_cluster(&self) -> Result<Vec<ClusterNode>> {
+        let response = self.execute_cluster_command(".show cluster", None).await?;
+        parse_cluster_nodes(response)
+    }
+
+    /// Gets the health of the cluster, with `.show diagnostics`.
+    pub async fn show_diagnostics(&self) -> Result<ClusterDiagnostics> {
+        let response = self
+            .execute_cluster_command(".show diagnostics", None)
+            .await?;
+        parse_diagnostics(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{load_v1_response, load_v2_response};
+
+    #[test]
+    fn parses_query_properties() {
+        let properties = load_v2_response("validFrames.json")
+            .query_properties()
+            .expect("Failed to parse the query properties");
+
+        assert!(properties.is_complete());
+        let properties = properties.data;
+        assert_eq!(properties.len(), 1);
+        assert_eq!(properties[0].table_id, 1);
+        assert_eq!(properties[0].key, "Visualization");
+        assert!(properties[0]
+            .value
+            .as_str()
+            .map_or(false, |v| v.contains("\"IsQuerySorted\":false")));
+    }
+
+    #[test]
+    fn parses_query_completion_information() {
+        let events = load_v2_response("validFrames.json")
+            .query_completion()
+            .expect("Failed to parse the query completion information")
+            .data;
+
+        assert_eq!(events.len(), 1);
+        let event = &events[0];
+        assert_eq!(event.timestamp.to_string(), "2023-01-03T00:00:01.0000000Z");
+        assert_eq!(event.client_request_id, "KRust;mock-transport");
+        assert_eq!(
+            event.activity_id,
+            Uuid::parse_str("123e4567-e89b-12d3-a456-426614174000").unwrap()
+        );
+        assert_eq!(event.level_name, "Info");
+        assert_eq!(event.status_code, 0);
+        assert_eq!(event.event_type_name, "QueryInfo");
+        assert_eq!(
+            event.payload,
+            r#"{"Count":1,"Text":"Query completed successfully"}"#
+        );
+    }
+
+    #[test]
+    fn metadata_tables_are_read_without_assembling_the_others() {
+        let response = load_v2_response("progressive_wrong_row_count.json");
+        assert!(response.parsed_data_tables().any(|table| table.is_err()));
+
+        let properties = response
+            .query_properties()
+            .expect("Failed to parse the query properties");
+        assert!(properties.is_complete());
+        assert_eq!(properties.data.len(), 1);
+    }
+
+    #[test]
+    fn errors_reported_in_metadata_tables_are_returned() {
+        let mut response = load_v2_response("validFrames.json");
+        for result in &mut response.results {
+            if let crate::models::V2QueryResult::DataTable(table) = result {
+                if table.table_kind == TableKind::QueryCompletionInformation {
+                    table.rows.push(serde_json::json!({"OneApiErrors": [{"error": {
+                        "code": "LimitsExceeded",
+                        "message": "Request is invalid and cannot be executed.",
+                        "@type": "Kusto.Data.Exceptions.KustoServicePartialQueryFailureLimitsExceededException",
+                        "@message": "Query execution has exceeded the allowed limits.",
+                        "@permanent": false
+                    }}]}));
+                }
+            }
+        }
+
+        let events = response
+            .query_completion()
+            .expect("Failed to parse the query completion information");
+        assert!(!events.is_complete());
+        assert_eq!(events.data.len(), 1);
+        assert_eq!(events.errors[0].error_message.code, "LimitsExceeded");
+    }
+
+    #[test]
+    fn metadata_tables_marked_as_primary_results_are_skipped() {
+        let mut response = load_v2_response("validFrames.json");
+        for result in &mut response.results {
+            if let crate::models::V2QueryResult::DataTable(table) = result {
+                if table.table_name == QUERY_PROPERTIES_TABLE_NAME {
+                    table.table_kind = TableKind::PrimaryResult;
+                }
+            }
+        }
+
+        assert_eq!(response.primary_results().count(), 2);
+        let tables: Vec<DataTable> = response
+            .primary_results_without_metadata()
+            .collect::<Result<_>>()
+            .expect("Failed to parse the tables");
+        assert_eq!(tables.len(), 1);
+        assert_eq!(tables[0].table_name, "PrimaryResult");
+    }
+
+    #[test]
+    fn parses_cluster_nodes() {
+        let nodes = parse_cluster_nodes(load_v1_response("show_cluster.json"))
+            .expect("Failed to parse the nodes");
+
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(
+            nodes[0],
+            ClusterNode {
+                node_id: "KEngine000000".to_string(),
+                address: "net.tcp://10.0.0.4:23107/".to_string(),
+                name: "KEngine000000".to_string(),
+                start_time: "2024-05-02T08:14:03.1234567Z".parse().unwrap(),
+                is_admin: Some(true),
+                machine_total_memory: Some(68_719_476_736),
+                machine_available_memory: Some(40_265_318_400),
+                processor_count: Some(8),
+                assigned_hot_cache_size: Some(549_755_813_888),
+                product_version: Some("1.0.8852.12345".to_string()),
+            }
+        );
+        assert_eq!(nodes[1].is_admin, Some(false));
+    }
+
+    #[test]
+    fn parses_cluster_nodes_of_older_clusters() {
+        let nodes = parse_cluster_nodes(load_v1_response("show_cluster_legacy.json"))
+            .expect("Failed to parse the nodes");
+
+        assert_eq!(
+            nodes,
+            vec![ClusterNode {
+                node_id: "KEngine000000".to_string(),
+                address: "net.tcp://10.0.0.4:23107/".to_string(),
+                name: "KEngine000000".to_string(),
+                start_time: "2019-11-20T16:02:11Z".parse().unwrap(),
+                is_admin: Some(true),
+                machine_total_memory: Some(34_359_738_368),
+                machine_available_memory: None,
+                processor_count: Some(4),
+                assigned_hot_cache_size: None,
+                product_version: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn parses_diagnostics() {
+        let diagnostics = parse_diagnostics(load_v1_response("show_diagnostics.json"))
+            .expect("Failed to parse the diagnostics");
+
+        assert!(!diagnostics.is_healthy);
+        assert_eq!(
+            diagnostics.not_healthy_reason.as_deref(),
+            Some("Machine KEngine000001 is offline")
+        );
+        assert_eq!(diagnostics.is_attention_required, Some(true));
+        assert_eq!(diagnostics.machines_total, 3);
+        assert_eq!(diagnostics.machines_offline, 1);
+        assert_eq!(
+            diagnostics.node_last_restarted_on.map(|d| d.to_string()),
+            Some("2024-05-02T08:15:41.7654321Z".to_string())
+        );
+        assert_eq!(diagnostics.memory_load_factor, Some(42.5));
+        assert_eq!(diagnostics.ingestions_in_progress, Some(7));
+        assert_eq!(diagnostics.ingestions_success_rate, Some(99.8));
+        assert_eq!(diagnostics.merges_in_progress, Some(2));
+        assert_eq!(diagnostics.product_version.as_deref(), Some("2024.05.01.3"));
+    }
+
+    #[test]
+    fn parses_diagnostics_of_older_clusters() {
+        let diagnostics = parse_diagnostics(load_v1_response("show_diagnostics_legacy.json"))
+            .expect("Failed to parse the diagnostics");
+
+        assert_eq!(
+            diagnostics,
+            ClusterDiagnostics {
+                is_healthy: true,
+                not_healthy_reason: None,
+                is_attention_required: None,
+                attention_required_reason: None,
+                is_scale_out_required: None,
+                machines_total: 2,
+                machines_offline: 0,
+                node_last_restarted_on: None,
+                admin_last_elected_on: Some("2019-11-20T16:02:11Z".parse().unwrap()),
+                memory_load_factor: Some(12.75),
+                extents_total: Some(904),
+                ingestions_load_factor: None,
+                ingestions_in_progress: Some(0),
+                ingestions_success_rate: None,
+                merges_in_progress: None,
+                cluster_data_capacity_factor: None,
+                build_version: Some("1.0.7263.23124".to_string()),
+                product_version: None,
+            }
+        );
+    }
+
+    #[test]
+    fn empty_diagnostics_are_an_error() {
+        let mut response = load_v1_response("show_diagnostics.json");
+        response.tables[0].rows.clear();
+        assert!(parse_diagnostics(response).is_err());
+    }
+}
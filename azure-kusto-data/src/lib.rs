@@ -6,15 +6,42 @@
 
 #[cfg(feature = "arrow")]
 mod arrow;
+pub mod async_operation;
 mod authorization_policy;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(feature = "test-util")]
+pub mod chaos;
 pub mod client;
 pub mod client_details;
 pub mod cloud_info;
+mod cloud_info_disk_cache;
+pub mod cluster_stopped;
+mod coalescing;
 pub mod connection_string;
 pub mod credentials;
+pub mod database_script;
+pub mod deserialization;
 pub mod error;
+#[cfg(test)]
+mod fixtures;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod http_transport;
+mod instrumentation;
+pub mod known_tables;
+pub mod lookup;
 pub mod models;
 mod operations;
+#[cfg(feature = "polars")]
+pub mod polars;
 pub mod prelude;
+mod redirect_policy;
 pub mod request_options;
+pub mod row_view;
+pub mod schema;
+pub mod slow_query;
+pub mod statistics;
+pub mod trace_context;
+mod trusted_endpoints;
 pub mod types;
+mod utils;
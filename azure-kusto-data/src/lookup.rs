@@ -0,0 +1,328 @@
+//! Conversion of result tables into keyed collections, such as a lookup from a key column to a value column,
+//! or the rows of a table keyed by one of their columns.
+//!
+//! # Example
+//! ```rust
+//! use serde_json::json;
+//! use azure_kusto_data::lookup::DuplicateKeys;
+//! use azure_kusto_data::models::*;
+//!
+//! let table = DataTable {
+//!     table_id: 1,
+//!     table_name: "PrimaryResult".to_string(),
+//!     table_kind: TableKind::PrimaryResult,
+//!     columns: vec![
+//!         Column { column_name: "Id".to_string(), column_type: ColumnType::Long },
+//!         Column { column_name: "Name".to_string(), column_type: ColumnType::String },
+//!     ],
+//!     rows: vec![json!([1, "first"]), json!([2, "second"])],
+//! };
+//!
+//! let names = table.to_lookup::<i64, String>("Id", "Name", DuplicateKeys::Error).unwrap();
+//! assert_eq!(names[&2], "second");
+//! ```
+
+use std::hash::Hash;
+
+use hashbrown::hash_map::Entry;
+use hashbrown::HashMap;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::deserialization::{deserialize_borrowed_values, CaseMapping};
+use crate::error::{Error, Result};
+use crate::models::DataTable;
+use crate::row_view::ColumnIndex;
+
+/// What to do when several rows have the same key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicateKeys {
+    /// Fail the conversion.
+    #[default]
+    Error,
+    /// Keep the value of the first row with the key.
+    KeepFirst,
+    /// Keep the value of the last row with the key.
+    KeepLast,
+}
+
+fn column_position(table: &DataTable, column: impl ColumnIndex) -> Result<usize> {
+    column.index_in(table).ok_or_else(|| {
+        Error::ConversionError(format!(
+            "table '{}', which has no column {column}",
+            table.table_name
+        ))
+    })
+}
+
+/// Deserializes the keys of the rows from the key column.
+fn keys<K: DeserializeOwned>(table: &DataTable, key_column: usize) -> Result<Vec<(K, &Value)>> {
+    table
+        .row_views()
+        .map(|row| {
+            let value = row.get(key_column)?;
+            Ok((K::deserialize(value)?, value))
+        })
+        .collect()
+}
+
+fn insert<K: Eq + Hash, V>(
+    map: &mut HashMap<K, V>,
+    key: K,
+    value: V,
+    duplicates: DuplicateKeys,
+    describe: impl FnOnce() -> String,
+) -> Result<()> {
+    match map.entry(key) {
+        Entry::Vacant(entry) => {
+            entry.insert(value);
+        }
+        Entry::Occupied(mut entry) => match duplicates {
+            DuplicateKeys::Error => return Err(Error::ConversionError(describe())),
+            DuplicateKeys::KeepFirst => {}
+            DuplicateKeys::KeepLast => {
+                entry.insert(value);
+            }
+        },
+    }
+    Ok(())
+}
+
+fn duplicate_key(table: &DataTable, key_column: usize, key: &Value, row: usize) -> String {
+    format!(
+        "table '{}', the key {key} of column '{}' is repeated in row {row}",
+        table.table_name, table.columns[key_column].column_name
+    )
+}
+
+impl DataTable {
+    /// Builds a lookup from the values of the key column to those of the value column.
+    ///
+    /// Keys and values are deserialized from their cells, so the key column of a `long` column can be read as an `i64`,
+    /// and that of a `string` column as a `String`. Rows with the same key are handled according to `duplicates`.
+    pub fn to_lookup<K, V>(
+        &self,
+        key_column: impl ColumnIndex,
+        value_column: impl ColumnIndex,
+        duplicates: DuplicateKeys,
+    ) -> Result<HashMap<K, V>>
+    where
+        K: DeserializeOwned + Eq + Hash,
+        V: DeserializeOwned,
+    {
+        let key_column = column_position(self, key_column)?;
+        let value_column = column_position(self, value_column)?;
+
+        let mut lookup = HashMap::with_capacity(self.rows.len());
+        for ((key, raw_key), row) in keys(self, key_column)?.into_iter().zip(self.row_views()) {
+            let value = V::deserialize(row.get(value_column)?)?;
+            insert(&mut lookup, key, value, duplicates, || {
+                duplicate_key(self, key_column, raw_key, row.index())
+            })?;
+        }
+        Ok(lookup)
+    }
+
+    /// Builds a lookup from the values of the key column to all of the values of the value column with that key, in order.
+    pub fn to_grouped_lookup<K, V>(
+        &self,
+        key_column: impl ColumnIndex,
+        value_column: impl ColumnIndex,
+    ) -> Result<HashMap<K, Vec<V>>>
+    where
+        K: DeserializeOwned + Eq + Hash,
+        V: DeserializeOwned,
+    {
+        let key_column = column_position(self, key_column)?;
+        let value_column = column_position(self, value_column)?;
+
+        let mut lookup: HashMap<K, Vec<V>> = HashMap::new();
+        for ((key, _), row) in keys(self, key_column)?.into_iter().zip(self.row_views()) {
+            let value = V::deserialize(row.get(value_column)?)?;
+            lookup.entry(key).or_default().push(value);
+        }
+        Ok(lookup)
+    }
+
+    /// Deserializes the rows of the table into `T`, keyed by the values of the key column.
+    ///
+    /// The columns are matched to the fields of `T` by name, according to the `case_mapping`.
+    /// Rows with the same key are handled according to `duplicates`.
+    pub fn rows_keyed_by<K, T>(
+        &self,
+        key_column: impl ColumnIndex,
+        case_mapping: CaseMapping,
+        duplicates: DuplicateKeys,
+    ) -> Result<HashMap<K, T>>
+    where
+        K: DeserializeOwned + Eq + Hash,
+        T: DeserializeOwned,
+    {
+        let key_column = column_position(self, key_column)?;
+        let keys = keys::<K>(self, key_column)?;
+        let rows: Vec<T> = deserialize_borrowed_values(&self.columns, &self.rows, case_mapping)?;
+
+        let mut keyed = HashMap::with_capacity(rows.len());
+        for (index, ((key, raw_key), row)) in keys.into_iter().zip(rows).enumerate() {
+            insert(&mut keyed, key, row, duplicates, || {
+                duplicate_key(self, key_column, raw_key, index)
+            })?;
+        }
+        Ok(keyed)
+    }
+
+    /// Deserializes the rows of the table into `T`, grouped by the values of the key column, in order.
+    ///
+    /// The columns are matched to the fields of `T` by name, according to the `case_mapping`.
+    pub fn rows_grouped_by<K, T>(
+        &self,
+        key_column: impl ColumnIndex,
+        case_mapping: CaseMapping,
+    ) -> Result<HashMap<K, Vec<T>>>
+    where
+        K: DeserializeOwned + Eq + Hash,
+        T: DeserializeOwned,
+    {
+        let key_column = column_position(self, key_column)?;
+        let keys = keys::<K>(self, key_column)?;
+        let rows: Vec<T> = deserialize_borrowed_values(&self.columns, &self.rows, case_mapping)?;
+
+        let mut grouped: HashMap<K, Vec<T>> = HashMap::new();
+        for ((key, _), row) in keys.into_iter().zip(rows) {
+            grouped.entry(key).or_default().push(row);
+        }
+        Ok(grouped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Column, ColumnType, TableKind};
+    use serde::Deserialize;
+    use serde_json::json;
+
+    fn table() -> DataTable {
+        let column = |name: &str, column_type| Column {
+            column_name: name.to_string(),
+            column_type,
+        };
+        DataTable {
+            table_id: 1,
+            table_name: "Users".to_string(),
+            table_kind: TableKind::PrimaryResult,
+            columns: vec![
+                column("Id", ColumnType::Long),
+                column("Team", ColumnType::String),
+                column("Name", ColumnType::String),
+            ],
+            rows: vec![
+                json!([1, "red", "ann"]),
+                json!([2, "blue", "bob"]),
+                json!([3, "red", "cat"]),
+            ],
+        }
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    #[serde(rename_all = "PascalCase")]
+    struct User {
+        id: i64,
+        team: String,
+        name: String,
+    }
+
+    #[test]
+    fn numeric_and_string_keys() {
+        let table = table();
+
+        let by_id: HashMap<i64, String> =
+            table.to_lookup("Id", "Name", DuplicateKeys::Error).unwrap();
+        assert_eq!(by_id.len(), 3);
+        assert_eq!(by_id[&2], "bob");
+
+        let by_name: HashMap<String, i64> =
+            table.to_lookup("Name", 0, DuplicateKeys::Error).unwrap();
+        assert_eq!(by_name["cat"], 3);
+    }
+
+    #[test]
+    fn duplicate_keys() {
+        let table = table();
+
+        let error = table
+            .to_lookup::<String, String>("Team", "Name", DuplicateKeys::Error)
+            .expect_err("Expected the duplicate team to fail");
+        assert!(error
+            .to_string()
+            .contains(r#"the key "red" of column 'Team' is repeated in row 2"#));
+
+        let first: HashMap<String, String> = table
+            .to_lookup("Team", "Name", DuplicateKeys::KeepFirst)
+            .unwrap();
+        assert_eq!(first["red"], "ann");
+
+        let last: HashMap<String, String> = table
+            .to_lookup("Team", "Name", DuplicateKeys::KeepLast)
+            .unwrap();
+        assert_eq!(last["red"], "cat");
+        assert_eq!(last["blue"], "bob");
+
+        let grouped: HashMap<String, Vec<String>> =
+            table.to_grouped_lookup("Team", "Name").unwrap();
+        assert_eq!(grouped["red"], vec!["ann", "cat"]);
+        assert_eq!(grouped["blue"], vec!["bob"]);
+    }
+
+    #[test]
+    fn rows_keyed_by_a_column() {
+        let table = table();
+
+        let users: HashMap<i64, User> = table
+            .rows_keyed_by("Id", CaseMapping::Exact, DuplicateKeys::Error)
+            .unwrap();
+        assert_eq!(
+            users[&1],
+            User {
+                id: 1,
+                team: "red".to_string(),
+                name: "ann".to_string()
+            }
+        );
+
+        assert!(table
+            .rows_keyed_by::<String, User>("Team", CaseMapping::Exact, DuplicateKeys::Error)
+            .is_err());
+        let last: HashMap<String, User> = table
+            .rows_keyed_by("Team", CaseMapping::Exact, DuplicateKeys::KeepLast)
+            .unwrap();
+        assert_eq!(last["red"].name, "cat");
+
+        let teams: HashMap<String, Vec<User>> =
+            table.rows_grouped_by("Team", CaseMapping::Exact).unwrap();
+        assert_eq!(
+            teams["red"].iter().map(|u| u.id).collect::<Vec<_>>(),
+            vec![1, 3]
+        );
+    }
+
+    #[test]
+    fn missing_columns_are_errors() {
+        let table = table();
+
+        let error = table
+            .to_lookup::<i64, String>("Id", "Email", DuplicateKeys::Error)
+            .expect_err("Expected the missing column to fail");
+        assert!(error.to_string().contains("no column Email"));
+
+        assert!(table
+            .rows_keyed_by::<i64, User>(7, CaseMapping::Exact, DuplicateKeys::Error)
+            .is_err());
+    }
+
+    #[test]
+    fn keys_of_another_type_are_errors() {
+        let result = table().to_lookup::<i64, String>("Name", "Id", DuplicateKeys::Error);
+        assert!(matches!(result, Err(Error::JsonError(_))));
+    }
+}
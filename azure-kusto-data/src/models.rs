@@ -1,17 +1,62 @@
 //! Models to parse responses from ADX.
 use crate::prelude::ClientRequestProperties;
-use serde::{Deserialize, Serialize};
+use crate::request_options::PropertiesSerialization;
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
 
-#[derive(Debug, Serialize, Deserialize)]
-#[allow(non_snake_case)]
+#[derive(Debug)]
 pub(crate) struct QueryBody {
     /// Name of the database in scope that is the target of the query or control command
     pub db: String,
     /// Text of the query or control command to execute
     pub csl: String,
     /// Additional parameters and options for fine-grained control of the request behavior
-    #[serde(skip_serializing_if = "Option::is_none")]
     pub properties: Option<ClientRequestProperties>,
+    /// How the properties are serialized when they, or their options and parameters, are not set
+    pub properties_serialization: PropertiesSerialization,
+}
+
+impl Serialize for QueryBody {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut body = serializer.serialize_struct("QueryBody", 3)?;
+        body.serialize_field("db", &self.db)?;
+        body.serialize_field("csl", &self.csl)?;
+        match (self.properties_serialization, &self.properties) {
+            (PropertiesSerialization::OmitNullProperties, Some(properties)) => {
+                body.serialize_field("properties", properties)?
+            }
+            (PropertiesSerialization::OmitNullProperties, None) => body.skip_field("properties")?,
+            (PropertiesSerialization::EmitNullProperties, None) => {
+                body.serialize_field("properties", &())?
+            }
+            (PropertiesSerialization::EmitEmptyObject, None) => {
+                body.serialize_field("properties", &serde_json::Map::new())?
+            }
+            (_, Some(properties)) => {
+                body.serialize_field("properties", &EmittedProperties(properties))?
+            }
+        }
+        body.end()
+    }
+}
+
+/// Serializes properties with their options and parameters, as `{}` when they are not set.
+struct EmittedProperties<'a>(&'a ClientRequestProperties);
+
+impl Serialize for EmittedProperties<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let empty = serde_json::Map::new();
+        let mut properties = serializer.serialize_struct("ClientRequestProperties", 2)?;
+        match &self.0.options {
+            Some(options) => properties.serialize_field("options", options)?,
+            None => properties.serialize_field("options", &empty)?,
+        }
+        match &self.0.parameters {
+            Some(parameters) => properties.serialize_field("parameters", parameters)?,
+            None => properties.serialize_field("parameters", &empty)?,
+        }
+        properties.end()
+    }
 }
 
 /// Represents the scalar data types of ADX. see [the docs for more information](https://docs.microsoft.com/en-us/azure/data-explorer/kusto/query/scalar-data-types/)
@@ -74,9 +119,19 @@ pub struct ColumnV1 {
     pub data_type: Option<ColumnType>,
 }
 
+impl ColumnV1 {
+    /// The type of the column. Responses set the Kusto type of a column in `ColumnType`, the .NET type in `DataType`, or both,
+    /// so the Kusto type is preferred when both are set.
+    #[must_use]
+    pub fn kusto_type(&self) -> Option<&ColumnType> {
+        self.column_type.as_ref().or(self.data_type.as_ref())
+    }
+}
+
 /// Represents a table in ADX, for a V1 (usually management) query.
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
-#[serde(rename_all = "PascalCase")]
+#[serde(rename_all = "PascalCase", try_from = "RawTableV1")]
+#[non_exhaustive]
 pub struct TableV1 {
     /// Name of the table.
     pub table_name: String,
@@ -84,17 +139,305 @@ pub struct TableV1 {
     pub columns: Vec<ColumnV1>,
     /// Rows in the table. Each row is a list of values, corresponding to the columns in the table.
     pub rows: Vec<Vec<serde_json::Value>>,
+    /// Exceptions reported in place of rows, when the table is only partially complete.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exceptions: Option<Vec<String>>,
+}
+
+impl TableV1 {
+    /// A table with the given rows, without exceptions.
+    #[must_use]
+    pub fn new(
+        table_name: impl Into<String>,
+        columns: Vec<ColumnV1>,
+        rows: Vec<Vec<serde_json::Value>>,
+    ) -> Self {
+        Self {
+            table_name: table_name.into(),
+            columns,
+            rows,
+            exceptions: None,
+        }
+    }
+}
+
+/// A row of a V1 table as it is received - either values, values keyed by column name, or exceptions that occurred while producing the table.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawRowV1 {
+    Values(Vec<serde_json::Value>),
+    Exceptions {
+        #[serde(rename = "Exceptions")]
+        exceptions: Vec<String>,
+    },
+    Object(serde_json::Map<String, serde_json::Value>),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct RawTableV1 {
+    table_name: String,
+    columns: Vec<ColumnV1>,
+    rows: Vec<RawRowV1>,
+    exceptions: Option<Vec<String>>,
+}
+
+impl TryFrom<RawTableV1> for TableV1 {
+    type Error = String;
+
+    fn try_from(raw: RawTableV1) -> Result<Self, Self::Error> {
+        let mut rows = Vec::with_capacity(raw.rows.len());
+        let mut exceptions = raw.exceptions;
+        for row in raw.rows {
+            match row {
+                RawRowV1::Values(values) => rows.push(values),
+                RawRowV1::Exceptions { exceptions: e } => {
+                    exceptions.get_or_insert_with(Vec::new).extend(e);
+                }
+                RawRowV1::Object(object) => {
+                    let mut values = vec![serde_json::Value::Null; raw.columns.len()];
+                    for (key, value) in object {
+                        let index = raw
+                            .columns
+                            .iter()
+                            .position(|c| c.column_name == key)
+                            .ok_or_else(|| {
+                                format!(
+                                    "row in table '{}' has a value for the unknown column '{key}'",
+                                    raw.table_name
+                                )
+                            })?;
+                        values[index] = value;
+                    }
+                    rows.push(values);
+                }
+            }
+        }
+
+        Ok(Self {
+            table_name: raw.table_name,
+            columns: raw.columns,
+            rows,
+            exceptions,
+        })
+    }
+}
+
+/// Removes the values of object rows that don't match any column in their table, from a raw V1 response.
+pub(crate) fn remove_unknown_row_keys(dataset: &mut serde_json::Value) {
+    let tables = match dataset.get_mut("Tables").and_then(|t| t.as_array_mut()) {
+        Some(tables) => tables,
+        None => return,
+    };
+
+    for table in tables {
+        let columns: Vec<String> = table
+            .get("Columns")
+            .and_then(|c| c.as_array())
+            .map(|columns| {
+                columns
+                    .iter()
+                    .filter_map(|c| c.get("ColumnName").and_then(|n| n.as_str()))
+                    .map(ToString::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let rows = table.get_mut("Rows").and_then(|r| r.as_array_mut());
+        for row in rows.into_iter().flatten() {
+            if let Some(row) = row.as_object_mut() {
+                if !row.contains_key("Exceptions") {
+                    row.retain(|key, _| columns.contains(key));
+                }
+            }
+        }
+    }
+}
+
+/// Checks that the rows of a V2 table, or of a fragment of it, have a value for each of the columns of the table,
+/// as told by its header and by the `field_count` of the fragment. `first_row` is the index of the first of the rows within the table.
+///
+/// Short rows are padded with nulls when `pad_short_rows` is set, otherwise they fail the check, as do rows with too many values.
+/// Rows that are not arrays, such as the errors of a partially failed query, are left as they are.
+pub(crate) fn check_row_widths(
+    table_id: i32,
+    table_name: &str,
+    columns: usize,
+    field_count: Option<i32>,
+    rows: &mut [serde_json::Value],
+    first_row: usize,
+    pad_short_rows: bool,
+) -> crate::error::Result<()> {
+    if let Some(field_count) = field_count {
+        if usize::try_from(field_count).ok() != Some(columns) {
+            return Err(crate::error::Error::ConversionError(format!(
+                "table '{table_name}' ({table_id}), a fragment has {field_count} fields but the table has {columns} columns"
+            )));
+        }
+    }
+
+    for (index, row) in rows.iter_mut().enumerate() {
+        let values = match row.as_array_mut() {
+            Some(values) => values,
+            None => continue,
+        };
+        if values.len() < columns && pad_short_rows {
+            values.resize(columns, serde_json::Value::Null);
+        }
+        if values.len() != columns {
+            return Err(crate::error::Error::ConversionError(format!(
+                "table '{table_name}' ({table_id}), row {} has {} values but the table has {columns} columns",
+                first_row + index,
+                values.len()
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// An error returned by the service, in the OneApi error format.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct OneApiError {
+    /// The details of the error.
+    #[serde(rename = "error")]
+    pub error_message: ErrorMessage,
 }
 
-/// The header of the V2 query response.
+/// The details of a [OneApiError].
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct ErrorMessage {
+    /// A short code for the error, e.g. `BadRequest`.
+    pub code: String,
+    /// A human readable summary of the error.
+    pub message: String,
+    /// The type of the exception raised in the service.
+    #[serde(rename = "@type")]
+    pub r#type: Option<String>,
+    /// The full description of the error.
+    #[serde(rename = "@message")]
+    pub description: Option<String>,
+    /// Additional context about the failed request.
+    #[serde(rename = "@context")]
+    pub context: Option<serde_json::Value>,
+    /// Whether the error is permanent, meaning retrying the request will not help.
+    #[serde(rename = "@permanent")]
+    pub is_permanent: Option<bool>,
+}
+
+impl std::fmt::Display for OneApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let error = &self.error_message;
+        write!(f, "{}: {}", error.code, error.message)?;
+        if let Some(description) = &error.description {
+            write!(f, " ({description})")?;
+        }
+        Ok(())
+    }
+}
+
+/// The header of the V2 query response, which tells how the frames that follow it are laid out.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Default)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct DataSetHeader {
-    /// Is the table progressive. If it is, tables can arrive in multiple chunks.
-    /// To control this, use the [RequestOptions.results_progressive_enabled] parameter.
+    /// Whether the response is progressive, which the service makes it when
+    /// [results_progressive_enabled](crate::request_options::Options::results_progressive_enabled) is set.
+    /// See [FrameLayout::Progressive].
     pub is_progressive: bool,
     /// Version of the header. Currently it is always `v2.0`.
     pub version: String,
+    /// Whether tables are sent in fragments, as newer versions of the service do for responses that aren't progressive.
+    /// Older versions don't send it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_fragmented: Option<bool>,
+    /// Where the errors of the query are reported. Older versions of the service don't send it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_reporting_placement: Option<ErrorReportingPlacement>,
+}
+
+impl DataSetHeader {
+    /// The header of a response, with the fields that older versions of the service don't send left unset.
+    #[must_use]
+    pub fn new(is_progressive: bool, version: impl Into<String>) -> Self {
+        Self {
+            is_progressive,
+            version: version.into(),
+            ..Self::default()
+        }
+    }
+
+    /// How the tables of the response are laid out in its frames.
+    /// Errors reported at the [end of each table](ErrorReportingPlacement::EndOfTable) are sent in its [TableCompletion],
+    /// so such responses are fragmented, even if the header doesn't say so.
+    #[must_use]
+    pub fn layout(&self) -> FrameLayout {
+        if self.is_progressive {
+            return FrameLayout::Progressive;
+        }
+        match (&self.error_reporting_placement, self.is_fragmented) {
+            (Some(ErrorReportingPlacement::EndOfTable), _) | (_, Some(true)) => {
+                FrameLayout::Fragmented
+            }
+            _ => FrameLayout::Tables,
+        }
+    }
+}
+
+/// How the tables of a V2 response are laid out in its frames, as told by its [DataSetHeader].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameLayout {
+    /// Each table is sent whole, as a [DataTable] frame.
+    /// Older versions of the service may still send some tables in fragments, which are then assembled as in [FrameLayout::Fragmented].
+    Tables,
+    /// Tables are sent one after the other, either whole or as a [TableHeader], [TableFragment]s and a [TableCompletion].
+    Fragmented,
+    /// The legacy progressive layout: tables are sent as in [FrameLayout::Fragmented], but [TableProgress] frames report the progress
+    /// of a table, [TableFragmentType::DataReplace] fragments replace the rows received so far, and whole tables, such as the query
+    /// properties, can be sent while a table is being received. Those are placed after the table.
+    Progressive,
+}
+
+/// Where the errors of a query are reported in a V2 response.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(from = "String", into = "String")]
+pub enum ErrorReportingPlacement {
+    /// Within the rows of the tables.
+    InData,
+    /// In the [TableCompletion] of each table.
+    EndOfTable,
+    /// Only in the [DataSetCompletion].
+    EndOfDataSet,
+    /// Another placement, unknown to this version of the crate.
+    Other(String),
+}
+
+impl ErrorReportingPlacement {
+    fn as_str(&self) -> &str {
+        match self {
+            ErrorReportingPlacement::InData => "InData",
+            ErrorReportingPlacement::EndOfTable => "EndOfTable",
+            ErrorReportingPlacement::EndOfDataSet => "EndOfDataSet",
+            ErrorReportingPlacement::Other(placement) => placement,
+        }
+    }
+}
+
+impl From<String> for ErrorReportingPlacement {
+    fn from(placement: String) -> Self {
+        match placement.as_str() {
+            "InData" => ErrorReportingPlacement::InData,
+            "EndOfTable" => ErrorReportingPlacement::EndOfTable,
+            "EndOfDataSet" => ErrorReportingPlacement::EndOfDataSet,
+            _ => ErrorReportingPlacement::Other(placement),
+        }
+    }
+}
+
+impl From<ErrorReportingPlacement> for String {
+    fn from(placement: ErrorReportingPlacement) -> Self {
+        placement.as_str().to_string()
+    }
 }
 
 /// A result of a V2 query.
@@ -119,6 +462,54 @@ pub enum V2QueryResult {
     TableCompletion(TableCompletion),
 }
 
+/// The fields of a V2 frame, and of the columns of its table, that the models of this crate don't have,
+/// as paths such as `DataTable.Columns[1].IsNullable`. Frames of unknown types are left to fail their deserialization.
+pub(crate) fn unknown_frame_fields(frame: &serde_json::Value) -> Vec<String> {
+    use crate::deserialization::struct_field_names;
+
+    let frame_type = match frame.get("FrameType").and_then(serde_json::Value::as_str) {
+        Some(frame_type) => frame_type,
+        None => return vec![],
+    };
+    let fields = match frame_type {
+        "DataSetHeader" => struct_field_names::<DataSetHeader>(),
+        "DataTable" => struct_field_names::<DataTable>(),
+        "DataSetCompletion" => struct_field_names::<DataSetCompletion>(),
+        "TableHeader" => struct_field_names::<TableHeader>(),
+        "TableFragment" => struct_field_names::<TableFragment>(),
+        "TableProgress" => struct_field_names::<TableProgress>(),
+        "TableCompletion" => struct_field_names::<TableCompletion>(),
+        _ => return vec![],
+    }
+    .unwrap_or_default();
+
+    let unknown_keys = |value: &serde_json::Value, fields: &[&str]| -> Vec<String> {
+        value.as_object().map_or_else(Vec::new, |object| {
+            object
+                .keys()
+                .filter(|key| key.as_str() != "FrameType" && !fields.contains(&key.as_str()))
+                .cloned()
+                .collect()
+        })
+    };
+
+    let mut unknown: Vec<String> = unknown_keys(frame, fields)
+        .into_iter()
+        .map(|key| format!("{frame_type}.{key}"))
+        .collect();
+    if let Some(columns) = frame.get("Columns").and_then(serde_json::Value::as_array) {
+        let column_fields = struct_field_names::<Column>().unwrap_or_default();
+        for (index, column) in columns.iter().enumerate() {
+            unknown.extend(
+                unknown_keys(column, column_fields)
+                    .into_iter()
+                    .map(|key| format!("{frame_type}.Columns[{index}].{key}")),
+            );
+        }
+    }
+    unknown
+}
+
 /// Query result DataTable, for a V2 Query.
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 #[serde(rename_all = "PascalCase")]
@@ -185,11 +576,31 @@ pub struct TableProgress {
 /// End of a table (in progressive mode).
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct TableCompletion {
     /// Table id - unique identifier of the table. Corresponds to the table_id in the TableHeader.
     pub table_id: i32,
     /// Total row count
     pub row_count: i32,
+    /// Errors that occurred while producing the table, if there were any.
+    #[serde(
+        rename = "OneApiErrors",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub one_api_errors: Option<Vec<OneApiError>>,
+}
+
+impl TableCompletion {
+    /// The completion of the table with the given id, without errors.
+    #[must_use]
+    pub fn new(table_id: i32, row_count: i32) -> Self {
+        Self {
+            table_id,
+            row_count,
+            one_api_errors: None,
+        }
+    }
 }
 
 /// Categorizes data tables according to the role they play in the data set that a Kusto query returns.
@@ -225,9 +636,29 @@ pub struct Column {
 /// Represents an end of the query result.
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct DataSetCompletion {
     /// did the query errored.
     pub has_errors: bool,
     /// Was the query cancelled.
     pub cancelled: bool,
+    /// Errors that occurred while executing the query, if there were any.
+    #[serde(
+        rename = "OneApiErrors",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub one_api_errors: Option<Vec<OneApiError>>,
+}
+
+impl DataSetCompletion {
+    /// The completion of a dataset, without the details of any errors it has.
+    #[must_use]
+    pub fn new(has_errors: bool, cancelled: bool) -> Self {
+        Self {
+            has_errors,
+            cancelled,
+            one_api_errors: None,
+        }
+    }
 }
@@ -1,58 +1,228 @@
+//! Incremental reading of the JSON array of frames of a V2 response, yielding each frame as soon as it is read.
+//!
+//! Frames are found by tracking the nesting of their brackets, so they may or may not be separated by newlines,
+//! depending on the `results_v2_newlines_between_frames` option.
+
 use std::io;
 
-use futures::{stream, AsyncBufRead, AsyncBufReadExt, AsyncReadExt, Stream};
+use futures::{stream, AsyncBufRead, AsyncBufReadExt, Stream};
 use serde::de::DeserializeOwned;
 
-// TODO: Find a crate that does this better / move this into another crate
-
 fn invalid_data(msg: &str) -> io::Error {
     io::Error::new(io::ErrorKind::InvalidData, msg)
 }
 
-async fn deserialize_single<T: DeserializeOwned>(
-    reader: &mut (impl AsyncBufRead + Send + Unpin),
-    buf: &mut Vec<u8>,
-) -> io::Result<T> {
-    buf.clear();
-    let size = reader.read_until(b'\n', buf).await?;
-    Ok(serde_json::from_slice(&buf[..size - 1])?)
+fn unexpected_eof() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        "The response ended before its array of frames was closed",
+    )
+}
+
+/// Where the reader is in the array of frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Position {
+    /// Before the opening bracket of the array
+    Start,
+    /// After the opening bracket, before the first frame
+    First,
+    /// After a frame
+    Next,
+}
+
+/// Tracks where a JSON object or array ends, as its bytes are read.
+#[derive(Debug, Default)]
+struct ValueScanner {
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+}
+
+impl ValueScanner {
+    /// Scans the next bytes of the value, returning how many of them belong to it if it ends within them.
+    fn scan(&mut self, bytes: &[u8]) -> Option<usize> {
+        for (index, &byte) in bytes.iter().enumerate() {
+            if self.in_string {
+                match byte {
+                    _ if self.escaped => self.escaped = false,
+                    b'\\' => self.escaped = true,
+                    b'"' => self.in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+            match byte {
+                b'"' => self.in_string = true,
+                b'{' | b'[' => self.depth += 1,
+                b'}' | b']' => {
+                    self.depth = self.depth.saturating_sub(1);
+                    if self.depth == 0 {
+                        return Some(index + 1);
+                    }
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+}
+
+/// Skips whitespace, and returns the next byte without consuming it, or `None` at the end of the reader.
+async fn peek_token(reader: &mut (impl AsyncBufRead + Unpin)) -> io::Result<Option<u8>> {
+    loop {
+        let available = reader.fill_buf().await?;
+        if available.is_empty() {
+            return Ok(None);
+        }
+        match available.iter().position(|b| !b.is_ascii_whitespace()) {
+            Some(index) => {
+                let token = available[index];
+                reader.consume_unpin(index);
+                return Ok(Some(token));
+            }
+            None => {
+                let length = available.len();
+                reader.consume_unpin(length);
+            }
+        }
+    }
 }
 
-async fn read_byte(reader: &mut (impl AsyncBufRead + Send + Unpin)) -> io::Result<u8> {
-    let mut buf = [0u8; 1];
-    reader.read_exact(&mut buf).await?;
-    Ok(buf[0])
+/// Reads the bytes of the object or array at the start of the reader into `buf`.
+async fn read_value(reader: &mut (impl AsyncBufRead + Unpin), buf: &mut Vec<u8>) -> io::Result<()> {
+    buf.clear();
+    let mut scanner = ValueScanner::default();
+    loop {
+        let available = reader.fill_buf().await?;
+        if available.is_empty() {
+            return Err(unexpected_eof());
+        }
+        let (length, complete) = match scanner.scan(available) {
+            Some(end) => (end, true),
+            None => (available.len(), false),
+        };
+        buf.extend_from_slice(&available[..length]);
+        reader.consume_unpin(length);
+        if complete {
+            return Ok(());
+        }
+    }
 }
 
 async fn yield_next_obj<T: DeserializeOwned>(
-    reader: &mut (impl AsyncBufRead + Send + Unpin),
+    reader: &mut (impl AsyncBufRead + Unpin),
     buf: &mut Vec<u8>,
+    position: &mut Position,
 ) -> Result<Option<T>, io::Error> {
-    Ok(Some(match read_byte(reader).await? {
-        b'[' => {
-            let newline = read_byte(reader).await?;
-            if newline != b'\n' {
+    if *position == Position::Start {
+        match peek_token(reader).await? {
+            Some(b'[') => reader.consume_unpin(1),
+            Some(b) => {
                 return Err(invalid_data(&format!(
-                    "Expected newline after opening '[', found {:?}",
-                    newline
-                )));
+                    "Expected the opening '[' of the frames, found {:?}",
+                    b as char
+                )))
             }
-            deserialize_single(reader, buf).await?
+            None => return Err(unexpected_eof()),
         }
-        b',' => deserialize_single(reader, buf).await?,
-        b']' => return Ok(None),
-        b => return Err(invalid_data(&format!("Unexpected byte {:?}", b))),
-    }))
+        *position = Position::First;
+    }
+
+    match (peek_token(reader).await?, *position) {
+        (Some(b']'), _) => {
+            reader.consume_unpin(1);
+            return Ok(None);
+        }
+        (Some(b','), Position::Next) => {
+            reader.consume_unpin(1);
+            if !matches!(peek_token(reader).await?, Some(b'{' | b'[')) {
+                return Err(invalid_data("Expected a frame after ','"));
+            }
+        }
+        (Some(b'{' | b'['), Position::First) => {}
+        (Some(b), _) => return Err(invalid_data(&format!("Unexpected byte {:?}", b as char))),
+        (None, _) => return Err(unexpected_eof()),
+    }
+
+    read_value(reader, buf).await?;
+    *position = Position::Next;
+    Ok(Some(serde_json::from_slice(buf)?))
 }
 
 pub fn iter_results<T: DeserializeOwned>(
-    reader: (impl AsyncBufRead + Send + Unpin),
+    reader: impl AsyncBufRead + Unpin,
 ) -> impl Stream<Item = Result<T, io::Error>> {
     let buf = vec![];
 
-    stream::try_unfold((buf, reader), move |(mut buf, mut reader)| async {
-        yield_next_obj(&mut reader, &mut buf)
-            .await
-            .map(|r| r.map(|obj| (obj, (buf, reader))))
-    })
+    stream::try_unfold(
+        (buf, reader, Position::Start),
+        move |(mut buf, mut reader, mut position)| async move {
+            yield_next_obj(&mut reader, &mut buf, &mut position)
+                .await
+                .map(|r| r.map(|obj| (obj, (buf, reader, position))))
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::TryStreamExt;
+    use serde_json::{json, Value};
+
+    async fn frames(body: &[u8]) -> io::Result<Vec<Value>> {
+        iter_results::<Value>(body).try_collect().await
+    }
+
+    #[tokio::test]
+    async fn frames_are_read_with_or_without_newlines() {
+        let expected = vec![
+            json!({"FrameType": "DataSetHeader", "Text": "a ] } [ { \" \\ , b"}),
+            json!({"FrameType": "DataTable", "Rows": [[1, "x"], [2, "]"]]}),
+            json!({"FrameType": "DataSetCompletion"}),
+        ];
+        let compact = serde_json::to_vec(&expected).unwrap();
+        let lines = format!(
+            "[\n{}\n]",
+            expected
+                .iter()
+                .map(Value::to_string)
+                .collect::<Vec<_>>()
+                .join("\n,")
+        );
+        let spaced = serde_json::to_vec_pretty(&expected).unwrap();
+
+        for body in [compact, lines.into_bytes(), spaced] {
+            assert_eq!(frames(&body).await.unwrap(), expected);
+        }
+        assert_eq!(frames(b" [ ] ").await.unwrap(), Vec::<Value>::new());
+    }
+
+    #[tokio::test]
+    async fn frames_are_read_across_small_reads() {
+        let body = br#"[{"a":"}"},{"b":[{"c":"\"]"}]}]"#;
+        let reader = futures::io::BufReader::with_capacity(1, &body[..]);
+        let read: Vec<Value> = iter_results(reader).try_collect().await.unwrap();
+        assert_eq!(read, vec![json!({"a": "}"}), json!({"b": [{"c": "\"]"}]})]);
+    }
+
+    #[tokio::test]
+    async fn malformed_arrays_are_errors() {
+        let bodies: [&[u8]; 7] = [
+            b"",
+            b"{}",
+            b"[{\"a\": 1}",
+            b"[{\"a\": 1},",
+            b"[{\"a\": 1} {\"b\": 2}]",
+            b"[,{\"a\": 1}]",
+            b"[{\"a\": \"unterminated}]",
+        ];
+        for body in bodies {
+            assert!(
+                frames(body).await.is_err(),
+                "Expected {} to fail",
+                String::from_utf8_lossy(body)
+            );
+        }
+    }
 }
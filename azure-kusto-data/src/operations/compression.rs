@@ -0,0 +1,111 @@
+//! Decompression of response bodies.
+//!
+//! The client asks for gzip compressed responses. The default transport decompresses them itself,
+//! but other transports may hand over the body as it was sent, along with its `Content-Encoding` header.
+
+use std::io::Read;
+
+use async_compression::futures::bufread::GzipDecoder;
+use azure_core::headers::{Headers, CONTENT_ENCODING};
+use bytes::Bytes;
+use futures::future::Either;
+use futures::io::BufReader;
+use futures::AsyncBufRead;
+
+/// Whether the `Content-Encoding` header says that the body is gzip compressed.
+pub(crate) fn is_gzip(headers: &Headers) -> bool {
+    headers
+        .get_optional_str(&CONTENT_ENCODING)
+        .map_or(false, |encoding| {
+            encoding
+                .split(',')
+                .any(|coding| coding.trim().eq_ignore_ascii_case("gzip"))
+        })
+}
+
+/// Decompresses a whole body, if its headers say that it is gzip compressed.
+pub(crate) fn decompress_body(headers: &Headers, body: Bytes) -> std::io::Result<Bytes> {
+    if !is_gzip(headers) {
+        return Ok(body);
+    }
+
+    let mut decompressed = Vec::with_capacity(body.len() * 4);
+    flate2::read::MultiGzDecoder::new(body.as_ref()).read_to_end(&mut decompressed)?;
+    Ok(decompressed.into())
+}
+
+/// Decompresses a body as it is read, if its headers say that it is gzip compressed.
+pub(crate) fn decompress_reader<R: AsyncBufRead + Unpin>(
+    headers: &Headers,
+    reader: R,
+) -> Either<R, BufReader<GzipDecoder<R>>> {
+    if is_gzip(headers) {
+        let mut decoder = GzipDecoder::new(reader);
+        decoder.multiple_members(true);
+        Either::Right(BufReader::new(decoder))
+    } else {
+        Either::Left(reader)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::GzEncoder, Compression};
+    use futures::AsyncReadExt;
+    use std::io::Write;
+
+    fn gzip(data: &[u8]) -> Bytes {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap().into()
+    }
+
+    fn headers(encoding: &str) -> Headers {
+        let mut headers = Headers::new();
+        headers.insert(CONTENT_ENCODING, encoding.to_string());
+        headers
+    }
+
+    #[test]
+    fn gzip_encoding_is_detected() {
+        assert!(is_gzip(&headers("gzip")));
+        assert!(is_gzip(&headers("GZIP")));
+        assert!(is_gzip(&headers("identity, gzip")));
+        assert!(!is_gzip(&headers("deflate")));
+        assert!(!is_gzip(&Headers::new()));
+    }
+
+    #[test]
+    fn bodies_are_decompressed_when_gzipped() {
+        let body = Bytes::from_static(b"{\"Tables\": []}");
+
+        let decompressed = decompress_body(&headers("gzip"), gzip(&body)).unwrap();
+        assert_eq!(decompressed, body);
+        assert_eq!(
+            decompress_body(&Headers::new(), body.clone()).unwrap(),
+            body
+        );
+        assert!(decompress_body(&headers("gzip"), body).is_err());
+    }
+
+    #[tokio::test]
+    async fn readers_are_decompressed_when_gzipped() {
+        let body = b"[{\"FrameType\": \"DataSetHeader\"}]";
+        let compressed = gzip(body);
+
+        let mut decompressed = Vec::new();
+        decompress_reader(&headers("gzip"), compressed.as_ref())
+            .read_to_end(&mut decompressed)
+            .await
+            .unwrap();
+        assert_eq!(decompressed, body);
+
+        let mut plain = Vec::new();
+        decompress_reader(&Headers::new(), &body[..])
+            .read_to_end(&mut plain)
+            .await
+            .unwrap();
+        assert_eq!(plain, body);
+    }
+}
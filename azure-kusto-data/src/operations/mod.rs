@@ -1,2 +1,6 @@
 mod async_deserializer;
+pub(crate) mod compression;
 pub mod query;
+#[cfg(feature = "tokio")]
+pub mod streaming;
+pub mod streaming_ingest;
@@ -1,23 +1,43 @@
 #[cfg(feature = "arrow")]
-use crate::arrow::convert_table;
+use crate::arrow::{convert_table, deserialize_record_batch, ArrowOptions};
 use crate::client::{KustoClient, QueryKind};
+use crate::cluster_stopped::stopped_error_in;
+use crate::coalescing::QueryKey;
 
 use crate::error::{Error, Result};
-use crate::models::{DataTable, QueryBody, TableFragmentType, TableKind, TableV1, V2QueryResult};
+use crate::instrumentation::{record_error, span, Instrument, Span};
+use crate::models::{
+    check_row_widths, remove_unknown_row_keys, unknown_frame_fields, DataSetCompletion,
+    DataSetHeader, DataTable, FrameLayout, OneApiError, QueryBody, TableFragmentType, TableKind,
+    TableV1, V2QueryResult,
+};
 use crate::operations::async_deserializer;
+use crate::operations::compression::{decompress_body, decompress_reader};
 use crate::prelude::ClientRequestProperties;
+use crate::redirect_policy::MaxRedirectCount;
+use crate::request_options::{ParseOptions, V1ParsingMode};
+use crate::slow_query::{MeasuredResults, QueryMeasurement};
 #[cfg(feature = "arrow")]
 use arrow_array::RecordBatch;
+#[cfg(not(target_arch = "wasm32"))]
 use async_convert::TryFrom;
 use azure_core::error::Error as CoreError;
-use azure_core::headers::Headers;
 use azure_core::prelude::*;
-use azure_core::{CustomHeaders, Method, Request, Response as HttpResponse, Response};
+use azure_core::{CustomHeaders, Method, Request, Response, StatusCode};
+// The wasm32 transport is not `Send`, so neither are the futures that use it.
+#[cfg(not(target_arch = "wasm32"))]
 use futures::future::BoxFuture;
-use futures::{Stream, TryFutureExt, TryStreamExt};
+#[cfg(target_arch = "wasm32")]
+use futures::future::LocalBoxFuture as BoxFuture;
+use futures::{AsyncBufRead, Stream, StreamExt, TryStreamExt};
+use hashbrown::HashMap;
+#[cfg(feature = "arrow")]
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
 use std::future::IntoFuture;
 use std::io::ErrorKind;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
 type QueryRun = BoxFuture<'static, Result<KustoResponse>>;
@@ -28,11 +48,11 @@ type V2QueryRun = BoxFuture<'static, Result<KustoResponseDataSetV2>>;
 #[builder(setter(into, prefix = "with"))]
 pub struct QueryRunner {
     client: KustoClient,
-    database: String,
+    /// The database of the query, or `None` when neither the query nor the connection string named one.
+    database: Option<String>,
     query: String,
     kind: QueryKind,
     client_request_properties: Option<ClientRequestProperties>,
-    default_headers: Arc<Headers>,
 }
 pub struct V1QueryRunner(pub QueryRunner);
 
@@ -46,7 +66,29 @@ impl V2QueryRunner {
 }
 
 impl QueryRunner {
-    async fn into_response(self) -> Result<Response> {
+    /// The span of the query, with the status code and number of rows to be recorded once known.
+    fn span(&self) -> Span {
+        span!(
+            "kusto.query",
+            otel.kind = "client",
+            db.system = "kusto",
+            database = %self.database.as_deref().unwrap_or_default(),
+            kind = ?self.kind,
+            query_hash = %crate::instrumentation::query_hash(&self.query),
+            client_request_id = self
+                .client_request_properties
+                .as_ref()
+                .and_then(|p| p.client_request_id.as_deref()),
+            status_code = tracing::field::Empty,
+            rows = tracing::field::Empty,
+            error = tracing::field::Empty,
+            otel.status_code = tracing::field::Empty,
+        )
+    }
+
+    /// Builds the request of the query, and the context that carries its headers and redirect limit.
+    /// Every way of running a query sends the request built here, so that they can't diverge for the same inputs.
+    fn request(self) -> Result<(Request, Context)> {
         let url = match self.kind {
             QueryKind::Management => self.client.management_url(),
             QueryKind::Query => self.client.query_url(),
@@ -54,33 +96,127 @@ impl QueryRunner {
         let mut request = Request::new(url.parse().map_err(CoreError::from)?, Method::Post);
 
         let mut context = Context::new();
-        let mut headers = self.default_headers.as_ref().clone();
+        context.insert(CustomHeaders::from(
+            self.client
+                .default_headers_for(self.client_request_properties.as_ref()),
+        ));
 
-        if let Some(client_request_properties) = &self.client_request_properties {
-            if let Some(client_request_id) = &client_request_properties.client_request_id {
-                headers.insert("x-ms-client-request-id", client_request_id);
-            }
-
-            if let Some(application) = &client_request_properties.application {
-                headers.insert("x-ms-app", application);
-            }
+        if let Some(max_redirect_count) = self
+            .client_request_properties
+            .as_ref()
+            .and_then(|p| p.options.as_ref())
+            .and_then(|o| o.client_max_redirect_count)
+        {
+            context.insert(MaxRedirectCount(
+                max_redirect_count.clamp(0, i64::from(u32::MAX)) as u32,
+            ));
         }
 
-        context.insert(CustomHeaders::from(headers));
-
         let body = QueryBody {
-            db: self.database,
+            db: self
+                .database
+                .ok_or_else(|| Error::QueryError("no database specified".into()))?,
             csl: self.query,
-            properties: self.client_request_properties,
+            properties: self
+                .client_request_properties
+                .map(ClientRequestProperties::with_identity_in_options),
+            properties_serialization: self.client.properties_serialization(),
         };
 
         let bytes = bytes::Bytes::from(serde_json::to_string(&body)?);
         request.set_body(bytes);
 
-        let response = self.client.pipeline().send(&context, &mut request).await?;
+        Ok((request, context))
+    }
+
+    /// Sends the query, recording the status code of the response in the current span.
+    /// Failures because the cluster is stopped are returned as [Error::ClusterStopped].
+    ///
+    /// The request is only recorded as successful by the callers, once the body of the response is known not to report an error.
+    async fn into_response(self) -> Result<Response> {
+        let client = self.client.clone();
+        let (mut request, context) = self.request()?;
+
+        let response = client
+            .pipeline()
+            .send(&context, &mut request)
+            .await
+            .map_err(|e| client.classify_error(e.into()))?;
+        Span::current().record("status_code", u16::from(response.status()));
         Ok(response)
     }
 
+    /// Starts measuring the query, if the client has a slow query hook.
+    fn measurement(&self) -> Option<QueryMeasurement> {
+        self.client.slow_query_hook().and_then(|hook| {
+            hook.start(
+                self.database.as_deref().unwrap_or_default(),
+                &self.query,
+                self.client_request_properties.as_ref(),
+            )
+        })
+    }
+
+    /// Sends the query and reads the whole response, returning it with the size of its body.
+    /// The frames of a V2 response are parsed as the body is read, without buffering it.
+    ///
+    /// Errors reported in the body are classified as those of the request are, and the request is only recorded as successful
+    /// once the body was parsed without one.
+    async fn execute(self) -> Result<(KustoResponse, usize)> {
+        let this = self.clone();
+        let response = self.into_response().await?;
+
+        let result = this
+            .read_response(response)
+            .await
+            .map_err(|e| this.client.classify_error(e));
+        if result.is_ok() {
+            this.client.request_succeeded();
+        }
+        result
+    }
+
+    /// Reads the body of the response, returning it with its size.
+    async fn read_response(&self, response: Response) -> Result<(KustoResponse, usize)> {
+        Ok(match self.kind {
+            QueryKind::Management => {
+                let (status_code, header_map, pinned_stream) = response.deconstruct();
+                let data = pinned_stream.collect().await?;
+                let size = data.len();
+                let response = KustoResponse::V1(KustoResponseDataSetV1::from_body(
+                    status_code,
+                    &decompress_body(&header_map, data)?,
+                    self.client_request_properties.as_ref(),
+                )?);
+                (response, size)
+            }
+            QueryKind::Query => {
+                let (_status_code, header_map, pinned_stream) = response.deconstruct();
+                let size = Arc::new(AtomicUsize::new(0));
+                let read = size.clone();
+                let reader = pinned_stream
+                    .inspect_ok(move |chunk| {
+                        read.fetch_add(chunk.len(), Ordering::Relaxed);
+                    })
+                    .map_err(|e| std::io::Error::new(ErrorKind::Other, e))
+                    .into_async_read();
+                let response = KustoResponseDataSetV2::from_async_buf_read(
+                    decompress_reader(&header_map, reader),
+                    self.client.parse_options(),
+                )
+                .await?;
+                if let Some(error) = response
+                    .completion()
+                    .and_then(|c| c.one_api_errors.as_deref())
+                    .and_then(stopped_error_in)
+                {
+                    return Err(error);
+                }
+                (KustoResponse::V2(response), size.load(Ordering::Relaxed))
+            }
+        })
+    }
+
     pub async fn into_stream(self) -> Result<impl Stream<Item = Result<V2QueryResult>>> {
         if self.kind != QueryKind::Query {
             return Err(Error::UnsupportedOperation(
@@ -88,13 +224,47 @@ impl QueryRunner {
             ));
         }
 
-        let response = self.into_response().await?;
-        let (_status_code, _header_map, pinned_stream) = response.deconstruct();
+        let span = self.span();
+        let measurement = self.measurement();
+        let bytes = measurement.as_ref().map(QueryMeasurement::bytes);
+        let client = self.client.clone();
+        let response = self
+            .into_response()
+            .instrument(span.clone())
+            .await
+            .map_err(|e| {
+                record_error(&span, &e);
+                e
+            })?;
+        let (_status_code, header_map, pinned_stream) = response.deconstruct();
         let reader = pinned_stream
+            .inspect_ok(move |chunk| {
+                if let Some(bytes) = &bytes {
+                    bytes.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+                }
+            })
             .map_err(|e| std::io::Error::new(ErrorKind::Other, e))
             .into_async_read();
+        let reader = decompress_reader(&header_map, reader);
 
-        Ok(async_deserializer::iter_results(reader).map_err(Error::from))
+        // The request is recorded as successful once the whole body was read without reporting that the cluster is stopped
+        let results = async_deserializer::iter_results(reader).map(move |frame| match frame {
+            Ok(V2QueryResult::DataSetCompletion(completion)) => {
+                match completion
+                    .one_api_errors
+                    .as_deref()
+                    .and_then(stopped_error_in)
+                {
+                    Some(error) => Err(client.classify_error(error)),
+                    None => {
+                        client.request_succeeded();
+                        Ok(V2QueryResult::DataSetCompletion(completion))
+                    }
+                }
+            }
+            frame => frame.map_err(|e| client.classify_error(e.into())),
+        });
+        Ok(MeasuredResults::new(results, measurement))
     }
 }
 
@@ -118,13 +288,28 @@ impl IntoFuture for V2QueryRunner {
     type IntoFuture = V2QueryRun;
 
     fn into_future(self) -> V2QueryRun {
-        Box::pin(async {
-            let V2QueryRunner(query_runner) = self;
-            let future = query_runner.into_future().await?;
-            Ok(
-                std::convert::TryInto::try_into(future).expect("Unexpected conversion error from KustoResponse to KustoResponseDataSetV2 - please report this issue to the Kusto team")
-            )
-        })
+        let V2QueryRunner(query_runner) = self;
+        let coalescing = query_runner.client.coalescer().cloned().map(|coalescer| {
+            let key = QueryKey::new(
+                query_runner.database.as_deref().unwrap_or_default(),
+                &query_runner.query,
+                query_runner.client_request_properties.as_ref(),
+            );
+            (coalescer, key)
+        });
+        let run = move || -> V2QueryRun {
+            Box::pin(async {
+                let future = query_runner.into_future().await?;
+                Ok(
+                    std::convert::TryInto::try_into(future).expect("Unexpected conversion error from KustoResponse to KustoResponseDataSetV2 - please report this issue to the Kusto team")
+                )
+            })
+        };
+
+        match coalescing {
+            Some((coalescer, key)) => Box::pin(async move { coalescer.run(key, run).await }),
+            None => run(),
+        }
     }
 }
 
@@ -133,24 +318,27 @@ impl IntoFuture for QueryRunner {
     type IntoFuture = QueryRun;
 
     fn into_future(self) -> QueryRun {
-        let this = self.clone();
-
-        Box::pin(async move {
-            let response = self.into_response().await?;
+        let span = self.span();
+        let measurement = self.measurement();
 
-            Ok(match this.kind {
-                QueryKind::Management => {
-                    <KustoResponseDataSetV1 as TryFrom<HttpResponse>>::try_from(response)
-                        .map_ok(KustoResponse::V1)
-                        .await?
+        Box::pin(
+            async move {
+                let result = self.execute().await;
+                match &result {
+                    Ok((response, bytes)) => {
+                        Span::current().record("rows", response.primary_row_count());
+                        if let Some(mut measurement) = measurement {
+                            measurement.add_bytes(*bytes);
+                            measurement.observe_response(response);
+                            measurement.finish();
+                        }
+                    }
+                    Err(e) => record_error(&Span::current(), e),
                 }
-                QueryKind::Query => {
-                    <KustoResponseDataSetV2 as TryFrom<HttpResponse>>::try_from(response)
-                        .map_ok(KustoResponse::V2)
-                        .await?
-                }
-            })
-        })
+                result.map(|(response, _)| response)
+            }
+            .instrument(span),
+        )
     }
 }
 
@@ -163,6 +351,42 @@ pub enum KustoResponse {
     V2(KustoResponseDataSetV2),
 }
 
+impl KustoResponse {
+    /// The number of rows in the primary results, as recorded in the query span.
+    fn primary_row_count(&self) -> usize {
+        match self {
+            KustoResponse::V1(v1) => v1.tables.first().map_or(0, |t| t.rows.len()),
+            KustoResponse::V2(v2) => {
+                // A table can be both sent whole and completed, so the rows are counted once per table id
+                let mut rows: HashMap<i32, usize> = HashMap::new();
+                let mut fragmented = Vec::new();
+                for result in &v2.results {
+                    match result {
+                        V2QueryResult::DataTable(table)
+                            if table.table_kind == TableKind::PrimaryResult =>
+                        {
+                            rows.insert(table.table_id, table.rows.len());
+                        }
+                        V2QueryResult::TableHeader(header)
+                            if header.table_kind == TableKind::PrimaryResult =>
+                        {
+                            fragmented.push(header.table_id);
+                        }
+                        V2QueryResult::TableCompletion(completion)
+                            if fragmented.contains(&completion.table_id) =>
+                        {
+                            rows.entry(completion.table_id)
+                                .or_insert(completion.row_count.max(0) as usize);
+                        }
+                        _ => {}
+                    }
+                }
+                rows.values().sum()
+            }
+        }
+    }
+}
+
 /// The top level response from a Kusto query.
 #[derive(Debug, Clone)]
 pub struct KustoResponseDataSetV2 {
@@ -170,6 +394,23 @@ pub struct KustoResponseDataSetV2 {
     pub results: Vec<V2QueryResult>,
 }
 
+/// Data of a response that may be incomplete, with the errors that the service reported in place of the rest of it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Partial<T> {
+    /// The data that was received.
+    pub data: T,
+    /// The errors reported within the data, such as `LimitsExceeded` when the results were truncated.
+    pub errors: Vec<OneApiError>,
+}
+
+impl<T> Partial<T> {
+    /// Whether no errors were reported, so that the data is complete.
+    #[must_use]
+    pub fn is_complete(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
 impl std::convert::TryFrom<KustoResponse> for KustoResponseDataSetV2 {
     type Error = Error;
 
@@ -192,90 +433,441 @@ impl std::convert::TryFrom<KustoResponse> for KustoResponseDataSetV1 {
     }
 }
 
-struct KustoResponseDataSetV2TableIterator<T: Iterator<Item = V2QueryResult>> {
+/// A frame of a V2 response that tables are assembled from, either owned by a consumed response or borrowed from one.
+///
+/// Borrowed frames are only cloned once they are part of a table that is yielded,
+/// so the rows of fragments that are replaced, and frames that aren't part of tables, are never cloned.
+trait Frame: Sized {
+    fn result(&self) -> &V2QueryResult;
+
+    fn into_result(self) -> V2QueryResult;
+
+    /// Moves or clones the rows of a fragment to the end of `rows`.
+    fn append_rows_to(self, rows: &mut Vec<serde_json::Value>);
+}
+
+impl Frame for V2QueryResult {
+    fn result(&self) -> &V2QueryResult {
+        self
+    }
+
+    fn into_result(self) -> V2QueryResult {
+        self
+    }
+
+    fn append_rows_to(self, rows: &mut Vec<serde_json::Value>) {
+        if let V2QueryResult::TableFragment(fragment) = self {
+            rows.extend(fragment.rows);
+        }
+    }
+}
+
+impl Frame for &V2QueryResult {
+    fn result(&self) -> &V2QueryResult {
+        self
+    }
+
+    fn into_result(self) -> V2QueryResult {
+        self.clone()
+    }
+
+    fn append_rows_to(self, rows: &mut Vec<serde_json::Value>) {
+        if let V2QueryResult::TableFragment(fragment) = self {
+            rows.extend_from_slice(&fragment.rows);
+        }
+    }
+}
+
+struct KustoResponseDataSetV2TableIterator<F: Frame, T: Iterator<Item = F>> {
     tables: T,
+    /// The starts of the tables that were received while the previous one was being assembled, to be yielded next
+    pending: VecDeque<F>,
+    /// The layout of the frames, as told by the header of the response once it was read
+    layout: FrameLayout,
     finished: bool,
 }
 
-impl<T: Iterator<Item = V2QueryResult>> KustoResponseDataSetV2TableIterator<T> {
+impl<F: Frame, T: Iterator<Item = F>> KustoResponseDataSetV2TableIterator<F, T> {
     fn new(tables: T) -> Self {
         Self {
             tables,
+            pending: VecDeque::new(),
+            layout: FrameLayout::Tables,
             finished: false,
         }
     }
 }
 
-impl<T: Iterator<Item = V2QueryResult>> Iterator for KustoResponseDataSetV2TableIterator<T> {
-    type Item = DataTable;
+/// Parses the frames of a V2 response, failing if any of them has fields that the models don't have.
+#[cfg(test)]
+fn parse_frames_strictly(data: &[u8]) -> Result<Vec<V2QueryResult>> {
+    let frames: Vec<serde_json::Value> = serde_json::from_slice(data)?;
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.finished {
-            return None;
-        }
-        let next_table = self.tables.find_map(|t| match t {
-            V2QueryResult::DataTable(_) | V2QueryResult::TableHeader(_) => Some(t),
+    let unknown: Vec<String> = frames
+        .iter()
+        .enumerate()
+        .flat_map(|(index, frame)| unknown_fields_of_frame(index, frame))
+        .collect();
+    if !unknown.is_empty() {
+        return Err(unknown_fields_error(&unknown));
+    }
+
+    Ok(frames
+        .into_iter()
+        .map(serde_json::from_value)
+        .collect::<std::result::Result<_, _>>()?)
+}
+
+fn unknown_fields_of_frame(index: usize, frame: &serde_json::Value) -> Vec<String> {
+    unknown_frame_fields(frame)
+        .into_iter()
+        .map(|field| format!("{field} (frame {index})"))
+        .collect()
+}
+
+fn unknown_fields_error(unknown: &[String]) -> Error {
+    Error::ConversionError(format!(
+        "V2 response frames, as they have unknown fields: {}",
+        unknown.join(", ")
+    ))
+}
+
+/// Orders the tables of a parsed response by their ids, which follow the order of the statements of the query.
+///
+/// The service sends the tables in that order, so the frames are left as they are unless a table starts with a lower id than the one before it.
+/// Then the frames of each table, from its start to the start of the next one, are moved together, so that a table that can't be
+/// assembled from its frames fails the same way wherever it ends up.
+/// In the [progressive layout](FrameLayout::Progressive), whole tables can be sent while another table is being received,
+/// so the frames of a table that was started are moved with it, even when they come after such a table.
+fn order_tables(results: &mut Vec<V2QueryResult>) {
+    let table_start = |result: &V2QueryResult| match result {
+        V2QueryResult::DataTable(table) => Some(table.table_id),
+        V2QueryResult::TableHeader(header) => Some(header.table_id),
+        _ => None,
+    };
+    let starts: Vec<i32> = results.iter().filter_map(table_start).collect();
+    if starts.windows(2).all(|ids| ids[0] < ids[1]) {
+        return;
+    }
+
+    let progressive = results.iter().any(|result| match result {
+        V2QueryResult::DataSetHeader(header) => header.layout() == FrameLayout::Progressive,
+        _ => false,
+    });
+    let table_frame = |result: &V2QueryResult| {
+        match result {
+            V2QueryResult::TableFragment(fragment) => Some(fragment.table_id),
+            V2QueryResult::TableProgress(progress) => Some(progress.table_id),
+            V2QueryResult::TableCompletion(completion) => Some(completion.table_id),
             _ => None,
-        });
+        }
+        .filter(|table_id| progressive && starts.contains(table_id))
+    };
+
+    // The header is kept before every table and the completion after them
+    let mut key = i32::MIN;
+    let mut keyed: Vec<(i32, V2QueryResult)> = std::mem::take(results)
+        .into_iter()
+        .map(|result| {
+            key = match &result {
+                V2QueryResult::DataSetCompletion(_) => i32::MAX,
+                other => table_start(other)
+                    .or_else(|| table_frame(other))
+                    .unwrap_or(key),
+            };
+            (key, result)
+        })
+        .collect();
+    keyed.sort_by_key(|(key, _)| *key);
+    results.extend(keyed.into_iter().map(|(_, result)| result));
+}
+
+/// Checks that each row of the frames of a V2 response has a value for each column of its table, one frame at a time.
+struct RowWidthCheck {
+    pad_short_rows: bool,
+    /// The columns of progressive tables are in their header, and their rows are spread over fragments
+    headers: HashMap<i32, (String, usize, usize)>,
+}
+
+impl RowWidthCheck {
+    fn new(pad_short_rows: bool) -> Self {
+        Self {
+            pad_short_rows,
+            headers: HashMap::new(),
+        }
+    }
+
+    fn check(&mut self, result: &mut V2QueryResult) -> Result<()> {
+        match result {
+            V2QueryResult::DataTable(table) => check_row_widths(
+                table.table_id,
+                &table.table_name,
+                table.columns.len(),
+                None,
+                &mut table.rows,
+                0,
+                self.pad_short_rows,
+            )?,
+            V2QueryResult::TableHeader(header) => {
+                self.headers.insert(
+                    header.table_id,
+                    (header.table_name.clone(), header.columns.len(), 0),
+                );
+            }
+            V2QueryResult::TableFragment(fragment) => {
+                if let Some((table_name, columns, row_count)) =
+                    self.headers.get_mut(&fragment.table_id)
+                {
+                    let first_row = match fragment.table_fragment_type {
+                        TableFragmentType::DataAppend => *row_count,
+                        TableFragmentType::DataReplace => 0,
+                    };
+                    check_row_widths(
+                        fragment.table_id,
+                        table_name,
+                        *columns,
+                        fragment.field_count,
+                        &mut fragment.rows,
+                        first_row,
+                        self.pad_short_rows,
+                    )?;
+                    *row_count = first_row + fragment.rows.len();
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// The name of the frame type of a result, as sent by the service.
+fn frame_type(result: &V2QueryResult) -> &'static str {
+    match result {
+        V2QueryResult::DataSetHeader(_) => "DataSetHeader",
+        V2QueryResult::DataTable(_) => "DataTable",
+        V2QueryResult::DataSetCompletion(_) => "DataSetCompletion",
+        V2QueryResult::TableHeader(_) => "TableHeader",
+        V2QueryResult::TableFragment(_) => "TableFragment",
+        V2QueryResult::TableProgress(_) => "TableProgress",
+        V2QueryResult::TableCompletion(_) => "TableCompletion",
+    }
+}
 
-        if let Some(V2QueryResult::DataTable(t)) = next_table {
-            return Some(t);
+impl<F: Frame, T: Iterator<Item = F>> Iterator for KustoResponseDataSetV2TableIterator<F, T> {
+    type Item = Result<DataTable>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished && self.pending.is_empty() {
+            return None;
         }
+        let next_table = loop {
+            // The frames are not read again once they ran out, but the tables received before that are still yielded
+            let next_frame = self.pending.pop_front().or_else(|| {
+                if self.finished {
+                    None
+                } else {
+                    self.tables.next()
+                }
+            });
+            let frame = match next_frame {
+                Some(frame) => frame,
+                None => break None,
+            };
+            match frame.result() {
+                V2QueryResult::DataSetHeader(header) => self.layout = header.layout(),
+                V2QueryResult::DataTable(_) | V2QueryResult::TableHeader(_) => {
+                    break Some(frame.into_result())
+                }
+                _ => {}
+            }
+        };
 
+        let header = match next_table {
+            Some(V2QueryResult::DataTable(t)) => return Some(Ok(t)),
+            Some(V2QueryResult::TableHeader(header)) => header,
+            _ => {
+                self.finished = true;
+                return None;
+            }
+        };
         let mut table = DataTable {
-            table_id: 0,
-            table_name: "".to_string(),
-            table_kind: TableKind::Unknown,
-            columns: vec![],
+            table_id: header.table_id,
+            table_name: header.table_name,
+            table_kind: header.table_kind,
+            columns: header.columns,
             rows: vec![],
         };
 
-        if let Some(V2QueryResult::TableHeader(header)) = next_table {
-            table.table_id = header.table_id;
-            table.table_kind = header.table_kind;
-            table.table_name = header.table_name;
-            table.columns = header.columns;
-        } else {
-            self.finished = true;
-            return None;
-        }
+        // The remaining frames of a table that failed are skipped when looking for the next one
+        let error = |table: &DataTable, message: String| {
+            Some(Err(Error::ConversionError(format!(
+                "table '{}' ({}), {message}",
+                table.table_name, table.table_id
+            ))))
+        };
+
+        // The fragments since the last replacement, whose rows are only taken once the table is complete
+        let mut fragments: Vec<F> = vec![];
+        let mut row_count = 0;
 
-        let mut finished_table = false;
+        for frame in &mut self.tables {
+            let result = frame.result();
+            let table_id = match result {
+                V2QueryResult::TableFragment(fragment) => fragment.table_id,
+                V2QueryResult::TableProgress(progress) => progress.table_id,
+                V2QueryResult::TableCompletion(completion) => completion.table_id,
+                V2QueryResult::DataTable(_) if self.layout == FrameLayout::Progressive => {
+                    self.pending.push_back(frame);
+                    continue;
+                }
+                other => {
+                    let message = format!(
+                        "a {} frame was found before the table was completed",
+                        frame_type(other)
+                    );
+                    if matches!(
+                        other,
+                        V2QueryResult::DataTable(_) | V2QueryResult::TableHeader(_)
+                    ) {
+                        self.pending.push_back(frame);
+                    }
+                    return error(&table, message);
+                }
+            };
+            if table_id != table.table_id {
+                return error(
+                    &table,
+                    format!(
+                        "a {} frame of table {table_id} was found before the table was completed",
+                        frame_type(result)
+                    ),
+                );
+            }
 
-        for result in &mut self.tables {
             match result {
                 V2QueryResult::TableFragment(fragment) => {
-                    assert_eq!(fragment.table_id, table.table_id);
-                    match fragment.table_fragment_type {
-                        TableFragmentType::DataAppend => table.rows.extend(fragment.rows),
-                        TableFragmentType::DataReplace => table.rows = fragment.rows,
-                    };
-                }
-                V2QueryResult::TableProgress(progress) => {
-                    assert_eq!(progress.table_id, table.table_id);
+                    if fragment.table_fragment_type == TableFragmentType::DataReplace {
+                        fragments.clear();
+                        row_count = 0;
+                    }
+                    row_count += fragment.rows.len();
+                    fragments.push(frame);
                 }
                 V2QueryResult::TableCompletion(completion) => {
-                    assert_eq!(completion.table_id, table.table_id);
-                    assert_eq!(
-                        completion.row_count,
-                        TryInto::<i32>::try_into(table.rows.len()).expect("Row count overflow")
-                    );
-                    finished_table = true;
-                    break;
+                    if TryInto::<i32>::try_into(row_count).ok() != Some(completion.row_count) {
+                        return error(
+                            &table,
+                            format!(
+                                "its completion reports {} rows but {row_count} were received",
+                                completion.row_count
+                            ),
+                        );
+                    }
+                    table.rows.reserve_exact(row_count + 1);
+                    for fragment in fragments {
+                        fragment.append_rows_to(&mut table.rows);
+                    }
+                    // The errors are kept as the last row, as they are in tables that are sent whole
+                    if let Some(errors) =
+                        completion.one_api_errors.as_ref().filter(|e| !e.is_empty())
+                    {
+                        table
+                            .rows
+                            .push(serde_json::json!({ "OneApiErrors": errors }));
+                    }
+                    return Some(Ok(table));
                 }
-                _ => unreachable!("Unexpected result type"),
+                _ => {}
             }
         }
 
-        if finished_table {
-            Some(table)
-        } else {
-            None
-        }
+        self.finished = true;
+        error(
+            &table,
+            "the response ended before the table was completed".to_string(),
+        )
     }
 }
 
 impl KustoResponseDataSetV2 {
+    /// Parses a V2 response body, checking that each row has a value for each column of its table, and ordering its tables by their ids.
+    /// Short rows are padded with nulls if [pad_short_rows](ParseOptions::with_pad_short_rows) is set, otherwise they fail the parsing.
+    /// Frames with unknown fields fail it if [deny_unknown_frame_fields](ParseOptions::with_deny_unknown_frame_fields) is set.
+    ///
+    /// The client parses responses as they are read, with [from_async_buf_read](Self::from_async_buf_read),
+    /// which the tests check against parsing the whole body.
+    #[cfg(test)]
+    pub(crate) fn from_body(data: &[u8], parse_options: ParseOptions) -> Result<Self> {
+        let mut results: Vec<V2QueryResult> = if parse_options.deny_unknown_frame_fields {
+            parse_frames_strictly(data)?
+        } else {
+            serde_json::from_slice(data)?
+        };
+
+        let mut check = RowWidthCheck::new(parse_options.pad_short_rows);
+        for result in &mut results {
+            check.check(result)?;
+        }
+        order_tables(&mut results);
+
+        Ok(Self { results })
+    }
+
+    /// Parses a V2 response body as it is read, one frame at a time, so that neither the whole body nor all of its frames as
+    /// JSON values are held in memory at once. Frames are found by the nesting of their brackets,
+    /// whether or not they are separated by newlines.
+    ///
+    /// The rows are checked as they are for the responses of the client, according to
+    /// [pad_short_rows](ParseOptions::with_pad_short_rows) and [deny_unknown_frame_fields](ParseOptions::with_deny_unknown_frame_fields).
+    /// # Example
+    /// ```rust
+    /// use azure_kusto_data::prelude::{KustoResponseDataSetV2, ParseOptions};
+    ///
+    /// # futures::executor::block_on(async {
+    /// let body = "[\n{\"FrameType\":\"DataSetHeader\",\"IsProgressive\":false,\"Version\":\"v2.0\"}\n]";
+    /// let response = KustoResponseDataSetV2::from_async_buf_read(body.as_bytes(), ParseOptions::default())
+    ///     .await
+    ///     .unwrap();
+    /// assert_eq!(response.raw_results_count(), 1);
+    /// # });
+    /// ```
+    pub async fn from_async_buf_read(
+        reader: impl AsyncBufRead + Unpin,
+        parse_options: ParseOptions,
+    ) -> Result<Self> {
+        let mut check = RowWidthCheck::new(parse_options.pad_short_rows);
+        let mut results = Vec::new();
+        if parse_options.deny_unknown_frame_fields {
+            let mut frames = Box::pin(async_deserializer::iter_results::<serde_json::Value>(
+                reader,
+            ));
+            let mut unknown = Vec::new();
+            let mut index = 0;
+            while let Some(frame) = frames.try_next().await? {
+                unknown.extend(unknown_fields_of_frame(index, &frame));
+                index += 1;
+                // Once a frame has unknown fields the response fails, so the rest are only read to list theirs
+                if unknown.is_empty() {
+                    let mut result = serde_json::from_value(frame)?;
+                    check.check(&mut result)?;
+                    results.push(result);
+                }
+            }
+            if !unknown.is_empty() {
+                return Err(unknown_fields_error(&unknown));
+            }
+        } else {
+            let mut frames = Box::pin(async_deserializer::iter_results::<V2QueryResult>(reader));
+            while let Some(mut result) = frames.try_next().await? {
+                check.check(&mut result)?;
+                results.push(result);
+            }
+        }
+        order_tables(&mut results);
+
+        Ok(Self { results })
+    }
+
     /// Count of the number of the raw results in the response.
     /// This, in addition to tables, includes headers and other non-table results.
     /// # Example
@@ -285,7 +877,7 @@ impl KustoResponseDataSetV2 {
     ///
     /// let data_set = KustoResponseDataSetV2 {
     ///    results: vec![
-    ///         V2QueryResult::DataSetHeader(DataSetHeader {is_progressive: false,version: "".to_string()}),
+    ///         V2QueryResult::DataSetHeader(DataSetHeader::new(false, "")),
     ///         V2QueryResult::DataTable(DataTable {
     ///         table_id: 0,
     ///         table_name: "table_1".to_string(),
@@ -302,11 +894,34 @@ impl KustoResponseDataSetV2 {
         self.results.len()
     }
 
+    /// The header of the dataset, which is the first frame of a complete response.
+    #[must_use]
+    pub fn header(&self) -> Option<&DataSetHeader> {
+        self.results.iter().find_map(|result| match result {
+            V2QueryResult::DataSetHeader(header) => Some(header),
+            _ => None,
+        })
+    }
+
+    /// The completion of the dataset, which is the last frame of a complete response.
+    /// Responses that were cut short, such as by a failure of the service, have no completion.
+    #[must_use]
+    pub fn completion(&self) -> Option<&DataSetCompletion> {
+        self.results.iter().rev().find_map(|result| match result {
+            V2QueryResult::DataSetCompletion(completion) => Some(completion),
+            _ => None,
+        })
+    }
+
     /// Iterates over the tables in the response.
     /// If the query is progressive, it will combine the table parts into a single table.
     ///
+    /// A progressive table whose frames are inconsistent, such as a completion whose row count doesn't match the rows received,
+    /// is yielded as an [Error::ConversionError], and the iteration continues with the next table.
+    ///
     /// This method does not consume the response, so it can be called multiple times.
-    /// [Use into_parsed_data_tables](#method.into_parsed_data_tables) to consume the response and reduce memory usage.
+    /// The rows of each table are cloned when the table is yielded, which the consuming
+    /// [into_parsed_data_tables](#method.into_parsed_data_tables) avoids by moving them out of the response.
     /// # Example
     /// ```rust
     /// use azure_kusto_data::models::*;
@@ -314,7 +929,7 @@ impl KustoResponseDataSetV2 {
     ///
     ///let data_set = KustoResponseDataSetV2 {
     ///results: vec![
-    ///    V2QueryResult::DataSetHeader(DataSetHeader {is_progressive: false,version: "".to_string()}),
+    ///    V2QueryResult::DataSetHeader(DataSetHeader::new(false, "")),
     ///    V2QueryResult::DataTable(DataTable {
     ///        table_id: 0,
     ///        table_name: "table_1".to_string(),
@@ -328,26 +943,80 @@ impl KustoResponseDataSetV2 {
     ///        table_kind: TableKind::PrimaryResult,
     ///        columns: vec![],
     ///    }),
-    ///    V2QueryResult::TableCompletion(TableCompletion {
-    ///        table_id: 1,
-    ///        row_count: 0,
-    ///    }),
+    ///    V2QueryResult::TableCompletion(TableCompletion::new(1, 0)),
     ///],
     ///};
     /// let mut results = vec![];
     /// for table in data_set.parsed_data_tables() {
+    ///    let table = table.unwrap();
     ///    results.push(format!("{} - {}", table.table_id, table.table_name));
     /// }
     ///
     /// assert_eq!(results, vec!["0 - table_1", "1 - table_2"]);
     /// ```
-    pub fn parsed_data_tables(&self) -> impl Iterator<Item = DataTable> + '_ {
-        KustoResponseDataSetV2TableIterator::new(self.results.iter().cloned())
+    pub fn parsed_data_tables(&self) -> impl Iterator<Item = Result<DataTable>> + '_ {
+        KustoResponseDataSetV2TableIterator::new(self.results.iter())
+    }
+
+    /// The first table of the response with the given name, such as `QueryCompletionInformation`, reassembled if the query is progressive.
+    ///
+    /// Several tables can have the same name, such as the `PrimaryResult` tables of a batch, so [table_by_id](#method.table_by_id)
+    /// selects a specific one. Returns `Ok(None)` if there is no such table.
+    ///
+    /// The name of a table that can't be assembled is not known, so the error of the first such table before the one that is
+    /// looked for is returned, as in [parsed_data_tables](#method.parsed_data_tables).
+    pub fn table_by_name(&self, table_name: &str) -> Result<Option<DataTable>> {
+        self.find_table(|table| table.table_name == table_name)
+    }
+
+    /// The table of the response with the given id, reassembled if the query is progressive.
+    /// Returns `Ok(None)` if there is no such table, and fails as [table_by_name](#method.table_by_name) does.
+    pub fn table_by_id(&self, table_id: i32) -> Result<Option<DataTable>> {
+        self.find_table(|table| table.table_id == table_id)
+    }
+
+    fn find_table(&self, predicate: impl Fn(&DataTable) -> bool) -> Result<Option<DataTable>> {
+        for table in self.parsed_data_tables() {
+            let table = table?;
+            if predicate(&table) {
+                return Ok(Some(table));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Iterates over the tables of the given kind, reassembled as in [parsed_data_tables](#method.parsed_data_tables).
+    /// The frames of the other tables are skipped, so that they are not assembled.
+    pub(crate) fn parsed_tables_of_kind(
+        &self,
+        kind: TableKind,
+    ) -> impl Iterator<Item = Result<DataTable>> + '_ {
+        let table_ids: Vec<i32> = self
+            .results
+            .iter()
+            .filter_map(|result| match result {
+                V2QueryResult::DataTable(table) if table.table_kind == kind => Some(table.table_id),
+                V2QueryResult::TableHeader(header) if header.table_kind == kind => {
+                    Some(header.table_id)
+                }
+                _ => None,
+            })
+            .collect();
+        KustoResponseDataSetV2TableIterator::new(self.results.iter().filter(move |result| {
+            frame_table_id(result).map_or(true, |table_id| table_ids.contains(&table_id))
+        }))
     }
 
     /// Iterates over the tables in the response, yielding only the primary tables.
     /// If the query is progressive, it will combine the table parts into a single table.
     ///
+    /// The tables of a response parsed by the client are yielded in ascending order of their [table_id](DataTable::table_id),
+    /// which is the order of the statements of the query that produced them. A statement can produce several tables, such as the legs of a `fork`,
+    /// or none, such as a `set` statement, so the position of a table is not always the index of its statement.
+    ///
+    /// Tables that can't be assembled are yielded as errors, as in [parsed_data_tables](#method.parsed_data_tables).
+    /// [Use primary_results_lossy](#method.primary_results_lossy) to skip them instead.
+    ///
     /// This method does not consume the response, so it can be called multiple times.
     /// [Use into_primary_results](#method.into_primary_results) to consume the response and reduce memory usage.
     /// # Example
@@ -357,7 +1026,7 @@ impl KustoResponseDataSetV2 {
     ///
     ///let data_set = KustoResponseDataSetV2 {
     ///results: vec![
-    ///    V2QueryResult::DataSetHeader(DataSetHeader {is_progressive: false,version: "".to_string()}),
+    ///    V2QueryResult::DataSetHeader(DataSetHeader::new(false, "")),
     ///    V2QueryResult::DataTable(DataTable {
     ///        table_id: 0,
     ///        table_name: "table_1".to_string(),
@@ -371,23 +1040,26 @@ impl KustoResponseDataSetV2 {
     ///        table_kind: TableKind::PrimaryResult,
     ///        columns: vec![],
     ///    }),
-    ///    V2QueryResult::TableCompletion(TableCompletion {
-    ///        table_id: 1,
-    ///        row_count: 0,
-    ///    }),
+    ///    V2QueryResult::TableCompletion(TableCompletion::new(1, 0)),
     ///],
     ///};
     /// let mut results = vec![];
     /// for table in data_set.primary_results() {
+    ///    let table = table.unwrap();
     ///    results.push(format!("{} - {}", table.table_id, table.table_name));
     /// }
     ///
     /// assert_eq!(results, vec!["1 - table_2"]);
     /// ```
     /// Consumes the response into an iterator over all PrimaryResult tables within the response dataset
-    pub fn primary_results(&self) -> impl Iterator<Item = DataTable> + '_ {
-        self.parsed_data_tables()
-            .filter(|t| t.table_kind == TableKind::PrimaryResult)
+    pub fn primary_results(&self) -> impl Iterator<Item = Result<DataTable>> + '_ {
+        self.parsed_data_tables().filter(is_primary_result)
+    }
+
+    /// Iterates over the primary tables in the response, skipping those that can't be assembled.
+    /// [Use primary_results](#method.primary_results) to get the errors of those tables.
+    pub fn primary_results_lossy(&self) -> impl Iterator<Item = DataTable> + '_ {
+        self.primary_results().filter_map(Result::ok)
     }
 
     /// Iterates over the tables in the response, and converts them into `arrow` `Batches`
@@ -403,7 +1075,7 @@ impl KustoResponseDataSetV2 {
     ///
     ///let data_set = KustoResponseDataSetV2 {
     ///results: vec![
-    ///    V2QueryResult::DataSetHeader(DataSetHeader {is_progressive: false,version: "".to_string()}),
+    ///    V2QueryResult::DataSetHeader(DataSetHeader::new(false, "")),
     ///    V2QueryResult::DataTable(DataTable {
     ///        table_id: 0,
     ///        table_name: "table_1".to_string(),
@@ -423,10 +1095,7 @@ impl KustoResponseDataSetV2 {
     ///       field_count: Some(1),
     ///       table_fragment_type: TableFragmentType::DataAppend,
     ///     }),
-    ///    V2QueryResult::TableCompletion(TableCompletion {
-    ///        table_id: 1,
-    ///        row_count: 2,
-    ///    }),
+    ///    V2QueryResult::TableCompletion(TableCompletion::new(1, 2)),
     ///],
     ///};
     /// let mut results = vec![];
@@ -439,85 +1108,491 @@ impl KustoResponseDataSetV2 {
     /// Consumes the response into an iterator over all PrimaryResult tables within the response dataset
     #[cfg(feature = "arrow")]
     pub fn record_batches(&self) -> impl Iterator<Item = Result<RecordBatch>> + '_ {
-        self.primary_results().map(convert_table)
+        self.record_batches_with(ArrowOptions::default())
     }
 
-    /// Consuming version for [parse_data_tables](#method.parse_data_tables).
-    pub fn into_parsed_data_tables(self) -> impl Iterator<Item = DataTable> {
-        KustoResponseDataSetV2TableIterator::new(self.results.into_iter())
+    /// Iterates over the primary tables in the response, and converts them into `arrow` `Batches` according to the `options`,
+    /// such as to convert dynamic columns into structs.
+    /// # Example
+    /// ```rust
+    /// use serde_json::json;
+    /// use azure_kusto_data::models::*;
+    /// use azure_kusto_data::prelude::{ArrowOptions, KustoResponseDataSetV2};
+    ///
+    /// let data_set = KustoResponseDataSetV2 {
+    ///     results: vec![V2QueryResult::DataTable(DataTable {
+    ///         table_id: 1,
+    ///         table_name: "PrimaryResult".to_string(),
+    ///         table_kind: TableKind::PrimaryResult,
+    ///         columns: vec![Column { column_name: "d".to_string(), column_type: ColumnType::Dynamic }],
+    ///         rows: vec![json!([{"a": 1, "b": "x"}]), json!([{"a": 2}])],
+    ///     })],
+    /// };
+    ///
+    /// let options = ArrowOptions::default().with_dynamic_as_struct(true);
+    /// let batch = data_set.record_batches_with(options).next().unwrap().unwrap();
+    /// assert!(matches!(batch.schema().field(0).data_type(), arrow_schema::DataType::Struct(_)));
+    /// ```
+    #[cfg(feature = "arrow")]
+    pub fn record_batches_with(
+        &self,
+        options: ArrowOptions,
+    ) -> impl Iterator<Item = Result<RecordBatch>> + '_ {
+        self.primary_results()
+            .map(move |table| table.and_then(|table| convert_table(table, options)))
     }
 
-    /// Consuming version for [primary_results](#method.primary_results).
-    pub fn into_primary_results(self) -> impl Iterator<Item = DataTable> {
-        self.into_parsed_data_tables()
-            .filter(|t| t.table_kind == TableKind::PrimaryResult)
+    /// Consuming version for [parsed_data_tables](#method.parsed_data_tables), which moves the rows out of the response rather than cloning them.
+    pub fn into_parsed_data_tables(self) -> impl Iterator<Item = Result<DataTable>> {
+        KustoResponseDataSetV2TableIterator::new(self.results.into_iter())
     }
 
-    #[cfg(feature = "arrow")]
-    /// Consuming version for [record_batches](#method.record_batches).
-    pub fn into_record_batches(self) -> impl Iterator<Item = Result<RecordBatch>> {
-        self.into_primary_results().map(convert_table)
+    /// Consuming version for [primary_results](#method.primary_results), yielding the tables in the same order.
+    pub fn into_primary_results(self) -> impl Iterator<Item = Result<DataTable>> {
+        self.into_parsed_data_tables().filter(is_primary_result)
     }
-}
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
-#[serde(rename_all = "PascalCase")]
-/// The header of a Kusto response dataset for v1. Contains a list of tables.
-pub struct KustoResponseDataSetV1 {
-    /// The list of tables in the dataset.
-    pub tables: Vec<TableV1>,
-}
+    /// Consuming version for [primary_results_lossy](#method.primary_results_lossy).
+    pub fn into_primary_results_lossy(self) -> impl Iterator<Item = DataTable> {
+        self.into_primary_results().filter_map(Result::ok)
+    }
 
-impl KustoResponseDataSetV1 {
-    #[must_use]
-    /// Count the number of tables in the dataset.
-    /// # Example
-    /// ```rust
-    /// use azure_kusto_data::models::TableV1;
-    /// use azure_kusto_data::prelude::KustoResponseDataSetV1;
-    /// let dataset = KustoResponseDataSetV1 {
-    ///    tables: vec![
-    ///       TableV1 {
-    ///         table_name: "table_1".to_string(),
-    ///         columns: vec![],
-    ///         rows: vec![],
-    ///      },
-    /// ]};
+    /// The primary tables of the response, as [primary_results](#method.primary_results) yields them, without the errors that the service
+    /// reported in place of some of their rows, which are returned next to them.
     ///
-    /// assert_eq!(dataset.table_count(), 1);
+    /// When a query partially fails, such as when its results exceed the limits of the service, its tables end with
+    /// a `{"OneApiErrors": [...]}` row, which is where the errors that the service reports in the completion of a fragmented table are put
+    /// as well. [primary_results](#method.primary_results) leaves those rows in the tables, where they are easily missed.
     ///
-    pub fn table_count(&self) -> usize {
-        self.tables.len()
+    /// Fails if a table can't be assembled, or if an error row can't be parsed.
+    /// [Use into_primary_results_checked](#method.into_primary_results_checked) to consume the response and reduce memory usage.
+    pub fn primary_results_checked(&self) -> Result<Partial<Vec<DataTable>>> {
+        checked(self.primary_results())
     }
-}
 
-#[async_convert::async_trait]
-impl TryFrom<HttpResponse> for KustoResponseDataSetV2 {
-    type Error = Error;
-
-    async fn try_from(response: HttpResponse) -> Result<Self> {
-        let (_status_code, _header_map, pinned_stream) = response.deconstruct();
-        let data = pinned_stream.collect().await?;
-        let tables: Vec<V2QueryResult> = serde_json::from_slice(&data)?;
-        Ok(Self { results: tables })
+    /// Consuming version for [primary_results_checked](#method.primary_results_checked).
+    pub fn into_primary_results_checked(self) -> Result<Partial<Vec<DataTable>>> {
+        checked(self.into_primary_results())
     }
-}
 
-#[async_convert::async_trait]
-impl TryFrom<HttpResponse> for KustoResponseDataSetV1 {
-    type Error = Error;
+    /// Merges the PrimaryResult tables of the response into a single table, such as those of the legs of a `fork`.
+    /// The rows of the tables are concatenated in order, under the id, name and columns of the first table.
+    ///
+    /// Fails if the tables don't all have the same columns, if one of them can't be assembled, or if there are no PrimaryResult tables.
+    /// [Use into_merged_primary_results](#method.into_merged_primary_results) to consume the response and reduce memory usage.
+    /// # Example
+    /// ```rust
+    /// use serde_json::json;
+    /// use azure_kusto_data::models::*;
+    /// use azure_kusto_data::prelude::KustoResponseDataSetV2;
+    ///
+    /// let table = |table_id, rows| V2QueryResult::DataTable(DataTable {
+    ///     table_id,
+    ///     table_name: "PrimaryResult".to_string(),
+    ///     table_kind: TableKind::PrimaryResult,
+    ///     columns: vec![Column { column_name: "x".to_string(), column_type: ColumnType::Long }],
+    ///     rows,
+    /// });
+    /// let data_set = KustoResponseDataSetV2 {
+    ///     results: vec![table(1, vec![json!([1]), json!([2])]), table(2, vec![json!([3])])],
+    /// };
+    ///
+    /// let merged = data_set.merge_primary_results().unwrap();
+    /// assert_eq!(merged.table_id, 1);
+    /// assert_eq!(merged.rows, vec![json!([1]), json!([2]), json!([3])]);
+    /// ```
+    pub fn merge_primary_results(&self) -> Result<DataTable> {
+        merge_tables(self.primary_results())
+    }
 
-    async fn try_from(response: HttpResponse) -> Result<Self> {
-        let (_status_code, _header_map, pinned_stream) = response.deconstruct();
-        let data = pinned_stream.collect().await?;
-        Ok(serde_json::from_slice(&data)?)
+    /// Consuming version for [merge_primary_results](#method.merge_primary_results).
+    pub fn into_merged_primary_results(self) -> Result<DataTable> {
+        merge_tables(self.into_primary_results())
     }
-}
+
+    /// Returns the single primary result of the response, which is the result of most queries, such as one ending with `take` or `summarize`.
+    ///
+    /// Fails with distinct errors if the response has no primary result or more than one,
+    /// and with the error of the table if it can't be assembled.
+    /// [Use into_single_primary_result](#method.into_single_primary_result) to consume the response and reduce memory usage.
+    /// # Example
+    /// ```rust
+    /// use serde_json::json;
+    /// use azure_kusto_data::models::*;
+    /// use azure_kusto_data::prelude::KustoResponseDataSetV2;
+    ///
+    /// let data_set = KustoResponseDataSetV2 {
+    ///     results: vec![V2QueryResult::DataTable(DataTable {
+    ///         table_id: 1,
+    ///         table_name: "PrimaryResult".to_string(),
+    ///         table_kind: TableKind::PrimaryResult,
+    ///         columns: vec![Column { column_name: "x".to_string(), column_type: ColumnType::Long }],
+    ///         rows: vec![json!([1])],
+    ///     })],
+    /// };
+    ///
+    /// let table = data_set.single_primary_result().unwrap();
+    /// assert_eq!(table.rows, vec![json!([1])]);
+    /// ```
+    pub fn single_primary_result(&self) -> Result<DataTable> {
+        single_table(self.primary_results())
+    }
+
+    /// Consuming version for [single_primary_result](#method.single_primary_result).
+    pub fn into_single_primary_result(self) -> Result<DataTable> {
+        single_table(self.into_primary_results())
+    }
+
+    /// Returns the primary result at the given position among the primary results of the response, in the order of
+    /// [primary_results](#method.primary_results), such as `1` for the result of `StormEvents | take 5` in `print 1; StormEvents | take 5`.
+    ///
+    /// Fails if the response has fewer primary results, or with the error of the table if it can't be assembled.
+    /// [Use into_primary_result_by_index](#method.into_primary_result_by_index) to consume the response and reduce memory usage.
+    pub fn primary_result_by_index(&self, index: usize) -> Result<DataTable> {
+        nth_table(self.primary_results(), index)
+    }
+
+    /// Consuming version for [primary_result_by_index](#method.primary_result_by_index).
+    pub fn into_primary_result_by_index(self, index: usize) -> Result<DataTable> {
+        nth_table(self.into_primary_results(), index)
+    }
+
+    /// Returns the first primary result with the given name. Primary results are named `PrimaryResult` unless the query names them,
+    /// such as with `StormEvents | take 5 | as Storms`.
+    ///
+    /// Fails if the response has no such primary result. If some primary results can't be assembled, the error of the first one is
+    /// returned instead, as it may have been the one.
+    /// [Use into_primary_result_by_name](#method.into_primary_result_by_name) to consume the response and reduce memory usage.
+    pub fn primary_result_by_name(&self, table_name: &str) -> Result<DataTable> {
+        named_table(self.primary_results(), table_name)
+    }
+
+    /// Consuming version for [primary_result_by_name](#method.primary_result_by_name).
+    pub fn into_primary_result_by_name(self, table_name: &str) -> Result<DataTable> {
+        named_table(self.into_primary_results(), table_name)
+    }
+
+    /// Deserializes the first primary result of the response into `T`, by converting it into an `arrow` record batch
+    /// and deserializing its columns, which avoids creating a JSON value for each row.
+    ///
+    /// Unlike the rows of [execute_query_to_struct](crate::client::KustoClient::execute_query_to_struct), which are deserialized from JSON,
+    /// the columns are matched to the fields of `T` by name, and are deserialized from their arrow types:
+    /// datetimes and timespans as `i64` nanoseconds, and decimals, guids and dynamic values as strings.
+    /// # Example
+    /// ```rust
+    /// use serde_json::json;
+    /// use azure_kusto_data::models::*;
+    /// use azure_kusto_data::prelude::KustoResponseDataSetV2;
+    ///
+    /// #[derive(serde::Deserialize)]
+    /// struct Row {
+    ///     name: String,
+    ///     count: i64,
+    /// }
+    ///
+    /// let data_set = KustoResponseDataSetV2 {
+    ///     results: vec![V2QueryResult::DataTable(DataTable {
+    ///         table_id: 1,
+    ///         table_name: "PrimaryResult".to_string(),
+    ///         table_kind: TableKind::PrimaryResult,
+    ///         columns: vec![
+    ///             Column { column_name: "name".to_string(), column_type: ColumnType::String },
+    ///             Column { column_name: "count".to_string(), column_type: ColumnType::Long },
+    ///         ],
+    ///         rows: vec![json!(["a", 1]), json!(["b", 2])],
+    ///     })],
+    /// };
+    ///
+    /// let rows: Vec<Row> = data_set.into_structs_via_arrow().unwrap();
+    /// assert_eq!(rows[1].count, 2);
+    /// ```
+    #[cfg(feature = "arrow")]
+    pub fn into_structs_via_arrow<T: DeserializeOwned>(self) -> Result<Vec<T>> {
+        let batch = convert_table(self.into_single_primary_result()?, ArrowOptions::default())?;
+        deserialize_record_batch(&batch)
+    }
+
+    #[cfg(feature = "arrow")]
+    /// Consuming version for [record_batches](#method.record_batches).
+    pub fn into_record_batches(self) -> impl Iterator<Item = Result<RecordBatch>> {
+        self.into_record_batches_with(ArrowOptions::default())
+    }
+
+    #[cfg(feature = "arrow")]
+    /// Consuming version for [record_batches_with](#method.record_batches_with).
+    pub fn into_record_batches_with(
+        self,
+        options: ArrowOptions,
+    ) -> impl Iterator<Item = Result<RecordBatch>> {
+        self.into_primary_results()
+            .map(move |table| table.and_then(|table| convert_table(table, options)))
+    }
+}
+
+/// Whether the result is a primary table, keeping the errors of tables whose kind is unknown because they failed.
+fn is_primary_result(table: &Result<DataTable>) -> bool {
+    table
+        .as_ref()
+        .map_or(true, |t| t.table_kind == TableKind::PrimaryResult)
+}
+
+/// The id of the table that a frame is part of, if it is part of one.
+fn frame_table_id(result: &V2QueryResult) -> Option<i32> {
+    match result {
+        V2QueryResult::DataTable(table) => Some(table.table_id),
+        V2QueryResult::TableHeader(header) => Some(header.table_id),
+        V2QueryResult::TableFragment(fragment) => Some(fragment.table_id),
+        V2QueryResult::TableProgress(progress) => Some(progress.table_id),
+        V2QueryResult::TableCompletion(completion) => Some(completion.table_id),
+        _ => None,
+    }
+}
+
+/// Splits the errors reported in place of rows out of the tables.
+pub(crate) fn checked(
+    tables: impl Iterator<Item = Result<DataTable>>,
+) -> Result<Partial<Vec<DataTable>>> {
+    let mut partial = Partial {
+        data: vec![],
+        errors: vec![],
+    };
+    for table in tables {
+        let mut table = table?;
+        let mut rows = Vec::with_capacity(table.rows.len());
+        for row in std::mem::take(&mut table.rows) {
+            match row.get("OneApiErrors") {
+                Some(errors) => partial
+                    .errors
+                    .extend(Vec::<OneApiError>::deserialize(errors)?),
+                None => rows.push(row),
+            }
+        }
+        table.rows = rows;
+        partial.data.push(table);
+    }
+    Ok(partial)
+}
+
+/// Returns the only one of the tables, failing with [Error::NoPrimaryResult] or [Error::SeveralPrimaryResults]
+/// if there are none or several of them.
+fn single_table(mut tables: impl Iterator<Item = Result<DataTable>>) -> Result<DataTable> {
+    let table = tables.next().ok_or(Error::NoPrimaryResult)?;
+
+    let others = tables.count();
+    if others > 0 {
+        return Err(Error::SeveralPrimaryResults(others + 1));
+    }
+
+    table
+}
+
+/// Returns the table at the index, failing if there are not as many tables.
+fn nth_table(tables: impl Iterator<Item = Result<DataTable>>, index: usize) -> Result<DataTable> {
+    let mut count = 0;
+    for (position, table) in tables.enumerate() {
+        if position == index {
+            return table;
+        }
+        count = position + 1;
+    }
+    Err(Error::QueryError(
+        format!("No primary result at index {index}, as there are {count}").into(),
+    ))
+}
+
+/// Returns the first table with the name, or else the first error, which may have been that table.
+fn named_table(
+    tables: impl Iterator<Item = Result<DataTable>>,
+    table_name: &str,
+) -> Result<DataTable> {
+    let mut failure = None;
+    for table in tables {
+        match table {
+            Ok(table) if table.table_name == table_name => return Ok(table),
+            Ok(_) => {}
+            Err(e) => {
+                failure.get_or_insert(e);
+            }
+        }
+    }
+    Err(failure.unwrap_or_else(|| {
+        Error::QueryError(format!("No primary result named `{table_name}`").into())
+    }))
+}
+
+/// Concatenates the rows of the tables into the first of them, as long as they all have the same columns.
+fn merge_tables(mut tables: impl Iterator<Item = Result<DataTable>>) -> Result<DataTable> {
+    let mut merged = tables.next().ok_or(Error::NoPrimaryResult)??;
+
+    let schema = |table: &DataTable| {
+        table
+            .columns
+            .iter()
+            .map(|c| format!("{}: {:?}", c.column_name, c.column_type))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    for table in tables {
+        let table = table?;
+        if table.columns != merged.columns {
+            return Err(Error::ConversionError(format!(
+                "merged primary results, as table {} has the columns ({}) while table {} has ({})",
+                table.table_id,
+                schema(&table),
+                merged.table_id,
+                schema(&merged)
+            )));
+        }
+        merged.rows.extend(table.rows);
+    }
+
+    Ok(merged)
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
+/// The header of a Kusto response dataset for v1. Contains a list of tables.
+pub struct KustoResponseDataSetV1 {
+    /// The list of tables in the dataset.
+    pub tables: Vec<TableV1>,
+    /// Exceptions reported for the whole dataset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exceptions: Option<Vec<String>>,
+}
+
+impl KustoResponseDataSetV1 {
+    /// A dataset of the given tables, without exceptions.
+    #[must_use]
+    pub fn new(tables: Vec<TableV1>) -> Self {
+        Self {
+            tables,
+            exceptions: None,
+        }
+    }
+
+    #[must_use]
+    /// Count the number of tables in the dataset.
+    /// # Example
+    /// ```rust
+    /// use azure_kusto_data::models::TableV1;
+    /// use azure_kusto_data::prelude::KustoResponseDataSetV1;
+    /// let dataset = KustoResponseDataSetV1::new(vec![TableV1::new("table_1", vec![], vec![])]);
+    ///
+    /// assert_eq!(dataset.table_count(), 1);
+    ///
+    pub fn table_count(&self) -> usize {
+        self.tables.len()
+    }
+
+    /// The exceptions reported for the dataset and for each of its tables, converted to errors.
+    /// Exceptions in the OneApi format are returned as [Error::QueryApiError], with their details parsed.
+    #[must_use]
+    pub fn errors(&self) -> Vec<Error> {
+        self.exceptions
+            .iter()
+            .chain(self.tables.iter().filter_map(|t| t.exceptions.as_ref()))
+            .flatten()
+            .map(|e| Error::from_exception(e))
+            .collect()
+    }
+
+    /// Parses a V1 response body, according to the V1 options of the `client_request_properties`.
+    /// Unless [defer_v1_exceptions](ClientRequestProperties::defer_v1_exceptions) is set, exceptions reported within the response fail the parsing.
+    pub(crate) fn from_body(
+        status_code: StatusCode,
+        data: &[u8],
+        client_request_properties: Option<&ClientRequestProperties>,
+    ) -> Result<Self> {
+        let defer_exceptions = client_request_properties
+            .and_then(|p| p.defer_v1_exceptions)
+            .unwrap_or(false);
+        let parsing_mode = client_request_properties
+            .and_then(|p| p.v1_parsing_mode)
+            .unwrap_or_default();
+
+        let parsed = match parsing_mode {
+            V1ParsingMode::Strict => serde_json::from_slice::<Self>(data),
+            V1ParsingMode::Lenient => {
+                serde_json::from_slice::<serde_json::Value>(data).and_then(|mut value| {
+                    remove_unknown_row_keys(&mut value);
+                    serde_json::from_value(value)
+                })
+            }
+        };
+
+        let dataset = match parsed {
+            Ok(dataset) => dataset,
+            Err(e) => {
+                return Err(match serde_json::from_slice::<serde_json::Value>(data) {
+                    Ok(value) => match serde_json::from_value::<OneApiError>(value) {
+                        Ok(error) => Error::QueryApiError(Box::new(error)),
+                        Err(_) => e.into(),
+                    },
+                    Err(_) => Error::from_http_body(status_code, data),
+                })
+            }
+        };
+
+        if !defer_exceptions {
+            let mut errors = dataset.errors();
+            match errors.len() {
+                0 => {}
+                1 => return Err(errors.remove(0)),
+                _ => return Err(Error::MultipleErrors(errors)),
+            }
+        }
+
+        Ok(dataset)
+    }
+}
+
+// async_convert requires `Send` futures, which the wasm32 transport does not provide.
+#[cfg(not(target_arch = "wasm32"))]
+#[async_convert::async_trait]
+impl TryFrom<Response> for KustoResponseDataSetV2 {
+    type Error = Error;
+
+    async fn try_from(response: Response) -> Result<Self> {
+        let (_status_code, header_map, pinned_stream) = response.deconstruct();
+        let reader = pinned_stream
+            .map_err(|e| std::io::Error::new(ErrorKind::Other, e))
+            .into_async_read();
+        Self::from_async_buf_read(
+            decompress_reader(&header_map, reader),
+            ParseOptions::default(),
+        )
+        .await
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[async_convert::async_trait]
+impl TryFrom<Response> for KustoResponseDataSetV1 {
+    type Error = Error;
+
+    async fn try_from(response: Response) -> Result<Self> {
+        let (status_code, header_map, pinned_stream) = response.deconstruct();
+        let data = decompress_body(&header_map, pinned_stream.collect().await?)?;
+        Self::from_body(status_code, &data, None)
+    }
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::path::PathBuf;
+    use crate::connection_string::ConnectionString;
+    use crate::fixtures::{
+        fixture_path, load_v1_response, load_v2_response, read_fixture,
+        three_statements_out_of_order,
+    };
+    use crate::models::ErrorReportingPlacement;
+    use crate::prelude::KustoClientOptions;
+    use azure_core::headers::Headers;
+    #[cfg(feature = "tracing")]
+    use std::collections::HashMap;
+    use std::sync::Arc;
 
     #[test]
     fn load_response_data() {
@@ -540,14 +1615,1296 @@ mod tests {
 
     #[test]
     fn load_adminthenquery_response() {
-        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-        path.push("tests/inputs/adminthenquery.json");
+        let parsed = load_v1_response("adminthenquery.json");
+        assert_eq!(parsed.table_count(), 4);
+    }
+
+    #[tokio::test]
+    async fn primary_results_are_in_statement_order() {
+        let response = load_v2_response("three_statements.json");
+        let first_columns = |tables: Vec<DataTable>| {
+            tables
+                .into_iter()
+                .map(|t| (t.table_id, t.columns[0].column_name.clone()))
+                .collect::<Vec<_>>()
+        };
+        let expected = vec![
+            (1, "first".to_string()),
+            (2, "second".to_string()),
+            (3, "third".to_string()),
+        ];
+
+        let borrowed = response.primary_results().collect::<Result<Vec<_>>>();
+        assert_eq!(first_columns(borrowed.unwrap()), expected);
+        let consumed = response
+            .clone()
+            .into_primary_results()
+            .collect::<Result<Vec<_>>>();
+        assert_eq!(first_columns(consumed.unwrap()), expected);
+
+        let out_of_order = serde_json::to_string(&three_statements_out_of_order()).unwrap();
+        let whole =
+            KustoResponseDataSetV2::from_body(out_of_order.as_bytes(), ParseOptions::default())
+                .expect("Failed to parse");
+        assert_eq!(whole.results, response.results);
+        let read = KustoResponseDataSetV2::from_async_buf_read(
+            line_delimited(&out_of_order).as_bytes(),
+            ParseOptions::default(),
+        )
+        .await
+        .expect("Failed to parse");
+        assert_eq!(read.results, response.results);
+    }
+
+    #[test]
+    fn primary_results_with_the_same_columns_are_merged() {
+        let response = load_v2_response("twotables_same_schema.json");
+
+        let merged = response
+            .merge_primary_results()
+            .expect("Failed to merge primary results");
+        assert_eq!(merged.table_id, 1);
+        assert_eq!(merged.table_kind, TableKind::PrimaryResult);
+        assert_eq!(merged.columns.len(), 2);
+        assert_eq!(
+            merged.rows,
+            vec![
+                serde_json::json!(["TEXAS", 4701]),
+                serde_json::json!(["KANSAS", 3166]),
+                serde_json::json!(["IOWA", 2337]),
+            ]
+        );
+
+        assert_eq!(response.into_merged_primary_results().unwrap(), merged);
+    }
+
+    #[test]
+    fn error_rows_are_split_out_of_primary_results() {
+        let response = load_v2_response("partial_error_full_dataset.json");
+
+        // Unchecked, the error is left as the last row of the table
+        let table = response.primary_results_lossy().next().unwrap();
+        assert_eq!(table.rows.len(), 3);
+
+        let partial = response
+            .primary_results_checked()
+            .expect("Failed to check the primary results");
+        assert!(!partial.is_complete());
+        assert_eq!(partial.data.len(), 1);
+        assert_eq!(
+            partial.data[0].rows,
+            vec![serde_json::json!([1, "a"]), serde_json::json!([2, "b"])]
+        );
+        assert_eq!(partial.errors.len(), 1);
+        assert_eq!(partial.errors[0].error_message.code, "LimitsExceeded");
+        assert_eq!(
+            Some(&partial.errors),
+            response
+                .completion()
+                .and_then(|c| c.one_api_errors.as_ref())
+        );
+
+        assert_eq!(response.into_primary_results_checked().unwrap(), partial);
+
+        // The errors of a fragmented table are reported in its completion
+        let fragmented = load_v2_response("fragmented_partial_error.json");
+        assert_eq!(
+            fragmented.header().map(DataSetHeader::layout),
+            Some(FrameLayout::Fragmented)
+        );
+        let truncated = fragmented
+            .primary_results_checked()
+            .expect("Failed to check the primary results");
+        assert!(!truncated.is_complete());
+        assert_eq!(
+            truncated.data[0].rows,
+            vec![serde_json::json!([1, "a"]), serde_json::json!([2, "b"])]
+        );
+        assert_eq!(truncated.errors.len(), 1);
+        assert_eq!(
+            Some(&truncated.errors),
+            fragmented
+                .completion()
+                .and_then(|c| c.one_api_errors.as_ref())
+        );
 
-        let data = std::fs::read_to_string(&path)
-            .unwrap_or_else(|_| panic!("Failed to read {}", path.display()));
+        let complete = load_v2_response("twotables_same_schema.json")
+            .into_primary_results_checked()
+            .unwrap();
+        assert!(complete.is_complete());
+        assert_eq!(complete.data.len(), 2);
+    }
+
+    /// A progressive response whose table has two columns, and whose second fragment has a short row
+    const SHORT_ROW_RESPONSE: &str = r#"[
+        {"FrameType":"DataSetHeader","IsProgressive":true,"Version":"v2.0"},
+        {"FrameType":"TableHeader","TableId":1,"TableKind":"PrimaryResult","TableName":"PrimaryResult","Columns":[{"ColumnName":"x","ColumnType":"long"},{"ColumnName":"y","ColumnType":"string"}]},
+        {"FrameType":"TableFragment","TableFragmentType":"DataAppend","TableId":1,"FieldCount":2,"Rows":[[1,"a"],[2,"b"]]},
+        {"FrameType":"TableFragment","TableFragmentType":"DataAppend","TableId":1,"FieldCount":2,"Rows":[[3,"c"],[4]]},
+        {"FrameType":"TableCompletion","TableId":1,"RowCount":4},
+        {"FrameType":"DataSetCompletion","HasErrors":false,"Cancelled":false}
+    ]"#;
+
+    #[test]
+    fn short_rows_fail_the_response() {
+        let error = KustoResponseDataSetV2::from_body(
+            SHORT_ROW_RESPONSE.as_bytes(),
+            ParseOptions::default(),
+        )
+        .expect_err("Expected the short row to fail the response");
+
+        assert!(matches!(error, Error::ConversionError(_)));
+        assert_eq!(
+            error.to_string(),
+            "Error converting Kusto response for table 'PrimaryResult' (1), row 3 has 1 values but the table has 2 columns"
+        );
+
+        let long_row = r#"[{"FrameType":"DataTable","TableId":0,"TableKind":"PrimaryResult","TableName":"T","Columns":[{"ColumnName":"x","ColumnType":"long"}],"Rows":[[1],[2,3]]}]"#;
+        let padding = ParseOptions::new().with_pad_short_rows(true);
+        let error = KustoResponseDataSetV2::from_body(long_row.as_bytes(), padding)
+            .expect_err("Expected the long row to fail the response");
+        assert!(error.to_string().contains("row 1 has 2 values"));
+
+        let wrong_field_count =
+            SHORT_ROW_RESPONSE.replace(r#""FieldCount":2"#, r#""FieldCount":3"#);
+        let error = KustoResponseDataSetV2::from_body(
+            wrong_field_count.as_bytes(),
+            ParseOptions::default(),
+        )
+        .expect_err("Expected the field count to fail the response");
+        assert!(error.to_string().contains("a fragment has 3 fields"));
+    }
+
+    #[test]
+    fn short_rows_can_be_padded() {
+        let padding = ParseOptions::new().with_pad_short_rows(true);
+        let response = KustoResponseDataSetV2::from_body(SHORT_ROW_RESPONSE.as_bytes(), padding)
+            .expect("Failed to parse");
+
+        let table = response
+            .into_primary_results()
+            .next()
+            .expect("Expected a primary result")
+            .expect("Failed to parse the primary result");
+        assert_eq!(table.rows[3], serde_json::json!([4, null]));
+        assert_eq!(table.rows[2], serde_json::json!([3, "c"]));
+    }
 
-        let parsed = serde_json::from_str::<KustoResponseDataSetV1>(&data)
+    /// Asserts that the first primary table failed with the message, and that the second one was still assembled.
+    fn assert_first_table_fails(file: &str, message: &str) {
+        let response = load_v2_response(file);
+
+        let tables: Vec<Result<DataTable>> = response.primary_results().collect();
+        assert_eq!(tables.len(), 2);
+        let error = tables[0]
+            .as_ref()
+            .expect_err("Expected the first table to fail");
+        assert!(
+            error.to_string().contains(message),
+            "unexpected error: {error}"
+        );
+        let table = tables[1].as_ref().expect("Expected the second table");
+        assert_eq!(table.table_id, 2);
+        assert_eq!(table.rows.len(), 2);
+
+        let tables: Vec<DataTable> = response.into_primary_results_lossy().collect();
+        assert_eq!(tables.len(), 1);
+        assert_eq!(tables[0].table_id, 2);
+    }
+
+    #[test]
+    fn fragments_of_another_table_are_errors() {
+        assert_first_table_fails(
+            "progressive_wrong_table_id.json",
+            "table 'PrimaryResult' (1), a TableFragment frame of table 7 was found",
+        );
+    }
+
+    #[test]
+    fn wrong_row_counts_are_errors() {
+        assert_first_table_fails(
+            "progressive_wrong_row_count.json",
+            "its completion reports 4 rows but 3 were received",
+        );
+    }
+
+    #[test]
+    fn interrupted_tables_are_errors() {
+        assert_first_table_fails(
+            "progressive_interrupted_table.json",
+            "a TableHeader frame was found before the table was completed",
+        );
+    }
+
+    #[test]
+    fn truncated_tables_are_errors() {
+        let mut response = load_v2_response("twotables_progressive.json");
+        // Cut after the first fragment of the second progressive table, with a whole table received while it was
+        let whole_table = response.results.remove(11);
+        response.results.truncate(9);
+        response.results.push(whole_table);
+
+        let tables: Vec<Result<DataTable>> = response.primary_results().collect();
+        assert_eq!(tables.len(), 2);
+        assert_eq!(
+            tables[0]
+                .as_ref()
+                .expect("Expected the first table")
+                .rows
+                .len(),
+            3
+        );
+        let error = tables[1]
+            .as_ref()
+            .expect_err("Expected the truncated table to fail");
+        assert!(
+            error.to_string().contains(
+                "table 'PrimaryResult' (2), the response ended before the table was completed"
+            ),
+            "unexpected error: {error}"
+        );
+
+        let ids: Vec<Option<i32>> = response
+            .parsed_data_tables()
+            .map(|t| t.ok().map(|t| t.table_id))
+            .collect();
+        assert_eq!(ids, vec![Some(0), Some(1), None, Some(3)]);
+    }
+
+    /// The id, kind and row count of each table of the response, in order.
+    fn table_shapes(response: &KustoResponseDataSetV2) -> Vec<(i32, TableKind, usize)> {
+        response
+            .parsed_data_tables()
+            .map(|t| t.expect("Failed to parse table"))
+            .map(|t| (t.table_id, t.table_kind, t.rows.len()))
+            .collect()
+    }
+
+    #[test]
+    fn responses_are_parsed_into_their_frames() {
+        let response = load_v2_response("validFrames.json");
+
+        assert_eq!(response.raw_results_count(), 5);
+        assert!(matches!(
+            response.results.first(),
+            Some(V2QueryResult::DataSetHeader(_))
+        ));
+        assert_eq!(response.header().map(|h| h.is_progressive), Some(false));
+        assert_eq!(
+            response.completion(),
+            Some(&DataSetCompletion {
+                has_errors: false,
+                cancelled: false,
+                one_api_errors: None,
+            })
+        );
+        assert_eq!(
+            table_shapes(&response),
+            vec![
+                (0, TableKind::QueryProperties, 1),
+                (1, TableKind::PrimaryResult, 3),
+                (2, TableKind::QueryCompletionInformation, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn progressive_responses_are_parsed_into_their_frames() {
+        let response = load_v2_response("twotables_progressive.json");
+
+        assert_eq!(response.raw_results_count(), 13);
+        assert_eq!(response.header().map(|h| h.is_progressive), Some(true));
+        assert!(matches!(
+            response.results.last(),
+            Some(V2QueryResult::DataSetCompletion(_))
+        ));
+        assert_eq!(
+            table_shapes(&response),
+            vec![
+                (0, TableKind::QueryProperties, 1),
+                (1, TableKind::PrimaryResult, 3),
+                (2, TableKind::PrimaryResult, 2),
+                (3, TableKind::QueryCompletionInformation, 2),
+            ]
+        );
+
+        let truncated = KustoResponseDataSetV2 {
+            results: response.results[..6].to_vec(),
+        };
+        assert!(truncated.header().is_some());
+        assert_eq!(truncated.completion(), None);
+    }
+
+    #[test]
+    fn tables_are_selected_by_name_and_id() {
+        for (file, rows) in [
+            ("twotables_progressive.json", 2),
+            ("twotables_same_schema.json", 1),
+        ] {
+            let response = load_v2_response(file);
+
+            let completion = response
+                .table_by_name("QueryCompletionInformation")
+                .expect("Failed to assemble the tables")
+                .unwrap_or_else(|| panic!("No completion information in {file}"));
+            assert_eq!(completion.table_id, 3);
+            assert_eq!(completion.table_kind, TableKind::QueryCompletionInformation);
+            assert_eq!(completion.rows.len(), rows);
+            assert_eq!(response.table_by_id(3).unwrap(), Some(completion));
+
+            // The first of the tables with the same name is selected by name, and the others by id
+            assert_eq!(
+                response
+                    .table_by_name("PrimaryResult")
+                    .unwrap()
+                    .map(|t| t.table_id),
+                Some(1)
+            );
+            let second = response
+                .table_by_id(2)
+                .unwrap()
+                .expect("No second primary result");
+            assert_eq!(second.table_name, "PrimaryResult");
+            assert_eq!(second.rows.len(), rows);
+
+            assert_eq!(response.table_by_name("Missing").unwrap(), None);
+            assert_eq!(response.table_by_id(4).unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn primary_results_are_selected_by_index_and_name() {
+        let response = load_v2_response("twotables_progressive.json");
+
+        let first = response.primary_result_by_index(0).unwrap();
+        assert_eq!(first.table_id, 1);
+        assert_eq!(first.rows.len(), 3);
+        let second = response.primary_result_by_index(1).unwrap();
+        assert_eq!(second.table_id, 2);
+        let error = response
+            .primary_result_by_index(2)
+            .expect_err("Expected no third primary result");
+        assert!(matches!(error, Error::QueryError(msg) if msg.as_str().contains("as there are 2")));
+        assert_eq!(
+            response.clone().into_primary_result_by_index(1).unwrap(),
+            second
+        );
+
+        // Primary results are all named PrimaryResult unless the query names them
+        assert_eq!(
+            response.primary_result_by_name("PrimaryResult").unwrap(),
+            first
+        );
+        assert!(response
+            .primary_result_by_name("QueryCompletionInformation")
+            .is_err());
+        let mut frames: Vec<serde_json::Value> = response
+            .results
+            .iter()
+            .map(|r| serde_json::to_value(r).unwrap())
+            .collect();
+        frames[2]["TableName"] = serde_json::json!("Storms");
+        let named = KustoResponseDataSetV2 {
+            results: serde_json::from_value(serde_json::Value::Array(frames)).unwrap(),
+        };
+        assert_eq!(named.primary_result_by_name("Storms").unwrap().table_id, 1);
+        assert_eq!(
+            named.primary_result_by_name("PrimaryResult").unwrap(),
+            second
+        );
+        assert_eq!(
+            named.into_primary_result_by_name("Storms").unwrap().rows,
+            first.rows
+        );
+
+        // A table that can't be assembled may be the one asked for
+        let error = load_v2_response("progressive_wrong_row_count.json")
+            .primary_result_by_name("Missing")
+            .expect_err("Expected the table that failed");
+        assert!(error.to_string().contains("its completion reports 4 rows"));
+        assert!(single_table(response.primary_results()).is_err());
+    }
+
+    /// A progressive table whose rows are spread over many small fragments, the first `replaced` of which are replaced.
+    fn fragmented_response(fragments: usize, replaced: usize) -> KustoResponseDataSetV2 {
+        use crate::models::{Column, ColumnType, TableCompletion, TableFragment, TableHeader};
+
+        let mut results = vec![V2QueryResult::TableHeader(TableHeader {
+            table_id: 1,
+            table_name: "PrimaryResult".to_string(),
+            table_kind: TableKind::PrimaryResult,
+            columns: vec![Column {
+                column_name: "x".to_string(),
+                column_type: ColumnType::Long,
+            }],
+        })];
+        results.extend((0..fragments).map(|i| {
+            V2QueryResult::TableFragment(TableFragment {
+                table_id: 1,
+                field_count: Some(1),
+                table_fragment_type: if i == replaced {
+                    TableFragmentType::DataReplace
+                } else {
+                    TableFragmentType::DataAppend
+                },
+                rows: vec![serde_json::json!([i]), serde_json::json!([i])],
+            })
+        }));
+        results.push(V2QueryResult::TableCompletion(TableCompletion {
+            table_id: 1,
+            row_count: ((fragments - replaced) * 2) as i32,
+            one_api_errors: None,
+        }));
+        KustoResponseDataSetV2 { results }
+    }
+
+    #[test]
+    fn primary_rows_are_counted_once_per_table() {
+        let mut response = fragmented_response(3, 0);
+        response.results.extend([
+            V2QueryResult::DataTable(DataTable {
+                table_id: 2,
+                table_name: "PrimaryResult".to_string(),
+                table_kind: TableKind::PrimaryResult,
+                columns: vec![],
+                rows: vec![serde_json::json!([1])],
+            }),
+            V2QueryResult::TableCompletion(crate::models::TableCompletion::new(2, 1)),
+            V2QueryResult::DataTable(DataTable {
+                table_id: 3,
+                table_name: "QueryCompletionInformation".to_string(),
+                table_kind: TableKind::QueryCompletionInformation,
+                columns: vec![],
+                rows: vec![serde_json::json!([1]), serde_json::json!([2])],
+            }),
+        ]);
+
+        assert_eq!(KustoResponse::V2(response).primary_row_count(), 7);
+    }
+
+    /// The addresses of the values of the rows, which stay the same when the rows are moved but not when they are cloned.
+    fn row_addresses<'a>(rows: impl Iterator<Item = &'a serde_json::Value>) -> Vec<usize> {
+        rows.map(|row| row.as_array().expect("Expected an array").as_ptr() as usize)
+            .collect()
+    }
+
+    #[test]
+    fn consumed_responses_move_the_rows_of_fragments() {
+        let response = fragmented_response(1000, 10);
+        let fragment_rows = row_addresses(
+            response.results[11..]
+                .iter()
+                .filter_map(|result| match result {
+                    V2QueryResult::TableFragment(fragment) => Some(&fragment.rows),
+                    _ => None,
+                })
+                .flatten(),
+        );
+        assert_eq!(fragment_rows.len(), 1980);
+
+        let borrowed = response
+            .parsed_data_tables()
+            .next()
+            .expect("Expected a table")
+            .expect("Failed to assemble the table");
+        assert_eq!(borrowed.rows.len(), 1980);
+        assert!(row_addresses(borrowed.rows.iter())
+            .iter()
+            .zip(&fragment_rows)
+            .all(|(cloned, original)| cloned != original));
+
+        let consumed = response
+            .into_parsed_data_tables()
+            .next()
+            .expect("Expected a table")
+            .expect("Failed to assemble the table");
+        assert_eq!(row_addresses(consumed.rows.iter()), fragment_rows);
+        assert_eq!(consumed, borrowed);
+    }
+
+    fn strict_options() -> ParseOptions {
+        ParseOptions::new().with_deny_unknown_frame_fields(true)
+    }
+
+    /// Fixtures with fields that the models intentionally don't have, to test their detection.
+    const EXTENDED_FIXTURES: &[&str] = &["unknown_frame_fields.json"];
+
+    #[test]
+    fn fixtures_have_no_unknown_frame_fields() {
+        let mut checked = 0;
+        for entry in std::fs::read_dir(fixture_path("")).expect("Failed to list the fixtures") {
+            let path = entry.expect("Failed to list the fixtures").path();
+            let name = path.file_name().unwrap().to_string_lossy().to_string();
+            let data = std::fs::read(&path).expect("Failed to read fixture");
+            // V2 responses are arrays of frames, while V1 responses are objects
+            if !serde_json::from_slice::<serde_json::Value>(&data).map_or(false, |v| v.is_array()) {
+                continue;
+            }
+
+            let result = KustoResponseDataSetV2::from_body(&data, strict_options());
+            if EXTENDED_FIXTURES.contains(&name.as_str()) {
+                assert!(result.is_err(), "{name} should have unknown fields");
+            } else if let Err(e) = result {
+                panic!("{name}: {e}");
+            }
+            checked += 1;
+        }
+        assert!(checked > EXTENDED_FIXTURES.len());
+    }
+
+    #[test]
+    fn unknown_frame_fields_are_listed() {
+        let data = read_fixture("unknown_frame_fields.json");
+
+        let error = KustoResponseDataSetV2::from_body(&data, strict_options())
+            .expect_err("Expected the unknown fields to fail the response")
+            .to_string();
+        assert!(
+            error.contains("DataTable.Columns[1].IsNullable (frame 1)"),
+            "IsNullable is missing from: {error}"
+        );
+        assert!(!error.contains("DataSetHeader"));
+        assert!(!error.contains("FrameType"));
+        assert!(!error.contains("ColumnType"));
+
+        // By default, unknown fields are ignored
+        let response = KustoResponseDataSetV2::from_body(&data, ParseOptions::default())
+            .expect("Failed to parse");
+        let header = response.header().expect("Expected a header");
+        assert!(!header.is_progressive);
+        assert_eq!(header.is_fragmented, Some(true));
+        assert_eq!(
+            header.error_reporting_placement,
+            Some(ErrorReportingPlacement::EndOfTable)
+        );
+        assert_eq!(header.layout(), FrameLayout::Fragmented);
+
+        // Errors reported at the end of each table need their completions, even when the header doesn't say it is fragmented
+        let mut header = header.clone();
+        header.is_fragmented = None;
+        assert_eq!(header.layout(), FrameLayout::Fragmented);
+        header.error_reporting_placement = Some(ErrorReportingPlacement::EndOfDataSet);
+        assert_eq!(header.layout(), FrameLayout::Tables);
+    }
+
+    /// Lays out the frames one per line, as the service sends them.
+    fn line_delimited(frames: &str) -> String {
+        let frames: Vec<serde_json::Value> = serde_json::from_str(frames).unwrap();
+        let frames: Vec<String> = frames.iter().map(|f| f.to_string()).collect();
+        format!("[\n{}\n]", frames.join("\n,"))
+    }
+
+    #[tokio::test]
+    async fn read_bodies_are_parsed_like_whole_ones() {
+        let data = read_fixture("twotables_progressive.json");
+
+        let whole = KustoResponseDataSetV2::from_body(&data, ParseOptions::default())
+            .expect("Failed to parse");
+        let read =
+            KustoResponseDataSetV2::from_async_buf_read(data.as_slice(), ParseOptions::default())
+                .await
+                .expect("Failed to parse");
+        assert_eq!(read.results, whole.results);
+
+        let short_rows = line_delimited(SHORT_ROW_RESPONSE);
+        let error = KustoResponseDataSetV2::from_async_buf_read(
+            short_rows.as_bytes(),
+            ParseOptions::default(),
+        )
+        .await
+        .expect_err("Expected the short row to fail the response");
+        assert!(error.to_string().contains("row 3 has 1 values"));
+
+        let unknown_fields =
+            line_delimited(&String::from_utf8(read_fixture("unknown_frame_fields.json")).unwrap());
+        let error = KustoResponseDataSetV2::from_async_buf_read(
+            unknown_fields.as_bytes(),
+            strict_options(),
+        )
+        .await
+        .expect_err("Expected the unknown fields to fail the response")
+        .to_string();
+        assert!(error.contains("DataTable.Columns[1].IsNullable (frame 1)"));
+    }
+
+    #[tokio::test]
+    async fn legacy_progressive_responses_are_assembled_alike() {
+        let data = String::from_utf8(read_fixture("legacy_progressive.json")).unwrap();
+
+        // The query properties arrive while the first table is received, and the query completion information while the second one is
+        let mut frames: Vec<serde_json::Value> = serde_json::from_str(&data).unwrap();
+        frames.remove(4);
+        let in_order = serde_json::to_string(&frames).unwrap();
+
+        for (body, expected) in [
+            (data.as_str(), vec![0, 1, 2, 3]),
+            (in_order.as_str(), vec![1, 2, 3]),
+        ] {
+            let whole = KustoResponseDataSetV2::from_body(body.as_bytes(), ParseOptions::default())
+                .expect("Failed to parse");
+            assert_eq!(
+                whole.header().map(DataSetHeader::layout),
+                Some(FrameLayout::Progressive)
+            );
+            let read = KustoResponseDataSetV2::from_async_buf_read(
+                line_delimited(body).as_bytes(),
+                ParseOptions::default(),
+            )
+            .await
+            .expect("Failed to parse");
+
+            let tables: Vec<DataTable> = whole
+                .parsed_data_tables()
+                .collect::<Result<_>>()
+                .expect("Failed to assemble the tables");
+            assert_eq!(
+                tables.iter().map(|t| t.table_id).collect::<Vec<_>>(),
+                expected
+            );
+            let read_tables: Vec<DataTable> = read
+                .into_parsed_data_tables()
+                .collect::<Result<_>>()
+                .expect("Failed to assemble the tables");
+            assert_eq!(read_tables, tables);
+
+            let primary: Vec<&DataTable> = tables
+                .iter()
+                .filter(|t| t.table_kind == TableKind::PrimaryResult)
+                .collect();
+            assert_eq!(
+                primary[0].rows,
+                (1..=4).map(|x| serde_json::json!([x])).collect::<Vec<_>>()
+            );
+            assert_eq!(
+                primary[1].rows,
+                vec![serde_json::json!(["b"]), serde_json::json!(["c"])]
+            );
+        }
+
+        // Outside of the progressive layout, tables can't be interleaved
+        let fragmented = in_order.replace(
+            r#""IsProgressive":true"#,
+            r#""IsProgressive":false,"IsFragmented":true"#,
+        );
+        let response =
+            KustoResponseDataSetV2::from_body(fragmented.as_bytes(), ParseOptions::default())
+                .expect("Failed to parse");
+        let error = response
+            .parsed_data_tables()
+            .nth(1)
+            .expect("Expected a second table")
+            .expect_err("Expected the interleaved table to fail the second one");
+        assert!(error
+            .to_string()
+            .contains("a DataTable frame was found before the table was completed"));
+
+        // Looking up a table after the one that fails returns its error rather than skipping it
+        assert_eq!(
+            response.table_by_id(1).unwrap().map(|t| t.table_id),
+            Some(1)
+        );
+        assert!(response.table_by_id(3).is_err());
+    }
+
+    #[tokio::test]
+    async fn large_bodies_are_parsed_as_they_are_read() {
+        const TABLES: usize = 200;
+        const ROWS: usize = 500;
+        let columns =
+            r#"[{"ColumnName":"x","ColumnType":"long"},{"ColumnName":"y","ColumnType":"string"}]"#;
+
+        // The body is generated a frame at a time as it is read, and never exists as a whole
+        let frames = std::iter::once(
+            "[\n{\"FrameType\":\"DataSetHeader\",\"IsProgressive\":false,\"Version\":\"v2.0\"}\n"
+                .to_string(),
+        )
+        .chain((0..TABLES).map(move |table| {
+            let rows: Vec<String> = (0..ROWS)
+                .map(|row| format!(r#"[{row},"row {row} of table {table}"]"#))
+                .collect();
+            format!(
+                r#",{{"FrameType":"DataTable","TableId":{table},"TableKind":"PrimaryResult","TableName":"T{table}","Columns":{columns},"Rows":[{}]}}"#,
+                rows.join(",")
+            ) + "\n"
+        }))
+        .chain(std::iter::once(
+            ",{\"FrameType\":\"DataSetCompletion\",\"HasErrors\":false,\"Cancelled\":false}\n]"
+                .to_string(),
+        ));
+        let reader =
+            futures::stream::iter(frames.map(|frame| Ok::<_, std::io::Error>(frame.into_bytes())))
+                .into_async_read();
+
+        let response = KustoResponseDataSetV2::from_async_buf_read(reader, ParseOptions::default())
+            .await
+            .expect("Failed to parse");
+        assert_eq!(response.raw_results_count(), TABLES + 2);
+        let tables: Vec<DataTable> = response
+            .into_primary_results()
+            .collect::<Result<_>>()
+            .expect("Failed to parse the primary results");
+        assert_eq!(tables.len(), TABLES);
+        assert!(tables.iter().all(|t| t.rows.len() == ROWS));
+        assert_eq!(
+            tables[TABLES - 1].rows[ROWS - 1],
+            serde_json::json!([
+                ROWS - 1,
+                format!("row {} of table {}", ROWS - 1, TABLES - 1)
+            ])
+        );
+    }
+
+    #[test]
+    fn merging_fails_on_tables_that_cannot_be_assembled() {
+        let error = load_v2_response("progressive_wrong_row_count.json")
+            .merge_primary_results()
+            .expect_err("Expected the merge to fail");
+        assert!(error.to_string().contains("reports 4 rows"));
+    }
+
+    #[test]
+    fn primary_results_with_different_columns_are_not_merged() {
+        let response = load_v2_response("twotables_progressive.json");
+
+        let error = response
+            .merge_primary_results()
+            .expect_err("Expected the schemas to differ");
+        assert!(matches!(error, Error::ConversionError(_)));
+        assert!(error.to_string().contains("(y: String)"));
+        assert!(error.to_string().contains("(x: Long)"));
+
+        let empty = KustoResponseDataSetV2 { results: vec![] };
+        assert!(matches!(
+            empty.merge_primary_results(),
+            Err(Error::NoPrimaryResult)
+        ));
+    }
+
+    #[test]
+    fn single_primary_result() {
+        let error = KustoResponseDataSetV2 { results: vec![] }
+            .single_primary_result()
+            .expect_err("Expected a response without tables to fail");
+        assert!(matches!(error, Error::NoPrimaryResult));
+
+        let response = load_v2_response("validFrames.json");
+        let table = response
+            .single_primary_result()
+            .expect("Failed to get the primary result");
+        assert_eq!(table.table_kind, TableKind::PrimaryResult);
+        assert_eq!(
+            response
+                .into_single_primary_result()
+                .expect("Failed to get the primary result"),
+            table
+        );
+
+        let error = load_v2_response("twotables_progressive.json")
+            .into_single_primary_result()
+            .expect_err("Expected several primary results to fail");
+        assert!(matches!(error, Error::SeveralPrimaryResults(2)));
+    }
+
+    #[test]
+    fn v1_exceptions_fail_the_response() {
+        let data = read_fixture("v1_partial_failure.json");
+
+        let result = KustoResponseDataSetV1::from_body(StatusCode::Ok, &data, None);
+
+        let errors = match result {
+            Err(Error::MultipleErrors(errors)) => errors,
+            other => panic!("Expected multiple errors, got {other:?}"),
+        };
+        assert_eq!(errors.len(), 2);
+        assert!(
+            matches!(&errors[0], Error::QueryError(msg) if msg.as_str().contains("80DA0007")),
+            "{:?}",
+            errors[0]
+        );
+        match &errors[1] {
+            Error::QueryApiError(error) => {
+                assert_eq!(error.error_message.code, "LimitsExceeded");
+                assert_eq!(error.error_message.is_permanent, Some(true));
+                assert!(error
+                    .error_message
+                    .description
+                    .as_deref()
+                    .expect("Expected a description")
+                    .contains("80DA0003"));
+            }
+            other => panic!("Expected a query api error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn v1_exceptions_can_be_deferred() {
+        let data = read_fixture("v1_partial_failure.json");
+
+        let properties = ClientRequestProperties {
+            defer_v1_exceptions: Some(true),
+            ..Default::default()
+        };
+        let parsed = KustoResponseDataSetV1::from_body(StatusCode::Ok, &data, Some(&properties))
             .expect("Failed to parse response");
-        assert_eq!(parsed.table_count(), 4);
+
+        let table = &parsed.tables[0];
+        assert_eq!(table.rows.len(), 2);
+        assert_eq!(table.exceptions.as_ref().map(Vec::len), Some(1));
+        assert_eq!(parsed.exceptions.as_ref().map(Vec::len), Some(1));
+        assert_eq!(parsed.errors().len(), 2);
+    }
+
+    #[test]
+    fn v1_object_rows_are_reordered_by_column() {
+        let data = read_fixture("v1_object_rows.json");
+
+        let parsed = KustoResponseDataSetV1::from_body(StatusCode::Ok, &data, None)
+            .expect("Failed to parse response");
+
+        assert_eq!(
+            parsed.tables[0].rows,
+            vec![
+                vec![
+                    serde_json::json!("SecuredReadyForAggregationQueue"),
+                    serde_json::json!("https://account.queue.core.windows.net/queue1"),
+                    serde_json::json!(1)
+                ],
+                vec![
+                    serde_json::json!("TempStorage"),
+                    serde_json::json!("https://account.blob.core.windows.net/container1"),
+                    serde_json::Value::Null
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn v1_mixed_rows_with_unknown_columns_depend_on_parsing_mode() {
+        let data = read_fixture("v1_mixed_rows.json");
+
+        let strict = KustoResponseDataSetV1::from_body(StatusCode::Ok, &data, None);
+        assert!(
+            matches!(&strict, Err(Error::JsonError(e)) if e.to_string().contains("Region")),
+            "{strict:?}"
+        );
+
+        let properties = ClientRequestProperties {
+            v1_parsing_mode: Some(V1ParsingMode::Lenient),
+            ..Default::default()
+        };
+        let lenient = KustoResponseDataSetV1::from_body(StatusCode::Ok, &data, Some(&properties))
+            .expect("Failed to parse response");
+
+        assert_eq!(lenient.table_count(), 2);
+        assert_eq!(
+            lenient.tables[0].rows[1],
+            vec![
+                serde_json::json!("TempStorage"),
+                serde_json::json!("https://account.blob.core.windows.net/container1"),
+                serde_json::json!(2)
+            ]
+        );
+        assert_eq!(
+            lenient.tables[1].rows,
+            vec![vec![serde_json::json!("positional")]]
+        );
+    }
+
+    #[test]
+    fn v1_error_bodies_are_surfaced() {
+        let error = r#"{"error":{"code":"BadRequest","message":"Syntax error","@permanent":true}}"#;
+        let result = KustoResponseDataSetV1::from_body(StatusCode::Ok, error.as_bytes(), None);
+        assert!(
+            matches!(result, Err(Error::QueryApiError(e)) if e.error_message.code == "BadRequest")
+        );
+
+        let result =
+            KustoResponseDataSetV1::from_body(StatusCode::BadGateway, b"upstream timed out", None);
+        assert!(
+            matches!(result, Err(Error::HttpError(StatusCode::BadGateway, body)) if body.as_str() == "upstream timed out")
+        );
+    }
+
+    #[test]
+    fn long_error_bodies_are_truncated_on_char_boundaries() {
+        // The limit of 4096 bytes falls in the middle of an emoji, which is dropped.
+        let body = format!("ab{}", "שגיאה 😀 ".repeat(1000));
+        let result =
+            KustoResponseDataSetV1::from_body(StatusCode::BadGateway, body.as_bytes(), None);
+
+        match result {
+            Err(Error::HttpError(StatusCode::BadGateway, truncated)) => {
+                assert_eq!(truncated.as_str().len(), 4093);
+                assert!(body.starts_with(truncated.as_str()));
+            }
+            other => panic!("Expected an http error, got {other:?}"),
+        }
+    }
+
+    /// Answers each query with a table holding the text of the query it received.
+    #[derive(Debug)]
+    struct EchoTransport;
+
+    #[async_trait::async_trait]
+    impl azure_core::Policy for EchoTransport {
+        async fn send(
+            &self,
+            _ctx: &Context,
+            request: &mut Request,
+            _next: &[Arc<dyn azure_core::Policy>],
+        ) -> azure_core::PolicyResult {
+            let body = match request.body() {
+                azure_core::Body::Bytes(bytes) => bytes.clone(),
+                _ => panic!("Expected a buffered request body"),
+            };
+            let query: serde_json::Value =
+                serde_json::from_slice(&body).expect("Request body is not valid JSON");
+            if query["csl"] == "fail" {
+                return Ok(Response::new(
+                    StatusCode::BadRequest,
+                    Headers::new(),
+                    Box::pin(azure_core::BytesStream::new("Syntax error")),
+                ));
+            }
+
+            let response = serde_json::json!([
+                { "FrameType": "DataSetHeader", "IsProgressive": false, "Version": "v2.0" },
+                {
+                    "FrameType": "DataTable",
+                    "TableId": 1,
+                    "TableName": "PrimaryResult",
+                    "TableKind": "PrimaryResult",
+                    "Columns": [{ "ColumnName": "csl", "ColumnType": "string" }],
+                    "Rows": [[query["csl"]]]
+                },
+                { "FrameType": "DataSetCompletion", "HasErrors": false, "Cancelled": false }
+            ]);
+
+            Ok(Response::new(
+                StatusCode::Ok,
+                Headers::new(),
+                Box::pin(azure_core::BytesStream::new(
+                    serde_json::to_vec(&response).expect("Failed to serialize response"),
+                )),
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn queries_with_multi_byte_and_control_characters_round_trip() {
+        let client = KustoClient::new(
+            ConnectionString::with_no_auth("https://mycluster.kusto.windows.net"),
+            KustoClientOptions::from(azure_core::ClientOptions::default().transport(
+                azure_core::TransportOptions::new_custom_policy(Arc::new(EchoTransport)),
+            )),
+        )
+        .expect("Failed to create client");
+
+        for query in [
+            "print s = '😀🏳️‍🌈𝔘𝔫𝔦𝔠𝔬𝔡𝔢'",
+            "print s = 'שלום עולם' // right to left",
+            "print s = 'cafe\u{301}'",
+            "print s = 'tab\there\u{0}\u{1b}[0m\u{7f}'",
+        ] {
+            let response = client
+                .execute_query("db", query, None)
+                .await
+                .expect("Failed to execute query");
+            let table = response
+                .into_primary_results()
+                .next()
+                .expect("Expected a primary result")
+                .expect("Failed to parse the primary result");
+
+            assert_eq!(table.rows, vec![serde_json::json!([query])]);
+        }
+    }
+
+    /// Collects the fields of every span, by span name.
+    #[cfg(feature = "tracing")]
+    #[derive(Clone, Default)]
+    #[allow(clippy::type_complexity)]
+    struct SpanCollector(Arc<std::sync::Mutex<HashMap<u64, (String, HashMap<String, String>)>>>);
+
+    #[cfg(feature = "tracing")]
+    impl SpanCollector {
+        fn fields(&self, name: &str) -> HashMap<String, String> {
+            self.0
+                .lock()
+                .unwrap()
+                .values()
+                .find(|(span, _)| span == name)
+                .map(|(_, fields)| fields.clone())
+                .unwrap_or_else(|| panic!("Span {name} was not recorded"))
+        }
+
+        fn record(&self, id: &tracing::span::Id, name: &str, values: &tracing::span::Record<'_>) {
+            struct Visitor<'a>(&'a mut HashMap<String, String>);
+
+            impl tracing::field::Visit for Visitor<'_> {
+                fn record_debug(
+                    &mut self,
+                    field: &tracing::field::Field,
+                    value: &dyn std::fmt::Debug,
+                ) {
+                    self.0
+                        .insert(field.name().to_string(), format!("{value:?}"));
+                }
+            }
+
+            let mut spans = self.0.lock().unwrap();
+            let (_, fields) = spans
+                .entry(id.into_u64())
+                .or_insert_with(|| (name.to_string(), HashMap::new()));
+            values.record(&mut Visitor(fields));
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for SpanCollector {
+        fn on_new_span(
+            &self,
+            attrs: &tracing::span::Attributes<'_>,
+            id: &tracing::span::Id,
+            _ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            self.record(
+                id,
+                attrs.metadata().name(),
+                &tracing::span::Record::new(attrs.values()),
+            );
+        }
+
+        fn on_record(
+            &self,
+            id: &tracing::span::Id,
+            values: &tracing::span::Record<'_>,
+            _ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            self.record(id, "", values);
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    #[tokio::test]
+    async fn queries_are_traced_without_their_text() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let collector = SpanCollector::default();
+        let _guard = tracing::subscriber::set_default(
+            tracing_subscriber::registry().with(collector.clone()),
+        );
+
+        let client = KustoClient::new(
+            ConnectionString::with_no_auth("https://mycluster.kusto.windows.net"),
+            KustoClientOptions::from(azure_core::ClientOptions::default().transport(
+                azure_core::TransportOptions::new_custom_policy(Arc::new(EchoTransport)),
+            )),
+        )
+        .expect("Failed to create client");
+        let properties = ClientRequestProperties {
+            client_request_id: Some("KRust;tracing-test".to_string()),
+            ..ClientRequestProperties::default()
+        };
+
+        client
+            .execute_query("db", "print secret = 'hunter2'", Some(properties))
+            .await
+            .expect("Failed to execute query");
+
+        let fields = collector.fields("kusto.query");
+        assert_eq!(fields["database"], "db");
+        assert_eq!(fields["client_request_id"], "\"KRust;tracing-test\"");
+        assert_eq!(fields["status_code"], "200");
+        assert_eq!(fields["rows"], "1");
+        assert!(fields.contains_key("query_hash"));
+        assert!(!fields.contains_key("error"));
+        assert!(fields.values().all(|value| !value.contains("hunter2")));
+        assert_eq!(fields["otel.kind"], "\"client\"");
+        assert_eq!(fields["db.system"], "\"kusto\"");
+        assert!(!fields.contains_key("otel.status_code"));
+    }
+
+    #[cfg(feature = "tracing")]
+    fn echo_client() -> KustoClient {
+        KustoClient::new(
+            ConnectionString::with_no_auth("https://mycluster.kusto.windows.net"),
+            KustoClientOptions::from(
+                azure_core::ClientOptions::default()
+                    .retry(azure_core::RetryOptions::none())
+                    .transport(azure_core::TransportOptions::new_custom_policy(Arc::new(
+                        EchoTransport,
+                    ))),
+            ),
+        )
+        .expect("Failed to create client")
+    }
+
+    #[cfg(feature = "tracing")]
+    #[tokio::test]
+    async fn commands_streams_and_failures_are_traced() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let traced = |collector: &SpanCollector| {
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(collector.clone()))
+        };
+        let client = echo_client();
+
+        let command = SpanCollector::default();
+        {
+            let _guard = traced(&command);
+            client
+                .execute_command("db", ".show version", None)
+                .await
+                .expect_err("Expected the echoed V2 response to fail as a V1 response");
+        }
+        let fields = command.fields("kusto.query");
+        assert_eq!(fields["kind"], "Management");
+        assert_eq!(fields["status_code"], "200");
+
+        let failed = SpanCollector::default();
+        {
+            let _guard = traced(&failed);
+            client
+                .execute_query("db", "fail", None)
+                .await
+                .expect_err("Expected the query to fail");
+        }
+        let fields = failed.fields("kusto.query");
+        assert_eq!(fields["kind"], "Query");
+        assert_eq!(fields["otel.status_code"], "\"ERROR\"");
+        assert!(fields.contains_key("error"));
+        assert!(!fields.contains_key("rows"));
+
+        let streamed = SpanCollector::default();
+        {
+            let _guard = traced(&streamed);
+            let frames: Vec<V2QueryResult> = client
+                .execute_query("db", "print 1", None)
+                .into_stream()
+                .await
+                .expect("Failed to start the stream")
+                .try_collect()
+                .await
+                .expect("Failed to read the stream");
+            assert_eq!(frames.len(), 3);
+        }
+        let fields = streamed.fields("kusto.query");
+        assert_eq!(fields["status_code"], "200");
+        assert!(!fields.contains_key("error"));
+    }
+
+    /// Answers commands with the show databases fixture, and queries with the two tables fixture, both gzip compressed.
+    #[derive(Debug)]
+    struct GzipTransport;
+
+    #[async_trait::async_trait]
+    impl azure_core::Policy for GzipTransport {
+        async fn send(
+            &self,
+            _ctx: &Context,
+            request: &mut Request,
+            _next: &[Arc<dyn azure_core::Policy>],
+        ) -> azure_core::PolicyResult {
+            use std::io::Write;
+
+            let fixture = if request.url().path().ends_with("/v1/rest/mgmt") {
+                "show_databases.json"
+            } else {
+                "twotables_progressive.json"
+            };
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(&read_fixture(fixture)).unwrap();
+            let mut headers = Headers::new();
+            headers.insert(azure_core::headers::CONTENT_ENCODING, "gzip");
+            Ok(Response::new(
+                StatusCode::Ok,
+                headers,
+                Box::pin(azure_core::BytesStream::new(encoder.finish().unwrap())),
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn gzipped_responses_are_decompressed() {
+        let client = KustoClient::new(
+            ConnectionString::with_no_auth("https://mycluster.kusto.windows.net"),
+            KustoClientOptions::from(azure_core::ClientOptions::default().transport(
+                azure_core::TransportOptions::new_custom_policy(Arc::new(GzipTransport)),
+            )),
+        )
+        .expect("Failed to create client");
+        let expected = load_v2_response("twotables_progressive.json");
+
+        let buffered = client
+            .execute_query("db", "print 1", None)
+            .await
+            .expect("Failed to read the gzipped response");
+        assert_eq!(buffered.results, expected.results);
+
+        let streamed: Vec<V2QueryResult> = client
+            .execute_query("db", "print 1", None)
+            .into_stream()
+            .await
+            .expect("Failed to start the stream")
+            .try_collect()
+            .await
+            .expect("Failed to read the gzipped stream");
+        let frames: Vec<serde_json::Value> =
+            serde_json::from_slice(&read_fixture("twotables_progressive.json")).unwrap();
+        assert_eq!(streamed.len(), frames.len());
+
+        let databases = client
+            .execute_command("db", ".show databases", None)
+            .await
+            .expect("Failed to read the gzipped command response");
+        assert_eq!(databases.tables[0].rows.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn frames_without_newlines_are_parsed_like_those_with_them() {
+        let with_newlines = read_fixture("twotables_progressive.json");
+        let without_newlines = read_fixture("twotables_progressive_no_newlines.json");
+        assert!(!without_newlines.contains(&b'\n'));
+
+        let streamed: Vec<V2QueryResult> =
+            async_deserializer::iter_results(without_newlines.as_slice())
+                .try_collect()
+                .await
+                .expect("Failed to stream the frames without newlines");
+        let expected: Vec<V2QueryResult> =
+            async_deserializer::iter_results(with_newlines.as_slice())
+                .try_collect()
+                .await
+                .expect("Failed to stream the frames with newlines");
+        assert_eq!(streamed, expected);
+
+        let parsed = KustoResponseDataSetV2::from_async_buf_read(
+            without_newlines.as_slice(),
+            ParseOptions::default(),
+        )
+        .await
+        .expect("Failed to parse the frames without newlines");
+        assert_eq!(
+            parsed.results,
+            load_v2_response("twotables_progressive.json").results
+        );
+    }
+
+    #[tokio::test]
+    async fn management_queries_cannot_be_streamed() {
+        let client = KustoClient::new(
+            ConnectionString::with_token_auth("https://mycluster.kusto.windows.net", "token"),
+            KustoClientOptions::default(),
+        )
+        .expect("Failed to create client");
+
+        let result = client
+            .execute_either("db", ".show version", None)
+            .into_stream()
+            .await;
+
+        assert!(matches!(result, Err(Error::UnsupportedOperation(_))));
     }
 }
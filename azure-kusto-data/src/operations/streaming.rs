@@ -0,0 +1,1189 @@
+//! Progressive query responses, assembled into tables by a background task.
+//! Requires the `tokio` feature, as the task is spawned on the tokio runtime.
+
+use crate::error::{Error, Result};
+use crate::instrumentation::{record_error, span, Instrument, Span};
+use crate::models::{
+    check_row_widths, DataSetCompletion, DataSetHeader, DataTable, OneApiError, TableFragmentType,
+    TableKind, V2QueryResult,
+};
+use futures::{Future, Stream, TryStreamExt};
+use serde_json::Value;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context as TaskContext, Poll};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{mpsc, watch, Semaphore};
+use tokio::task::JoinHandle;
+
+/// A progressive query response, that yields each primary result table as soon as it has been fully received.
+///
+/// Tables are yielded in ascending order of their [table_id](DataTable::table_id), which is the order of the statements of the query,
+/// as the service sends them. As tables are yielded before the rest of the response is received, they can't be reordered,
+/// so a primary table that is received after one with a higher id fails the stream instead.
+///
+/// The response is read by a background task, so the dataset header and completion are delivered even if the tables are not consumed.
+/// Tables that were received are buffered until they are read from the stream. Dropping the dataset stops the task,
+/// and the rest of the response is not read.
+/// Await the header and completion with [header](#method.header) and [completion](#method.completion),
+/// or check whether they were already received with [try_header](#method.try_header) and [try_completion](#method.try_completion).
+/// Once the response was read, a [StreamSummary] of it is returned by [finish](#method.finish) and [for_each_table](#method.for_each_table).
+///
+/// At most [DEFAULT_TABLE_CAPACITY] tables are buffered, unless another capacity is set with
+/// [KustoClientOptions::with_streaming_table_capacity](crate::client::KustoClientOptions::with_streaming_table_capacity).
+/// Consumers that are slower than the response can watch how far behind they are with [metrics](#method.metrics),
+/// or be called back as the buffer fills up and drains with [with_watermarks](#method.with_watermarks).
+pub struct StreamingDataset {
+    header: watch::Receiver<Option<DataSetHeader>>,
+    completion: watch::Receiver<Option<DataSetCompletion>>,
+    summary: watch::Receiver<Option<StreamSummary>>,
+    tables: mpsc::Receiver<TableResult>,
+    backpressure: Arc<Backpressure>,
+    /// The error that ended the response, until it is yielded by the stream.
+    failure: Arc<Mutex<Option<Arc<Error>>>>,
+    /// The task that reads the response, stopped when the dataset is dropped.
+    task: JoinHandle<()>,
+}
+
+/// A table sent by the task that reads the response, or the error that ended it, shared with the waiters of the header and completion.
+type TableResult = std::result::Result<DataTable, Arc<Error>>;
+
+/// How far the consumer of a [StreamingDataset] is behind the response, as returned by [StreamingDataset::metrics].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StreamMetrics {
+    /// The number of tables that were received and are waiting to be read from the stream.
+    pub queued_tables: usize,
+    /// The number of times the reading of the response waited for a table to be consumed, as the buffer of tables was full.
+    pub producer_stalls: u64,
+}
+
+/// A threshold of the number of queued tables of a [StreamingDataset], crossed as set by [StreamingDataset::with_watermarks].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Watermark {
+    /// The queued tables reached the high watermark.
+    High,
+    /// The queued tables went back down to the low watermark, after reaching the high one.
+    Low,
+}
+
+type WatermarkCallback = Box<dyn Fn(Watermark, StreamMetrics) + Send + Sync>;
+
+struct Watermarks {
+    high: usize,
+    low: usize,
+    above_high: bool,
+    callback: WatermarkCallback,
+}
+
+/// The state of the buffer of tables, shared by the dataset and the task that reads the response.
+#[derive(Default)]
+struct Backpressure {
+    queued: AtomicUsize,
+    peak: AtomicUsize,
+    stalls: AtomicU64,
+    watermarks: Mutex<Option<Watermarks>>,
+}
+
+impl Backpressure {
+    fn metrics(&self) -> StreamMetrics {
+        StreamMetrics {
+            queued_tables: self.queued.load(Ordering::SeqCst),
+            producer_stalls: self.stalls.load(Ordering::SeqCst),
+        }
+    }
+
+    /// Counts a table that is about to be sent, and calls back if it reaches the high watermark.
+    fn enqueued(&self) {
+        let queued = self.queued.fetch_add(1, Ordering::SeqCst) + 1;
+        self.peak.fetch_max(queued, Ordering::SeqCst);
+        self.notify(|w| !w.above_high && queued >= w.high, true);
+    }
+
+    /// Counts a table that was read, and calls back if it brings the queue down to the low watermark.
+    fn dequeued(&self) {
+        let queued = self.queued.fetch_sub(1, Ordering::SeqCst) - 1;
+        self.notify(|w| w.above_high && queued <= w.low, false);
+    }
+
+    fn notify(&self, crossed: impl FnOnce(&Watermarks) -> bool, above_high: bool) {
+        let mut watermarks = self
+            .watermarks
+            .lock()
+            .expect("The watermarks lock was poisoned by a panicking callback");
+        if let Some(watermarks) = watermarks.as_mut().filter(|w| crossed(w)) {
+            watermarks.above_high = above_high;
+            let watermark = if above_high {
+                Watermark::High
+            } else {
+                Watermark::Low
+            };
+            (watermarks.callback)(watermark, self.metrics());
+        }
+    }
+}
+
+/// What a streaming response contained, gathered while it was read.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamSummary {
+    /// The header of the dataset, if it was received.
+    pub header: Option<DataSetHeader>,
+    /// The completion of the dataset, if it was received.
+    pub completion: Option<DataSetCompletion>,
+    /// Each table of the response, including the ones that are not primary results, in the order they were received.
+    pub tables: Vec<TableSummary>,
+    /// The errors reported for the whole dataset, in its completion.
+    pub errors: Vec<OneApiError>,
+    /// Whether the completion reported the query as cancelled.
+    pub cancelled: bool,
+    /// The time from the start of the stream until the response was read, or failed.
+    pub duration: Duration,
+    /// The largest number of tables that were waiting to be read from the stream at once.
+    pub peak_queued_tables: usize,
+    /// The number of times the reading of the response waited for a table to be consumed, as the buffer of tables was full.
+    pub producer_stalls: u64,
+}
+
+/// A table of a streaming response, as summarized in a [StreamSummary].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableSummary {
+    /// The id of the table.
+    pub table_id: i32,
+    /// The name of the table.
+    pub table_name: String,
+    /// The kind of the table.
+    pub table_kind: TableKind,
+    /// The number of rows of the table, after the fragments that replaced its rows.
+    pub rows: usize,
+    /// The size of the rows of the table, as the length of their JSON, if it was measured as enabled by
+    /// [KustoClientOptions::with_streaming_table_bytes](crate::client::KustoClientOptions::with_streaming_table_bytes).
+    pub bytes: Option<usize>,
+    /// The errors reported in the completion of the table.
+    pub errors: Vec<OneApiError>,
+}
+
+impl TableSummary {
+    fn new(table_id: i32, table_name: &str, table_kind: &TableKind, measure_bytes: bool) -> Self {
+        Self {
+            table_id,
+            table_name: table_name.to_string(),
+            table_kind: table_kind.clone(),
+            rows: 0,
+            bytes: measure_bytes.then_some(0),
+            errors: vec![],
+        }
+    }
+
+    fn add_rows(&mut self, rows: &[Value]) {
+        self.rows += rows.len();
+        if let Some(bytes) = &mut self.bytes {
+            *bytes += json_length(rows);
+        }
+    }
+}
+
+/// Counts the bytes written to it, to measure serialized JSON without allocating it.
+struct ByteCount(usize);
+
+impl std::io::Write for ByteCount {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn json_length(rows: &[Value]) -> usize {
+    let mut count = ByteCount(0);
+    rows.iter()
+        .map(|row| {
+            count.0 = 0;
+            serde_json::to_writer(&mut count, row).map_or(0, |_| count.0)
+        })
+        .sum()
+}
+
+/// The number of tables a [StreamingDataset] buffers before the reading of the response waits for them to be consumed,
+/// unless another capacity is set.
+pub const DEFAULT_TABLE_CAPACITY: usize = 16;
+
+/// How a [StreamingDataset] reads a response, from the options of its client.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct StreamingOptions {
+    /// Whether rows with fewer values than columns are padded with nulls, instead of failing the stream.
+    pub(crate) pad_short_rows: bool,
+    /// The number of tables buffered before waiting for them to be read, or [DEFAULT_TABLE_CAPACITY] if it is not set.
+    pub(crate) table_capacity: Option<usize>,
+    /// Whether the size of the rows of each table is measured for its [TableSummary].
+    pub(crate) measure_table_bytes: bool,
+}
+
+impl StreamingDataset {
+    /// Starts assembling the tables of the results, as `options` tell.
+    pub(crate) fn new(
+        results: impl Stream<Item = Result<V2QueryResult>> + Send + 'static,
+        options: StreamingOptions,
+    ) -> Self {
+        let (header_sender, header) = watch::channel(None);
+        let (completion_sender, completion) = watch::channel(None);
+        let (summary_sender, summary) = watch::channel(None);
+        let capacity = options
+            .table_capacity
+            .unwrap_or(DEFAULT_TABLE_CAPACITY)
+            .clamp(1, Semaphore::MAX_PERMITS);
+        let (tables_sender, tables) = mpsc::channel(capacity);
+        let backpressure = Arc::new(Backpressure::default());
+        let failure = Arc::new(Mutex::new(None));
+
+        let assembler = TableAssembler {
+            results: Box::pin(results),
+            header: header_sender,
+            completion: completion_sender,
+            summary: summary_sender,
+            current: None,
+            pad_short_rows: options.pad_short_rows,
+            measure_table_bytes: options.measure_table_bytes,
+            frames: 0,
+            rows: 0,
+            started: Instant::now(),
+            tables: vec![],
+            last_primary_result: None,
+            backpressure: backpressure.clone(),
+            failure: failure.clone(),
+        };
+        let span = span!(
+            "kusto.streaming",
+            frames = tracing::field::Empty,
+            rows = tracing::field::Empty,
+            error = tracing::field::Empty,
+            otel.status_code = tracing::field::Empty,
+        );
+        let task = tokio::spawn(assembler.run(tables_sender).instrument(span));
+
+        Self {
+            header,
+            completion,
+            summary,
+            tables,
+            backpressure,
+            failure,
+            task,
+        }
+    }
+
+    /// Calls `callback` when the number of queued tables reaches `high`, and then when it goes back down to `low`,
+    /// alternately, so a consumer can shed load or widen its batches while it is behind.
+    ///
+    /// The callback is called by whichever side moved the queue across the watermark, either the task reading the response
+    /// or the consumer of the stream, so it should return quickly. Tables queued before it was set are counted as well.
+    #[must_use]
+    pub fn with_watermarks(
+        self,
+        high: usize,
+        low: usize,
+        callback: impl Fn(Watermark, StreamMetrics) + Send + Sync + 'static,
+    ) -> Self {
+        *self
+            .backpressure
+            .watermarks
+            .lock()
+            .expect("The watermarks lock was poisoned by a panicking callback") =
+            Some(Watermarks {
+                high,
+                low,
+                above_high: false,
+                callback: Box::new(callback),
+            });
+        self
+    }
+
+    /// The current number of queued tables, and how many times the reading of the response waited for them to be read.
+    #[must_use]
+    pub fn metrics(&self) -> StreamMetrics {
+        self.backpressure.metrics()
+    }
+
+    /// Waits for the header of the dataset.
+    /// Fails if the response ended, or failed, before the header was received.
+    pub async fn header(&self) -> Result<DataSetHeader> {
+        self.wait_for_frame(self.header.clone(), "dataset header")
+            .await
+    }
+
+    /// The header of the dataset, if it was already received.
+    #[must_use]
+    pub fn try_header(&self) -> Option<DataSetHeader> {
+        self.header.borrow().clone()
+    }
+
+    /// Waits for the completion of the dataset, which is received after all of the tables.
+    /// Fails if the response ended, or failed, before the completion was received.
+    pub async fn completion(&self) -> Result<DataSetCompletion> {
+        self.wait_for_frame(self.completion.clone(), "dataset completion")
+            .await
+    }
+
+    /// The completion of the dataset, if it was already received.
+    #[must_use]
+    pub fn try_completion(&self) -> Option<DataSetCompletion> {
+        self.completion.borrow().clone()
+    }
+
+    /// The summary of the response, if it was already read.
+    /// It is available once the stream of tables has ended.
+    #[must_use]
+    pub fn try_summary(&self) -> Option<StreamSummary> {
+        self.summary.borrow().clone()
+    }
+
+    /// Reads the rest of the response, dropping the tables that were not read yet, and returns its summary.
+    /// Fails with the error that ended the stream, if there was one.
+    pub async fn finish(self) -> Result<StreamSummary> {
+        self.for_each_table(drop).await
+    }
+
+    /// Calls `f` with each remaining table, as soon as it has been received and in the same order as the stream yields them,
+    /// and returns the summary of the response.
+    /// Fails with the error that ended the stream, if there was one.
+    pub async fn for_each_table(mut self, mut f: impl FnMut(DataTable)) -> Result<StreamSummary> {
+        while let Some(table) = self.try_next().await? {
+            f(table);
+        }
+        self.wait_for_frame(self.summary.clone(), "stream summary")
+            .await
+    }
+
+    /// Waits for a frame, failing with the error that ended the response, if it failed, as the source.
+    async fn wait_for_frame<T: Clone>(
+        &self,
+        mut receiver: watch::Receiver<Option<T>>,
+        frame: &str,
+    ) -> Result<T> {
+        if let Ok(value) = receiver.wait_for(Option::is_some).await {
+            return Ok(value.clone().expect(
+                "Frame was checked to be present - please report this issue to the Kusto team",
+            ));
+        }
+
+        let failure = self.failure.lock().expect(STATE_POISONED).clone();
+        Err(match failure {
+            Some(source) => Error::StreamFailed {
+                frame: frame.to_string(),
+                source,
+            },
+            None => Error::ConversionError(format!(
+                "{frame}, the response ended before it was received"
+            )),
+        })
+    }
+}
+
+const STATE_POISONED: &str =
+    "The state of the streaming dataset was poisoned by a panicking callback";
+
+impl Stream for StreamingDataset {
+    type Item = Result<DataTable>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<Self::Item>> {
+        let table = match self.tables.poll_recv(cx) {
+            Poll::Ready(Some(table)) => table,
+            Poll::Ready(None) => return Poll::Ready(None),
+            Poll::Pending => return Poll::Pending,
+        };
+        self.backpressure.dequeued();
+        Poll::Ready(Some(table.map_err(|error| {
+            // The error is yielded as it is, unless a waiter of the header or completion still holds it
+            self.failure.lock().expect(STATE_POISONED).take();
+            Arc::try_unwrap(error).unwrap_or_else(|source| Error::StreamFailed {
+                frame: "table".to_string(),
+                source,
+            })
+        })))
+    }
+}
+
+impl Drop for StreamingDataset {
+    fn drop(&mut self) {
+        // Nothing reads the tables anymore, so the rest of the response isn't read either
+        self.task.abort();
+    }
+}
+
+/// Sends a table, counting a stall if the buffer is full. Returns whether the receiving side is still there.
+/// Takes the backpressure state rather than the assembler, which is not `Sync`, so that the task stays `Send` while it waits.
+async fn send(
+    backpressure: Arc<Backpressure>,
+    tables: &mpsc::Sender<TableResult>,
+    table: TableResult,
+) -> bool {
+    backpressure.enqueued();
+    let sent = match tables.try_send(table) {
+        Ok(()) => true,
+        Err(TrySendError::Full(table)) => {
+            backpressure.stalls.fetch_add(1, Ordering::SeqCst);
+            tables.send(table).await.is_ok()
+        }
+        Err(TrySendError::Closed(_)) => false,
+    };
+    if !sent {
+        backpressure.queued.fetch_sub(1, Ordering::SeqCst);
+    }
+    sent
+}
+
+/// Combines the progressive frames of a response into whole tables.
+struct TableAssembler {
+    results: Pin<Box<dyn Stream<Item = Result<V2QueryResult>> + Send>>,
+    header: watch::Sender<Option<DataSetHeader>>,
+    completion: watch::Sender<Option<DataSetCompletion>>,
+    current: Option<DataTable>,
+    /// Whether rows with fewer values than columns are padded with nulls, instead of failing the stream.
+    pad_short_rows: bool,
+    /// Whether the size of the rows of each table is measured for its summary.
+    measure_table_bytes: bool,
+    /// The number of frames read so far, recorded in the span of the response.
+    frames: usize,
+    /// The number of primary result rows sent so far, recorded in the span of the response.
+    rows: usize,
+    summary: watch::Sender<Option<StreamSummary>>,
+    started: Instant,
+    /// The summaries of the tables received so far.
+    tables: Vec<TableSummary>,
+    /// The id of the last primary result table received, which the next one must be greater than.
+    last_primary_result: Option<i32>,
+    backpressure: Arc<Backpressure>,
+    failure: Arc<Mutex<Option<Arc<Error>>>>,
+}
+
+impl TableAssembler {
+    /// Reads the whole response, sending each primary result table as soon as it is complete.
+    /// Stops at the first error, or once the receiving side was dropped.
+    async fn run(mut self, tables: mpsc::Sender<TableResult>) {
+        loop {
+            let table = match self.next_primary_result().await {
+                Ok(Some(table)) => {
+                    self.rows += table.rows.len();
+                    Ok(table)
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    record_error(&Span::current(), &e);
+                    let e = Arc::new(e);
+                    *self.failure.lock().expect(STATE_POISONED) = Some(e.clone());
+                    Err(e)
+                }
+            };
+            let failed = table.is_err();
+            if !send(self.backpressure.clone(), &tables, table).await || failed {
+                break;
+            }
+        }
+
+        Span::current()
+            .record("frames", self.frames)
+            .record("rows", self.rows);
+
+        let completion = self.completion.borrow().clone();
+        self.summary.send_replace(Some(StreamSummary {
+            header: self.header.borrow().clone(),
+            errors: completion
+                .as_ref()
+                .and_then(|c| c.one_api_errors.clone())
+                .unwrap_or_default(),
+            cancelled: completion.as_ref().map_or(false, |c| c.cancelled),
+            completion,
+            tables: self.tables,
+            duration: self.started.elapsed(),
+            peak_queued_tables: self.backpressure.peak.load(Ordering::SeqCst),
+            producer_stalls: self.backpressure.stalls.load(Ordering::SeqCst),
+        }));
+    }
+
+    fn table_summary(&mut self, table_id: i32) -> Option<&mut TableSummary> {
+        self.tables
+            .iter_mut()
+            .rev()
+            .find(|t| t.table_id == table_id)
+    }
+
+    /// Checks that primary result tables are received in ascending order of their ids, as they are yielded in the order they are received.
+    fn check_order(&mut self, table_id: i32, table_kind: &TableKind) -> Result<()> {
+        if *table_kind != TableKind::PrimaryResult {
+            return Ok(());
+        }
+        if let Some(previous) = self.last_primary_result {
+            if table_id <= previous {
+                return Err(Error::ConversionError(format!(
+                    "table {table_id}, received after table {previous}, so the tables are not in the order of their statements"
+                )));
+            }
+        }
+        self.last_primary_result = Some(table_id);
+        Ok(())
+    }
+
+    fn current_table(&mut self, table_id: i32) -> Result<&mut DataTable> {
+        match &mut self.current {
+            Some(table) if table.table_id == table_id => Ok(table),
+            _ => Err(Error::ConversionError(format!(
+                "table {table_id}, received a frame without a matching table header"
+            ))),
+        }
+    }
+
+    async fn next_primary_result(&mut self) -> Result<Option<DataTable>> {
+        while let Some(result) = self.results.try_next().await? {
+            self.frames += 1;
+            match result {
+                V2QueryResult::DataSetHeader(header) => {
+                    self.header.send_replace(Some(header));
+                }
+                V2QueryResult::DataSetCompletion(completion) => {
+                    self.completion.send_replace(Some(completion));
+                }
+                V2QueryResult::DataTable(mut table) => {
+                    self.check_order(table.table_id, &table.table_kind)?;
+                    let mut summary = TableSummary::new(
+                        table.table_id,
+                        &table.table_name,
+                        &table.table_kind,
+                        self.measure_table_bytes,
+                    );
+                    summary.add_rows(&table.rows);
+                    self.tables.push(summary);
+                    if table.table_kind == TableKind::PrimaryResult {
+                        check_row_widths(
+                            table.table_id,
+                            &table.table_name,
+                            table.columns.len(),
+                            None,
+                            &mut table.rows,
+                            0,
+                            self.pad_short_rows,
+                        )?;
+                        return Ok(Some(table));
+                    }
+                }
+                V2QueryResult::TableHeader(header) => {
+                    self.check_order(header.table_id, &header.table_kind)?;
+                    self.tables.push(TableSummary::new(
+                        header.table_id,
+                        &header.table_name,
+                        &header.table_kind,
+                        self.measure_table_bytes,
+                    ));
+                    self.current = Some(DataTable {
+                        table_id: header.table_id,
+                        table_name: header.table_name,
+                        table_kind: header.table_kind,
+                        columns: header.columns,
+                        rows: vec![],
+                    });
+                }
+                V2QueryResult::TableFragment(mut fragment) => {
+                    let pad_short_rows = self.pad_short_rows;
+                    let table = self.current_table(fragment.table_id)?;
+                    let first_row = match fragment.table_fragment_type {
+                        TableFragmentType::DataAppend => table.rows.len(),
+                        TableFragmentType::DataReplace => 0,
+                    };
+                    check_row_widths(
+                        table.table_id,
+                        &table.table_name,
+                        table.columns.len(),
+                        fragment.field_count,
+                        &mut fragment.rows,
+                        first_row,
+                        pad_short_rows,
+                    )?;
+                    let table_id = table.table_id;
+                    if let Some(summary) = self.table_summary(table_id) {
+                        if fragment.table_fragment_type == TableFragmentType::DataReplace {
+                            summary.rows = 0;
+                            summary.bytes = summary.bytes.map(|_| 0);
+                        }
+                        summary.add_rows(&fragment.rows);
+                    }
+                    let table = self.current_table(table_id)?;
+                    match fragment.table_fragment_type {
+                        TableFragmentType::DataAppend => table.rows.extend(fragment.rows),
+                        TableFragmentType::DataReplace => table.rows = fragment.rows,
+                    };
+                }
+                V2QueryResult::TableProgress(progress) => {
+                    self.current_table(progress.table_id)?;
+                }
+                V2QueryResult::TableCompletion(completion) => {
+                    self.current_table(completion.table_id)?;
+                    if let Some(summary) = self.table_summary(completion.table_id) {
+                        summary.errors = completion.one_api_errors.unwrap_or_default();
+                    }
+                    if let Some(table) = self.current.take() {
+                        if table.table_kind == TableKind::PrimaryResult {
+                            return Ok(Some(table));
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// Results that end with [Error::QueryCancelled] as soon as their cancellation resolves, dropping the rest of the response,
+/// so that the task reading them stops.
+pub(crate) struct Cancellable<S> {
+    results: Option<Pin<Box<S>>>,
+    cancel: Pin<Box<dyn Future<Output = ()> + Send>>,
+    client_request_id: String,
+}
+
+impl<S> Cancellable<S> {
+    pub(crate) fn new(
+        results: S,
+        cancel: impl Future<Output = ()> + Send + 'static,
+        client_request_id: String,
+    ) -> Self {
+        Self {
+            results: Some(Box::pin(results)),
+            cancel: Box::pin(cancel),
+            client_request_id,
+        }
+    }
+}
+
+impl<S: Stream<Item = Result<V2QueryResult>>> Stream for Cancellable<S> {
+    type Item = Result<V2QueryResult>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        if this.results.is_some() && this.cancel.as_mut().poll(cx).is_ready() {
+            this.results = None;
+            return Poll::Ready(Some(Err(Error::QueryCancelled {
+                client_request_id: std::mem::take(&mut this.client_request_id),
+            })));
+        }
+        match this.results.as_mut() {
+            Some(results) => results.as_mut().poll_next(cx),
+            None => Poll::Ready(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{read_fixture, three_statements_out_of_order};
+    use crate::operations::async_deserializer;
+    use crate::operations::query::KustoResponseDataSetV2;
+    use crate::request_options::ParseOptions;
+    use futures::{stream, StreamExt};
+
+    fn progressive_dataset() -> StreamingDataset {
+        dataset_of("twotables_progressive.json")
+    }
+
+    fn dataset_of(file: &str) -> StreamingDataset {
+        dataset_of_with(file, StreamingOptions::default())
+    }
+
+    fn dataset_of_with(file: &str, options: StreamingOptions) -> StreamingDataset {
+        let results = async_deserializer::iter_results::<V2QueryResult>(futures::io::Cursor::new(
+            read_fixture(file),
+        ))
+        .map_err(Error::from);
+        StreamingDataset::new(results, options)
+    }
+
+    async fn next_table(dataset: &mut StreamingDataset) -> DataTable {
+        dataset
+            .try_next()
+            .await
+            .expect("Failed to read table")
+            .expect("Expected a table")
+    }
+
+    #[tokio::test]
+    async fn streaming_dataset_assembles_primary_results() {
+        let mut dataset = progressive_dataset();
+
+        let first = next_table(&mut dataset).await;
+        assert_eq!(first.table_id, 1);
+        assert_eq!(first.rows.len(), 3);
+        assert!(
+            dataset
+                .try_header()
+                .expect("Expected a header")
+                .is_progressive
+        );
+
+        let second = next_table(&mut dataset).await;
+        assert_eq!(second.table_id, 2);
+        assert_eq!(
+            second.rows,
+            vec![serde_json::json!(["b"]), serde_json::json!(["c"])]
+        );
+
+        assert!(dataset
+            .try_next()
+            .await
+            .expect("Failed to read end of stream")
+            .is_none());
+        assert!(
+            !dataset
+                .try_completion()
+                .expect("Expected a completion after draining the tables")
+                .has_errors
+        );
+    }
+
+    #[tokio::test]
+    async fn streaming_dataset_completion_before_draining() {
+        let mut dataset = progressive_dataset();
+
+        let completion = dataset
+            .completion()
+            .await
+            .expect("Failed to receive completion");
+        assert!(!completion.has_errors);
+        assert!(
+            dataset
+                .header()
+                .await
+                .expect("Expected a header")
+                .is_progressive
+        );
+
+        assert_eq!(next_table(&mut dataset).await.table_id, 1);
+        assert_eq!(next_table(&mut dataset).await.table_id, 2);
+    }
+
+    #[tokio::test]
+    async fn streaming_dataset_completion_while_draining() {
+        let mut dataset = progressive_dataset();
+
+        assert_eq!(next_table(&mut dataset).await.table_id, 1);
+        assert!(dataset.completion().await.is_ok());
+        assert_eq!(next_table(&mut dataset).await.table_id, 2);
+        assert!(dataset
+            .try_next()
+            .await
+            .expect("Failed to read end of stream")
+            .is_none());
+    }
+
+    fn short_row_results() -> Vec<Result<V2QueryResult>> {
+        let columns = ["x", "y"]
+            .map(|name| crate::models::Column {
+                column_name: name.to_string(),
+                column_type: crate::models::ColumnType::Long,
+            })
+            .to_vec();
+        vec![
+            Ok(V2QueryResult::TableHeader(crate::models::TableHeader {
+                table_id: 1,
+                table_name: "PrimaryResult".to_string(),
+                table_kind: TableKind::PrimaryResult,
+                columns,
+            })),
+            Ok(V2QueryResult::TableFragment(crate::models::TableFragment {
+                table_id: 1,
+                field_count: None,
+                table_fragment_type: TableFragmentType::DataAppend,
+                rows: vec![serde_json::json!([1, 2]), serde_json::json!([3])],
+            })),
+            Ok(V2QueryResult::TableCompletion(
+                crate::models::TableCompletion {
+                    table_id: 1,
+                    row_count: 2,
+                    one_api_errors: None,
+                },
+            )),
+        ]
+    }
+
+    #[tokio::test]
+    async fn streaming_dataset_rejects_short_rows() {
+        let mut dataset = StreamingDataset::new(
+            stream::iter(short_row_results()),
+            StreamingOptions::default(),
+        );
+
+        let error = dataset
+            .try_next()
+            .await
+            .expect_err("Expected the short row to fail the stream");
+        assert!(matches!(error, Error::ConversionError(_)));
+        assert!(error.to_string().contains("row 1 has 1 values"));
+    }
+
+    #[tokio::test]
+    async fn streaming_dataset_pads_short_rows() {
+        let mut dataset = StreamingDataset::new(
+            stream::iter(short_row_results()),
+            StreamingOptions {
+                pad_short_rows: true,
+                ..StreamingOptions::default()
+            },
+        );
+
+        let table = next_table(&mut dataset).await;
+        assert_eq!(
+            table.rows,
+            vec![serde_json::json!([1, 2]), serde_json::json!([3, null])]
+        );
+    }
+
+    #[tokio::test]
+    async fn streaming_dataset_rejects_orphan_fragments() {
+        let results: Vec<Result<V2QueryResult>> = vec![Ok(V2QueryResult::TableFragment(
+            crate::models::TableFragment {
+                table_id: 1,
+                field_count: None,
+                table_fragment_type: TableFragmentType::DataAppend,
+                rows: vec![],
+            },
+        ))];
+        let mut dataset = StreamingDataset::new(stream::iter(results), StreamingOptions::default());
+
+        assert!(matches!(
+            dataset.try_next().await,
+            Err(Error::ConversionError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn streaming_dataset_fails_before_header() {
+        let results: Vec<Result<V2QueryResult>> =
+            vec![Err(Error::QueryError("connection reset".into()))];
+        let mut dataset = StreamingDataset::new(stream::iter(results), StreamingOptions::default());
+
+        let error = dataset.header().await.expect_err("Expected no header");
+        assert!(matches!(
+            &error,
+            Error::StreamFailed { frame, source }
+                if frame == "dataset header" && matches!(**source, Error::QueryError(_))
+        ));
+        assert!(std::error::Error::source(&error)
+            .expect("Expected the cause of the failure")
+            .to_string()
+            .contains("connection reset"));
+        drop(error);
+        assert!(dataset.try_header().is_none());
+        assert!(matches!(
+            dataset.completion().await,
+            Err(Error::StreamFailed { .. })
+        ));
+        // Once no waiter holds it, the stream yields the error itself
+        assert!(matches!(
+            dataset.try_next().await,
+            Err(Error::QueryError(msg)) if msg.as_str() == "connection reset"
+        ));
+        assert!(dataset
+            .try_next()
+            .await
+            .expect("Expected the stream to end")
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn summary_counts_the_rows_of_each_table() {
+        let mut dataset = dataset_of_with(
+            "twotables_progressive.json",
+            StreamingOptions {
+                measure_table_bytes: true,
+                ..StreamingOptions::default()
+            },
+        );
+        assert!(dataset.try_summary().is_none());
+        assert_eq!(next_table(&mut dataset).await.table_id, 1);
+
+        let summary = dataset.finish().await.expect("Failed to read the response");
+        let tables: Vec<(i32, TableKind, usize)> = summary
+            .tables
+            .iter()
+            .map(|t| (t.table_id, t.table_kind.clone(), t.rows))
+            .collect();
+        assert_eq!(
+            tables,
+            vec![
+                (0, TableKind::QueryProperties, 1),
+                (1, TableKind::PrimaryResult, 3),
+                (2, TableKind::PrimaryResult, 2),
+                (3, TableKind::QueryCompletionInformation, 2),
+            ]
+        );
+        // `[1]`, `[2]` and `[3]`, and only the `["b"]` and `["c"]` that replaced `["a"]`
+        assert_eq!(summary.tables[1].bytes, Some(9));
+        assert_eq!(summary.tables[2].bytes, Some(10));
+        assert!(summary.tables.iter().all(|t| t.errors.is_empty()));
+        assert!(summary.errors.is_empty());
+        assert!(!summary.cancelled);
+        assert!(summary.header.expect("Expected a header").is_progressive);
+        assert!(
+            !summary
+                .completion
+                .expect("Expected a completion")
+                .has_errors
+        );
+    }
+
+    #[tokio::test]
+    async fn summary_attributes_errors_to_their_table() {
+        let mut tables = vec![];
+        let summary = dataset_of("progressive_partial_error.json")
+            .for_each_table(|table| tables.push(table.table_id))
+            .await
+            .expect("Failed to read the response");
+
+        assert_eq!(tables, vec![1, 2]);
+        // The rows are only measured when asked to
+        assert!(summary.tables.iter().all(|t| t.bytes.is_none()));
+        assert!(summary.tables[0].errors.is_empty());
+        assert_eq!(summary.tables[1].rows, 1);
+        assert_eq!(summary.tables[1].errors.len(), 1);
+        assert_eq!(
+            summary.tables[1].errors[0].error_message.code,
+            "LimitsExceeded"
+        );
+        assert_eq!(summary.errors, summary.tables[1].errors);
+        assert!(
+            summary
+                .completion
+                .expect("Expected a completion")
+                .has_errors
+        );
+    }
+
+    #[tokio::test]
+    async fn legacy_progressive_tables_are_assembled_like_whole_responses() {
+        let data = read_fixture("legacy_progressive.json");
+        let whole: Vec<DataTable> =
+            KustoResponseDataSetV2::from_body(&data, ParseOptions::default())
+                .expect("Failed to parse")
+                .into_primary_results()
+                .collect::<Result<_>>()
+                .expect("Failed to assemble the tables");
+
+        let mut tables = vec![];
+        let summary = dataset_of("legacy_progressive.json")
+            .for_each_table(|table| tables.push(table))
+            .await
+            .expect("Failed to read the response");
+
+        assert_eq!(tables, whole);
+        let rows: Vec<(i32, usize)> = summary
+            .tables
+            .iter()
+            .map(|t| (t.table_id, t.rows))
+            .collect();
+        assert_eq!(rows, vec![(1, 4), (0, 1), (2, 2), (3, 2)]);
+    }
+
+    #[tokio::test]
+    async fn summary_is_available_once_the_stream_ends() {
+        let mut dataset = progressive_dataset();
+        while dataset
+            .try_next()
+            .await
+            .expect("Failed to read table")
+            .is_some()
+        {}
+        assert_eq!(dataset.try_summary().map(|s| s.tables.len()), Some(4));
+
+        let results: Vec<Result<V2QueryResult>> =
+            vec![Err(Error::QueryError("connection reset".into()))];
+        let failed = StreamingDataset::new(stream::iter(results), StreamingOptions::default());
+        assert!(matches!(
+            failed.finish().await,
+            Err(Error::QueryError(msg)) if msg.as_str() == "connection reset"
+        ));
+    }
+
+    #[tokio::test]
+    async fn tables_are_yielded_in_statement_order() {
+        let mut dataset = dataset_of("three_statements.json");
+        let mut streamed = vec![];
+        while let Some(table) = dataset.try_next().await.expect("Failed to read table") {
+            streamed.push(table.table_id);
+        }
+        assert_eq!(streamed, vec![1, 2, 3]);
+
+        let mut visited = vec![];
+        let summary = dataset_of("three_statements.json")
+            .for_each_table(|table| visited.push(table.table_id))
+            .await
+            .expect("Failed to read the response");
+        assert_eq!(visited, vec![1, 2, 3]);
+        assert_eq!(
+            summary
+                .tables
+                .iter()
+                .map(|t| t.table_id)
+                .collect::<Vec<_>>(),
+            vec![0, 1, 2, 3, 4]
+        );
+    }
+
+    #[tokio::test]
+    async fn tables_out_of_statement_order_fail_the_stream() {
+        let results: Vec<Result<V2QueryResult>> = three_statements_out_of_order()
+            .into_iter()
+            .map(|frame| serde_json::from_value(frame).map_err(Error::from))
+            .collect();
+
+        let mut dataset = StreamingDataset::new(stream::iter(results), StreamingOptions::default());
+        assert_eq!(next_table(&mut dataset).await.table_id, 3);
+        let error = dataset
+            .try_next()
+            .await
+            .expect_err("Expected the earlier table to fail the stream");
+        assert!(error
+            .to_string()
+            .contains("table 1, received after table 3"));
+    }
+
+    fn primary_results(count: i32) -> Vec<Result<V2QueryResult>> {
+        (1..=count)
+            .map(|table_id| {
+                Ok(V2QueryResult::DataTable(DataTable {
+                    table_id,
+                    table_name: "PrimaryResult".to_string(),
+                    table_kind: TableKind::PrimaryResult,
+                    columns: vec![],
+                    rows: vec![],
+                }))
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn metrics_count_the_queued_tables() {
+        let mut dataset = progressive_dataset();
+        dataset
+            .completion()
+            .await
+            .expect("Failed to receive completion");
+        assert_eq!(
+            dataset.metrics(),
+            StreamMetrics {
+                queued_tables: 2,
+                producer_stalls: 0
+            }
+        );
+
+        assert_eq!(next_table(&mut dataset).await.table_id, 1);
+        assert_eq!(dataset.metrics().queued_tables, 1);
+
+        let summary = dataset.finish().await.expect("Failed to read the response");
+        assert_eq!(summary.peak_queued_tables, 2);
+        assert_eq!(summary.producer_stalls, 0);
+    }
+
+    #[tokio::test]
+    async fn slow_consumers_stall_the_response_and_cross_watermarks() {
+        let events = Arc::new(Mutex::new(vec![]));
+        let recorded = events.clone();
+        let mut dataset = StreamingDataset::new(
+            stream::iter(primary_results(6)),
+            StreamingOptions {
+                table_capacity: Some(2),
+                ..StreamingOptions::default()
+            },
+        )
+        .with_watermarks(2, 0, move |watermark, metrics| {
+            recorded
+                .lock()
+                .unwrap()
+                .push((watermark, metrics.queued_tables));
+        });
+
+        while dataset.metrics().producer_stalls == 0 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(*events.lock().unwrap(), vec![(Watermark::High, 2)]);
+
+        let mut tables = vec![];
+        while let Some(table) = dataset.try_next().await.expect("Failed to read table") {
+            tables.push(table.table_id);
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(tables, vec![1, 2, 3, 4, 5, 6]);
+
+        let stalls = dataset.metrics().producer_stalls;
+        assert!(stalls >= 1);
+        let summary = dataset.finish().await.expect("Failed to read the response");
+        assert_eq!(summary.producer_stalls, stalls);
+        assert!(summary.peak_queued_tables >= 2);
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.last(), Some(&(Watermark::Low, 0)));
+        for (index, (watermark, _)) in events.iter().enumerate() {
+            let expected = if index % 2 == 0 {
+                Watermark::High
+            } else {
+                Watermark::Low
+            };
+            assert_eq!(*watermark, expected, "Watermarks alternate, in {events:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn cancelling_stops_reading_the_response() {
+        let (cancel, cancelled) = futures::channel::oneshot::channel::<()>();
+        // Dropped along with the response, once the task reading it stops
+        let (response_alive, response_dropped) = futures::channel::oneshot::channel::<()>();
+        let results = stream::iter(primary_results(1))
+            .chain(stream::pending())
+            .inspect(move |_| {
+                let _ = &response_alive;
+            });
+        let mut dataset = StreamingDataset::new(
+            Cancellable::new(
+                results,
+                async move {
+                    let _ = cancelled.await;
+                },
+                "KRust;cancelled".to_string(),
+            ),
+            StreamingOptions::default(),
+        );
+
+        assert_eq!(next_table(&mut dataset).await.table_id, 1);
+        cancel.send(()).unwrap();
+
+        match dataset.try_next().await {
+            Err(Error::QueryCancelled { client_request_id }) => {
+                assert_eq!(client_request_id, "KRust;cancelled");
+            }
+            other => panic!("Expected the stream to be cancelled, got {other:?}"),
+        }
+        assert!(response_dropped.await.is_err());
+        assert!(dataset.try_next().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn dropping_the_dataset_stops_reading_the_response() {
+        let (response_alive, response_dropped) = futures::channel::oneshot::channel::<()>();
+        let results = stream::iter(primary_results(1))
+            .chain(stream::pending())
+            .inspect(move |_| {
+                let _ = &response_alive;
+            });
+        let mut dataset = StreamingDataset::new(results, StreamingOptions::default());
+        dataset
+            .try_next()
+            .await
+            .expect("Failed to read table")
+            .expect("Expected a table");
+
+        drop(dataset);
+
+        assert!(response_dropped.await.is_err());
+    }
+}
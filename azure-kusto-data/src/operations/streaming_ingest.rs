@@ -0,0 +1,123 @@
+//! Streaming ingestion, which ingests data into a table directly through the engine, rather than through the data management service.
+use crate::client::KustoClient;
+use crate::error::{Error, Result};
+use crate::operations::query::KustoResponseDataSetV1;
+use crate::prelude::ClientRequestProperties;
+use azure_core::error::Error as CoreError;
+use azure_core::prelude::*;
+use azure_core::{CustomHeaders, Method, Request, Url};
+use bytes::Bytes;
+
+/// The data ingested by [KustoClient::execute_streaming_ingest].
+#[derive(Debug, Clone)]
+pub enum StreamingIngestSource {
+    /// Data sent in the body of the request.
+    Data {
+        /// The data, in the format of the ingestion.
+        data: Bytes,
+        /// Whether the data is gzip compressed.
+        compressed: bool,
+    },
+    /// A blob that the service reads the data from. The uri must include the SAS token, or managed identity hint, required to access it.
+    BlobUri(String),
+}
+
+/// Builds the url of a streaming ingestion, e.g. `https://cluster/v1/rest/ingest/db/table?streamFormat=csv`.
+pub(crate) fn streaming_ingest_url(
+    ingest_url: &str,
+    database: &str,
+    table: &str,
+    format: &str,
+    mapping_name: Option<&str>,
+    source: &StreamingIngestSource,
+) -> Result<Url> {
+    let mut url = Url::parse(ingest_url).map_err(CoreError::from)?;
+    url.path_segments_mut()
+        .map_err(|_| Error::UnsupportedOperation(format!("Invalid ingest url {ingest_url}")))?
+        .push(database)
+        .push(table);
+    {
+        let mut query = url.query_pairs_mut();
+        query.append_pair("streamFormat", format);
+        if let Some(mapping_name) = mapping_name {
+            query.append_pair("mappingName", mapping_name);
+        }
+        if let StreamingIngestSource::BlobUri(_) = source {
+            query.append_pair("sourceKind", "uri");
+        }
+    }
+    Ok(url)
+}
+
+pub(crate) async fn execute_streaming_ingest(
+    client: &KustoClient,
+    url: Url,
+    source: StreamingIngestSource,
+    client_request_properties: Option<ClientRequestProperties>,
+) -> Result<KustoResponseDataSetV1> {
+    let mut request = Request::new(url, Method::Post);
+    let mut headers = client.default_headers_for(client_request_properties.as_ref());
+
+    let body = match source {
+        StreamingIngestSource::Data { data, compressed } => {
+            headers.insert("content-type", "application/octet-stream");
+            if compressed {
+                headers.insert("content-encoding", "gzip");
+            }
+            data
+        }
+        StreamingIngestSource::BlobUri(uri) => Bytes::from(serde_json::to_vec(
+            &serde_json::json!({ "SourceUri": uri }),
+        )?),
+    };
+    request.set_body(body);
+
+    let mut context = Context::new();
+    context.insert(CustomHeaders::from(headers));
+
+    let response = client.pipeline().send(&context, &mut request).await?;
+    let (status_code, _header_map, pinned_stream) = response.deconstruct();
+    let data = pinned_stream.collect().await?;
+    KustoResponseDataSetV1::from_body(status_code, &data, client_request_properties.as_ref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn streaming_ingest_urls() {
+        let data = StreamingIngestSource::Data {
+            data: Bytes::new(),
+            compressed: false,
+        };
+        let blob = StreamingIngestSource::BlobUri("https://blob".to_string());
+
+        assert_eq!(
+            streaming_ingest_url(
+                "https://cluster.kusto.windows.net/v1/rest/ingest",
+                "db",
+                "table",
+                "csv",
+                None,
+                &data
+            )
+            .unwrap()
+            .as_str(),
+            "https://cluster.kusto.windows.net/v1/rest/ingest/db/table?streamFormat=csv"
+        );
+        assert_eq!(
+            streaming_ingest_url(
+                "https://cluster.kusto.windows.net/v1/rest/ingest",
+                "my db",
+                "table/1",
+                "json",
+                Some("my mapping"),
+                &blob
+            )
+            .unwrap()
+            .as_str(),
+            "https://cluster.kusto.windows.net/v1/rest/ingest/my%20db/table%2F1?streamFormat=json&mappingName=my+mapping&sourceKind=uri"
+        );
+    }
+}
@@ -0,0 +1,439 @@
+//! Conversion of query results into polars [DataFrame]s.
+//!
+//! The types of the columns match those of the `arrow` conversion, except for decimals,
+//! which are converted to polars decimals with the scale of the most precise value of the column.
+
+use std::str::FromStr;
+
+use ::polars::prelude::{
+    DataFrame, Int128Chunked, Int64Chunked, IntoSeries, NamedFrom, NewChunkedArray, PolarsError,
+    PolarsResult, Series, TimeUnit,
+};
+use azure_core::error::{ErrorKind, ResultExt};
+use serde_json::Value;
+
+use crate::error::{Error, Result};
+use crate::models::{Column, ColumnType, DataTable};
+use crate::operations::query::KustoResponseDataSetV2;
+use crate::types::{KustoDateTime, KustoTimespan};
+
+fn convert_series_string(name: &str, values: Vec<Value>) -> Result<Series> {
+    let strings: Vec<Option<String>> = serde_json::from_value(Value::Array(values))?;
+    Ok(Series::new(name, strings))
+}
+
+fn convert_series_datetime(name: &str, values: Vec<Value>) -> Result<Series> {
+    let dates: Vec<Option<String>> = serde_json::from_value(Value::Array(values))?;
+    let timestamps = dates.into_iter().map(|d| {
+        d.and_then(|d| KustoDateTime::from_str(&d).ok())
+            .and_then(|d| i64::try_from(d.unix_timestamp_nanos()).ok())
+    });
+    Ok(Int64Chunked::from_iter_options(name, timestamps)
+        .into_datetime(TimeUnit::Nanoseconds, None)
+        .into_series())
+}
+
+fn convert_series_timespan(name: &str, values: Vec<Value>) -> Result<Series> {
+    let strings: Vec<Option<String>> = serde_json::from_value(Value::Array(values))?;
+    let durations = strings.into_iter().map(|s| {
+        s.and_then(|s| KustoTimespan::from_str(&s).ok())
+            .and_then(|d| i64::try_from(d.whole_nanoseconds()).ok())
+    });
+    Ok(Int64Chunked::from_iter_options(name, durations)
+        .into_duration(TimeUnit::Nanoseconds)
+        .into_series())
+}
+
+/// Reals are sent as numbers, except for `NaN` and the infinities, which are sent as strings.
+/// As in the `arrow` conversion, `NaN` values are converted to nulls.
+fn convert_series_float(name: &str, values: Vec<Value>) -> Result<Series> {
+    let reals = values
+        .into_iter()
+        .map(|value| match value {
+            Value::String(val) if val == "NaN" => Ok(None),
+            Value::String(val) if val == "Infinity" => Ok(Some(f64::INFINITY)),
+            Value::String(val) if val == "-Infinity" => Ok(Some(-f64::INFINITY)),
+            _ => Ok(serde_json::from_value(value)?),
+        })
+        .collect::<Result<Vec<Option<f64>>>>()?;
+    Ok(Series::new(name, reals))
+}
+
+fn convert_series_bool(name: &str, values: Vec<Value>) -> Result<Series> {
+    let bools: Vec<Option<bool>> = serde_json::from_value(Value::Array(values))?;
+    Ok(Series::new(name, bools))
+}
+
+fn convert_series_i32(name: &str, values: Vec<Value>) -> Result<Series> {
+    let ints: Vec<Option<i32>> = serde_json::from_value(Value::Array(values))?;
+    Ok(Series::new(name, ints))
+}
+
+fn convert_series_i64(name: &str, values: Vec<Value>) -> Result<Series> {
+    let ints: Vec<Option<i64>> = serde_json::from_value(Value::Array(values))?;
+    Ok(Series::new(name, ints))
+}
+
+/// Splits a decimal in plain notation, such as `-12.50`, into its digits as an integer and its scale.
+fn parse_decimal(value: &str) -> Option<(i128, usize)> {
+    let (negative, unsigned) = match value.strip_prefix('-') {
+        Some(unsigned) => (true, unsigned),
+        None => (false, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let (whole, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+    if whole.is_empty() && fraction.is_empty()
+        || !whole
+            .chars()
+            .chain(fraction.chars())
+            .all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+
+    let digits = whole
+        .chars()
+        .chain(fraction.chars())
+        .try_fold(0i128, |acc, c| {
+            acc.checked_mul(10)?
+                .checked_add(i128::from(c.to_digit(10)?))
+        })?;
+    Some((if negative { -digits } else { digits }, fraction.len()))
+}
+
+fn convert_series_decimal(name: &str, values: Vec<Value>) -> Result<Series> {
+    let decimals = values
+        .into_iter()
+        .map(|value| {
+            let text = match value {
+                Value::Null => return Ok(None),
+                Value::String(s) => s,
+                Value::Number(n) => n.to_string(),
+                other => {
+                    return Err(Error::ConversionError(format!(
+                        "decimal, expected a string or a number but got {other}"
+                    )))
+                }
+            };
+            parse_decimal(&text).map(Some).ok_or_else(|| {
+                Error::ConversionError(format!("decimal, '{text}' is not a valid decimal"))
+            })
+        })
+        .collect::<Result<Vec<Option<(i128, usize)>>>>()?;
+
+    // All the values of a polars decimal column share a scale, so they are rescaled to the largest one
+    let scale = decimals
+        .iter()
+        .flatten()
+        .map(|(_, s)| *s)
+        .max()
+        .unwrap_or(0);
+    let rescaled = decimals
+        .into_iter()
+        .map(|decimal| {
+            decimal
+                .map(|(digits, s)| {
+                    10i128
+                        .checked_pow((scale - s) as u32)
+                        .and_then(|factor| digits.checked_mul(factor))
+                        .ok_or_else(|| {
+                            Error::ConversionError(format!(
+                                "decimal, the values of column '{name}' don't fit at a scale of {scale}"
+                            ))
+                        })
+                })
+                .transpose()
+        })
+        .collect::<Result<Vec<Option<i128>>>>()?;
+
+    Ok(Int128Chunked::from_iter_options(name, rescaled.into_iter())
+        .into_decimal(None, scale)
+        .context(ErrorKind::DataConversion, "Failed to create decimal series")?
+        .into_series())
+}
+
+fn convert_series_dynamic(name: &str, values: Vec<Value>) -> Result<Series> {
+    let json: Vec<Option<String>> = values
+        .into_iter()
+        .map(|value| match value {
+            Value::Null => None,
+            value => Some(value.to_string()),
+        })
+        .collect();
+    Ok(Series::new(name, json))
+}
+
+fn convert_series(data: Vec<Value>, column: &Column) -> Result<Series> {
+    let name = column.column_name.as_str();
+    match column.column_type {
+        ColumnType::String | ColumnType::Guid => convert_series_string(name, data),
+        ColumnType::Decimal => convert_series_decimal(name, data),
+        ColumnType::Dynamic => convert_series_dynamic(name, data),
+        ColumnType::Bool => convert_series_bool(name, data),
+        ColumnType::Int => convert_series_i32(name, data),
+        ColumnType::Long => convert_series_i64(name, data),
+        ColumnType::Real => convert_series_float(name, data),
+        ColumnType::Datetime => convert_series_datetime(name, data),
+        ColumnType::Timespan => convert_series_timespan(name, data),
+    }
+}
+
+fn convert_table(table: DataTable) -> Result<DataFrame> {
+    let mut buffer: Vec<Vec<Value>> = (0..table.columns.len())
+        .map(|_| Vec::with_capacity(table.rows.len()))
+        .collect();
+    for row in table.rows {
+        let values = match row {
+            Value::Array(values) if values.len() == table.columns.len() => values,
+            other => {
+                return Err(Error::ConversionError(format!(
+                    "table '{}', expected rows of {} values but got {other}",
+                    table.table_name,
+                    table.columns.len()
+                )))
+            }
+        };
+        for (column, value) in buffer.iter_mut().zip(values) {
+            column.push(value);
+        }
+    }
+
+    let series = buffer
+        .into_iter()
+        .zip(table.columns.iter())
+        .map(|(data, column)| convert_series(data, column))
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(DataFrame::new(series).context(ErrorKind::DataConversion, "Failed to create data frame")?)
+}
+
+impl DataTable {
+    /// Converts the table into a polars [DataFrame].
+    ///
+    /// Decimals are converted to polars decimals, guids are kept as strings and dynamic values are serialized to JSON strings.
+    /// # Example
+    /// ```rust
+    /// use serde_json::json;
+    /// use azure_kusto_data::models::*;
+    ///
+    /// let table = DataTable {
+    ///     table_id: 1,
+    ///     table_name: "PrimaryResult".to_string(),
+    ///     table_kind: TableKind::PrimaryResult,
+    ///     columns: vec![Column { column_name: "x".to_string(), column_type: ColumnType::Long }],
+    ///     rows: vec![json!([1]), json!([null])],
+    /// };
+    ///
+    /// let frame = table.to_polars().unwrap();
+    /// assert_eq!(frame.shape(), (2, 1));
+    /// ```
+    pub fn to_polars(self) -> PolarsResult<DataFrame> {
+        convert_table(self).map_err(|e| PolarsError::ComputeError(e.to_string().into()))
+    }
+}
+
+impl KustoResponseDataSetV2 {
+    /// Iterates over the primary tables in the response, and converts them into polars [DataFrame]s.
+    /// If the query is progressive, it will combine the table parts into a single table.
+    ///
+    /// This method does not consume the response, so it can be called multiple times.
+    /// [Use into_polars_frames](#method.into_polars_frames) to consume the response and reduce memory usage.
+    pub fn polars_frames(&self) -> impl Iterator<Item = Result<DataFrame>> + '_ {
+        self.primary_results()
+            .map(|table| table.and_then(convert_table))
+    }
+
+    /// Consuming version for [polars_frames](#method.polars_frames).
+    pub fn into_polars_frames(self) -> impl Iterator<Item = Result<DataFrame>> {
+        self.into_primary_results()
+            .map(|table| table.and_then(convert_table))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::load_v2_response;
+    use crate::models::{TableKind, V2QueryResult};
+    use ::polars::prelude::{AnyValue, DataType};
+
+    fn column(column_type: ColumnType) -> Column {
+        Column {
+            column_name: "col".to_string(),
+            column_type,
+        }
+    }
+
+    #[test]
+    fn read_data_types() {
+        let frames = load_v2_response("dataframe.json")
+            .polars_frames()
+            .collect::<Result<Vec<_>>>()
+            .expect("Failed to convert to data frames");
+
+        assert_eq!(frames.len(), 1);
+        assert!(frames[0].width() > 0);
+        assert!(frames[0].height() > 0);
+    }
+
+    #[test]
+    fn frames_keep_column_types() {
+        let frames = load_v2_response("dataframe.json")
+            .into_polars_frames()
+            .collect::<Result<Vec<_>>>()
+            .expect("Failed to convert to data frames");
+
+        let types: Vec<(&str, &DataType)> = frames[0]
+            .get_columns()
+            .iter()
+            .map(|s| (s.name(), s.dtype()))
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                ("RecordName", &DataType::Utf8),
+                (
+                    "RecordTime",
+                    &DataType::Datetime(TimeUnit::Nanoseconds, None)
+                ),
+                ("RecordOffset", &DataType::Duration(TimeUnit::Nanoseconds)),
+                ("RecordBool", &DataType::Boolean),
+                ("RecordInt", &DataType::Int32),
+                ("RecordReal", &DataType::Float64),
+            ]
+        );
+    }
+
+    #[test]
+    #[allow(clippy::approx_constant)]
+    fn values_and_nulls_are_converted() {
+        let frame = load_v2_response("dataframe.json")
+            .into_polars_frames()
+            .next()
+            .expect("Expected a primary result")
+            .expect("Failed to convert to a data frame");
+
+        let times = frame.column("RecordTime").unwrap().to_physical_repr();
+        let times = times.i64().unwrap();
+        assert_eq!(times.get(0), Some(1_640_173_380_000_000_000));
+        assert_eq!(times.get(7), None);
+
+        let offsets = frame.column("RecordOffset").unwrap().to_physical_repr();
+        let offsets = offsets.i64().unwrap();
+        assert_eq!(offsets.get(0), Some(90_061_000_000_000));
+        assert_eq!(offsets.get(7), None);
+
+        let reals = frame.column("RecordReal").unwrap().f64().unwrap();
+        assert_eq!(
+            reals.into_iter().take(4).collect::<Vec<_>>(),
+            vec![
+                Some(3.14159),
+                None,
+                Some(f64::INFINITY),
+                Some(-f64::INFINITY)
+            ]
+        );
+
+        let names = frame.column("RecordName").unwrap().utf8().unwrap();
+        assert_eq!(names.get(0), Some("now"));
+        assert_eq!(names.get(7), None);
+    }
+
+    #[test]
+    fn decimal_dynamic_and_guid_columns() {
+        let decimals = convert_series(
+            vec![
+                Value::from("2.00000000000001"),
+                Value::from(5),
+                Value::from("-0.5"),
+                Value::Null,
+            ],
+            &column(ColumnType::Decimal),
+        )
+        .expect("Failed to convert decimals");
+        assert_eq!(decimals.dtype(), &DataType::Decimal(None, Some(14)));
+        assert_eq!(
+            decimals.get(0).unwrap(),
+            AnyValue::Decimal(200_000_000_000_001, 14)
+        );
+        assert_eq!(
+            decimals.get(1).unwrap(),
+            AnyValue::Decimal(500_000_000_000_000, 14)
+        );
+        assert_eq!(
+            decimals.get(2).unwrap(),
+            AnyValue::Decimal(-50_000_000_000_000, 14)
+        );
+        assert_eq!(decimals.get(3).unwrap(), AnyValue::Null);
+
+        let dynamics = convert_series(
+            vec![serde_json::json!({"moshe": "value"}), Value::Null],
+            &column(ColumnType::Dynamic),
+        )
+        .expect("Failed to convert dynamics");
+        assert_eq!(dynamics.dtype(), &DataType::Utf8);
+        assert_eq!(
+            dynamics.utf8().unwrap().into_iter().collect::<Vec<_>>(),
+            vec![Some(r#"{"moshe":"value"}"#), None]
+        );
+
+        let guids = convert_series(
+            vec![Value::from("74be27de-1e4e-49d9-b579-fe0b331d3642")],
+            &column(ColumnType::Guid),
+        )
+        .expect("Failed to convert guids");
+        assert_eq!(guids.dtype(), &DataType::Utf8);
+    }
+
+    #[test]
+    fn invalid_decimals_are_errors() {
+        for value in ["1e5", "", ".", "1.2.3", "--1"] {
+            assert!(
+                convert_series(vec![Value::from(value)], &column(ColumnType::Decimal)).is_err(),
+                "{value} should not be a valid decimal"
+            );
+        }
+        assert!(convert_series(vec![Value::from(true)], &column(ColumnType::Decimal)).is_err());
+    }
+
+    #[test]
+    fn frames_propagate_conversion_errors() {
+        let table = |table_id, value: Value| {
+            V2QueryResult::DataTable(DataTable {
+                table_id,
+                table_name: format!("table_{table_id}"),
+                table_kind: TableKind::PrimaryResult,
+                columns: vec![Column {
+                    column_name: "count".to_string(),
+                    column_type: ColumnType::Long,
+                }],
+                rows: vec![Value::Array(vec![value])],
+            })
+        };
+        let response = KustoResponseDataSetV2 {
+            results: vec![
+                table(1, Value::from(1)),
+                table(2, Value::from("not a number")),
+            ],
+        };
+
+        let result = response.into_polars_frames().collect::<Result<Vec<_>>>();
+        assert!(matches!(result, Err(Error::JsonError(_))));
+    }
+
+    #[test]
+    fn to_polars_reports_polars_errors() {
+        let table = DataTable {
+            table_id: 1,
+            table_name: "short".to_string(),
+            table_kind: TableKind::PrimaryResult,
+            columns: vec![column(ColumnType::Long), column(ColumnType::Long)],
+            rows: vec![serde_json::json!([1])],
+        };
+
+        let error = table
+            .to_polars()
+            .expect_err("Expected the short row to fail");
+        assert!(matches!(error, PolarsError::ComputeError(_)));
+        assert!(error.to_string().contains("expected rows of 2 values"));
+    }
+}
@@ -11,16 +11,35 @@
 //! use azure_kusto_data::prelude::*;
 //! ```
 
+#[cfg(feature = "arrow")]
+pub use crate::arrow::ArrowOptions;
 pub use crate::client::{KustoClient, KustoClientOptions, QueryKind};
 pub use crate::connection_string::{
-    ConnectionString, ConnectionStringAuth, DeviceCodeFunction, TokenCallbackFunction,
+    AuthKind, ConnectionString, ConnectionStringAuth, DeviceCodeFunction, TokenCallbackFunction,
 };
+pub use crate::deserialization::CaseMapping;
 pub use crate::error::Error;
-pub use crate::models::{DataTable, V2QueryResult};
-pub use crate::operations::query::{KustoResponse, KustoResponseDataSetV1, KustoResponseDataSetV2};
+pub use crate::lookup::DuplicateKeys;
+pub use crate::models::{
+    Column, ColumnType, DataSetCompletion, DataSetHeader, DataTable, ErrorMessage,
+    ErrorReportingPlacement, FrameLayout, OneApiError, TableCompletion, TableFragment,
+    TableFragmentType, TableHeader, TableKind, TableProgress, V2QueryResult,
+};
+pub use crate::operations::query::{
+    KustoResponse, KustoResponseDataSetV1, KustoResponseDataSetV2, Partial,
+};
+#[cfg(feature = "tokio")]
+pub use crate::operations::streaming::{
+    StreamMetrics, StreamSummary, StreamingDataset, TableSummary, Watermark,
+};
+pub use crate::operations::streaming_ingest::StreamingIngestSource;
 pub use crate::request_options::{
-    ClientRequestProperties, ClientRequestPropertiesBuilder, Options, OptionsBuilder,
+    ClientRequestProperties, ClientRequestPropertiesBuilder, DataScope, Options, OptionsBuilder,
+    ParseOptions, PropertiesSerialization, QueryConsistency, QueryLanguage, V1ParsingMode,
+    WeakConsistencySession,
 };
+pub use crate::row_view::RowView;
+pub use crate::types::{KustoDateTime, KustoDynamic, KustoTimespan};
 
 // Token credentials are re-exported for user convenience
 pub use azure_identity::{
@@ -0,0 +1,237 @@
+use crate::trusted_endpoints::is_trusted_endpoint;
+use azure_core::headers::HeaderName;
+use azure_core::{Context, Policy, PolicyResult, Request, StatusCode, Url};
+use std::sync::Arc;
+
+/// The number of redirects followed when [Options::client_max_redirect_count](crate::request_options::Options::client_max_redirect_count) is not set.
+pub(crate) const DEFAULT_MAX_REDIRECT_COUNT: u32 = 3;
+
+const LOCATION: HeaderName = HeaderName::from_static("location");
+
+/// The maximum number of redirects to follow for a request, set in the request's [Context].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct MaxRedirectCount(pub(crate) u32);
+
+/// Follows redirects to other Kusto endpoints, re-sending the request with its original method and body.
+/// The transport of the client must not follow redirects itself, or they never reach this policy.
+/// Must come before the [AuthorizationPolicy](crate::authorization_policy::AuthorizationPolicy), so that the request is authorized for the endpoint it was redirected to.
+#[derive(Debug, Default)]
+pub(crate) struct RedirectPolicy;
+
+fn is_redirect(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::MovedPermanently
+            | StatusCode::Found
+            | StatusCode::TemporaryRedirect
+            | StatusCode::PermanentRedirect
+    )
+}
+
+/// Whether a request to `from` may be redirected to `to` - either the same host, or a known Kusto endpoint over https.
+fn is_trusted_redirect(from: &Url, to: &Url) -> bool {
+    if from.origin() == to.origin() {
+        return true;
+    }
+
+    to.scheme() == "https" && is_trusted_endpoint(to, None)
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+impl Policy for RedirectPolicy {
+    async fn send(
+        &self,
+        ctx: &Context,
+        request: &mut Request,
+        next: &[Arc<dyn Policy>],
+    ) -> PolicyResult {
+        assert!(
+            !next.is_empty(),
+            "Redirect policies cannot be the last policy of a pipeline"
+        );
+
+        let max_redirects = ctx
+            .get::<MaxRedirectCount>()
+            .map_or(DEFAULT_MAX_REDIRECT_COUNT, |count| count.0);
+        let mut redirects = 0;
+
+        loop {
+            let response = next[0].send(ctx, request, &next[1..]).await?;
+            if redirects >= max_redirects || !is_redirect(response.status()) {
+                return Ok(response);
+            }
+
+            let target = match response
+                .headers()
+                .get_optional_str(&LOCATION)
+                .and_then(|location| request.url().join(location).ok())
+            {
+                Some(target) if is_trusted_redirect(request.url(), &target) => target,
+                _ => return Ok(response),
+            };
+
+            *request.url_mut() = target;
+            redirects += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use azure_core::headers::Headers;
+    use azure_core::{Body, BytesStream, Method, Response};
+    use bytes::Bytes;
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    const BODY: &[u8] = b"{\"csl\":\".show version\"}";
+
+    /// A transport that returns a scripted sequence of responses, and records the requests it received.
+    #[derive(Debug)]
+    struct MockTransport {
+        responses: Mutex<VecDeque<(StatusCode, Option<&'static str>)>>,
+        requests: Mutex<Vec<(String, Bytes)>>,
+    }
+
+    impl MockTransport {
+        fn new(responses: Vec<(StatusCode, Option<&'static str>)>) -> Arc<Self> {
+            Arc::new(Self {
+                responses: Mutex::new(responses.into()),
+                requests: Mutex::new(vec![]),
+            })
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Policy for MockTransport {
+        async fn send(
+            &self,
+            _ctx: &Context,
+            request: &mut Request,
+            _next: &[Arc<dyn Policy>],
+        ) -> PolicyResult {
+            let body = match request.body() {
+                Body::Bytes(bytes) => bytes.clone(),
+                _ => Bytes::new(),
+            };
+            self.requests
+                .lock()
+                .unwrap()
+                .push((request.url().to_string(), body));
+
+            let (status, location) = self
+                .responses
+                .lock()
+                .unwrap()
+                .pop_front()
+                .expect("Unexpected request");
+            let mut headers = Headers::new();
+            if let Some(location) = location {
+                headers.insert(LOCATION, location);
+            }
+            Ok(Response::new(
+                status,
+                headers,
+                Box::pin(BytesStream::new(Bytes::new())),
+            ))
+        }
+    }
+
+    async fn send(
+        transport: &Arc<MockTransport>,
+        ctx: &Context,
+    ) -> (StatusCode, Vec<(String, Bytes)>) {
+        let mut request = Request::new(
+            Url::parse("https://alias.kusto.windows.net/v1/rest/mgmt").unwrap(),
+            Method::Post,
+        );
+        request.set_body(Bytes::from_static(BODY));
+
+        let next: Vec<Arc<dyn Policy>> = vec![transport.clone()];
+        let response = RedirectPolicy
+            .send(ctx, &mut request, &next)
+            .await
+            .expect("Failed to send request");
+
+        let requests = std::mem::take(&mut *transport.requests.lock().unwrap());
+        (response.status(), requests)
+    }
+
+    #[tokio::test]
+    async fn follows_redirects_with_the_original_body() {
+        let transport = MockTransport::new(vec![
+            (
+                StatusCode::Found,
+                Some("https://cluster.westeurope.kusto.windows.net/v1/rest/mgmt"),
+            ),
+            (
+                StatusCode::TemporaryRedirect,
+                Some("/v1/rest/mgmt?redirected=1"),
+            ),
+            (StatusCode::Ok, None),
+        ]);
+
+        let (status, requests) = send(&transport, &Context::new()).await;
+
+        assert_eq!(status, StatusCode::Ok);
+        let urls: Vec<&str> = requests.iter().map(|(url, _)| url.as_str()).collect();
+        assert_eq!(
+            urls,
+            vec![
+                "https://alias.kusto.windows.net/v1/rest/mgmt",
+                "https://cluster.westeurope.kusto.windows.net/v1/rest/mgmt",
+                "https://cluster.westeurope.kusto.windows.net/v1/rest/mgmt?redirected=1",
+            ]
+        );
+        assert!(requests.iter().all(|(_, body)| body.as_ref() == BODY));
+    }
+
+    #[tokio::test]
+    async fn stops_after_the_max_redirect_count() {
+        let transport = MockTransport::new(vec![
+            (
+                StatusCode::Found,
+                Some("https://cluster1.kusto.windows.net/v1/rest/mgmt"),
+            ),
+            (
+                StatusCode::Found,
+                Some("https://cluster2.kusto.windows.net/v1/rest/mgmt"),
+            ),
+        ]);
+        let mut ctx = Context::new();
+        ctx.insert(MaxRedirectCount(1));
+
+        let (status, requests) = send(&transport, &ctx).await;
+
+        assert_eq!(status, StatusCode::Found);
+        assert_eq!(requests.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn does_not_follow_redirects_to_untrusted_hosts() {
+        let transport = MockTransport::new(vec![(
+            StatusCode::MovedPermanently,
+            Some("https://attacker.example.com/v1/rest/mgmt"),
+        )]);
+
+        let (status, requests) = send(&transport, &Context::new()).await;
+
+        assert_eq!(status, StatusCode::MovedPermanently);
+        assert_eq!(requests.len(), 1);
+    }
+
+    #[test]
+    fn trusted_redirects() {
+        let from = Url::parse("https://alias.kusto.windows.net/v1/rest/mgmt").unwrap();
+        let trusted = |to: &str| is_trusted_redirect(&from, &Url::parse(to).unwrap());
+
+        assert!(trusted("https://alias.kusto.windows.net/other"));
+        assert!(trusted("https://cluster.region.KUSTO.windows.net/"));
+        assert!(trusted("https://cluster.kusto.fabric.microsoft.com/"));
+        assert!(!trusted("http://cluster.region.kusto.windows.net/"));
+        assert!(!trusted("https://kusto.windows.net.attacker.com/"));
+        assert!(!trusted("https://attacker.com/?.kusto.windows.net"));
+    }
+}
@@ -1,6 +1,8 @@
 //! Request options for the Azure Data Explorer Client.
 
-use crate::types::{KustoDateTime, KustoDuration};
+use crate::deserialization::CaseMapping;
+use crate::error::InvalidArgumentError;
+use crate::types::{KustoDateTime, KustoTimespan};
 use hashbrown::HashMap;
 use serde::{Deserialize, Serialize};
 use serde_json::Number;
@@ -47,6 +49,77 @@ pub enum QueryConsistency {
     /// Same as weak consistency, but affinized by the database.
     #[serde(rename = "databaseaffinitizedweakconsistency")]
     DatabaseAffinitizedWeakConsistency,
+    /// Same as weak consistency, but affinized by the [Options::query_weak_consistency_session_id].
+    /// See [WeakConsistencySession] for a handle that sets both.
+    #[serde(rename = "weakconsistency_by_session_id")]
+    WeakConsistencyBySessionId,
+}
+
+/// Controls how the `properties` of request bodies are serialized when they, or their options and parameters, are not set.
+/// The service accepts all of them, but some gateways in front of it only accept one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PropertiesSerialization {
+    /// Requests without properties have no `properties` field, and unset options and parameters are left out of the properties.
+    #[default]
+    OmitNullProperties,
+    /// Requests without properties have a `properties` field that is `null`, and unset options and parameters are sent as `{}`.
+    EmitNullProperties,
+    /// Requests without properties have a `properties` field that is `{}`, and unset options and parameters are sent as `{}`.
+    EmitEmptyObject,
+}
+
+/// Controls how strictly V1 (management) responses are parsed.
+/// Rows are usually arrays of values, but can also be objects keyed by column name, which are reordered to match the columns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum V1ParsingMode {
+    /// Object rows that have a value for an unknown column fail the parsing.
+    #[default]
+    Strict,
+    /// Values for unknown columns in object rows are ignored.
+    Lenient,
+}
+
+/// Controls how the responses of queries are parsed and deserialized.
+/// Set on a client with [KustoClientOptions::with_parse_options](crate::client::KustoClientOptions::with_parse_options).
+/// Everything is off by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ParseOptions {
+    pub(crate) pad_short_rows: bool,
+    pub(crate) deny_unknown_frame_fields: bool,
+    pub(crate) check_struct_columns: bool,
+}
+
+impl ParseOptions {
+    /// Create new options
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// If true, rows of V2 responses that have fewer values than their table has columns are padded with nulls,
+    /// instead of failing the response. Rows with more values than columns always fail it.
+    #[must_use]
+    pub fn with_pad_short_rows(mut self, pad_short_rows: bool) -> Self {
+        self.pad_short_rows = pad_short_rows;
+        self
+    }
+
+    /// If true, V2 responses whose frames have fields that this crate doesn't model fail, with the names of the fields in the error.
+    /// Meant to notice changes of the protocol early, in tests and canary environments - by default, unknown fields are ignored.
+    /// Only buffered responses are checked, not progressive streams.
+    #[must_use]
+    pub fn with_deny_unknown_frame_fields(mut self, deny_unknown_frame_fields: bool) -> Self {
+        self.deny_unknown_frame_fields = deny_unknown_frame_fields;
+        self
+    }
+
+    /// If true, results deserialized into structs by position are first checked to have a column for each field of the struct,
+    /// named as the field regardless of case and underscores, so that a mismatch fails with the names of the column and the field.
+    #[must_use]
+    pub fn with_check_struct_columns(mut self, check_struct_columns: bool) -> Self {
+        self.check_struct_columns = check_struct_columns;
+        self
+    }
 }
 
 #[skip_serializing_none]
@@ -62,14 +135,69 @@ pub struct ClientRequestProperties {
     /// Client request id.
     pub client_request_id: Option<String>,
     #[serde(skip)]
-    /// Application name for tracing.
+    /// Application name for tracing, reported by the service along with the request, such as in `.show queries`.
+    ///
+    /// The application and user of a request are the first of these that is set:
+    /// 1. [application](Self::application) and [user](Self::user) of the request properties.
+    /// 2. [Options::request_app_name] and [Options::request_user].
+    /// 3. The application and user of the [connection string](crate::connection_string::ConnectionString),
+    ///    which default to the name of the executable and the user running it.
+    ///
+    /// They are sent in the `x-ms-app` and `x-ms-user` headers, and, when set by either of the first two,
+    /// in the `request_app_name` and `request_user` options of the body as well, so that both agree.
+    /// [Options::request_description] is only sent in the options of the body, as it has no header.
     pub application: Option<String>,
     #[serde(skip)]
-    /// User name for tracing.
+    /// User name for tracing. See [application](Self::application) for how it is sent.
     pub user: Option<String>,
+    #[serde(skip)]
+    /// How column names are matched to struct fields when deserializing results into structs.
+    /// When unset, columns are matched to fields by position.
+    pub case_mapping: Option<CaseMapping>,
+    #[serde(skip)]
+    /// If true, exceptions reported within a management command response are returned as part of the
+    /// [KustoResponseDataSetV1](crate::prelude::KustoResponseDataSetV1) instead of failing the request.
+    /// This mirrors [Options::defer_partial_query_failures] for V1 responses.
+    pub defer_v1_exceptions: Option<bool>,
+    #[serde(skip)]
+    /// How strictly V1 (management) responses are parsed. Defaults to [V1ParsingMode::Strict].
+    pub v1_parsing_mode: Option<V1ParsingMode>,
 }
 
 impl ClientRequestProperties {
+    /// The application of the request, from [application](Self::application) or else [Options::request_app_name].
+    pub(crate) fn request_application(&self) -> Option<&str> {
+        self.application.as_deref().or_else(|| {
+            self.options
+                .as_ref()
+                .and_then(|o| o.request_app_name.as_deref())
+        })
+    }
+
+    /// The user of the request, from [user](Self::user) or else [Options::request_user].
+    pub(crate) fn request_user(&self) -> Option<&str> {
+        self.user.as_deref().or_else(|| {
+            self.options
+                .as_ref()
+                .and_then(|o| o.request_user.as_deref())
+        })
+    }
+
+    /// Copies the application and user of the request into its options, as they are sent in the body.
+    pub(crate) fn with_identity_in_options(mut self) -> Self {
+        if self.application.is_none() && self.user.is_none() {
+            return self;
+        }
+        let options = self.options.get_or_insert_with(Options::default);
+        if let Some(application) = &self.application {
+            options.request_app_name = Some(application.clone());
+        }
+        if let Some(user) = &self.user {
+            options.request_user = Some(user.clone());
+        }
+        self
+    }
+
     /// Add a query parameter with a string value.
     pub fn add_string_parameter(&mut self, name: Cow<str>, value: Cow<str>) {
         self.add_parameter(name, serde_json::Value::String(value.into()));
@@ -81,13 +209,20 @@ impl ClientRequestProperties {
     }
 
     /// Add a query parameter with a float value.
-    pub fn add_f64_parameter(&mut self, name: Cow<str>, value: f64) {
-        self.add_parameter(
-            name,
-            Number::from_f64(value)
-                .map(serde_json::Value::Number)
-                .unwrap_or_else(|| serde_json::Value::String(value.to_string())),
-        );
+    ///
+    /// Fails with [InvalidArgumentError::NonFiniteParameter] if the value is NaN or infinite, leaving the parameters as they were.
+    /// Those values can't be sent as JSON numbers, and sending them as strings would make the query compare them as strings.
+    /// Real columns of results do hold them, as the `NaN`, `Infinity` and `-Infinity` strings, which this crate reads back as floats.
+    /// To pass them to a query, declare the parameter as `real` and add it with [add_string_parameter](Self::add_string_parameter)
+    /// as the Kusto literals `real(nan)`, `real(+inf)` or `real(-inf)`.
+    pub fn add_f64_parameter(&mut self, name: Cow<str>, value: f64) -> crate::error::Result<()> {
+        let number =
+            Number::from_f64(value).ok_or_else(|| InvalidArgumentError::NonFiniteParameter {
+                name: name.to_string(),
+                value: value.to_string(),
+            })?;
+        self.add_parameter(name, serde_json::Value::Number(number));
+        Ok(())
     }
 
     /// Add a query parameter with a boolean value.
@@ -95,6 +230,13 @@ impl ClientRequestProperties {
         self.add_parameter(name, serde_json::Value::Bool(value));
     }
 
+    /// Add a query parameter with a timespan value, for parameters declared as `timespan`.
+    ///
+    /// The value is sent as the Kusto literal of the timespan, `[-][d.]hh:mm:ss.fffffff`, as its [Display](std::fmt::Display) formats it.
+    pub fn add_timespan_parameter(&mut self, name: Cow<str>, value: KustoTimespan) {
+        self.add_parameter(name, serde_json::Value::String(value.to_string()));
+    }
+
     /// Add a query parameter with a generic value.
     pub fn add_parameter(&mut self, name: Cow<str>, value: serde_json::Value) {
         if self.parameters.is_none() {
@@ -113,15 +255,79 @@ impl From<Options> for ClientRequestProperties {
     }
 }
 
+/// A session of queries that run with weak consistency on the same node of the cluster.
+///
+/// Weakly consistent queries run on any node, and may not observe data ingested moments before.
+/// Affinizing a session to a node makes its queries benefit from the node's caches, and observe a consistent view of the data across the session,
+/// at the cost of concentrating the load of the session on a single node.
+/// Unlike [QueryConsistency::AffinitizedWeakConsistency], the affinity doesn't depend on the query text, so different queries share it.
+///
+/// Create one with [KustoClient::weak_consistency_session](crate::client::KustoClient::weak_consistency_session),
+/// and pass its [properties](Self::properties) to the execute calls of the session.
+/// Call [new_session](Self::new_session) to move on to a new session, possibly on another node, e.g. when the current node is overloaded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WeakConsistencySession {
+    session_id: String,
+}
+
+impl WeakConsistencySession {
+    /// Creates a session with a new, random, session id.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            session_id: format!("KRust;{}", uuid::Uuid::new_v4()),
+        }
+    }
+
+    /// The id that the queries of the session are affinized by.
+    #[must_use]
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    /// Replaces the session id with a new one, so that the following queries are affinized separately from the previous ones.
+    pub fn new_session(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Returns the properties to execute queries of the session with.
+    /// Options or parameters can be added to them, as long as the consistency options are kept.
+    #[must_use]
+    pub fn properties(&self) -> ClientRequestProperties {
+        Options {
+            query_consistency: Some(QueryConsistency::WeakConsistencyBySessionId),
+            query_weak_consistency_session_id: Some(self.session_id.clone()),
+            ..Options::default()
+        }
+        .into()
+    }
+}
+
+impl Default for WeakConsistencySession {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<&WeakConsistencySession> for ClientRequestProperties {
+    fn from(session: &WeakConsistencySession) -> Self {
+        session.properties()
+    }
+}
+
 #[skip_serializing_none]
 #[derive(Serialize, Deserialize, Debug, Clone, Default, derive_builder::Builder)]
 #[builder(setter(into, strip_option, prefix = "with"), default)]
 /// Request options for queries, can be used to set the size, consistency, and other options.
 pub struct Options {
     /// If set and positive, indicates the maximum number of HTTP redirects that the client will process.
+    /// Redirects are only followed to the original host, or to known Kusto endpoints. Defaults to 3.
     pub client_max_redirect_count: Option<i64>,
     /// If true, disables reporting partial query failures as part of the result set
-    #[serde(rename = "deferpartialqueryfailures")]
+    #[serde(
+        rename = "deferpartialqueryfailures",
+        alias = "defer_partial_query_failures"
+    )]
     pub defer_partial_query_failures: Option<bool>,
     /// A hint to use shuffle strategy for materialized views that are referenced in the query.
     /// The property is an array of materialized views names and the shuffle keys to use.
@@ -130,16 +336,19 @@ pub struct Options {
     /// Overrides the default maximum amount of memory a whole query may allocate per node
     pub max_memory_consumption_per_query_per_node: Option<u64>,
     /// Overrides the default maximum amount of memory a query operator may allocate.
-    #[serde(rename = "maxmemoryconsumptionperiterator")]
+    #[serde(
+        rename = "maxmemoryconsumptionperiterator",
+        alias = "max_memory_consumption_per_iterator"
+    )]
     pub max_memory_consumption_per_iterator: Option<u64>,
     /// Overrides the default maximum number of columns a query is allowed to produce.
-    #[serde(rename = "maxoutputcolumns")]
+    #[serde(rename = "maxoutputcolumns", alias = "max_output_columns")]
     pub max_output_columns: Option<u64>,
     /// Enables setting the request timeout to its maximum value.
-    #[serde(rename = "norequesttimeout")]
+    #[serde(rename = "norequesttimeout", alias = "no_request_timeout")]
     pub no_request_timeout: Option<bool>,
     /// Enables suppressing truncation of the query results returned to the caller.
-    #[serde(rename = "notruncation")]
+    #[serde(rename = "notruncation", alias = "no_truncation")]
     pub no_truncation: Option<bool>,
     /// If true, push simple selection through aggregation
     pub push_selection_through_aggregation: Option<bool>,
@@ -160,13 +369,19 @@ pub struct Options {
     /// Controls the query's datascope -- whether the query applies to all data or just part of it.
     query_datascope: Option<DataScope>,
     /// Controls the column name for the query's datetime scope (query_datetimescope_to / query_datetimescope_from).
-    #[serde(rename = "query_datetimescope_column")]
+    #[serde(
+        rename = "query_datetimescope_column",
+        alias = "query_datetime_scope_column"
+    )]
     pub query_datetime_scope_column: Option<String>,
     /// Controls the query's datetime scope (earliest) -- used as auto-applied filter on query_datetimescope_column only (if defined).
-    #[serde(rename = "query_datetimescope_from")]
+    #[serde(
+        rename = "query_datetimescope_from",
+        alias = "query_datetime_scope_from"
+    )]
     pub query_datetime_scope_from: Option<KustoDateTime>,
     /// Controls the query's datetime scope (latest) -- used as auto-applied filter on query_datetimescope_column only (if defined).
-    #[serde(rename = "query_datetimescope_to")]
+    #[serde(rename = "query_datetimescope_to", alias = "query_datetime_scope_to")]
     pub query_datetime_scope_to: Option<KustoDateTime>,
     /// If set, controls the way the subquery merge behaves: the executing node will introduce an additional
     /// level in the query hierarchy for each subgroup of nodes; the size of the subgroup is set by this option.
@@ -190,7 +405,7 @@ pub struct Options {
     /// If set, retrieves the schema of each tabular data in the results of the query instead of the data itself.
     pub query_results_apply_getschema: Option<bool>,
     /// If positive, controls the maximum age of the cached query results the service is allowed to return
-    pub query_results_cache_max_age: Option<KustoDuration>,
+    pub query_results_cache_max_age: Option<KustoTimespan>,
     /// If set, enables per-shard query cache.
     pub query_results_cache_per_shard: Option<bool>,
     /// Hint for Kusto as to how many records to send in each update (takes effect only if OptionResultsProgressiveEnabled is set)
@@ -200,8 +415,14 @@ pub struct Options {
     ///  Enables limiting query results to this number of records.
     pub query_take_max_records: Option<i64>,
     /// Controls query consistency
-    #[serde(skip_serializing_if = "Option::is_none", rename = "queryconsistency")]
+    #[serde(rename = "queryconsistency", alias = "query_consistency")]
     pub query_consistency: Option<QueryConsistency>,
+    /// The session that queries with [QueryConsistency::WeakConsistencyBySessionId] are affinized by.
+    #[serde(
+        rename = "query_weakconsistency_session_id",
+        alias = "query_weak_consistency_session_id"
+    )]
+    pub query_weak_consistency_session_id: Option<String>,
     /// Request application name to be used in the reporting (e.g. show queries).
     pub request_app_name: Option<String>,
     /// If specified, blocks access to tables for which row_level_security policy is enabled
@@ -222,23 +443,350 @@ pub struct Options {
     pub request_sandboxed_execution_disabled: Option<bool>,
     /// Request user to be used in the reporting (e.g. show queries).
     pub request_user: Option<String>,
-    /// If set, enables the progressive query stream
+    /// If set, enables the progressive query stream, in which the service reports the progress of the tables and can replace
+    /// the rows sent so far, see [FrameLayout::Progressive](crate::models::FrameLayout::Progressive).
+    /// The responses are assembled into the same tables either way, so this only changes how early rows are received.
+    /// [execute_streaming_query](crate::client::KustoClient::execute_streaming_query) always sets it.
     pub results_progressive_enabled: Option<bool>,
     /// Overrides the default request timeout.
-    #[serde(rename = "servertimeout")]
-    pub server_timeout: Option<KustoDuration>,
+    #[serde(rename = "servertimeout", alias = "server_timeout")]
+    pub server_timeout: Option<KustoTimespan>,
     /// Overrides the default maximum number of records a query is allowed to return to the caller (truncation).
-    #[serde(rename = "truncationmaxrecords")]
+    #[serde(rename = "truncationmaxrecords", alias = "truncation_max_records")]
     pub truncation_max_records: Option<i64>,
     /// Overrides the default maximum data size a query is allowed to return to the caller (truncation).
-    #[serde(rename = "truncationmaxsize")]
+    #[serde(rename = "truncationmaxsize", alias = "truncation_max_size")]
     pub truncation_max_size: Option<i64>,
     /// Validates user's permissions to perform the query and doesn't run the query itself.
     pub validate_permissions: Option<bool>,
     /// If set, enables the newlines between frames in the progressive query stream.
+    /// Responses are parsed the same way whether or not their frames are separated by newlines.
     #[builder(default = "Some(true)")]
-    results_v2_newlines_between_frames: Option<bool>,
+    pub results_v2_newlines_between_frames: Option<bool>,
     /// Additional options to be passed to the service.
-    #[serde(flatten)]
+    /// Options that are not known to the client are collected here when deserializing, with values that are not strings in their JSON form.
+    #[serde(flatten, deserialize_with = "values_as_strings")]
     pub additional: HashMap<String, String>,
 }
+
+/// Deserializes the values of a map of options as strings, keeping the JSON text of values of other types.
+fn values_as_strings<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<HashMap<String, String>, D::Error> {
+    Ok(
+        HashMap::<String, serde_json::Value>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(name, value)| match value {
+                serde_json::Value::String(value) => (name, value),
+                other => (name, other.to_string()),
+            })
+            .collect(),
+    )
+}
+
+/// An option whose value differs between two [Options], as found by [Options::diff].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OptionDiff {
+    /// The name of the option, as sent to the service.
+    pub name: String,
+    /// The value of the option in the requested options, if it is set.
+    pub requested: Option<serde_json::Value>,
+    /// The value of the option in the effective options, if it is set.
+    pub effective: Option<serde_json::Value>,
+}
+
+impl Options {
+    /// Lists the options whose values differ between these options and the `effective` ones, ordered by name.
+    ///
+    /// The `effective` options are usually those that the service reports it ran the request with, parsed from its
+    /// `EffectiveRequestOptions` into [Options]. Options that are only set on one side are listed as well, such as those
+    /// the service set to its defaults.
+    /// # Example
+    /// ```rust
+    /// use azure_kusto_data::prelude::*;
+    /// use azure_kusto_data::types::KustoTimespan;
+    /// use std::str::FromStr;
+    ///
+    /// let requested = OptionsBuilder::default()
+    ///     .with_server_timeout(KustoTimespan::from_str("00:10:00").unwrap())
+    ///     .build()
+    ///     .unwrap();
+    /// let effective: Options = serde_json::from_str(r#"{"servertimeout": "00:04:00"}"#).unwrap();
+    ///
+    /// let diff = requested.diff(&effective);
+    /// assert_eq!(diff.len(), 2);
+    /// assert_eq!(diff[0].name, "results_v2_newlines_between_frames");
+    /// assert_eq!(diff[1].name, "servertimeout");
+    /// assert_eq!(diff[1].effective, Some(serde_json::json!("00:04:00.0000000")));
+    /// ```
+    #[must_use]
+    pub fn diff(&self, effective: &Options) -> Vec<OptionDiff> {
+        fn to_map(options: &Options) -> serde_json::Map<String, serde_json::Value> {
+            match serde_json::to_value(options) {
+                Ok(serde_json::Value::Object(map)) => map,
+                _ => serde_json::Map::new(),
+            }
+        }
+
+        let requested = to_map(self);
+        let effective = to_map(effective);
+
+        let mut diff: Vec<OptionDiff> = requested
+            .iter()
+            .map(|(name, value)| (name, Some(value), effective.get(name)))
+            .chain(
+                effective
+                    .iter()
+                    .filter(|(name, _)| !requested.contains_key(*name))
+                    .map(|(name, value)| (name, None, Some(value))),
+            )
+            .filter(|(_, requested, effective)| requested != effective)
+            .map(|(name, requested, effective)| OptionDiff {
+                name: name.clone(),
+                requested: requested.cloned(),
+                effective: effective.cloned(),
+            })
+            .collect();
+        diff.sort_by(|a, b| a.name.cmp(&b.name));
+        diff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::read_fixture;
+    use crate::models::{TableKind, V2QueryResult};
+    use std::str::FromStr;
+
+    /// The payload of the `EffectiveRequestOptions` event in the completion information of the fixture
+    fn effective_request_options() -> String {
+        let frames: Vec<V2QueryResult> =
+            serde_json::from_slice(&read_fixture("twotables_progressive.json"))
+                .expect("Failed to parse");
+        frames
+            .iter()
+            .find_map(|frame| match frame {
+                V2QueryResult::DataTable(table)
+                    if table.table_kind == TableKind::QueryCompletionInformation =>
+                {
+                    table.row_views().find_map(|row| {
+                        (row.get_string("EventTypeName").unwrap().as_deref()
+                            == Some("EffectiveRequestOptions"))
+                        .then(|| row.get_string("Payload").unwrap().unwrap())
+                    })
+                }
+                _ => None,
+            })
+            .expect("Expected effective request options")
+    }
+
+    #[test]
+    fn effective_options_round_trip() {
+        let payload = effective_request_options();
+        let options: Options = serde_json::from_str(&payload).expect("Failed to parse options");
+
+        assert_eq!(
+            options.server_timeout,
+            Some(KustoTimespan::from_str("00:04:00").unwrap())
+        );
+        assert!(matches!(
+            options.query_consistency,
+            Some(QueryConsistency::StrongConsistency)
+        ));
+        assert_eq!(
+            options.max_memory_consumption_per_iterator,
+            Some(5368709120)
+        );
+        assert_eq!(options.no_truncation, Some(false));
+        assert!(matches!(options.query_datascope, Some(DataScope::All)));
+        assert_eq!(
+            options.additional.get("request_readonly_hardline"),
+            Some(&"false".to_string())
+        );
+
+        // Every option of the payload is kept when the options are serialized again
+        let original: serde_json::Map<String, serde_json::Value> =
+            serde_json::from_str(&payload).unwrap();
+        let serialized = serde_json::to_value(&options).unwrap();
+        for name in original.keys() {
+            assert!(serialized.get(name).is_some(), "{name} was dropped");
+        }
+    }
+
+    #[test]
+    fn field_names_are_accepted_as_aliases() {
+        let options: Options = serde_json::from_str(
+            r#"{"server_timeout": "00:01:00", "query_consistency": "weakconsistency", "truncation_max_records": 10}"#,
+        )
+        .expect("Failed to parse options");
+
+        assert_eq!(
+            options.server_timeout,
+            Some(KustoTimespan::from_str("00:01:00").unwrap())
+        );
+        assert!(matches!(
+            options.query_consistency,
+            Some(QueryConsistency::WeakConsistency)
+        ));
+        assert_eq!(options.truncation_max_records, Some(10));
+        assert!(options.additional.is_empty());
+    }
+
+    #[test]
+    fn diff_against_effective_options() {
+        let requested = OptionsBuilder::default()
+            .with_server_timeout(KustoTimespan::from_str("00:10:00").unwrap())
+            .with_query_consistency(QueryConsistency::StrongConsistency)
+            .with_request_app_name("KRust")
+            .with_results_progressive_enabled(true)
+            .with_truncation_max_records(500000)
+            .with_no_truncation(true)
+            .build()
+            .unwrap();
+        let effective: Options =
+            serde_json::from_str(&effective_request_options()).expect("Failed to parse options");
+
+        let diff = requested.diff(&effective);
+        let changed = |name: &str| diff.iter().find(|d| d.name == name).cloned();
+
+        assert_eq!(
+            changed("servertimeout"),
+            Some(OptionDiff {
+                name: "servertimeout".to_string(),
+                requested: Some(serde_json::json!("00:10:00.0000000")),
+                effective: Some(serde_json::json!("00:04:00.0000000")),
+            })
+        );
+        assert_eq!(
+            changed("notruncation"),
+            Some(OptionDiff {
+                name: "notruncation".to_string(),
+                requested: Some(serde_json::json!(true)),
+                effective: Some(serde_json::json!(false)),
+            })
+        );
+        // Options the service set without them being requested are listed, unlike options with the requested values
+        assert_eq!(changed("query_language").map(|d| d.requested), Some(None));
+        for unchanged in [
+            "queryconsistency",
+            "request_app_name",
+            "results_progressive_enabled",
+            "results_v2_newlines_between_frames",
+            "truncationmaxrecords",
+        ] {
+            assert_eq!(changed(unchanged), None, "{unchanged} is unchanged");
+        }
+        assert!(diff.windows(2).all(|w| w[0].name < w[1].name));
+        assert!(requested.diff(&requested).is_empty());
+    }
+
+    #[test]
+    fn weak_consistency_session_serialization() {
+        let session = WeakConsistencySession::new();
+
+        let properties = serde_json::to_value(session.properties()).unwrap();
+
+        assert_eq!(
+            properties["options"]["queryconsistency"],
+            "weakconsistency_by_session_id"
+        );
+        assert_eq!(
+            properties["options"]["query_weakconsistency_session_id"],
+            session.session_id()
+        );
+        assert_eq!(
+            serde_json::to_value(ClientRequestProperties::from(&session)).unwrap(),
+            properties
+        );
+    }
+
+    #[test]
+    fn new_session_rotates_the_session_id() {
+        let mut session = WeakConsistencySession::new();
+        let previous = session.clone();
+
+        session.new_session();
+
+        assert_ne!(session, previous);
+        assert!(session.session_id().starts_with("KRust;"));
+    }
+
+    #[test]
+    fn query_consistency_serialization() {
+        for (consistency, expected) in [
+            (QueryConsistency::StrongConsistency, "strongconsistency"),
+            (QueryConsistency::WeakConsistency, "weakconsistency"),
+            (
+                QueryConsistency::AffinitizedWeakConsistency,
+                "affinitizedweakconsistency",
+            ),
+            (
+                QueryConsistency::DatabaseAffinitizedWeakConsistency,
+                "databaseaffinitizedweakconsistency",
+            ),
+            (
+                QueryConsistency::WeakConsistencyBySessionId,
+                "weakconsistency_by_session_id",
+            ),
+        ] {
+            assert_eq!(serde_json::to_value(consistency).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn f64_parameters_must_be_finite() {
+        let mut properties = ClientRequestProperties::default();
+        properties
+            .add_f64_parameter("ratio".into(), 0.25)
+            .expect("Expected a finite value to be accepted");
+        properties
+            .add_f64_parameter("negative".into(), -1e300)
+            .expect("Expected a finite value to be accepted");
+
+        for (value, text) in [
+            (f64::NAN, "NaN"),
+            (f64::INFINITY, "inf"),
+            (f64::NEG_INFINITY, "-inf"),
+        ] {
+            let error = properties
+                .add_f64_parameter("ratio".into(), value)
+                .expect_err("Expected a non-finite value to be rejected");
+            assert!(matches!(
+                error,
+                crate::error::Error::InvalidArgumentError(InvalidArgumentError::NonFiniteParameter { name, value })
+                    if name == "ratio" && value == text
+            ));
+        }
+
+        let serialized = serde_json::to_value(&properties).unwrap();
+        assert_eq!(
+            serialized["parameters"],
+            serde_json::json!({ "ratio": 0.25, "negative": -1e300 })
+        );
+    }
+
+    #[test]
+    fn timespan_parameters_are_kusto_literals() {
+        let mut properties = ClientRequestProperties::default();
+        let timespan = KustoTimespan::from(
+            -(time::Duration::days(3)
+                + time::Duration::hours(4)
+                + time::Duration::minutes(5)
+                + time::Duration::seconds(6)
+                + time::Duration::milliseconds(789)),
+        );
+        properties.add_timespan_parameter("window".into(), timespan);
+        properties.add_timespan_parameter("short".into(), KustoTimespan::from_ticks(1));
+
+        let serialized = serde_json::to_value(&properties).unwrap();
+        assert_eq!(
+            serialized["parameters"],
+            serde_json::json!({ "window": "-3.04:05:06.7890000", "short": "00:00:00.0000001" })
+        );
+        assert_eq!(
+            serde_json::to_value(timespan).unwrap(),
+            serialized["parameters"]["window"]
+        );
+    }
+}
@@ -0,0 +1,345 @@
+//! Typed access to the cells of a [DataTable], for reading results without defining a struct for their rows.
+//!
+//! # Example
+//! ```no_run
+//! use azure_kusto_data::prelude::*;
+//! # #[tokio::main] async fn main() -> Result<(), Error> {
+//! let client = KustoClient::new(
+//!     ConnectionString::with_default_auth("https://mycluster.region.kusto.windows.net/"),
+//!     KustoClientOptions::default())?;
+//!
+//! let response = client.execute_query("some_database", "MyTable | take 10", None).await?;
+//! for table in response.into_primary_results() {
+//!     let table = table?;
+//!     for row in table.row_views() {
+//!         let name: Option<String> = row.get_string("Name")?;
+//!         let time: Option<KustoDateTime> = row.get_datetime(1)?;
+//!         println!("{name:?} at {time:?}");
+//!     }
+//! }
+//! # Ok(())}
+//! ```
+use std::fmt::Display;
+use std::str::FromStr;
+
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::error::{Error, Result};
+use crate::models::DataTable;
+use crate::types::{KustoDateTime, KustoTimespan};
+
+/// Identifies a column of a [DataTable], either by its position or by its name.
+pub trait ColumnIndex: Display {
+    /// The position of the column in the table, if it exists.
+    fn index_in(&self, table: &DataTable) -> Option<usize>;
+}
+
+impl ColumnIndex for usize {
+    fn index_in(&self, table: &DataTable) -> Option<usize> {
+        (*self < table.columns.len()).then_some(*self)
+    }
+}
+
+impl ColumnIndex for str {
+    fn index_in(&self, table: &DataTable) -> Option<usize> {
+        table.column_index(self)
+    }
+}
+
+impl ColumnIndex for String {
+    fn index_in(&self, table: &DataTable) -> Option<usize> {
+        table.column_index(self)
+    }
+}
+
+impl<T: ColumnIndex + ?Sized> ColumnIndex for &T {
+    fn index_in(&self, table: &DataTable) -> Option<usize> {
+        (**self).index_in(table)
+    }
+}
+
+impl DataTable {
+    /// The position of the column with the given name, if there is one.
+    #[must_use]
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        self.columns.iter().position(|c| c.column_name == name)
+    }
+
+    /// Iterates over the rows of the table, as [RowView]s with typed getters for their cells.
+    pub fn row_views(&self) -> impl ExactSizeIterator<Item = RowView<'_>> + '_ {
+        self.rows
+            .iter()
+            .enumerate()
+            .map(move |(index, row)| RowView {
+                table: self,
+                index,
+                row,
+            })
+    }
+}
+
+/// A row of a [DataTable], with getters that convert its cells to the Rust type of their column.
+///
+/// Every getter returns `Ok(None)` for null cells, and an error naming the row and the column for cells of another type.
+#[derive(Debug, Clone, Copy)]
+pub struct RowView<'a> {
+    table: &'a DataTable,
+    index: usize,
+    row: &'a Value,
+}
+
+impl<'a> RowView<'a> {
+    /// The position of the row in its table.
+    #[must_use]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The table of the row.
+    #[must_use]
+    pub fn table(&self) -> &'a DataTable {
+        self.table
+    }
+
+    /// The raw JSON value of a cell.
+    pub fn get(&self, column: impl ColumnIndex) -> Result<&'a Value> {
+        let index = column.index_in(self.table).ok_or_else(|| {
+            Error::ConversionError(format!(
+                "row {} of table '{}', which has no column {column}",
+                self.index, self.table.table_name
+            ))
+        })?;
+        let values = self.row.as_array().ok_or_else(|| {
+            Error::ConversionError(format!(
+                "row {} of table '{}', which is not an array of values",
+                self.index, self.table.table_name
+            ))
+        })?;
+        values.get(index).ok_or_else(|| {
+            Error::ConversionError(format!(
+                "row {} of table '{}', which has no value for column '{}'",
+                self.index, self.table.table_name, self.table.columns[index].column_name
+            ))
+        })
+    }
+
+    /// Converts a cell that isn't null, failing with the context of the cell when `convert` returns `None`.
+    fn cell<T>(
+        &self,
+        column: impl ColumnIndex,
+        expected: &str,
+        convert: impl FnOnce(&'a Value) -> Option<T>,
+    ) -> Result<Option<T>> {
+        let value = self.get(&column)?;
+        if value.is_null() {
+            return Ok(None);
+        }
+        convert(value).map(Some).ok_or_else(|| {
+            Error::ConversionError(format!(
+                "row {}, column {column} of table '{}', expected {expected} but got {value}",
+                self.index, self.table.table_name
+            ))
+        })
+    }
+
+    /// Reads a `string` cell.
+    pub fn get_string(&self, column: impl ColumnIndex) -> Result<Option<String>> {
+        self.cell(column, "a string", |v| v.as_str().map(ToString::to_string))
+    }
+
+    /// Reads a `bool` cell.
+    pub fn get_bool(&self, column: impl ColumnIndex) -> Result<Option<bool>> {
+        self.cell(column, "a bool", Value::as_bool)
+    }
+
+    /// Reads an `int` cell.
+    pub fn get_i32(&self, column: impl ColumnIndex) -> Result<Option<i32>> {
+        self.cell(column, "an int", |v| {
+            v.as_i64().and_then(|i| i32::try_from(i).ok())
+        })
+    }
+
+    /// Reads a `long` cell, or an `int` cell.
+    pub fn get_i64(&self, column: impl ColumnIndex) -> Result<Option<i64>> {
+        self.cell(column, "a long", Value::as_i64)
+    }
+
+    /// Reads a `real` cell, including the `NaN` and infinite values that Kusto sends as strings.
+    pub fn get_f64(&self, column: impl ColumnIndex) -> Result<Option<f64>> {
+        self.cell(column, "a real", |v| match v {
+            Value::String(s) if s == "NaN" => Some(f64::NAN),
+            Value::String(s) if s == "Infinity" => Some(f64::INFINITY),
+            Value::String(s) if s == "-Infinity" => Some(f64::NEG_INFINITY),
+            v => v.as_f64(),
+        })
+    }
+
+    /// Reads a `datetime` cell.
+    pub fn get_datetime(&self, column: impl ColumnIndex) -> Result<Option<KustoDateTime>> {
+        self.cell(column, "a datetime", |v| {
+            v.as_str().and_then(|s| KustoDateTime::from_str(s).ok())
+        })
+    }
+
+    /// Reads a `timespan` cell, either in the Kusto format or as a number of ticks.
+    pub fn get_timespan(&self, column: impl ColumnIndex) -> Result<Option<KustoTimespan>> {
+        self.cell(column, "a timespan", |v| match v {
+            Value::String(s) => KustoTimespan::from_str(s).ok(),
+            Value::Number(n) => n.as_i64().map(KustoTimespan::from_ticks),
+            _ => None,
+        })
+    }
+
+    /// Reads a `guid` cell.
+    pub fn get_guid(&self, column: impl ColumnIndex) -> Result<Option<Uuid>> {
+        self.cell(column, "a guid", |v| {
+            v.as_str().and_then(|s| Uuid::parse_str(s).ok())
+        })
+    }
+
+    /// Reads a `dynamic` cell, which may hold any JSON value.
+    pub fn get_dynamic(&self, column: impl ColumnIndex) -> Result<Option<Value>> {
+        self.cell(column, "a dynamic value", |v| Some(v.clone()))
+    }
+
+    /// Reads a `decimal` cell. Decimals are returned as strings, such as `2.00000000000001`, to preserve their precision.
+    pub fn get_decimal(&self, column: impl ColumnIndex) -> Result<Option<String>> {
+        self.cell(column, "a decimal", |v| match v {
+            Value::String(s) => Some(s.clone()),
+            Value::Number(n) => Some(n.to_string()),
+            _ => None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::load_v2_response;
+
+    fn all_data_types() -> DataTable {
+        load_v2_response("alldatatypes.json")
+            .into_primary_results()
+            .next()
+            .expect("Expected a primary result")
+            .expect("Failed to parse the primary result")
+    }
+
+    #[test]
+    fn read_all_data_types() {
+        let table = all_data_types();
+        let rows: Vec<RowView> = table.row_views().collect();
+        assert_eq!(rows.len(), 3);
+
+        let row = rows[0];
+        assert_eq!(row.get_i32("vnum").unwrap(), Some(1));
+        assert_eq!(
+            row.get_decimal("vdec").unwrap().as_deref(),
+            Some("2.00000000000001")
+        );
+        assert_eq!(
+            row.get_datetime("vdate").unwrap(),
+            Some(KustoDateTime::from_str("2020-03-04T14:05:01.3109965Z").unwrap())
+        );
+        assert_eq!(
+            row.get_timespan("vspan").unwrap(),
+            Some(KustoTimespan::from_str("01:23:45.6789").unwrap())
+        );
+        assert_eq!(
+            row.get_dynamic("vobj").unwrap(),
+            Some(serde_json::json!({"moshe": "value"}))
+        );
+        assert_eq!(row.get_bool("vb").unwrap(), Some(true));
+        assert_eq!(row.get_f64("vreal").unwrap(), Some(0.01));
+        assert_eq!(row.get_string("vstr").unwrap().as_deref(), Some("asdf"));
+        assert_eq!(row.get_i64("vlong").unwrap(), Some(i64::MAX));
+        assert_eq!(
+            row.get_guid("vguid").unwrap(),
+            Some(Uuid::parse_str("74be27de-1e4e-49d9-b579-fe0b331d3642").unwrap())
+        );
+
+        let row = rows[1];
+        assert_eq!(row.get_i32(0).unwrap(), Some(-2));
+        assert_eq!(
+            row.get_decimal(1).unwrap().as_deref(),
+            Some("-5.00000000000005")
+        );
+        assert_eq!(
+            row.get_datetime(2)
+                .unwrap()
+                .map(|d| d.to_string())
+                .as_deref(),
+            Some("2022-05-06T16:07:03.1234300Z")
+        );
+        assert_eq!(
+            row.get_timespan(3)
+                .unwrap()
+                .map(|t| t.to_string())
+                .as_deref(),
+            Some("-1.04:56:59.9120000")
+        );
+        assert_eq!(
+            row.get_dynamic(4).unwrap(),
+            Some(serde_json::json!([1, "two"]))
+        );
+        assert_eq!(row.get_bool(5).unwrap(), Some(false));
+        assert!(row.get_f64(6).unwrap().expect("Expected a real").is_nan());
+        assert_eq!(row.get_string(7).unwrap().as_deref(), Some(""));
+        assert_eq!(row.get_i64(8).unwrap(), Some(i64::MIN));
+        assert_eq!(
+            row.get_guid(9).unwrap(),
+            Some(Uuid::parse_str("f6e97f76-8b73-45c0-b9ef-f68e8f897713").unwrap())
+        );
+
+        let row = rows[2];
+        assert_eq!(row.get_i32("vnum").unwrap(), None);
+        assert_eq!(row.get_decimal("vdec").unwrap(), None);
+        assert_eq!(row.get_datetime("vdate").unwrap(), None);
+        assert_eq!(row.get_timespan("vspan").unwrap(), None);
+        assert_eq!(row.get_dynamic("vobj").unwrap(), None);
+        assert_eq!(row.get_bool("vb").unwrap(), None);
+        assert_eq!(row.get_f64("vreal").unwrap(), None);
+        assert_eq!(row.get_string("vstr").unwrap(), None);
+        assert_eq!(row.get_i64("vlong").unwrap(), None);
+        assert_eq!(row.get_guid("vguid").unwrap(), None);
+    }
+
+    #[test]
+    fn column_indices() {
+        let table = all_data_types();
+
+        assert_eq!(table.column_index("vnum"), Some(0));
+        assert_eq!(table.column_index("vguid"), Some(9));
+        assert_eq!(table.column_index("missing"), None);
+
+        let row = table.row_views().next().expect("Expected a row");
+        let name = "vstr".to_string();
+        assert_eq!(row.get(&name).unwrap(), row.get(7).unwrap());
+        assert_eq!(row.get("vstr").unwrap(), "asdf");
+    }
+
+    #[test]
+    fn errors_name_the_cell() {
+        let table = all_data_types();
+        let row = table.row_views().nth(1).expect("Expected a row");
+
+        let error = row.get_i64("vstr").expect_err("Expected a type error");
+        assert!(matches!(error, Error::ConversionError(_)));
+        assert_eq!(
+            error.to_string(),
+            "Error converting Kusto response for row 1, column vstr of table 'AllDataTypes', expected a long but got \"\""
+        );
+
+        let error = row.get_datetime(8).expect_err("Expected a type error");
+        assert!(error
+            .to_string()
+            .contains("row 1, column 8 of table 'AllDataTypes'"));
+
+        let error = row
+            .get_string("missing")
+            .expect_err("Expected a missing column");
+        assert!(error.to_string().contains("no column missing"));
+        assert!(row.get_string(10).is_err());
+    }
+}
@@ -0,0 +1,328 @@
+//! Listing of the databases and tables of a cluster, and of the schemas of tables, through management commands.
+//!
+//! The helpers are methods of [KustoClient], such as [list_databases](KustoClient::list_databases) and [get_table_schema](KustoClient::get_table_schema).
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use crate::client::KustoClient;
+use crate::deserialization::{deserialize_table_v1, CaseMapping};
+use crate::error::{Error, Result};
+use crate::models::ColumnType;
+use crate::operations::query::KustoResponseDataSetV1;
+
+/// A database of the cluster, as listed by `.show databases`.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DatabaseInfo {
+    /// Name of the database.
+    #[serde(rename = "DatabaseName")]
+    pub name: String,
+    /// Pretty name of the database, if one is set.
+    #[serde(rename = "PrettyName", default)]
+    pub pretty_name: Option<String>,
+    /// Version of the database schema.
+    #[serde(rename = "Version", default)]
+    pub version: Option<String>,
+    /// Whether this is the database that the command was run in.
+    #[serde(rename = "IsCurrent", default)]
+    pub is_current: bool,
+    /// Access mode of the database, such as `ReadWrite` or `ReadOnly`.
+    #[serde(rename = "DatabaseAccessMode", default)]
+    pub access_mode: Option<String>,
+}
+
+/// A table of a database, as listed by `.show tables`.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TableInfo {
+    /// Name of the table.
+    #[serde(rename = "TableName")]
+    pub name: String,
+    /// Name of the database of the table.
+    #[serde(rename = "DatabaseName")]
+    pub database_name: String,
+    /// Folder of the table, empty if it is not in a folder.
+    #[serde(
+        rename = "Folder",
+        default,
+        deserialize_with = "crate::deserialization::null_as_empty"
+    )]
+    pub folder: String,
+    /// Description of the table, empty if it has none.
+    #[serde(
+        rename = "DocString",
+        default,
+        deserialize_with = "crate::deserialization::null_as_empty"
+    )]
+    pub doc_string: String,
+}
+
+/// The schema of a table, as returned by `.show table T schema as json`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableSchema {
+    /// Name of the table.
+    pub name: String,
+    /// Name of the database of the table.
+    pub database_name: String,
+    /// Folder of the table, empty if it is not in a folder.
+    pub folder: String,
+    /// Description of the table, empty if it has none.
+    pub doc_string: String,
+    /// Columns of the table, in order.
+    pub columns: Vec<ColumnSchema>,
+}
+
+/// A column of a [TableSchema].
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ColumnSchema {
+    /// Name of the column.
+    #[serde(rename = "Name")]
+    pub name: String,
+    /// Type of the column.
+    #[serde(rename = "CslType")]
+    pub column_type: ColumnType,
+    /// Description of the column, empty if it has none.
+    #[serde(
+        rename = "DocString",
+        default,
+        deserialize_with = "crate::deserialization::null_as_empty"
+    )]
+    pub doc_string: String,
+}
+
+/// A row of `.show table T schema as json`, whose schema is a JSON string.
+#[derive(Deserialize)]
+struct SchemaRow {
+    #[serde(rename = "TableName")]
+    table_name: String,
+    #[serde(rename = "Schema")]
+    schema: String,
+    #[serde(rename = "DatabaseName")]
+    database_name: String,
+    #[serde(
+        rename = "Folder",
+        default,
+        deserialize_with = "crate::deserialization::null_as_empty"
+    )]
+    folder: String,
+    #[serde(
+        rename = "DocString",
+        default,
+        deserialize_with = "crate::deserialization::null_as_empty"
+    )]
+    doc_string: String,
+}
+
+#[derive(Deserialize)]
+struct OrderedColumns {
+    #[serde(rename = "OrderedColumns")]
+    ordered_columns: Vec<ColumnSchema>,
+}
+
+/// Quotes the name of an entity, so that names with spaces or other special characters can be used in commands.
+fn quote_name(name: &str) -> String {
+    format!("[\"{}\"]", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Deserializes the rows of the first table of a management command response, matching the columns to fields by name.
+fn deserialize_rows<T: DeserializeOwned>(response: KustoResponseDataSetV1) -> Result<Vec<T>> {
+    let table = response
+        .tables
+        .into_iter()
+        .next()
+        .ok_or_else(|| Error::QueryError("No results found for the command".into()))?;
+    deserialize_table_v1(table, CaseMapping::Exact)
+}
+
+fn parse_table_schema(response: KustoResponseDataSetV1, table: &str) -> Result<TableSchema> {
+    let row = deserialize_rows::<SchemaRow>(response)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| {
+            Error::QueryError(format!("No schema found for the table `{table}`").into())
+        })?;
+    let schema: OrderedColumns = serde_json::from_str(&row.schema)?;
+
+    Ok(TableSchema {
+        name: row.table_name,
+        database_name: row.database_name,
+        folder: row.folder,
+        doc_string: row.doc_string,
+        columns: schema.ordered_columns,
+    })
+}
+
+impl KustoClient {
+    /// Lists the databases of the cluster that the caller has access to, with `.show databases`.
+    /// # Example
+    /// ```no_run
+    /// use azure_kusto_data::prelude::*;
+    /// # #[tokio::main] async fn main() -> Result<(), Error> {
+    /// let client = KustoClient::new(
+    ///    ConnectionString::with_default_auth("https://mycluster.region.kusto.windows.net/"),
+    ///    KustoClientOptions::default())?;
+    ///
+    /// for database in client.list_databases().await? {
+    ///     println!("{}", database.name);
+    /// }
+    /// # Ok(())}
+    /// ```
+    pub async fn list_databases(&self) -> Result<Vec<DatabaseInfo>> {
+        let response = self
+            .execute_cluster_command(".show databases", None)
+            .await?;
+        deserialize_rows(response)
+    }
+
+    /// Lists the tables of the database, with `.show tables`.
+    pub async fn list_tables(&self, database: impl Into<String>) -> Result<Vec<TableInfo>> {
+        let response = self.execute_command(database, ".show tables", None).await?;
+        deserialize_rows(response)
+    }
+
+    /// Gets the schema of a table, with its columns in order, with `.show table T schema as json`.
+    /// # Example
+    /// ```no_run
+    /// use azure_kusto_data::prelude::*;
+    /// # #[tokio::main] async fn main() -> Result<(), Error> {
+    /// let client = KustoClient::new(
+    ///    ConnectionString::with_default_auth("https://mycluster.region.kusto.windows.net/"),
+    ///    KustoClientOptions::default())?;
+    ///
+    /// let schema = client.get_table_schema("Samples", "StormEvents").await?;
+    /// for column in schema.columns {
+    ///     println!("{}: {:?}", column.name, column.column_type);
+    /// }
+    /// # Ok(())}
+    /// ```
+    pub async fn get_table_schema(
+        &self,
+        database: impl Into<String>,
+        table: &str,
+    ) -> Result<TableSchema> {
+        let command = format!(".show table {} schema as json", quote_name(table));
+        let response = self.execute_command(database, command, None).await?;
+        parse_table_schema(response, table)
+    }
+
+    /// Whether a database of the given name exists, and the caller has access to it.
+    pub async fn database_exists(&self, database: &str) -> Result<bool> {
+        Ok(self
+            .list_databases()
+            .await?
+            .iter()
+            .any(|d| d.name == database))
+    }
+
+    /// Whether a table of the given name exists in the database.
+    pub async fn table_exists(&self, database: impl Into<String>, table: &str) -> Result<bool> {
+        Ok(self
+            .list_tables(database)
+            .await?
+            .iter()
+            .any(|t| t.name == table))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::load_v1_response;
+    use crate::models::TableV1;
+
+    #[test]
+    fn parses_databases() {
+        let databases: Vec<DatabaseInfo> =
+            deserialize_rows(load_v1_response("show_databases.json")).expect("Failed to parse");
+
+        assert_eq!(
+            databases,
+            vec![
+                DatabaseInfo {
+                    name: "Samples".to_string(),
+                    pretty_name: Some("Sample Data".to_string()),
+                    version: Some("v36.4".to_string()),
+                    is_current: true,
+                    access_mode: Some("ReadWrite".to_string()),
+                },
+                DatabaseInfo {
+                    name: "Logs".to_string(),
+                    pretty_name: None,
+                    version: Some("v12.0".to_string()),
+                    is_current: false,
+                    access_mode: Some("ReadOnly".to_string()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_tables() {
+        let tables: Vec<TableInfo> =
+            deserialize_rows(load_v1_response("show_tables.json")).expect("Failed to parse");
+
+        assert_eq!(tables.len(), 2);
+        assert_eq!(
+            tables[0],
+            TableInfo {
+                name: "StormEvents".to_string(),
+                database_name: "Samples".to_string(),
+                folder: "Weather".to_string(),
+                doc_string: "US storm events, 2007".to_string(),
+            }
+        );
+        assert_eq!(tables[1].folder, "");
+    }
+
+    #[test]
+    fn parses_table_schema() {
+        let schema = parse_table_schema(load_v1_response("show_table_schema.json"), "AllDataTypes")
+            .expect("Failed to parse");
+
+        assert_eq!(schema.name, "AllDataTypes");
+        assert_eq!(schema.database_name, "Samples");
+        assert_eq!(schema.folder, "Types");
+        assert_eq!(
+            schema
+                .columns
+                .iter()
+                .map(|c| (c.name.as_str(), c.column_type.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("vnum", ColumnType::Int),
+                ("vdec", ColumnType::Decimal),
+                ("vdate", ColumnType::Datetime),
+                ("vspan", ColumnType::Timespan),
+                ("vobj", ColumnType::Dynamic),
+                ("vb", ColumnType::Bool),
+                ("vreal", ColumnType::Real),
+                ("vstr", ColumnType::String),
+                ("vlong", ColumnType::Long),
+                ("vguid", ColumnType::Guid),
+            ]
+        );
+        assert_eq!(schema.columns[2].doc_string, "When the row was written");
+        assert_eq!(schema.columns[0].doc_string, "");
+    }
+
+    #[test]
+    fn missing_schema_is_an_error() {
+        let response = KustoResponseDataSetV1 {
+            tables: vec![TableV1 {
+                table_name: "Table_0".to_string(),
+                columns: vec![],
+                rows: vec![],
+                exceptions: None,
+            }],
+            exceptions: None,
+        };
+
+        let error = parse_table_schema(response, "Missing").expect_err("Expected an error");
+        assert!(error.to_string().contains("Missing"));
+    }
+
+    #[test]
+    fn names_are_quoted() {
+        assert_eq!(quote_name("StormEvents"), r#"["StormEvents"]"#);
+        assert_eq!(quote_name(r#"my "table" \ 1"#), r#"["my \"table\" \\ 1"]"#);
+    }
+}
@@ -0,0 +1,374 @@
+//! Reports of queries that exceed thresholds of duration, rows or bytes, for alerting on slow or large queries without tracing.
+//!
+//! Register a callback with [KustoClientOptions::with_slow_query_hook](crate::client::KustoClientOptions::with_slow_query_hook).
+
+use std::fmt::{Debug, Formatter};
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context as TaskContext, Poll};
+use std::time::{Duration, Instant};
+
+use futures::Stream;
+use hashbrown::HashSet;
+
+use crate::error::Result;
+use crate::models::{TableKind, V2QueryResult};
+use crate::operations::query::KustoResponse;
+use crate::prelude::ClientRequestProperties;
+use crate::statistics::completion_statistics;
+use crate::utils::redact_string_literals;
+
+/// The limits above which a query is reported. Queries are reported if they exceed any of the limits that are set.
+/// # Example
+/// ```rust
+/// use azure_kusto_data::slow_query::SlowQueryThresholds;
+/// use std::time::Duration;
+///
+/// let thresholds = SlowQueryThresholds::default()
+///     .with_duration(Duration::from_secs(10))
+///     .with_bytes(100 * 1024 * 1024);
+/// assert_eq!(thresholds.rows, None);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SlowQueryThresholds {
+    /// The time from sending the query to receiving the whole response.
+    pub duration: Option<Duration>,
+    /// The time the query took to execute on the service, as reported in the `QueryCompletionInformation` table.
+    pub execution_time: Option<Duration>,
+    /// The number of rows of the primary results.
+    pub rows: Option<usize>,
+    /// The size of the response body, in bytes.
+    pub bytes: Option<u64>,
+}
+
+impl SlowQueryThresholds {
+    /// Reports queries that take longer than `duration` to complete.
+    #[must_use]
+    pub fn with_duration(mut self, duration: Duration) -> Self {
+        self.duration = Some(duration);
+        self
+    }
+
+    /// Reports queries that take longer than `execution_time` to execute on the service.
+    #[must_use]
+    pub fn with_execution_time(mut self, execution_time: Duration) -> Self {
+        self.execution_time = Some(execution_time);
+        self
+    }
+
+    /// Reports queries that return more than `rows` primary result rows.
+    #[must_use]
+    pub fn with_rows(mut self, rows: usize) -> Self {
+        self.rows = Some(rows);
+        self
+    }
+
+    /// Reports queries whose response is larger than `bytes`.
+    #[must_use]
+    pub fn with_bytes(mut self, bytes: u64) -> Self {
+        self.bytes = Some(bytes);
+        self
+    }
+}
+
+/// One of the [SlowQueryThresholds].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SlowQueryThreshold {
+    /// [SlowQueryThresholds::duration]
+    Duration,
+    /// [SlowQueryThresholds::execution_time]
+    ExecutionTime,
+    /// [SlowQueryThresholds::rows]
+    Rows,
+    /// [SlowQueryThresholds::bytes]
+    Bytes,
+}
+
+/// A query that exceeded some of the [SlowQueryThresholds], with what was measured for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlowQueryReport {
+    /// The text of the query, with the contents of its string literals masked.
+    pub query: String,
+    /// The database the query ran in.
+    pub database: String,
+    /// The client request id of the query, if one was set in its [ClientRequestProperties].
+    pub client_request_id: Option<String>,
+    /// The time from sending the query to receiving the whole response.
+    pub duration: Duration,
+    /// The time the query took to execute on the service, if the response reported it.
+    pub execution_time: Option<Duration>,
+    /// The number of rows of the primary results.
+    pub rows: usize,
+    /// The size of the response body, in bytes.
+    pub bytes: u64,
+    /// The thresholds that the query exceeded, in the order of the fields of [SlowQueryThresholds].
+    pub exceeded: Vec<SlowQueryThreshold>,
+}
+
+/// The callback that receives the [SlowQueryReport]s.
+pub type SlowQueryCallback = Arc<dyn Fn(SlowQueryReport) + Send + Sync>;
+
+/// The thresholds of the client, and the callback that is called for the queries that exceed them.
+#[derive(Clone)]
+pub(crate) struct SlowQueryHook {
+    thresholds: SlowQueryThresholds,
+    callback: SlowQueryCallback,
+}
+
+impl Debug for SlowQueryHook {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SlowQueryHook")
+            .field("thresholds", &self.thresholds)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SlowQueryHook {
+    pub(crate) fn new(thresholds: SlowQueryThresholds, callback: SlowQueryCallback) -> Self {
+        Self {
+            thresholds,
+            callback,
+        }
+    }
+
+    /// Starts measuring a query that is about to be sent.
+    /// Returns `None` in WebAssembly, where the time can't be measured.
+    pub(crate) fn start(
+        self: &Arc<Self>,
+        database: &str,
+        query: &str,
+        client_request_properties: Option<&ClientRequestProperties>,
+    ) -> Option<QueryMeasurement> {
+        if cfg!(target_arch = "wasm32") {
+            return None;
+        }
+
+        Some(QueryMeasurement {
+            hook: self.clone(),
+            database: database.to_string(),
+            query: query.to_string(),
+            client_request_id: client_request_properties.and_then(|p| p.client_request_id.clone()),
+            started: Instant::now(),
+            execution_time: None,
+            rows: 0,
+            bytes: Arc::new(AtomicU64::new(0)),
+            primary_tables: HashSet::new(),
+        })
+    }
+}
+
+/// What was measured for a query so far. Reported to the hook when the query completes successfully with [finish](#method.finish).
+pub(crate) struct QueryMeasurement {
+    hook: Arc<SlowQueryHook>,
+    database: String,
+    query: String,
+    client_request_id: Option<String>,
+    started: Instant,
+    execution_time: Option<Duration>,
+    rows: usize,
+    /// Shared with the body of the response, which is read separately from its results when they are streamed
+    bytes: Arc<AtomicU64>,
+    /// The ids of the progressive primary result tables, whose rows are counted in their completion
+    primary_tables: HashSet<i32>,
+}
+
+impl QueryMeasurement {
+    /// The counter of the bytes of the response body.
+    pub(crate) fn bytes(&self) -> Arc<AtomicU64> {
+        self.bytes.clone()
+    }
+
+    /// Counts bytes of the response body.
+    pub(crate) fn add_bytes(&self, bytes: usize) {
+        self.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Measures a result of a V2 response.
+    pub(crate) fn observe(&mut self, result: &V2QueryResult) {
+        match result {
+            V2QueryResult::DataTable(table) if table.table_kind == TableKind::PrimaryResult => {
+                self.rows += table.rows.len();
+            }
+            V2QueryResult::DataTable(table) => {
+                // A malformed payload only leaves the execution time unknown
+                if let Ok(Some(statistics)) = completion_statistics(table) {
+                    self.execution_time = Some(statistics.execution_duration());
+                }
+            }
+            V2QueryResult::TableHeader(header) if header.table_kind == TableKind::PrimaryResult => {
+                self.primary_tables.insert(header.table_id);
+            }
+            V2QueryResult::TableCompletion(completion)
+                if self.primary_tables.contains(&completion.table_id) =>
+            {
+                self.rows += completion.row_count.max(0) as usize;
+            }
+            _ => {}
+        }
+    }
+
+    /// Measures a whole response.
+    pub(crate) fn observe_response(&mut self, response: &KustoResponse) {
+        match response {
+            KustoResponse::V1(v1) => self.rows += v1.tables.first().map_or(0, |t| t.rows.len()),
+            KustoResponse::V2(v2) => v2.results.iter().for_each(|result| self.observe(result)),
+        }
+    }
+
+    /// Completes the measurement, calling the hook if the query exceeded any of its thresholds.
+    /// A panic of the callback is caught, so that it doesn't fail the query.
+    pub(crate) fn finish(self) {
+        let duration = self.started.elapsed();
+        let bytes = self.bytes.load(Ordering::Relaxed);
+        let thresholds = &self.hook.thresholds;
+
+        let exceeded: Vec<SlowQueryThreshold> = [
+            (
+                SlowQueryThreshold::Duration,
+                thresholds.duration.map_or(false, |max| duration > max),
+            ),
+            (
+                SlowQueryThreshold::ExecutionTime,
+                thresholds
+                    .execution_time
+                    .zip(self.execution_time)
+                    .map_or(false, |(max, execution_time)| execution_time > max),
+            ),
+            (
+                SlowQueryThreshold::Rows,
+                thresholds.rows.map_or(false, |max| self.rows > max),
+            ),
+            (
+                SlowQueryThreshold::Bytes,
+                thresholds.bytes.map_or(false, |max| bytes > max),
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(threshold, exceeded)| exceeded.then_some(threshold))
+        .collect();
+
+        if exceeded.is_empty() {
+            return;
+        }
+
+        let report = SlowQueryReport {
+            query: redact_string_literals(&self.query),
+            database: self.database,
+            client_request_id: self.client_request_id,
+            duration,
+            execution_time: self.execution_time,
+            rows: self.rows,
+            bytes,
+            exceeded,
+        };
+        let callback = &self.hook.callback;
+        let _ = std::panic::catch_unwind(AssertUnwindSafe(|| callback(report)));
+    }
+}
+
+/// Measures the results of a progressive response as they are read, and completes the measurement once they all were.
+/// Responses that fail, or that are dropped before they end, are not reported.
+pub(crate) struct MeasuredResults<S> {
+    results: Pin<Box<S>>,
+    measurement: Option<QueryMeasurement>,
+}
+
+impl<S> MeasuredResults<S> {
+    pub(crate) fn new(results: S, measurement: Option<QueryMeasurement>) -> Self {
+        Self {
+            results: Box::pin(results),
+            measurement,
+        }
+    }
+}
+
+impl<S: Stream<Item = Result<V2QueryResult>>> Stream for MeasuredResults<S> {
+    type Item = Result<V2QueryResult>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let next = futures::ready!(this.results.as_mut().poll_next(cx));
+        match &next {
+            Some(Ok(result)) => {
+                if let Some(measurement) = &mut this.measurement {
+                    measurement.observe(result);
+                }
+            }
+            Some(Err(_)) => this.measurement = None,
+            None => {
+                if let Some(measurement) = this.measurement.take() {
+                    measurement.finish();
+                }
+            }
+        }
+        Poll::Ready(next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::read_fixture;
+    use std::sync::Mutex;
+
+    fn recording_hook(
+        thresholds: SlowQueryThresholds,
+    ) -> (Arc<SlowQueryHook>, Arc<Mutex<Vec<SlowQueryReport>>>) {
+        let reports = Arc::new(Mutex::new(vec![]));
+        let recorded = reports.clone();
+        let hook = SlowQueryHook::new(
+            thresholds,
+            Arc::new(move |report| recorded.lock().unwrap().push(report)),
+        );
+        (Arc::new(hook), reports)
+    }
+
+    #[test]
+    fn queries_within_the_thresholds_are_not_reported() {
+        let (hook, reports) = recording_hook(
+            SlowQueryThresholds::default()
+                .with_duration(Duration::from_secs(60))
+                .with_rows(10)
+                .with_bytes(1000),
+        );
+
+        let mut measurement = hook.start("db", "T", None).unwrap();
+        measurement.rows = 10;
+        measurement.bytes.store(1000, Ordering::Relaxed);
+        measurement.finish();
+
+        assert!(reports.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn unknown_execution_times_do_not_exceed_their_threshold() {
+        let (hook, reports) =
+            recording_hook(SlowQueryThresholds::default().with_execution_time(Duration::ZERO));
+
+        hook.start("db", "T", None).unwrap().finish();
+        assert!(reports.lock().unwrap().is_empty());
+
+        let mut measurement = hook.start("db", "T", None).unwrap();
+        measurement.execution_time = Some(Duration::from_millis(1));
+        measurement.finish();
+        assert_eq!(
+            reports.lock().unwrap()[0].exceeded,
+            vec![SlowQueryThreshold::ExecutionTime]
+        );
+    }
+
+    #[test]
+    fn progressive_rows_are_counted_for_primary_tables() {
+        let (hook, _) = recording_hook(SlowQueryThresholds::default());
+        let mut measurement = hook.start("db", "T", None).unwrap();
+
+        let results: Vec<V2QueryResult> =
+            serde_json::from_slice(&read_fixture("twotables_progressive.json"))
+                .expect("Failed to deserialize results");
+        results.iter().for_each(|r| measurement.observe(r));
+
+        assert_eq!(measurement.rows, 5);
+    }
+}
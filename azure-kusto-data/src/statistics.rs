@@ -0,0 +1,280 @@
+//! Statistics of the execution of a query, as reported by the service in the `QueryResourceConsumption` event
+//! of the `QueryCompletionInformation` table of V2 responses.
+//!
+//! Get them with [KustoResponseDataSetV2::query_statistics].
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::models::{DataTable, TableKind, V2QueryResult};
+use crate::operations::query::KustoResponseDataSetV2;
+use crate::types::KustoTimespan;
+
+/// The resources consumed by a query, and statistics of the data it read and returned.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct QueryStatistics {
+    /// The time the query took to execute on the service, in seconds.
+    #[serde(rename = "ExecutionTime")]
+    pub execution_time: f64,
+    /// The resources used by the query.
+    pub resource_usage: ResourceUsage,
+    /// Statistics of the data that the query read.
+    pub input_dataset_statistics: Option<InputDatasetStatistics>,
+    /// Statistics of each of the tables that the query returned.
+    pub dataset_statistics: Vec<TableStatistics>,
+}
+
+impl QueryStatistics {
+    /// The time the query took to execute on the service.
+    #[must_use]
+    pub fn execution_duration(&self) -> Duration {
+        if self.execution_time.is_finite() && self.execution_time > 0.0 {
+            Duration::from_secs_f64(self.execution_time.min(u32::MAX.into()))
+        } else {
+            Duration::ZERO
+        }
+    }
+}
+
+/// The resources used by a query.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct ResourceUsage {
+    /// Use of the caches of the cluster.
+    pub cache: CacheUsage,
+    /// CPU time used by the query.
+    pub cpu: CpuUsage,
+    /// Memory used by the query.
+    pub memory: MemoryUsage,
+}
+
+/// Use of the caches of the cluster by a query.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct CacheUsage {
+    /// Use of the memory cache.
+    pub memory: CacheHits,
+    /// Use of the disk cache.
+    pub disk: CacheHits,
+}
+
+/// Hits and misses of a cache.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct CacheHits {
+    /// Number of lookups that were found in the cache.
+    pub hits: u64,
+    /// Number of lookups that were not found in the cache.
+    pub misses: u64,
+    /// Total number of lookups.
+    pub total: u64,
+}
+
+impl CacheHits {
+    /// The ratio of lookups that were found in the cache, between 0 and 1, or `None` if there were no lookups.
+    #[must_use]
+    pub fn hit_ratio(&self) -> Option<f64> {
+        if self.total == 0 {
+            None
+        } else {
+            Some(self.hits as f64 / self.total as f64)
+        }
+    }
+}
+
+/// CPU time used by a query, as timespans in the format of the service.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct CpuUsage {
+    /// Time spent in user mode.
+    pub user: String,
+    /// Time spent in kernel mode.
+    pub kernel: String,
+    /// Total CPU time.
+    #[serde(rename = "total cpu", alias = "totalcpu")]
+    pub total_cpu: String,
+}
+
+impl CpuUsage {
+    /// The total CPU time, if it is a valid timespan.
+    #[must_use]
+    pub fn total(&self) -> Option<KustoTimespan> {
+        self.total_cpu.parse().ok()
+    }
+}
+
+/// Memory used by a query.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct MemoryUsage {
+    /// The peak memory used by the query on any node, in bytes.
+    pub peak_per_node: u64,
+}
+
+/// Statistics of the data that a query read.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct InputDatasetStatistics {
+    /// Extents of the tables that the query read.
+    pub extents: ScanCounts,
+    /// Rows of the tables that the query read.
+    pub rows: ScanCounts,
+}
+
+/// How many of the items of the data were scanned by a query.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct ScanCounts {
+    /// Number of items in the data.
+    pub total: u64,
+    /// Number of items that were scanned.
+    pub scanned: u64,
+}
+
+/// Statistics of a table returned by a query.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct TableStatistics {
+    /// Number of rows in the table.
+    pub table_row_count: u64,
+    /// Size of the table, in bytes.
+    pub table_size: u64,
+}
+
+impl KustoResponseDataSetV2 {
+    /// Parses the statistics of the query from the `QueryResourceConsumption` event of the `QueryCompletionInformation` table.
+    ///
+    /// Returns `None` if the response has no such event, such as when it was not fully received.
+    /// # Example
+    /// ```no_run
+    /// use azure_kusto_data::prelude::*;
+    /// # #[tokio::main] async fn main() -> Result<(), Error> {
+    /// let client = KustoClient::new(
+    ///    ConnectionString::with_default_auth("https://mycluster.region.kusto.windows.net/"),
+    ///    KustoClientOptions::default())?;
+    ///
+    /// let response = client.execute_query("some_database", "MyTable | take 10", None).await?;
+    /// if let Some(statistics) = response.query_statistics()? {
+    ///     println!("executed in {:?}", statistics.execution_duration());
+    ///     println!("memory cache hit ratio: {:?}", statistics.resource_usage.cache.memory.hit_ratio());
+    /// }
+    /// # Ok(())}
+    /// ```
+    pub fn query_statistics(&self) -> Result<Option<QueryStatistics>> {
+        for result in &self.results {
+            if let V2QueryResult::DataTable(table) = result {
+                if let Some(statistics) = completion_statistics(table)? {
+                    return Ok(Some(statistics));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// Parses the statistics of the query from the `QueryResourceConsumption` event of a table, if it is the `QueryCompletionInformation` table.
+pub(crate) fn completion_statistics(table: &DataTable) -> Result<Option<QueryStatistics>> {
+    if table.table_kind != TableKind::QueryCompletionInformation
+        || table.column_index("EventTypeName").is_none()
+        || table.column_index("Payload").is_none()
+    {
+        return Ok(None);
+    }
+
+    for row in table.row_views() {
+        if row.get_string("EventTypeName")?.as_deref() != Some("QueryResourceConsumption") {
+            continue;
+        }
+        let payload = row.get_string("Payload")?.ok_or_else(|| {
+            Error::ConversionError(
+                "query statistics, the QueryResourceConsumption event has no payload".to_string(),
+            )
+        })?;
+        return Ok(Some(serde_json::from_str(&payload)?));
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::load_v2_response;
+
+    #[test]
+    fn parses_query_statistics() {
+        let statistics = load_v2_response("dataframe.json")
+            .query_statistics()
+            .expect("Failed to parse statistics")
+            .expect("Expected statistics");
+
+        assert_eq!(statistics.execution_time, 0.0156222);
+        assert_eq!(statistics.execution_duration().as_micros(), 15622);
+
+        let cache = &statistics.resource_usage.cache;
+        assert_eq!(
+            cache.memory,
+            CacheHits {
+                hits: 13,
+                misses: 0,
+                total: 13
+            }
+        );
+        assert_eq!(cache.memory.hit_ratio(), Some(1.0));
+        assert_eq!(cache.disk.hit_ratio(), None);
+
+        assert_eq!(statistics.resource_usage.memory.peak_per_node, 16777312);
+        assert_eq!(statistics.resource_usage.cpu.total_cpu, "00: 00: 00");
+        assert_eq!(
+            statistics.dataset_statistics,
+            vec![TableStatistics {
+                table_row_count: 3,
+                table_size: 191
+            }]
+        );
+        assert_eq!(statistics.input_dataset_statistics, None);
+    }
+
+    #[test]
+    fn parses_service_payloads() {
+        let statistics: QueryStatistics = serde_json::from_str(
+            r#"{"ExecutionTime":1.5,"resource_usage":{"cache":{"memory":{"hits":3,"misses":1,"total":4},"disk":{"hits":1,"misses":0,"total":1},"shards":{}},"cpu":{"user":"00:00:00.0312500","kernel":"00:00:00","total cpu":"00:00:00.0312500","breakdown":{}},"memory":{"peak_per_node":1048576},"network":{}},"input_dataset_statistics":{"extents":{"total":10,"scanned":2},"rows":{"total":1000,"scanned":200}},"dataset_statistics":[],"cross_cluster_resource_usage":{}}"#,
+        )
+        .expect("Failed to parse statistics");
+
+        assert_eq!(statistics.execution_duration(), Duration::from_millis(1500));
+        assert_eq!(
+            statistics.resource_usage.cache.memory.hit_ratio(),
+            Some(0.75)
+        );
+        assert_eq!(
+            statistics.resource_usage.cpu.total(),
+            Some(KustoTimespan::from_ticks(312_500))
+        );
+        assert_eq!(
+            statistics.input_dataset_statistics,
+            Some(InputDatasetStatistics {
+                extents: ScanCounts {
+                    total: 10,
+                    scanned: 2
+                },
+                rows: ScanCounts {
+                    total: 1000,
+                    scanned: 200
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn responses_without_statistics() {
+        let statistics = load_v2_response("validFrames.json")
+            .query_statistics()
+            .expect("Failed to parse statistics");
+        assert_eq!(statistics, None);
+    }
+}
@@ -0,0 +1,330 @@
+//! Propagation of the [W3C trace context](https://www.w3.org/TR/trace-context/) of the application to the service,
+//! so that the logs of the service can be joined with the traces of the application.
+//!
+//! When a trace context is current, requests carry it in their `traceparent` header, and the client request ids generated
+//! for them embed its trace id, as `KRust;<trace id>;<unique id>`. Without one, requests are sent as they are otherwise.
+//!
+//! The current trace context is read from the provider set with
+//! [KustoClientOptions::with_trace_context_provider](crate::client::KustoClientOptions::with_trace_context_provider), or,
+//! when the `tracing` feature is enabled, from the current span or its parents, as attached with [set_span_trace_context].
+//! With the `opentelemetry` feature, the OpenTelemetry context of the current span, as tracked by
+//! [tracing-opentelemetry](https://docs.rs/tracing-opentelemetry), is used when no context was attached.
+
+use std::fmt::{Debug, Display, Formatter};
+use std::sync::Arc;
+
+/// Returns the trace context of the current operation of the application, if any.
+pub type TraceContextProvider = Arc<dyn Fn() -> Option<TraceContext> + Send + Sync>;
+
+/// A W3C trace context, as carried by the `traceparent` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceContext {
+    trace_id: [u8; 16],
+    parent_id: [u8; 8],
+    flags: u8,
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn from_hex<const N: usize>(hex: &str) -> Option<[u8; N]> {
+    if hex.len() != N * 2 || !hex.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')) {
+        return None;
+    }
+    let mut bytes = [0; N];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(bytes)
+}
+
+impl TraceContext {
+    /// Creates a trace context, returning `None` if the trace id or parent id are all zeros, which the specification makes invalid.
+    #[must_use]
+    pub fn new(trace_id: [u8; 16], parent_id: [u8; 8], flags: u8) -> Option<Self> {
+        if trace_id == [0; 16] || parent_id == [0; 8] {
+            return None;
+        }
+        Some(Self {
+            trace_id,
+            parent_id,
+            flags,
+        })
+    }
+
+    /// Parses the value of a `traceparent` header, such as `00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01`.
+    /// Returns `None` for invalid values, which the specification requires to be ignored.
+    #[must_use]
+    pub fn parse(traceparent: &str) -> Option<Self> {
+        let mut parts = traceparent.trim().split('-');
+        let version = from_hex::<1>(parts.next()?)?[0];
+        let trace_id = from_hex(parts.next()?)?;
+        let parent_id = from_hex(parts.next()?)?;
+        let flags = from_hex::<1>(parts.next()?)?[0];
+        // Later versions may append fields, but version 0 has exactly these ones
+        if version == 0xff || (version == 0 && parts.next().is_some()) {
+            return None;
+        }
+        Self::new(trace_id, parent_id, flags)
+    }
+
+    /// The trace id, as 32 lowercase hex digits.
+    #[must_use]
+    pub fn trace_id(&self) -> String {
+        to_hex(&self.trace_id)
+    }
+
+    /// The id of the span the requests are made in, as 16 lowercase hex digits.
+    #[must_use]
+    pub fn parent_id(&self) -> String {
+        to_hex(&self.parent_id)
+    }
+
+    /// Whether the trace is sampled by the application.
+    #[must_use]
+    pub fn sampled(&self) -> bool {
+        self.flags & 1 == 1
+    }
+
+    /// The value of the `traceparent` header for this context, in version 0 of the format.
+    #[must_use]
+    pub fn traceparent(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl Display for TraceContext {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "00-{}-{}-{:02x}",
+            self.trace_id(),
+            self.parent_id(),
+            self.flags
+        )
+    }
+}
+
+/// Attaches a trace context to a span, so that the requests made within it, or within its children, carry it.
+///
+/// The context is stored in the span data of the [tracing_subscriber::Registry], so this has no effect, and returns `false`,
+/// when the current subscriber isn't built on it, or the span is disabled.
+#[cfg(feature = "tracing")]
+pub fn set_span_trace_context(span: &tracing::Span, context: TraceContext) -> bool {
+    use tracing_subscriber::registry::LookupSpan;
+
+    span.with_subscriber(|(id, dispatch)| {
+        dispatch
+            .downcast_ref::<tracing_subscriber::Registry>()
+            .and_then(|registry| registry.span(id))
+            .map(|span| {
+                span.extensions_mut().replace(context);
+            })
+            .is_some()
+    })
+    .unwrap_or(false)
+}
+
+/// The trace context attached to the current span, or to the closest of its parents, and otherwise the OpenTelemetry
+/// context of the current span.
+#[cfg(feature = "tracing")]
+fn from_current_span() -> Option<TraceContext> {
+    use tracing_subscriber::registry::LookupSpan;
+
+    let current = tracing::Span::current();
+    current
+        .with_subscriber(|(id, dispatch)| {
+            let registry = dispatch.downcast_ref::<tracing_subscriber::Registry>()?;
+            let span = registry.span(id)?;
+            span.scope()
+                .find_map(|span| span.extensions().get::<TraceContext>().copied())
+        })
+        .flatten()
+        .or_else(|| from_opentelemetry_span(&current))
+}
+
+/// The OpenTelemetry context of a span, which is only tracked when the subscriber has an `OpenTelemetryLayer`.
+#[cfg(feature = "opentelemetry")]
+fn from_opentelemetry_span(span: &tracing::Span) -> Option<TraceContext> {
+    use opentelemetry::trace::TraceContextExt;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    let context = span.context();
+    let span_context = context.span().span_context().clone();
+    if !span_context.is_valid() {
+        return None;
+    }
+    TraceContext::new(
+        span_context.trace_id().to_bytes(),
+        span_context.span_id().to_bytes(),
+        span_context.trace_flags().to_u8(),
+    )
+}
+
+#[cfg(all(feature = "tracing", not(feature = "opentelemetry")))]
+fn from_opentelemetry_span(_span: &tracing::Span) -> Option<TraceContext> {
+    None
+}
+
+#[cfg(not(feature = "tracing"))]
+fn from_current_span() -> Option<TraceContext> {
+    None
+}
+
+/// Where a client reads the current trace context from: the provider of its options when set, and otherwise the current span.
+#[derive(Clone, Default)]
+pub(crate) struct TraceContextSource {
+    provider: Option<TraceContextProvider>,
+}
+
+impl Debug for TraceContextSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TraceContextSource")
+            .field("provider", &self.provider.is_some())
+            .finish()
+    }
+}
+
+impl TraceContextSource {
+    pub(crate) fn new(provider: Option<TraceContextProvider>) -> Self {
+        Self { provider }
+    }
+
+    /// The current trace context, if any.
+    pub(crate) fn current(&self) -> Option<TraceContext> {
+        match &self.provider {
+            Some(provider) => provider(),
+            None => from_current_span(),
+        }
+    }
+}
+
+/// Generates a client request id, embedding the trace id of the context when there is one.
+pub(crate) fn new_client_request_id(context: Option<&TraceContext>) -> String {
+    match context {
+        Some(context) => format!("KRust;{};{}", context.trace_id(), uuid::Uuid::new_v4()),
+        None => format!("KRust;{}", uuid::Uuid::new_v4()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    #[test]
+    fn parses_and_formats_traceparent() {
+        let context = TraceContext::parse(TRACEPARENT).expect("Failed to parse");
+        assert_eq!(context.trace_id(), "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(context.parent_id(), "00f067aa0ba902b7");
+        assert!(context.sampled());
+        assert_eq!(context.traceparent(), TRACEPARENT);
+
+        // Fields appended by later versions are ignored
+        let context =
+            TraceContext::parse("01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00-extra")
+                .expect("Failed to parse a later version");
+        assert!(!context.sampled());
+        assert_eq!(
+            context.traceparent(),
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00"
+        );
+    }
+
+    #[test]
+    fn invalid_traceparents_are_ignored() {
+        for traceparent in [
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e473-00f067aa0ba902b7-01",
+        ] {
+            assert_eq!(TraceContext::parse(traceparent), None, "{traceparent}");
+        }
+    }
+
+    #[test]
+    fn client_request_ids_embed_the_trace_id() {
+        let context = TraceContext::parse(TRACEPARENT).unwrap();
+        let id = new_client_request_id(Some(&context));
+        let parts: Vec<_> = id.split(';').collect();
+        assert_eq!(parts.len(), 3);
+        assert_eq!(parts[0], "KRust");
+        assert_eq!(parts[1], "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert!(uuid::Uuid::parse_str(parts[2]).is_ok());
+
+        let id = new_client_request_id(None);
+        assert!(id.starts_with("KRust;"));
+        assert!(uuid::Uuid::parse_str(&id["KRust;".len()..]).is_ok());
+    }
+
+    #[test]
+    fn the_provider_takes_precedence() {
+        let context = TraceContext::parse(TRACEPARENT).unwrap();
+        let source = TraceContextSource::new(Some(Arc::new(move || Some(context))));
+        assert_eq!(source.current(), Some(context));
+        assert_eq!(TraceContextSource::default().current(), None);
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn contexts_are_read_from_the_current_span_or_its_parents() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let context = TraceContext::parse(TRACEPARENT).unwrap();
+        let _guard = tracing::subscriber::set_default(
+            tracing_subscriber::registry().with(tracing_subscriber::layer::Identity::new()),
+        );
+
+        let outer = tracing::info_span!("outer");
+        assert!(set_span_trace_context(&outer, context));
+        let _outer = outer.enter();
+        let _inner = tracing::info_span!("inner").entered();
+        assert_eq!(TraceContextSource::default().current(), Some(context));
+    }
+
+    #[cfg(feature = "opentelemetry")]
+    #[test]
+    fn contexts_are_read_from_the_opentelemetry_context_of_the_span() {
+        use opentelemetry::trace::{
+            SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState,
+        };
+        use tracing_opentelemetry::OpenTelemetrySpanExt;
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let context = TraceContext::parse(TRACEPARENT).unwrap();
+        let _guard = tracing::subscriber::set_default(
+            tracing_subscriber::registry().with(
+                tracing_opentelemetry::layer()
+                    .with_tracer(opentelemetry::trace::noop::NoopTracer::new()),
+            ),
+        );
+
+        let outer = tracing::info_span!("outer");
+        outer.set_parent(
+            opentelemetry::Context::new().with_remote_span_context(SpanContext::new(
+                TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap(),
+                SpanId::from_hex("00f067aa0ba902b7").unwrap(),
+                TraceFlags::SAMPLED,
+                true,
+                TraceState::default(),
+            )),
+        );
+        let _outer = outer.enter();
+        let _inner = tracing::info_span!("inner").entered();
+        assert_eq!(TraceContextSource::default().current(), Some(context));
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn no_context_without_a_span() {
+        let _guard = tracing::subscriber::set_default(tracing_subscriber::registry());
+        assert_eq!(TraceContextSource::default().current(), None);
+    }
+}
@@ -0,0 +1,164 @@
+//! Well-known Kusto endpoints, which the client sends tokens and follows redirects to.
+//! Tokens sent to any other host could be used by that host to impersonate the caller, so they are refused
+//! unless [KustoClientOptions::with_allow_untrusted_endpoints](crate::client::KustoClientOptions::with_allow_untrusted_endpoints) is set.
+use crate::cloud_info::CloudInfo;
+use azure_core::Url;
+
+/// Domains of the Kusto endpoints of each cloud, by the login endpoint of the cloud, as found in its [CloudInfo](crate::cloud_info::CloudInfo).
+const WELL_KNOWN_DOMAINS: &[(&str, &[&str])] = &[
+    (
+        "https://login.microsoftonline.com",
+        &[
+            ".kusto.windows.net",
+            ".kustomfa.windows.net",
+            ".kusto.data.microsoft.com",
+            ".kusto.fabric.microsoft.com",
+            ".kusto.azuresynapse.net",
+        ],
+    ),
+    (
+        "https://login.chinacloudapi.cn",
+        &[
+            ".kusto.chinacloudapi.cn",
+            ".kustomfa.chinacloudapi.cn",
+            ".kusto.azuresynapse.azure.cn",
+        ],
+    ),
+    (
+        "https://login.microsoftonline.us",
+        &[
+            ".kusto.usgovcloudapi.net",
+            ".kustomfa.usgovcloudapi.net",
+            ".kusto.azuresynapse.usgovcloudapi.net",
+        ],
+    ),
+];
+
+/// Hosts of the machine itself, such as the Kusto emulator, which tokens don't leave.
+const LOOPBACK_HOSTS: &[&str] = &["localhost", "127.0.0.1", "[::1]"];
+
+/// The parent domain of the host of an endpoint, such as `.contoso.cloud` for `https://login.contoso.cloud`.
+/// Returns `None` when the parent is a top-level domain, which would trust every host under it.
+fn parent_domain(endpoint: &str) -> Option<String> {
+    let url = Url::parse(endpoint).ok()?;
+    let host = url.host_str()?.to_ascii_lowercase();
+    let (_, parent) = host.split_once('.')?;
+    parent.contains('.').then(|| format!(".{parent}"))
+}
+
+/// Whether the url is a well-known Kusto endpoint, or on the loopback interface.
+///
+/// When the cloud is known, only the domains of that cloud are trusted, otherwise those of all clouds are.
+/// The domains of a private cloud, whose login endpoint isn't well-known, are those of its login endpoint
+/// and of its Kusto service resource.
+pub(crate) fn is_trusted_endpoint(url: &Url, cloud_info: Option<&CloudInfo>) -> bool {
+    let host = match url.host_str() {
+        Some(host) => host.to_ascii_lowercase(),
+        None => return false,
+    };
+    if LOOPBACK_HOSTS.contains(&host.as_str()) {
+        return true;
+    }
+
+    let cloud_info = match cloud_info {
+        Some(cloud_info) => cloud_info,
+        None => {
+            return WELL_KNOWN_DOMAINS
+                .iter()
+                .flat_map(|(_, domains)| domains.iter())
+                .any(|domain| host.ends_with(domain))
+        }
+    };
+    let login_endpoint = cloud_info
+        .login_endpoint
+        .trim_end_matches('/')
+        .to_ascii_lowercase();
+    match WELL_KNOWN_DOMAINS
+        .iter()
+        .find(|(cloud, _)| *cloud == login_endpoint)
+    {
+        Some((_, domains)) => domains.iter().any(|domain| host.ends_with(domain)),
+        None => [
+            cloud_info.login_endpoint.as_ref(),
+            cloud_info.kusto_service_resource_id.as_ref(),
+        ]
+        .into_iter()
+        .filter_map(parent_domain)
+        .any(|domain| host.ends_with(&domain)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_trusted(url: &str, login_endpoint: Option<&str>) -> bool {
+        let cloud_info = login_endpoint.map(|login_endpoint| CloudInfo {
+            login_endpoint: login_endpoint.to_string().into(),
+            ..CloudInfo::default()
+        });
+        is_trusted_endpoint(&Url::parse(url).unwrap(), cloud_info.as_ref())
+    }
+
+    #[test]
+    fn public_endpoints_are_trusted() {
+        assert!(is_trusted("https://help.kusto.windows.net", None));
+        assert!(is_trusted(
+            "https://Cluster.Region.KUSTO.windows.net/",
+            None
+        ));
+        assert!(is_trusted(
+            "https://ingest-cluster.region.kusto.windows.net",
+            None
+        ));
+        assert!(is_trusted(
+            "https://trd-abc.z0.kusto.fabric.microsoft.com",
+            Some("https://login.microsoftonline.com/")
+        ));
+        assert!(is_trusted("http://localhost:8080", None));
+        assert!(is_trusted("http://127.0.0.1:8080", None));
+    }
+
+    #[test]
+    fn other_hosts_are_not_trusted() {
+        assert!(!is_trusted("https://attacker.example.com", None));
+        assert!(!is_trusted("https://kusto.windows.net.attacker.com", None));
+        assert!(!is_trusted("https://mykusto.windows.net", None));
+        assert!(!is_trusted("https://localhost.attacker.com", None));
+    }
+
+    #[test]
+    fn domains_are_trusted_for_their_cloud() {
+        let china = Some("https://login.chinacloudapi.cn");
+        assert!(is_trusted("https://cluster.kusto.chinacloudapi.cn", china));
+        assert!(!is_trusted("https://cluster.kusto.windows.net", china));
+    }
+
+    #[test]
+    fn private_clouds_trust_their_own_domains() {
+        let cloud_info = CloudInfo {
+            login_endpoint: "https://login.contoso.cloud/".into(),
+            kusto_service_resource_id: "https://kusto.data.contoso.net".into(),
+            ..CloudInfo::default()
+        };
+        let is_trusted =
+            |url: &str| is_trusted_endpoint(&Url::parse(url).unwrap(), Some(&cloud_info));
+
+        assert!(is_trusted("https://cluster.region.contoso.cloud"));
+        assert!(is_trusted("https://cluster.data.contoso.net"));
+        assert!(!is_trusted("https://cluster.contoso.net"));
+        assert!(!is_trusted("https://contoso.cloud.attacker.com"));
+        assert!(!is_trusted("https://cluster.kusto.windows.net"));
+
+        // A top-level domain alone is not trusted
+        let cloud_info = CloudInfo {
+            login_endpoint: "https://login.cloud".into(),
+            kusto_service_resource_id: "https://kusto.cloud".into(),
+            ..CloudInfo::default()
+        };
+        assert!(!is_trusted_endpoint(
+            &Url::parse("https://attacker.cloud").unwrap(),
+            Some(&cloud_info)
+        ));
+    }
+}
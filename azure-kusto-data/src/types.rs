@@ -3,26 +3,60 @@
 use azure_core::error::{ErrorKind, ResultExt};
 use once_cell::sync::Lazy;
 use regex::{Captures, Regex};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
 use serde_with::{DeserializeFromStr, SerializeDisplay};
 use std::fmt::{Debug, Display, Formatter};
-use std::ops::Deref;
+use std::ops::{Add, Deref, Neg, Sub};
 use std::str::FromStr;
 use time::{Duration, OffsetDateTime};
 
 use crate::error::{Error, InvalidArgumentError};
 use time::format_description::well_known::Rfc3339;
+use time::format_description::FormatItem;
+use time::macros::format_description;
+use time::{Date, PrimitiveDateTime, UtcOffset};
 
 /// Represents a datetime field for kusto, for serialization and deserialization.
-#[derive(PartialEq, Eq, Copy, Clone, DeserializeFromStr, SerializeDisplay)]
+///
+/// Parsing accepts RFC3339 datetimes, as well as the shapes Kusto emits from some commands, which are assumed to be in UTC:
+/// datetimes without an offset, separated with either a `T` or a space, and bare dates.
+/// Datetimes are always formatted as RFC3339 in UTC with 7 fractional digits, the precision of Kusto, so that they round-trip losslessly.
+#[derive(
+    PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone, DeserializeFromStr, SerializeDisplay,
+)]
 pub struct KustoDateTime(pub OffsetDateTime);
 
+/// A datetime without an offset, e.g. `2020-03-04T14:05:01.3109965`
+const ISO_WITHOUT_OFFSET: &[FormatItem<'static>] =
+    format_description!("[year]-[month]-[day]T[hour]:[minute]:[second][optional [.[subsecond]]]");
+
+/// A datetime separated with a space and without an offset, e.g. `2020-03-04 14:05:01.3109965`
+const SPACE_SEPARATED: &[FormatItem<'static>] =
+    format_description!("[year]-[month]-[day] [hour]:[minute]:[second][optional [.[subsecond]]]");
+
+/// A bare date, e.g. `2020-03-04`
+const BARE_DATE: &[FormatItem<'static>] = format_description!("[year]-[month]-[day]");
+
+/// RFC3339 in UTC with the 7 fractional digits (ticks) that Kusto keeps, e.g. `2020-03-04T14:05:01.3109965Z`
+const KUSTO_FORMAT: &[FormatItem<'static>] =
+    format_description!("[year]-[month]-[day]T[hour]:[minute]:[second].[subsecond digits:7]Z");
+
 impl FromStr for KustoDateTime {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(OffsetDateTime::parse(s, &Rfc3339)
-            .map(KustoDateTime)
-            .context(ErrorKind::DataConversion, "Failed to parse KustoDateTime")?)
+        if let Ok(datetime) = OffsetDateTime::parse(s, &Rfc3339) {
+            return Ok(KustoDateTime(datetime));
+        }
+
+        Ok(PrimitiveDateTime::parse(s, ISO_WITHOUT_OFFSET)
+            .or_else(|_| PrimitiveDateTime::parse(s, SPACE_SEPARATED))
+            .or_else(|_| Date::parse(s, BARE_DATE).map(Date::midnight))
+            .map(|datetime| KustoDateTime(datetime.assume_utc()))
+            .with_context(ErrorKind::DataConversion, || {
+                format!("Failed to parse KustoDateTime from '{s}'")
+            })?)
     }
 }
 
@@ -31,7 +65,10 @@ impl Display for KustoDateTime {
         write!(
             f,
             "{}",
-            self.0.format(&Rfc3339).unwrap_or_else(|_| "".into())
+            self.0
+                .to_offset(UtcOffset::UTC)
+                .format(KUSTO_FORMAT)
+                .unwrap_or_else(|_| "".into())
         )?;
         Ok(())
     }
@@ -49,6 +86,29 @@ impl From<OffsetDateTime> for KustoDateTime {
     }
 }
 
+/// Converts a [KustoDateTime] into a chrono [DateTime](chrono::DateTime) in UTC.
+#[cfg(feature = "chrono")]
+impl From<KustoDateTime> for chrono::DateTime<chrono::Utc> {
+    fn from(datetime: KustoDateTime) -> Self {
+        chrono::DateTime::from_timestamp(datetime.unix_timestamp(), datetime.nanosecond())
+            .expect("Every datetime of the time crate is within the range of chrono")
+    }
+}
+
+/// Converts a chrono [DateTime](chrono::DateTime) into a [KustoDateTime].
+/// Fails if the datetime is outside of the range of the time crate, years -9999 to 9999.
+#[cfg(feature = "chrono")]
+impl TryFrom<chrono::DateTime<chrono::Utc>> for KustoDateTime {
+    type Error = Error;
+
+    fn try_from(datetime: chrono::DateTime<chrono::Utc>) -> Result<Self, Self::Error> {
+        OffsetDateTime::from_unix_timestamp(datetime.timestamp())
+            .and_then(|d| d.replace_nanosecond(datetime.timestamp_subsec_nanos()))
+            .map(KustoDateTime)
+            .map_err(|_| InvalidArgumentError::DateTimeOutOfRange(datetime.to_rfc3339()).into())
+    }
+}
+
 impl Deref for KustoDateTime {
     type Target = OffsetDateTime;
 
@@ -57,17 +117,134 @@ impl Deref for KustoDateTime {
     }
 }
 
-/// Represent a timespan for kusto, for serialization and deserialization.
-#[derive(PartialEq, Eq, Copy, Clone, DeserializeFromStr, SerializeDisplay)]
-pub struct KustoDuration(pub Duration);
+impl KustoDateTime {
+    /// Adds a timespan to the datetime, or returns `None` if the result is outside of the range of datetimes.
+    #[must_use]
+    pub fn checked_add(self, rhs: KustoTimespan) -> Option<Self> {
+        self.0.checked_add(rhs.0).map(Self)
+    }
+
+    /// Subtracts a timespan from the datetime, or returns `None` if the result is outside of the range of datetimes.
+    #[must_use]
+    pub fn checked_sub(self, rhs: KustoTimespan) -> Option<Self> {
+        self.0.checked_sub(rhs.0).map(Self)
+    }
+}
+
+/// Adds a timespan to a datetime, saturating at the limits of the range of datetimes.
+/// Use [KustoDateTime::checked_add] to detect overflow.
+impl Add<KustoTimespan> for KustoDateTime {
+    type Output = KustoDateTime;
+
+    fn add(self, rhs: KustoTimespan) -> Self::Output {
+        Self(self.0.saturating_add(rhs.0))
+    }
+}
 
-impl From<Duration> for KustoDuration {
+/// Subtracts a timespan from a datetime, saturating at the limits of the range of datetimes.
+/// Use [KustoDateTime::checked_sub] to detect overflow.
+impl Sub<KustoTimespan> for KustoDateTime {
+    type Output = KustoDateTime;
+
+    fn sub(self, rhs: KustoTimespan) -> Self::Output {
+        Self(self.0.saturating_sub(rhs.0))
+    }
+}
+
+impl Sub for KustoDateTime {
+    type Output = KustoTimespan;
+
+    fn sub(self, rhs: KustoDateTime) -> Self::Output {
+        KustoTimespan::saturating(self.0 - rhs.0)
+    }
+}
+
+/// Represents a timespan for kusto, for serialization and deserialization.
+///
+/// Timespans have the precision and range of Kusto: ticks of 100 nanoseconds, between [KustoTimespan::MIN] and [KustoTimespan::MAX].
+/// Parsing accepts the `[-][d.]hh:mm[:ss[.fffffff]]` format, as well as a bare number of ticks, which Kusto returns in some results.
+/// Timespans are always formatted as `[-][d.]hh:mm:ss.fffffff`, so that they round-trip losslessly.
+///
+/// Arithmetic saturates at the limits of Kusto, and adding or subtracting a null (`None`) timespan results in null:
+/// ```
+/// # use azure_kusto_data::types::KustoTimespan;
+/// # use std::str::FromStr;
+/// let hour = KustoTimespan::from_str("01:00").unwrap();
+/// assert_eq!((hour + hour).to_string(), "02:00:00.0000000");
+/// assert_eq!((-hour).to_string(), "-01:00:00.0000000");
+/// assert_eq!(hour + None, None);
+/// assert_eq!(KustoTimespan::MAX + hour, KustoTimespan::MAX);
+/// ```
+#[derive(
+    PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone, DeserializeFromStr, SerializeDisplay,
+)]
+pub struct KustoTimespan(pub Duration);
+
+/// The timespan type of kusto, under its former name.
+#[deprecated(note = "Use KustoTimespan")]
+pub type KustoDuration = KustoTimespan;
+
+const NANOS_PER_TICK: i64 = 100;
+const TICKS_PER_SECOND: i64 = 10_000_000;
+
+impl KustoTimespan {
+    /// The smallest timespan Kusto supports, `-10675199.02:48:05.4775808`.
+    pub const MIN: KustoTimespan = KustoTimespan::from_ticks(i64::MIN);
+    /// The largest timespan Kusto supports, `10675199.02:48:05.4775807`.
+    pub const MAX: KustoTimespan = KustoTimespan::from_ticks(i64::MAX);
+    /// The empty timespan.
+    pub const ZERO: KustoTimespan = KustoTimespan(Duration::ZERO);
+
+    /// Creates a timespan from a number of ticks of 100 nanoseconds, the unit of Kusto.
+    #[must_use]
+    pub const fn from_ticks(ticks: i64) -> Self {
+        Self(Duration::new(
+            ticks / TICKS_PER_SECOND,
+            ((ticks % TICKS_PER_SECOND) * NANOS_PER_TICK) as i32,
+        ))
+    }
+
+    /// The number of whole ticks of 100 nanoseconds in the timespan, saturated to the range of an `i64`.
+    #[must_use]
+    pub fn ticks(&self) -> i64 {
+        let ticks = self.0.whole_nanoseconds() / i128::from(NANOS_PER_TICK);
+        i64::try_from(ticks).unwrap_or(if ticks < 0 { i64::MIN } else { i64::MAX })
+    }
+
+    /// Clamps a duration to the range of timespans Kusto supports.
+    fn saturating(duration: Duration) -> Self {
+        Self(duration.clamp(Self::MIN.0, Self::MAX.0))
+    }
+}
+
+/// Converts a [Duration] into a [KustoTimespan].
+/// Durations beyond the limits of Kusto are saturated to [KustoTimespan::MIN] or [KustoTimespan::MAX].
+impl From<Duration> for KustoTimespan {
     fn from(duration: Duration) -> Self {
-        Self(duration)
+        Self::saturating(duration)
+    }
+}
+
+/// Converts a [std::time::Duration] into a [KustoTimespan].
+/// Durations that are too large to be represented are saturated to [KustoTimespan::MAX].
+impl From<std::time::Duration> for KustoTimespan {
+    fn from(duration: std::time::Duration) -> Self {
+        Self::saturating(Duration::try_from(duration).unwrap_or(Duration::MAX))
     }
 }
 
-impl Deref for KustoDuration {
+/// Converts a [KustoTimespan] into a [std::time::Duration].
+/// Fails if the timespan is negative, since [std::time::Duration] cannot represent negative spans.
+impl TryFrom<KustoTimespan> for std::time::Duration {
+    type Error = Error;
+
+    fn try_from(timespan: KustoTimespan) -> Result<Self, Self::Error> {
+        std::time::Duration::try_from(timespan.0)
+            .map_err(|_| InvalidArgumentError::NegativeDuration(timespan.to_string()).into())
+    }
+}
+
+impl Deref for KustoTimespan {
     type Target = Duration;
 
     fn deref(&self) -> &Self::Target {
@@ -75,45 +252,104 @@ impl Deref for KustoDuration {
     }
 }
 
-fn parse_regex_segment(captures: &Captures, name: &str) -> i64 {
-    captures
-        .name(name)
-        .map_or(0, |m| m.as_str().parse::<i64>().expect("Failed to parse regex segment as i64 - this is a bug - please report this issue to the Kusto team"))
+impl Add for KustoTimespan {
+    type Output = KustoTimespan;
+
+    fn add(self, rhs: KustoTimespan) -> Self::Output {
+        Self::saturating(self.0.saturating_add(rhs.0))
+    }
+}
+
+impl Sub for KustoTimespan {
+    type Output = KustoTimespan;
+
+    fn sub(self, rhs: KustoTimespan) -> Self::Output {
+        Self::saturating(self.0.saturating_sub(rhs.0))
+    }
+}
+
+impl Neg for KustoTimespan {
+    type Output = KustoTimespan;
+
+    fn neg(self) -> Self::Output {
+        Self::saturating(Duration::ZERO.saturating_sub(self.0))
+    }
+}
+
+impl Add<Option<KustoTimespan>> for KustoTimespan {
+    type Output = Option<KustoTimespan>;
+
+    fn add(self, rhs: Option<KustoTimespan>) -> Self::Output {
+        rhs.map(|rhs| self + rhs)
+    }
+}
+
+impl Sub<Option<KustoTimespan>> for KustoTimespan {
+    type Output = Option<KustoTimespan>;
+
+    fn sub(self, rhs: Option<KustoTimespan>) -> Self::Output {
+        rhs.map(|rhs| self - rhs)
+    }
+}
+
+impl Add<KustoTimespan> for Option<KustoTimespan> {
+    type Output = Option<KustoTimespan>;
+
+    fn add(self, rhs: KustoTimespan) -> Self::Output {
+        self.map(|lhs| lhs + rhs)
+    }
 }
-static KUSTO_DURATION_REGEX: Lazy<Regex> = Lazy::new(|| {
-    Regex::new(r"^(?P<neg>-)?((?P<days>\d+)\.)?(?P<hours>\d+):(?P<minutes>\d+):(?P<seconds>\d+)(\.(?P<nanos>\d+))?$")
-        .expect("Failed to compile KustoDuration regex, this should never happen - please report this issue to the Kusto team")
+
+impl Sub<KustoTimespan> for Option<KustoTimespan> {
+    type Output = Option<KustoTimespan>;
+
+    fn sub(self, rhs: KustoTimespan) -> Self::Output {
+        self.map(|lhs| lhs - rhs)
+    }
+}
+
+static KUSTO_TIMESPAN_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(?P<neg>-)?((?P<days>\d+)\.)?(?P<hours>\d+):(?P<minutes>\d+)(:(?P<seconds>\d+)(\.(?P<fraction>\d+))?)?$")
+        .expect("Failed to compile KustoTimespan regex, this should never happen - please report this issue to the Kusto team")
 });
 
-impl FromStr for KustoDuration {
+/// The ticks of a timespan in the `[-][d.]hh:mm[:ss[.fffffff]]` format, or `None` if a segment is out of range.
+fn ticks_from_captures(captures: &Captures) -> Option<i128> {
+    let segment = |name: &str| {
+        captures
+            .name(name)
+            .map_or(Some(0), |m| m.as_str().parse::<i64>().ok().map(i128::from))
+    };
+    // Digits beyond ticks are truncated, as Kusto doesn't keep them
+    let fraction = captures.name("fraction").map_or(Some(0), |m| {
+        let digits = m.as_str();
+        format!("{digits:0<7.7}").parse::<i128>().ok()
+    })?;
+
+    let seconds = ((segment("days")? * 24 + segment("hours")?) * 60 + segment("minutes")?) * 60
+        + segment("seconds")?;
+    let ticks = seconds * i128::from(TICKS_PER_SECOND) + fraction;
+    Some(match captures.name("neg") {
+        Some(_) => -ticks,
+        None => ticks,
+    })
+}
+
+impl FromStr for KustoTimespan {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        KUSTO_DURATION_REGEX
-            .captures(s)
-            .map(|captures| {
-                let neg = match captures.name("neg") {
-                    None => 1,
-                    Some(_) => -1,
-                };
-                let days = parse_regex_segment(&captures, "days");
-                let hours = parse_regex_segment(&captures, "hours");
-                let minutes = parse_regex_segment(&captures, "minutes");
-                let seconds = parse_regex_segment(&captures, "seconds");
-                let nanos = parse_regex_segment(&captures, "nanos");
-                let duration = neg
-                    * (Duration::days(days)
-                        + Duration::hours(hours)
-                        + Duration::minutes(minutes)
-                        + Duration::seconds(seconds)
-                        + Duration::nanoseconds(nanos * 100)); // Ticks
-                Self(duration)
-            })
+        let ticks = match KUSTO_TIMESPAN_REGEX.captures(s) {
+            Some(captures) => ticks_from_captures(&captures).and_then(|t| i64::try_from(t).ok()),
+            None => s.parse::<i64>().ok(),
+        };
+        ticks
+            .map(Self::from_ticks)
             .ok_or_else(|| InvalidArgumentError::InvalidDuration(s.to_string()).into())
     }
 }
 
-impl Display for KustoDuration {
+impl Display for KustoTimespan {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let neg = if self.is_negative() {
             write!(f, "-")?;
@@ -139,12 +375,52 @@ impl Display for KustoDuration {
     }
 }
 
-impl Debug for KustoDuration {
+impl Debug for KustoTimespan {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "{self}")
     }
 }
 
+/// Parses a dynamic value that the service sent as a string holding JSON, such as the `Value` column of the `@ExtendedProperties` table,
+/// into the JSON it holds.
+///
+/// Only strings that start with `{` or `[` are parsed, and those that are not valid JSON are returned untouched, like all other values.
+/// Strings nested in the parsed value are kept as they are, even if they hold JSON themselves.
+pub fn parse_dynamic(value: Value) -> Value {
+    match value {
+        Value::String(s) if s.trim_start().starts_with(['{', '[']) => {
+            serde_json::from_str(&s).unwrap_or(Value::String(s))
+        }
+        other => other,
+    }
+}
+
+/// Represents a dynamic field for kusto, whose value is deserialized as JSON whether the service sent it as JSON,
+/// or as a string holding JSON, which is parsed as [parse_dynamic] does.
+#[derive(Serialize, Default, Debug, Clone, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct KustoDynamic(pub Value);
+
+impl<'de> Deserialize<'de> for KustoDynamic {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Value::deserialize(deserializer).map(|value| KustoDynamic(parse_dynamic(value)))
+    }
+}
+
+impl Deref for KustoDynamic {
+    type Target = Value;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl From<KustoDynamic> for Value {
+    fn from(dynamic: KustoDynamic) -> Self {
+        dynamic.0
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -160,11 +436,18 @@ mod tests {
             ("-01:00:00", -3_600_000_000_000),
             ("-1.00:00:00.0000000", -86_400_000_000_000),
             ("00:00:00.1234567", 123_456_700),
+            ("00:00:00.5", 500_000_000),
+            ("01:30", 5_400_000_000_000),
+            ("1.02:30", 95_400_000_000_000),
+            ("-00:01", -60_000_000_000),
+            ("864000000000", 86_400_000_000_000),
+            ("-10", -1_000),
+            ("0", 0),
         ];
 
         for (from, to) in refs {
             assert_eq!(
-                KustoDuration::from_str(from)
+                KustoTimespan::from_str(from)
                     .unwrap_or_else(|_| panic!("Failed to parse duration {}", from))
                     .whole_nanoseconds(),
                 i128::from(to)
@@ -184,9 +467,342 @@ mod tests {
         ];
 
         for duration in refs {
-            let parsed = KustoDuration::from_str(duration)
+            let parsed = KustoTimespan::from_str(duration)
                 .unwrap_or_else(|_| panic!("Failed to parse duration {}", duration));
             assert_eq!(format!("{:?}", parsed), duration);
         }
     }
+
+    #[test]
+    fn std_duration_conversion() {
+        let duration = KustoTimespan::from(std::time::Duration::from_millis(1500));
+        assert_eq!(duration.whole_milliseconds(), 1500);
+
+        let std_duration: std::time::Duration = duration
+            .try_into()
+            .expect("Failed to convert positive duration");
+        assert_eq!(std_duration, std::time::Duration::from_millis(1500));
+
+        let negative = KustoTimespan::from_str("-01:00:00").expect("Failed to parse duration");
+        assert!(matches!(
+            std::time::Duration::try_from(negative),
+            Err(Error::InvalidArgumentError(
+                InvalidArgumentError::NegativeDuration(_)
+            ))
+        ));
+    }
+
+    #[test]
+    fn datetime_duration_arithmetic() {
+        let start = KustoDateTime::from_str("2023-01-01T00:00:00Z").expect("Failed to parse");
+        let span = KustoTimespan::from_str("1.02:00:00").expect("Failed to parse");
+
+        assert_eq!(
+            start + span,
+            KustoDateTime::from_str("2023-01-02T02:00:00Z").expect("Failed to parse")
+        );
+        assert_eq!(
+            start - span,
+            KustoDateTime::from_str("2022-12-30T22:00:00Z").expect("Failed to parse")
+        );
+        assert_eq!((start + span) - start, span);
+        assert_eq!(start - (start + span), KustoTimespan(-*span));
+    }
+
+    #[test]
+    fn datetime_arithmetic_overflow() {
+        let max = KustoDateTime(OffsetDateTime::new_utc(Date::MAX, time::Time::MAX));
+        let min = KustoDateTime(OffsetDateTime::new_utc(Date::MIN, time::Time::MIDNIGHT));
+        let day = KustoTimespan::from_str("1.00:00:00").expect("Failed to parse");
+
+        assert_eq!(max.checked_add(day), None);
+        assert_eq!(min.checked_sub(day), None);
+        assert_eq!((max - day).checked_add(day), Some(max));
+
+        assert_eq!(max + day, max);
+        assert_eq!(min - KustoTimespan::MAX, min);
+    }
+
+    #[test]
+    fn invalid_timespans() {
+        for timespan in [
+            "",
+            "1.01",
+            "01:00:00:00",
+            "yesterday",
+            "10675200.00:00:00",
+            "9223372036854775808",
+            "99999999999999999999.00:00:00",
+        ] {
+            assert!(KustoTimespan::from_str(timespan).is_err(), "{timespan}");
+        }
+    }
+
+    #[test]
+    fn timespan_limits() {
+        assert_eq!(KustoTimespan::MIN.ticks(), i64::MIN);
+        assert_eq!(KustoTimespan::MAX.ticks(), i64::MAX);
+        assert_eq!(KustoTimespan::MIN.to_string(), "-10675199.02:48:05.4775808");
+        assert_eq!(KustoTimespan::MAX.to_string(), "10675199.02:48:05.4775807");
+        assert_eq!(
+            KustoTimespan::from_str("10675199.02:48:05.4775807").unwrap(),
+            KustoTimespan::MAX
+        );
+        assert_eq!(
+            KustoTimespan::from_str("-10675199.02:48:05.4775808").unwrap(),
+            KustoTimespan::MIN
+        );
+
+        let second = KustoTimespan::from_ticks(TICKS_PER_SECOND);
+        assert_eq!(KustoTimespan::MAX + second, KustoTimespan::MAX);
+        assert_eq!(KustoTimespan::MIN - second, KustoTimespan::MIN);
+        assert_eq!(-KustoTimespan::MIN, KustoTimespan::MAX);
+        assert_eq!(
+            KustoTimespan::from(std::time::Duration::MAX),
+            KustoTimespan::MAX
+        );
+        assert_eq!(KustoTimespan::from(Duration::MAX), KustoTimespan::MAX);
+        assert_eq!(KustoTimespan::from(Duration::MIN), KustoTimespan::MIN);
+        assert_eq!(
+            [second, KustoTimespan::MAX, KustoTimespan::MIN]
+                .iter()
+                .max(),
+            Some(&KustoTimespan::MAX)
+        );
+    }
+
+    #[test]
+    fn timespan_arithmetic() {
+        let hour = KustoTimespan::from_str("01:00:00").expect("Failed to parse");
+        let minute = KustoTimespan::from_str("00:01").expect("Failed to parse");
+
+        assert_eq!((hour + minute).to_string(), "01:01:00.0000000");
+        assert_eq!((minute - hour).to_string(), "-00:59:00.0000000");
+        assert_eq!(-(minute - hour), hour - minute);
+
+        assert_eq!(hour + Some(minute), Some(hour + minute));
+        assert_eq!(hour - None, None);
+        assert_eq!(Some(hour) - minute, Some(hour - minute));
+        assert_eq!(None + minute, None);
+    }
+
+    #[test]
+    fn timespan_serialization() {
+        let timespan = KustoTimespan::from_str("1.00:00").expect("Failed to parse");
+
+        assert_eq!(
+            serde_json::to_string(&timespan).unwrap(),
+            "\"1.00:00:00.0000000\""
+        );
+        for serialized in ["\"1.00:00:00.0000000\"", "\"864000000000\""] {
+            assert_eq!(
+                serde_json::from_str::<KustoTimespan>(serialized).unwrap(),
+                timespan,
+                "{serialized}"
+            );
+        }
+        assert!(serde_json::from_str::<KustoTimespan>("864000000000").is_err());
+        assert!(serde_json::from_str::<KustoTimespan>("1.5").is_err());
+        assert!(serde_json::from_str::<KustoTimespan>("\"18446744073709551615\"").is_err());
+        assert_eq!(
+            serde_json::from_str::<Option<KustoTimespan>>("null").unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn datetime_ordering() {
+        let mut dates = [
+            KustoDateTime::from_str("2023-01-03T00:00:00Z").expect("Failed to parse"),
+            KustoDateTime::from_str("2023-01-01T00:00:00Z").expect("Failed to parse"),
+            KustoDateTime::from_str("2023-01-02T00:00:00Z").expect("Failed to parse"),
+        ];
+        dates.sort();
+
+        assert_eq!(
+            dates.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            vec![
+                "2023-01-01T00:00:00.0000000Z",
+                "2023-01-02T00:00:00.0000000Z",
+                "2023-01-03T00:00:00.0000000Z"
+            ]
+        );
+        assert!(dates[0] < dates[1]);
+        assert_eq!(dates.iter().max(), Some(&dates[2]));
+    }
+
+    #[test]
+    fn datetime_as_map_key() {
+        let first = KustoDateTime::from_str("2023-01-01T00:00:00Z").expect("Failed to parse");
+        let second = KustoDateTime::from_str("2023-01-02T00:00:00Z").expect("Failed to parse");
+
+        let mut hash_map = std::collections::HashMap::new();
+        *hash_map.entry(first).or_insert(0) += 1;
+        *hash_map.entry(second).or_insert(0) += 1;
+        *hash_map.entry(first).or_insert(0) += 1;
+        assert_eq!(hash_map[&first], 2);
+        assert_eq!(hash_map[&second], 1);
+
+        let mut btree_map = std::collections::BTreeMap::new();
+        btree_map.insert(second, "second");
+        btree_map.insert(first, "first");
+        assert_eq!(
+            btree_map.values().collect::<Vec<_>>(),
+            vec![&"first", &"second"]
+        );
+    }
+
+    #[test]
+    fn datetime_shapes() {
+        let cases = [
+            (
+                "2020-03-04T14:05:01.3109965Z",
+                "2020-03-04T14:05:01.3109965Z",
+            ),
+            (
+                "2020-03-04T16:05:01.3109965+02:00",
+                "2020-03-04T14:05:01.3109965Z",
+            ),
+            ("2020-03-04T14:05:01.31Z", "2020-03-04T14:05:01.3100000Z"),
+            ("2020-03-04T14:05:01Z", "2020-03-04T14:05:01.0000000Z"),
+            (
+                "2020-03-04T14:05:01.3109965",
+                "2020-03-04T14:05:01.3109965Z",
+            ),
+            ("2020-03-04T14:05:01", "2020-03-04T14:05:01.0000000Z"),
+            (
+                "2020-03-04 14:05:01.3109965",
+                "2020-03-04T14:05:01.3109965Z",
+            ),
+            ("2020-03-04 14:05:01", "2020-03-04T14:05:01.0000000Z"),
+            ("2020-03-04", "2020-03-04T00:00:00.0000000Z"),
+            ("0001-01-01T00:00:00Z", "0001-01-01T00:00:00.0000000Z"),
+            (
+                "0001-01-01 00:00:00.0000000",
+                "0001-01-01T00:00:00.0000000Z",
+            ),
+            ("0001-01-01", "0001-01-01T00:00:00.0000000Z"),
+            (
+                "9999-12-31T23:59:59.9999999Z",
+                "9999-12-31T23:59:59.9999999Z",
+            ),
+            (
+                "9999-12-31 23:59:59.9999999",
+                "9999-12-31T23:59:59.9999999Z",
+            ),
+            ("9999-12-31", "9999-12-31T00:00:00.0000000Z"),
+        ];
+
+        for (from, to) in cases {
+            let parsed = KustoDateTime::from_str(from)
+                .unwrap_or_else(|_| panic!("Failed to parse datetime {}", from));
+            assert_eq!(parsed.to_string(), to, "{from}");
+            assert_eq!(
+                KustoDateTime::from_str(to).expect("Failed to parse formatted datetime"),
+                parsed,
+                "{from}"
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_datetimes() {
+        for datetime in [
+            "",
+            "yesterday",
+            "2020-13-01",
+            "2020-03-04T25:00:00Z",
+            "04/03/2020 14:05:01",
+        ] {
+            assert!(KustoDateTime::from_str(datetime).is_err(), "{datetime}");
+        }
+    }
+
+    #[test]
+    fn datetime_serialization() {
+        let datetime =
+            KustoDateTime::from_str("2020-03-04 14:05:01.3109965").expect("Failed to parse");
+
+        assert_eq!(
+            serde_json::to_string(&datetime).unwrap(),
+            "\"2020-03-04T14:05:01.3109965Z\""
+        );
+        assert_eq!(
+            serde_json::from_str::<KustoDateTime>("\"2020-03-04T14:05:01.3109965Z\"").unwrap(),
+            datetime
+        );
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn chrono_conversion() {
+        use chrono::{TimeZone, Utc};
+
+        let datetime =
+            KustoDateTime::from_str("2020-03-04T14:05:01.3109965Z").expect("Failed to parse");
+        let converted = chrono::DateTime::<Utc>::from(datetime);
+        assert_eq!(
+            converted,
+            Utc.with_ymd_and_hms(2020, 3, 4, 14, 5, 1).unwrap()
+                + chrono::Duration::nanoseconds(310_996_500)
+        );
+        assert_eq!(KustoDateTime::try_from(converted).unwrap(), datetime);
+
+        for edge in ["0001-01-01T00:00:00Z", "9999-12-31T23:59:59.9999999Z"] {
+            let datetime = KustoDateTime::from_str(edge).expect("Failed to parse");
+            assert_eq!(
+                KustoDateTime::try_from(chrono::DateTime::<Utc>::from(datetime)).unwrap(),
+                datetime
+            );
+        }
+
+        let too_late = Utc.with_ymd_and_hms(10000, 1, 1, 0, 0, 0).unwrap();
+        assert!(matches!(
+            KustoDateTime::try_from(too_late),
+            Err(Error::InvalidArgumentError(
+                InvalidArgumentError::DateTimeOutOfRange(_)
+            ))
+        ));
+    }
+
+    #[test]
+    fn dynamic_strings_holding_json_are_parsed() {
+        let nested = Value::from(r#"{"a": {"b": [1, {"c": null}]}, "d": "{\"e\": 1}"}"#);
+        assert_eq!(
+            parse_dynamic(nested),
+            serde_json::json!({"a": {"b": [1, {"c": null}]}, "d": "{\"e\": 1}"})
+        );
+        assert_eq!(
+            parse_dynamic(Value::from(" [1, [2, 3]]")),
+            serde_json::json!([1, [2, 3]])
+        );
+
+        for looks_like_json in ["{not json}", "[1, 2", "[start] of a sentence", "{}extra"] {
+            assert_eq!(
+                parse_dynamic(Value::from(looks_like_json)),
+                Value::from(looks_like_json)
+            );
+        }
+        for other in [
+            Value::from("plain"),
+            Value::from("1"),
+            Value::from(1),
+            Value::Null,
+            serde_json::json!({"a": "[1]"}),
+        ] {
+            assert_eq!(parse_dynamic(other.clone()), other);
+        }
+    }
+
+    #[test]
+    fn dynamics_are_deserialized_from_json_and_strings() {
+        let from_string: KustoDynamic = serde_json::from_str(r#""{\"a\": [1]}""#).unwrap();
+        let from_json: KustoDynamic = serde_json::from_str(r#"{"a": [1]}"#).unwrap();
+        assert_eq!(from_string, from_json);
+        assert_eq!(from_string["a"][0], 1);
+
+        let text: KustoDynamic = serde_json::from_str(r#""{\"a\": ""#).unwrap();
+        assert_eq!(*text, Value::from("{\"a\": "));
+        assert_eq!(serde_json::to_string(&from_json).unwrap(), r#"{"a":[1]}"#);
+    }
 }
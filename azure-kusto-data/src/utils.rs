@@ -0,0 +1,154 @@
+//! Helpers shared across the crate.
+
+/// Runs blocking work, such as file or process I/O, on the blocking thread pool when called inside a tokio runtime,
+/// so that it does not block the executor. Otherwise, it runs in place.
+pub(crate) async fn run_blocking<T: Send + 'static>(
+    work: impl FnOnce() -> T + Send + 'static,
+) -> T {
+    #[cfg(feature = "tokio")]
+    if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+        return match runtime.spawn_blocking(work).await {
+            Ok(result) => result,
+            Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+            Err(e) => panic!("Blocking task failed: {e}"),
+        };
+    }
+
+    work()
+}
+
+/// Truncates `s` to at most `max_bytes` bytes, backing off to the previous character boundary so that a multi-byte character is never split.
+/// The result is always valid UTF-8, although a combining character may be separated from the character it modifies.
+pub(crate) fn truncate_utf8_lossy(s: &str, max_bytes: usize) -> &str {
+    if s.len() <= max_bytes {
+        return s;
+    }
+
+    let mut end = max_bytes;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+/// Masks the contents of the string literals of a query, which may contain sensitive data, keeping the rest of its text.
+/// Handles escaped quotes, verbatim (`@'...'`) and multi-line (` ``` `) literals, and leaves comments untouched.
+pub(crate) fn redact_string_literals(query: &str) -> String {
+    const MASK: &str = "***";
+
+    let mut redacted = String::with_capacity(query.len());
+    let mut rest = query;
+    while let Some(c) = rest.chars().next() {
+        if rest.starts_with("//") {
+            let end = rest.find('\n').unwrap_or(rest.len());
+            redacted.push_str(&rest[..end]);
+            rest = &rest[end..];
+        } else if rest.starts_with("```") || rest.starts_with("~~~") {
+            let delimiter = &rest[..3];
+            let end = rest[3..].find(delimiter).map_or(rest.len(), |i| i + 6);
+            redacted.push_str(delimiter);
+            redacted.push_str(MASK);
+            redacted.push_str(delimiter);
+            rest = &rest[end..];
+        } else if c == '\'' || c == '"' {
+            // Backslashes don't escape in verbatim literals, where quotes are escaped by doubling them
+            let verbatim = redacted.ends_with('@');
+            let mut end = rest.len();
+            let mut chars = rest.char_indices().skip(1);
+            while let Some((i, next)) = chars.next() {
+                if next == '\\' && !verbatim {
+                    chars.next();
+                } else if next == c {
+                    if verbatim && rest[i + 1..].starts_with(c) {
+                        chars.next();
+                    } else {
+                        end = i + 1;
+                        break;
+                    }
+                }
+            }
+            redacted.push(c);
+            redacted.push_str(MASK);
+            redacted.push(c);
+            rest = &rest[end..];
+        } else {
+            redacted.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+
+    redacted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_strings_are_unchanged() {
+        assert_eq!(truncate_utf8_lossy("print 1", 7), "print 1");
+        assert_eq!(truncate_utf8_lossy("print 1", 100), "print 1");
+        assert_eq!(truncate_utf8_lossy("", 0), "");
+    }
+
+    #[test]
+    fn truncates_on_char_boundaries() {
+        // 'a' is 1 byte, 'ש' is 2 bytes, '€' is 3 bytes and '😀' is 4 bytes.
+        let s = "aש€😀";
+        let truncated: Vec<&str> = (0..=s.len())
+            .map(|max_bytes| truncate_utf8_lossy(s, max_bytes))
+            .collect();
+
+        assert_eq!(
+            truncated,
+            vec![
+                "",
+                "a",
+                "a",
+                "aש",
+                "aש",
+                "aש",
+                "aש€",
+                "aש€",
+                "aש€",
+                "aש€",
+                "aש€😀"
+            ]
+        );
+    }
+
+    #[test]
+    fn combining_characters_may_be_separated() {
+        // 'e' followed by a combining acute accent (2 bytes).
+        let s = "cafe\u{301}";
+
+        assert_eq!(truncate_utf8_lossy(s, 5), "cafe");
+        assert_eq!(truncate_utf8_lossy(s, 6), s);
+    }
+
+    #[test]
+    fn string_literals_are_redacted() {
+        assert_eq!(
+            redact_string_literals(r#"T | where Name == 'alice' and Id in ("a\"b", h'c')"#),
+            r#"T | where Name == '***' and Id in ("***", h'***')"#
+        );
+        assert_eq!(
+            redact_string_literals(r#"print @'C:\path' , @"say ""hi"""  , 'x\'y'"#),
+            r#"print @'***' , @"***"  , '***'"#
+        );
+        assert_eq!(
+            redact_string_literals("print ```multi\n'line'```, ~~~x~~~"),
+            "print ```***```, ~~~***~~~"
+        );
+    }
+
+    #[test]
+    fn comments_and_unterminated_literals() {
+        assert_eq!(
+            redact_string_literals("T // don't redact\n| where x == 'y'"),
+            "T // don't redact\n| where x == '***'"
+        );
+        assert_eq!(redact_string_literals("print 'secret"), "print '***'");
+        assert_eq!(redact_string_literals("print 1"), "print 1");
+    }
+}
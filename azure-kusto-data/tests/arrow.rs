@@ -68,7 +68,7 @@ async fn arrow_roundtrip() {
             true,
         ),
     ]));
-    let expected = vec![
+    let expected = [
         "+----+------------+----------+---------+------------+-----------+---------------------+",
         "| id | string_col | bool_col | int_col | bigint_col | float_col | timestamp_col       |",
         "+----+------------+----------+---------+------------+-----------+---------------------+",
@@ -3,7 +3,7 @@ use serde_json::Value;
 use std::str::FromStr;
 use time::Duration;
 
-use azure_kusto_data::types::{KustoDateTime, KustoDuration};
+use azure_kusto_data::types::{KustoDateTime, KustoTimespan};
 use decimal::d128;
 use uuid::Uuid;
 
@@ -14,7 +14,7 @@ struct Item {
     vnum: i32,
     vdec: d128,
     vdate: KustoDateTime,
-    vspan: KustoDuration,
+    vspan: KustoTimespan,
     vobj: Value,
     vb: bool,
     vreal: f64,
@@ -61,7 +61,11 @@ async fn create_query_delete_table() {
         .await
         .expect("Failed to run query");
 
-    let results = response.into_primary_results().next().expect("No results");
+    let results = response
+        .into_primary_results()
+        .next()
+        .expect("No results")
+        .expect("Failed to parse results");
 
     let rows = results.rows;
 
@@ -70,7 +74,7 @@ async fn create_query_delete_table() {
             vnum: 1,
             vdec: d128!(2.00000000000001),
             vdate: KustoDateTime::from_str("2020-03-04T14:05:01.3109965Z").unwrap(),
-            vspan: KustoDuration::from(
+            vspan: KustoTimespan::from(
                 Duration::seconds(3600 + 23 * 60 + 45) + Duration::microseconds(678900),
             ),
             vobj: Value::Object(serde_json::Map::from_iter(vec![(
@@ -87,7 +91,7 @@ async fn create_query_delete_table() {
             vnum: 2,
             vdec: d128!(5.00000000000005),
             vdate: KustoDateTime::from_str("2022-05-06T16:07:03.1234300Z").unwrap(),
-            vspan: KustoDuration::from(
+            vspan: KustoTimespan::from(
                 Duration::seconds(4 * 3600 + 56 * 60 + 59) + Duration::microseconds(912000),
             ),
             vobj: Value::Object(serde_json::Map::from_iter(vec![(
@@ -104,7 +108,7 @@ async fn create_query_delete_table() {
             vnum: 3,
             vdec: d128!(9.9999999999999),
             vdate: KustoDateTime::from_str("2023-07-08T18:09:05.5678000Z").unwrap(),
-            vspan: KustoDuration::from(
+            vspan: KustoTimespan::from(
                 Duration::seconds(7 * 3600 + 43 * 60 + 12) + Duration::microseconds(345600),
             ),
             vobj: Value::Object(serde_json::Map::from_iter(vec![(
@@ -132,3 +136,31 @@ async fn create_query_delete_table() {
 
     assert_eq!(response.tables[0].rows.len(), 0);
 }
+
+#[tokio::test]
+async fn weak_consistency_session_is_echoed_in_effective_request_options() {
+    use azure_kusto_data::models::TableKind;
+
+    let (client, database) = setup::create_kusto_client();
+    let session = client.weak_consistency_session();
+
+    let response = client
+        .execute_query(database, "print 1", Some(session.properties()))
+        .await
+        .expect("Failed to run query");
+
+    // The options the service applied are reported in the completion information, as the EffectiveRequestOptions event
+    let completion_information = response
+        .parsed_data_tables()
+        .map(|t| t.expect("Failed to parse table"))
+        .find(|t| t.table_kind == TableKind::QueryCompletionInformation)
+        .expect("No completion information");
+    let effective_options = completion_information
+        .rows
+        .iter()
+        .map(|row| row.to_string())
+        .find(|row| row.contains("EffectiveRequestOptions"))
+        .expect("No EffectiveRequestOptions");
+
+    assert!(effective_options.contains(session.session_id()));
+}
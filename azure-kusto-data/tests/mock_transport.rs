@@ -0,0 +1,1414 @@
+//! Shows how to unit test code that uses a [KustoClient], by replacing its transport with one that serves recorded responses.
+#![cfg(not(target_arch = "wasm32"))]
+
+use azure_core::headers::{HeaderName, Headers};
+use azure_core::{
+    Body, BytesStream, ClientOptions, Context, Policy, PolicyResult, Request, Response,
+    RetryOptions, StatusCode, TransportOptions,
+};
+use azure_kusto_data::cloud_info::CloudInfo;
+use azure_kusto_data::error::{ConnectionStringError, InvalidArgumentError};
+use azure_kusto_data::http_transport::HttpTransportOptions;
+use azure_kusto_data::prelude::*;
+use azure_kusto_data::slow_query::{SlowQueryReport, SlowQueryThreshold, SlowQueryThresholds};
+use azure_kusto_data::trace_context::TraceContext;
+use std::future::IntoFuture;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const CLUSTER: &str = "https://mycluster.kusto.windows.net";
+
+/// A request received by the [MockKusto] transport.
+#[derive(Debug, Clone, PartialEq)]
+struct RecordedRequest {
+    url: String,
+    client_request_id: Option<String>,
+    body: serde_json::Value,
+    /// The headers, sorted by name, and the body exactly as they were sent
+    headers: Vec<(String, String)>,
+    raw_body: Vec<u8>,
+}
+
+/// Answers queries with `validFrames.json` and management commands with `adminthenquery.json`, and records the requests it received.
+/// The query `fail` is answered with a body that is not a valid response, the query `stopped` with the error of a stopped cluster,
+/// the query `stopped in body` with a successful response that reports that error, and the query `hang` never.
+#[derive(Debug, Default)]
+struct MockKusto {
+    requests: Mutex<Vec<RecordedRequest>>,
+    /// The file that queries are answered with, instead of `validFrames.json`
+    query_input: Option<&'static str>,
+    /// Held by tests to keep the requests waiting for their response, after they are recorded
+    gate: futures::lock::Mutex<()>,
+}
+
+impl MockKusto {
+    fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+fn read_input(name: &str) -> Vec<u8> {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("tests/inputs");
+    path.push(name);
+    std::fs::read(&path).unwrap_or_else(|_| panic!("Failed to read {}", path.display()))
+}
+
+#[async_trait::async_trait]
+impl Policy for MockKusto {
+    async fn send(
+        &self,
+        _ctx: &Context,
+        request: &mut Request,
+        _next: &[Arc<dyn Policy>],
+    ) -> PolicyResult {
+        let raw_body = match request.body() {
+            Body::Bytes(bytes) => bytes.to_vec(),
+            _ => panic!("Expected a buffered request body"),
+        };
+        let body: serde_json::Value =
+            serde_json::from_slice(&raw_body).expect("Request body is not valid JSON");
+        let mut headers = request
+            .headers()
+            .iter()
+            .map(|(name, value)| (name.as_str().to_string(), value.as_str().to_string()))
+            .collect::<Vec<_>>();
+        headers.sort();
+        self.requests.lock().unwrap().push(RecordedRequest {
+            url: request.url().to_string(),
+            client_request_id: request
+                .headers()
+                .get_optional_string(&HeaderName::from_static("x-ms-client-request-id")),
+            body: body.clone(),
+            headers,
+            raw_body,
+        });
+        let _open = self.gate.lock().await;
+
+        // Queries that hang are cancelled with their client request id, which hangs too when it is `hang`
+        if body["csl"] == "hang" || body["csl"] == ".cancel query \"hang\"" {
+            futures::future::pending::<()>().await;
+        }
+        if body["csl"] == "stopped" {
+            return Ok(Response::new(
+                StatusCode::BadRequest,
+                Headers::new(),
+                Box::pin(BytesStream::new(read_input("cluster_stopped.json"))),
+            ));
+        }
+
+        let response = match request.url().path() {
+            "/v2/rest/query" if body["csl"] == "fail" => b"not json".to_vec(),
+            "/v2/rest/query" if body["csl"] == "stopped in body" => {
+                read_input("cluster_stopped_v2.json")
+            }
+            "/v1/rest/mgmt" if body["csl"] == "stopped in body" => {
+                read_input("cluster_stopped.json")
+            }
+            "/v2/rest/query" => read_input(self.query_input.unwrap_or("validFrames.json")),
+            "/v1/rest/mgmt" => read_input("adminthenquery.json"),
+            path if path.starts_with("/v1/rest/ingest/") => read_input("adminthenquery.json"),
+            path => panic!("Unexpected request to {path}"),
+        };
+
+        Ok(Response::new(
+            StatusCode::Ok,
+            Headers::new(),
+            Box::pin(BytesStream::new(response)),
+        ))
+    }
+}
+
+/// Creates a client that sends its requests to the mock, instead of to the cluster.
+fn mock_client(mock: Arc<MockKusto>) -> KustoClient {
+    mock_client_with_options(mock, |options| options)
+}
+
+fn mock_client_with_options(
+    mock: Arc<MockKusto>,
+    configure: impl FnOnce(KustoClientOptions) -> KustoClientOptions,
+) -> KustoClient {
+    KustoClient::new(
+        // Without authentication, the client doesn't need a token, or the cloud info of the cluster.
+        ConnectionString::with_no_auth(CLUSTER),
+        configure(KustoClientOptions::from(
+            ClientOptions::default().transport(TransportOptions::new_custom_policy(mock)),
+        )),
+    )
+    .expect("Failed to create client")
+}
+
+#[tokio::test]
+async fn queries_are_sent_to_the_transport() {
+    let mock = Arc::new(MockKusto::default());
+    let client = mock_client(mock.clone());
+
+    let mut properties = ClientRequestProperties::from(
+        OptionsBuilder::default()
+            .with_truncation_max_records(10)
+            .build()
+            .unwrap(),
+    );
+    properties.client_request_id = Some("KRust;mock-transport".to_string());
+    properties.add_string_parameter("name".into(), "first".into());
+
+    let response = client
+        .execute_query("mydb", "MyTable | take 10", Some(properties))
+        .await
+        .expect("Failed to execute query");
+
+    let requests = mock.requests();
+    assert_eq!(requests.len(), 1);
+    let request = &requests[0];
+    assert_eq!(request.url, format!("{CLUSTER}/v2/rest/query"));
+    assert_eq!(
+        request.client_request_id.as_deref(),
+        Some("KRust;mock-transport")
+    );
+    assert_eq!(request.body["db"], "mydb");
+    assert_eq!(request.body["csl"], "MyTable | take 10");
+    assert_eq!(
+        request.body["properties"]["options"]["truncationmaxrecords"],
+        10
+    );
+    assert_eq!(request.body["properties"]["parameters"]["name"], "first");
+
+    let table = response
+        .into_primary_results()
+        .next()
+        .expect("Expected a primary result")
+        .expect("Failed to parse the primary result");
+    assert_eq!(table.table_name, "PrimaryResult");
+    assert_eq!(table.rows.len(), 3);
+}
+
+#[tokio::test]
+async fn queries_without_a_database_use_the_initial_catalog() {
+    let mock = Arc::new(MockKusto::default());
+    let client = KustoClient::new(
+        ConnectionString::with_no_auth(CLUSTER).with_initial_catalog("defaultdb"),
+        KustoClientOptions::from(
+            ClientOptions::default().transport(TransportOptions::new_custom_policy(mock.clone())),
+        ),
+    )
+    .expect("Failed to create client");
+    assert_eq!(client.default_database(), Some("defaultdb"));
+
+    client
+        .execute_query_on_default_db("MyTable | take 10", None)
+        .await
+        .expect("Failed to execute query");
+    client
+        .execute_command_on_default_db(".show tables", None)
+        .await
+        .expect("Failed to execute command");
+    client
+        .execute_query("mydb", "MyTable | take 10", None)
+        .await
+        .expect("Failed to execute query");
+    client
+        .execute_cluster_command(".show databases", None)
+        .await
+        .expect("Failed to execute command");
+
+    let databases: Vec<_> = mock
+        .requests()
+        .iter()
+        .map(|request| request.body["db"].clone())
+        .collect();
+    assert_eq!(
+        databases,
+        vec!["defaultdb", "defaultdb", "mydb", "defaultdb"]
+    );
+}
+
+#[tokio::test]
+async fn queries_without_any_database_fail() {
+    let mock = Arc::new(MockKusto::default());
+    let client = mock_client(mock.clone());
+    assert_eq!(client.default_database(), None);
+
+    let error = client
+        .execute_query_on_default_db("MyTable | take 10", None)
+        .await
+        .expect_err("Expected the query to fail without a database");
+    assert!(
+        matches!(error, Error::QueryError(message) if message.as_str() == "no database specified")
+    );
+    assert!(mock.requests().is_empty());
+
+    // Cluster commands run in the database the service uses when none is named
+    client
+        .execute_cluster_command(".show databases", None)
+        .await
+        .expect("Failed to execute command");
+    assert_eq!(mock.requests()[0].body["db"], "NetDefaultDB");
+}
+
+#[tokio::test]
+async fn application_and_user_follow_their_precedence_in_headers_and_body() {
+    let mock = Arc::new(MockKusto::default());
+    let mut connection_string = ConnectionString::with_no_auth(CLUSTER);
+    connection_string.application = Some("connection-app".to_string());
+    connection_string.user = Some("connection-user".to_string());
+    let client = KustoClient::new(
+        connection_string,
+        KustoClientOptions::from(
+            ClientOptions::default().transport(TransportOptions::new_custom_policy(mock.clone())),
+        ),
+    )
+    .expect("Failed to create client");
+
+    let options = OptionsBuilder::default()
+        .with_request_app_name("options-app")
+        .with_request_user("options-user")
+        .with_request_description("nightly report")
+        .build()
+        .unwrap();
+    let mut overridden = ClientRequestProperties::from(options.clone());
+    overridden.application = Some("request-app".to_string());
+    overridden.user = Some("request-user".to_string());
+
+    for properties in [None, Some(options.into()), Some(overridden)] {
+        client
+            .execute_query("mydb", "MyTable", properties)
+            .await
+            .expect("Failed to execute query");
+    }
+
+    let sent: Vec<_> = mock
+        .requests()
+        .into_iter()
+        .map(|request| {
+            let header = |name: &str| {
+                request
+                    .headers
+                    .iter()
+                    .find(|(n, _)| n == name)
+                    .map(|(_, v)| v.clone())
+            };
+            let options = &request.body["properties"]["options"];
+            (
+                header("x-ms-app"),
+                header("x-ms-user"),
+                options["request_app_name"].as_str().map(str::to_string),
+                options["request_user"].as_str().map(str::to_string),
+                options["request_description"].as_str().map(str::to_string),
+            )
+        })
+        .collect();
+    let some = |s: &str| Some(s.to_string());
+    assert_eq!(
+        sent,
+        vec![
+            (
+                some("connection-app"),
+                some("connection-user"),
+                None,
+                None,
+                None
+            ),
+            (
+                some("options-app"),
+                some("options-user"),
+                some("options-app"),
+                some("options-user"),
+                some("nightly report")
+            ),
+            (
+                some("request-app"),
+                some("request-user"),
+                some("request-app"),
+                some("request-user"),
+                some("nightly report")
+            ),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn management_commands_are_sent_to_the_transport() {
+    let mock = Arc::new(MockKusto::default());
+    let client = mock_client(mock.clone());
+
+    let response = client
+        .execute_command("mydb", ".show tables", None)
+        .await
+        .expect("Failed to execute command");
+
+    let requests = mock.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].url, format!("{CLUSTER}/v1/rest/mgmt"));
+    assert_eq!(requests[0].body["db"], "mydb");
+    assert_eq!(requests[0].body["csl"], ".show tables");
+    assert!(requests[0].body.get("properties").is_none());
+
+    assert_eq!(response.tables[0].rows.len(), 2);
+}
+
+#[derive(serde::Deserialize, Debug, PartialEq)]
+struct TableRow {
+    database_name: String,
+    table_name: String,
+}
+
+#[tokio::test]
+async fn management_commands_are_deserialized_into_structs() {
+    let mock = Arc::new(MockKusto::default());
+    let client = mock_client(mock);
+
+    let properties = ClientRequestProperties {
+        case_mapping: Some(CaseMapping::PascalToSnake),
+        ..ClientRequestProperties::default()
+    };
+    let tables: Vec<TableRow> = client
+        .execute_command_to_struct("mydb", ".show tables", Some(properties))
+        .await
+        .expect("Failed to execute command");
+
+    assert_eq!(
+        tables,
+        vec![
+            TableRow {
+                database_name: "Kuskus".to_string(),
+                table_name: "KustoLogs".to_string(),
+            },
+            TableRow {
+                database_name: "Kuskus".to_string(),
+                table_name: "LiorTmp".to_string(),
+            },
+        ]
+    );
+
+    // Without a case mapping, the column names must match the field names exactly
+    let result = client
+        .execute_command_to_struct::<TableRow>("mydb", ".show tables", None)
+        .await;
+    assert!(matches!(result, Err(Error::JsonError(_))));
+}
+
+/// Has the columns of the primary result of `validFrames.json`, except for its `Timestamp` column.
+#[derive(Debug, serde::Deserialize)]
+#[allow(dead_code)]
+struct MissingTimestamp {
+    name: String,
+    value: i64,
+}
+
+#[tokio::test]
+async fn mismatched_structs_fail_with_the_names_of_the_columns() {
+    let mock = Arc::new(MockKusto::default());
+    let client = mock_client(mock.clone());
+
+    let error = client
+        .execute_query_to_struct::<MissingTimestamp>("mydb", "MyTable | take 10", None)
+        .await
+        .expect_err("Expected the mismatched struct to fail")
+        .to_string();
+    assert!(
+        error.contains("Timestamp: Datetime, Name: String, Value: Long"),
+        "{error}"
+    );
+
+    let client = mock_client_with_options(mock, |options| {
+        options.with_parse_options(ParseOptions::new().with_check_struct_columns(true))
+    });
+    let error = client
+        .execute_query_to_struct::<MissingTimestamp>("mydb", "MyTable | take 10", None)
+        .await
+        .expect_err("Expected the mismatched struct to fail")
+        .to_string();
+    assert!(
+        error.contains("has 2 fields (name, value) but the result has 3 columns"),
+        "{error}"
+    );
+}
+
+#[derive(Debug, PartialEq, serde::Deserialize)]
+struct X {
+    x: i64,
+}
+
+#[derive(Debug, PartialEq, serde::Deserialize)]
+struct Y {
+    y: String,
+}
+
+#[tokio::test]
+async fn queries_with_several_statements_are_deserialized_per_statement() {
+    let mock = Arc::new(MockKusto {
+        query_input: Some("twotables_progressive.json"),
+        ..MockKusto::default()
+    });
+    let client = mock_client(mock);
+    let query = "range x from 1 to 3 step 1; datatable(y: string) ['b', 'c']";
+
+    let error = client
+        .execute_query_to_struct::<X>("mydb", query, None)
+        .await
+        .expect_err("Expected the second primary result not to be ignored");
+    assert!(matches!(error, Error::SeveralPrimaryResults(2)));
+    let error = client
+        .execute_query_to_lookup::<i64, i64>("mydb", query, "x", "x", DuplicateKeys::Error, None)
+        .await
+        .expect_err("Expected the second primary result not to be ignored");
+    assert!(matches!(error, Error::SeveralPrimaryResults(2)));
+
+    let (xs, ys) = client
+        .execute_query_to_structs_multi::<X, Y>("mydb", query, None)
+        .await
+        .expect("Failed to deserialize the primary results");
+    assert_eq!(xs, vec![X { x: 1 }, X { x: 2 }, X { x: 3 }]);
+    assert_eq!(ys, vec![Y { y: "b".to_string() }, Y { y: "c".to_string() }]);
+
+    // validFrames.json has a single primary result
+    let error = mock_client(Arc::new(MockKusto::default()))
+        .execute_query_to_structs_multi::<X, Y>("mydb", query, None)
+        .await
+        .expect_err("Expected a single primary result to fail");
+    assert!(
+        matches!(error, Error::QueryError(msg) if msg.as_str() == "Expected 2 primary results, but found 1")
+    );
+}
+
+#[tokio::test]
+async fn all_entry_points_send_the_same_requests() {
+    let mock = Arc::new(MockKusto::default());
+    let client = mock_client(mock.clone());
+
+    let mut properties = ClientRequestProperties::from(
+        OptionsBuilder::default()
+            .with_truncation_max_records(10)
+            .build()
+            .unwrap(),
+    );
+    properties.client_request_id = Some("KRust;same-requests".to_string());
+    properties.application = Some("mock-transport".to_string());
+    properties.add_string_parameter("name".into(), "first".into());
+
+    let query = "MyTable | take 10";
+    #[allow(deprecated)]
+    client
+        .execute_with_options("mydb", query, QueryKind::Query, Some(properties.clone()))
+        .await
+        .expect("Failed to execute query");
+    client
+        .execute_query("mydb", query, Some(properties.clone()))
+        .await
+        .expect("Failed to execute query");
+    client
+        .execute_either("mydb", query, Some(properties.clone()))
+        .await
+        .expect("Failed to execute query");
+
+    let command = ".show tables";
+    #[allow(deprecated)]
+    client
+        .execute_with_options(
+            "mydb",
+            command,
+            QueryKind::Management,
+            Some(properties.clone()),
+        )
+        .await
+        .expect("Failed to execute command");
+    client
+        .execute_command("mydb", command, Some(properties.clone()))
+        .await
+        .expect("Failed to execute command");
+    client
+        .execute_either("mydb", command, Some(properties))
+        .await
+        .expect("Failed to execute command");
+
+    let requests = mock.requests();
+    assert_eq!(requests.len(), 6);
+    assert_eq!(requests[0].url, format!("{CLUSTER}/v2/rest/query"));
+    assert_eq!(requests[1], requests[0]);
+    assert_eq!(requests[2], requests[0]);
+    assert_eq!(requests[3].url, format!("{CLUSTER}/v1/rest/mgmt"));
+    assert_eq!(requests[4], requests[3]);
+    assert_eq!(requests[5], requests[3]);
+}
+
+#[tokio::test]
+async fn identical_concurrent_queries_are_sent_once() {
+    let mock = Arc::new(MockKusto::default());
+    let client = mock_client_with_options(mock.clone(), |options| {
+        options.with_request_coalescing(Duration::ZERO)
+    });
+
+    let gate = mock.gate.lock().await;
+    let mut callers: Vec<_> = (0..5)
+        .map(|_| {
+            client
+                .execute_query("mydb", "MyTable | take 10", None)
+                .into_future()
+        })
+        .collect();
+    for caller in &mut callers {
+        assert!(futures::poll!(caller).is_pending());
+    }
+    assert_eq!(mock.requests().len(), 1);
+    drop(gate);
+
+    let responses = futures::future::join_all(callers).await;
+    assert_eq!(mock.requests().len(), 1);
+    for response in responses {
+        let table = response
+            .expect("Failed to execute query")
+            .into_primary_results()
+            .next()
+            .expect("Expected a primary result")
+            .expect("Failed to parse the primary result");
+        assert_eq!(table.rows.len(), 3);
+    }
+
+    // Once the query completes, an identical query is sent again
+    client
+        .execute_query("mydb", "MyTable | take 10", None)
+        .await
+        .expect("Failed to execute query");
+    assert_eq!(mock.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn shared_queries_are_cancelled_by_their_last_caller() {
+    let mock = Arc::new(MockKusto::default());
+    let client = mock_client_with_options(mock.clone(), |options| {
+        options.with_request_coalescing(Duration::ZERO)
+    });
+    let query = || {
+        client
+            .execute_query("mydb", "MyTable | take 10", None)
+            .into_future()
+    };
+
+    // The first caller dropping doesn't cancel the query that the second one waits for
+    let gate = mock.gate.lock().await;
+    let mut first = query();
+    let mut second = query();
+    assert!(futures::poll!(&mut first).is_pending());
+    assert!(futures::poll!(&mut second).is_pending());
+    drop(first);
+    drop(gate);
+
+    second.await.expect("Failed to execute query");
+    assert_eq!(mock.requests().len(), 1);
+
+    // The last caller dropping cancels the query, so the next caller sends it again
+    let gate = mock.gate.lock().await;
+    let mut third = query();
+    assert!(futures::poll!(&mut third).is_pending());
+    assert_eq!(mock.requests().len(), 2);
+    drop(third);
+    drop(gate);
+
+    query().await.expect("Failed to execute query");
+    assert_eq!(mock.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn completed_queries_are_reused_within_the_window() {
+    let mock = Arc::new(MockKusto::default());
+    let client = mock_client_with_options(mock.clone(), |options| {
+        options.with_request_coalescing(Duration::from_secs(60))
+    });
+
+    for _ in 0..3 {
+        client
+            .execute_query("mydb", "MyTable | take 10", None)
+            .await
+            .expect("Failed to execute query");
+    }
+    assert_eq!(mock.requests().len(), 1);
+
+    // Queries with other text or options, and management commands, are not shared
+    client
+        .execute_query("mydb", "MyTable | take 5", None)
+        .await
+        .expect("Failed to execute query");
+    let properties = ClientRequestProperties::from(
+        OptionsBuilder::default()
+            .with_truncation_max_records(10)
+            .build()
+            .unwrap(),
+    );
+    client
+        .execute_query("mydb", "MyTable | take 10", Some(properties))
+        .await
+        .expect("Failed to execute query");
+    for _ in 0..2 {
+        client
+            .execute_command("mydb", ".show tables", None)
+            .await
+            .expect("Failed to execute command");
+    }
+    assert_eq!(mock.requests().len(), 5);
+}
+
+/// A client whose queries are answered with `dataframe.json`, and that records the reports of its slow query hook.
+fn slow_query_client(
+    thresholds: SlowQueryThresholds,
+) -> (
+    Arc<MockKusto>,
+    KustoClient,
+    Arc<Mutex<Vec<SlowQueryReport>>>,
+) {
+    let mock = Arc::new(MockKusto {
+        query_input: Some("dataframe.json"),
+        ..MockKusto::default()
+    });
+    let reports = Arc::new(Mutex::new(vec![]));
+    let recorded = reports.clone();
+    let client = mock_client_with_options(mock.clone(), |options| {
+        options.with_slow_query_hook(
+            thresholds,
+            Arc::new(move |report| recorded.lock().unwrap().push(report)),
+        )
+    });
+    (mock, client, reports)
+}
+
+#[tokio::test]
+async fn slow_queries_are_reported_with_their_measurements() {
+    let (_mock, client, reports) = slow_query_client(
+        SlowQueryThresholds::default()
+            .with_duration(Duration::from_secs(3600))
+            .with_rows(5),
+    );
+
+    let properties = ClientRequestProperties {
+        client_request_id: Some("KRust;slow-query".to_string()),
+        ..ClientRequestProperties::default()
+    };
+    client
+        .execute_query(
+            "mydb",
+            "MyTable | where Name == 'customer-42'",
+            Some(properties),
+        )
+        .await
+        .expect("Failed to execute query");
+
+    let reports = reports.lock().unwrap().clone();
+    assert_eq!(reports.len(), 1);
+    let report = &reports[0];
+    assert_eq!(report.query, "MyTable | where Name == '***'");
+    assert_eq!(report.database, "mydb");
+    assert_eq!(
+        report.client_request_id.as_deref(),
+        Some("KRust;slow-query")
+    );
+    assert_eq!(report.rows, 8);
+    assert_eq!(report.bytes, read_input("dataframe.json").len() as u64);
+    assert_eq!(report.execution_time.map(|t| t.as_micros()), Some(15622));
+    assert!(report.duration < Duration::from_secs(3600));
+    assert_eq!(report.exceeded, vec![SlowQueryThreshold::Rows]);
+}
+
+#[tokio::test]
+async fn each_threshold_is_reported() {
+    let cases = [
+        (
+            SlowQueryThresholds::default().with_execution_time(Duration::from_millis(10)),
+            SlowQueryThreshold::ExecutionTime,
+        ),
+        (
+            SlowQueryThresholds::default().with_rows(7),
+            SlowQueryThreshold::Rows,
+        ),
+        (
+            SlowQueryThresholds::default().with_bytes(1024),
+            SlowQueryThreshold::Bytes,
+        ),
+    ];
+
+    for (thresholds, threshold) in cases {
+        let (_mock, client, reports) = slow_query_client(thresholds);
+        client
+            .execute_query("mydb", "MyTable", None)
+            .await
+            .expect("Failed to execute query");
+
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.len(), 1, "{threshold:?}");
+        assert_eq!(reports[0].exceeded, vec![threshold]);
+    }
+}
+
+#[tokio::test]
+async fn long_queries_exceed_the_duration_threshold() {
+    let (mock, client, reports) =
+        slow_query_client(SlowQueryThresholds::default().with_duration(Duration::from_millis(20)));
+
+    let gate = mock.gate.lock().await;
+    let mut query = client.execute_query("mydb", "MyTable", None).into_future();
+    assert!(futures::poll!(&mut query).is_pending());
+    std::thread::sleep(Duration::from_millis(50));
+    drop(gate);
+    query.await.expect("Failed to execute query");
+
+    let reports = reports.lock().unwrap();
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].exceeded, vec![SlowQueryThreshold::Duration]);
+    assert!(reports[0].duration >= Duration::from_millis(50));
+}
+
+#[tokio::test]
+async fn queries_within_the_thresholds_are_not_reported() {
+    let (_mock, client, reports) = slow_query_client(
+        SlowQueryThresholds::default()
+            .with_duration(Duration::from_secs(3600))
+            .with_execution_time(Duration::from_secs(1))
+            .with_rows(8)
+            .with_bytes(1024 * 1024),
+    );
+
+    client
+        .execute_query("mydb", "MyTable", None)
+        .await
+        .expect("Failed to execute query");
+    client
+        .execute_command("mydb", ".show tables", None)
+        .await
+        .expect("Failed to execute command");
+
+    assert!(reports.lock().unwrap().is_empty());
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn streaming_queries_are_reported_at_completion() {
+    use futures::TryStreamExt;
+
+    let (_mock, client, reports) = slow_query_client(SlowQueryThresholds::default().with_rows(5));
+
+    let mut dataset = client
+        .execute_streaming_query("mydb", "MyTable | where Name == 'x'", None)
+        .await
+        .expect("Failed to execute query");
+    while dataset
+        .try_next()
+        .await
+        .expect("Failed to read table")
+        .is_some()
+    {}
+
+    let reports = reports.lock().unwrap();
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].query, "MyTable | where Name == '***'");
+    assert_eq!(reports[0].rows, 8);
+    assert_eq!(reports[0].bytes, read_input("dataframe.json").len() as u64);
+    assert_eq!(reports[0].exceeded, vec![SlowQueryThreshold::Rows]);
+}
+
+#[tokio::test]
+async fn panicking_callbacks_do_not_fail_queries() {
+    let mock = Arc::new(MockKusto::default());
+    let client = mock_client_with_options(mock, |options| {
+        options.with_slow_query_hook(
+            SlowQueryThresholds::default().with_rows(0),
+            Arc::new(|_| panic!("callback failed")),
+        )
+    });
+
+    client
+        .execute_query("mydb", "MyTable", None)
+        .await
+        .expect("Failed to execute query");
+}
+
+async fn body_sent_with(
+    serialization: PropertiesSerialization,
+    properties: Option<ClientRequestProperties>,
+) -> String {
+    let mock = Arc::new(MockKusto::default());
+    let client = mock_client_with_options(mock.clone(), |options| {
+        options.with_properties_serialization(serialization)
+    });
+    client
+        .execute_query("mydb", "T", properties)
+        .await
+        .expect("Failed to execute query");
+    String::from_utf8(mock.requests()[0].raw_body.clone()).unwrap()
+}
+
+#[tokio::test]
+async fn missing_properties_are_serialized_as_configured() {
+    assert_eq!(
+        body_sent_with(PropertiesSerialization::OmitNullProperties, None).await,
+        r#"{"db":"mydb","csl":"T"}"#
+    );
+    assert_eq!(
+        body_sent_with(PropertiesSerialization::EmitNullProperties, None).await,
+        r#"{"db":"mydb","csl":"T","properties":null}"#
+    );
+    assert_eq!(
+        body_sent_with(PropertiesSerialization::EmitEmptyObject, None).await,
+        r#"{"db":"mydb","csl":"T","properties":{}}"#
+    );
+}
+
+#[tokio::test]
+async fn unset_options_and_parameters_are_serialized_as_configured() {
+    let empty = || Some(ClientRequestProperties::default());
+    assert_eq!(
+        body_sent_with(PropertiesSerialization::OmitNullProperties, empty()).await,
+        r#"{"db":"mydb","csl":"T","properties":{}}"#
+    );
+    for serialization in [
+        PropertiesSerialization::EmitNullProperties,
+        PropertiesSerialization::EmitEmptyObject,
+    ] {
+        assert_eq!(
+            body_sent_with(serialization, empty()).await,
+            r#"{"db":"mydb","csl":"T","properties":{"options":{},"parameters":{}}}"#
+        );
+    }
+
+    let with_options = || {
+        Some(ClientRequestProperties::from(
+            OptionsBuilder::default()
+                .with_truncation_max_records(10)
+                .build()
+                .unwrap(),
+        ))
+    };
+    assert_eq!(
+        body_sent_with(PropertiesSerialization::OmitNullProperties, with_options()).await,
+        r#"{"db":"mydb","csl":"T","properties":{"options":{"truncationmaxrecords":10,"results_v2_newlines_between_frames":true}}}"#
+    );
+    assert_eq!(
+        body_sent_with(PropertiesSerialization::EmitEmptyObject, with_options()).await,
+        r#"{"db":"mydb","csl":"T","properties":{"options":{"truncationmaxrecords":10,"results_v2_newlines_between_frames":true},"parameters":{}}}"#
+    );
+}
+
+#[tokio::test]
+async fn parallel_queries_are_bounded_and_kept_in_order() {
+    let mock = Arc::new(MockKusto::default());
+    let client = mock_client(mock.clone());
+    let queries = ["q0", "q1", "fail", "q3", "q4"];
+
+    let gate = mock.gate.lock().await;
+    let mut batch = Box::pin(client.execute_queries_parallel("mydb", queries, None, 2));
+    assert!(futures::poll!(&mut batch).is_pending());
+    let sent = mock.requests();
+    assert_eq!(sent.len(), 2);
+    assert_eq!(sent[0].body["csl"], "q0");
+    assert_eq!(sent[1].body["csl"], "q1");
+    drop(gate);
+
+    let results = batch.await;
+    assert_eq!(mock.requests().len(), 5);
+    assert_eq!(results.len(), 5);
+    for (query, result) in queries.iter().zip(&results) {
+        if *query == "fail" {
+            assert!(result.is_err(), "Expected {query} to fail");
+        } else {
+            let response = result.as_ref().expect("Failed to execute query");
+            assert_eq!(response.primary_results().count(), 1);
+        }
+    }
+}
+
+#[tokio::test]
+async fn requests_without_authentication_have_no_authorization_header() {
+    let mock = Arc::new(MockKusto::default());
+    let client = mock_client(mock.clone());
+
+    client
+        .execute_query("mydb", "MyTable | take 10", None)
+        .await
+        .expect("Failed to execute query");
+    client
+        .execute_command("mydb", ".show tables", None)
+        .await
+        .expect("Failed to execute command");
+
+    let requests = mock.requests();
+    assert_eq!(requests.len(), 2);
+    for request in requests {
+        assert!(
+            request
+                .headers
+                .iter()
+                .all(|(name, _)| !name.eq_ignore_ascii_case("authorization")),
+            "Expected no authorization header to be sent to {}",
+            request.url
+        );
+    }
+}
+
+/// Serves HTTP on the local machine, redirecting every request back to where it was sent, and counts the requests it receives.
+fn redirecting_server() -> (String, Arc<AtomicUsize>) {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("Failed to listen");
+    let address = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Arc::new(AtomicUsize::new(0));
+    let received = requests.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { return };
+            let mut reader = BufReader::new(&mut stream);
+            let mut path = String::new();
+            let mut content_length = 0;
+            let mut line = String::new();
+            while reader.read_line(&mut line).is_ok() && line.trim_end() != "" {
+                if path.is_empty() {
+                    path = line.split(' ').nth(1).unwrap_or("/").to_string();
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        content_length = value.trim().parse().unwrap_or(0);
+                    }
+                }
+                line.clear();
+            }
+            let mut body = vec![0; content_length];
+            let _ = reader.read_exact(&mut body);
+            received.fetch_add(1, Ordering::SeqCst);
+            let _ = write!(
+                stream,
+                "HTTP/1.1 307 Temporary Redirect\r\nLocation: {path}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+            );
+        }
+    });
+    (address, requests)
+}
+
+#[tokio::test]
+async fn redirects_only_reach_the_client_through_a_transport_that_does_not_follow_them() {
+    let (address, requests) = redirecting_server();
+    let client = |options: KustoClientOptions| {
+        KustoClient::new(
+            ConnectionString::with_no_auth(address.as_str()),
+            options.with_allow_insecure_endpoint(true),
+        )
+        .expect("Failed to create client")
+    };
+    let client_options = || ClientOptions::default().retry(RetryOptions::none());
+
+    // The client follows the redirects up to its limit, then fails with the last one
+    let tuned = client(
+        KustoClientOptions::from(client_options()).with_http_transport(HttpTransportOptions::new()),
+    );
+    let result = tuned.execute_command("mydb", ".show tables", None).await;
+    assert!(result.is_err());
+    assert_eq!(requests.swap(0, Ordering::SeqCst), 4);
+
+    // The default transport kept from the ClientOptions follows them itself, past the limit of the client
+    let kept = client(KustoClientOptions::from(client_options()));
+    let result = kept.execute_command("mydb", ".show tables", None).await;
+    assert!(result.is_err());
+    assert!(requests.load(Ordering::SeqCst) > 4);
+}
+
+#[tokio::test]
+async fn data_sources_keep_their_port_and_lose_their_trailing_slash() {
+    for (data_source, expected) in [
+        (
+            "https://mycluster.kusto.windows.net/",
+            "https://mycluster.kusto.windows.net",
+        ),
+        (
+            "https://mycluster.kusto.windows.net:8443",
+            "https://mycluster.kusto.windows.net:8443",
+        ),
+        (
+            "mycluster.kusto.windows.net:8443/",
+            "https://mycluster.kusto.windows.net:8443",
+        ),
+        ("http://localhost:8080/", "http://localhost:8080"),
+    ] {
+        let mock = Arc::new(MockKusto::default());
+        let client = KustoClient::new(
+            ConnectionString::with_no_auth(data_source),
+            KustoClientOptions::from(
+                ClientOptions::default()
+                    .transport(TransportOptions::new_custom_policy(mock.clone())),
+            )
+            .with_allow_insecure_endpoint(true),
+        )
+        .expect("Failed to create client");
+
+        client
+            .execute_query("mydb", "MyTable | take 10", None)
+            .await
+            .expect("Failed to execute query");
+        client
+            .execute_command("mydb", ".show tables", None)
+            .await
+            .expect("Failed to execute command");
+
+        let urls: Vec<String> = mock.requests().into_iter().map(|r| r.url).collect();
+        assert_eq!(
+            urls,
+            vec![
+                format!("{expected}/v2/rest/query"),
+                format!("{expected}/v1/rest/mgmt")
+            ],
+            "Unexpected urls for the data source {data_source}"
+        );
+    }
+}
+
+#[test]
+fn unsupported_data_sources_are_rejected() {
+    let options = || KustoClientOptions::new().with_allow_insecure_endpoint(true);
+
+    let error = KustoClient::new(
+        ConnectionString::with_no_auth("net.tcp://localhost"),
+        options(),
+    )
+    .expect_err("Expected net.tcp to be rejected");
+    assert!(
+        matches!(
+            &error,
+            Error::ConnectionStringError(ConnectionStringError::UnsupportedScheme { scheme, .. })
+                if scheme == "net.tcp"
+        ),
+        "Unexpected error {error:?}"
+    );
+
+    for data_source in [
+        "https://",
+        "https://mycluster.kusto.windows.net/?db=x",
+        "https://my cluster",
+    ] {
+        let error = KustoClient::new(ConnectionString::with_no_auth(data_source), options())
+            .expect_err("Expected the data source to be rejected");
+        assert!(
+            matches!(
+                error,
+                Error::ConnectionStringError(ConnectionStringError::InvalidDataSource { .. })
+            ),
+            "Unexpected error {error:?} for the data source {data_source}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn requests_are_sent_to_the_overridden_endpoints_with_tokens_for_the_cluster() {
+    let mock = Arc::new(MockKusto::default());
+    // The token is the scope it was requested for
+    let connection_string = ConnectionString::with_token_callback_auth(
+        CLUSTER,
+        Arc::new(|scopes: &[&str]| scopes.join(" ")),
+        None,
+    );
+    let client = KustoClient::new(
+        connection_string,
+        KustoClientOptions::from(
+            ClientOptions::default().transport(TransportOptions::new_custom_policy(mock.clone())),
+        )
+        .with_allow_untrusted_endpoints(true)
+        .with_cloud_info(CloudInfo::azure_us_government())
+        .with_endpoint_overrides(
+            "https://query-proxy.contoso.com/v2/rest/query",
+            "https://mgmt-proxy.contoso.com/v1/rest/mgmt",
+        ),
+    )
+    .expect("Failed to create client");
+
+    client
+        .execute_query("mydb", "MyTable | take 10", None)
+        .await
+        .expect("Failed to execute query");
+    client
+        .execute_command("mydb", ".show tables", None)
+        .await
+        .expect("Failed to execute command");
+    client
+        .execute_streaming_ingest(
+            "mydb",
+            "MyTable",
+            StreamingIngestSource::BlobUri("https://account.blob.core.windows.net/c/b".into()),
+            "csv",
+            None,
+            None,
+        )
+        .await
+        .expect("Failed to ingest");
+
+    let requests = mock.requests();
+    assert_eq!(
+        requests.iter().map(|r| r.url.as_str()).collect::<Vec<_>>(),
+        vec![
+            "https://query-proxy.contoso.com/v2/rest/query",
+            "https://mgmt-proxy.contoso.com/v1/rest/mgmt",
+            "https://mgmt-proxy.contoso.com/v1/rest/ingest/mydb/MyTable?streamFormat=csv&sourceKind=uri",
+        ]
+    );
+    // Fetching the cloud info of the proxies would fall back to the public cloud
+    for request in &requests {
+        assert!(request.headers.contains(&(
+            "authorization".to_string(),
+            "Bearer https://kusto.kusto.usgovcloudapi.net/.default".to_string()
+        )));
+    }
+}
+
+#[test]
+fn invalid_endpoint_overrides_are_rejected() {
+    let client = |connection_string: ConnectionString, query_url: &str| {
+        KustoClient::new(
+            connection_string,
+            KustoClientOptions::new()
+                .with_endpoint_overrides(query_url, format!("{CLUSTER}/v1/rest/mgmt")),
+        )
+    };
+
+    for query_url in [
+        "mycluster.kusto.windows.net/v2/rest/query",
+        "http://mycluster.kusto.windows.net/v2/rest/query",
+        "net.tcp://mycluster.kusto.windows.net",
+        "https://mycluster.kusto.windows.net/v2/rest/query#fragment",
+    ] {
+        let error = client(ConnectionString::with_no_auth(CLUSTER), query_url)
+            .expect_err("Expected the override to be rejected");
+        assert!(
+            matches!(
+                &error,
+                Error::InvalidArgumentError(InvalidArgumentError::InvalidEndpoint { endpoint, .. })
+                    if endpoint == query_url
+            ),
+            "Unexpected error {error:?} for {query_url}"
+        );
+    }
+
+    // Tokens are only sent to unknown endpoints when they are allowed
+    let proxy = "https://query-proxy.contoso.com/v2/rest/query";
+    assert!(client(ConnectionString::with_default_auth(CLUSTER), proxy).is_err());
+    assert!(client(ConnectionString::with_no_auth(CLUSTER), proxy).is_ok());
+}
+
+#[tokio::test]
+async fn concurrent_queries_to_a_stopped_cluster_call_the_handler_once() {
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let recorded = calls.clone();
+    let mock = Arc::new(MockKusto::default());
+    let client = mock_client_with_options(mock.clone(), |options| {
+        options.with_cluster_stopped_handler(Arc::new(move |cluster| {
+            recorded.lock().unwrap().push(cluster.to_string());
+        }))
+    });
+
+    let results = futures::future::join_all(
+        (0..8).map(|_| client.execute_query("mydb", "stopped", None).into_future()),
+    )
+    .await;
+    assert_eq!(mock.requests().len(), 8);
+    for result in results {
+        match result {
+            Err(Error::ClusterStopped { cluster }) => assert_eq!(cluster, CLUSTER),
+            other => panic!("Expected the cluster to be stopped, got {other:?}"),
+        }
+    }
+    assert_eq!(*calls.lock().unwrap(), vec![CLUSTER.to_string()]);
+
+    // Once a request succeeds, the cluster being stopped again is reported again
+    client
+        .execute_query("mydb", "MyTable | take 10", None)
+        .await
+        .expect("Failed to execute query");
+    let error = client
+        .execute_command("mydb", "stopped", None)
+        .await
+        .expect_err("Expected the command to fail");
+    assert!(matches!(error, Error::ClusterStopped { .. }));
+    assert_eq!(calls.lock().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn stopped_clusters_reported_in_successful_responses_call_the_handler_once() {
+    let calls = Arc::new(Mutex::new(0));
+    let recorded = calls.clone();
+    let client = mock_client_with_options(Arc::new(MockKusto::default()), |options| {
+        options.with_cluster_stopped_handler(Arc::new(move |_| *recorded.lock().unwrap() += 1))
+    });
+
+    // The error in the body of a response doesn't make the request successful, which would report it again
+    for _ in 0..2 {
+        let error = client
+            .execute_command("mydb", "stopped in body", None)
+            .await
+            .expect_err("Expected the command to fail");
+        assert!(matches!(error, Error::ClusterStopped { .. }), "{error:?}");
+        let error = client
+            .execute_query("mydb", "stopped in body", None)
+            .await
+            .expect_err("Expected the query to fail");
+        assert!(matches!(error, Error::ClusterStopped { .. }), "{error:?}");
+    }
+    assert_eq!(*calls.lock().unwrap(), 1);
+
+    client
+        .execute_query("mydb", "MyTable | take 10", None)
+        .await
+        .expect("Failed to execute query");
+    let error = client
+        .execute_query("mydb", "stopped in body", None)
+        .await
+        .expect_err("Expected the query to fail");
+    assert!(matches!(error, Error::ClusterStopped { .. }));
+    assert_eq!(*calls.lock().unwrap(), 2);
+}
+
+#[tokio::test]
+async fn cancelled_queries_are_dropped_and_cancelled_on_the_cluster() {
+    let mock = Arc::new(MockKusto::default());
+    let client = mock_client(mock.clone());
+    let (cancel, cancelled) = futures::channel::oneshot::channel::<()>();
+
+    let query = client.execute_query_with_cancellation("mydb", "hang", None, async {
+        let _ = cancelled.await;
+    });
+    let trigger = async {
+        while mock.requests().is_empty() {
+            tokio::task::yield_now().await;
+        }
+        cancel.send(()).unwrap();
+    };
+    let (result, ()) = futures::join!(query, trigger);
+
+    let client_request_id = match result {
+        Err(Error::QueryCancelled { client_request_id }) => client_request_id,
+        other => panic!("Expected the query to be cancelled, got {other:?}"),
+    };
+    // `.cancel query` is sent in the background
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while mock.requests().len() < 2 {
+            tokio::task::yield_now().await;
+        }
+    })
+    .await
+    .expect("Expected the query to be cancelled on the cluster");
+    let requests = mock.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(
+        requests[0].client_request_id.as_deref(),
+        Some(client_request_id.as_str())
+    );
+    assert!(client_request_id.starts_with("KRust;"));
+    assert_eq!(requests[1].url, format!("{CLUSTER}/v1/rest/mgmt"));
+    assert_eq!(requests[1].body["db"], "mydb");
+    assert_eq!(
+        requests[1].body["csl"],
+        format!(".cancel query \"{client_request_id}\"")
+    );
+
+    // Queries that complete first are returned as they are, without cancelling them
+    let response = client
+        .execute_query_with_cancellation(
+            "mydb",
+            "MyTable | take 10",
+            None,
+            futures::future::pending(),
+        )
+        .await
+        .expect("Failed to execute query");
+    assert_eq!(response.primary_results().count(), 1);
+    assert_eq!(mock.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn cancelled_queries_return_without_waiting_for_the_cluster() {
+    let mock = Arc::new(MockKusto::default());
+    let client = mock_client(mock.clone());
+    // The `.cancel query` of this client request id never completes
+    let properties = ClientRequestProperties {
+        client_request_id: Some("hang".to_string()),
+        ..ClientRequestProperties::default()
+    };
+
+    // Without the tokio feature, the cancellation is waited for up to a timeout of 5 seconds
+    let result = tokio::time::timeout(
+        Duration::from_secs(10),
+        client.execute_query_with_cancellation("mydb", "hang", Some(properties.clone()), async {}),
+    )
+    .await
+    .expect("Expected the query to return without waiting for its cancellation");
+    assert!(matches!(result, Err(Error::QueryCancelled { .. })));
+
+    #[cfg(feature = "tokio")]
+    {
+        let result = tokio::time::timeout(
+            Duration::from_secs(5),
+            client.execute_streaming_query_with_cancellation(
+                "mydb",
+                "hang",
+                Some(properties),
+                async {},
+            ),
+        )
+        .await
+        .expect("Expected the query to return without waiting for its cancellation");
+        assert!(matches!(result, Err(Error::QueryCancelled { .. })));
+    }
+}
+
+#[tokio::test]
+async fn requests_carry_the_trace_context() {
+    const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+    let mock = Arc::new(MockKusto::default());
+    let client = mock_client_with_options(mock.clone(), |options| {
+        options.with_trace_context_provider(Arc::new(|| TraceContext::parse(TRACEPARENT)))
+    });
+
+    client
+        .execute_query("mydb", "MyTable | take 10", None)
+        .await
+        .expect("Failed to execute query");
+    client
+        .execute_command("mydb", ".show tables", None)
+        .await
+        .expect("Failed to execute command");
+    let properties = ClientRequestProperties {
+        client_request_id: Some("MyApp;my-id".to_string()),
+        ..ClientRequestProperties::default()
+    };
+    client
+        .execute_query("mydb", "MyTable | take 10", Some(properties))
+        .await
+        .expect("Failed to execute query");
+
+    let requests = mock.requests();
+    assert_eq!(requests.len(), 3);
+    for request in &requests {
+        assert!(request
+            .headers
+            .contains(&("traceparent".to_string(), TRACEPARENT.to_string())));
+    }
+    for request in &requests[..2] {
+        let client_request_id = request.client_request_id.as_deref().unwrap();
+        let parts: Vec<_> = client_request_id.split(';').collect();
+        assert_eq!(parts.len(), 3, "{client_request_id}");
+        assert_eq!(parts[0], "KRust");
+        assert_eq!(parts[1], "4bf92f3577b34da6a3ce929d0e0e4736");
+    }
+    // Client request ids set by the caller are kept as they are
+    assert_eq!(
+        requests[2].client_request_id.as_deref(),
+        Some("MyApp;my-id")
+    );
+
+    // Without a trace context, requests are sent as before
+    let mock = Arc::new(MockKusto::default());
+    let client = mock_client_with_options(mock.clone(), |options| {
+        options.with_trace_context_provider(Arc::new(|| None))
+    });
+    client
+        .execute_query("mydb", "MyTable | take 10", None)
+        .await
+        .expect("Failed to execute query");
+    let requests = mock.requests();
+    assert!(requests[0]
+        .headers
+        .iter()
+        .all(|(name, _)| name != "traceparent"));
+    assert_eq!(requests[0].client_request_id, None);
+}
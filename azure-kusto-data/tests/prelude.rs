@@ -0,0 +1,102 @@
+//! Builds requests and responses with nothing but the prelude, so that removing one of its exports fails the build.
+
+use std::str::FromStr;
+
+use azure_kusto_data::prelude::*;
+use serde_json::json;
+
+fn column(name: &str, column_type: ColumnType) -> Column {
+    Column {
+        column_name: name.to_string(),
+        column_type,
+    }
+}
+
+#[test]
+fn responses_are_built_from_the_prelude() {
+    let columns = vec![
+        column("Name", ColumnType::String),
+        column("Time", ColumnType::Datetime),
+        column("Elapsed", ColumnType::Timespan),
+        column("Details", ColumnType::Dynamic),
+    ];
+    let row = json!(["a", "2023-01-01T00:00:00Z", "00:00:01", "{\"b\": 1}"]);
+
+    let response = KustoResponseDataSetV2 {
+        results: vec![
+            V2QueryResult::DataSetHeader(DataSetHeader::new(true, "v2.0")),
+            V2QueryResult::TableHeader(TableHeader {
+                table_id: 1,
+                table_name: "PrimaryResult".to_string(),
+                table_kind: TableKind::PrimaryResult,
+                columns: columns.clone(),
+            }),
+            V2QueryResult::TableFragment(TableFragment {
+                table_id: 1,
+                field_count: Some(4),
+                table_fragment_type: TableFragmentType::DataAppend,
+                rows: vec![row.clone()],
+            }),
+            V2QueryResult::TableProgress(TableProgress {
+                table_id: 1,
+                table_progress: 100.0,
+            }),
+            V2QueryResult::TableCompletion(TableCompletion::new(1, 1)),
+            V2QueryResult::DataSetCompletion(DataSetCompletion::new(false, false)),
+        ],
+    };
+
+    let table: DataTable = response
+        .single_primary_result()
+        .expect("Failed to assemble the primary result");
+    assert_eq!(table.columns, columns);
+    assert_eq!(table.rows, vec![row]);
+
+    let row: RowView = table.row_views().next().expect("Expected a row");
+    let time: Option<KustoDateTime> = row.get_datetime("Time").expect("Failed to read the time");
+    assert_eq!(time, KustoDateTime::from_str("2023-01-01T00:00:00Z").ok());
+    let elapsed: Option<KustoTimespan> = row
+        .get_timespan("Elapsed")
+        .expect("Failed to read the timespan");
+    assert_eq!(elapsed, KustoTimespan::from_str("00:00:01").ok());
+    let details: KustoDynamic = serde_json::from_value(row.get("Details").unwrap().clone())
+        .expect("Failed to read the dynamic");
+    assert_eq!(details["b"], 1);
+
+    let _: fn(&ErrorMessage) -> &ErrorMessage = |e| e;
+}
+
+#[test]
+fn requests_are_built_from_the_prelude() {
+    let options: Options = OptionsBuilder::default()
+        .with_query_consistency(QueryConsistency::WeakConsistency)
+        .with_query_language(QueryLanguage::Kql)
+        .with_server_timeout(KustoTimespan::from_str("00:01:00").unwrap())
+        .build()
+        .expect("Failed to build the options");
+
+    let properties: ClientRequestProperties = ClientRequestPropertiesBuilder::default()
+        .with_options(options)
+        .with_case_mapping(CaseMapping::PascalToSnake)
+        .build()
+        .expect("Failed to build the request properties");
+    assert!(matches!(
+        properties
+            .options
+            .as_ref()
+            .and_then(|o| o.query_consistency.as_ref()),
+        Some(QueryConsistency::WeakConsistency)
+    ));
+
+    let connection_string =
+        ConnectionString::with_default_auth("https://mycluster.kusto.windows.net");
+    assert!(KustoClient::new(connection_string, KustoClientOptions::default()).is_ok());
+    let _ = (DuplicateKeys::Error, QueryKind::Query, DataScope::HotCache);
+    let _ = V1ParsingMode::default();
+    let _ = PropertiesSerialization::default();
+    let _: Option<WeakConsistencySession> = None;
+    let _: Option<KustoResponse> = None;
+    let _: Option<KustoResponseDataSetV1> = None;
+    let _: Option<StreamingIngestSource> = None;
+    let _: Option<Error> = None;
+}
@@ -0,0 +1,71 @@
+//! Runs the buffered query path on wasm32, with a transport that replays a recorded response.
+//! Run with `wasm-pack test --node -- --no-default-features --features wasm --test wasm`.
+#![cfg(target_arch = "wasm32")]
+
+use azure_core::headers::{Headers, AUTHORIZATION};
+use azure_core::{
+    BytesStream, ClientOptions, Context, Policy, PolicyResult, Request, Response, StatusCode,
+    TransportOptions,
+};
+use azure_kusto_data::cloud_info::CloudInfo;
+use azure_kusto_data::prelude::*;
+use bytes::Bytes;
+use std::sync::Arc;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+const CLUSTER: &str = "https://mycluster.kusto.windows.net";
+
+/// Answers every request with a recorded v2 query response, instead of sending it.
+#[derive(Debug)]
+struct ReplayTransport;
+
+#[async_trait::async_trait(?Send)]
+impl Policy for ReplayTransport {
+    async fn send(
+        &self,
+        _ctx: &Context,
+        request: &mut Request,
+        _next: &[Arc<dyn Policy>],
+    ) -> PolicyResult {
+        assert_eq!(request.url().as_str(), format!("{CLUSTER}/v2/rest/query"));
+        assert_eq!(
+            request.headers().get_optional_str(&AUTHORIZATION),
+            Some("Bearer token")
+        );
+
+        Ok(Response::new(
+            StatusCode::Ok,
+            Headers::new(),
+            Box::pin(BytesStream::new(Bytes::from_static(include_bytes!(
+                "inputs/dataframe.json"
+            )))),
+        ))
+    }
+}
+
+#[wasm_bindgen_test]
+async fn buffered_query_with_token_auth() {
+    // Avoid fetching the cloud info from the (fake) cluster.
+    CloudInfo::add_to_cache(CLUSTER, CloudInfo::default()).await;
+
+    let client = KustoClient::new(
+        ConnectionString::with_token_auth(CLUSTER, "token"),
+        KustoClientOptions::from(ClientOptions::default().transport(
+            TransportOptions::new_custom_policy(Arc::new(ReplayTransport)),
+        )),
+    )
+    .expect("Failed to create client");
+
+    let response = client
+        .execute_query("db", "datatable | take 8", None)
+        .await
+        .expect("Failed to execute query");
+
+    let tables: Vec<DataTable> = response
+        .into_primary_results()
+        .collect::<Result<_, _>>()
+        .expect("Failed to parse the primary results");
+    assert_eq!(tables.len(), 1);
+    assert_eq!(tables[0].columns.len(), 6);
+    assert_eq!(tables[0].rows.len(), 8);
+}
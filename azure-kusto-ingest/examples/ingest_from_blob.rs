@@ -3,7 +3,7 @@ use std::env;
 use azure_kusto_data::prelude::{ConnectionString, KustoClient, KustoClientOptions};
 use azure_kusto_ingest::data_format::DataFormat;
 use azure_kusto_ingest::descriptors::{BlobAuth, BlobDescriptor};
-use azure_kusto_ingest::ingestion_properties::IngestionProperties;
+use azure_kusto_ingest::ingestion_properties::IngestionPropertiesBuilder;
 use azure_kusto_ingest::queued_ingest::QueuedIngestClient;
 
 /// Example of ingesting data into Kusto from Azure Blob Storage using managed identities.
@@ -29,17 +29,18 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     // Create a queued ingest client
     let queued_ingest_client = QueuedIngestClient::new(kusto_client);
 
-    // Define ingestion properties
-    let ingestion_properties = IngestionProperties {
-        database_name: env::var("KUSTO_DATABASE_NAME").expect("Must define KUSTO_DATABASE_NAME"),
-        table_name: env::var("KUSTO_TABLE_NAME").expect("Must define KUSTO_TABLE_NAME"),
+    // Define ingestion properties, which are validated when built
+    let ingestion_properties = IngestionPropertiesBuilder::default()
+        .with_database_name(
+            env::var("KUSTO_DATABASE_NAME").expect("Must define KUSTO_DATABASE_NAME"),
+        )
+        .with_table_name(env::var("KUSTO_TABLE_NAME").expect("Must define KUSTO_TABLE_NAME"))
         // Don't delete the blob on successful ingestion
-        retain_blob_on_success: Some(true),
-        // File format of the blob is Parquet
-        data_format: DataFormat::Parquet,
-        // Assume the server side default for flush_immediately
-        flush_immediately: None,
-    };
+        .with_retain_blob_on_success(true)
+        // File format of the blob is Parquet, whose columns are mapped to the table columns by name
+        .with_data_format(DataFormat::Parquet)
+        // Everything else, such as flush_immediately, assumes the server side defaults
+        .build()?;
 
     // Define the blob to ingest from
     let blob_uri = env::var("BLOB_URI").expect("Must define BLOB_URI");
@@ -53,9 +54,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let blob_descriptor = BlobDescriptor::new(blob_uri, blob_size, None)
         .with_blob_auth(BlobAuth::SystemAssignedManagedIdentity);
 
-    let _ = queued_ingest_client
+    let result = queued_ingest_client
         .ingest_from_blob(blob_descriptor, ingestion_properties)
         .await?;
+    println!("{result}");
 
     Ok(())
 }
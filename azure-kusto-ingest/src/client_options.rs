@@ -1,10 +1,42 @@
+use std::time::Duration;
+
 use azure_core::ClientOptions;
 
-/// Allows configurability of ClientOptions for the storage clients used within [QueuedIngestClient](crate::queued_ingest::QueuedIngestClient)
-#[derive(Clone, Default)]
+use crate::queued_ingest::DEFAULT_MAX_CONCURRENT_ENQUEUES;
+use crate::resource_manager::RESOURCE_REFRESH_PERIOD;
+use crate::staging::{DEFAULT_STAGING_POLL_INTERVAL, DEFAULT_STAGING_TIMEOUT};
+
+/// Allows configurability of ClientOptions for the storage clients used within [QueuedIngestClient](crate::queued_ingest::QueuedIngestClient).
+/// Create them with [QueuedIngestClientOptionsBuilder], or from [ClientOptions]
+#[derive(Clone)]
+#[non_exhaustive]
 pub struct QueuedIngestClientOptions {
     pub queue_service_options: ClientOptions,
     pub blob_service_options: ClientOptions,
+    /// Allows storage resources served over http on loopback hosts, such as by the Azurite storage emulator. Remote hosts must use https regardless
+    pub allow_insecure_endpoint: bool,
+    /// How long the ingestion resources and the authorization context are cached before being fetched from Kusto again. Defaults to an hour.
+    /// Once expired, they keep being used while they are fetched in the background, until they are older than the [resource_max_age](Self::resource_max_age)
+    pub resource_refresh_period: Duration,
+    /// How old the ingestion resources and the authorization context may get while they are fetched in the background, after which they are fetched before being used.
+    /// When [None], twice the [resource_refresh_period](Self::resource_refresh_period)
+    pub resource_max_age: Option<Duration>,
+    /// Time to live of the ingestion messages in the storage queue. When [None], the default of the queue service is used
+    pub message_ttl: Option<Duration>,
+    /// How long ingestion messages are invisible in the storage queue after being posted. When [None], they are visible immediately
+    pub message_visibility_timeout: Option<Duration>,
+    /// How often the status of a copy into the temporary storage is checked by [ingest_via_staging](crate::queued_ingest::QueuedIngestClient::ingest_via_staging). Defaults to a second
+    pub staging_poll_interval: Duration,
+    /// How long [ingest_via_staging](crate::queued_ingest::QueuedIngestClient::ingest_via_staging) waits for a copy into the temporary storage to complete. Defaults to 10 minutes
+    pub staging_timeout: Duration,
+    /// How many messages [ingest_from_blobs](crate::queued_ingest::QueuedIngestClient::ingest_from_blobs) posts to the queues at once. Defaults to 16
+    pub max_concurrent_enqueues: usize,
+}
+
+impl Default for QueuedIngestClientOptions {
+    fn default() -> Self {
+        Self::from(ClientOptions::default())
+    }
 }
 
 impl From<ClientOptions> for QueuedIngestClientOptions {
@@ -13,15 +45,37 @@ impl From<ClientOptions> for QueuedIngestClientOptions {
         Self {
             queue_service_options: client_options.clone(),
             blob_service_options: client_options,
+            allow_insecure_endpoint: false,
+            resource_refresh_period: RESOURCE_REFRESH_PERIOD,
+            resource_max_age: None,
+            message_ttl: None,
+            message_visibility_timeout: None,
+            staging_poll_interval: DEFAULT_STAGING_POLL_INTERVAL,
+            staging_timeout: DEFAULT_STAGING_TIMEOUT,
+            max_concurrent_enqueues: DEFAULT_MAX_CONCURRENT_ENQUEUES,
         }
     }
 }
 
 /// Builder for [QueuedIngestClientOptions], call `build()` to create the [QueuedIngestClientOptions]
-#[derive(Clone, Default)]
+#[derive(Clone)]
 pub struct QueuedIngestClientOptionsBuilder {
     queue_service_options: ClientOptions,
     blob_service_options: ClientOptions,
+    allow_insecure_endpoint: bool,
+    resource_refresh_period: Duration,
+    resource_max_age: Option<Duration>,
+    message_ttl: Option<Duration>,
+    message_visibility_timeout: Option<Duration>,
+    staging_poll_interval: Duration,
+    staging_timeout: Duration,
+    max_concurrent_enqueues: usize,
+}
+
+impl Default for QueuedIngestClientOptionsBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl QueuedIngestClientOptionsBuilder {
@@ -29,6 +83,14 @@ impl QueuedIngestClientOptionsBuilder {
         Self {
             queue_service_options: ClientOptions::default(),
             blob_service_options: ClientOptions::default(),
+            allow_insecure_endpoint: false,
+            resource_refresh_period: RESOURCE_REFRESH_PERIOD,
+            resource_max_age: None,
+            message_ttl: None,
+            message_visibility_timeout: None,
+            staging_poll_interval: DEFAULT_STAGING_POLL_INTERVAL,
+            staging_timeout: DEFAULT_STAGING_TIMEOUT,
+            max_concurrent_enqueues: DEFAULT_MAX_CONCURRENT_ENQUEUES,
         }
     }
 
@@ -42,10 +104,70 @@ impl QueuedIngestClientOptionsBuilder {
         self
     }
 
+    /// Allows storage resources served over http on loopback hosts, for testing against the Azurite storage emulator
+    pub fn with_allow_insecure_endpoint(mut self, allow_insecure_endpoint: bool) -> Self {
+        self.allow_insecure_endpoint = allow_insecure_endpoint;
+        self
+    }
+
+    /// Sets how long the ingestion resources and the authorization context are cached before being fetched from Kusto again.
+    /// Shorter periods pick up changes sooner, while longer periods reduce the load on the management endpoint
+    pub fn with_resource_refresh_period(mut self, resource_refresh_period: Duration) -> Self {
+        self.resource_refresh_period = resource_refresh_period;
+        self
+    }
+
+    /// Sets how old the ingestion resources and the authorization context may get while they are fetched again in the background.
+    /// Older resources are fetched before being used, failing the ingestion if they can't be
+    pub fn with_resource_max_age(mut self, resource_max_age: Duration) -> Self {
+        self.resource_max_age = Some(resource_max_age);
+        self
+    }
+
+    /// Sets the time to live of the ingestion messages in the storage queue
+    pub fn with_message_ttl(mut self, message_ttl: Duration) -> Self {
+        self.message_ttl = Some(message_ttl);
+        self
+    }
+
+    /// Sets how long ingestion messages are invisible in the storage queue after being posted
+    pub fn with_message_visibility_timeout(mut self, message_visibility_timeout: Duration) -> Self {
+        self.message_visibility_timeout = Some(message_visibility_timeout);
+        self
+    }
+
+    /// Sets how often the status of a copy into the temporary storage is checked when ingesting via staging
+    pub fn with_staging_poll_interval(mut self, staging_poll_interval: Duration) -> Self {
+        self.staging_poll_interval = staging_poll_interval;
+        self
+    }
+
+    /// Sets how long to wait for a copy into the temporary storage to complete when ingesting via staging.
+    /// Copies that take longer are abandoned, and their staged blob is deleted
+    pub fn with_staging_timeout(mut self, staging_timeout: Duration) -> Self {
+        self.staging_timeout = staging_timeout;
+        self
+    }
+
+    /// Sets how many messages are posted to the queues at once when ingesting a batch of blobs.
+    /// Zero is treated as one
+    pub fn with_max_concurrent_enqueues(mut self, max_concurrent_enqueues: usize) -> Self {
+        self.max_concurrent_enqueues = max_concurrent_enqueues;
+        self
+    }
+
     pub fn build(self) -> QueuedIngestClientOptions {
         QueuedIngestClientOptions {
             queue_service_options: self.queue_service_options,
             blob_service_options: self.blob_service_options,
+            allow_insecure_endpoint: self.allow_insecure_endpoint,
+            resource_refresh_period: self.resource_refresh_period,
+            resource_max_age: self.resource_max_age,
+            message_ttl: self.message_ttl,
+            message_visibility_timeout: self.message_visibility_timeout,
+            staging_poll_interval: self.staging_poll_interval,
+            staging_timeout: self.staging_timeout,
+            max_concurrent_enqueues: self.max_concurrent_enqueues,
         }
     }
 }
@@ -2,7 +2,7 @@ use serde::Serialize;
 
 /// All data formats supported by Kusto.
 /// Default is [DataFormat::CSV]
-#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+#[derive(Serialize, Clone, Debug, Default, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum DataFormat {
     ApacheAvro,
@@ -25,6 +25,86 @@ pub enum DataFormat {
     W3CLOGFILE,
 }
 
+/// Kinds of ingestion mappings, as referenced by the `ingestionMappingType` of an ingestion
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub enum IngestionMappingKind {
+    Csv,
+    Json,
+    Avro,
+    ApacheAvro,
+    Parquet,
+    SStream,
+    Orc,
+    W3CLogFile,
+}
+
+impl DataFormat {
+    /// Returns the kind of ingestion mapping that data of this format is ingested with
+    pub fn ingestion_mapping_kind(&self) -> IngestionMappingKind {
+        match self {
+            DataFormat::CSV
+            | DataFormat::TSV
+            | DataFormat::SCSV
+            | DataFormat::SOHsv
+            | DataFormat::PSV
+            | DataFormat::TXT
+            | DataFormat::RAW
+            | DataFormat::TSVe => IngestionMappingKind::Csv,
+            DataFormat::JSON | DataFormat::MultiJSON | DataFormat::SingleJSON => {
+                IngestionMappingKind::Json
+            }
+            DataFormat::Avro => IngestionMappingKind::Avro,
+            DataFormat::ApacheAvro => IngestionMappingKind::ApacheAvro,
+            DataFormat::Parquet => IngestionMappingKind::Parquet,
+            DataFormat::SStream => IngestionMappingKind::SStream,
+            DataFormat::ORC => IngestionMappingKind::Orc,
+            DataFormat::W3CLOGFILE => IngestionMappingKind::W3CLogFile,
+        }
+    }
+
+    /// The kind of mapping that Kusto uses for this format when no mapping kind is given.
+    /// Same as [DataFormat::ingestion_mapping_kind]
+    pub fn default_mapping_kind(&self) -> IngestionMappingKind {
+        self.ingestion_mapping_kind()
+    }
+
+    /// The name of the format, as expected by Kusto
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DataFormat::ApacheAvro => "apacheavro",
+            DataFormat::Avro => "avro",
+            DataFormat::CSV => "csv",
+            DataFormat::JSON => "json",
+            DataFormat::MultiJSON => "multijson",
+            DataFormat::ORC => "orc",
+            DataFormat::Parquet => "parquet",
+            DataFormat::PSV => "psv",
+            DataFormat::RAW => "raw",
+            DataFormat::SCSV => "scsv",
+            DataFormat::SOHsv => "sohsv",
+            DataFormat::SingleJSON => "singlejson",
+            DataFormat::SStream => "sstream",
+            DataFormat::TSV => "tsv",
+            DataFormat::TSVe => "tsve",
+            DataFormat::TXT => "txt",
+            DataFormat::W3CLOGFILE => "w3clogfile",
+        }
+    }
+
+    /// Whether data of this format benefits from being gzipped before upload.
+    /// Binary formats are compressed internally, so compressing them again only costs time
+    pub fn compressible(&self) -> bool {
+        !matches!(
+            self,
+            DataFormat::Parquet
+                | DataFormat::ORC
+                | DataFormat::Avro
+                | DataFormat::ApacheAvro
+                | DataFormat::SStream
+        )
+    }
+}
+
 // Unit tests
 #[cfg(test)]
 mod tests {
@@ -34,4 +114,116 @@ mod tests {
     fn data_format_default() {
         assert_eq!(DataFormat::default(), DataFormat::CSV);
     }
+
+    #[test]
+    fn data_format_serialization() {
+        let cases = [
+            (DataFormat::ApacheAvro, "apacheavro"),
+            (DataFormat::Avro, "avro"),
+            (DataFormat::CSV, "csv"),
+            (DataFormat::JSON, "json"),
+            (DataFormat::MultiJSON, "multijson"),
+            (DataFormat::ORC, "orc"),
+            (DataFormat::Parquet, "parquet"),
+            (DataFormat::PSV, "psv"),
+            (DataFormat::RAW, "raw"),
+            (DataFormat::SCSV, "scsv"),
+            (DataFormat::SOHsv, "sohsv"),
+            (DataFormat::SingleJSON, "singlejson"),
+            (DataFormat::SStream, "sstream"),
+            (DataFormat::TSV, "tsv"),
+            (DataFormat::TSVe, "tsve"),
+            (DataFormat::TXT, "txt"),
+            (DataFormat::W3CLOGFILE, "w3clogfile"),
+        ];
+
+        for (data_format, expected) in cases {
+            assert_eq!(
+                serde_json::to_string(&data_format).unwrap(),
+                format!("\"{expected}\""),
+                "{data_format:?}"
+            );
+            assert_eq!(data_format.as_str(), expected);
+        }
+    }
+
+    #[test]
+    fn data_format_compressible() {
+        for data_format in [
+            DataFormat::Parquet,
+            DataFormat::ORC,
+            DataFormat::Avro,
+            DataFormat::ApacheAvro,
+            DataFormat::SStream,
+        ] {
+            assert!(!data_format.compressible(), "{data_format:?}");
+        }
+        for data_format in [
+            DataFormat::CSV,
+            DataFormat::TSV,
+            DataFormat::JSON,
+            DataFormat::MultiJSON,
+            DataFormat::W3CLOGFILE,
+            DataFormat::RAW,
+        ] {
+            assert!(data_format.compressible(), "{data_format:?}");
+        }
+    }
+
+    #[test]
+    fn data_format_ingestion_mapping_kind() {
+        assert_eq!(
+            DataFormat::TSVe.ingestion_mapping_kind(),
+            IngestionMappingKind::Csv
+        );
+        assert_eq!(
+            DataFormat::MultiJSON.ingestion_mapping_kind(),
+            IngestionMappingKind::Json
+        );
+        assert_eq!(
+            DataFormat::Parquet.ingestion_mapping_kind(),
+            IngestionMappingKind::Parquet
+        );
+        assert_eq!(
+            DataFormat::ApacheAvro.ingestion_mapping_kind(),
+            IngestionMappingKind::ApacheAvro
+        );
+        assert_eq!(
+            DataFormat::SingleJSON.ingestion_mapping_kind(),
+            IngestionMappingKind::Json
+        );
+        assert_eq!(
+            DataFormat::ORC.ingestion_mapping_kind(),
+            IngestionMappingKind::Orc
+        );
+        assert_eq!(
+            DataFormat::SStream.ingestion_mapping_kind(),
+            IngestionMappingKind::SStream
+        );
+        assert_eq!(
+            serde_json::to_string(&DataFormat::W3CLOGFILE.ingestion_mapping_kind()).unwrap(),
+            "\"W3CLogFile\""
+        );
+    }
+
+    #[test]
+    fn data_format_default_mapping_kind() {
+        for data_format in [
+            DataFormat::TSVe,
+            DataFormat::MultiJSON,
+            DataFormat::Parquet,
+            DataFormat::ApacheAvro,
+            DataFormat::W3CLOGFILE,
+        ] {
+            assert_eq!(
+                data_format.default_mapping_kind(),
+                data_format.ingestion_mapping_kind(),
+                "{data_format:?}"
+            );
+        }
+        assert_eq!(
+            DataFormat::TSVe.default_mapping_kind(),
+            IngestionMappingKind::Csv
+        );
+    }
 }
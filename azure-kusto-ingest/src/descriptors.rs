@@ -37,6 +37,20 @@ impl BlobDescriptor {
         self
     }
 
+    /// Returns the uri without its query string, which may carry a SAS token, nor any of the authentication information of the descriptor
+    pub(crate) fn redacted_uri(&self) -> &str {
+        self.uri.split('?').next().unwrap_or_default()
+    }
+
+    /// Whether the blob is accessed with a managed identity of the Kusto cluster, rather than with credentials in its uri
+    pub(crate) fn uses_managed_identity(&self) -> bool {
+        matches!(
+            self.blob_auth,
+            Some(BlobAuth::UserAssignedManagedIdentity(_))
+                | Some(BlobAuth::SystemAssignedManagedIdentity)
+        )
+    }
+
     /// Returns the uri with the authentication information concatenated, ready to be serialized into the ingestion message
     pub(crate) fn uri(&self) -> String {
         match &self.blob_auth {
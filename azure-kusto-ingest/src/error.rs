@@ -7,10 +7,26 @@ pub enum Error {
     #[error("Error obtaining ingestion resources: {0}")]
     ResourceManagerError(#[from] super::resource_manager::ResourceManagerError),
 
+    /// Error raised when failing to copy a blob into the temporary storage of Kusto.
+    #[error("Error staging blob: {0}")]
+    StagingError(#[from] super::staging::StagingError),
+
+    /// Error raised when the ingestion properties are invalid, before queuing the ingestion.
+    #[error(transparent)]
+    IngestionPropertiesError(#[from] super::ingestion_properties::IngestionPropertiesError),
+
+    /// Error raised when records cannot be serialized for ingestion
+    #[error("Error serializing records: {0}")]
+    RecordsError(String),
+
     /// Error relating to (de-)serialization of JSON data
     #[error("Error in JSON serialization/deserialization: {0}")]
     JsonError(#[from] serde_json::Error),
 
+    /// Error raised when executing a command against Kusto
+    #[error("Error in Kusto: {0}")]
+    KustoError(#[from] azure_kusto_data::error::Error),
+
     /// Error occurring within core azure crates
     #[error("Error in azure-core: {0}")]
     AzureError(#[from] azure_core::error::Error),
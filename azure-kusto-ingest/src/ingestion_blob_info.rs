@@ -1,9 +1,15 @@
-use serde::Serialize;
+use std::collections::BTreeMap;
+
+use azure_kusto_data::trace_context::TraceContext;
+use serde::ser::Error as _;
+use serde::{Serialize, Serializer};
 use uuid::Uuid;
 
 use crate::{
-    data_format::DataFormat, descriptors::BlobDescriptor,
-    ingestion_properties::IngestionProperties,
+    data_format::{DataFormat, IngestionMappingKind},
+    descriptors::BlobDescriptor,
+    ingestion_properties::{IngestionProperties, IngestionPropertiesError},
+    ingestion_status::{ReportLevel, ReportMethod},
     resource_manager::authorization_context::KustoIdentityToken,
 };
 
@@ -48,25 +54,50 @@ pub(crate) struct QueuedIngestionMessage {
     /// If set to `true`, any server side aggregation will be skipped - thus overriding the batching policy. Default is `false`.
     #[serde(skip_serializing_if = "Option::is_none")]
     flush_immediately: Option<bool>,
+    /// Which outcomes of the ingestion are reported. Default is failures only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    report_level: Option<ReportLevel>,
+    /// Where the outcomes are reported. Default is the status queues
+    #[serde(skip_serializing_if = "Option::is_none")]
+    report_method: Option<ReportMethod>,
     #[serde(with = "kusto_ingest_iso8601_format")]
     source_message_creation_time: OffsetDateTime,
     // source_message_creation_time: DateTime<Utc>,
     // Extra properties added to the ingestion command
+    #[serde(serialize_with = "serialize_additional_properties")]
     additional_properties: AdditionalProperties,
 }
 
 impl QueuedIngestionMessage {
+    /// Builds the message of a blob.
+    /// Fails when a raw additional property would replace a reserved one, even if the properties were not validated,
+    /// unless [allow_additional_property_overrides](IngestionProperties::allow_additional_property_overrides) is set
     pub(crate) fn new(
         blob_descriptor: &BlobDescriptor,
         ingestion_properties: &IngestionProperties,
         authorization_context: KustoIdentityToken,
-    ) -> Self {
+    ) -> Result<Self, IngestionPropertiesError> {
+        let problems = ingestion_properties.additional_property_override_problems();
+        if !problems.is_empty() {
+            return Err(IngestionPropertiesError { problems });
+        }
+
         let additional_properties = AdditionalProperties {
             authorization_context,
             data_format: ingestion_properties.data_format.clone(),
+            ingestion_mapping_type: ingestion_properties
+                .ingestion_mapping_reference
+                .as_ref()
+                .map(|_| ingestion_properties.data_format.ingestion_mapping_kind()),
+            ingestion_mapping_reference: ingestion_properties.ingestion_mapping_reference.clone(),
+            ignore_first_record: ingestion_properties
+                .ignore_first_record
+                .map(|ignore| ignore.to_string()),
+            trace_parent: None,
+            raw: ingestion_properties.raw_additional_properties.clone(),
         };
 
-        Self {
+        Ok(Self {
             id: blob_descriptor.source_id,
             blob_path: blob_descriptor.uri(),
             raw_data_size: blob_descriptor.size,
@@ -74,9 +105,19 @@ impl QueuedIngestionMessage {
             table_name: ingestion_properties.table_name.clone(),
             retain_blob_on_success: ingestion_properties.retain_blob_on_success,
             flush_immediately: ingestion_properties.flush_immediately,
+            report_level: ingestion_properties.report_level,
+            report_method: ingestion_properties.report_method.or(ingestion_properties
+                .report_level
+                .map(|_| ReportMethod::Queue)),
             source_message_creation_time: OffsetDateTime::now_utc(),
             additional_properties,
-        }
+        })
+    }
+
+    /// Sets the trace context the ingestion is queued in, if any.
+    pub(crate) fn with_trace_context(mut self, trace_context: Option<&TraceContext>) -> Self {
+        self.additional_properties.trace_parent = trace_context.map(TraceContext::traceparent);
+        self
     }
 }
 
@@ -89,11 +130,52 @@ struct AdditionalProperties {
     authorization_context: KustoIdentityToken,
     #[serde(rename = "format")]
     data_format: DataFormat,
+    /// Name of the mapping defined on the table to ingest with
+    #[serde(
+        rename = "ingestionMappingReference",
+        skip_serializing_if = "Option::is_none"
+    )]
+    ingestion_mapping_reference: Option<String>,
+    /// Kind of the mapping, which Kusto expects along with the reference
+    #[serde(
+        rename = "ingestionMappingType",
+        skip_serializing_if = "Option::is_none"
+    )]
+    ingestion_mapping_type: Option<IngestionMappingKind>,
+    /// Whether to skip the first record, sent as a string like the other ingestion properties
+    #[serde(rename = "ignoreFirstRecord", skip_serializing_if = "Option::is_none")]
+    ignore_first_record: Option<String>,
+    /// The W3C `traceparent` of the trace the ingestion was queued in, to join the logs of the service with it
+    #[serde(rename = "traceParent", skip_serializing_if = "Option::is_none")]
+    trace_parent: Option<String>,
+    /// Properties that are not modelled, added after the typed ones and replacing those of the same name
+    #[serde(skip)]
+    raw: BTreeMap<String, serde_json::Value>,
+}
+
+/// Serializes the typed additional properties, then the raw ones.
+/// Raw properties that collide with typed ones are rejected when the message is built unless overrides are allowed,
+/// in which case they replace them regardless of case.
+fn serialize_additional_properties<S: Serializer>(
+    properties: &AdditionalProperties,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let mut map = match serde_json::to_value(properties).map_err(S::Error::custom)? {
+        serde_json::Value::Object(map) => map,
+        _ => return Err(S::Error::custom("additional properties are not an object")),
+    };
+    for (key, value) in &properties.raw {
+        map.retain(|typed, _| !typed.eq_ignore_ascii_case(key));
+        map.insert(key.clone(), value.clone());
+    }
+    map.serialize(serializer)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::descriptors::BlobAuth;
+    use crate::ingestion_properties::IngestionPropertiesProblem;
 
     #[test]
     fn time_custom_iso8601_serialization() {
@@ -116,4 +198,281 @@ mod tests {
             "{\"customised_time_format\":\"2009-02-13T23:31:30.123456789Z\"}"
         );
     }
+
+    #[test]
+    fn message_format_serialization() {
+        let blob_descriptor = BlobDescriptor::new(
+            "https://account.blob.core.windows.net/container/data.parquet",
+            Some(42),
+            None,
+        );
+        let ingestion_properties = IngestionProperties {
+            database_name: "db".to_string(),
+            table_name: "table".to_string(),
+            data_format: DataFormat::Parquet,
+            ..IngestionProperties::default()
+        };
+
+        let message =
+            QueuedIngestionMessage::new(&blob_descriptor, &ingestion_properties, "token".into())
+                .unwrap();
+        let message = serde_json::to_value(&message).unwrap();
+
+        assert_eq!(message["AdditionalProperties"]["format"], "parquet");
+        assert_eq!(message["RawDataSize"], 42);
+        assert_eq!(message["Id"], blob_descriptor.source_id.to_string());
+        assert!(message.get("ReportLevel").is_none());
+        assert!(message["AdditionalProperties"]
+            .get("ingestionMappingReference")
+            .is_none());
+        assert!(message.get("ReportMethod").is_none());
+        assert!(message["AdditionalProperties"].get("traceParent").is_none());
+    }
+
+    #[test]
+    fn message_trace_context_serialization() {
+        let blob_descriptor = BlobDescriptor::new(
+            "https://account.blob.core.windows.net/container/data.csv",
+            None,
+            None,
+        );
+        let trace_context =
+            TraceContext::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01");
+
+        let message = QueuedIngestionMessage::new(
+            &blob_descriptor,
+            &IngestionProperties::default(),
+            "token".into(),
+        )
+        .unwrap()
+        .with_trace_context(trace_context.as_ref());
+        let message = serde_json::to_value(&message).unwrap();
+
+        assert_eq!(
+            message["AdditionalProperties"]["traceParent"],
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+        );
+        assert!(message.get("TraceParent").is_none());
+    }
+
+    #[test]
+    fn message_mapping_serialization() {
+        let blob_descriptor = BlobDescriptor::new(
+            "https://account.blob.core.windows.net/container/data.avro",
+            None,
+            None,
+        );
+        let ingestion_properties = IngestionProperties {
+            data_format: DataFormat::ApacheAvro,
+            ingestion_mapping_reference: Some("EventsMapping".to_string()),
+            ..IngestionProperties::default()
+        };
+
+        let message =
+            QueuedIngestionMessage::new(&blob_descriptor, &ingestion_properties, "token".into())
+                .unwrap();
+        let message = serde_json::to_value(&message).unwrap();
+
+        assert_eq!(
+            message["AdditionalProperties"]["ingestionMappingReference"],
+            "EventsMapping"
+        );
+        assert_eq!(
+            message["AdditionalProperties"]["ingestionMappingType"],
+            "ApacheAvro"
+        );
+    }
+
+    #[test]
+    fn message_report_level_serialization() {
+        let blob_descriptor = BlobDescriptor::new(
+            "https://account.blob.core.windows.net/container/data.csv",
+            None,
+            None,
+        );
+        let ingestion_properties = IngestionProperties {
+            report_level: Some(ReportLevel::FailuresAndSuccesses),
+            ..IngestionProperties::default()
+        };
+
+        let message =
+            QueuedIngestionMessage::new(&blob_descriptor, &ingestion_properties, "token".into())
+                .unwrap();
+        let message = serde_json::to_value(&message).unwrap();
+
+        assert_eq!(message["ReportLevel"], 2);
+        assert_eq!(message["ReportMethod"], 0);
+    }
+
+    #[test]
+    fn message_serialization_matches_documented_shape() {
+        let blob_descriptor = BlobDescriptor::new(
+            "https://account.blob.core.windows.net/container/data.json",
+            Some(1024),
+            None,
+        )
+        .with_blob_auth(BlobAuth::SASToken("sig=secret".to_string()));
+        let ingestion_properties = IngestionProperties {
+            database_name: "db".to_string(),
+            table_name: "events".to_string(),
+            retain_blob_on_success: Some(true),
+            data_format: DataFormat::MultiJSON,
+            flush_immediately: Some(true),
+            report_level: Some(ReportLevel::FailuresAndSuccesses),
+            report_method: Some(ReportMethod::QueueAndTable),
+            ingestion_mapping_reference: Some("EventsMapping".to_string()),
+            ignore_first_record: Some(true),
+            raw_additional_properties: BTreeMap::new(),
+            allow_additional_property_overrides: None,
+        };
+
+        let message =
+            QueuedIngestionMessage::new(&blob_descriptor, &ingestion_properties, "token".into())
+                .unwrap();
+        let mut message = serde_json::to_value(&message).unwrap();
+        // The creation time is the only field that isn't deterministic
+        assert!(message
+            .as_object_mut()
+            .unwrap()
+            .remove("SourceMessageCreationTime")
+            .is_some());
+
+        assert_eq!(
+            message,
+            serde_json::json!({
+                "Id": blob_descriptor.source_id.to_string(),
+                "BlobPath": "https://account.blob.core.windows.net/container/data.json?sig=secret",
+                "DatabaseName": "db",
+                "TableName": "events",
+                "RawDataSize": 1024,
+                "RetainBlobOnSuccess": true,
+                "FlushImmediately": true,
+                "ReportLevel": 2,
+                "ReportMethod": 2,
+                "AdditionalProperties": {
+                    "authorizationContext": "token",
+                    "format": "multijson",
+                    "ingestionMappingReference": "EventsMapping",
+                    "ingestionMappingType": "Json",
+                    "ignoreFirstRecord": "true"
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn omitted_properties_are_not_serialized() {
+        let blob_descriptor = BlobDescriptor::new(
+            "https://account.blob.core.windows.net/container/data.csv",
+            None,
+            None,
+        );
+
+        let message = QueuedIngestionMessage::new(
+            &blob_descriptor,
+            &IngestionProperties::default(),
+            "token".into(),
+        )
+        .unwrap();
+        let message = serde_json::to_value(&message).unwrap();
+
+        for omitted in [
+            "RawDataSize",
+            "RetainBlobOnSuccess",
+            "FlushImmediately",
+            "ReportLevel",
+            "ReportMethod",
+        ] {
+            assert!(message.get(omitted).is_none(), "{omitted}");
+        }
+        assert!(message["AdditionalProperties"]
+            .get("ignoreFirstRecord")
+            .is_none());
+    }
+
+    fn message_with(ingestion_properties: &IngestionProperties) -> serde_json::Value {
+        let blob_descriptor = BlobDescriptor::new(
+            "https://account.blob.core.windows.net/container/data.csv",
+            None,
+            None,
+        );
+        let message =
+            QueuedIngestionMessage::new(&blob_descriptor, ingestion_properties, "token".into())
+                .unwrap();
+        serde_json::to_value(&message).unwrap()
+    }
+
+    #[test]
+    fn raw_additional_properties_are_added_after_typed_ones() {
+        let ingestion_properties = IngestionProperties::default()
+            .with_raw_additional_property("creationTime".to_string(), "2024-01-01".into())
+            .with_raw_additional_property(
+                "tags".to_string(),
+                serde_json::json!(["drop-by:2024-01-01"]),
+            );
+
+        assert_eq!(
+            message_with(&ingestion_properties)["AdditionalProperties"],
+            serde_json::json!({
+                "authorizationContext": "token",
+                "format": "csv",
+                "creationTime": "2024-01-01",
+                "tags": ["drop-by:2024-01-01"]
+            })
+        );
+    }
+
+    #[test]
+    fn raw_additional_properties_cannot_replace_reserved_ones_without_validation() {
+        let blob_descriptor = BlobDescriptor::new(
+            "https://account.blob.core.windows.net/container/data.csv",
+            None,
+            None,
+        );
+        for reserved in ["AuthorizationContext", "format", "traceParent"] {
+            let ingestion_properties = IngestionProperties::default()
+                .with_raw_additional_property(reserved.to_string(), "raw".into());
+
+            let error = QueuedIngestionMessage::new(
+                &blob_descriptor,
+                &ingestion_properties,
+                "token".into(),
+            )
+            .expect_err("Expected the reserved property to be rejected");
+            assert_eq!(
+                error.problems,
+                vec![IngestionPropertiesProblem::ReservedAdditionalProperty(
+                    reserved.to_string()
+                )]
+            );
+        }
+    }
+
+    #[test]
+    fn raw_additional_properties_replace_typed_ones_when_allowed() {
+        let ingestion_properties = IngestionProperties {
+            database_name: "db".to_string(),
+            table_name: "events".to_string(),
+            ..IngestionProperties::default()
+        }
+        .with_raw_additional_property("Format".to_string(), "tsv".into());
+        let error = ingestion_properties
+            .validate()
+            .expect_err("Expected the reserved property to be rejected");
+        assert_eq!(
+            error.to_string(),
+            "Invalid ingestion properties: additional property 'Format' is set from a typed property, and overrides are not allowed"
+        );
+
+        let ingestion_properties = IngestionProperties {
+            allow_additional_property_overrides: Some(true),
+            ..ingestion_properties
+        };
+        assert!(ingestion_properties.validate().is_ok());
+        let message = message_with(&ingestion_properties);
+        assert_eq!(
+            message["AdditionalProperties"],
+            serde_json::json!({"authorizationContext": "token", "Format": "tsv"})
+        );
+    }
 }
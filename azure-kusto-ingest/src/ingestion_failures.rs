@@ -0,0 +1,273 @@
+use azure_kusto_data::models::TableV1;
+use azure_kusto_data::prelude::KustoClient;
+use azure_kusto_data::types::KustoDateTime;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+use crate::error::Result;
+
+/// Filter applied to `.show ingestion failures`, every field that is set narrows down the results
+#[derive(Clone, Debug, Default)]
+pub struct FailuresFilter {
+    /// Only return failures that happened on or after this time
+    pub since: Option<KustoDateTime>,
+    /// Only return failures for this database
+    pub database: Option<String>,
+    /// Only return failures for this table
+    pub table: Option<String>,
+    /// Only return failures of the ingestion with this source id, as set on the [BlobDescriptor](crate::descriptors::BlobDescriptor).
+    ///
+    /// `.show ingestion failures` has no column for the source id, so failures match when their operation id is the source id,
+    /// or when their source path contains it, as the paths of the blobs that this crate uploads do.
+    /// The failures of blobs that are named otherwise, and whose operation id differs, are not returned
+    pub source_id: Option<Uuid>,
+}
+
+impl FailuresFilter {
+    /// Builds the management command that applies this filter
+    pub(crate) fn to_command(&self) -> String {
+        let mut command = String::from(".show ingestion failures");
+
+        if let Some(since) = &self.since {
+            command.push_str(&format!(" | where FailedOn >= datetime({since})"));
+        }
+        if let Some(database) = &self.database {
+            command.push_str(&format!(
+                " | where Database == {}",
+                kql_string_literal(database)
+            ));
+        }
+        if let Some(table) = &self.table {
+            command.push_str(&format!(" | where Table == {}", kql_string_literal(table)));
+        }
+        if let Some(source_id) = &self.source_id {
+            command.push_str(&format!(
+                " | where OperationId == guid({source_id}) or IngestionSourcePath contains \"{source_id}\""
+            ));
+        }
+
+        command
+    }
+}
+
+/// Escapes a value into a double quoted KQL string literal
+fn kql_string_literal(value: &str) -> String {
+    let mut literal = String::with_capacity(value.len() + 2);
+    literal.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            c => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+/// Whether an ingestion failure is expected to go away when retried
+#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum FailureKind {
+    /// The failure will not be resolved by retrying, e.g. the data or mapping is invalid
+    Permanent,
+    /// The failure is transient, retrying the ingestion may succeed
+    Transient,
+    /// Any other kind reported by the service
+    #[serde(other)]
+    Unknown,
+}
+
+/// A single row of the result of `.show ingestion failures`
+///
+/// See https://learn.microsoft.com/en-us/azure/data-explorer/kusto/management/ingestion-failures
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct IngestionFailureInfo {
+    /// Id of the ingestion operation that failed
+    pub operation_id: Uuid,
+    /// Database the data was ingested into
+    pub database: String,
+    /// Table the data was ingested into
+    pub table: String,
+    /// Time of the failure
+    pub failed_on: KustoDateTime,
+    /// Path of the source that failed to ingest
+    #[serde(default)]
+    pub ingestion_source_path: Option<String>,
+    /// Description of the failure
+    #[serde(default)]
+    pub details: Option<String>,
+    /// Whether the failure is permanent or transient
+    pub failure_kind: FailureKind,
+    /// Error code of the failure
+    #[serde(default)]
+    pub error_code: Option<String>,
+    /// Whether the failure happened in an update policy of the target table
+    #[serde(default)]
+    pub originates_from_update_policy: Option<bool>,
+    /// Whether the service recommends retrying the ingestion, when reported
+    #[serde(default)]
+    pub should_retry: Option<bool>,
+}
+
+impl IngestionFailureInfo {
+    /// Whether retrying the ingestion may succeed.
+    /// Uses the service's recommendation when present, and falls back to the failure kind otherwise
+    pub fn should_retry(&self) -> bool {
+        self.should_retry
+            .unwrap_or(self.failure_kind == FailureKind::Transient)
+    }
+}
+
+/// Parses the table returned by `.show ingestion failures` into [IngestionFailureInfo]s
+pub(crate) fn parse_ingestion_failures(table: &TableV1) -> Result<Vec<IngestionFailureInfo>> {
+    table
+        .rows
+        .iter()
+        .map(|row| {
+            let record: Map<String, Value> = table
+                .columns
+                .iter()
+                .zip(row.iter())
+                .map(|(column, value)| (column.column_name.clone(), value.clone()))
+                .collect();
+            Ok(serde_json::from_value(Value::Object(record))?)
+        })
+        .collect()
+}
+
+/// Retrieves the failed ingestions matching the given filter by executing `.show ingestion failures`
+///
+/// **NOTE**: ingestion failures are recorded by the engine, so the [KustoClient] must be created with a connection string that points to the engine endpoint, not the ingestion endpoint
+pub async fn show_ingestion_failures(
+    client: &KustoClient,
+    filter: FailuresFilter,
+) -> Result<Vec<IngestionFailureInfo>> {
+    let command = match &filter.database {
+        Some(database) => client.execute_command(database, filter.to_command(), None),
+        None => client.execute_cluster_command(filter.to_command(), None),
+    };
+    let results = command.await?;
+
+    match results.tables.first() {
+        Some(table) => parse_ingestion_failures(table),
+        None => Ok(Vec::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use azure_kusto_data::prelude::KustoResponseDataSetV1;
+
+    use super::*;
+
+    const FAILURES_RESPONSE: &str = r#"{
+        "Tables": [{
+            "TableName": "Table_0",
+            "Columns": [
+                {"ColumnName": "OperationId", "DataType": "Guid"},
+                {"ColumnName": "Database", "DataType": "String"},
+                {"ColumnName": "Table", "DataType": "String"},
+                {"ColumnName": "FailedOn", "DataType": "DateTime"},
+                {"ColumnName": "IngestionSourcePath", "DataType": "String"},
+                {"ColumnName": "Details", "DataType": "String"},
+                {"ColumnName": "FailureKind", "DataType": "String"},
+                {"ColumnName": "RootActivityId", "DataType": "Guid"},
+                {"ColumnName": "OperationKind", "DataType": "String"},
+                {"ColumnName": "OriginatesFromUpdatePolicy", "DataType": "Boolean"},
+                {"ColumnName": "ErrorCode", "DataType": "String"},
+                {"ColumnName": "ShouldRetry", "DataType": "Boolean"}
+            ],
+            "Rows": [
+                ["3827def6-0773-4f2a-859e-c02cf395deaf", "db", "events", "2023-05-01T10:15:30.1234567Z", "https://account.blob.core.windows.net/container/blob.csv", "Stream with id 'blob.csv' has a malformed Csv format", "Permanent", "2ed9f6c4-0b55-4a4e-8b52-5b3fb1bc24ae", "DataIngestPull", false, "BadRequest_InvalidCsvFormat", false],
+                ["841fafa4-076a-4cba-9300-4836da0d9c75", "db", "events", "2023-05-01T11:00:00Z", "https://account.blob.core.windows.net/container/other.csv", "Service is overloaded", "Transient", "df4fcb28-9cc7-4e10-9d35-5d1e4e3d2c3e", "DataIngestPull", true, "General_ThrottledIngestion", null]
+            ]
+        }]
+    }"#;
+
+    #[test]
+    fn parse_failures_fixture() {
+        let response: KustoResponseDataSetV1 =
+            serde_json::from_str(FAILURES_RESPONSE).expect("Failed to parse response");
+
+        let failures =
+            parse_ingestion_failures(&response.tables[0]).expect("Failed to parse failures");
+
+        assert_eq!(failures.len(), 2);
+
+        assert_eq!(
+            failures[0].operation_id,
+            Uuid::parse_str("3827def6-0773-4f2a-859e-c02cf395deaf").unwrap()
+        );
+        assert_eq!(failures[0].database, "db");
+        assert_eq!(failures[0].table, "events");
+        assert_eq!(
+            failures[0].failed_on,
+            KustoDateTime::from_str("2023-05-01T10:15:30.1234567Z").unwrap()
+        );
+        assert_eq!(failures[0].failure_kind, FailureKind::Permanent);
+        assert_eq!(
+            failures[0].error_code.as_deref(),
+            Some("BadRequest_InvalidCsvFormat")
+        );
+        assert_eq!(failures[0].originates_from_update_policy, Some(false));
+        assert!(!failures[0].should_retry());
+
+        assert_eq!(failures[1].failure_kind, FailureKind::Transient);
+        assert_eq!(failures[1].should_retry, None);
+        assert!(failures[1].should_retry());
+    }
+
+    #[test]
+    fn should_retry_prefers_service_flag() {
+        let response: KustoResponseDataSetV1 =
+            serde_json::from_str(FAILURES_RESPONSE).expect("Failed to parse response");
+        let mut failure = parse_ingestion_failures(&response.tables[0]).unwrap()[0].clone();
+
+        failure.should_retry = Some(true);
+        assert!(failure.should_retry());
+    }
+
+    #[test]
+    fn empty_filter_command() {
+        assert_eq!(
+            FailuresFilter::default().to_command(),
+            ".show ingestion failures"
+        );
+    }
+
+    #[test]
+    fn full_filter_command() {
+        let filter = FailuresFilter {
+            since: Some(KustoDateTime::from_str("2023-05-01T00:00:00Z").unwrap()),
+            database: Some("db".to_string()),
+            table: Some("events".to_string()),
+            source_id: Some(Uuid::parse_str("3827def6-0773-4f2a-859e-c02cf395deaf").unwrap()),
+        };
+
+        assert_eq!(
+            filter.to_command(),
+            ".show ingestion failures | where FailedOn >= datetime(2023-05-01T00:00:00.0000000Z) | where Database == \"db\" | where Table == \"events\" \
+             | where OperationId == guid(3827def6-0773-4f2a-859e-c02cf395deaf) or IngestionSourcePath contains \"3827def6-0773-4f2a-859e-c02cf395deaf\""
+        );
+    }
+
+    #[test]
+    fn filter_command_escapes_values() {
+        let filter = FailuresFilter {
+            table: Some("my\"table\\\n| take 1".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            filter.to_command(),
+            ".show ingestion failures | where Table == \"my\\\"table\\\\\\n| take 1\""
+        );
+    }
+}
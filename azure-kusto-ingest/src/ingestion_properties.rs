@@ -1,7 +1,31 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
 use crate::data_format::DataFormat;
+use crate::ingestion_status::{ReportLevel, ReportMethod};
+
+/// The longest name Kusto allows for a database or table
+const MAX_ENTITY_NAME_LENGTH: usize = 1024;
 
-/// Properties of ingestion that can be used when ingesting data into Kusto allowing for customisation of the ingestion process
-#[derive(Clone, Debug, Default)]
+/// The additional properties of the ingestion message that are set from the typed fields of [IngestionProperties], or by the client
+const RESERVED_ADDITIONAL_PROPERTIES: [&str; 6] = [
+    "authorizationContext",
+    "format",
+    "ingestionMappingReference",
+    "ingestionMappingType",
+    "ignoreFirstRecord",
+    "traceParent",
+];
+
+/// Properties of ingestion that can be used when ingesting data into Kusto allowing for customisation of the ingestion process.
+/// Prefer [IngestionPropertiesBuilder], which [validates](IngestionProperties::validate) the properties when built
+#[derive(Clone, Debug, Default, derive_builder::Builder)]
+#[non_exhaustive]
+#[builder(
+    setter(into, strip_option, prefix = "with"),
+    default,
+    build_fn(validate = "Self::validate", error = "IngestionPropertiesError")
+)]
 pub struct IngestionProperties {
     /// Name of the database to ingest into
     pub database_name: String,
@@ -9,10 +33,530 @@ pub struct IngestionProperties {
     pub table_name: String,
     /// Whether the blob is retained after ingestion.
     /// Note that the default when not provided is `false`, meaning that Kusto will attempt to delete the blob upon ingestion.
-    /// This will only be successful if provided sufficient permissions on the blob
+    ///
+    /// Deleting the blob requires more permissions than reading it: a SAS token with the delete permission, or a managed identity
+    /// allowed to delete blobs, such as with the Storage Blob Data Contributor role. When Kusto is only allowed to read the blob,
+    /// the data is still ingested but the blob is left in place - set this to `true` to make that explicit
     pub retain_blob_on_success: Option<bool>,
     /// Format of the data being ingested
     pub data_format: DataFormat,
     /// If set to `true`, any aggregation will be skipped. Default is `false`
     pub flush_immediately: Option<bool>,
+    /// Which outcomes of the ingestion Kusto reports to the status queues, for [check_status](crate::queued_ingest::QueuedIngestClient::check_status).
+    /// When not provided, only failures are reported
+    pub report_level: Option<ReportLevel>,
+    /// Where Kusto reports the outcomes of the ingestion.
+    /// When not provided, outcomes are reported to the status queues
+    pub report_method: Option<ReportMethod>,
+    /// Name of an ingestion mapping defined on the table, which maps the data to the columns of the table.
+    /// Its kind must match the [ingestion_mapping_kind](DataFormat::ingestion_mapping_kind) of the data format
+    pub ingestion_mapping_reference: Option<String>,
+    /// If set to `true`, the first record of the data is skipped, such as the header row of a CSV file.
+    /// Default is `false`
+    pub ignore_first_record: Option<bool>,
+    /// Additional properties of queued ingestion that this crate doesn't model, sent as they are, after the typed ones.
+    /// See [with_raw_additional_property](IngestionProperties::with_raw_additional_property)
+    pub raw_additional_properties: BTreeMap<String, serde_json::Value>,
+    /// If set to `true`, [raw_additional_properties](Self::raw_additional_properties) may replace the additional properties
+    /// that are set from the typed fields, such as `format`. Otherwise, such properties make the ingestion properties invalid
+    pub allow_additional_property_overrides: Option<bool>,
+}
+
+impl IngestionPropertiesBuilder {
+    /// Applies [IngestionProperties::validate] to the properties being built
+    fn validate(&self) -> Result<(), IngestionPropertiesError> {
+        let properties = IngestionProperties {
+            database_name: self.database_name.clone().unwrap_or_default(),
+            table_name: self.table_name.clone().unwrap_or_default(),
+            retain_blob_on_success: self.retain_blob_on_success.flatten(),
+            data_format: self.data_format.clone().unwrap_or_default(),
+            flush_immediately: self.flush_immediately.flatten(),
+            report_level: self.report_level.flatten(),
+            report_method: self.report_method.flatten(),
+            ingestion_mapping_reference: self.ingestion_mapping_reference.clone().flatten(),
+            ignore_first_record: self.ignore_first_record.flatten(),
+            raw_additional_properties: self.raw_additional_properties.clone().unwrap_or_default(),
+            allow_additional_property_overrides: self.allow_additional_property_overrides.flatten(),
+        };
+        properties.validate().map(|_warnings| ())
+    }
+}
+
+/// A problem with [IngestionProperties] that would make the ingestion fail
+#[derive(thiserror::Error, Clone, Debug, PartialEq, Eq)]
+pub enum IngestionPropertiesProblem {
+    /// The database name is empty
+    #[error("database name is empty")]
+    EmptyDatabaseName,
+    /// The table name is empty
+    #[error("table name is empty")]
+    EmptyTableName,
+    /// The name of the database or table breaks the naming rules of Kusto
+    #[error("{entity} name '{name}' {reason}")]
+    IllegalEntityName {
+        /// Whether the name is of the `database` or of the `table`
+        entity: &'static str,
+        /// The illegal name
+        name: String,
+        /// Which rule the name breaks
+        reason: String,
+    },
+    /// The data format can only be ingested with a mapping, but no [ingestion_mapping_reference](IngestionProperties::ingestion_mapping_reference) was provided
+    #[error("{0:?} data requires an ingestion mapping reference")]
+    MissingMapping(DataFormat),
+    /// The [ingestion_mapping_reference](IngestionProperties::ingestion_mapping_reference) is empty
+    #[error("ingestion mapping reference is empty")]
+    EmptyMappingReference,
+    /// A [raw additional property](IngestionProperties::raw_additional_properties) has an empty name
+    #[error("additional property name is empty")]
+    EmptyAdditionalPropertyName,
+    /// A [raw additional property](IngestionProperties::raw_additional_properties) would replace one that is set from a typed field,
+    /// without [allow_additional_property_overrides](IngestionProperties::allow_additional_property_overrides)
+    #[error(
+        "additional property '{0}' is set from a typed property, and overrides are not allowed"
+    )]
+    ReservedAdditionalProperty(String),
+}
+
+/// Error returned when [IngestionProperties] are invalid, listing all of their problems.
+/// Also returned by [IngestionPropertiesBuilder::build]
+#[derive(thiserror::Error, Clone, Debug, PartialEq, Eq)]
+#[error("Invalid ingestion properties: {}", join_problems(.problems))]
+pub struct IngestionPropertiesError {
+    /// Every problem found in the properties
+    pub problems: Vec<IngestionPropertiesProblem>,
+}
+
+fn join_problems(problems: &[IngestionPropertiesProblem]) -> String {
+    problems
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Every field of [IngestionPropertiesBuilder] has a default, so none is ever uninitialized,
+/// but the builder requires its error to be convertible from this one
+impl From<derive_builder::UninitializedFieldError> for IngestionPropertiesError {
+    fn from(error: derive_builder::UninitializedFieldError) -> Self {
+        unreachable!("{error}")
+    }
+}
+
+/// A combination of [IngestionProperties] that is valid, but discouraged
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IngestionPropertiesWarning {
+    /// Flushing immediately skips the aggregation of small ingestions into larger extents, which hurts the performance of the table
+    FlushImmediately,
+    /// Without a mapping, JSON properties are only matched to the columns of the same name, and nested properties are not ingested
+    JsonWithoutMapping(DataFormat),
+    /// Kusto reports neither failures nor successes to the status queues, so the status of the ingestion can't be checked
+    StatusNotReported,
+}
+
+impl fmt::Display for IngestionPropertiesWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IngestionPropertiesWarning::FlushImmediately => write!(
+                f,
+                "flush_immediately skips aggregation, and should only be used for small volumes of data"
+            ),
+            IngestionPropertiesWarning::JsonWithoutMapping(data_format) => write!(
+                f,
+                "{data_format:?} data without an ingestion mapping is only matched to columns by name"
+            ),
+            IngestionPropertiesWarning::StatusNotReported => write!(
+                f,
+                "the status of the ingestion is not reported, so it can't be checked"
+            ),
+        }
+    }
+}
+
+/// Checks a database or table name against the naming rules of Kusto:
+/// letters, digits, underscores, spaces, dots and dashes, up to 1024 characters, and not only whitespace
+fn check_entity_name(
+    entity: &'static str,
+    name: &str,
+    problems: &mut Vec<IngestionPropertiesProblem>,
+) {
+    let mut illegal = |reason: String| {
+        problems.push(IngestionPropertiesProblem::IllegalEntityName {
+            entity,
+            name: name.to_string(),
+            reason,
+        })
+    };
+
+    if name.trim().is_empty() {
+        illegal("is only whitespace".to_string());
+    } else if name.chars().count() > MAX_ENTITY_NAME_LENGTH {
+        illegal(format!(
+            "is longer than {MAX_ENTITY_NAME_LENGTH} characters"
+        ));
+    } else if let Some(c) = name
+        .chars()
+        .find(|c| !(c.is_alphanumeric() || matches!(c, '_' | ' ' | '.' | '-')))
+    {
+        illegal(format!("contains the illegal character '{c}'"));
+    }
+}
+
+impl IngestionProperties {
+    /// Adds an additional property of queued ingestion that this crate doesn't model yet, such as one of a new service feature,
+    /// to be sent as it is. Raw properties are added to the ingestion message after the typed ones.
+    ///
+    /// The name must not be empty, and must not be one of the properties set from typed fields, such as `format`,
+    /// unless [allow_additional_property_overrides](Self::allow_additional_property_overrides) is set, in which case the raw value replaces the typed one.
+    /// Both are checked by [validate](Self::validate), and reserved names are also rejected when the ingestion message is built
+    /// # Example
+    /// ```rust
+    /// use azure_kusto_ingest::ingestion_properties::IngestionPropertiesBuilder;
+    ///
+    /// let properties = IngestionPropertiesBuilder::default()
+    ///     .with_database_name("db")
+    ///     .with_table_name("events")
+    ///     .build()
+    ///     .unwrap()
+    ///     .with_raw_additional_property("validationPolicy".to_string(), serde_json::json!("{\"ValidationOptions\":1}"));
+    ///
+    /// assert!(properties.validate().is_ok());
+    /// ```
+    #[must_use]
+    pub fn with_raw_additional_property(mut self, key: String, value: serde_json::Value) -> Self {
+        self.raw_additional_properties.insert(key, value);
+        self
+    }
+
+    /// The raw additional properties that would replace a reserved one, unless
+    /// [allow_additional_property_overrides](Self::allow_additional_property_overrides) is set
+    pub(crate) fn additional_property_override_problems(&self) -> Vec<IngestionPropertiesProblem> {
+        if self.allow_additional_property_overrides.unwrap_or(false) {
+            return vec![];
+        }
+        self.raw_additional_properties
+            .keys()
+            .filter(|name| {
+                RESERVED_ADDITIONAL_PROPERTIES
+                    .iter()
+                    .any(|reserved| reserved.eq_ignore_ascii_case(name))
+            })
+            .map(|name| IngestionPropertiesProblem::ReservedAdditionalProperty(name.clone()))
+            .collect()
+    }
+
+    /// Checks the properties for problems that would make the ingestion fail in Kusto, where the failure can't be seen by the client.
+    ///
+    /// Returns all the problems found, or the warnings about discouraged combinations of properties when there are no problems.
+    /// [QueuedIngestClient](crate::queued_ingest::QueuedIngestClient) validates the properties before queuing an ingestion
+    pub fn validate(&self) -> Result<Vec<IngestionPropertiesWarning>, IngestionPropertiesError> {
+        let mut problems = Vec::new();
+        let mut warnings = Vec::new();
+
+        if self.database_name.is_empty() {
+            problems.push(IngestionPropertiesProblem::EmptyDatabaseName);
+        } else {
+            check_entity_name("database", &self.database_name, &mut problems);
+        }
+        if self.table_name.is_empty() {
+            problems.push(IngestionPropertiesProblem::EmptyTableName);
+        } else {
+            check_entity_name("table", &self.table_name, &mut problems);
+        }
+
+        match self.ingestion_mapping_reference.as_deref() {
+            Some(mapping) if mapping.trim().is_empty() => {
+                problems.push(IngestionPropertiesProblem::EmptyMappingReference)
+            }
+            Some(_) => {}
+            // Avro and sstream are only ingested with a mapping, other binary formats map their columns by name
+            None => match self.data_format {
+                DataFormat::Avro | DataFormat::SStream => problems.push(
+                    IngestionPropertiesProblem::MissingMapping(self.data_format.clone()),
+                ),
+                DataFormat::JSON | DataFormat::MultiJSON | DataFormat::SingleJSON => warnings.push(
+                    IngestionPropertiesWarning::JsonWithoutMapping(self.data_format.clone()),
+                ),
+                _ => {}
+            },
+        }
+
+        for name in self.raw_additional_properties.keys() {
+            if name.trim().is_empty() {
+                problems.push(IngestionPropertiesProblem::EmptyAdditionalPropertyName);
+            }
+        }
+        problems.extend(self.additional_property_override_problems());
+
+        if self.flush_immediately == Some(true) {
+            warnings.push(IngestionPropertiesWarning::FlushImmediately);
+        }
+        if self.report_level == Some(ReportLevel::DoNotReport) {
+            warnings.push(IngestionPropertiesWarning::StatusNotReported);
+        }
+
+        if problems.is_empty() {
+            Ok(warnings)
+        } else {
+            Err(IngestionPropertiesError { problems })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn valid() -> IngestionProperties {
+        IngestionProperties {
+            database_name: "db".to_string(),
+            table_name: "My-Table_2.0 raw".to_string(),
+            ..IngestionProperties::default()
+        }
+    }
+
+    fn problems(properties: IngestionProperties) -> Vec<IngestionPropertiesProblem> {
+        properties
+            .validate()
+            .expect_err("Expected the properties to be invalid")
+            .problems
+    }
+
+    #[test]
+    fn valid_properties() {
+        assert_eq!(valid().validate(), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn all_problems_are_reported() {
+        let properties = IngestionProperties {
+            database_name: String::new(),
+            table_name: String::new(),
+            data_format: DataFormat::Avro,
+            ..IngestionProperties::default()
+        };
+
+        let error = properties.validate().unwrap_err();
+
+        assert_eq!(
+            error.problems,
+            vec![
+                IngestionPropertiesProblem::EmptyDatabaseName,
+                IngestionPropertiesProblem::EmptyTableName,
+                IngestionPropertiesProblem::MissingMapping(DataFormat::Avro),
+            ]
+        );
+        assert_eq!(
+            error.to_string(),
+            "Invalid ingestion properties: database name is empty; table name is empty; Avro data requires an ingestion mapping reference"
+        );
+    }
+
+    #[test]
+    fn illegal_characters_in_names() {
+        let properties = IngestionProperties {
+            database_name: "db;drop".to_string(),
+            table_name: "table|take 1".to_string(),
+            ..valid()
+        };
+
+        assert_eq!(
+            problems(properties),
+            vec![
+                IngestionPropertiesProblem::IllegalEntityName {
+                    entity: "database",
+                    name: "db;drop".to_string(),
+                    reason: "contains the illegal character ';'".to_string(),
+                },
+                IngestionPropertiesProblem::IllegalEntityName {
+                    entity: "table",
+                    name: "table|take 1".to_string(),
+                    reason: "contains the illegal character '|'".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn whitespace_and_long_names() {
+        let properties = IngestionProperties {
+            database_name: "   ".to_string(),
+            table_name: "t".repeat(MAX_ENTITY_NAME_LENGTH + 1),
+            ..valid()
+        };
+
+        let problems = problems(properties);
+
+        assert_eq!(problems.len(), 2);
+        assert!(
+            matches!(&problems[0], IngestionPropertiesProblem::IllegalEntityName { entity: "database", reason, .. } if reason == "is only whitespace")
+        );
+        assert!(
+            matches!(&problems[1], IngestionPropertiesProblem::IllegalEntityName { entity: "table", reason, .. } if reason == "is longer than 1024 characters")
+        );
+    }
+
+    #[test]
+    fn mappings() {
+        for data_format in [DataFormat::Avro, DataFormat::SStream] {
+            let properties = IngestionProperties {
+                data_format: data_format.clone(),
+                ..valid()
+            };
+            assert_eq!(
+                problems(properties.clone()),
+                vec![IngestionPropertiesProblem::MissingMapping(data_format)]
+            );
+
+            let properties = IngestionProperties {
+                ingestion_mapping_reference: Some("mapping".to_string()),
+                ..properties
+            };
+            assert_eq!(properties.validate(), Ok(Vec::new()));
+        }
+
+        let properties = IngestionProperties {
+            ingestion_mapping_reference: Some(" ".to_string()),
+            ..valid()
+        };
+        assert_eq!(
+            problems(properties),
+            vec![IngestionPropertiesProblem::EmptyMappingReference]
+        );
+
+        for data_format in [DataFormat::Parquet, DataFormat::ApacheAvro, DataFormat::ORC] {
+            let properties = IngestionProperties {
+                data_format,
+                ..valid()
+            };
+            assert_eq!(properties.validate(), Ok(Vec::new()));
+        }
+    }
+
+    #[test]
+    fn builder() {
+        let properties = IngestionPropertiesBuilder::default()
+            .with_database_name("db")
+            .with_table_name("events")
+            .with_data_format(DataFormat::Avro)
+            .with_ingestion_mapping_reference("EventsMapping")
+            .with_retain_blob_on_success(true)
+            .build()
+            .expect("Failed to build properties");
+
+        assert_eq!(properties.database_name, "db");
+        assert_eq!(properties.table_name, "events");
+        assert_eq!(properties.data_format, DataFormat::Avro);
+        assert_eq!(
+            properties.ingestion_mapping_reference.as_deref(),
+            Some("EventsMapping")
+        );
+        assert_eq!(properties.retain_blob_on_success, Some(true));
+        assert_eq!(properties.flush_immediately, None);
+    }
+
+    #[test]
+    fn builder_rejects_empty_table_name() {
+        let error = IngestionPropertiesBuilder::default()
+            .with_database_name("db")
+            .build()
+            .unwrap_err();
+
+        assert_eq!(
+            error.problems,
+            vec![IngestionPropertiesProblem::EmptyTableName]
+        );
+        assert_eq!(
+            error.to_string(),
+            "Invalid ingestion properties: table name is empty"
+        );
+    }
+
+    #[test]
+    fn builder_rejects_format_without_mapping() {
+        let error = IngestionPropertiesBuilder::default()
+            .with_database_name("db")
+            .with_table_name("events")
+            .with_data_format(DataFormat::SStream)
+            .build()
+            .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Invalid ingestion properties: SStream data requires an ingestion mapping reference"
+        );
+    }
+
+    #[test]
+    fn raw_additional_properties() {
+        let properties = valid()
+            .with_raw_additional_property("creationTime".to_string(), "2024-01-01".into())
+            .with_raw_additional_property(" ".to_string(), true.into())
+            .with_raw_additional_property("Format".to_string(), "csv".into());
+        assert_eq!(
+            problems(properties.clone()),
+            vec![
+                IngestionPropertiesProblem::EmptyAdditionalPropertyName,
+                IngestionPropertiesProblem::ReservedAdditionalProperty("Format".to_string()),
+            ]
+        );
+
+        let mut properties = IngestionProperties {
+            allow_additional_property_overrides: Some(true),
+            ..properties
+        };
+        assert_eq!(
+            problems(properties.clone()),
+            vec![IngestionPropertiesProblem::EmptyAdditionalPropertyName]
+        );
+
+        properties.raw_additional_properties.remove(" ");
+        assert_eq!(properties.validate(), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn json_without_mapping_is_discouraged() {
+        let properties = IngestionProperties {
+            data_format: DataFormat::MultiJSON,
+            ..valid()
+        };
+
+        assert_eq!(
+            properties.validate(),
+            Ok(vec![IngestionPropertiesWarning::JsonWithoutMapping(
+                DataFormat::MultiJSON
+            )])
+        );
+    }
+
+    #[test]
+    fn flush_immediately_is_discouraged() {
+        let properties = IngestionProperties {
+            flush_immediately: Some(true),
+            ..valid()
+        };
+        assert_eq!(
+            properties.validate(),
+            Ok(vec![IngestionPropertiesWarning::FlushImmediately])
+        );
+
+        let properties = IngestionProperties {
+            flush_immediately: Some(false),
+            ..valid()
+        };
+        assert_eq!(properties.validate(), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn unreported_status_is_discouraged() {
+        let properties = IngestionProperties {
+            report_level: Some(ReportLevel::DoNotReport),
+            ..valid()
+        };
+
+        assert_eq!(
+            properties.validate(),
+            Ok(vec![IngestionPropertiesWarning::StatusNotReported])
+        );
+    }
 }
@@ -0,0 +1,128 @@
+//! Defines [IngestionResult], returned when a blob was queued for ingestion.
+
+use std::fmt::{Display, Formatter};
+
+use uuid::Uuid;
+
+use crate::descriptors::BlobDescriptor;
+use crate::ingestion_properties::IngestionProperties;
+
+/// Identifies an ingestion that was queued, to log it and correlate it with its status or failures.
+///
+/// Queueing is asynchronous - the result only confirms that the ingestion message was accepted by the queue service, not that the data was ingested.
+/// None of the fields carry secrets, so the result can be logged as is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IngestionResult {
+    /// The source id of the ingestion, as set on the [BlobDescriptor], or generated for it
+    pub source_id: Uuid,
+    /// The uri of the blob, without its SAS token or other authentication information
+    pub blob_uri: String,
+    /// Name of the database the blob is ingested into
+    pub database: String,
+    /// Name of the table the blob is ingested into
+    pub table: String,
+    /// Name of the storage queue the ingestion message was posted to
+    pub queue_name: String,
+    /// Id of the message in the storage queue
+    pub message_id: String,
+    /// Pop receipt of the message, as returned by the queue service
+    pub pop_receipt: String,
+}
+
+impl IngestionResult {
+    pub(crate) fn new(
+        blob_descriptor: &BlobDescriptor,
+        ingestion_properties: &IngestionProperties,
+        queue_name: impl Into<String>,
+        message_id: impl Into<String>,
+        pop_receipt: impl Into<String>,
+    ) -> Self {
+        Self {
+            source_id: blob_descriptor.source_id,
+            blob_uri: blob_descriptor.redacted_uri().to_string(),
+            database: ingestion_properties.database_name.clone(),
+            table: ingestion_properties.table_name.clone(),
+            queue_name: queue_name.into(),
+            message_id: message_id.into(),
+            pop_receipt: pop_receipt.into(),
+        }
+    }
+}
+
+/// The outcome of [ingest_from_records](crate::queued_ingest::QueuedIngestClient::ingest_from_records)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordsIngestionResult {
+    /// The queued ingestion of the records, or `None` when there were no records, in which case nothing was uploaded or queued
+    pub ingestion: Option<IngestionResult>,
+    /// Number of records ingested
+    pub record_count: usize,
+    /// Size of the serialized records in bytes, before compression
+    pub serialized_size: usize,
+    /// Size of the uploaded blob in bytes, after compression
+    pub compressed_size: usize,
+}
+
+impl Display for IngestionResult {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Queued ingestion {} of {} into {}.{} (queue {}, message {})",
+            self.source_id,
+            self.blob_uri,
+            self.database,
+            self.table,
+            self.queue_name,
+            self.message_id
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::descriptors::BlobAuth;
+
+    fn ingestion_properties() -> IngestionProperties {
+        IngestionProperties {
+            database_name: "db".to_string(),
+            table_name: "table".to_string(),
+            ..IngestionProperties::default()
+        }
+    }
+
+    #[test]
+    fn sas_tokens_are_not_in_the_result() {
+        let source_id = Uuid::new_v4();
+        for blob_descriptor in [
+            BlobDescriptor::new(
+                "https://account.blob.core.windows.net/container/blob.csv",
+                None,
+                Some(source_id),
+            )
+            .with_blob_auth(BlobAuth::SASToken("sig=secret".to_string())),
+            BlobDescriptor::new(
+                "https://account.blob.core.windows.net/container/blob.csv?sig=secret",
+                None,
+                Some(source_id),
+            ),
+        ] {
+            let result = IngestionResult::new(
+                &blob_descriptor,
+                &ingestion_properties(),
+                "readyforaggregation-secured",
+                "message-id",
+                "pop-receipt",
+            );
+
+            assert_eq!(result.source_id, source_id);
+            assert_eq!(
+                result.blob_uri,
+                "https://account.blob.core.windows.net/container/blob.csv"
+            );
+            assert_eq!(result.queue_name, "readyforaggregation-secured");
+            assert!(!format!("{result}").contains("secret"));
+            assert!(!format!("{result:?}").contains("secret"));
+            assert!(format!("{result}").contains("into db.table"));
+        }
+    }
+}
@@ -0,0 +1,255 @@
+//! Status of queued ingestions, as reported by Kusto in the status queues returned by `.get ingestion resources`
+use std::time::{Duration, Instant};
+
+use azure_core::base64;
+use azure_storage_queues::{QueueClient, VisibilityTimeout};
+use serde::{Deserialize, Serialize, Serializer};
+use uuid::Uuid;
+
+use crate::error::Result;
+use crate::ingestion_failures::FailureKind;
+
+/// The most messages that can be dequeued from a storage queue at once
+const MAX_DEQUEUED_MESSAGES: u8 = 32;
+
+/// How long the status messages of other ingestions are hidden from the readers of the queues, once they were read
+const STATUS_VISIBILITY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Which outcomes of an ingestion Kusto reports to the status queues
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportLevel {
+    /// Only failed ingestions are reported. This is the default of the service
+    FailuresOnly,
+    /// Nothing is reported, so the status of the ingestion can't be checked
+    DoNotReport,
+    /// Both failed and successful ingestions are reported
+    FailuresAndSuccesses,
+}
+
+/// The ingestion message expects the report level as its numeric value
+impl Serialize for ReportLevel {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_u8(match self {
+            ReportLevel::FailuresOnly => 0,
+            ReportLevel::DoNotReport => 1,
+            ReportLevel::FailuresAndSuccesses => 2,
+        })
+    }
+}
+
+/// Where Kusto reports the outcomes of an ingestion
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportMethod {
+    /// Outcomes are posted to the status queues. This is the default, and the only method read by
+    /// [check_status](crate::queued_ingest::QueuedIngestClient::check_status)
+    Queue,
+    /// Outcomes are written to the status table of the ingestion resources
+    Table,
+    /// Outcomes are both posted to the status queues and written to the status table
+    QueueAndTable,
+}
+
+/// The ingestion message expects the report method as its numeric value
+impl Serialize for ReportMethod {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_u8(match self {
+            ReportMethod::Queue => 0,
+            ReportMethod::Table => 1,
+            ReportMethod::QueueAndTable => 2,
+        })
+    }
+}
+
+/// Status of a queued ingestion
+#[derive(Clone, Debug, PartialEq)]
+pub enum IngestionStatus {
+    /// No status was reported for the ingestion yet.
+    /// Successful ingestions stay pending unless they were queued with [ReportLevel::FailuresAndSuccesses]
+    Pending,
+    /// The data was ingested
+    Succeeded,
+    /// The ingestion failed
+    Failed(IngestionFailureReason),
+}
+
+/// Why an ingestion failed, as reported in the failed ingestions queue
+#[derive(Clone, Debug, PartialEq)]
+pub struct IngestionFailureReason {
+    /// Description of the failure
+    pub details: Option<String>,
+    /// Error code of the failure
+    pub error_code: Option<String>,
+    /// Whether the failure is permanent or transient
+    pub failure_kind: FailureKind,
+}
+
+/// A message posted by Kusto to the successful or failed ingestions queue
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct StatusMessage {
+    /// The source id of the ingestion, as set on the [BlobDescriptor](crate::descriptors::BlobDescriptor)
+    pub ingestion_source_id: Uuid,
+    #[serde(default)]
+    pub details: Option<String>,
+    #[serde(default)]
+    pub error_code: Option<String>,
+    /// Only set on messages of the failed ingestions queue
+    #[serde(default)]
+    pub failure_status: Option<FailureKind>,
+}
+
+impl StatusMessage {
+    /// Parses the text of a status queue message, which is base64 encoded JSON
+    pub(crate) fn parse(message_text: &str) -> Result<Self> {
+        let message = base64::decode(message_text)?;
+        Ok(serde_json::from_slice(&message)?)
+    }
+}
+
+impl From<StatusMessage> for IngestionFailureReason {
+    fn from(message: StatusMessage) -> Self {
+        Self {
+            details: message.details,
+            error_code: message.error_code,
+            failure_kind: message.failure_status.unwrap_or(FailureKind::Unknown),
+        }
+    }
+}
+
+/// Takes the status message of the ingestion with the given source id out of the given queues, deleting it once found.
+///
+/// Messages are dequeued in batches until a queue has no more visible messages, so that the whole queue is read.
+/// The messages of other ingestions are left in the queues, hidden for the [STATUS_VISIBILITY_TIMEOUT], and a queue is
+/// read for no longer than that, so that its first messages aren't read again.
+pub(crate) async fn take_status_message(
+    queues: &[QueueClient],
+    source_id: Uuid,
+) -> Result<Option<StatusMessage>> {
+    for queue in queues {
+        let started = Instant::now();
+        while started.elapsed() < STATUS_VISIBILITY_TIMEOUT {
+            let response = queue
+                .get_messages()
+                .number_of_messages(MAX_DEQUEUED_MESSAGES)
+                .visibility_timeout(VisibilityTimeout::new(STATUS_VISIBILITY_TIMEOUT))
+                .await?;
+            if response.messages.is_empty() {
+                break;
+            }
+
+            for message in response.messages {
+                // Other writers may share the queue, so messages that are not status messages are skipped rather than failing the check
+                match StatusMessage::parse(&message.message_text) {
+                    Ok(status) if status.ingestion_source_id == source_id => {
+                        queue
+                            .pop_receipt_client(message.pop_receipt())
+                            .delete()
+                            .await?;
+                        return Ok(Some(status));
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SUCCESS_MESSAGE: &str = r#"{
+        "OperationId": "ad0f9f8e-a0c2-4b1a-a7b8-0a0c8ad7a5e9",
+        "Database": "db",
+        "Table": "events",
+        "SucceededOn": "2023-05-01T10:15:30.1234567Z",
+        "IngestionSourceId": "3827def6-0773-4f2a-859e-c02cf395deaf",
+        "IngestionSourcePath": "https://account.blob.core.windows.net/container/blob.csv",
+        "RootActivityId": "2ed9f6c4-0b55-4a4e-8b52-5b3fb1bc24ae"
+    }"#;
+
+    const FAILURE_MESSAGE: &str = r#"{
+        "OperationId": "ad0f9f8e-a0c2-4b1a-a7b8-0a0c8ad7a5e9",
+        "Database": "db",
+        "Table": "events",
+        "FailedOn": "2023-05-01T10:15:30.1234567Z",
+        "IngestionSourceId": "3827def6-0773-4f2a-859e-c02cf395deaf",
+        "IngestionSourcePath": "https://account.blob.core.windows.net/container/blob.csv",
+        "Details": "Stream with id 'blob.csv' has a malformed Csv format",
+        "FailureStatus": "Permanent",
+        "RootActivityId": "2ed9f6c4-0b55-4a4e-8b52-5b3fb1bc24ae",
+        "OriginatesFromUpdatePolicy": false,
+        "ErrorCode": "BadRequest_InvalidCsvFormat"
+    }"#;
+
+    #[test]
+    fn parse_success_message() {
+        let message = StatusMessage::parse(&base64::encode(SUCCESS_MESSAGE))
+            .expect("Failed to parse message");
+
+        assert_eq!(
+            message.ingestion_source_id,
+            Uuid::parse_str("3827def6-0773-4f2a-859e-c02cf395deaf").unwrap()
+        );
+        assert_eq!(message.failure_status, None);
+        assert_eq!(message.details, None);
+    }
+
+    #[test]
+    fn parse_failure_message() {
+        let message = StatusMessage::parse(&base64::encode(FAILURE_MESSAGE))
+            .expect("Failed to parse message");
+
+        assert_eq!(
+            IngestionFailureReason::from(message),
+            IngestionFailureReason {
+                details: Some("Stream with id 'blob.csv' has a malformed Csv format".to_string()),
+                error_code: Some("BadRequest_InvalidCsvFormat".to_string()),
+                failure_kind: FailureKind::Permanent,
+            }
+        );
+    }
+
+    #[test]
+    fn unknown_failure_statuses_are_parsed() {
+        let message = FAILURE_MESSAGE.replace("Permanent", "Exhausted");
+        let message =
+            StatusMessage::parse(&base64::encode(message)).expect("Failed to parse message");
+
+        assert_eq!(message.failure_status, Some(FailureKind::Unknown));
+    }
+
+    #[test]
+    fn messages_that_are_not_status_messages_are_errors() {
+        assert!(StatusMessage::parse("not base64!").is_err());
+        assert!(StatusMessage::parse(&base64::encode(r#"{"Database": "db"}"#)).is_err());
+    }
+
+    #[test]
+    fn report_level_serialization() {
+        assert_eq!(
+            serde_json::to_string(&ReportLevel::FailuresOnly).unwrap(),
+            "0"
+        );
+        assert_eq!(
+            serde_json::to_string(&ReportLevel::DoNotReport).unwrap(),
+            "1"
+        );
+        assert_eq!(
+            serde_json::to_string(&ReportLevel::FailuresAndSuccesses).unwrap(),
+            "2"
+        );
+    }
+
+    #[test]
+    fn report_method_serialization() {
+        assert_eq!(serde_json::to_string(&ReportMethod::Queue).unwrap(), "0");
+        assert_eq!(serde_json::to_string(&ReportMethod::Table).unwrap(), "1");
+        assert_eq!(
+            serde_json::to_string(&ReportMethod::QueueAndTable).unwrap(),
+            "2"
+        );
+    }
+}
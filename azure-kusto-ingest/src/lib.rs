@@ -3,6 +3,13 @@ pub mod data_format;
 pub mod descriptors;
 pub mod error;
 pub(crate) mod ingestion_blob_info;
+pub mod ingestion_failures;
 pub mod ingestion_properties;
+pub mod ingestion_result;
+pub mod ingestion_status;
+pub mod managed_streaming;
 pub mod queued_ingest;
+pub mod records;
 pub(crate) mod resource_manager;
+pub mod staging;
+pub mod streaming_ingest;
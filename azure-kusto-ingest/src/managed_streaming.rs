@@ -0,0 +1,593 @@
+//! Defines [ManagedStreamingIngestClient], which ingests with streaming ingestion when possible, and falls back to queued ingestion otherwise.
+
+use std::time::Duration;
+
+use azure_core::error::ErrorKind;
+use azure_kusto_data::error::Error as KustoError;
+use azure_kusto_data::prelude::KustoClient;
+use uuid::Uuid;
+
+use crate::descriptors::BlobDescriptor;
+use crate::error::{Error, Result};
+use crate::ingestion_properties::IngestionProperties;
+use crate::ingestion_result::IngestionResult;
+use crate::queued_ingest::QueuedIngestClient;
+use crate::resource_manager::retry::is_transient;
+use crate::streaming_ingest::StreamingIngestClient;
+
+/// The default number of streaming attempts made before falling back to queued ingestion
+pub const DEFAULT_MAX_STREAMING_ATTEMPTS: u32 = 3;
+
+/// The default delay before retrying a streaming ingestion, doubled after each failed attempt
+pub const DEFAULT_STREAMING_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// The default size, in bytes, above which blobs are queued without attempting streaming ingestion.
+/// Kusto rejects streaming ingestions of more than 4 MiB of uncompressed data
+pub const DEFAULT_STREAMING_SIZE_THRESHOLD: u64 = 4 * 1024 * 1024;
+
+/// The errors that Kusto returns when streaming ingestion is not possible for a table, which are not worth retrying
+pub const DEFAULT_FALLBACK_ERROR_CODES: [&str; 3] = [
+    "Kusto.DataNode.Exceptions.StreamingIngestionPolicyNotEnabledException",
+    "Kusto.DataNode.Exceptions.StreamingIngestionDisabledForClusterException",
+    "Kusto.DataNode.Exceptions.StreamingIngestionRequestEntityTooLargeException",
+];
+
+/// Controls when [ManagedStreamingIngestClient] retries streaming ingestion, and when it falls back to queued ingestion
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ManagedStreamingPolicy {
+    /// The number of streaming attempts made while they fail with transient errors, before falling back to queued ingestion.
+    /// At least one attempt is always made
+    pub max_streaming_attempts: u32,
+    /// The delay before retrying a streaming ingestion, doubled after each failed attempt
+    pub retry_delay: Duration,
+    /// Blobs larger than this size, in bytes, are queued without attempting streaming ingestion.
+    /// Blobs of unknown size are always streamed first
+    pub size_threshold: u64,
+    /// Error codes, or exception types, of streaming failures that fall back to queued ingestion immediately.
+    /// Responses with a status of 413 (payload too large) always fall back
+    pub fallback_error_codes: Vec<String>,
+}
+
+impl Default for ManagedStreamingPolicy {
+    fn default() -> Self {
+        Self {
+            max_streaming_attempts: DEFAULT_MAX_STREAMING_ATTEMPTS,
+            retry_delay: DEFAULT_STREAMING_RETRY_DELAY,
+            size_threshold: DEFAULT_STREAMING_SIZE_THRESHOLD,
+            fallback_error_codes: DEFAULT_FALLBACK_ERROR_CODES
+                .iter()
+                .map(|code| code.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl ManagedStreamingPolicy {
+    /// Returns the code that makes the error fall back to queued ingestion, if any
+    fn fallback_code(&self, error: &KustoError) -> Option<String> {
+        let is_fallback_code = |code: &str| self.fallback_error_codes.iter().any(|c| c == code);
+        match error {
+            KustoError::QueryApiError(error) => {
+                let message = &error.error_message;
+                std::iter::once(message.code.as_str())
+                    .chain(message.r#type.as_deref())
+                    .find(|code| is_fallback_code(code))
+                    .map(str::to_string)
+            }
+            KustoError::HttpError(status, _) if u16::from(*status) == 413 => {
+                Some(u16::from(*status).to_string())
+            }
+            KustoError::AzureError(error) => match error.kind() {
+                ErrorKind::HttpResponse { status, .. } if u16::from(*status) == 413 => {
+                    Some(u16::from(*status).to_string())
+                }
+                ErrorKind::HttpResponse {
+                    error_code: Some(code),
+                    ..
+                } if is_fallback_code(code) => Some(code.clone()),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Whether a failed streaming ingestion is worth retrying
+fn is_retryable(error: &KustoError) -> bool {
+    match error {
+        KustoError::QueryApiError(error) => error.error_message.is_permanent == Some(false),
+        error => is_transient(error),
+    }
+}
+
+/// Why an ingestion was queued rather than streamed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FallbackReason {
+    /// The blob is larger than the [size_threshold](ManagedStreamingPolicy::size_threshold), so streaming was not attempted
+    SizeThreshold,
+    /// Streaming failed with one of the [fallback_error_codes](ManagedStreamingPolicy::fallback_error_codes), or with a 413 status
+    FallbackError(String),
+    /// Streaming failed with transient errors for all of the [max_streaming_attempts](ManagedStreamingPolicy::max_streaming_attempts)
+    RetriesExhausted(u32),
+}
+
+/// The outcome of an ingestion by [ManagedStreamingIngestClient], stating which flavour of ingestion was used
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ManagedIngestionResult {
+    /// The data was ingested with streaming ingestion
+    Streamed {
+        /// The source id of the ingestion, as set on the [BlobDescriptor], or generated for it
+        source_id: Uuid,
+        /// The number of streaming attempts made, including the successful one
+        attempts: u32,
+    },
+    /// The ingestion was queued, with the same source id as the streaming attempts
+    Queued {
+        /// Identifies the queued ingestion
+        result: IngestionResult,
+        /// Why streaming ingestion was not used
+        reason: FallbackReason,
+    },
+}
+
+impl ManagedIngestionResult {
+    /// The source id of the ingestion, whichever flavour of ingestion was used
+    pub fn source_id(&self) -> Uuid {
+        match self {
+            ManagedIngestionResult::Streamed { source_id, .. } => *source_id,
+            ManagedIngestionResult::Queued { result, .. } => result.source_id,
+        }
+    }
+}
+
+/// Streaming ingestion, abstracted so that the fallback can be tested without a cluster
+#[async_trait::async_trait]
+pub(crate) trait StreamingIngestor {
+    async fn stream(
+        &self,
+        blob_descriptor: &BlobDescriptor,
+        ingestion_properties: &IngestionProperties,
+    ) -> Result<()>;
+}
+
+#[async_trait::async_trait]
+impl StreamingIngestor for StreamingIngestClient {
+    async fn stream(
+        &self,
+        blob_descriptor: &BlobDescriptor,
+        ingestion_properties: &IngestionProperties,
+    ) -> Result<()> {
+        self.ingest_from_blob(blob_descriptor.clone(), ingestion_properties.clone())
+            .await
+    }
+}
+
+/// Queued ingestion, abstracted so that the fallback can be tested without a cluster
+#[async_trait::async_trait]
+pub(crate) trait QueuedIngestor {
+    async fn enqueue(
+        &self,
+        blob_descriptor: BlobDescriptor,
+        ingestion_properties: IngestionProperties,
+    ) -> Result<IngestionResult>;
+}
+
+#[async_trait::async_trait]
+impl QueuedIngestor for QueuedIngestClient {
+    async fn enqueue(
+        &self,
+        blob_descriptor: BlobDescriptor,
+        ingestion_properties: IngestionProperties,
+    ) -> Result<IngestionResult> {
+        self.ingest_from_blob(blob_descriptor, ingestion_properties)
+            .await
+    }
+}
+
+/// Streams the blob according to the policy, and queues it when streaming is not possible
+pub(crate) async fn managed_ingest(
+    streaming: &(impl StreamingIngestor + Sync),
+    queued: &(impl QueuedIngestor + Sync),
+    policy: &ManagedStreamingPolicy,
+    blob_descriptor: BlobDescriptor,
+    ingestion_properties: IngestionProperties,
+) -> Result<ManagedIngestionResult> {
+    // Invalid properties fail both flavours of ingestion, so they are reported rather than falling back
+    ingestion_properties.validate()?;
+
+    let reason = if blob_descriptor
+        .size
+        .is_some_and(|size| size > policy.size_threshold)
+    {
+        FallbackReason::SizeThreshold
+    } else {
+        let mut delay = policy.retry_delay;
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let error = match streaming
+                .stream(&blob_descriptor, &ingestion_properties)
+                .await
+            {
+                Ok(()) => {
+                    return Ok(ManagedIngestionResult::Streamed {
+                        source_id: blob_descriptor.source_id,
+                        attempts,
+                    })
+                }
+                Err(Error::KustoError(error)) => error,
+                Err(error) => return Err(error),
+            };
+
+            if let Some(code) = policy.fallback_code(&error) {
+                break FallbackReason::FallbackError(code);
+            }
+            if !is_retryable(&error) {
+                return Err(error.into());
+            }
+            if attempts >= policy.max_streaming_attempts {
+                break FallbackReason::RetriesExhausted(attempts);
+            }
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+        }
+    };
+
+    #[cfg(feature = "tracing")]
+    tracing::info!(source_id = %blob_descriptor.source_id, ?reason, "Falling back to queued ingestion");
+
+    let result = queued
+        .enqueue(blob_descriptor, ingestion_properties)
+        .await?;
+    Ok(ManagedIngestionResult::Queued { result, reason })
+}
+
+/// Client for ingesting data into Kusto with streaming ingestion when possible, falling back to queued ingestion otherwise.
+///
+/// Blobs larger than the [size_threshold](ManagedStreamingPolicy::size_threshold) are queued directly. Other blobs are streamed,
+/// retrying transient failures, and are queued with the same source id when streaming is not enabled for the table,
+/// or keeps failing. Use the [ManagedIngestionResult] to tell which flavour of ingestion was used
+#[derive(Clone)]
+pub struct ManagedStreamingIngestClient {
+    streaming_client: StreamingIngestClient,
+    queued_client: QueuedIngestClient,
+    policy: ManagedStreamingPolicy,
+}
+
+impl ManagedStreamingIngestClient {
+    /// Creates a new client, with the default [ManagedStreamingPolicy].
+    ///
+    /// **WARNING**: the `engine_client` must point to the engine endpoint of the cluster, and the `ingestion_client` to its ingestion endpoint
+    pub fn new(engine_client: KustoClient, ingestion_client: KustoClient) -> Self {
+        Self::new_with_policy(
+            StreamingIngestClient::new(engine_client),
+            QueuedIngestClient::new(ingestion_client),
+            ManagedStreamingPolicy::default(),
+        )
+    }
+
+    /// Creates a new client from existing streaming and queued clients, with the given [ManagedStreamingPolicy]
+    pub fn new_with_policy(
+        streaming_client: StreamingIngestClient,
+        queued_client: QueuedIngestClient,
+        policy: ManagedStreamingPolicy,
+    ) -> Self {
+        Self {
+            streaming_client,
+            queued_client,
+            policy,
+        }
+    }
+
+    /// The policy deciding when to fall back to queued ingestion
+    pub fn policy(&self) -> &ManagedStreamingPolicy {
+        &self.policy
+    }
+
+    /// Ingest a file into Kusto from Azure Blob Storage, with streaming ingestion when possible.
+    /// The [IngestionProperties] are [validated](IngestionProperties::validate) first, and nothing is ingested when they are invalid.
+    ///
+    /// Errors that are neither transient nor in the [fallback_error_codes](ManagedStreamingPolicy::fallback_error_codes)
+    /// are returned without falling back, as queued ingestion would fail the same way
+    pub async fn ingest_from_blob(
+        &self,
+        blob_descriptor: BlobDescriptor,
+        ingestion_properties: IngestionProperties,
+    ) -> Result<ManagedIngestionResult> {
+        managed_ingest(
+            &self.streaming_client,
+            &self.queued_client,
+            &self.policy,
+            blob_descriptor,
+            ingestion_properties,
+        )
+        .await
+    }
+
+    /// The client used for queued ingestion, e.g. to [check the status](QueuedIngestClient::check_status) of queued ingestions
+    pub fn queued_client(&self) -> &QueuedIngestClient {
+        &self.queued_client
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use azure_core::StatusCode;
+    use azure_kusto_data::models::{ErrorMessage, OneApiError};
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    /// A streaming ingestion that fails with a scripted sequence of errors, then succeeds
+    #[derive(Default)]
+    struct FakeStreaming {
+        errors: Mutex<VecDeque<KustoError>>,
+        attempts: Mutex<u32>,
+    }
+
+    impl FakeStreaming {
+        fn failing_with(errors: Vec<KustoError>) -> Self {
+            Self {
+                errors: Mutex::new(errors.into()),
+                ..Self::default()
+            }
+        }
+
+        fn attempts(&self) -> u32 {
+            *self.attempts.lock().unwrap()
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl StreamingIngestor for FakeStreaming {
+        async fn stream(
+            &self,
+            _blob_descriptor: &BlobDescriptor,
+            _ingestion_properties: &IngestionProperties,
+        ) -> Result<()> {
+            *self.attempts.lock().unwrap() += 1;
+            match self.errors.lock().unwrap().pop_front() {
+                Some(error) => Err(error.into()),
+                None => Ok(()),
+            }
+        }
+    }
+
+    /// A queued ingestion recording the source ids of the blobs it queued
+    #[derive(Default)]
+    struct FakeQueued {
+        queued: Mutex<Vec<Uuid>>,
+    }
+
+    #[async_trait::async_trait]
+    impl QueuedIngestor for FakeQueued {
+        async fn enqueue(
+            &self,
+            blob_descriptor: BlobDescriptor,
+            ingestion_properties: IngestionProperties,
+        ) -> Result<IngestionResult> {
+            self.queued.lock().unwrap().push(blob_descriptor.source_id);
+            Ok(IngestionResult::new(
+                &blob_descriptor,
+                &ingestion_properties,
+                "queue",
+                "message",
+                "receipt",
+            ))
+        }
+    }
+
+    fn blob(size: Option<u64>) -> BlobDescriptor {
+        BlobDescriptor::new(
+            "https://account.blob.core.windows.net/container/blob.csv",
+            size,
+            None,
+        )
+    }
+
+    fn ingestion_properties() -> IngestionProperties {
+        IngestionProperties {
+            database_name: "db".to_string(),
+            table_name: "table".to_string(),
+            ..IngestionProperties::default()
+        }
+    }
+
+    fn api_error(code: &str, r#type: &str, is_permanent: bool) -> KustoError {
+        KustoError::QueryApiError(Box::new(OneApiError {
+            error_message: ErrorMessage {
+                code: code.to_string(),
+                message: "Streaming ingestion failed".to_string(),
+                r#type: Some(r#type.to_string()),
+                description: None,
+                context: None,
+                is_permanent: Some(is_permanent),
+            },
+        }))
+    }
+
+    fn throttled() -> KustoError {
+        KustoError::HttpError(StatusCode::TooManyRequests, "".into())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn transient_failures_are_retried() {
+        let streaming = FakeStreaming::failing_with(vec![
+            throttled(),
+            api_error(
+                "ServiceBusy",
+                "Kusto.Common.Svc.Exceptions.ServiceBusyException",
+                false,
+            ),
+        ]);
+        let queued = FakeQueued::default();
+        let blob = blob(Some(1024));
+        let source_id = blob.source_id;
+        let started = tokio::time::Instant::now();
+
+        let result = managed_ingest(
+            &streaming,
+            &queued,
+            &ManagedStreamingPolicy::default(),
+            blob,
+            ingestion_properties(),
+        )
+        .await
+        .expect("Failed to ingest");
+
+        assert_eq!(
+            result,
+            ManagedIngestionResult::Streamed {
+                source_id,
+                attempts: 3
+            }
+        );
+        // The delay doubles after each failed attempt
+        assert_eq!(
+            started.elapsed(),
+            DEFAULT_STREAMING_RETRY_DELAY + DEFAULT_STREAMING_RETRY_DELAY * 2
+        );
+        assert!(queued.queued.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn fallback_errors_are_queued_with_the_same_source_id() {
+        let streaming = FakeStreaming::failing_with(vec![api_error(
+            "BadRequest",
+            DEFAULT_FALLBACK_ERROR_CODES[0],
+            true,
+        )]);
+        let queued = FakeQueued::default();
+        let blob = blob(None);
+        let source_id = blob.source_id;
+
+        let result = managed_ingest(
+            &streaming,
+            &queued,
+            &ManagedStreamingPolicy::default(),
+            blob,
+            ingestion_properties(),
+        )
+        .await
+        .expect("Failed to ingest");
+
+        assert_eq!(streaming.attempts(), 1);
+        assert_eq!(*queued.queued.lock().unwrap(), vec![source_id]);
+        assert_eq!(result.source_id(), source_id);
+        assert!(matches!(
+            result,
+            ManagedIngestionResult::Queued { reason: FallbackReason::FallbackError(code), .. }
+                if code == DEFAULT_FALLBACK_ERROR_CODES[0]
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn payloads_too_large_are_queued() {
+        let streaming = FakeStreaming::failing_with(vec![KustoError::HttpError(
+            StatusCode::PayloadTooLarge,
+            "".into(),
+        )]);
+        let queued = FakeQueued::default();
+
+        let result = managed_ingest(
+            &streaming,
+            &queued,
+            &ManagedStreamingPolicy::default(),
+            blob(None),
+            ingestion_properties(),
+        )
+        .await
+        .expect("Failed to ingest");
+
+        assert!(matches!(
+            result,
+            ManagedIngestionResult::Queued {
+                reason: FallbackReason::FallbackError(_),
+                ..
+            }
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn exhausted_retries_are_queued_with_the_same_source_id() {
+        let streaming =
+            FakeStreaming::failing_with(vec![throttled(), throttled(), throttled(), throttled()]);
+        let queued = FakeQueued::default();
+        let blob = blob(Some(1024));
+        let source_id = blob.source_id;
+
+        let result = managed_ingest(
+            &streaming,
+            &queued,
+            &ManagedStreamingPolicy::default(),
+            blob,
+            ingestion_properties(),
+        )
+        .await
+        .expect("Failed to ingest");
+
+        assert_eq!(streaming.attempts(), DEFAULT_MAX_STREAMING_ATTEMPTS);
+        assert_eq!(*queued.queued.lock().unwrap(), vec![source_id]);
+        assert!(matches!(
+            result,
+            ManagedIngestionResult::Queued {
+                reason: FallbackReason::RetriesExhausted(DEFAULT_MAX_STREAMING_ATTEMPTS),
+                ..
+            }
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn large_blobs_are_queued_without_streaming() {
+        let streaming = FakeStreaming::default();
+        let queued = FakeQueued::default();
+        let blob = blob(Some(DEFAULT_STREAMING_SIZE_THRESHOLD + 1));
+        let source_id = blob.source_id;
+
+        let result = managed_ingest(
+            &streaming,
+            &queued,
+            &ManagedStreamingPolicy::default(),
+            blob,
+            ingestion_properties(),
+        )
+        .await
+        .expect("Failed to ingest");
+
+        assert_eq!(streaming.attempts(), 0);
+        assert_eq!(*queued.queued.lock().unwrap(), vec![source_id]);
+        assert!(matches!(
+            result,
+            ManagedIngestionResult::Queued {
+                reason: FallbackReason::SizeThreshold,
+                ..
+            }
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn permanent_failures_are_not_queued() {
+        let streaming = FakeStreaming::failing_with(vec![api_error(
+            "BadRequest",
+            "Kusto.Data.Exceptions.EntityNotFoundException",
+            true,
+        )]);
+        let queued = FakeQueued::default();
+
+        let result = managed_ingest(
+            &streaming,
+            &queued,
+            &ManagedStreamingPolicy::default(),
+            blob(None),
+            ingestion_properties(),
+        )
+        .await;
+
+        assert!(matches!(
+            result,
+            Err(Error::KustoError(KustoError::QueryApiError(_)))
+        ));
+        assert_eq!(streaming.attempts(), 1);
+        assert!(queued.queued.lock().unwrap().is_empty());
+    }
+}
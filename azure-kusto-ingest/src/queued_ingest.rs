@@ -1,25 +1,47 @@
 use std::sync::Arc;
+use std::time::Duration;
 
 use crate::error::Result;
 use azure_core::base64;
 use azure_kusto_data::prelude::KustoClient;
+use azure_storage_queues::{MessageTTL, QueueClient, VisibilityTimeout};
+use futures::StreamExt;
+use rand::Rng;
+use serde::Serialize;
+use uuid::Uuid;
 
 use crate::client_options::QueuedIngestClientOptions;
 use crate::descriptors::BlobDescriptor;
 use crate::ingestion_blob_info::QueuedIngestionMessage;
 use crate::ingestion_properties::IngestionProperties;
-use crate::resource_manager::ResourceManager;
+use crate::ingestion_result::{IngestionResult, RecordsIngestionResult};
+use crate::ingestion_status::{take_status_message, IngestionStatus};
+use crate::records::{gzip, serialize_records, RecordsFormat};
+use crate::resource_manager::{ResourceManager, ResourceManagerError};
+use crate::staging::{copy_source, stage_blob, staged_blob_name, temp_blob_name};
+
+/// The default number of messages that [QueuedIngestClient::ingest_from_blobs] posts to the queues at once
+pub const DEFAULT_MAX_CONCURRENT_ENQUEUES: usize = 16;
 
 /// Client for ingesting data into Kusto using the queued flavour of ingestion
 #[derive(Clone)]
 pub struct QueuedIngestClient {
+    kusto_client: KustoClient,
     resource_manager: Arc<ResourceManager>,
+    message_ttl: Option<Duration>,
+    message_visibility_timeout: Option<Duration>,
+    staging_poll_interval: Duration,
+    staging_timeout: Duration,
+    max_concurrent_enqueues: usize,
 }
 
 impl QueuedIngestClient {
     /// Creates a new client from the given [KustoClient].
     ///
-    /// **WARNING**: the [KustoClient] must be created with a connection string that points to the ingestion endpoint
+    /// **WARNING**: the [KustoClient] must be created with a connection string that points to the ingestion endpoint.
+    /// To reach the ingestion endpoint through a proxy, override its management endpoint with
+    /// [with_endpoint_overrides](azure_kusto_data::prelude::KustoClientOptions::with_endpoint_overrides), which keeps the tokens
+    /// requested for the ingestion endpoint of the connection string.
     pub fn new(kusto_client: KustoClient) -> Self {
         Self::new_with_client_options(kusto_client, QueuedIngestClientOptions::default())
     }
@@ -33,30 +55,833 @@ impl QueuedIngestClient {
         options: QueuedIngestClientOptions,
     ) -> Self {
         Self {
-            resource_manager: Arc::new(ResourceManager::new(kusto_client, options)),
+            message_ttl: options.message_ttl,
+            message_visibility_timeout: options.message_visibility_timeout,
+            staging_poll_interval: options.staging_poll_interval,
+            staging_timeout: options.staging_timeout,
+            max_concurrent_enqueues: options.max_concurrent_enqueues,
+            resource_manager: Arc::new(ResourceManager::new(kusto_client.clone(), options)),
+            kusto_client,
         }
     }
 
-    /// Ingest a file into Kusto from Azure Blob Storage
+    /// Ingest a file into Kusto from Azure Blob Storage.
+    /// Returns once the ingestion was queued, with an [IngestionResult] that identifies it.
+    ///
+    /// The [IngestionProperties] are [validated](IngestionProperties::validate) first, and nothing is queued when they are invalid
+    // The blob uri is not recorded in the span, as it may carry a SAS token.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "kusto.ingest",
+            skip_all,
+            fields(
+                source_id = %blob_descriptor.source_id,
+                database = %ingestion_properties.database_name,
+                table = %ingestion_properties.table_name,
+            ),
+            err
+        )
+    )]
     pub async fn ingest_from_blob(
         &self,
         blob_descriptor: BlobDescriptor,
         ingestion_properties: IngestionProperties,
-    ) -> Result<()> {
+    ) -> Result<IngestionResult> {
+        // Invalid messages fail in Kusto, where the failure can't be seen
+        let _warnings = ingestion_properties.validate()?;
+        #[cfg(feature = "tracing")]
+        for warning in &_warnings {
+            tracing::warn!(%warning, "Discouraged ingestion properties");
+        }
+
         let queue_client = self.resource_manager.random_ingestion_queue().await?;
 
         let auth_context = self.resource_manager.authorization_context().await?;
 
+        self.enqueue(
+            &queue_client,
+            &blob_descriptor,
+            &ingestion_properties,
+            auth_context,
+        )
+        .await
+    }
+
+    /// Ingest many files into Kusto from Azure Blob Storage, with the same [IngestionProperties].
+    /// Returns once the ingestion of each blob was queued, or failed to be, with the result of each blob keyed by its source id, in the order of the blobs.
+    ///
+    /// The ingestion resources and the authorization context are fetched once for the whole batch, and the messages are serialized and posted
+    /// with up to [max_concurrent_enqueues](QueuedIngestClientOptions::max_concurrent_enqueues) in flight, spread evenly over the ingestion queues.
+    /// The failure of a blob doesn't stop the others from being queued, but nothing is queued when the properties are
+    /// [invalid](IngestionProperties::validate), or when the resources or authorization context can't be fetched.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "kusto.ingest_batch",
+            skip_all,
+            fields(
+                blobs = blob_descriptors.len(),
+                database = %ingestion_properties.database_name,
+                table = %ingestion_properties.table_name,
+            ),
+            err
+        )
+    )]
+    pub async fn ingest_from_blobs(
+        &self,
+        blob_descriptors: Vec<BlobDescriptor>,
+        ingestion_properties: &IngestionProperties,
+    ) -> Result<Vec<(Uuid, Result<IngestionResult>)>> {
+        let _warnings = ingestion_properties.validate()?;
+        #[cfg(feature = "tracing")]
+        for warning in &_warnings {
+            tracing::warn!(%warning, "Discouraged ingestion properties");
+        }
+        if blob_descriptors.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let queues = self.resource_manager.ingestion_queues().await?;
+        if queues.is_empty() {
+            return Err(ResourceManagerError::NoResourcesFound.into());
+        }
+        let auth_context = self.resource_manager.authorization_context().await?;
+
+        // Blobs are assigned to the queues in turn, from a random one, so that batches don't all start on the same queue
+        let first_queue = rand::thread_rng().gen_range(0..queues.len());
+        let results = futures::stream::iter(blob_descriptors.into_iter().enumerate())
+            .map(|(index, blob_descriptor)| {
+                let queue_client = &queues[(first_queue + index) % queues.len()];
+                let auth_context = auth_context.clone();
+                async move {
+                    let result = self
+                        .enqueue(
+                            queue_client,
+                            &blob_descriptor,
+                            ingestion_properties,
+                            auth_context,
+                        )
+                        .await;
+                    (blob_descriptor.source_id, result)
+                }
+            })
+            .buffered(self.max_concurrent_enqueues.max(1))
+            .collect()
+            .await;
+        Ok(results)
+    }
+
+    /// Posts the ingestion message of a blob to the queue, with the current trace context of the [KustoClient], if any.
+    async fn enqueue(
+        &self,
+        queue_client: &QueueClient,
+        blob_descriptor: &BlobDescriptor,
+        ingestion_properties: &IngestionProperties,
+        auth_context: String,
+    ) -> Result<IngestionResult> {
+        let trace_context = self.kusto_client.trace_context();
         let message =
-            QueuedIngestionMessage::new(&blob_descriptor, &ingestion_properties, auth_context);
+            QueuedIngestionMessage::new(blob_descriptor, ingestion_properties, auth_context)?
+                .with_trace_context(trace_context.as_ref());
 
         let message = serde_json::to_string(&message)?;
 
         // Base64 encode the ingestion message
         let message = base64::encode(&message);
 
-        let _resp = queue_client.put_message(message).await?;
+        let mut put_message = queue_client.put_message(message);
+        if let Some(message_ttl) = self.message_ttl {
+            put_message = put_message.ttl(MessageTTL::new(message_ttl));
+        }
+        if let Some(message_visibility_timeout) = self.message_visibility_timeout {
+            put_message =
+                put_message.visibility_timeout(VisibilityTimeout::new(message_visibility_timeout));
+        }
+        let response = put_message.await?;
+
+        Ok(IngestionResult::new(
+            blob_descriptor,
+            ingestion_properties,
+            queue_client.queue_name(),
+            response.queue_message.message_id,
+            response.queue_message.pop_receipt,
+        ))
+    }
+
+    /// Ingest a blob that Kusto cannot read directly, by first copying it into the temporary storage of Kusto.
+    ///
+    /// The copy is done by the storage service, so the source must be readable with the uri and SAS token of the [BlobDescriptor];
+    /// managed identity authentication is not supported. The status of the copy is checked every
+    /// [staging_poll_interval](QueuedIngestClientOptions::staging_poll_interval), for up to the
+    /// [staging_timeout](QueuedIngestClientOptions::staging_timeout), before queuing the ingestion of the staged blob.
+    ///
+    /// Kusto deletes the staged blob once ingested, unless [retain_blob_on_success](IngestionProperties::retain_blob_on_success) is set.
+    /// If the copy or the queuing fails, the staged blob is deleted before returning the error.
+    pub async fn ingest_via_staging(
+        &self,
+        source: BlobDescriptor,
+        ingestion_properties: IngestionProperties,
+    ) -> Result<IngestionResult> {
+        // Checked before the copy too, so that nothing is staged for an ingestion that can't be queued
+        ingestion_properties.validate()?;
+        let copy_source = copy_source(&source)?;
+        let container = self.resource_manager.random_temp_storage().await?;
+
+        let blob_name = staged_blob_name(&source, &ingestion_properties);
+        let blob_client = container.client.blob_client(&blob_name);
+        stage_blob(
+            &blob_client,
+            copy_source,
+            self.staging_poll_interval,
+            self.staging_timeout,
+        )
+        .await?;
+
+        let staged = BlobDescriptor::new(
+            container.blob_uri(&blob_name),
+            source.size,
+            Some(source.source_id),
+        );
+        let result = self.ingest_from_blob(staged, ingestion_properties).await;
+        if result.is_err() {
+            // Nothing will ingest the staged blob, so it is only taking up space in the temporary storage
+            let _ = blob_client.delete().await;
+        }
+        result
+    }
+
+    /// Ingest in-memory records, serialized as [MultiJson](RecordsFormat::MultiJson), so that the fields of the records are matched to the columns of the table by name.
+    /// See [ingest_from_records_with_format](Self::ingest_from_records_with_format).
+    pub async fn ingest_from_records<T: Serialize>(
+        &self,
+        records: &[T],
+        ingestion_properties: IngestionProperties,
+    ) -> Result<RecordsIngestionResult> {
+        self.ingest_from_records_with_format(
+            records,
+            RecordsFormat::MultiJson,
+            ingestion_properties,
+        )
+        .await
+    }
+
+    /// Ingest in-memory records, by serializing them in the given format, compressing them, and uploading them to the temporary storage of Kusto
+    /// before queuing their ingestion. See the [records](crate::records) module for how fields are serialized for the column types of Kusto.
+    ///
+    /// The [data_format](IngestionProperties::data_format) of the properties is set to that of the records, and the header row of CSV records is skipped.
+    /// When there are no records, nothing is uploaded or queued, and the result has no [ingestion](RecordsIngestionResult::ingestion).
+    /// If the queuing fails, the uploaded blob is deleted before returning the error.
+    pub async fn ingest_from_records_with_format<T: Serialize>(
+        &self,
+        records: &[T],
+        format: RecordsFormat,
+        mut ingestion_properties: IngestionProperties,
+    ) -> Result<RecordsIngestionResult> {
+        if records.is_empty() {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(
+                database = %ingestion_properties.database_name,
+                table = %ingestion_properties.table_name,
+                "No records to ingest, nothing was queued"
+            );
+            return Ok(RecordsIngestionResult {
+                ingestion: None,
+                record_count: 0,
+                serialized_size: 0,
+                compressed_size: 0,
+            });
+        }
+
+        ingestion_properties.data_format = format.data_format();
+        if let RecordsFormat::Csv { .. } = format {
+            ingestion_properties.ignore_first_record = Some(true);
+        }
+        // Checked before the upload too, so that nothing is uploaded for an ingestion that can't be queued
+        ingestion_properties.validate()?;
+
+        let data = serialize_records(records, &format)?;
+        let compressed = gzip(&data)?;
+        let compressed_size = compressed.len();
+
+        let source_id = Uuid::new_v4();
+        let container = self.resource_manager.random_temp_storage().await?;
+        let blob_name = temp_blob_name(
+            &ingestion_properties,
+            source_id,
+            &format!("records.{}.gz", format.file_extension()),
+        );
+        let blob_client = container.client.blob_client(&blob_name);
+        blob_client.put_block_blob(compressed).await?;
+
+        let blob = BlobDescriptor::new(
+            container.blob_uri(&blob_name),
+            Some(data.len() as u64),
+            Some(source_id),
+        );
+        let ingestion = match self.ingest_from_blob(blob, ingestion_properties).await {
+            Ok(ingestion) => ingestion,
+            Err(e) => {
+                // Nothing will ingest the uploaded blob, so it is only taking up space in the temporary storage
+                let _ = blob_client.delete().await;
+                return Err(e);
+            }
+        };
+
+        Ok(RecordsIngestionResult {
+            ingestion: Some(ingestion),
+            record_count: records.len(),
+            serialized_size: data.len(),
+            compressed_size,
+        })
+    }
+
+    /// Checks the status queues for the outcome of the ingestion with the given source id,
+    /// as found on the [IngestionResult] or set on the [BlobDescriptor].
+    ///
+    /// Kusto only reports successful ingestions when they were queued with [ReportLevel::FailuresAndSuccesses](crate::ingestion_status::ReportLevel::FailuresAndSuccesses),
+    /// otherwise they remain [IngestionStatus::Pending]. The status message of the ingestion is deleted from its queue once found,
+    /// so its status is returned once, and is [IngestionStatus::Pending] afterwards. The messages of other ingestions that are read
+    /// while looking for it are hidden from the readers of the queues for 30 seconds.
+    pub async fn check_status(&self, source_id: Uuid) -> Result<IngestionStatus> {
+        let failed_ingestions_queues = self.resource_manager.failed_ingestions_queues().await?;
+        if failed_ingestions_queues.is_empty() {
+            return Err(ResourceManagerError::NoResourcesFound.into());
+        }
+        if let Some(message) = take_status_message(&failed_ingestions_queues, source_id).await? {
+            return Ok(IngestionStatus::Failed(message.into()));
+        }
+
+        let successful_ingestions_queues =
+            self.resource_manager.successful_ingestions_queues().await?;
+        if take_status_message(&successful_ingestions_queues, source_id)
+            .await?
+            .is_some()
+        {
+            return Ok(IngestionStatus::Succeeded);
+        }
+
+        Ok(IngestionStatus::Pending)
+    }
+
+    /// Discards the cached ingestion resources and authorization context, so that they are fetched from Kusto on the next ingestion.
+    /// Resources are otherwise refreshed after the [resource_refresh_period](QueuedIngestClientOptions::resource_refresh_period)
+    pub async fn force_refresh(&self) {
+        self.resource_manager.force_refresh().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use crate::ingestion_properties::IngestionPropertiesProblem;
+    use azure_core::headers::{HeaderName, Headers};
+    use azure_core::{
+        Body, BytesStream, ClientOptions, Context, Method, Policy, PolicyResult, Request, Response,
+        RetryOptions, StatusCode, TransportOptions, Url,
+    };
+    use azure_kusto_data::prelude::{ConnectionString, KustoClientOptions};
+    use azure_kusto_data::trace_context::TraceContext;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    /// Answers the commands for the ingestion resources, with two ingestion queues, and for the authorization context,
+    /// and records the commands it received, with their urls and `traceparent` headers.
+    #[derive(Debug, Default)]
+    struct KustoTransport {
+        commands: Mutex<Vec<String>>,
+        urls: Mutex<Vec<String>>,
+        traceparents: Mutex<Vec<Option<String>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Policy for KustoTransport {
+        async fn send(
+            &self,
+            _ctx: &Context,
+            request: &mut Request,
+            _next: &[Arc<dyn Policy>],
+        ) -> PolicyResult {
+            let body: serde_json::Value = match request.body() {
+                Body::Bytes(bytes) => serde_json::from_slice(bytes).unwrap(),
+                _ => panic!("Expected a buffered request body"),
+            };
+            let command = body["csl"].as_str().unwrap().to_string();
+            let table = match command.as_str() {
+                ".get ingestion resources" => serde_json::json!({
+                    "TableName": "Table_0",
+                    "Columns": [
+                        { "ColumnName": "ResourceTypeName", "DataType": "String", "ColumnType": "string" },
+                        { "ColumnName": "StorageRoot", "DataType": "String", "ColumnType": "string" }
+                    ],
+                    "Rows": [
+                        ["SecuredReadyForAggregationQueue", "https://account.queue.core.windows.net/queue1?sas=token"],
+                        ["SecuredReadyForAggregationQueue", "https://account.queue.core.windows.net/queue2?sas=token"],
+                        ["TempStorage", "https://account.blob.core.windows.net/container1?sas=token"],
+                        ["SuccessfulIngestionsQueue", "https://account.queue.core.windows.net/succeeded?sas=token"],
+                        ["FailedIngestionsQueue", "https://account.queue.core.windows.net/failed?sas=token"]
+                    ]
+                }),
+                ".get kusto identity token" => serde_json::json!({
+                    "TableName": "Table_0",
+                    "Columns": [{ "ColumnName": "AuthorizationContext", "DataType": "String", "ColumnType": "string" }],
+                    "Rows": [["token"]]
+                }),
+                other => panic!("Unexpected command {other}"),
+            };
+            self.commands.lock().unwrap().push(command);
+            self.urls.lock().unwrap().push(request.url().to_string());
+            self.traceparents.lock().unwrap().push(
+                request
+                    .headers()
+                    .get_optional_string(&HeaderName::from_static("traceparent")),
+            );
+
+            let body = serde_json::json!({ "Tables": [table] });
+            Ok(Response::new(
+                StatusCode::Ok,
+                Headers::new(),
+                Box::pin(BytesStream::new(serde_json::to_vec(&body).unwrap())),
+            ))
+        }
+    }
+
+    /// Rejects every message posted to a queue, and records the paths and bodies they were posted with,
+    /// and the most messages that were in flight at once.
+    #[derive(Debug, Default)]
+    struct RejectingQueues {
+        paths: Mutex<Vec<String>>,
+        bodies: Mutex<Vec<String>>,
+        in_flight: AtomicUsize,
+        peak_in_flight: AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl Policy for RejectingQueues {
+        async fn send(
+            &self,
+            _ctx: &Context,
+            request: &mut Request,
+            _next: &[Arc<dyn Policy>],
+        ) -> PolicyResult {
+            self.paths
+                .lock()
+                .unwrap()
+                .push(request.url().path().to_string());
+            if let Body::Bytes(bytes) = request.body() {
+                self.bodies
+                    .lock()
+                    .unwrap()
+                    .push(String::from_utf8_lossy(bytes).into_owned());
+            }
+
+            // The response is delayed, so that the messages posted concurrently are in flight together
+            let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+            for _ in 0..10 {
+                tokio::task::yield_now().await;
+            }
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+            Ok(Response::new(
+                StatusCode::Forbidden,
+                Headers::new(),
+                Box::pin(BytesStream::new("")),
+            ))
+        }
+    }
+
+    /// Options of a Kusto client that sends its requests to `kusto`.
+    fn kusto_client_options(kusto: &Arc<KustoTransport>) -> KustoClientOptions {
+        KustoClientOptions::from(
+            ClientOptions::default().transport(TransportOptions::new_custom_policy(kusto.clone())),
+        )
+    }
+
+    /// Options of a queued ingest client that sends its requests to `queues`, without retrying them.
+    fn queue_options(queues: Arc<dyn Policy>) -> QueuedIngestClientOptions {
+        QueuedIngestClientOptions::from(
+            ClientOptions::default()
+                .retry(RetryOptions::none())
+                .transport(TransportOptions::new_custom_policy(queues)),
+        )
+    }
+
+    /// A queued ingest client of the test cluster, with a Kusto client created with `kusto_options`.
+    fn queued_client(
+        kusto_options: KustoClientOptions,
+        options: QueuedIngestClientOptions,
+    ) -> QueuedIngestClient {
+        let kusto_client = KustoClient::new(
+            ConnectionString::with_no_auth("https://ingest-mycluster.kusto.windows.net"),
+            kusto_options,
+        )
+        .expect("Failed to create client");
+        QueuedIngestClient::new_with_client_options(kusto_client, options)
+    }
+
+    fn blob(i: usize) -> BlobDescriptor {
+        BlobDescriptor::new(
+            format!("https://account.blob.core.windows.net/data/{i}.csv"),
+            None,
+            None,
+        )
+    }
+
+    fn ingestion_properties() -> IngestionProperties {
+        IngestionProperties {
+            database_name: "db".to_string(),
+            table_name: "table".to_string(),
+            ..IngestionProperties::default()
+        }
+    }
+
+    /// The ingestion message in the body of a request that posted it to a queue.
+    fn queued_message(body: &str) -> serde_json::Value {
+        let text = body
+            .split("<MessageText>")
+            .nth(1)
+            .and_then(|rest| rest.split("</MessageText>").next())
+            .expect("Expected a queue message");
+        serde_json::from_slice(&base64::decode(text).unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn batches_fetch_their_resources_once() {
+        let kusto = Arc::new(KustoTransport::default());
+        let queues = Arc::new(RejectingQueues::default());
+        let client = queued_client(kusto_client_options(&kusto), queue_options(queues.clone()));
+
+        let blobs: Vec<BlobDescriptor> = (0..5).map(blob).collect();
+        let source_ids: Vec<Uuid> = blobs.iter().map(|b| b.source_id).collect();
+        let ingestion_properties = ingestion_properties();
+
+        let results = client
+            .ingest_from_blobs(blobs, &ingestion_properties)
+            .await
+            .expect("Failed to queue the batch");
+
+        assert_eq!(
+            results.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+            source_ids
+        );
+        assert!(results.iter().all(|(_, result)| result.is_err()));
+        assert_eq!(
+            *kusto.commands.lock().unwrap(),
+            vec![".get ingestion resources", ".get kusto identity token"]
+        );
+
+        let paths = queues.paths.lock().unwrap().clone();
+        assert_eq!(paths.len(), 5);
+        let mut per_queue = [
+            paths.iter().filter(|p| p.contains("queue1")).count(),
+            paths.iter().filter(|p| p.contains("queue2")).count(),
+        ];
+        per_queue.sort_unstable();
+        assert_eq!(per_queue, [2, 3]);
+    }
+
+    #[tokio::test]
+    async fn batches_post_up_to_the_maximum_of_concurrent_messages() {
+        let kusto = Arc::new(KustoTransport::default());
+        let queues = Arc::new(RejectingQueues::default());
+        let mut options = queue_options(queues.clone());
+        options.max_concurrent_enqueues = 3;
+        let client = queued_client(kusto_client_options(&kusto), options);
+
+        let blobs: Vec<BlobDescriptor> = (0..10).map(blob).collect();
+        let source_ids: Vec<Uuid> = blobs.iter().map(|b| b.source_id).collect();
+        let ingestion_properties = ingestion_properties();
+
+        let results = client
+            .ingest_from_blobs(blobs, &ingestion_properties)
+            .await
+            .expect("Failed to queue the batch");
+
+        assert_eq!(
+            results.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+            source_ids
+        );
+        assert_eq!(queues.paths.lock().unwrap().len(), 10);
+        assert_eq!(queues.peak_in_flight.load(Ordering::SeqCst), 3);
+        // Every message carries the authorization context that was fetched once for the batch
+        assert_eq!(kusto.commands.lock().unwrap().len(), 2);
+        for body in queues.bodies.lock().unwrap().iter() {
+            assert_eq!(
+                queued_message(body)["AdditionalProperties"]["authorizationContext"],
+                "token"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn requests_and_messages_carry_the_trace_context() {
+        const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let kusto = Arc::new(KustoTransport::default());
+        let queues = Arc::new(RejectingQueues::default());
+        let client = queued_client(
+            kusto_client_options(&kusto)
+                .with_trace_context_provider(Arc::new(|| TraceContext::parse(TRACEPARENT))),
+            queue_options(queues.clone()),
+        );
+
+        let result = client
+            .ingest_from_blob(blob(0), ingestion_properties())
+            .await;
+        assert!(result.is_err());
+
+        assert_eq!(
+            *kusto.traceparents.lock().unwrap(),
+            vec![Some(TRACEPARENT.to_string()); 2]
+        );
+        let bodies = queues.bodies.lock().unwrap().clone();
+        assert_eq!(bodies.len(), 1);
+        assert_eq!(
+            queued_message(&bodies[0])["AdditionalProperties"]["traceParent"],
+            TRACEPARENT
+        );
+    }
+
+    #[tokio::test]
+    async fn commands_are_sent_to_the_overridden_endpoint() {
+        const PROXY: &str = "https://ingest-proxy.contoso.com/v1/rest/mgmt";
+        let kusto = Arc::new(KustoTransport::default());
+        let queues = Arc::new(RejectingQueues::default());
+        let client = queued_client(
+            kusto_client_options(&kusto)
+                .with_endpoint_overrides("https://query-proxy.contoso.com/v2/rest/query", PROXY),
+            queue_options(queues.clone()),
+        );
+
+        let result = client
+            .ingest_from_blob(blob(0), ingestion_properties())
+            .await;
+        assert!(result.is_err());
+
+        assert_eq!(*kusto.urls.lock().unwrap(), vec![PROXY.to_string(); 2]);
+    }
+
+    #[tokio::test]
+    async fn invalid_properties_are_rejected_before_being_queued() {
+        let kusto = Arc::new(KustoTransport::default());
+        let queues = Arc::new(RejectingQueues::default());
+        let client = queued_client(kusto_client_options(&kusto), queue_options(queues.clone()));
+        let properties = IngestionProperties {
+            table_name: "table".to_string(),
+            ..IngestionProperties::default()
+        };
+
+        let result = client.ingest_from_blob(blob(0), properties.clone()).await;
+        assert!(matches!(
+            result,
+            Err(Error::IngestionPropertiesError(error))
+                if error.problems == vec![IngestionPropertiesProblem::EmptyDatabaseName]
+        ));
+
+        let result = client.ingest_from_blobs(vec![blob(1)], &properties).await;
+        assert!(matches!(result, Err(Error::IngestionPropertiesError(_))));
+
+        assert!(kusto.commands.lock().unwrap().is_empty());
+        assert!(queues.bodies.lock().unwrap().is_empty());
+    }
+
+    /// Accepts every message posted to a queue, as the queue service does, and records the urls they were posted to.
+    #[derive(Debug, Default)]
+    struct AcceptingQueues {
+        urls: Mutex<Vec<Url>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Policy for AcceptingQueues {
+        async fn send(
+            &self,
+            _ctx: &Context,
+            request: &mut Request,
+            _next: &[Arc<dyn Policy>],
+        ) -> PolicyResult {
+            self.urls.lock().unwrap().push(request.url().clone());
+
+            let mut headers = Headers::new();
+            headers.insert("x-ms-request-id", Uuid::new_v4().to_string());
+            headers.insert("x-ms-version", "2019-12-12");
+            headers.insert("date", "Mon, 01 May 2023 10:15:30 GMT");
+            headers.insert("server", "Windows-Azure-Queue/1.0 Microsoft-HTTPAPI/2.0");
+            Ok(Response::new(
+                StatusCode::Created,
+                headers,
+                Box::pin(BytesStream::new(
+                    "<?xml version=\"1.0\" encoding=\"utf-8\"?><QueueMessagesList><QueueMessage><MessageId>message-id</MessageId><InsertionTime>Mon, 01 May 2023 10:15:30 GMT</InsertionTime><ExpirationTime>Mon, 01 May 2023 11:15:30 GMT</ExpirationTime><PopReceipt>pop-receipt</PopReceipt><TimeNextVisible>Mon, 01 May 2023 10:16:00 GMT</TimeNextVisible></QueueMessage></QueueMessagesList>",
+                )),
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn queued_messages_are_identified_by_the_queue_service() {
+        let kusto = Arc::new(KustoTransport::default());
+        let queues = Arc::new(AcceptingQueues::default());
+        let client = queued_client(
+            kusto_client_options(&kusto),
+            QueuedIngestClientOptions {
+                message_ttl: Some(Duration::from_secs(3600)),
+                message_visibility_timeout: Some(Duration::from_secs(30)),
+                ..queue_options(queues.clone())
+            },
+        );
+        let source_id = Uuid::new_v4();
+
+        let result = client
+            .ingest_from_blob(
+                BlobDescriptor::new(
+                    "https://account.blob.core.windows.net/data/0.csv?sig=secret",
+                    None,
+                    Some(source_id),
+                ),
+                ingestion_properties(),
+            )
+            .await
+            .expect("Failed to queue the ingestion");
+
+        assert_eq!(result.source_id, source_id);
+        assert_eq!(
+            result.blob_uri,
+            "https://account.blob.core.windows.net/data/0.csv"
+        );
+        assert_eq!(
+            (result.database.as_str(), result.table.as_str()),
+            ("db", "table")
+        );
+        assert_eq!(result.message_id, "message-id");
+        assert_eq!(result.pop_receipt, "pop-receipt");
+
+        let urls = queues.urls.lock().unwrap().clone();
+        assert_eq!(urls.len(), 1);
+        assert_eq!(urls[0].path(), format!("/{}/messages", result.queue_name));
+        let query = urls[0].query().unwrap_or_default();
+        assert!(query.contains("messagettl=3600"), "{query}");
+        assert!(query.contains("visibilitytimeout=30"), "{query}");
+    }
+
+    /// Answers the first read of the failed ingestions queue with the status messages of another ingestion and of `source_id`,
+    /// and the other reads with no messages, and records the requests it receives, with their methods and query strings.
+    #[derive(Debug)]
+    struct StatusQueues {
+        source_id: Uuid,
+        failed_reads: AtomicUsize,
+        requests: Mutex<Vec<String>>,
+    }
+
+    impl StatusQueues {
+        fn new(source_id: Uuid) -> Self {
+            Self {
+                source_id,
+                failed_reads: AtomicUsize::new(0),
+                requests: Mutex::new(Vec::new()),
+            }
+        }
+
+        fn queue_message(id: &str, source_id: Uuid) -> String {
+            let status = serde_json::json!({
+                "OperationId": "ad0f9f8e-a0c2-4b1a-a7b8-0a0c8ad7a5e9",
+                "IngestionSourceId": source_id,
+                "Details": "Stream with id 'blob.csv' has a malformed Csv format",
+                "FailureStatus": "Permanent",
+                "ErrorCode": "BadRequest_InvalidCsvFormat"
+            });
+            format!(
+                "<QueueMessage><MessageId>{id}</MessageId><InsertionTime>Mon, 01 May 2023 10:15:30 GMT</InsertionTime><ExpirationTime>Mon, 08 May 2023 10:15:30 GMT</ExpirationTime><PopReceipt>receipt-{id}</PopReceipt><TimeNextVisible>Mon, 01 May 2023 10:16:00 GMT</TimeNextVisible><DequeueCount>1</DequeueCount><MessageText>{}</MessageText></QueueMessage>",
+                base64::encode(serde_json::to_vec(&status).unwrap())
+            )
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Policy for StatusQueues {
+        async fn send(
+            &self,
+            _ctx: &Context,
+            request: &mut Request,
+            _next: &[Arc<dyn Policy>],
+        ) -> PolicyResult {
+            let url = request.url().clone();
+            let method = match request.method() {
+                Method::Get => "GET",
+                Method::Delete => "DELETE",
+                _ => "OTHER",
+            };
+            self.requests.lock().unwrap().push(format!(
+                "{method} {}?{}",
+                url.path(),
+                url.query().unwrap_or_default()
+            ));
+
+            let mut headers = Headers::new();
+            headers.insert("x-ms-request-id", Uuid::new_v4().to_string());
+            headers.insert("x-ms-version", "2019-12-12");
+            headers.insert("date", "Mon, 01 May 2023 10:15:30 GMT");
+            headers.insert("server", "Windows-Azure-Queue/1.0 Microsoft-HTTPAPI/2.0");
+
+            let (status, body) = match (request.method(), url.path()) {
+                (Method::Delete, _) => (StatusCode::NoContent, String::new()),
+                (_, "/failed/messages") if self.failed_reads.fetch_add(1, Ordering::SeqCst) == 0 => (
+                    StatusCode::Ok,
+                    format!(
+                        "<?xml version=\"1.0\" encoding=\"utf-8\"?><QueueMessagesList>{}{}</QueueMessagesList>",
+                        Self::queue_message("other", Uuid::new_v4()),
+                        Self::queue_message("mine", self.source_id)
+                    ),
+                ),
+                _ => (
+                    StatusCode::Ok,
+                    "<?xml version=\"1.0\" encoding=\"utf-8\"?><QueueMessagesList />".to_string(),
+                ),
+            };
+            Ok(Response::new(
+                status,
+                headers,
+                Box::pin(BytesStream::new(body)),
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn status_messages_are_dequeued_and_deleted_once_found() {
+        let source_id = Uuid::new_v4();
+        let kusto = Arc::new(KustoTransport::default());
+        let queues = Arc::new(StatusQueues::new(source_id));
+        let client = queued_client(kusto_client_options(&kusto), queue_options(queues.clone()));
+
+        let status = client
+            .check_status(source_id)
+            .await
+            .expect("Failed to check the status");
+        match status {
+            IngestionStatus::Failed(reason) => {
+                assert_eq!(
+                    reason.error_code.as_deref(),
+                    Some("BadRequest_InvalidCsvFormat")
+                );
+            }
+            other => panic!("Expected a failed ingestion, got {other:?}"),
+        }
+
+        // The status was deleted, so it is not found again
+        let status = client
+            .check_status(source_id)
+            .await
+            .expect("Failed to check the status");
+        assert_eq!(status, IngestionStatus::Pending);
 
-        Ok(())
+        let requests = queues.requests.lock().unwrap().clone();
+        assert_eq!(requests.len(), 4);
+        assert!(requests[0].starts_with("GET /failed/messages?"));
+        assert!(requests[0].contains("numofmessages=32"));
+        assert!(requests[0].contains("visibilitytimeout=30"));
+        // Only the message of the ingestion is deleted, the other one becomes visible again
+        assert!(requests[1].starts_with("DELETE /failed/messages/mine?"));
+        assert!(requests[1].contains("popreceipt=receipt-mine"));
+        assert!(requests[2].starts_with("GET /failed/messages?"));
+        assert!(requests[3].starts_with("GET /succeeded/messages?"));
     }
 }
@@ -0,0 +1,264 @@
+//! Serialization of in-memory records, for [QueuedIngestClient::ingest_from_records](crate::queued_ingest::QueuedIngestClient::ingest_from_records).
+//!
+//! Records are serialized with [serde], so the fields of a record must serialize to values that Kusto can ingest into the columns of the table:
+//! - `datetime` columns: [KustoDateTime](azure_kusto_data::types::KustoDateTime), or an [OffsetDateTime](time::OffsetDateTime)
+//!   with `#[serde(with = "time::serde::rfc3339")]`
+//! - `timespan` columns: [KustoTimespan](azure_kusto_data::types::KustoTimespan)
+//! - `decimal` columns: strings, such as a decimal type serialized with its `Display` implementation, so that no precision is lost to floating point
+//! - `guid` columns: [Uuid](uuid::Uuid)
+//! - `dynamic` columns: [serde_json::Value], or any type that serializes to a JSON object or array
+//!
+//! Fields that are `None` are ingested as nulls.
+
+use std::borrow::Cow;
+use std::io::Write;
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::data_format::DataFormat;
+use crate::error::{Error, Result};
+
+/// The format that records are serialized to before they are ingested
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum RecordsFormat {
+    /// One JSON object per line, matched to the columns of the table by name, or with a JSON ingestion mapping
+    #[default]
+    MultiJson,
+    /// CSV with a header row, and a column for each of the named fields in this order, which is matched to the columns of the table by position.
+    /// Nested values, such as those of `dynamic` columns, are written as JSON
+    Csv {
+        /// Names of the fields of the records, in the order of the columns of the table
+        columns: Vec<String>,
+    },
+}
+
+impl RecordsFormat {
+    /// The data format that Kusto ingests the serialized records with
+    pub fn data_format(&self) -> DataFormat {
+        match self {
+            RecordsFormat::MultiJson => DataFormat::MultiJSON,
+            RecordsFormat::Csv { .. } => DataFormat::CSV,
+        }
+    }
+
+    /// The extension of the file that the records are uploaded as
+    pub(crate) fn file_extension(&self) -> &'static str {
+        match self {
+            RecordsFormat::MultiJson => "multijson",
+            RecordsFormat::Csv { .. } => "csv",
+        }
+    }
+}
+
+/// Serializes the records in the given format, without compressing them
+pub(crate) fn serialize_records<T: Serialize>(
+    records: &[T],
+    format: &RecordsFormat,
+) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    match format {
+        RecordsFormat::MultiJson => {
+            for record in records {
+                serde_json::to_writer(&mut data, record)?;
+                data.push(b'\n');
+            }
+        }
+        RecordsFormat::Csv { columns } => {
+            write_csv_line(&mut data, columns.iter().map(|c| Cow::Borrowed(c.as_str())));
+            for (index, record) in records.iter().enumerate() {
+                let record = match serde_json::to_value(record)? {
+                    Value::Object(fields) => fields,
+                    other => {
+                        return Err(Error::RecordsError(format!(
+                            "record {index} is not a struct or a map, but {other}"
+                        )))
+                    }
+                };
+                write_csv_line(
+                    &mut data,
+                    columns
+                        .iter()
+                        .map(|column| csv_field(record.get(column).unwrap_or(&Value::Null))),
+                );
+            }
+        }
+    }
+    Ok(data)
+}
+
+/// The text of a CSV field, before escaping
+fn csv_field(value: &Value) -> Cow<'_, str> {
+    match value {
+        Value::Null => Cow::Borrowed(""),
+        Value::String(s) => Cow::Borrowed(s),
+        other => Cow::Owned(other.to_string()),
+    }
+}
+
+/// Writes a line of CSV, quoting the fields that contain separators, quotes or line breaks
+fn write_csv_line<'a>(data: &mut Vec<u8>, fields: impl Iterator<Item = Cow<'a, str>>) {
+    for (index, field) in fields.enumerate() {
+        if index > 0 {
+            data.push(b',');
+        }
+        if field.contains(['"', ',', '\n', '\r']) {
+            data.push(b'"');
+            data.extend_from_slice(field.replace('"', "\"\"").as_bytes());
+            data.push(b'"');
+        } else {
+            data.extend_from_slice(field.as_bytes());
+        }
+    }
+    data.push(b'\n');
+}
+
+/// Compresses the serialized records with gzip, which Kusto decompresses when ingesting blobs with a `.gz` extension
+pub(crate) fn gzip(data: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(data)
+        .and_then(|_| encoder.finish())
+        .map_err(|e| Error::RecordsError(format!("failed to compress the records: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use azure_kusto_data::types::{KustoDateTime, KustoTimespan};
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+    use std::str::FromStr;
+
+    #[derive(Serialize)]
+    struct Event {
+        name: String,
+        count: i64,
+        time: KustoDateTime,
+        duration: KustoTimespan,
+        amount: String,
+        tags: Option<Value>,
+    }
+
+    fn events() -> Vec<Event> {
+        vec![
+            Event {
+                name: "started".to_string(),
+                count: 1,
+                time: KustoDateTime::from_str("2023-01-01T00:00:00Z").unwrap(),
+                duration: KustoTimespan::from_str("00:01:30").unwrap(),
+                amount: "2.00000000000001".to_string(),
+                tags: Some(serde_json::json!({"source": "test"})),
+            },
+            Event {
+                name: "said \"hi, there\"\nand left".to_string(),
+                count: -2,
+                time: KustoDateTime::from_str("2023-01-02 12:30:00").unwrap(),
+                duration: KustoTimespan::from_str("-1.00:00:00").unwrap(),
+                amount: "-5".to_string(),
+                tags: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn records_as_multijson() {
+        let data = serialize_records(&events(), &RecordsFormat::MultiJson)
+            .expect("Failed to serialize records");
+
+        assert_eq!(
+            String::from_utf8(data).unwrap(),
+            concat!(
+                r#"{"name":"started","count":1,"time":"2023-01-01T00:00:00.0000000Z","duration":"00:01:30.0000000","amount":"2.00000000000001","tags":{"source":"test"}}"#,
+                "\n",
+                r#"{"name":"said \"hi, there\"\nand left","count":-2,"time":"2023-01-02T12:30:00.0000000Z","duration":"-1.00:00:00.0000000","amount":"-5","tags":null}"#,
+                "\n"
+            )
+        );
+    }
+
+    #[test]
+    fn records_as_csv() {
+        let format = RecordsFormat::Csv {
+            columns: [
+                "time", "name", "count", "duration", "amount", "tags", "missing",
+            ]
+            .map(String::from)
+            .to_vec(),
+        };
+        let data = serialize_records(&events(), &format).expect("Failed to serialize records");
+
+        assert_eq!(
+            String::from_utf8(data).unwrap(),
+            concat!(
+                "time,name,count,duration,amount,tags,missing\n",
+                "2023-01-01T00:00:00.0000000Z,started,1,00:01:30.0000000,2.00000000000001,\"{\"\"source\"\":\"\"test\"\"}\",\n",
+                "2023-01-02T12:30:00.0000000Z,\"said \"\"hi, there\"\"\nand left\",-2,-1.00:00:00.0000000,-5,,\n",
+            )
+        );
+        assert_eq!(format.data_format(), DataFormat::CSV);
+    }
+
+    #[test]
+    fn csv_records_must_have_fields() {
+        let format = RecordsFormat::Csv {
+            columns: vec!["value".to_string()],
+        };
+
+        let error = serialize_records(&[1, 2], &format).expect_err("Expected numbers to fail");
+        assert!(matches!(error, Error::RecordsError(_)));
+        assert!(error.to_string().contains("record 0"));
+    }
+
+    #[test]
+    fn records_are_compressed() {
+        let records: Vec<Event> = (0..100).flat_map(|_| events()).collect();
+        let data = serialize_records(&records, &RecordsFormat::MultiJson)
+            .expect("Failed to serialize records");
+        let compressed = gzip(&data).expect("Failed to compress records");
+        assert!(compressed.len() < data.len());
+
+        let mut decompressed = Vec::new();
+        GzDecoder::new(compressed.as_slice())
+            .read_to_end(&mut decompressed)
+            .expect("Failed to decompress records");
+        assert_eq!(decompressed, data);
+    }
+
+    #[tokio::test]
+    async fn no_records_are_not_ingested() {
+        use crate::ingestion_properties::IngestionProperties;
+        use crate::ingestion_result::RecordsIngestionResult;
+        use crate::queued_ingest::QueuedIngestClient;
+        use azure_kusto_data::prelude::{ConnectionString, KustoClient, KustoClientOptions};
+
+        // The client has no way to reach the cluster, which it doesn't need to when there is nothing to ingest
+        let kusto_client = KustoClient::new(
+            ConnectionString::with_no_auth("https://ingest-cluster.kusto.windows.net"),
+            KustoClientOptions::default(),
+        )
+        .expect("Failed to create client");
+        let ingestion_properties = IngestionProperties {
+            database_name: "db".to_string(),
+            table_name: "events".to_string(),
+            ..IngestionProperties::default()
+        };
+
+        let result = QueuedIngestClient::new(kusto_client)
+            .ingest_from_records::<Event>(&[], ingestion_properties)
+            .await
+            .expect("Expected no records to be a no-op");
+
+        assert_eq!(
+            result,
+            RecordsIngestionResult {
+                ingestion: None,
+                record_count: 0,
+                serialized_size: 0,
+                compressed_size: 0,
+            }
+        );
+    }
+}
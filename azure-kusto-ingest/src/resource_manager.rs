@@ -4,6 +4,7 @@ pub mod authorization_context;
 pub mod cache;
 pub mod ingest_client_resources;
 pub mod resource_uri;
+pub(crate) mod retry;
 pub mod utils;
 
 use azure_kusto_data::prelude::KustoClient;
@@ -14,11 +15,12 @@ use crate::client_options::QueuedIngestClientOptions;
 
 use self::{
     authorization_context::{AuthorizationContext, KustoIdentityToken},
-    ingest_client_resources::IngestClientResources,
+    ingest_client_resources::{IngestClientResources, TempStorageContainer},
 };
 
 use rand::{seq::SliceRandom, thread_rng};
 
+/// The default period after which cached ingestion resources and authorization contexts are fetched again
 pub const RESOURCE_REFRESH_PERIOD: Duration = Duration::from_secs(60 * 60);
 
 #[derive(Debug, thiserror::Error)]
@@ -44,17 +46,23 @@ pub struct ResourceManager {
 impl ResourceManager {
     /// Creates a new ResourceManager from the given [KustoClient] and the [QueuedIngestClientOptions] as provided by the user
     pub fn new(client: KustoClient, client_options: QueuedIngestClientOptions) -> Self {
+        let refresh_period = client_options.resource_refresh_period;
+        let max_age = client_options.resource_max_age;
         Self {
             ingest_client_resources: Arc::new(IngestClientResources::new(
                 client.clone(),
                 client_options,
             )),
-            authorization_context: Arc::new(AuthorizationContext::new(client)),
+            authorization_context: Arc::new(AuthorizationContext::new(
+                client,
+                refresh_period,
+                max_age,
+            )),
         }
     }
 
     /// Returns the latest [QueueClient]s ready for posting ingestion messages to
-    async fn ingestion_queues(&self) -> Result<Vec<QueueClient>> {
+    pub(crate) async fn ingestion_queues(&self) -> Result<Vec<QueueClient>> {
         Ok(self.ingest_client_resources.get().await?.ingestion_queues)
     }
 
@@ -71,6 +79,41 @@ impl ResourceManager {
         Ok(selected_queue.clone())
     }
 
+    /// Returns a [TempStorageContainer] to stage blobs into.
+    /// This is a random selection from the list of temporary storage containers
+    pub async fn random_temp_storage(&self) -> Result<TempStorageContainer> {
+        let temp_storage_containers = self
+            .ingest_client_resources
+            .get()
+            .await?
+            .temp_storage_containers;
+
+        let mut rng = thread_rng();
+        let selected_container = temp_storage_containers
+            .choose(&mut rng)
+            .ok_or(ResourceManagerError::NoResourcesFound)?;
+
+        Ok(selected_container.clone())
+    }
+
+    /// Returns the [QueueClient]s of the queues that Kusto reports successful ingestions to
+    pub async fn successful_ingestions_queues(&self) -> Result<Vec<QueueClient>> {
+        Ok(self
+            .ingest_client_resources
+            .get()
+            .await?
+            .successful_ingestions_queues)
+    }
+
+    /// Returns the [QueueClient]s of the queues that Kusto reports failed ingestions to
+    pub async fn failed_ingestions_queues(&self) -> Result<Vec<QueueClient>> {
+        Ok(self
+            .ingest_client_resources
+            .get()
+            .await?
+            .failed_ingestions_queues)
+    }
+
     /// Returns the latest [KustoIdentityToken] to be added as an authorization context to ingestion messages
     pub async fn authorization_context(&self) -> Result<KustoIdentityToken> {
         self.authorization_context
@@ -78,4 +121,10 @@ impl ResourceManager {
             .await
             .map_err(ResourceManagerError::AuthorizationContextError)
     }
+
+    /// Discards the cached ingestion resources and authorization context, so that they are fetched from Kusto on next use
+    pub async fn force_refresh(&self) {
+        self.ingest_client_resources.force_refresh().await;
+        self.authorization_context.force_refresh().await;
+    }
 }
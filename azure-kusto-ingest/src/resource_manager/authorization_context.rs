@@ -1,9 +1,12 @@
+use std::future::IntoFuture;
+use std::time::Duration;
+
 use azure_kusto_data::prelude::KustoClient;
 use serde_json::Value;
 
 use super::cache::ThreadSafeCachedValue;
+use super::retry::retry_metadata_request;
 use super::utils::get_column_index;
-use super::RESOURCE_REFRESH_PERIOD;
 
 pub(crate) type KustoIdentityToken = String;
 
@@ -41,19 +44,23 @@ pub(crate) struct AuthorizationContext {
 }
 
 impl AuthorizationContext {
-    pub fn new(client: KustoClient) -> Self {
+    /// Creates a new AuthorizationContext, which caches the token for `refresh_period` before querying it again,
+    /// and uses it while it is queried again until it is older than `max_age`, which defaults to twice the refresh period
+    pub fn new(client: KustoClient, refresh_period: Duration, max_age: Option<Duration>) -> Self {
         Self {
             client,
-            token_cache: ThreadSafeCachedValue::new(RESOURCE_REFRESH_PERIOD),
+            token_cache: ThreadSafeCachedValue::new(refresh_period, max_age),
         }
     }
 
     /// Executes a KQL query to get the Kusto identity token from the management endpoint
-    async fn query_kusto_identity_token(&self) -> Result<KustoIdentityToken> {
-        let results = self
-            .client
-            .execute_command("NetDefaultDB", ".get kusto identity token", None)
-            .await?;
+    async fn query_kusto_identity_token(client: KustoClient) -> Result<KustoIdentityToken> {
+        let results = retry_metadata_request(|| {
+            client
+                .execute_command("NetDefaultDB", ".get kusto identity token", None)
+                .into_future()
+        })
+        .await?;
 
         // Check that there is only 1 table in the results returned by the query
         let table = match &results.tables[..] {
@@ -94,10 +101,120 @@ impl AuthorizationContext {
         Ok(token.to_string())
     }
 
-    /// Fetches the latest Kusto identity token, either retrieving from cache if valid, or by executing a KQL query
+    /// Fetches the latest Kusto identity token, either retrieving from cache if valid, or by executing a KQL query.
+    /// An expired token is returned while a new one is queried in the background, until it is too old to be used
     pub(crate) async fn get(&self) -> Result<KustoIdentityToken> {
         self.token_cache
-            .get(self.query_kusto_identity_token())
+            .get(|| Self::query_kusto_identity_token(self.client.clone()))
             .await
     }
+
+    /// Discards the cached token, so that the next call to [get](Self::get) queries Kusto for a new one
+    pub(crate) async fn force_refresh(&self) {
+        self.token_cache.invalidate().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use azure_core::headers::Headers;
+    use azure_core::{
+        BytesStream, ClientOptions, Context, Policy, PolicyResult, Request, Response, StatusCode,
+        TransportOptions,
+    };
+    use azure_kusto_data::prelude::{ConnectionString, KustoClientOptions};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Answers every `.get kusto identity token` command with a new token, and counts the commands it received.
+    #[derive(Debug, Default)]
+    struct TokenTransport {
+        requests: AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl Policy for TokenTransport {
+        async fn send(
+            &self,
+            _ctx: &Context,
+            _request: &mut Request,
+            _next: &[Arc<dyn Policy>],
+        ) -> PolicyResult {
+            let count = self.requests.fetch_add(1, Ordering::SeqCst) + 1;
+            let body = serde_json::json!({
+                "Tables": [{
+                    "TableName": "Table_0",
+                    "Columns": [{ "ColumnName": AUTHORIZATION_CONTEXT, "DataType": "String", "ColumnType": "string" }],
+                    "Rows": [[format!("token{count}")]]
+                }]
+            });
+            Ok(Response::new(
+                StatusCode::Ok,
+                Headers::new(),
+                Box::pin(BytesStream::new(serde_json::to_vec(&body).unwrap())),
+            ))
+        }
+    }
+
+    fn authorization_context(
+        refresh_period: Duration,
+    ) -> (AuthorizationContext, Arc<TokenTransport>) {
+        let transport = Arc::new(TokenTransport::default());
+        let client = KustoClient::new(
+            ConnectionString::with_no_auth("https://ingest-mycluster.kusto.windows.net"),
+            KustoClientOptions::from(
+                ClientOptions::default()
+                    .transport(TransportOptions::new_custom_policy(transport.clone())),
+            ),
+        )
+        .expect("Failed to create client");
+
+        (
+            AuthorizationContext::new(client, refresh_period, None),
+            transport,
+        )
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn token_is_queried_again_after_the_refresh_period() {
+        let (context, transport) = authorization_context(Duration::from_millis(10));
+
+        assert_eq!(context.get().await.unwrap(), "token1");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(context.get().await.unwrap(), "token2");
+        assert_eq!(transport.requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn expired_token_is_returned_while_a_single_refresh_runs() {
+        let (context, transport) = authorization_context(Duration::from_millis(100));
+
+        assert_eq!(context.get().await.unwrap(), "token1");
+        tokio::time::sleep(Duration::from_millis(120)).await;
+
+        let tokens = futures::future::join_all((0..10).map(|_| context.get())).await;
+        assert!(tokens
+            .iter()
+            .all(|token| token.as_deref().ok() == Some("token1")));
+
+        // Let the background refresh complete
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(context.get().await.unwrap(), "token2");
+        assert_eq!(transport.requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn token_is_cached_until_forcibly_refreshed() {
+        let (context, transport) = authorization_context(Duration::from_secs(3600));
+
+        assert_eq!(context.get().await.unwrap(), "token1");
+        assert_eq!(context.get().await.unwrap(), "token1");
+        assert_eq!(transport.requests.load(Ordering::SeqCst), 1);
+
+        context.force_refresh().await;
+
+        assert_eq!(context.get().await.unwrap(), "token2");
+        assert_eq!(transport.requests.load(Ordering::SeqCst), 2);
+    }
 }
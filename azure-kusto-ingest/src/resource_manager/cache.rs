@@ -1,11 +1,15 @@
 use std::{
     error::Error,
     future::Future,
-    sync::Arc,
-    time::{Duration, Instant},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
 };
 
 use async_lock::RwLock;
+use tokio::time::Instant;
 
 /// Wrapper around a value that allows for storing when the value was last updated,
 /// as well as the period after which it should be refreshed (i.e. expired)
@@ -14,6 +18,8 @@ pub struct Cached<T> {
     inner: T,
     last_updated: Instant,
     refresh_period: Duration,
+    /// How many times the value was updated, so that a refresh started before an update doesn't overwrite it
+    generation: u64,
 }
 
 impl<T> Cached<T> {
@@ -22,6 +28,7 @@ impl<T> Cached<T> {
             inner,
             last_updated: Instant::now(),
             refresh_period,
+            generation: 0,
         }
     }
 
@@ -33,39 +40,72 @@ impl<T> Cached<T> {
         self.last_updated.elapsed() >= self.refresh_period
     }
 
+    /// Whether the value was last updated more than `max_age` ago
+    pub fn is_older_than(&self, max_age: Duration) -> bool {
+        self.last_updated.elapsed() >= max_age
+    }
+
+    /// The number of times the value was updated
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
     pub fn update(&mut self, inner: T) {
         self.inner = inner;
         self.last_updated = Instant::now();
+        self.generation += 1;
     }
 }
 
+/// A value that is fetched when first needed, and refreshed once its refresh period expired.
+///
+/// An expired value keeps being returned while it is refreshed in the background, so that callers don't wait for the refresh,
+/// until it is older than its maximum age, of twice its refresh period unless set. Older values are refreshed before being returned, failing if they can't be.
+/// Values are only refreshed in the background on a tokio runtime, and are otherwise refreshed before being returned once expired.
+/// A background refresh that completes after the value was invalidated or updated is discarded.
 #[derive(Debug, Clone)]
 pub struct ThreadSafeCachedValue<T>
 where
     T: Clone,
 {
     cache: Arc<RwLock<Cached<Option<T>>>>,
+    max_age: Duration,
+    /// Whether a background refresh is running, so that only one of them is started at a time
+    refreshing: Arc<AtomicBool>,
 }
 
-impl<T: Clone> ThreadSafeCachedValue<T> {
-    pub fn new(refresh_period: Duration) -> Self {
+impl<T: Clone + Send + Sync + 'static> ThreadSafeCachedValue<T> {
+    /// Creates an empty cache, whose values expire after `refresh_period`, and are no longer returned once older than `max_age`,
+    /// which defaults to twice the refresh period
+    pub fn new(refresh_period: Duration, max_age: Option<Duration>) -> Self {
         Self {
             cache: Arc::new(RwLock::new(Cached::new(None, refresh_period))),
+            max_age: max_age.unwrap_or_else(|| refresh_period.saturating_mul(2)),
+            refreshing: Arc::new(AtomicBool::new(false)),
         }
     }
 
-    /// Fetches the latest value, either retrieving from cache if valid, or by executing the callback
-    pub async fn get<F, E: Error>(&self, callback: F) -> Result<T, E>
+    /// Fetches the latest value, either retrieving from cache if valid, or by executing the future returned by `fetch`.
+    /// Expired values that are not too old are returned right away, while `fetch` refreshes them in the background
+    pub async fn get<F, Fut, E>(&self, fetch: F) -> Result<T, E>
     where
-        F: Future<Output = Result<T, E>>,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>> + Send + 'static,
+        E: Error + Send + 'static,
     {
         // First, try to get a value from the cache by obtaining a read lock
         {
             let cache = self.cache.read().await;
-            if !cache.is_expired() {
-                if let Some(cached_value) = cache.get() {
+            if let Some(cached_value) = cache.get() {
+                if !cache.is_expired() {
                     return Ok(cached_value.clone());
                 }
+                if !cache.is_older_than(self.max_age) {
+                    if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+                        self.refresh_in_background(&runtime, cache.generation(), fetch);
+                        return Ok(cached_value.clone());
+                    }
+                }
             }
         }
 
@@ -80,12 +120,54 @@ impl<T: Clone> ThreadSafeCachedValue<T> {
             }
         }
 
-        // Fetch new value by executing the callback, update the cache, and return the value
-        let fetched_value = callback.await?;
+        // Fetch new value by executing the callback, update the cache, and return the value.
+        // If the callback fails, the cache is left as it was, so the next call tries again
+        let fetched_value = fetch().await?;
         cache.update(Some(fetched_value.clone()));
 
         Ok(fetched_value)
     }
+
+    /// Starts refreshing the value of the `generation` on the runtime, unless a refresh is already running.
+    /// If the refresh fails, or the value was updated since, the value is left as it was, so that the next call starts another one
+    fn refresh_in_background<F, Fut, E>(
+        &self,
+        runtime: &tokio::runtime::Handle,
+        generation: u64,
+        fetch: F,
+    ) where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>> + Send + 'static,
+        E: Error + Send + 'static,
+    {
+        if self.refreshing.swap(true, Ordering::AcqRel) {
+            return;
+        }
+
+        let fetched = fetch();
+        let cache = self.cache.clone();
+        let refreshing = self.refreshing.clone();
+        runtime.spawn(async move {
+            match fetched.await {
+                Ok(value) => {
+                    let mut cache = cache.write().await;
+                    if cache.generation() == generation {
+                        cache.update(Some(value));
+                    }
+                }
+                Err(_error) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(error = %_error, "Failed to refresh a cached value in the background");
+                }
+            }
+            refreshing.store(false, Ordering::Release);
+        });
+    }
+
+    /// Discards the cached value, so that the next call to [get](Self::get) executes its callback
+    pub async fn invalidate(&self) {
+        self.cache.write().await.update(None);
+    }
 }
 
 #[cfg(test)]
@@ -145,39 +227,146 @@ mod thread_safe_cached_value_tests {
             }
         }
 
-        async fn get_new_token(&self) -> Result<usize, Error> {
+        async fn get_new_token(self: Arc<Self>) -> Result<usize, Error> {
             // Include an incrementing counter in the token to track how many times the token has been refreshed
             let mut call_count = self.get_token_call_count.lock().unwrap();
             *call_count += 1;
-            Ok(call_count.clone())
+            Ok(*call_count)
+        }
+
+        /// Gets a new token after a delay, as a fetch from Kusto would take
+        async fn get_new_token_slowly(self: Arc<Self>, delay: Duration) -> Result<usize, Error> {
+            tokio::time::sleep(delay).await;
+            self.get_new_token().await
+        }
+
+        fn call_count(&self) -> usize {
+            *self.get_token_call_count.lock().unwrap()
         }
     }
 
-    #[tokio::test]
+    #[tokio::test(start_paused = true)]
     async fn returns_same_value_if_unexpired() -> Result<(), Error> {
-        let cache = ThreadSafeCachedValue::new(Duration::from_secs(300));
-        let mock_token = MockToken::new();
+        let cache = ThreadSafeCachedValue::new(Duration::from_secs(300), None);
+        let mock_token = Arc::new(MockToken::new());
 
-        let token1 = cache.get(mock_token.get_new_token()).await?;
-        let token2 = cache.get(mock_token.get_new_token()).await?;
+        let token1 = cache.get(|| mock_token.clone().get_new_token()).await?;
+        let token2 = cache.get(|| mock_token.clone().get_new_token()).await?;
 
         assert_eq!(token1, 1);
         assert_eq!(token2, 1);
         Ok(())
     }
 
-    #[tokio::test]
+    #[tokio::test(start_paused = true)]
     async fn returns_new_value_if_expired() -> Result<(), Error> {
-        let cache = ThreadSafeCachedValue::new(Duration::from_millis(1));
-        let mock_token = MockToken::new();
+        let cache = ThreadSafeCachedValue::new(Duration::from_millis(1), None);
+        let mock_token = Arc::new(MockToken::new());
 
-        let token1 = cache.get(mock_token.get_new_token()).await?;
+        let token1 = cache.get(|| mock_token.clone().get_new_token()).await?;
         // Sleep to ensure the token expires
         tokio::time::sleep(Duration::from_secs(1)).await;
-        let token2 = cache.get(mock_token.get_new_token()).await?;
+        let token2 = cache.get(|| mock_token.clone().get_new_token()).await?;
 
         assert_eq!(token1, 1);
         assert_eq!(token2, 2);
         Ok(())
     }
+
+    #[tokio::test(start_paused = true)]
+    async fn returns_new_value_if_invalidated() -> Result<(), Error> {
+        let cache = ThreadSafeCachedValue::new(Duration::from_secs(300), None);
+        let mock_token = Arc::new(MockToken::new());
+
+        let token1 = cache.get(|| mock_token.clone().get_new_token()).await?;
+        cache.invalidate().await;
+        let token2 = cache.get(|| mock_token.clone().get_new_token()).await?;
+
+        assert_eq!(token1, 1);
+        assert_eq!(token2, 2);
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn expired_values_are_returned_while_refreshed_once() -> Result<(), Error> {
+        let cache = ThreadSafeCachedValue::new(Duration::from_millis(200), None);
+        let mock_token = Arc::new(MockToken::new());
+        let slow_fetch = || {
+            mock_token
+                .clone()
+                .get_new_token_slowly(Duration::from_millis(100))
+        };
+
+        assert_eq!(cache.get(slow_fetch).await?, 1);
+        tokio::time::sleep(Duration::from_millis(250)).await;
+
+        // Every caller gets the expired value without waiting for the refresh, which only one of them starts
+        let started = Instant::now();
+        let tokens = futures::future::join_all((0..10).map(|_| cache.get(slow_fetch))).await;
+        assert!(started.elapsed() < Duration::from_millis(100));
+        assert!(tokens.into_iter().all(|token| token == Ok(1)));
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(cache.get(slow_fetch).await?, 2);
+        assert_eq!(mock_token.call_count(), 2);
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn values_past_their_maximum_age_are_refreshed_first() {
+        let cache = ThreadSafeCachedValue::new(Duration::from_millis(20), None);
+        let mock_token = Arc::new(MockToken::new());
+
+        assert_eq!(
+            cache.get(|| mock_token.clone().get_new_token()).await,
+            Ok(1)
+        );
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let failed = cache.get(|| async { Err::<usize, _>(Error) }).await;
+        assert_eq!(failed, Err(Error));
+        assert_eq!(
+            cache.get(|| mock_token.clone().get_new_token()).await,
+            Ok(2)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn maximum_age_can_be_set() -> Result<(), Error> {
+        let cache =
+            ThreadSafeCachedValue::new(Duration::from_millis(20), Some(Duration::from_secs(1)));
+        let mock_token = Arc::new(MockToken::new());
+
+        assert_eq!(cache.get(|| mock_token.clone().get_new_token()).await?, 1);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // The value is older than twice its refresh period, but not its maximum age, so it is returned while the refresh fails
+        let failed = cache.get(|| async { Err::<usize, _>(Error) }).await;
+        assert_eq!(failed, Ok(1));
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn background_refreshes_do_not_overwrite_invalidated_values() -> Result<(), Error> {
+        let cache = ThreadSafeCachedValue::new(Duration::from_millis(200), None);
+        let mock_token = Arc::new(MockToken::new());
+        let slow_fetch = || {
+            mock_token
+                .clone()
+                .get_new_token_slowly(Duration::from_millis(100))
+        };
+
+        assert_eq!(cache.get(|| mock_token.clone().get_new_token()).await?, 1);
+        tokio::time::sleep(Duration::from_millis(250)).await;
+
+        // Starts a background refresh, which completes after the value was invalidated and fetched again
+        assert_eq!(cache.get(slow_fetch).await?, 1);
+        cache.invalidate().await;
+        assert_eq!(cache.get(|| mock_token.clone().get_new_token()).await?, 2);
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(mock_token.call_count(), 3);
+        assert_eq!(cache.get(|| mock_token.clone().get_new_token()).await?, 2);
+        Ok(())
+    }
 }
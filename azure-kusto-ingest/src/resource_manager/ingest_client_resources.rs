@@ -3,8 +3,10 @@ use crate::client_options::QueuedIngestClientOptions;
 use super::{
     cache::ThreadSafeCachedValue,
     resource_uri::{ClientFromResourceUri, ResourceUri},
-    utils, RESOURCE_REFRESH_PERIOD,
+    retry::retry_metadata_request,
+    utils,
 };
+use std::future::IntoFuture;
 
 use azure_core::ClientOptions;
 use azure_kusto_data::{models::TableV1, prelude::KustoClient};
@@ -42,7 +44,11 @@ fn get_column_index(table: &TableV1, column_name: &str) -> Result<usize> {
 }
 
 /// Helper to get a resource URI from a table, erroring if there are no resources of the given name
-fn get_resource_by_name(table: &TableV1, resource_name: String) -> Result<Vec<ResourceUri>> {
+fn get_resource_by_name(
+    table: &TableV1,
+    resource_name: String,
+    allow_insecure: bool,
+) -> Result<Vec<ResourceUri>> {
     let storage_root_index = get_column_index(table, "StorageRoot")?;
     let resource_type_name_index = get_column_index(table, "ResourceTypeName")?;
 
@@ -54,7 +60,7 @@ fn get_resource_by_name(table: &TableV1, resource_name: String) -> Result<Vec<Re
             let x = r[storage_root_index].as_str().ok_or(
                 IngestionResourceError::ParseAsStringError(r[storage_root_index].clone()),
             )?;
-            ResourceUri::try_from(x).map_err(IngestionResourceError::ResourceUriError)
+            ResourceUri::parse(x, allow_insecure).map_err(IngestionResourceError::ResourceUriError)
         })
         .collect();
 
@@ -65,6 +71,18 @@ fn get_resource_by_name(table: &TableV1, resource_name: String) -> Result<Vec<Re
     resource_uris.into_iter().collect()
 }
 
+/// Helper to get the resource URIs of an optional resource, which the cluster may not return
+fn get_optional_resource_by_name(
+    table: &TableV1,
+    resource_name: String,
+    allow_insecure: bool,
+) -> Result<Vec<ResourceUri>> {
+    match get_resource_by_name(table, resource_name, allow_insecure) {
+        Err(IngestionResourceError::NoResourcesFound(_)) => Ok(Vec::new()),
+        result => result,
+    }
+}
+
 /// Helper to turn a vector of resource URIs into a vector of Azure clients of type T with the provided [ClientOptions]
 fn create_clients_vec<T>(resource_uris: &[ResourceUri], client_options: &ClientOptions) -> Vec<T>
 where
@@ -76,11 +94,35 @@ where
         .collect()
 }
 
+/// A container of the temporary storage of Kusto, that blobs are staged into before being ingested
+#[derive(Debug, Clone)]
+pub struct TempStorageContainer {
+    pub client: ContainerClient,
+    resource_uri: ResourceUri,
+}
+
+impl TempStorageContainer {
+    /// The uri that Kusto reads the blob from, including the SAS token of the container
+    pub fn blob_uri(&self, blob_name: &str) -> String {
+        format!(
+            "{}/{}/{}?{}",
+            self.resource_uri.service_uri,
+            self.resource_uri.object_name,
+            blob_name,
+            self.resource_uri.sas_query
+        )
+    }
+}
+
 /// Storage of the clients required for ingestion
 #[derive(Debug, Clone)]
 pub struct InnerIngestClientResources {
     pub ingestion_queues: Vec<QueueClient>,
-    pub temp_storage_containers: Vec<ContainerClient>,
+    pub temp_storage_containers: Vec<TempStorageContainer>,
+    /// Queues that Kusto reports successful ingestions to, when requested by the [ReportLevel](crate::ingestion_status::ReportLevel)
+    pub successful_ingestions_queues: Vec<QueueClient>,
+    /// Queues that Kusto reports failed ingestions to
+    pub failed_ingestions_queues: Vec<QueueClient>,
 }
 
 impl TryFrom<(&TableV1, &QueuedIngestClientOptions)> for InnerIngestClientResources {
@@ -90,18 +132,49 @@ impl TryFrom<(&TableV1, &QueuedIngestClientOptions)> for InnerIngestClientResour
     fn try_from(
         (table, client_options): (&TableV1, &QueuedIngestClientOptions),
     ) -> std::result::Result<Self, Self::Error> {
-        let secured_ready_for_aggregation_queues =
-            get_resource_by_name(table, "SecuredReadyForAggregationQueue".to_string())?;
-        let temp_storage = get_resource_by_name(table, "TempStorage".to_string())?;
+        let secured_ready_for_aggregation_queues = get_resource_by_name(
+            table,
+            "SecuredReadyForAggregationQueue".to_string(),
+            client_options.allow_insecure_endpoint,
+        )?;
+        let temp_storage = get_resource_by_name(
+            table,
+            "TempStorage".to_string(),
+            client_options.allow_insecure_endpoint,
+        )?;
+        let successful_ingestions_queues = get_optional_resource_by_name(
+            table,
+            "SuccessfulIngestionsQueue".to_string(),
+            client_options.allow_insecure_endpoint,
+        )?;
+        let failed_ingestions_queues = get_optional_resource_by_name(
+            table,
+            "FailedIngestionsQueue".to_string(),
+            client_options.allow_insecure_endpoint,
+        )?;
 
         Ok(Self {
             ingestion_queues: create_clients_vec(
                 &secured_ready_for_aggregation_queues,
                 &client_options.queue_service_options,
             ),
-            temp_storage_containers: create_clients_vec(
-                &temp_storage,
-                &client_options.blob_service_options,
+            temp_storage_containers: temp_storage
+                .into_iter()
+                .map(|resource_uri| TempStorageContainer {
+                    client: ContainerClient::create_client(
+                        resource_uri.clone(),
+                        client_options.blob_service_options.clone(),
+                    ),
+                    resource_uri,
+                })
+                .collect(),
+            successful_ingestions_queues: create_clients_vec(
+                &successful_ingestions_queues,
+                &client_options.queue_service_options,
+            ),
+            failed_ingestions_queues: create_clients_vec(
+                &failed_ingestions_queues,
+                &client_options.queue_service_options,
             ),
         })
     }
@@ -117,33 +190,193 @@ pub struct IngestClientResources {
 }
 
 impl IngestClientResources {
+    /// Creates a new IngestClientResources, which caches the resources for the [resource_refresh_period](QueuedIngestClientOptions::resource_refresh_period) of the options,
+    /// and uses them while they are refreshed until they are older than its [resource_max_age](QueuedIngestClientOptions::resource_max_age)
     pub fn new(client: KustoClient, client_options: QueuedIngestClientOptions) -> Self {
         Self {
             client,
-            resources_cache: ThreadSafeCachedValue::new(RESOURCE_REFRESH_PERIOD),
+            resources_cache: ThreadSafeCachedValue::new(
+                client_options.resource_refresh_period,
+                client_options.resource_max_age,
+            ),
             client_options,
         }
     }
 
     /// Executes a KQL management query that retrieves resource URIs for the various Azure resources used for ingestion
-    async fn query_ingestion_resources(&self) -> Result<InnerIngestClientResources> {
-        let results = self
-            .client
-            .execute_command("NetDefaultDB", ".get ingestion resources", None)
-            .await?;
+    async fn query_ingestion_resources(
+        client: KustoClient,
+        client_options: QueuedIngestClientOptions,
+    ) -> Result<InnerIngestClientResources> {
+        let results = retry_metadata_request(|| {
+            client
+                .execute_command("NetDefaultDB", ".get ingestion resources", None)
+                .into_future()
+        })
+        .await?;
 
         let new_resources = results
             .tables
             .first()
             .ok_or(IngestionResourceError::NoTablesFound)?;
 
-        InnerIngestClientResources::try_from((new_resources, &self.client_options))
+        InnerIngestClientResources::try_from((new_resources, &client_options))
     }
 
-    /// Gets the latest resources either from cache, or fetching from Kusto and updating the cached resources
+    /// Gets the latest resources either from cache, or fetching from Kusto and updating the cached resources.
+    /// Expired resources are returned while they are fetched again in the background, until they are too old to be used
     pub async fn get(&self) -> Result<InnerIngestClientResources> {
         self.resources_cache
-            .get(self.query_ingestion_resources())
+            .get(|| {
+                Self::query_ingestion_resources(self.client.clone(), self.client_options.clone())
+            })
             .await
     }
+
+    /// Discards the cached resources, so that the next call to [get](Self::get) fetches them from Kusto
+    pub async fn force_refresh(&self) {
+        self.resources_cache.invalidate().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use azure_core::headers::Headers;
+    use azure_core::{
+        BytesStream, Context, Policy, PolicyResult, Request, Response, RetryOptions, StatusCode,
+        TransportOptions,
+    };
+    use azure_kusto_data::prelude::{ConnectionString, KustoClientOptions};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    const COLUMNS: &str = r#"[
+        { "ColumnName": "ResourceTypeName", "DataType": "String", "ColumnType": "string" },
+        { "ColumnName": "StorageRoot", "DataType": "String", "ColumnType": "string" }
+    ]"#;
+
+    fn table(rows: &str) -> TableV1 {
+        serde_json::from_str(&format!(
+            r#"{{ "TableName": "Table_0", "Columns": {COLUMNS}, "Rows": {rows} }}"#
+        ))
+        .expect("Failed to parse table")
+    }
+
+    #[test]
+    fn resources_are_parsed_from_array_and_object_rows() {
+        let positional = table(
+            r#"[
+                ["SecuredReadyForAggregationQueue", "https://account.queue.core.windows.net/queue1?sas=token"],
+                ["TempStorage", "https://account.blob.core.windows.net/container1?sas=token"]
+            ]"#,
+        );
+        let keyed = table(
+            r#"[
+                { "StorageRoot": "https://account.queue.core.windows.net/queue1?sas=token", "ResourceTypeName": "SecuredReadyForAggregationQueue" },
+                { "ResourceTypeName": "TempStorage", "StorageRoot": "https://account.blob.core.windows.net/container1?sas=token" }
+            ]"#,
+        );
+
+        for resource_name in ["SecuredReadyForAggregationQueue", "TempStorage"] {
+            let from_positional =
+                get_resource_by_name(&positional, resource_name.to_string(), false)
+                    .expect("Failed to get resources from array rows");
+            let from_keyed = get_resource_by_name(&keyed, resource_name.to_string(), false)
+                .expect("Failed to get resources from object rows");
+
+            assert_eq!(from_positional.len(), 1);
+            assert_eq!(from_keyed.len(), 1);
+            assert_eq!(from_positional[0].service_uri, from_keyed[0].service_uri);
+            assert_eq!(from_positional[0].object_name, from_keyed[0].object_name);
+        }
+    }
+
+    #[test]
+    fn status_queues_are_optional() {
+        let options = QueuedIngestClientOptions::default();
+        let without_status_queues = table(
+            r#"[
+                ["SecuredReadyForAggregationQueue", "https://account.queue.core.windows.net/queue1?sas=token"],
+                ["TempStorage", "https://account.blob.core.windows.net/container1?sas=token"]
+            ]"#,
+        );
+        let with_status_queues = table(
+            r#"[
+                ["SecuredReadyForAggregationQueue", "https://account.queue.core.windows.net/queue1?sas=token"],
+                ["TempStorage", "https://account.blob.core.windows.net/container1?sas=token"],
+                ["SuccessfulIngestionsQueue", "https://account.queue.core.windows.net/successfulingestions?sas=token"],
+                ["FailedIngestionsQueue", "https://account.queue.core.windows.net/failedingestions?sas=token"],
+                ["FailedIngestionsQueue", "https://account2.queue.core.windows.net/failedingestions?sas=token"]
+            ]"#,
+        );
+
+        let resources =
+            InnerIngestClientResources::try_from((&without_status_queues, &options)).unwrap();
+        assert!(resources.successful_ingestions_queues.is_empty());
+        assert!(resources.failed_ingestions_queues.is_empty());
+
+        let resources =
+            InnerIngestClientResources::try_from((&with_status_queues, &options)).unwrap();
+        assert_eq!(resources.successful_ingestions_queues.len(), 1);
+        assert_eq!(resources.failed_ingestions_queues.len(), 2);
+    }
+
+    /// Fails the first request with a transient error, and answers the ones after it with ingestion resources.
+    #[derive(Debug, Default)]
+    struct FlakyTransport {
+        requests: AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl Policy for FlakyTransport {
+        async fn send(
+            &self,
+            _ctx: &Context,
+            _request: &mut Request,
+            _next: &[Arc<dyn Policy>],
+        ) -> PolicyResult {
+            if self.requests.fetch_add(1, Ordering::SeqCst) == 0 {
+                return Ok(Response::new(
+                    StatusCode::ServiceUnavailable,
+                    Headers::new(),
+                    Box::pin(BytesStream::new("Service Unavailable")),
+                ));
+            }
+
+            let body = format!(
+                r#"{{ "Tables": [{{ "TableName": "Table_0", "Columns": {COLUMNS}, "Rows": [
+                    ["SecuredReadyForAggregationQueue", "https://account.queue.core.windows.net/queue1?sas=token"],
+                    ["TempStorage", "https://account.blob.core.windows.net/container1?sas=token"]
+                ] }}] }}"#
+            );
+            Ok(Response::new(
+                StatusCode::Ok,
+                Headers::new(),
+                Box::pin(BytesStream::new(body)),
+            ))
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn resources_are_fetched_again_after_a_transient_failure() {
+        let transport = Arc::new(FlakyTransport::default());
+        let client = KustoClient::new(
+            ConnectionString::with_no_auth("https://ingest-mycluster.kusto.windows.net"),
+            KustoClientOptions::from(
+                ClientOptions::default()
+                    // Leave the retries to the resources, rather than to the pipeline
+                    .retry(RetryOptions::none())
+                    .transport(TransportOptions::new_custom_policy(transport.clone())),
+            ),
+        )
+        .expect("Failed to create client");
+        let resources = IngestClientResources::new(client, QueuedIngestClientOptions::default());
+
+        let inner = resources.get().await.expect("Failed to get resources");
+
+        assert_eq!(transport.requests.load(Ordering::SeqCst), 2);
+        assert_eq!(inner.ingestion_queues.len(), 1);
+        assert_eq!(inner.temp_storage_containers.len(), 1);
+    }
 }
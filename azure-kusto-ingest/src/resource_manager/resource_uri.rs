@@ -12,6 +12,9 @@ pub enum ResourceUriError {
     #[error("URI host must be a domain")]
     InvalidHost,
 
+    #[error("URI scheme 'http' is only allowed for loopback hosts, was '{0}'")]
+    InsecureRemoteHost(String),
+
     #[error("Object name is missing in the URI")]
     MissingObjectName,
 
@@ -35,51 +38,86 @@ pub(crate) struct ResourceUri {
     pub(crate) object_name: String,
     pub(crate) account_name: String,
     pub(crate) sas_token: StorageCredentials,
+    /// The SAS token as found in the query of the URI, to pass on to Kusto along with URIs of the resource
+    pub(crate) sas_query: String,
 }
 
-impl TryFrom<&str> for ResourceUri {
-    type Error = ResourceUriError;
+/// Whether the host is the local machine, where storage emulators such as Azurite are served.
+fn is_loopback(host: &url::Host<&str>) -> bool {
+    match host {
+        url::Host::Domain(domain) => domain.eq_ignore_ascii_case("localhost"),
+        url::Host::Ipv4(ip) => ip.is_loopback(),
+        url::Host::Ipv6(ip) => ip.is_loopback(),
+    }
+}
 
-    fn try_from(uri: &str) -> Result<Self, Self::Error> {
+impl ResourceUri {
+    /// Parses a resource URI, which must use https and be hosted on a domain.
+    /// When `allow_insecure` is set, loopback hosts may use http as well, and are parsed as the path-style URIs of local storage emulators such as Azurite,
+    /// where the account name is the first path segment (e.g. `http://127.0.0.1:10000/devstoreaccount1/container?sas=token`).
+    /// Remote hosts must use https regardless.
+    pub(crate) fn parse(uri: &str, allow_insecure: bool) -> Result<Self, ResourceUriError> {
         let parsed_uri = Url::parse(uri)?;
+        let host = parsed_uri.host().ok_or(ResourceUriError::InvalidHost)?;
+        let local_emulator = allow_insecure && is_loopback(&host);
 
         match parsed_uri.scheme() {
             "https" => {}
+            "http" if local_emulator => {}
+            "http" if allow_insecure => {
+                return Err(ResourceUriError::InsecureRemoteHost(host.to_string()))
+            }
             other_scheme => return Err(ResourceUriError::InvalidScheme(other_scheme.to_string())),
         };
 
-        let host_string = match parsed_uri.host() {
-            Some(url::Host::Domain(host_string)) => host_string,
-            _ => return Err(ResourceUriError::InvalidHost),
-        };
-
-        let service_uri = String::from("https://") + host_string;
-
-        // WIBNI: better parsing that this conforms to a storage resource URI,
-        // perhaps then ResourceUri could take a type like ResourceUri<Queue> or ResourceUri<Container>
-        let (account_name, _service_endpoint) = host_string
-            .split_once('.')
-            .ok_or(ResourceUriError::MissingAccountName)?;
+        let mut path_segments = parsed_uri
+            .path_segments()
+            .ok_or(ResourceUriError::MissingObjectName)?;
 
-        let object_name = match parsed_uri.path_segments() {
-            Some(mut path_segments) => {
-                let object_name = match path_segments.next() {
-                    Some(object_name) if !object_name.is_empty() => object_name,
-                    _ => return Err(ResourceUriError::MissingObjectName),
+        let (service_uri, account_name) = match host {
+            _ if local_emulator => {
+                // Emulators serve all accounts from the same host and port, and address the account by path.
+                let account_name = match path_segments.next() {
+                    Some(account_name) if !account_name.is_empty() => account_name,
+                    _ => return Err(ResourceUriError::MissingAccountName),
                 };
-                // Ensure there is only one path segment (i.e. the object name)
-                if path_segments.next().is_some() {
-                    return Err(ResourceUriError::MissingObjectName);
-                };
-                object_name
+                (
+                    format!(
+                        "{}/{}",
+                        parsed_uri.origin().ascii_serialization(),
+                        account_name
+                    ),
+                    account_name,
+                )
+            }
+            url::Host::Domain(host_string) => {
+                // WIBNI: better parsing that this conforms to a storage resource URI,
+                // perhaps then ResourceUri could take a type like ResourceUri<Queue> or ResourceUri<Container>
+                let (account_name, _service_endpoint) = host_string
+                    .split_once('.')
+                    .ok_or(ResourceUriError::MissingAccountName)?;
+                (
+                    format!("{}://{}", parsed_uri.scheme(), host_string),
+                    account_name,
+                )
             }
-            None => return Err(ResourceUriError::MissingObjectName),
+            _ => return Err(ResourceUriError::InvalidHost),
+        };
+
+        let object_name = match path_segments.next() {
+            Some(object_name) if !object_name.is_empty() => object_name,
+            _ => return Err(ResourceUriError::MissingObjectName),
+        };
+        // Ensure there is only one path segment (i.e. the object name)
+        if path_segments.next().is_some() {
+            return Err(ResourceUriError::MissingObjectName);
         };
 
         let sas_token = parsed_uri
             .query()
             .ok_or(ResourceUriError::MissingSasToken)?;
 
+        let sas_query = sas_token.to_string();
         let sas_token = StorageCredentials::sas_token(sas_token)?;
 
         Ok(Self {
@@ -87,10 +125,19 @@ impl TryFrom<&str> for ResourceUri {
             object_name: object_name.to_string(),
             account_name: account_name.to_string(),
             sas_token,
+            sas_query,
         })
     }
 }
 
+impl TryFrom<&str> for ResourceUri {
+    type Error = ResourceUriError;
+
+    fn try_from(uri: &str) -> Result<Self, Self::Error> {
+        Self::parse(uri, false)
+    }
+}
+
 /// Trait to be used to create an Azure client from a resource URI with configurability of ClientOptions
 pub(crate) trait ClientFromResourceUri {
     fn create_client(resource_uri: ResourceUri, client_options: ClientOptions) -> Self;
@@ -170,6 +217,63 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn insecure_uris_are_only_allowed_for_loopback_hosts() {
+        for uri in [
+            "http://storageaccountname.blob.core.windows.com/containerobjectname?sas=token",
+            "http://10.0.0.4:10000/devstoreaccount1/containerobjectname?sas=token",
+        ] {
+            assert!(matches!(
+                ResourceUri::parse(uri, true),
+                Err(ResourceUriError::InsecureRemoteHost(_))
+            ));
+        }
+
+        let uri = "https://storageaccountname.blob.core.windows.com/containerobjectname?sas=token";
+        let resource_uri = ResourceUri::parse(uri, true).unwrap();
+        assert_eq!(
+            resource_uri.service_uri,
+            "https://storageaccountname.blob.core.windows.com"
+        );
+        assert_eq!(resource_uri.account_name, "storageaccountname");
+    }
+
+    #[test]
+    fn emulator_uris_are_path_style() {
+        for (uri, service_uri) in [
+            (
+                "http://127.0.0.1:10000/devstoreaccount1/containerobjectname?sas=token",
+                "http://127.0.0.1:10000/devstoreaccount1",
+            ),
+            (
+                "http://localhost:10001/devstoreaccount1/containerobjectname?sas=token",
+                "http://localhost:10001/devstoreaccount1",
+            ),
+            (
+                "http://[::1]:10002/devstoreaccount1/containerobjectname?sas=token",
+                "http://[::1]:10002/devstoreaccount1",
+            ),
+        ] {
+            let resource_uri = ResourceUri::parse(uri, true).unwrap();
+
+            assert_eq!(resource_uri.service_uri, service_uri);
+            assert_eq!(resource_uri.account_name, "devstoreaccount1");
+            assert_eq!(resource_uri.object_name, "containerobjectname");
+        }
+
+        assert!(matches!(
+            ResourceUri::parse("http://127.0.0.1:10000/devstoreaccount1?sas=token", true),
+            Err(ResourceUriError::MissingObjectName)
+        ));
+        assert!(matches!(
+            ResourceUri::parse(
+                "http://127.0.0.1:10000/devstoreaccount1/containerobjectname?sas=token",
+                false
+            ),
+            Err(ResourceUriError::InvalidScheme(_))
+        ));
+    }
+
     #[test]
     fn missing_host_str() {
         let uri = "https:";
@@ -241,6 +345,7 @@ mod tests {
             object_name: "queuename".to_string(),
             account_name: "mystorageaccount".to_string(),
             sas_token: StorageCredentials::sas_token("sas=token").unwrap(),
+            sas_query: "sas=token".to_string(),
         };
 
         let client_options = ClientOptions::default();
@@ -256,6 +361,7 @@ mod tests {
             object_name: "containername".to_string(),
             account_name: "mystorageaccount".to_string(),
             sas_token: StorageCredentials::sas_token("sas=token").unwrap(),
+            sas_query: "sas=token".to_string(),
         };
 
         let client_options = ClientOptions::default();
@@ -0,0 +1,117 @@
+use std::{future::Future, time::Duration};
+
+use azure_core::error::ErrorKind;
+use azure_kusto_data::error::Error as KustoError;
+
+/// The number of attempts made to fetch ingestion metadata from Kusto before failing
+pub const METADATA_MAX_ATTEMPTS: u32 = 3;
+
+/// The delay before retrying to fetch ingestion metadata, doubled after each failed attempt
+pub const METADATA_INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+
+fn is_transient_status(status: u16) -> bool {
+    status == 408 || status == 429 || status >= 500
+}
+
+/// Whether an error is likely to go away on its own, so that the request is worth retrying
+pub fn is_transient(error: &KustoError) -> bool {
+    match error {
+        KustoError::HttpError(status, _) => is_transient_status(u16::from(*status)),
+        KustoError::AzureError(error) => match error.kind() {
+            ErrorKind::Io => true,
+            ErrorKind::HttpResponse { status, .. } => is_transient_status(u16::from(*status)),
+            _ => false,
+        },
+        KustoError::IoError(_) => true,
+        _ => false,
+    }
+}
+
+/// Runs an idempotent request against Kusto, retrying it with exponential backoff while it fails with transient errors,
+/// for up to [METADATA_MAX_ATTEMPTS] attempts
+pub async fn retry_metadata_request<T, F, Fut>(mut request: F) -> Result<T, KustoError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, KustoError>>,
+{
+    let mut backoff = METADATA_INITIAL_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        match request().await {
+            Err(error) if attempt < METADATA_MAX_ATTEMPTS && is_transient(&error) => {
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use azure_core::StatusCode;
+    use std::cell::Cell;
+
+    #[test]
+    fn transient_errors() {
+        assert!(is_transient(&KustoError::HttpError(
+            StatusCode::ServiceUnavailable,
+            "".into()
+        )));
+        assert!(is_transient(&KustoError::HttpError(
+            StatusCode::TooManyRequests,
+            "".into()
+        )));
+        assert!(!is_transient(&KustoError::HttpError(
+            StatusCode::Forbidden,
+            "".into()
+        )));
+        assert!(!is_transient(&KustoError::QueryError(
+            "Syntax error".into()
+        )));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn permanent_errors_are_not_retried() {
+        let attempts = Cell::new(0);
+
+        let result: Result<(), _> = retry_metadata_request(|| {
+            attempts.set(attempts.get() + 1);
+            async { Err(KustoError::HttpError(StatusCode::Forbidden, "".into())) }
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts.get(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn transient_errors_are_retried_up_to_the_max_attempts() {
+        let attempts = Cell::new(0);
+        let started = tokio::time::Instant::now();
+
+        let result: Result<(), _> = retry_metadata_request(|| {
+            attempts.set(attempts.get() + 1);
+            async {
+                Err(KustoError::HttpError(
+                    StatusCode::ServiceUnavailable,
+                    "".into(),
+                ))
+            }
+        })
+        .await;
+
+        assert!(matches!(
+            result,
+            Err(KustoError::HttpError(StatusCode::ServiceUnavailable, _))
+        ));
+        assert_eq!(attempts.get(), METADATA_MAX_ATTEMPTS);
+        // The backoff doubles after each failed attempt
+        assert_eq!(
+            started.elapsed(),
+            METADATA_INITIAL_BACKOFF + METADATA_INITIAL_BACKOFF * 2
+        );
+    }
+}
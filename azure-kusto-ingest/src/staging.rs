@@ -0,0 +1,321 @@
+//! Staging of blobs into the temporary storage of Kusto, for sources that the Kusto data management service cannot reach,
+//! used by [QueuedIngestClient::ingest_via_staging](crate::queued_ingest::QueuedIngestClient::ingest_via_staging).
+
+use std::time::{Duration, Instant};
+
+use azure_storage_blobs::blob::CopyStatus;
+use azure_storage_blobs::prelude::BlobClient;
+use url::Url;
+use uuid::Uuid;
+
+use crate::descriptors::BlobDescriptor;
+use crate::ingestion_properties::IngestionProperties;
+
+/// The default interval between checks of the status of a copy into the temporary storage
+pub const DEFAULT_STAGING_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The default time to wait for a copy into the temporary storage to complete
+pub const DEFAULT_STAGING_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Error raised when a blob could not be copied into the temporary storage of Kusto
+#[derive(Debug, thiserror::Error)]
+pub enum StagingError {
+    /// The source blob is authenticated with a managed identity of the Kusto cluster, which the storage service cannot copy it with
+    #[error(
+        "Blobs authenticated with a managed identity cannot be staged, use a SAS token instead"
+    )]
+    UnsupportedBlobAuth,
+
+    /// The uri of the source blob is not a valid url
+    #[error("Invalid source blob uri: {0}")]
+    InvalidUri(#[from] url::ParseError),
+
+    /// The storage service reported that the copy failed
+    #[error("Copy into the temporary storage failed: {0}")]
+    CopyFailed(String),
+
+    /// The copy was aborted before it completed
+    #[error("Copy into the temporary storage was aborted: {0}")]
+    CopyAborted(String),
+
+    /// The copy did not complete within the staging timeout
+    #[error("Copy into the temporary storage did not complete within {0:?}")]
+    Timeout(Duration),
+
+    /// Error occurring when calling the storage service
+    #[error("Error in azure-core: {0}")]
+    AzureError(#[from] azure_core::error::Error),
+}
+
+type Result<T> = std::result::Result<T, StagingError>;
+
+/// The state of a copy, as reported by the storage service
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum CopyState {
+    Pending,
+    Success,
+    Failed(String),
+    Aborted(String),
+}
+
+impl CopyState {
+    fn new(status: Option<CopyStatus>, description: Option<String>) -> Self {
+        let description = description.unwrap_or_default();
+        match status {
+            // A blob without a copy status was not created by a copy, which only happens once a synchronous copy completed
+            Some(CopyStatus::Success) | None => CopyState::Success,
+            Some(CopyStatus::Pending) => CopyState::Pending,
+            Some(CopyStatus::Failed) => CopyState::Failed(description),
+            Some(CopyStatus::Aborted) => CopyState::Aborted(description),
+        }
+    }
+}
+
+/// The operations on the staged blob, abstracted so that the polling and cleanup can be tested without a storage account
+#[async_trait::async_trait]
+pub(crate) trait StagedBlob {
+    /// Starts a server side copy of the source into the staged blob
+    async fn start_copy(&self, source: Url) -> azure_core::Result<CopyState>;
+    /// Reads the state of the copy into the staged blob
+    async fn copy_state(&self) -> azure_core::Result<CopyState>;
+    /// Deletes the staged blob, aborting its copy if it is still pending
+    async fn delete(&self) -> azure_core::Result<()>;
+}
+
+#[async_trait::async_trait]
+impl StagedBlob for BlobClient {
+    async fn start_copy(&self, source: Url) -> azure_core::Result<CopyState> {
+        let response = self.copy(source).await?;
+        Ok(CopyState::new(Some(response.copy_status), None))
+    }
+
+    async fn copy_state(&self) -> azure_core::Result<CopyState> {
+        let properties = self.get_properties().await?.blob.properties;
+        Ok(CopyState::new(
+            properties.copy_status,
+            properties.copy_status_description,
+        ))
+    }
+
+    async fn delete(&self) -> azure_core::Result<()> {
+        BlobClient::delete(self).await?;
+        Ok(())
+    }
+}
+
+/// The name of the blob that the source is staged as, unique to the ingestion
+pub(crate) fn staged_blob_name(
+    source: &BlobDescriptor,
+    ingestion_properties: &IngestionProperties,
+) -> String {
+    let file_name = source.redacted_uri().rsplit('/').next().unwrap_or_default();
+    temp_blob_name(ingestion_properties, source.source_id, file_name)
+}
+
+/// The name of a blob uploaded to the temporary storage of Kusto, unique to the ingestion with the given source id
+pub(crate) fn temp_blob_name(
+    ingestion_properties: &IngestionProperties,
+    source_id: Uuid,
+    file_name: &str,
+) -> String {
+    // Only keep characters that don't need escaping in a url, so the name is the same for the storage service and for Kusto
+    let sanitize = |name: &str| -> String {
+        name.chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect()
+    };
+
+    format!(
+        "{}__{}__{}__{}",
+        sanitize(&ingestion_properties.database_name),
+        sanitize(&ingestion_properties.table_name),
+        source_id,
+        sanitize(file_name)
+    )
+}
+
+/// The url that the storage service copies the source blob from, including its SAS token
+pub(crate) fn copy_source(source: &BlobDescriptor) -> Result<Url> {
+    if source.uses_managed_identity() {
+        return Err(StagingError::UnsupportedBlobAuth);
+    }
+    Ok(Url::parse(&source.uri())?)
+}
+
+/// Copies the source into the staged blob, and waits for the copy to complete, checking its state every `poll_interval`.
+/// If the copy fails, or does not complete within `timeout`, the staged blob is deleted.
+pub(crate) async fn stage_blob(
+    blob: &(impl StagedBlob + Sync),
+    source: Url,
+    poll_interval: Duration,
+    timeout: Duration,
+) -> Result<()> {
+    let result = wait_for_copy(blob, source, poll_interval, timeout).await;
+    if result.is_err() {
+        // The staged blob may not exist if the copy did not start, and the original error is the one worth reporting
+        let _ = blob.delete().await;
+    }
+    result
+}
+
+async fn wait_for_copy(
+    blob: &(impl StagedBlob + Sync),
+    source: Url,
+    poll_interval: Duration,
+    timeout: Duration,
+) -> Result<()> {
+    let started = Instant::now();
+    let mut state = blob.start_copy(source).await?;
+
+    loop {
+        match state {
+            CopyState::Success => return Ok(()),
+            CopyState::Failed(description) => return Err(StagingError::CopyFailed(description)),
+            CopyState::Aborted(description) => return Err(StagingError::CopyAborted(description)),
+            CopyState::Pending => {}
+        }
+
+        if started.elapsed() >= timeout {
+            return Err(StagingError::Timeout(timeout));
+        }
+        azure_core::sleep::sleep(poll_interval.min(timeout.saturating_sub(started.elapsed())))
+            .await;
+
+        state = blob.copy_state().await?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::descriptors::BlobAuth;
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    /// A staged blob whose copy goes through a scripted sequence of states.
+    #[derive(Default)]
+    struct FakeBlob {
+        states: Mutex<VecDeque<CopyState>>,
+        polls: Mutex<usize>,
+        deleted: Mutex<bool>,
+    }
+
+    impl FakeBlob {
+        fn new(states: Vec<CopyState>) -> Self {
+            Self {
+                states: Mutex::new(states.into()),
+                ..Self::default()
+            }
+        }
+
+        fn next_state(&self) -> CopyState {
+            let mut states = self.states.lock().unwrap();
+            // The last state sticks, so that a pending copy stays pending
+            if states.len() > 1 {
+                states.pop_front().unwrap()
+            } else {
+                states.front().cloned().unwrap()
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl StagedBlob for FakeBlob {
+        async fn start_copy(&self, _source: Url) -> azure_core::Result<CopyState> {
+            Ok(self.next_state())
+        }
+
+        async fn copy_state(&self) -> azure_core::Result<CopyState> {
+            *self.polls.lock().unwrap() += 1;
+            Ok(self.next_state())
+        }
+
+        async fn delete(&self) -> azure_core::Result<()> {
+            *self.deleted.lock().unwrap() = true;
+            Ok(())
+        }
+    }
+
+    fn source() -> Url {
+        Url::parse("https://account.blob.core.windows.net/container/blob.csv?sig=secret").unwrap()
+    }
+
+    const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+    #[tokio::test]
+    async fn pending_copies_are_polled_until_they_complete() {
+        let blob = FakeBlob::new(vec![
+            CopyState::Pending,
+            CopyState::Pending,
+            CopyState::Pending,
+            CopyState::Success,
+        ]);
+
+        stage_blob(&blob, source(), POLL_INTERVAL, Duration::from_secs(60))
+            .await
+            .expect("Failed to stage blob");
+
+        assert_eq!(*blob.polls.lock().unwrap(), 3);
+        assert!(!*blob.deleted.lock().unwrap());
+    }
+
+    #[tokio::test]
+    async fn failed_copies_are_cleaned_up() {
+        let blob = FakeBlob::new(vec![
+            CopyState::Pending,
+            CopyState::Failed("403 AuthorizationFailure".to_string()),
+        ]);
+
+        let result = stage_blob(&blob, source(), POLL_INTERVAL, Duration::from_secs(60)).await;
+
+        assert!(
+            matches!(result, Err(StagingError::CopyFailed(description)) if description == "403 AuthorizationFailure")
+        );
+        assert!(*blob.deleted.lock().unwrap());
+    }
+
+    #[tokio::test]
+    async fn copies_that_do_not_complete_in_time_are_cleaned_up() {
+        let blob = FakeBlob::new(vec![CopyState::Pending]);
+
+        let result = stage_blob(&blob, source(), POLL_INTERVAL, Duration::from_millis(20)).await;
+
+        assert!(matches!(result, Err(StagingError::Timeout(_))));
+        assert!(*blob.polls.lock().unwrap() > 0);
+        assert!(*blob.deleted.lock().unwrap());
+    }
+
+    #[test]
+    fn staged_blob_names_are_unique_and_safe() {
+        let source = BlobDescriptor::new(
+            "https://account.blob.core.windows.net/container/my%20data.csv.gz",
+            None,
+            None,
+        )
+        .with_blob_auth(BlobAuth::SASToken("sig=secret".to_string()));
+        let ingestion_properties = IngestionProperties {
+            database_name: "db".to_string(),
+            table_name: "my table".to_string(),
+            ..IngestionProperties::default()
+        };
+
+        assert_eq!(
+            staged_blob_name(&source, &ingestion_properties),
+            format!("db__my_table__{}__my_20data.csv.gz", source.source_id)
+        );
+        assert_eq!(
+            copy_source(&source).unwrap().as_str(),
+            "https://account.blob.core.windows.net/container/my%20data.csv.gz?sig=secret"
+        );
+        assert!(matches!(
+            copy_source(&source.with_blob_auth(BlobAuth::SystemAssignedManagedIdentity)),
+            Err(StagingError::UnsupportedBlobAuth)
+        ));
+    }
+}
@@ -0,0 +1,77 @@
+//! Defines [StreamingIngestClient], for ingesting data directly into the engine of Kusto with streaming ingestion.
+
+use azure_kusto_data::prelude::{ClientRequestProperties, KustoClient, StreamingIngestSource};
+
+use crate::descriptors::BlobDescriptor;
+use crate::error::Result;
+use crate::ingestion_properties::IngestionProperties;
+
+/// Client for ingesting data into Kusto using the streaming flavour of ingestion.
+///
+/// Streaming ingestion has a low latency, but is meant for small amounts of data, and must be enabled on the cluster and
+/// on the table or database ingested into. [ManagedStreamingIngestClient](crate::managed_streaming::ManagedStreamingIngestClient)
+/// falls back to queued ingestion when streaming is not possible
+#[derive(Clone)]
+pub struct StreamingIngestClient {
+    kusto_client: KustoClient,
+}
+
+impl StreamingIngestClient {
+    /// Creates a new client from the given [KustoClient].
+    ///
+    /// **WARNING**: the [KustoClient] must be created with a connection string that points to the engine endpoint, not to the ingestion endpoint
+    pub fn new(kusto_client: KustoClient) -> Self {
+        Self { kusto_client }
+    }
+
+    /// Ingest a file into Kusto from Azure Blob Storage, returning once the data was ingested.
+    /// The [IngestionProperties] are [validated](IngestionProperties::validate) first, and nothing is ingested when they are invalid.
+    ///
+    /// The source id of the [BlobDescriptor] is sent as part of the client request id, to correlate the ingestion with its traces in Kusto.
+    /// Only the [ingestion_mapping_reference](IngestionProperties::ingestion_mapping_reference) and the data format of the properties apply to streaming ingestion
+    // The blob uri is not recorded in the span, as it may carry a SAS token.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "kusto.ingest.streaming",
+            skip_all,
+            fields(
+                source_id = %blob_descriptor.source_id,
+                database = %ingestion_properties.database_name,
+                table = %ingestion_properties.table_name,
+            ),
+            err
+        )
+    )]
+    pub async fn ingest_from_blob(
+        &self,
+        blob_descriptor: BlobDescriptor,
+        ingestion_properties: IngestionProperties,
+    ) -> Result<()> {
+        let _warnings = ingestion_properties.validate()?;
+        #[cfg(feature = "tracing")]
+        for warning in &_warnings {
+            tracing::warn!(%warning, "Discouraged ingestion properties");
+        }
+
+        let client_request_properties = ClientRequestProperties {
+            client_request_id: Some(format!(
+                "KRS.ingest_from_blob;{}",
+                blob_descriptor.source_id
+            )),
+            ..ClientRequestProperties::default()
+        };
+
+        self.kusto_client
+            .execute_streaming_ingest(
+                &ingestion_properties.database_name,
+                &ingestion_properties.table_name,
+                StreamingIngestSource::BlobUri(blob_descriptor.uri()),
+                ingestion_properties.data_format.as_str(),
+                ingestion_properties.ingestion_mapping_reference.as_deref(),
+                Some(client_request_properties),
+            )
+            .await?;
+        Ok(())
+    }
+}
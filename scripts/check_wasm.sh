@@ -0,0 +1,15 @@
+#!/usr/bin/env bash
+# Checks that the data crate builds for wasm32-unknown-unknown, and runs the wasm tests if wasm-pack is installed.
+set -euo pipefail
+
+cd "$(dirname "$0")/../azure-kusto-data"
+
+rustup target add wasm32-unknown-unknown
+cargo check --target wasm32-unknown-unknown --no-default-features --features wasm
+cargo check --target wasm32-unknown-unknown --no-default-features --features wasm --example wasm
+
+if command -v wasm-pack > /dev/null; then
+    wasm-pack test --node -- --no-default-features --features wasm --test wasm
+else
+    echo "wasm-pack is not installed, skipping the wasm tests"
+fi